@@ -1,6 +1,6 @@
 use std::{
     any::Any,
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{BTreeSet, HashMap},
     hash::{DefaultHasher, Hash, Hasher},
     io::Cursor,
@@ -14,7 +14,10 @@ use std::{
 use crate::{get_ast_time, START_TIME};
 use js_sys::Date;
 use leptos::*;
-use uiua::{now, GitTarget, Handle, Report, Span, SysBackend, Uiua, EXAMPLE_TXT, EXAMPLE_UA};
+use uiua::{
+    now, GamepadState, GitTarget, Handle, MouseState, Report, Span, SysBackend, Uiua, EXAMPLE_TXT,
+    EXAMPLE_UA,
+};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{HtmlAudioElement, Request, RequestInit, RequestMode, Response};
@@ -66,8 +69,60 @@ pub fn delete_file(path: &PathBuf) {
     FILES.with(|files| files.borrow_mut().remove(path));
 }
 
+/// Check if a file was added by the user rather than being a default file or downloaded module
+pub fn is_user_file(path: &Path) -> bool {
+    !["example.txt", "example.ua"].contains(&path.to_str().unwrap()) && !path.ends_with("lib.ua")
+}
+
+/// A file path and its contents
+pub type ProjectFile = (PathBuf, Vec<u8>);
+
+/// Get the files added by the user, sorted by path
+pub fn user_files() -> Vec<ProjectFile> {
+    let mut files: Vec<_> = FILES.with(|files| {
+        (files.borrow().iter())
+            .filter(|(path, _)| is_user_file(path))
+            .map(|(path, contents)| (path.clone(), contents.clone()))
+            .collect()
+    });
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files
+}
+
 thread_local! {
     static BREAKPOINTS: RefCell<HashMap<u64, (u64, usize)>> = Default::default();
+    static MOUSE: Cell<MouseState> = Default::default();
+}
+
+/// Start tracking the mouse for `&mouse`
+pub fn track_mouse() {
+    thread_local! {
+        static TRACKING: Cell<bool> = const { Cell::new(false) };
+    }
+    if TRACKING.with(|tracking| tracking.replace(true)) {
+        return;
+    }
+    let update = |event: web_sys::MouseEvent| {
+        MOUSE.with(|mouse| {
+            let buttons = event.buttons();
+            mouse.set(MouseState {
+                pos: [event.client_x() as f64, event.client_y() as f64],
+                buttons: [1, 2, 4].map(|bit| buttons & bit != 0),
+                ..mouse.get()
+            })
+        })
+    };
+    window_event_listener(ev::mousemove, update);
+    window_event_listener(ev::mousedown, update);
+    window_event_listener(ev::mouseup, update);
+    window_event_listener(ev::wheel, |event| {
+        MOUSE.with(|mouse| {
+            mouse.set(MouseState {
+                scroll: mouse.get().scroll + event.delta_y(),
+                ..mouse.get()
+            })
+        })
+    });
 }
 
 impl Default for WebBackend {
@@ -127,6 +182,14 @@ impl OutputItem {
     pub fn is_report(&self) -> bool {
         matches!(self, OutputItem::Report(_))
     }
+    /// Get the text of the item, if it has any
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            OutputItem::String(s) | OutputItem::Faint(s) | OutputItem::Classed(_, s) => Some(s),
+            OutputItem::Separator => Some(""),
+            _ => None,
+        }
+    }
 }
 
 impl WebBackend {
@@ -239,6 +302,16 @@ impl SysBackend for WebBackend {
         });
         Ok(())
     }
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.file_write_all(path, contents)
+    }
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        if self.streams.lock().unwrap().contains_key(&handle) {
+            Ok(())
+        } else {
+            Err("Invalid file handle".into())
+        }
+    }
     fn file_read_all(&self, path: &Path) -> Result<Vec<u8>, String> {
         self.file(path, |contents| contents.to_vec())
     }
@@ -314,13 +387,13 @@ impl SysBackend for WebBackend {
     fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
         let mut streams = self.streams.lock().unwrap();
         let stream = streams.get_mut(&handle).ok_or("Invalid stream handle")?;
-        let offset = stream.contents[stream.pos..]
-            .windows(delim.len())
-            .position(|w| w == delim)
-            .unwrap_or(stream.contents.len());
-        let end = stream.pos + offset;
-        let data = stream.contents[stream.pos..end].to_vec();
-        stream.pos = (end + delim.len()).min(stream.contents.len());
+        let rest = &stream.contents[stream.pos..];
+        // The delimiter is included, like in the default implementation
+        let len = (rest.windows(delim.len()).position(|w| w == delim))
+            .map(|i| i + delim.len())
+            .unwrap_or(rest.len());
+        let data = rest[..len].to_vec();
+        stream.pos += len;
         Ok(data)
     }
     fn delete(&self, path: &str) -> Result<(), String> {
@@ -366,6 +439,32 @@ impl SysBackend for WebBackend {
         )?;
         self.play_audio(bytes, None)
     }
+    fn stream_audio_buffers(&self, mut f: uiua::AudioBufferFn) -> Result<(), String> {
+        let mut samples = Vec::new();
+        const SAMPLE_RATE: u32 = 44100;
+        const SAMPLES_PER_FRAME: usize = 10000;
+        let ast_time = get_ast_time();
+        while (samples.len() as f64 / SAMPLE_RATE as f64) < ast_time {
+            match f(samples.len() as u64, SAMPLES_PER_FRAME) {
+                Ok(s) => {
+                    let done = s.len() < SAMPLES_PER_FRAME;
+                    samples.extend(s);
+                    if done {
+                        break;
+                    }
+                }
+                Err(err) => return Err(format!("{err}")),
+            }
+        }
+        let bytes = uiua::encode::stereo_to_wave_bytes(
+            &samples,
+            |s| (s * i16::MAX as f64) as i16,
+            16,
+            hound::SampleFormat::Int,
+            SAMPLE_RATE,
+        )?;
+        self.play_audio(bytes, None)
+    }
     fn now(&self) -> f64 {
         *START_TIME.get_or_init(|| 0.0) + now()
     }
@@ -379,6 +478,33 @@ impl SysBackend for WebBackend {
         _ = window().navigator().clipboard().write_text(contents);
         Ok(())
     }
+    fn mouse(&self) -> Result<MouseState, String> {
+        Ok(MOUSE.with(|mouse| {
+            let state = mouse.get();
+            mouse.set(MouseState {
+                scroll: 0.0,
+                ..state
+            });
+            state
+        }))
+    }
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        let gamepads = (window().navigator().get_gamepads())
+            .map_err(|_| "Gamepads are not available".to_string())?;
+        let Ok(gamepad) = gamepads.get(index as u32).dyn_into::<web_sys::Gamepad>() else {
+            return Ok(None);
+        };
+        let mut state = GamepadState::default();
+        for (axis, value) in state.axes.iter_mut().zip(gamepad.axes().iter()) {
+            *axis = value.as_f64().unwrap_or(0.0);
+        }
+        for (button, value) in state.buttons.iter_mut().zip(gamepad.buttons().iter()) {
+            *button = (value.dyn_into::<web_sys::GamepadButton>())
+                .map(|button| button.value())
+                .unwrap_or(0.0);
+        }
+        Ok(Some(state))
+    }
     fn sleep(&self, seconds: f64) -> Result<(), String> {
         let start = now();
         while now() - start < seconds {}
@@ -45,6 +45,9 @@ thread_local! {
 
 static START_TIME: OnceLock<f64> = OnceLock::new();
 
+/// The number of output lines rendered at a time
+const OUTPUT_CHUNK_SIZE: usize = 1000;
+
 /// An editor for Uiua code
 #[component]
 pub fn Editor<'a>(
@@ -109,6 +112,9 @@ pub fn Editor<'a>(
     let (example, set_example) = create_signal(0);
     let (diag_output, set_diag_output) = create_signal(View::default());
     let (output, set_output) = create_signal(View::default());
+    let (more_output, set_more_output) = create_signal(Vec::<View>::new());
+    let (hidden_output_count, set_hidden_output_count) = create_signal(0);
+    let (has_text_output, set_has_text_output) = create_signal(false);
     let (token_count, set_token_count) = create_signal(0);
 
     // let code_text = move || code_text(&code_id());
@@ -140,6 +146,7 @@ pub fn Editor<'a>(
         future: Vec::new(),
         challenge,
         loading_module: false,
+        top_value: None,
         curr: {
             let code = initial_code.get_untracked().unwrap();
             let len = code.chars().count() as u32;
@@ -235,10 +242,10 @@ pub fn Editor<'a>(
 
         // Update URL
         {
-            let encoded = url_encode_code(&clean_code());
+            let query = url_encode_pad(&clean_code());
             if let EditorMode::Pad = mode {
                 BrowserIntegration {}.navigate(&LocationChange {
-                    value: format!("/pad?src={encoded}"),
+                    value: format!("/pad?{query}"),
                     scroll: false,
                     replace: true,
                     ..Default::default()
@@ -249,71 +256,114 @@ pub fn Editor<'a>(
         (input, seed)
     };
 
-    // Run the code
-    let run = move |do_format: bool, set_cursor: bool| {
-        // Format code
-        let (input, seed) = format(do_format, set_cursor);
-
-        // Run code
-        set_output.set(view!(<div class="running-text">"Running"</div>).into_view());
-        let allow_autoplay = !matches!(mode, EditorMode::Example) && get_autoplay();
-        let render_output_item = move |item| match item {
-            OutputItem::String(s) => {
-                if s.is_empty() {
-                    view!(<div class="output-item"><br/></div>).into_view()
-                } else {
-                    view!(<div class="output-item">{s}</div>).into_view()
-                }
-            }
-            OutputItem::Classed(class, s) => {
-                let class = format!("output-item {class}");
-                view!(<div class=class>{s}</div>).into_view()
-            }
-            OutputItem::Faint(s) => {
-                view!(<div class="output-item output-fainter">{s}</div>).into_view()
+    // Render an output item
+    let render_output_item = move |item| match item {
+        OutputItem::String(s) => {
+            if s.is_empty() {
+                view!(<div class="output-item"><br/></div>).into_view()
+            } else {
+                view!(<div class="output-item">{s}</div>).into_view()
             }
-            OutputItem::Image(bytes, label) => {
-                let encoded = STANDARD.encode(bytes);
+        }
+        OutputItem::Classed(class, s) => {
+            let class = format!("output-item {class}");
+            view!(<div class=class>{s}</div>).into_view()
+        }
+        OutputItem::Faint(s) => {
+            view!(<div class="output-item output-fainter">{s}</div>).into_view()
+        }
+        OutputItem::Image(bytes, label) => {
+            let encoded = STANDARD.encode(bytes);
+            view!(<div class="output-media-wrapper">
+                <div class="output-image-label">{label}</div>
+                <img class="output-image" src={format!("data:image/png;base64,{encoded}")} />
+            </div>)
+            .into_view()
+        }
+        OutputItem::Gif(bytes, label) => {
+            let encoded = STANDARD.encode(bytes);
+            view!(<div class="output-media-wrapper">
+                <div class="output-image-label">{label}</div>
+                <img class="output-image" src={format!("data:image/gif;base64,{encoded}")} />
+            </div>)
+            .into_view()
+        }
+        OutputItem::Audio(bytes, label) => {
+            let allow_autoplay = !matches!(mode, EditorMode::Example) && get_autoplay();
+            let encoded = STANDARD.encode(bytes);
+            let src = format!("data:audio/wav;base64,{}", encoded);
+            let label = label.map(|s| format!("{s}:"));
+            if allow_autoplay {
                 view!(<div class="output-media-wrapper">
-                    <div class="output-image-label">{label}</div>
-                    <img class="output-image" src={format!("data:image/png;base64,{encoded}")} />
+                    <div class="output-item output-audio-label">{label}</div>
+                    <audio class="output-audio" controls autoplay src=src/>
                 </div>)
                 .into_view()
-            }
-            OutputItem::Gif(bytes, label) => {
-                let encoded = STANDARD.encode(bytes);
+            } else {
                 view!(<div class="output-media-wrapper">
-                    <div class="output-image-label">{label}</div>
-                    <img class="output-image" src={format!("data:image/gif;base64,{encoded}")} />
+                    <div class="output-item output-audio-label">{label}</div>
+                    <audio class="output-audio" controls src=src/>
                 </div>)
                 .into_view()
             }
-            OutputItem::Audio(bytes, label) => {
-                let encoded = STANDARD.encode(bytes);
-                let src = format!("data:audio/wav;base64,{}", encoded);
-                let label = label.map(|s| format!("{s}:"));
-                if allow_autoplay {
-                    view!(<div class="output-media-wrapper">
-                        <div class="output-item output-audio-label">{label}</div>
-                        <audio class="output-audio" controls autoplay src=src/>
-                    </div>)
-                    .into_view()
-                } else {
-                    view!(<div class="output-media-wrapper">
-                        <div class="output-item output-audio-label">{label}</div>
-                        <audio class="output-audio" controls src=src/>
-                    </div>)
-                    .into_view()
-                }
-            }
-            OutputItem::Svg(s) => view!(<div><img
-                    class="output-image"
-                    src={format!("data:image/svg+xml;utf8, {}", urlencoding::encode(&s))}/>
-                </div>)
-            .into_view(),
-            OutputItem::Report(report) => report_view(&report).into_view(),
-            OutputItem::Separator => view!(<div class="output-item"><hr/></div>).into_view(),
+        }
+        OutputItem::Svg(s) => view!(<div><img
+                class="output-image"
+                src={format!("data:image/svg+xml;utf8, {}", urlencoding::encode(&s))}/>
+            </div>)
+        .into_view(),
+        OutputItem::Report(report) => report_view(&report).into_view(),
+        OutputItem::Separator => view!(<div class="output-item"><hr/></div>).into_view(),
+    };
+
+    // Show output, only rendering the first lines of large output
+    let hidden_output = store_value(Vec::<OutputItem>::new());
+    let output_text = store_value(String::new());
+    let show_output = move |output: Vec<OutputItem>| {
+        let (diags, mut items): (Vec<_>, Vec<_>) =
+            output.into_iter().partition(OutputItem::is_report);
+        let text: Vec<&str> = items.iter().filter_map(OutputItem::text).collect();
+        output_text.set_value(text.join("\n"));
+        set_has_text_output.set(!text.is_empty());
+        let hidden = items.split_off(items.len().min(OUTPUT_CHUNK_SIZE));
+        set_hidden_output_count.set(hidden.len());
+        hidden_output.set_value(hidden);
+        set_more_output.set(Vec::new());
+        let items: Vec<_> = items.into_iter().map(render_output_item).collect();
+        let diags: Vec<_> = diags.into_iter().map(render_output_item).collect();
+        set_output.set(items.into_view());
+        set_diag_output.set(diags.into_view());
+    };
+    let show_more_output = move |_| {
+        let next = hidden_output
+            .try_update_value(|hidden| {
+                let count = hidden.len().min(OUTPUT_CHUNK_SIZE);
+                hidden.drain(..count).collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        set_hidden_output_count.set(hidden_output.with_value(Vec::len));
+        set_more_output.update(|more| more.extend(next.into_iter().map(render_output_item)));
+    };
+    let download_output_text = move |_| {
+        output_text.with_value(|text| download_file("output.txt", "text/plain", text));
+    };
+    let download_output_csv = move |_| {
+        let Some(value) = get_state.get_untracked().top_value else {
+            return;
         };
+        match value_to_csv(value) {
+            Ok(csv) => download_file("output.csv", "text/csv", &csv),
+            Err(e) => logging::log!("Unable to encode output as CSV: {e}"),
+        }
+    };
+
+    // Run the code
+    let run = move |do_format: bool, set_cursor: bool| {
+        // Format code
+        let (input, seed) = format(do_format, set_cursor);
+
+        // Run code
+        set_output.set(view!(<div class="running-text">"Running"</div>).into_view());
         set_timeout(
             move || {
                 state.update(|st| {
@@ -324,26 +374,13 @@ pub fn Editor<'a>(
                             move || {
                                 state.update(|st| {
                                     seed_random(seed);
-                                    let output = st.run_code(&input);
-                                    let (diags, items): (Vec<_>, Vec<_>) =
-                                        output.into_iter().partition(OutputItem::is_report);
-                                    let items: Vec<_> =
-                                        items.into_iter().map(render_output_item).collect();
-                                    let diags: Vec<_> =
-                                        diags.into_iter().map(render_output_item).collect();
-                                    set_output.set(items.into_view());
-                                    set_diag_output.set(diags.into_view());
+                                    show_output(st.run_code(&input));
                                 });
                             },
                             Duration::from_millis(200),
                         );
                     } else {
-                        let (diags, items): (Vec<_>, Vec<_>) =
-                            output.into_iter().partition(OutputItem::is_report);
-                        let items: Vec<_> = items.into_iter().map(render_output_item).collect();
-                        let diags: Vec<_> = diags.into_iter().map(render_output_item).collect();
-                        set_output.set(items.into_view());
-                        set_diag_output.set(diags.into_view());
+                        show_output(output);
                     }
                 });
             },
@@ -427,6 +464,8 @@ pub fn Editor<'a>(
         });
     };
 
+    backend::track_mouse();
+
     // Handle key events
     window_event_listener(mousemove, move |event| {
         if let Some(overlay_element) = get_element::<HtmlDivElement>(&overlay_id()) {
@@ -1343,8 +1382,8 @@ pub fn Editor<'a>(
 
     // Copy a link to the code
     let copy_link_impl = move |markdown: bool| {
-        let encoded = url_encode_code(&clean_code());
-        let url = format!("https://uiua.org/pad?src={encoded}");
+        let query = url_encode_pad(&clean_code());
+        let url = format!("https://uiua.org/pad?{query}");
         let to_copy = if markdown {
             let text =
                 if let Some((start, end)) = get_code_cursor().filter(|(start, end)| start != end) {
@@ -1368,7 +1407,7 @@ pub fn Editor<'a>(
             window()
                 .history()
                 .unwrap()
-                .push_state_with_url(&JsValue::NULL, "", Some(&format!("/pad?src={encoded}")))
+                .push_state_with_url(&JsValue::NULL, "", Some(&format!("/pad?{query}")))
                 .unwrap();
         }
         set_copied_link.set(true);
@@ -1454,6 +1493,7 @@ pub fn Editor<'a>(
         );
         set_inlay_values(!get_inlay_values());
     };
+    let toggle_stack_panel = move |_| set_stack_panel(!get_stack_panel());
     let on_select_font = move |event: Event| {
         let input: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
         let name = input.value();
@@ -1496,15 +1536,12 @@ pub fn Editor<'a>(
         // Plus it handles cases where files are created/deleted after the code runs.
         let _ = output.get();
 
-        let excluded_files = ["example.txt", "example.ua"];
         backend::FILES.with(|files| {
             files
                 .borrow()
-                .iter()
-                .filter(|(path, _)| {
-                    !excluded_files.contains(&path.to_str().unwrap()) && !path.ends_with("lib.ua")
-                })
-                .map(|(path, _)| path.clone())
+                .keys()
+                .filter(|path| backend::is_user_file(path))
+                .cloned()
                 .collect::<Vec<_>>()
         })
     };
@@ -1642,6 +1679,13 @@ pub fn Editor<'a>(
                                 checked=get_inlay_values
                                 on:change=toggle_inlay_values/>
                         </div>
+                        <div title="Show the stack after each line below the output">
+                            "Stack panel:"
+                            <input
+                                type="checkbox"
+                                checked=get_stack_panel
+                                on:change=toggle_stack_panel/>
+                        </div>
                         <div>
                             "Stack:"
                             <select
@@ -1775,6 +1819,34 @@ pub fn Editor<'a>(
                             <div class="output-wrapper">
                                 <div id=format!("output-{id}") class="output sized-code">
                                     { move || output.get() }
+                                    { move || more_output.get() }
+                                    { move || (hidden_output_count.get() > 0).then(|| view! {
+                                        <div class="output-more">
+                                            <button on:click=show_more_output>
+                                                {format!("Show more ({} lines hidden)", hidden_output_count.get())}
+                                            </button>
+                                        </div>
+                                    })}
+                                    { move || (has_text_output.get()
+                                        && (mode == EditorMode::Pad || hidden_output_count.get() > 0))
+                                        .then(|| view! {
+                                        <div class="output-downloads">
+                                            <button
+                                                class="glyph-button"
+                                                data-title="Download output as text"
+                                                on:click=download_output_text>
+                                                "⤓ txt"
+                                            </button>
+                                            { move || get_state.get().top_value.is_some().then(|| view! {
+                                                <button
+                                                    class="glyph-button"
+                                                    data-title="Download the top stack value as CSV"
+                                                    on:click=download_output_csv>
+                                                    "⤓ csv"
+                                                </button>
+                                            })}
+                                        </div>
+                                    })}
                                     { move || get_state.get().challenge.as_ref().map(|chal| {
                                         let intended = chal.intended_answer.clone();
                                         let click_intended = move|_| {
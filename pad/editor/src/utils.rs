@@ -1,12 +1,14 @@
 use base64::engine::{general_purpose::URL_SAFE, Engine};
 use leptos::*;
-use std::path::Path;
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+use std::path::{Path, PathBuf};
 use std::{
     borrow::Cow,
     cell::Cell,
     collections::HashMap,
     mem::{replace, take},
     str::FromStr,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 use uiua::UiuaErrorKind;
@@ -15,18 +17,19 @@ use uiua::{
     ast::Item,
     encode::SmartOutput,
     lsp::{BindingDocsKind, ImportSrc},
-    Compiler, DiagnosticKind, Inputs, Primitive, Report, ReportFragment, ReportKind, SpanKind,
-    Spans, Uiua, UiuaError, UiuaResult, Value,
+    Array, ArrayValue, CodeSpan, Compiler, DiagnosticKind, InputSrc, Inputs, PreEvalMode,
+    Primitive, Report, ReportFragment, ReportKind, SpanKind, Spans, Uiua, UiuaError, UiuaResult,
+    Value,
 };
 use unicode_segmentation::UnicodeSegmentation;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    DomRect, Event, HtmlDivElement, HtmlSpanElement, HtmlStyleElement, HtmlTextAreaElement,
-    KeyboardEvent, MouseEvent,
+    Blob, BlobPropertyBag, DomRect, Event, HtmlAnchorElement, HtmlDivElement, HtmlSpanElement,
+    HtmlStyleElement, HtmlTextAreaElement, KeyboardEvent, MouseEvent, Url,
 };
 
 use crate::{
-    backend::{OutputItem, WebBackend},
+    backend::{user_files, OutputItem, ProjectFile, WebBackend},
     binding_class, code_font, modifier_class, prim_sig_class,
 };
 
@@ -54,6 +57,8 @@ pub struct State {
     pub curr: Record,
     pub challenge: Option<ChallengeDef>,
     pub loading_module: bool,
+    /// The value on top of the stack after the code was last run
+    pub top_value: Option<Value>,
 }
 
 /// A record of a code change
@@ -907,15 +912,25 @@ impl State {
     /// Run code and return the output
     pub fn run_code(&mut self, code: &str) -> Vec<OutputItem> {
         if let Some(chal) = &self.challenge {
-            let mut example = run_code_single(
-                &self.code_id,
-                &challenge_code(&chal.intended_answer, &chal.example, chal.flip),
-            )
-            .0;
-            example.insert(0, OutputItem::Faint(format!("Example: {}", chal.example)));
-            let mut output_sections = vec![example];
+            let mut output_sections = Vec::new();
+            if !chal.example.is_empty() {
+                let mut example = run_code_single(
+                    &self.code_id,
+                    &challenge_code(&chal.intended_answer, &chal.example, chal.flip),
+                )
+                .0;
+                example.insert(0, OutputItem::Faint(format!("Example: {}", chal.example)));
+                output_sections.push(example);
+            }
+            // Challenges without tests just compare the output of the code
+            let no_tests = [String::new()];
+            let tests = if chal.tests.is_empty() {
+                no_tests.as_slice()
+            } else {
+                &chal.tests
+            };
             let mut correct = true;
-            for test in &chal.tests {
+            for test in tests {
                 let answer = || {
                     just_values(
                         &self.code_id,
@@ -931,7 +946,9 @@ impl State {
                         _ => false,
                     };
                 let mut output = run_code_single(&self.code_id, &user_input).0;
-                output.insert(0, OutputItem::Faint(format!("Input: {test}")));
+                if !test.is_empty() {
+                    output.insert(0, OutputItem::Faint(format!("Input: {test}")));
+                }
                 output_sections.push(output);
             }
             let hidden_answer = || {
@@ -971,7 +988,8 @@ impl State {
             }
             output
         } else {
-            let (output, error) = run_code_single(&self.code_id, code);
+            let (output, error, top_value) = run_code_single(&self.code_id, code);
+            self.top_value = top_value;
             self.loading_module = false;
             if let Some(error) = error {
                 if error.to_string().contains("Waiting for module") {
@@ -984,16 +1002,31 @@ impl State {
 }
 
 #[allow(clippy::mutable_key_type)]
-fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>) {
+fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>, Option<Value>) {
     // Run
     let mut rt = init_rt(id, code);
     let mut error = None;
     let mut comp = Compiler::with_backend(WebBackend::new(id, code));
+    // The web backend is already sandboxed, and it shows compile-time output
+    comp.comptime_sandbox(false);
     let comp_backend;
-    let res = comp.load_str(code).map(|comp| rt.run_compiler(comp));
+    let mut line_stacks = get_stack_panel().then(LineStacks::default);
+    if line_stacks.is_some() {
+        // Pre-evaluating multiple lines together would hide their stacks
+        comp.pre_eval_mode(PreEvalMode::Line);
+    }
+    let res = comp.load_str(code).map(|comp| {
+        if let Some(line_stacks) = &mut line_stacks {
+            rt = line_stacks.record(take(&mut rt), comp);
+        }
+        rt.run_compiler(comp)
+    });
     let (mut values, io) = match res {
         Ok(Ok(())) => {
             let stack = rt.take_stack();
+            if let Some(line_stacks) = &line_stacks {
+                line_stacks.finish(&stack);
+            }
             let backend = rt.downcast_backend::<WebBackend>().unwrap();
             backend.finish();
             (stack, backend)
@@ -1015,6 +1048,9 @@ fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>)
             (Vec::new(), &comp_backend)
         }
     };
+    // Show the outputs with the program's format settings
+    rt.format_settings().set();
+    let top_value = values.last().cloned();
     if get_top_at_top() {
         values.reverse();
     }
@@ -1040,19 +1076,7 @@ fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>)
             SmartOutput::Normal(value) => value,
         };
         // Otherwise, just show the value
-        let class = if value_count == 1 {
-            ""
-        } else {
-            match i % 6 {
-                0 => "output-a",
-                1 => "output-b",
-                2 => "output-c",
-                3 => "output-d",
-                4 => "output-e",
-                5 => "output-f",
-                _ => unreachable!(),
-            }
-        };
+        let class = value_class(i, value_count);
         for line in value.show().lines() {
             stack.push(OutputItem::Classed(class, line.to_string()));
         }
@@ -1094,6 +1118,13 @@ fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>)
         }
         output.extend(stack);
     }
+    if let Some(line_stacks) = line_stacks {
+        let panel = line_stacks.output();
+        if !output.is_empty() && !panel.is_empty() {
+            output.push(OutputItem::Separator);
+        }
+        output.extend(panel);
+    }
     if let Some(error) = &error {
         if !output.is_empty() {
             output.push(OutputItem::String("".into()));
@@ -1121,7 +1152,143 @@ fn run_code_single(id: &str, code: &str) -> (Vec<OutputItem>, Option<UiuaError>)
         }
     }
     output.extend(rt.take_reports().into_iter().map(OutputItem::Report));
-    (output, error)
+    (output, error, top_value)
+}
+
+/// Have the browser download a file with the given contents
+pub fn download_file(name: &str, mime: &str, contents: &str) {
+    let parts = js_sys::Array::of1(&contents.into());
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let Ok(blob) = Blob::new_with_str_sequence_and_options(&parts, &options) else {
+        return;
+    };
+    let Ok(url) = Url::create_object_url_with_blob(&blob) else {
+        return;
+    };
+    let anchor: HtmlAnchorElement = document().create_element("a").unwrap().unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    _ = Url::revoke_object_url(&url);
+}
+
+/// Encode a value as CSV
+pub fn value_to_csv(value: Value) -> Result<String, String> {
+    let mut rt = Uiua::with_safe_sys();
+    rt.push(value);
+    rt.run_str("csv").map_err(|e| e.to_string())?;
+    let csv = rt.pop("CSV").map_err(|e| e.to_string())?;
+    csv.as_string(&rt, "CSV must be a string")
+        .map_err(|e| e.to_string())
+}
+
+/// Get the output class for the `i`th of `count` values on a stack
+fn value_class(i: usize, count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        match i % 6 {
+            0 => "output-a",
+            1 => "output-b",
+            2 => "output-c",
+            3 => "output-d",
+            4 => "output-e",
+            5 => "output-f",
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Records the stack after each top-level line for the stack panel
+#[derive(Default)]
+struct LineStacks {
+    lines: Vec<(CodeSpan, usize)>,
+    stacks: Arc<Mutex<Vec<Vec<Value>>>>,
+}
+
+impl LineStacks {
+    /// Add a hook to the runtime that records the stacks of the compiled lines
+    ///
+    /// A line's stack is recorded when execution reaches a later line.
+    /// Lines that only push values never reach the hook, so stacks are
+    /// truncated to the line's known stack height.
+    fn record(&mut self, rt: Uiua, comp: &Compiler) -> Uiua {
+        self.lines = (comp.code_meta().top_level_heights.iter())
+            .filter(|(span, _)| matches!(span.src, InputSrc::Str(_)))
+            .map(|(span, height)| (span.clone(), *height))
+            .collect();
+        let lines = self.lines.clone();
+        let stacks = self.stacks.clone();
+        rt.with_exec_hook(move |env, span| {
+            let mut stacks = stacks.lock().unwrap();
+            let done = stacks.len();
+            let reached = lines[done..].iter().position(|(line, _)| {
+                line.src == span.src
+                    && line.start.char_pos <= span.start.char_pos
+                    && span.end.char_pos <= line.end.char_pos
+            });
+            if let Some(reached) = reached {
+                for (_, height) in &lines[done..][..reached] {
+                    let stack = env.stack();
+                    stacks.push(stack[..(*height).min(stack.len())].to_vec());
+                }
+            }
+            Ok(())
+        })
+    }
+    /// Record the stacks of the remaining lines after the program finishes
+    fn finish(&self, stack: &[Value]) {
+        let mut stacks = self.stacks.lock().unwrap();
+        for (_, height) in &self.lines[stacks.len()..] {
+            stacks.push(stack[..(*height).min(stack.len())].to_vec());
+        }
+    }
+    /// Get the output items for the panel
+    fn output(self) -> Vec<OutputItem> {
+        let stacks = take(&mut *self.stacks.lock().unwrap());
+        let mut output = Vec::new();
+        for ((span, _), mut stack) in self.lines.into_iter().zip(stacks) {
+            output.push(OutputItem::Faint(format!("After line {}:", span.end.line)));
+            if stack.is_empty() {
+                output.push(OutputItem::Faint("  (empty)".into()));
+            }
+            if get_top_at_top() {
+                stack.reverse();
+            }
+            let count = stack.len();
+            for (i, value) in stack.iter().enumerate() {
+                let class = value_class(i, count);
+                for line in stack_panel_string(value).lines() {
+                    output.push(OutputItem::Classed(class, format!("  {line}")));
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Show a value in the stack panel, summarizing it if it is large
+fn stack_panel_string(value: &Value) -> String {
+    const MAX_SHOWN_ELEMS: usize = 100;
+    fn summarize<T: ArrayValue>(arr: &Array<T>) -> String {
+        T::summarize(&arr.elements().cloned().collect::<Vec<_>>())
+    }
+    if value.element_count() <= MAX_SHOWN_ELEMS {
+        return value.show();
+    }
+    let summary = match value {
+        Value::Num(arr) => summarize(arr),
+        Value::Byte(arr) => summarize(arr),
+        Value::Complex(arr) => summarize(arr),
+        Value::Char(arr) => summarize(arr),
+        Value::Box(arr) => summarize(arr),
+    };
+    if summary.is_empty() {
+        value.shape_string()
+    } else {
+        format!("{}: {summary}", value.shape_string())
+    }
 }
 
 pub fn report_view(report: &Report) -> impl IntoView {
@@ -1254,6 +1421,64 @@ pub fn url_encode_code(code: &str) -> String {
     )
 }
 
+/// Encode the pad's code as a URL query
+///
+/// If the user has added files, the code and files are encoded as a project.
+pub fn url_encode_pad(code: &str) -> String {
+    let files = user_files();
+    if files.is_empty() {
+        format!("src={}", url_encode_code(code))
+    } else {
+        format!("project={}", url_encode_project(code, &files))
+    }
+}
+
+/// Encode code and the files it uses as a compressed project
+pub fn url_encode_project(code: &str, files: &[ProjectFile]) -> String {
+    fn push_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+        buffer.extend((bytes.len() as u32).to_le_bytes());
+        buffer.extend(bytes);
+    }
+    let mut buffer = Vec::new();
+    push_bytes(&mut buffer, code.as_bytes());
+    for (path, contents) in files {
+        push_bytes(&mut buffer, path.to_string_lossy().as_bytes());
+        push_bytes(&mut buffer, contents);
+    }
+    let compressed = compress_to_vec(&buffer, 10);
+    format!(
+        "{}__{}",
+        uiua::VERSION.replace('.', "_"),
+        URL_SAFE.encode(compressed)
+    )
+}
+
+/// Decode a project encoded with [`url_encode_project`] into its code and files
+pub fn url_decode_project(encoded: &str) -> Option<(String, Vec<ProjectFile>)> {
+    const MAX_PROJECT_SIZE: usize = 64 * 1024 * 1024;
+    fn take_bytes<'a>(buffer: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let len = u32::from_le_bytes(buffer.get(..4)?.try_into().unwrap()) as usize;
+        let end = len.checked_add(4)?;
+        let bytes = buffer.get(4..end)?;
+        *buffer = &buffer[end..];
+        Some(bytes)
+    }
+    let encoded = encoded
+        .split_once("__")
+        .map_or(encoded, |(_, encoded)| encoded);
+    let compressed = URL_SAFE.decode(encoded.as_bytes()).ok()?;
+    let buffer = decompress_to_vec_with_limit(&compressed, MAX_PROJECT_SIZE).ok()?;
+    let mut buffer = buffer.as_slice();
+    let code = String::from_utf8_lossy(take_bytes(&mut buffer)?).into_owned();
+    let mut files = Vec::new();
+    while !buffer.is_empty() {
+        let path = PathBuf::from(String::from_utf8_lossy(take_bytes(&mut buffer)?).as_ref());
+        let contents = take_bytes(&mut buffer)?.to_vec();
+        files.push((path, contents));
+    }
+    Some((code, files))
+}
+
 fn get_local_var<T>(name: &str, default: impl FnOnce() -> T) -> T
 where
     T: FromStr,
@@ -1367,6 +1592,13 @@ pub fn set_inlay_values(inlay_values: bool) {
     set_local_var("inlay-values", inlay_values);
 }
 
+pub fn get_stack_panel() -> bool {
+    get_local_var("stack-panel", || false)
+}
+pub fn set_stack_panel(stack_panel: bool) {
+    set_local_var("stack-panel", stack_panel);
+}
+
 fn update_style() {
     let font_name = get_font_name();
     let font_size = get_font_size();
@@ -8,7 +8,7 @@ use enum_iterator::all;
 use leptos::{leptos_dom::helpers::location, *};
 use leptos_meta::*;
 use leptos_router::*;
-use uiua::{PrimClass, Primitive, SysOpClass};
+use uiua::{PrimClass, PrimDocLine, Primitive, SysOpClass};
 use wasm_bindgen::JsCast;
 use web_sys::{Event, EventInit, HtmlInputElement, ScrollBehavior, ScrollIntoViewOptions};
 
@@ -129,7 +129,7 @@ fn DocsHome(#[prop(optional)] search: String) -> impl IntoView {
     let (results, set_result) = create_signal(None);
     let (current_prim, set_current_prim) = create_signal(None);
     let (clear_button, set_clear_button) = create_signal(None);
-    let (old_allowed, set_old_allowed) = create_signal(Allowed::all());
+    let (old_results, set_old_results) = create_signal(SearchResults::Table(Allowed::all()));
     let update_search = move |text: &str, update_location: bool| {
         // Update clear button
         set_clear_button.set(if text.is_empty() {
@@ -146,8 +146,8 @@ fn DocsHome(#[prop(optional)] search: String) -> impl IntoView {
             Some(view!( {}<button on:click=clear_search>"✕"</button>).into_view())
         });
 
-        // Derive allowed primitives
-        let allowed = Allowed::from_search(text);
+        // Derive search results
+        let search_results = SearchResults::from_search(text);
         if !text.is_empty() {
             let siv_options = ScrollIntoViewOptions::new();
             siv_options.set_behavior(ScrollBehavior::Smooth);
@@ -170,28 +170,40 @@ fn DocsHome(#[prop(optional)] search: String) -> impl IntoView {
                 Duration::from_secs(2),
             );
         }
-        if allowed == old_allowed.get() && results.get().is_some() {
+        if search_results == old_results.get() && results.get().is_some() {
             return;
         }
-        set_old_allowed.set(allowed.clone());
-        if allowed.classes.is_empty() && allowed.prims.is_empty() {
-            // No Results
-            set_result.set(Some(view!( <p>"No results"</p>).into_view()));
-            set_current_prim.set(None);
-        } else if allowed.prims.len() == 1
-            && [PrimClass::all().count(), 1].contains(&allowed.classes.len())
-        {
-            // Only one result
-            let prim = allowed.prims.into_iter().next().unwrap();
-            let siv_options = ScrollIntoViewOptions::new();
-            siv_options.set_behavior(ScrollBehavior::Instant);
-            scroll_to_docs_functions(&siv_options);
-            set_result.set(Some(view!( <PrimDocs prim=prim/>).into_view()));
-            set_current_prim.set(Some(prim));
-        } else {
-            // Multiple results
-            set_result.set(Some(allowed.table().into_view()));
-            set_current_prim.set(None);
+        set_old_results.set(search_results.clone());
+        match search_results {
+            SearchResults::Table(allowed) if allowed.classes.is_empty() => {
+                // No Results
+                set_result.set(Some(view!( <p>"No results"</p>).into_view()));
+                set_current_prim.set(None);
+            }
+            SearchResults::Table(allowed) => {
+                // Results by class
+                set_result.set(Some(allowed.table().into_view()));
+                set_current_prim.set(None);
+            }
+            SearchResults::Ranked(ranked) if ranked.is_empty() => {
+                // No Results
+                set_result.set(Some(view!( <p>"No results"</p>).into_view()));
+                set_current_prim.set(None);
+            }
+            SearchResults::Ranked(ranked) if ranked.len() == 1 => {
+                // Only one result
+                let prim = ranked[0].0;
+                let siv_options = ScrollIntoViewOptions::new();
+                siv_options.set_behavior(ScrollBehavior::Instant);
+                scroll_to_docs_functions(&siv_options);
+                set_result.set(Some(view!( <PrimDocs prim=prim/>).into_view()));
+                set_current_prim.set(Some(prim));
+            }
+            SearchResults::Ranked(ranked) => {
+                // Multiple results
+                set_result.set(Some(ranked_view(&ranked).into_view()));
+                set_current_prim.set(None);
+            }
         }
     };
     let update_title = move || {
@@ -274,7 +286,7 @@ fn DocsHome(#[prop(optional)] search: String) -> impl IntoView {
                     value=search
                     on:input=on_search_input
                     pattern="[^0-9]"
-                    placeholder="Search by name, glyph, or category..."/>
+                    placeholder="Search by name, glyph, category, or description..."/>
                 { move || clear_button.get() }
             </div>
             <A href="/docs/all-functions">"Scrollable List"</A>
@@ -314,17 +326,38 @@ fn aliases() -> HashMap<&'static str, &'static [Primitive]> {
     .collect()
 }
 
+/// The lowercase short text and example code of every primitive
+fn doc_texts() -> HashMap<Primitive, (String, String)> {
+    Primitive::all()
+        .map(|prim| {
+            let doc = prim.doc();
+            let examples = (doc.lines.iter())
+                .filter_map(|line| match line {
+                    PrimDocLine::Example(ex) => Some(ex.input()),
+                    PrimDocLine::Text(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            (prim, (doc.short_text().to_lowercase(), examples))
+        })
+        .collect()
+}
+
 thread_local! {
     static ALIASES: HashMap<&'static str, &'static [Primitive]> = aliases();
+    static DOC_TEXTS: HashMap<Primitive, (String, String)> = doc_texts();
 }
 
-impl Allowed {
-    fn all() -> Self {
-        Self {
-            classes: PrimClass::all().collect(),
-            prims: Primitive::all().collect(),
-        }
-    }
+/// The results of a function search
+#[derive(Clone, PartialEq, Eq)]
+enum SearchResults {
+    /// Primitives grouped by class
+    Table(Allowed),
+    /// Primitives with their scores, from best to worst match
+    Ranked(Vec<(Primitive, u32)>),
+}
+
+impl SearchResults {
     fn from_search(search: &str) -> Self {
         let search = search.trim().to_lowercase();
         let parts: Vec<_> = search
@@ -332,48 +365,161 @@ impl Allowed {
             .filter(|&part| part.chars().any(|c| !c.is_ascii_digit()))
             .collect();
         if parts.is_empty() {
-            return Self::all();
+            return SearchResults::Table(Allowed::all());
         }
-        let mut prims = HashSet::new();
-        let all = Primitive::all;
-        let prim_matching_part_exactly = |part: &str| -> Option<Primitive> {
-            Primitive::non_deprecated().find(|p| {
-                p.name().to_lowercase() == part
-                    || p.ascii().is_some_and(|a| a.to_string() == part)
-                    || p.glyph().is_some_and(|u| part.chars().all(|c| c == u))
-            })
+        // Exact matches
+        if let Some(prim) = Primitive::non_deprecated().find(|p| exact_match(*p, &search)) {
+            return SearchResults::Ranked(vec![(prim, u32::MAX)]);
+        }
+        // Class names
+        let names_prim = |part: &str| {
+            Primitive::non_deprecated().any(|p| p.name().to_lowercase().starts_with(part))
+                || ALIASES.with(|aliases| aliases.contains_key(part))
         };
-        for part in &parts {
-            ALIASES.with(|aliases| {
-                if let Some(prim) = aliases.get(part) {
-                    prims.extend(prim.iter().copied());
-                }
-            });
+        if !parts.iter().any(|part| names_prim(part)) {
+            if let Some(allowed) = Allowed::from_class_search(&parts) {
+                return SearchResults::Table(allowed);
+            }
         }
-        if let Some(prim) = prim_matching_part_exactly(&search) {
-            prims.insert(prim);
-            return Self {
-                classes: [prim.class()].into(),
-                prims,
-            };
-        } else {
-            for &part in &parts {
-                if let Some(prim) = prim_matching_part_exactly(part) {
-                    prims.insert(prim);
-                    continue;
+        // Fuzzy matches
+        let mut ranked: Vec<(Primitive, usize, u32)> = Primitive::all()
+            .filter_map(|prim| {
+                let scores: Vec<u32> = parts
+                    .iter()
+                    .filter_map(|part| search_score(prim, part))
+                    .collect();
+                let mut score: u32 = scores.iter().sum();
+                if prim.is_deprecated() {
+                    score /= 4;
                 }
-                let matches = all()
-                    .filter(|p| p.name().to_lowercase().starts_with(part))
-                    .chain(all().filter(|p| {
-                        p.ascii()
-                            .is_some_and(|simple| part.contains(&simple.to_string()))
-                    }))
-                    .chain(
-                        all().filter(|p| p.glyph().is_some_and(|unicode| part.contains(unicode))),
-                    );
-                prims.extend(matches);
+                (score > 0).then_some((prim, scores.len(), score))
+            })
+            .collect();
+        ranked.sort_by(|(a, a_parts, a_score), (b, b_parts, b_score)| {
+            (b_parts.cmp(a_parts))
+                .then(b_score.cmp(a_score))
+                .then(a.cmp(b))
+        });
+        SearchResults::Ranked(
+            (ranked.into_iter())
+                .take(MAX_RANKED_RESULTS)
+                .map(|(prim, _, score)| (prim, score))
+                .collect(),
+        )
+    }
+}
+
+const MAX_RANKED_RESULTS: usize = 40;
+
+/// Check if a search is exactly a primitive's name, ASCII spelling, or glyph
+fn exact_match(prim: Primitive, search: &str) -> bool {
+    prim.name().to_lowercase() == search
+        || prim.ascii().is_some_and(|a| a.to_string() == search)
+        || prim.glyph().is_some_and(|u| search.chars().all(|c| c == u))
+}
+
+/// Score how well a part of a search matches a primitive
+///
+/// Matches on the name score highest, followed by aliases,
+/// the short description, and finally the examples.
+fn search_score(prim: Primitive, part: &str) -> Option<u32> {
+    if exact_match(prim, part) {
+        return Some(1000);
+    }
+    if prim.glyph().is_some_and(|glyph| part.contains(glyph))
+        || prim
+            .ascii()
+            .is_some_and(|ascii| part.contains(&ascii.to_string()))
+    {
+        return Some(900);
+    }
+    let name = prim.name().to_lowercase();
+    if name.starts_with(part) {
+        return Some(800 - (name.len() - part.len()).min(100) as u32);
+    }
+    if ALIASES.with(|aliases| aliases.get(part).is_some_and(|prims| prims.contains(&prim))) {
+        return Some(700);
+    }
+    if part.chars().count() < 3 {
+        return None;
+    }
+    if name.contains(part) {
+        return Some(600);
+    }
+    if let Some(gaps) = subsequence_gaps(&name, part) {
+        return Some(500u32.saturating_sub(50 * gaps).max(300));
+    }
+    DOC_TEXTS.with(|texts| {
+        let (short, examples) = &texts[&prim];
+        if short
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word.starts_with(part))
+        {
+            Some(200)
+        } else if short.contains(part) {
+            Some(150)
+        } else if examples.contains(part) {
+            Some(100)
+        } else {
+            None
+        }
+    })
+}
+
+/// Get the number of gaps between the characters of `part` in `name`
+/// if they appear in order, starting at the first character
+fn subsequence_gaps(name: &str, part: &str) -> Option<u32> {
+    let mut name_chars = name.chars();
+    let mut part_chars = part.chars();
+    if name_chars.next()? != part_chars.next()? {
+        return None;
+    }
+    let mut gaps = 0;
+    let mut in_gap = false;
+    for c in part_chars {
+        loop {
+            if name_chars.next()? == c {
+                in_gap = false;
+                break;
+            }
+            if !in_gap {
+                gaps += 1;
+                in_gap = true;
             }
         }
+    }
+    Some(gaps)
+}
+
+fn ranked_view(ranked: &[(Primitive, u32)]) -> impl IntoView {
+    let rows: Vec<_> = ranked
+        .iter()
+        .map(|&(prim, _)| {
+            let style = if prim.is_deprecated() {
+                "text-decoration: line-through;"
+            } else {
+                ""
+            };
+            view!(<div style="display: flex; align-items: center;">
+                <div style="min-width: 10em; display: flex; align-items: center;">
+                    <div style=style><Prim prim=prim/></div>
+                </div>
+                {prim.doc().short_text().into_owned()}
+            </div>)
+        })
+        .collect();
+    view!(<div class="primitive-list">{ rows }</div>)
+}
+
+impl Allowed {
+    fn all() -> Self {
+        Self {
+            classes: PrimClass::all().collect(),
+            prims: Primitive::all().collect(),
+        }
+    }
+    /// Get the classes named by every part of a search, if any
+    fn from_class_search(parts: &[&str]) -> Option<Self> {
         let mut classes: HashSet<PrimClass> = PrimClass::all().collect();
         let system_classes: Vec<PrimClass> = SysOpClass::all().map(PrimClass::Sys).collect();
         let mut function_classes: Vec<PrimClass> = system_classes.clone();
@@ -385,7 +531,7 @@ impl Allowed {
             PrimClass::DyadicArray,
             PrimClass::Misc,
         ]);
-        'parts: for part in &parts {
+        'parts: for part in parts {
             for (pattern, pat_classes) in [
                 ("stack", [PrimClass::Stack].as_slice()),
                 (
@@ -437,21 +583,10 @@ impl Allowed {
                     continue 'parts;
                 }
             }
-            classes.clear();
-            break;
-        }
-        prims.extend(classes.iter().flat_map(|p| p.primitives()));
-        classes.extend(prims.iter().map(|p| p.class()));
-        if classes.is_empty() && !parts.is_empty() {
-            return Self::default();
-        }
-        if prims.is_empty() {
-            prims = Primitive::all().collect();
-        }
-        if classes.is_empty() {
-            classes = PrimClass::all().collect();
+            return None;
         }
-        Self { classes, prims }
+        let prims = classes.iter().flat_map(|p| p.primitives()).collect();
+        Some(Self { classes, prims })
     }
     fn table(&self) -> impl IntoView {
         let mut table_cells = Vec::new();
@@ -586,3 +721,25 @@ impl Allowed {
         view!( <table>{ rows }</table>)
     }
 }
+
+#[cfg(test)]
+#[test]
+fn fuzzy_search() {
+    use Primitive::*;
+    let ranked = |search: &str| match SearchResults::from_search(search) {
+        SearchResults::Ranked(ranked) => ranked.into_iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        SearchResults::Table(_) => panic!("{search:?} should have ranked results"),
+    };
+    assert_eq!(ranked("reverse"), [Reverse]);
+    assert_eq!(ranked("⇌"), [Reverse]);
+    assert_eq!(ranked("rev")[0], Reverse);
+    assert_eq!(ranked("revrse")[0], Reverse);
+    assert_eq!(ranked("window")[0], Stencil);
+    let sorted = ranked("sorted");
+    assert!(sorted.contains(&Rise) && sorted.contains(&Fall));
+    assert!(matches!(
+        SearchResults::from_search("pervasive"),
+        SearchResults::Table(allowed) if allowed.prims.contains(&Add)
+    ));
+    assert_eq!(ranked("qwxz"), []);
+}
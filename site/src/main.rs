@@ -21,7 +21,10 @@ use leptos_router::*;
 use rand::prelude::*;
 use uiua::{now, ConstantDef, Primitive, SysOp};
 use uiua_editor::{
-    binding_name_class, utils::ChallengeDef, Editor, EditorMode, Prim, EDITOR_SHORTCUTS,
+    backend::drop_file,
+    binding_name_class,
+    utils::{url_decode_project, ChallengeDef},
+    Editor, EditorMode, Prim, EDITOR_SHORTCUTS,
 };
 use wasm_bindgen::JsCast;
 use web_sys::{Element, HtmlAudioElement};
@@ -482,6 +485,8 @@ pub fn PadPage() -> impl IntoView {
         }
         <br/>
         <p>"You can load files into the pad by dragging and dropping them into the window."</p>
+        <p>"Links to the pad include any files you have loaded, so they can be shared along with code that imports them."</p>
+        <p>"The pad and documentation work offline once visited. Your browser may also let you install this site as an app."</p>
         <p>"Replace "<code>"pad"</code>" in links with "<code>"embed"</code>" or "<code>"embedpad"</code>" to embed the editor."</p>
         <p>"Keyboard shortcuts:"</p>
         <code class="code-block">
@@ -509,6 +514,13 @@ pub fn Embed() -> impl IntoView {
 }
 
 fn pad_src() -> String {
+    let project = use_query_map().with_untracked(|params| params.get("project").cloned());
+    if let Some((code, files)) = project.as_deref().and_then(url_decode_project) {
+        for (path, contents) in files {
+            drop_file(path, contents);
+        }
+        return code;
+    }
     let mut src = use_query_map()
         .with_untracked(|params| params.get("src").cloned())
         .unwrap_or_default();
@@ -523,6 +535,22 @@ fn pad_src() -> String {
     src
 }
 
+#[test]
+fn project_links() {
+    use std::path::PathBuf;
+    use uiua_editor::utils::url_encode_project;
+
+    let code = "~ \"utils.ua\" ~ Foo\nFoo 5";
+    let files = vec![
+        (PathBuf::from("utils.ua"), "Foo ← +1".into()),
+        (PathBuf::from("data/bytes.bin"), vec![0, 255, 1, 254]),
+    ];
+    let encoded = url_encode_project(code, &files);
+    assert!(encoded.starts_with(&uiua::VERSION.replace('.', "_")));
+    assert_eq!(url_decode_project(&encoded), Some((code.into(), files)));
+    assert_eq!(url_decode_project("0_14_0__notaproject"), None);
+}
+
 #[test]
 fn site() {
     type Test = (
@@ -1,10 +1,12 @@
+use std::cell::Cell;
+
 use comrak::{
     nodes::{AstNode, ListType, NodeValue},
     *,
 };
 use leptos::*;
 use uiua::{Inputs, Primitive, Token};
-use uiua_editor::{backend::fetch, Editor};
+use uiua_editor::{backend::fetch, utils::ChallengeDef, Editor};
 
 use crate::{examples::LOGO, Hd, NotFound, Prim, ScrollToHash};
 
@@ -146,6 +148,26 @@ fn node_view<'a>(node: &'a AstNode<'a>) -> View {
         NodeValue::CodeBlock(block) => {
             if block.literal.trim() == "LOGO" {
                 view!(<Editor example=LOGO/>).into_view()
+            } else if block.info.starts_with("uiua") && block.info.contains("exercise") {
+                let (start, solution) = split_exercise(&block.literal);
+                let def = ChallengeDef {
+                    example: String::new(),
+                    intended_answer: solution.into(),
+                    best_answer: None,
+                    tests: Vec::new(),
+                    hidden: String::new(),
+                    flip: false,
+                    did_init_run: Cell::new(false),
+                };
+                view! {
+                    <div class="challenge">
+                        <Editor challenge=def example=start/>
+                        { solution_view(solution) }
+                    </div>
+                }
+                .into_view()
+            } else if block.info.starts_with("uiua") && block.info.contains("solution") {
+                solution_view(block.literal.trim_end()).into_view()
             } else if (block.info.is_empty() || block.info.starts_with("uiua"))
                 && uiua::parse(&block.literal, (), &mut Default::default())
                     .1
@@ -170,6 +192,30 @@ fn node_view<'a>(node: &'a AstNode<'a>) -> View {
     }
 }
 
+/// Split an exercise block into its starting code and its solution
+///
+/// The solution is everything after a `# Solution` line.
+fn split_exercise(literal: &str) -> (&str, &str) {
+    let mut start_len = 0;
+    for line in literal.split_inclusive('\n') {
+        if line.trim() == "# Solution" {
+            let solution = &literal[start_len + line.len()..];
+            return (literal[..start_len].trim_end(), solution.trim_end());
+        }
+        start_len += line.len();
+    }
+    ("", literal.trim_end())
+}
+
+fn solution_view(solution: &str) -> impl IntoView {
+    view! {
+        <details>
+            <summary>"Solution"</summary>
+            <Editor example=solution nonprogressive=true/>
+        </details>
+    }
+}
+
 #[cfg(test)]
 fn node_html<'a>(node: &'a AstNode<'a>) -> String {
     use uiua::{Compiler, SafeSys, Uiua, UiuaErrorKind, Value};
@@ -384,6 +430,8 @@ fn text_code_blocks() {
                         let should_fail = block.info.contains("should fail");
                         let literal = if block.literal.trim() == "LOGO" {
                             LOGO
+                        } else if block.info.contains("exercise") {
+                            split_exercise(&block.literal).1
                         } else {
                             block.literal.as_str()
                         };
@@ -420,3 +468,14 @@ fn text_code_blocks() {
         }
     }
 }
+
+#[cfg(test)]
+#[test]
+fn exercise_blocks() {
+    assert_eq!(
+        split_exercise("[1 2 3]\n# Solution\n⇌[1 2 3]\n"),
+        ("[1 2 3]", "⇌[1 2 3]")
+    );
+    assert_eq!(split_exercise("  # Solution  \n⇌"), ("", "⇌"));
+    assert_eq!(split_exercise("⇌[1 2 3]\n"), ("", "⇌[1 2 3]"));
+}
@@ -296,6 +296,8 @@ pub fn Optimizations() -> impl IntoView {
             <li><Prims prims=[Table, Fork]/><code>"F"</code><code>"G"</code>" is optimized to "<Prims prims=[Fork, Table]/><code>"F"</code><Prims prims=[Table]/><code>"G"</code>" for pure functions."</li>
             <li><Prim prim=Group/>" and "<Prim prim=Partition/>" are optimized to be fast with "<Prim prim=Len/>", "<Prim prim=First/>", "<Prim prim=Last/>"."</li>
             <li><Prim prim=Repeat/>" with a simple-enough function and a small constant count will compile to an unrolled loop."</li>
+            <li><Prims prims=[Reduce, Add, Mul]/>" on two lists of numbers computes the dot product without creating an intermediate array."</li>
+            <li><Prims prims=[Dip, Identity]/>" is removed entirely."</li>
             <li>"The following splitting patterns are optimized for monadic function "<code>"F"</code>":"
                 <table class="bordered-table">
                     <tr><td><Prims prims=[Partition]/><code>"F"</code><Prims prims=[By, Ne]/></td></tr>
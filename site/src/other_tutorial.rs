@@ -235,5 +235,20 @@ pub fn Documentation() -> impl IntoView {
         <Editor example="F ← +2 # Deprecated! Use G instead\nG ← ×2\nF 5"/> // Should fail
         <p><code>"# Deprecated!"</code>" works for constants, macros, and modules as well."</p>
         <Editor example="# Deprecated! Don't use it!\n┌─╴Foo\n  # Deprecated! It's no good!\n  Bar ← 5\n└─╴\nFoo~Bar"/> // Should fail
+
+        <Hd id="cfg"><code>"# Cfg!"</code></Hd>
+
+        <p>"Some system functions are only available when running natively, and some only on the web. The "<code>"# Cfg!"</code>" semantic comment makes the binding, module, or import on the next line only compile for the listed targets."</p>
+        <p>"The targets are "<code>"native"</code>" and "<code>"web"</code>". The same name can be bound once for each target."</p>
+        <Editor example="# Cfg! native\nRead ← &fras\n# Cfg! web\nRead ← $\"Can't read _\"\nRead \"example.txt\""/>
+        <p>"The current target is also available as the "<code>"Target"</code>" constant."</p>
+        <Editor example="Target"/>
+
+        <Hd id="type-check"><code>"# Type check!"</code></Hd>
+
+        <p>"The "<code>"# Type check!"</code>" semantic comment enables extra checking for the rest of the file. Code that will always fail because of the types or shapes of its arguments causes a warning, even if the code is never run."</p>
+        <Editor example="# Type check!\nF ← ⊂ \"Length: \" ⧻\nG ← ⊟ [1 2] ⊂ [1 2 3] ⧻"/> // Should fail
+        <p>"Only errors that can be found without knowing the values of a function's arguments are reported, so this is not a replacement for testing."</p>
+        <p>"The "<code>"uiua check --types"</code>" command enables these checks for every file it checks."</p>
     }
 }
@@ -442,7 +442,8 @@ fn prim_docs() {
         for line in &prim.doc().lines {
             if let PrimDocLine::Example(ex) = line {
                 if [
-                    "&sl", "&tcpc", "&tlsc", "&ast", "&clip", "&frab", "&fmd", "&b",
+                    "&sl", "&tcpc", "&tlsc", "&ast", "&clip", "&frab", "&fmd", "&b", "&smtp",
+                    "&serial", "&cwd", "&canon", "&glob ", "&fwalk", "&flock", "&fwr",
                 ]
                 .iter()
                 .any(|prim| ex.input().contains(prim))
@@ -1220,9 +1220,9 @@ splitArray([1, 2, 3, 7, 2, 4, 5])"</code>
         <Hd id="recursion">"Recursion"</Hd>
         <p>"A bound function that refers to its own name is a "<a href="https://en.wikipedia.org/wiki/Recursion_(computer_science)">"recursive function"</a>". A function that calls itself can easily recurse infinitely, so it is important to have a "<em>"base case"</em>" that stops the recursion when a condition is met. Switch functions are great for this."</p>
         <p>"As a simple example, here is a function that calculates the factorial of a number. Note that you should not actually do this, as "<Prims prims=[Reduce, Mul, Add]/><code>"1"</code><Prims prims=[Range]/>" is shorter, faster, and more idiomatic."</p>
-        <Editor example="Fact ← |1 ⨬(×Fact-1.|1)<2.\nFact 5"/>
+        <Editor example="Fact ← ⨬(×Fact-1.|1)<2.\nFact 5"/>
         <p>"The base case is when the input is "<code>"1"</code>". In this case, the function returns "<code>"1"</code>". Otherwise, it multiplies the input by the result of calling itself with the input decremented by "<code>"1"</code>"."</p>
-        <p>"Recursive functions are required to have signatures declared."</p>
+        <p>"The signature of a recursive function is inferred by assuming a signature for the recursive call and checking that the function agrees. If no signature can be inferred, it must be declared."</p>
         <p>"Recursion is only recommended if a particular problem "<em>"really"</em>" calls for it. Recursion in Uiua can be slow, and there is a limit to how deep you can recur."</p>
         <p>"It is usually better to use either array-based methods or iteration with "<Prim prim=Repeat/>" or "<Prim prim=Do/>"."</p>
 
@@ -1650,6 +1650,9 @@ fn TutorialModules() -> impl IntoView {
         <p>"To enter this arrow, you can put a "<code>"~"</code>" after a binding's normal "<code>"←"</code>" or "<code>"="</code>"."</p>
         <p>"Try formatting the following example to see how this works."</p>
         <Editor example="A = +1\nB ← +2\nC =~ +3\nD ←~ +4"/>
+        <p>"To make an imported item or module part of a module's public API, re-export it by binding its name to itself with "<code>"←"</code>"."</p>
+        <p>"Items can also be re-exported under a different name."</p>
+        <Editor example="┌─╴M\n  Ex ~ \"example.ua\" ~ Square\n  Square ← Square\n  Ex     ← Ex\n  Dub    ← Ex~Double\n└─╴\nM~Square 3\nM~Ex~Increment 3\nM~Dub 3"/>
 
         <Hd id="git-modules">"Git Modules"</Hd>
         <p>"Modules can be imported from Git repositories. Instead of a path, use a URL prefixed with "<code>"git:"</code>"."</p>
@@ -0,0 +1,381 @@
+//! Reverse-mode automatic differentiation
+
+use ecow::eco_vec;
+
+use crate::{
+    algorithm::walk::{walk, StackWalker, WalkError, Walker},
+    Array, Assembly, ImplPrimitive, Node, Primitive, Shape, SigNode, Uiua, UiuaError, UiuaResult,
+    Value,
+};
+
+/// A differentiable operation
+#[derive(Clone, Copy)]
+enum Op {
+    Neg,
+    Not,
+    Sqrt,
+    Sin,
+    Cos,
+    Abs,
+    Sum,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Log,
+    Min,
+    Max,
+}
+
+struct Entry {
+    value: Value,
+    /// Whether this value depends on any of the inputs
+    tracked: bool,
+    op: Option<(Op, Vec<usize>)>,
+}
+
+/// A recording of the operations performed by a function
+struct Tape<'a> {
+    env: &'a mut Uiua,
+    entries: Vec<Entry>,
+    /// Indices into `entries`
+    stack: Vec<usize>,
+    depth: usize,
+}
+
+/// Calculate the gradient of a function with respect to each of its arguments
+pub fn gradient(f: SigNode, env: &mut Uiua) -> UiuaResult {
+    let sig = f.sig;
+    if sig.outputs != 1 {
+        return Err(env.error(format!(
+            "{}'s function must have 1 output, but its signature is {sig}",
+            Primitive::Gradient.format()
+        )));
+    }
+    let mut tape = Tape {
+        entries: Vec::with_capacity(sig.args),
+        stack: Vec::with_capacity(sig.args),
+        depth: 0,
+        env,
+    };
+    for i in 0..sig.args {
+        let value = match tape.env.pop(i + 1)? {
+            Value::Num(arr) => arr.into(),
+            Value::Byte(arr) => arr.convert::<f64>().into(),
+            val => {
+                return Err(tape.env.error(format!(
+                    "Cannot take the {} of {}",
+                    Primitive::Gradient.format(),
+                    val.type_name_plural()
+                )))
+            }
+        };
+        tape.entries.push(Entry {
+            value,
+            tracked: true,
+            op: None,
+        });
+    }
+    tape.stack.extend((0..sig.args).rev());
+    walk(&mut tape, &f.node)?;
+    let output = tape.pop()?;
+    let grads = tape.backward(output)?;
+    for (entry, grad) in tape.entries.iter().zip(grads).take(sig.args).rev() {
+        let grad = match grad {
+            Some(grad) => grad,
+            None => zeros(entry.value.shape().clone()),
+        };
+        tape.env.push(grad);
+    }
+    Ok(())
+}
+
+fn is_add(args: &[SigNode]) -> bool {
+    matches!(args, [f] if matches!(f.node, Node::Prim(Primitive::Add, _)))
+}
+
+fn zeros(shape: Shape) -> Value {
+    let len = shape.elements();
+    Array::new(shape, eco_vec![0.0; len]).into()
+}
+
+impl Walker for Tape<'_> {
+    type Error = UiuaError;
+    fn asm(&self) -> &Assembly {
+        &self.env.asm
+    }
+    fn depth(&mut self) -> &mut usize {
+        &mut self.depth
+    }
+    fn error(&self, error: WalkError) -> UiuaError {
+        match error {
+            WalkError::TooDeep => self.env.error(format!(
+                "{}'s function is too deeply nested",
+                Primitive::Gradient.format()
+            )),
+            WalkError::NotSupported(what) => self.not_supported(what),
+            WalkError::NoInverse(e) => self.env.error(e),
+        }
+    }
+    fn leaf(&mut self, node: &Node) -> UiuaResult {
+        use Primitive::*;
+        match node {
+            Node::Push(val) => self.push_const(val.clone()),
+            Node::Prim(prim, _) => {
+                if self.stack_prim(*prim)? {
+                    return Ok(());
+                }
+                let node = node.clone();
+                match prim {
+                    Neg => self.op(Op::Neg, node, 1)?,
+                    Not => self.op(Op::Not, node, 1)?,
+                    Sqrt => self.op(Op::Sqrt, node, 1)?,
+                    Sin => self.op(Op::Sin, node, 1)?,
+                    Abs => self.op(Op::Abs, node, 1)?,
+                    Add => self.op(Op::Add, node, 2)?,
+                    Sub => self.op(Op::Sub, node, 2)?,
+                    Mul => self.op(Op::Mul, node, 2)?,
+                    Div => self.op(Op::Div, node, 2)?,
+                    Pow => self.op(Op::Pow, node, 2)?,
+                    Log => self.op(Op::Log, node, 2)?,
+                    Min => self.op(Op::Min, node, 2)?,
+                    Max => self.op(Op::Max, node, 2)?,
+                    Sign | Floor | Ceil | Round => self.non_diff(node, 1)?,
+                    Eq | Ne | Lt | Le | Gt | Ge => self.non_diff(node, 2)?,
+                    prim => return Err(self.not_supported(prim.format())),
+                }
+            }
+            Node::ImplPrim(ImplPrimitive::Cos, _) => self.op(Op::Cos, node.clone(), 1)?,
+            Node::ImplPrim(ImplPrimitive::DotProduct, span) => {
+                let add = SigNode::new((2, 1), Node::Prim(Add, *span));
+                self.op(Op::Mul, Node::Prim(Mul, *span), 2)?;
+                self.op(Op::Sum, Node::Mod(Reduce, eco_vec![add], *span), 1)?;
+            }
+            Node::ImplPrim(prim, _) => return Err(self.not_supported(prim)),
+            Node::Mod(Reduce, args, _) if is_add(args) => self.op(Op::Sum, node.clone(), 1)?,
+            Node::Mod(prim, ..) => return Err(self.not_supported(prim.format())),
+            _ => return Err(self.not_supported("this function")),
+        }
+        Ok(())
+    }
+    fn combinator(&mut self, prim: Primitive, args: &[SigNode], _: usize) -> UiuaResult {
+        self.eval_combinator(prim, args)
+    }
+}
+
+impl StackWalker for Tape<'_> {
+    type Value = usize;
+    fn stack(&mut self) -> &mut Vec<usize> {
+        &mut self.stack
+    }
+    fn empty_stack(&self) -> UiuaError {
+        self.env.error("Stack was empty when recording gradient")
+    }
+}
+
+impl Tape<'_> {
+    fn push_const(&mut self, value: Value) {
+        self.stack.push(self.entries.len());
+        self.entries.push(Entry {
+            value,
+            tracked: false,
+            op: None,
+        });
+    }
+    /// Run a node on the values of some entries
+    fn forward(&mut self, node: Node, args: &[usize]) -> UiuaResult<Value> {
+        for &i in args.iter().rev() {
+            self.env.push(self.entries[i].value.clone());
+        }
+        self.env.exec(node)?;
+        self.env.pop("gradient intermediate")
+    }
+    /// Record an operation
+    fn op(&mut self, op: Op, node: Node, arg_count: usize) -> UiuaResult {
+        let args = self.pop_n(arg_count)?;
+        let value = self.forward(node, &args)?;
+        let tracked = args.iter().any(|&i| self.entries[i].tracked);
+        self.stack.push(self.entries.len());
+        self.entries.push(Entry {
+            value,
+            tracked,
+            op: tracked.then_some((op, args)),
+        });
+        Ok(())
+    }
+    /// Run an operation whose gradient is always zero
+    fn non_diff(&mut self, node: Node, arg_count: usize) -> UiuaResult {
+        let args = self.pop_n(arg_count)?;
+        let value = self.forward(node, &args)?;
+        self.push_const(value);
+        Ok(())
+    }
+    fn not_supported(&self, what: impl std::fmt::Display) -> UiuaError {
+        self.env.error(format!(
+            "{} does not support {what}",
+            Primitive::Gradient.format()
+        ))
+    }
+    /// Propagate gradients backwards from the output
+    fn backward(&mut self, output: usize) -> UiuaResult<Vec<Option<Value>>> {
+        let mut grads: Vec<Option<Value>> = vec![None; self.entries.len()];
+        if self.entries[output].tracked {
+            let shape = self.entries[output].value.shape().clone();
+            let len = shape.elements();
+            grads[output] = Some(Array::new(shape, eco_vec![1.0; len]).into());
+        }
+        for i in (0..self.entries.len()).rev() {
+            let Some(g) = grads[i].take() else {
+                continue;
+            };
+            let entry = &self.entries[i];
+            let Some((op, args)) = &entry.op else {
+                grads[i] = Some(g);
+                continue;
+            };
+            let env = &*self.env;
+            let out = &entry.value;
+            let val = |j: usize| self.entries[args[j]].value.clone();
+            let wants = |j: usize| self.entries[args[j]].tracked;
+            let mut contribs: Vec<(usize, Value)> = Vec::with_capacity(2);
+            match op {
+                Op::Neg | Op::Not => contribs.push((0, g.neg(env)?)),
+                Op::Sqrt => {
+                    let two_out = Value::from(2).mul(out.clone(), env)?;
+                    contribs.push((0, two_out.div(g, env)?));
+                }
+                Op::Sin => contribs.push((0, val(0).cos(env)?.mul(g, env)?)),
+                Op::Cos => contribs.push((0, val(0).sin(env)?.mul(g, env)?.neg(env)?)),
+                Op::Abs => contribs.push((0, val(0).sign(env)?.mul(g, env)?)),
+                Op::Sum => {
+                    let x = val(0);
+                    let grad = if x.rank() == 0 {
+                        g
+                    } else {
+                        Value::from_row_values(vec![g; x.row_count()], env)?
+                    };
+                    contribs.push((0, grad));
+                }
+                Op::Add => {
+                    contribs.push((0, g.clone()));
+                    contribs.push((1, g));
+                }
+                Op::Sub => {
+                    contribs.push((0, g.clone().neg(env)?));
+                    contribs.push((1, g));
+                }
+                Op::Mul => {
+                    if wants(0) {
+                        contribs.push((0, val(1).mul(g.clone(), env)?));
+                    }
+                    if wants(1) {
+                        contribs.push((1, val(0).mul(g, env)?));
+                    }
+                }
+                // out = b / a
+                Op::Div => {
+                    if wants(0) {
+                        let grad = val(0).div(out.clone().mul(g.clone(), env)?, env)?;
+                        contribs.push((0, grad.neg(env)?));
+                    }
+                    if wants(1) {
+                        contribs.push((1, val(0).div(g, env)?));
+                    }
+                }
+                // out = b ^ a
+                Op::Pow => {
+                    if wants(0) {
+                        let ln_b = Value::from(std::f64::consts::E).log(val(1), env)?;
+                        let grad = out.clone().mul(ln_b, env)?.mul(g.clone(), env)?;
+                        contribs.push((0, grad));
+                    }
+                    if wants(1) {
+                        let lower = Value::from(1).sub(val(0), env)?.pow(val(1), env)?;
+                        let grad = val(0).mul(lower, env)?.mul(g, env)?;
+                        contribs.push((1, grad));
+                    }
+                }
+                // out = log_a(b)
+                Op::Log => {
+                    let ln_a = Value::from(std::f64::consts::E).log(val(0), env)?;
+                    if wants(0) {
+                        let denom = val(0).mul(ln_a.clone(), env)?;
+                        let grad = denom.div(out.clone().mul(g.clone(), env)?, env)?;
+                        contribs.push((0, grad.neg(env)?));
+                    }
+                    if wants(1) {
+                        let denom = val(1).mul(ln_a, env)?;
+                        contribs.push((1, denom.div(g, env)?));
+                    }
+                }
+                // Ties send the gradient to the top argument
+                Op::Max | Op::Min => {
+                    let top_wins = if let Op::Max = op {
+                        val(1).other_is_ge(val(0), env)?
+                    } else {
+                        val(1).other_is_le(val(0), env)?
+                    };
+                    if wants(0) {
+                        contribs.push((0, top_wins.clone().mul(g.clone(), env)?));
+                    }
+                    if wants(1) {
+                        let bottom_wins = top_wins.not(env)?;
+                        contribs.push((1, bottom_wins.mul(g, env)?));
+                    }
+                }
+            }
+            for (j, grad) in contribs {
+                let arg = &self.entries[args[j]];
+                if !arg.tracked {
+                    continue;
+                }
+                let grad = fit_shape(grad, arg.value.shape(), env)?;
+                grads[args[j]] = Some(match grads[args[j]].take() {
+                    Some(acc) => acc.add(grad, env)?,
+                    None => grad,
+                });
+            }
+        }
+        Ok(grads)
+    }
+}
+
+/// Sum a gradient over any axes its value was extended along
+fn fit_shape(grad: Value, shape: &Shape, env: &Uiua) -> UiuaResult<Value> {
+    if grad.shape() == shape {
+        return Ok(grad);
+    }
+    let arr = match grad {
+        Value::Num(arr) => arr,
+        Value::Byte(arr) => arr.convert(),
+        val => {
+            return Err(env.error(format!(
+                "Gradient became {}. This is a bug in the interpreter.",
+                val.type_name_plural()
+            )))
+        }
+    };
+    let count = shape.elements();
+    if count == 0 {
+        return Ok(zeros(shape.clone()));
+    }
+    if !(arr.shape.starts_with(shape) || count == 1) {
+        return Err(env.error(format!(
+            "Cannot take the {} of a value with shape {} that was extended to shape {}",
+            Primitive::Gradient.format(),
+            shape,
+            arr.shape
+        )));
+    }
+    let chunk = arr.data.len() / count;
+    let data: ecow::EcoVec<f64> = if chunk == 0 {
+        eco_vec![0.0; count]
+    } else {
+        arr.data
+            .chunks_exact(chunk)
+            .map(|c| c.iter().sum())
+            .collect()
+    };
+    Ok(Array::new(shape.clone(), data).into())
+}
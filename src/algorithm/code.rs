@@ -0,0 +1,249 @@
+//! Structured access to Uiua code
+
+use std::time::Duration;
+
+use ecow::EcoVec;
+
+use crate::{
+    ast::{Item, Modifier, Word},
+    parse::parse,
+    Array, Boxed, CodeSpan, Compiler, Inputs, SafeSys, Sp, Uiua, UiuaResult, Value,
+};
+
+const NODE_KEYS: [&str; 4] = ["kind", "span", "code", "children"];
+/// The limit on the duration of code run by [`eval`]
+const EVAL_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// Parse code into an AST value
+pub fn ast(env: &mut Uiua) -> UiuaResult {
+    let code = (env.pop(1)?).as_string(env, "Code must be a string")?;
+    let mut inputs = Inputs::default();
+    let (items, errors, _) = parse(&code, (), &mut inputs);
+    if let Some(error) = errors.first() {
+        return Err(env.error(format!("Invalid code: {error}")));
+    }
+    let builder = AstBuilder {
+        code: &code,
+        env: &*env,
+    };
+    let mut children = Vec::new();
+    for item in &items {
+        builder.item(item, &mut children)?;
+    }
+    let end = code.chars().count();
+    let root = builder.node("code", 0, end, code.clone(), children)?;
+    env.push(root);
+    Ok(())
+}
+
+/// Convert an AST value back into code
+pub fn unast(env: &mut Uiua) -> UiuaResult {
+    let node = env.pop(1)?;
+    let code = node_code(&node, env)?;
+    env.push(code);
+    Ok(())
+}
+
+/// Evaluate code in a sandbox, with a list of arguments
+pub fn eval(env: &mut Uiua) -> UiuaResult {
+    let code = env.pop("code")?;
+    let args = env.pop("arguments")?;
+    let code = match code {
+        Value::Char(_) => code.as_string(env, "")?,
+        node => node_code(&node, env)?,
+    };
+    let mut comp = Compiler::with_backend(SafeSys::default());
+    let mut inner = Uiua::with_backend(SafeSys::default())
+        .with_execution_limit(EVAL_TIME_LIMIT)
+        .with_op_budget(Compiler::DEFAULT_COMPTIME_OP_BUDGET);
+    // The first argument is on top of the stack
+    for arg in args.into_rows().rev() {
+        inner.push(arg.unboxed());
+    }
+    (comp.load_str(&code).map(drop))
+        .and_then(|_| inner.run_compiler(&mut comp))
+        .map_err(|e| env.error(format!("Evaluation failed: {e}")))?;
+    let outputs: EcoVec<Boxed> = (inner.take_stack().into_iter().rev()).map(Boxed).collect();
+    env.push(Array::from(outputs));
+    Ok(())
+}
+
+struct AstBuilder<'a> {
+    code: &'a str,
+    env: &'a Uiua,
+}
+
+impl AstBuilder<'_> {
+    fn node(
+        &self,
+        kind: &str,
+        start: usize,
+        end: usize,
+        code: String,
+        children: Vec<Value>,
+    ) -> UiuaResult<Value> {
+        let keys: Value = NODE_KEYS.iter().map(|&key| Boxed(key.into())).collect();
+        let children: EcoVec<Boxed> = children.into_iter().map(Boxed).collect();
+        let span = Value::from([start as f64, end as f64]);
+        let mut node: Value = Array::from(EcoVec::from([
+            Boxed(kind.into()),
+            Boxed(span),
+            Boxed(code.into()),
+            Boxed(Array::from(children).into()),
+        ]))
+        .into();
+        node.map(keys, self.env)?;
+        Ok(node)
+    }
+    fn span_node(&self, kind: &str, span: &CodeSpan, children: Vec<Value>) -> UiuaResult<Value> {
+        let code = self.code[span.byte_range()].to_string();
+        let (start, end) = (span.start.char_pos as usize, span.end.char_pos as usize);
+        self.node(kind, start, end, code, children)
+    }
+    fn item(&self, item: &Item, nodes: &mut Vec<Value>) -> UiuaResult {
+        match item {
+            Item::Words(lines) => {
+                for line in lines {
+                    let mut words = line.iter().filter(|w| w.value.is_code());
+                    let Some(first) = words.next() else {
+                        continue;
+                    };
+                    let span = (words.next_back()).map_or(first.span.clone(), |last| {
+                        first.span.clone().merge(last.span.clone())
+                    });
+                    let children = self.words(line)?;
+                    nodes.push(self.span_node("line", &span, children)?);
+                }
+            }
+            Item::Binding(binding) => {
+                let children = self.words(&binding.words)?;
+                nodes.push(self.span_node("binding", &binding.span(), children)?);
+            }
+            Item::Import(import) => {
+                nodes.push(self.span_node("import", &import.span(), Vec::new())?)
+            }
+            Item::Module(module) => {
+                nodes.push(self.span_node("module", &module.span, Vec::new())?)
+            }
+            Item::Data(data) => nodes.push(self.span_node("data", &data.span(), Vec::new())?),
+        }
+        Ok(())
+    }
+    fn words<'w>(&self, words: impl IntoIterator<Item = &'w Sp<Word>>) -> UiuaResult<Vec<Value>> {
+        (words.into_iter())
+            .filter(|word| word.value.is_code())
+            .map(|word| self.word(word))
+            .collect()
+    }
+    fn word(&self, word: &Sp<Word>) -> UiuaResult<Value> {
+        let (kind, children) = match &word.value {
+            Word::Number(_) => ("number", Vec::new()),
+            Word::Char(_) => ("char", Vec::new()),
+            Word::String(_) => ("string", Vec::new()),
+            Word::MultilineString(_) => ("multiline string", Vec::new()),
+            Word::FormatString(_) => ("format string", Vec::new()),
+            Word::MultilineFormatString(_) => ("multiline format string", Vec::new()),
+            Word::Label(_) => ("label", Vec::new()),
+            Word::Ref(_) | Word::IncompleteRef { .. } => ("ref", Vec::new()),
+            Word::Strand(items) => ("strand", self.words(items)?),
+            Word::Array(arr) => {
+                let kind = if arr.boxes { "box array" } else { "array" };
+                (kind, self.words(arr.lines.iter().flatten())?)
+            }
+            Word::Func(func) => ("func", self.words(func.lines.iter().flatten())?),
+            Word::Pack(pack) => {
+                let branches = (pack.branches.iter())
+                    .map(|branch| {
+                        let children = self.words(branch.value.lines.iter().flatten())?;
+                        self.span_node("branch", &branch.span, children)
+                    })
+                    .collect::<UiuaResult<_>>()?;
+                ("pack", branches)
+            }
+            Word::Primitive(_) => ("primitive", Vec::new()),
+            Word::Modified(modified) => {
+                let kind = match &modified.modifier.value {
+                    Modifier::Primitive(_) => "primitive",
+                    Modifier::Ref(_) => "ref",
+                    Modifier::Macro(_) => "inline macro",
+                };
+                let mut children =
+                    vec![self.span_node(kind, &modified.modifier.span, Vec::new())?];
+                children.extend(self.words(modified.code_operands())?);
+                ("modified", children)
+            }
+            Word::Placeholder(_) | Word::NamedPlaceholder(_) => ("placeholder", Vec::new()),
+            Word::SemanticComment(_) => ("semantic comment", Vec::new()),
+            Word::Subscripted(sub) => ("subscripted", vec![self.word(&sub.word)?]),
+            Word::InlineMacro(mac) => (
+                "inline macro",
+                self.words(mac.func.value.lines.iter().flatten())?,
+            ),
+            Word::Comment(_)
+            | Word::Spaces
+            | Word::BreakLine
+            | Word::FlipLine
+            | Word::OutputComment { .. } => ("comment", Vec::new()),
+        };
+        self.span_node(kind, &word.span, children)
+    }
+}
+
+/// Get the code of an AST node
+///
+/// The code of a node with children is its own code with the
+/// spans of its children replaced by the children's code.
+fn node_code(node: &Value, env: &Uiua) -> UiuaResult<String> {
+    let field = |node: &Value, key: &str| -> UiuaResult<Value> {
+        (node.get(&key.into(), env).map(Value::unboxed))
+            .map_err(|_| env.error(format!("AST node must be a map with a `{key}` key")))
+    };
+    let code: Vec<char> = field(node, "code")?
+        .as_string(env, "AST node code must be a string")?
+        .chars()
+        .collect();
+    let children = field(node, "children")?;
+    if children.row_count() == 0 {
+        return Ok(code.into_iter().collect());
+    }
+    let span = |node: &Value| -> UiuaResult<[usize; 2]> {
+        let span = field(node, "span")?;
+        let span = span.as_nats(env, "AST node span must be a list of natural numbers")?;
+        <[usize; 2]>::try_from(span).map_err(|span| {
+            env.error(format!(
+                "AST node span must have 2 numbers, but it has {}",
+                span.len()
+            ))
+        })
+    };
+    let [start, end] = span(node)?;
+    if start > end || end - start != code.len() {
+        return Err(env.error(format!(
+            "AST node span {start}-{end} does not match its code of length {}",
+            code.len()
+        )));
+    }
+    let mut children: Vec<(usize, usize, Value)> = (children.into_rows())
+        .map(|child| {
+            let child = child.unboxed();
+            let [child_start, child_end] = span(&child)?;
+            Ok((child_start, child_end, child))
+        })
+        .collect::<UiuaResult<_>>()?;
+    children.sort_by_key(|(start, ..)| *start);
+    let mut result = String::new();
+    let mut pos = start;
+    for (child_start, child_end, child) in children {
+        if child_start < pos || child_end < child_start || child_end > end {
+            return Err(env.error(format!(
+                "AST node span {child_start}-{child_end} does not fit \
+                in its parent and between its siblings"
+            )));
+        }
+        result.extend(&code[pos - start..child_start - start]);
+        result.push_str(&node_code(&child, env)?);
+        pos = child_end;
+    }
+    result.extend(&code[pos - start..]);
+    Ok(result)
+}
@@ -0,0 +1,382 @@
+//! Drawing shapes and text onto image arrays
+
+use ecow::EcoVec;
+
+use crate::{
+    algorithm::{validate_size, FillContext},
+    encode::layout_text,
+    Array, Boxed, Shape, Uiua, UiuaResult, Value,
+};
+
+type Color = [f64; 4];
+type Point = [f64; 2];
+
+/// An RGBA canvas with straight alpha
+struct Canvas {
+    height: usize,
+    width: usize,
+    data: EcoVec<f64>,
+}
+
+/// Draw shapes onto an image or a new canvas
+pub fn draw(env: &mut Uiua) -> UiuaResult {
+    let shapes = env.pop("shapes")?;
+    let canvas = env.pop("canvas")?;
+    let mut canvas = Canvas::new(canvas, env)?;
+    // A single shape starts with its kind
+    let single = match &shapes {
+        Value::Box(arr) => matches!(arr.data.first(), Some(Boxed(Value::Char(_)))),
+        _ => false,
+    };
+    if single {
+        canvas.draw_shape(&shapes, env)?;
+    } else {
+        for shape in shapes.into_rows().map(Value::unboxed) {
+            canvas.draw_shape(&shape, env)?;
+        }
+    }
+    let shape = Shape::from_iter([canvas.height, canvas.width, 4]);
+    env.push(Array::new(shape, canvas.data));
+    Ok(())
+}
+
+impl Canvas {
+    fn new(val: Value, env: &Uiua) -> UiuaResult<Self> {
+        let arr = val.as_number_array::<f64>(env, "Canvas must be an image or a size")?;
+        let (height, width, data) = match *arr.shape().dims() {
+            [2] => {
+                let [h, w] = [arr.data[0], arr.data[1]];
+                if [h, w]
+                    .iter()
+                    .any(|n| n.is_nan() || *n < 0.0 || n.fract() != 0.0)
+                {
+                    return Err(env.error(format!(
+                        "Canvas size must be 2 non-negative integers, but it is {h}×{w}"
+                    )));
+                }
+                let (h, w) = (h as usize, w as usize);
+                validate_size::<f64>([h, w, 4], env)?;
+                let background = match env.array_fill::<f64>() {
+                    Ok(fill) => match *fill.shape.dims() {
+                        [] | [1] => [fill.data[0], fill.data[0], fill.data[0], 1.0],
+                        [3] => [fill.data[0], fill.data[1], fill.data[2], 1.0],
+                        [4] => [fill.data[0], fill.data[1], fill.data[2], fill.data[3]],
+                        _ => return Err(env.error("Fill color must be a list of 3 or 4 numbers")),
+                    },
+                    Err(_) => [0.0; 4],
+                };
+                let data = (0..h * w).flat_map(|_| background).collect();
+                (h, w, data)
+            }
+            [h, w] => {
+                let data = arr.data.iter().flat_map(|&v| [v, v, v, 1.0]).collect();
+                (h, w, data)
+            }
+            [h, w, 4] => (h, w, arr.data.into()),
+            [h, w, 3] => {
+                let data = (arr.data.chunks_exact(3))
+                    .flat_map(|px| [px[0], px[1], px[2], 1.0])
+                    .collect();
+                (h, w, data)
+            }
+            _ => {
+                return Err(env.error(format!(
+                    "Canvas must be a size or an image with shape \
+                    [h w], [h w 3], or [h w 4], but its shape is {}",
+                    arr.shape()
+                )))
+            }
+        };
+        Ok(Canvas {
+            height,
+            width,
+            data,
+        })
+    }
+    /// Blend a color into a pixel with the "over" operator
+    fn blend(&mut self, y: usize, x: usize, color: Color, coverage: f64) {
+        let a = color[3] * coverage;
+        if a <= 0.0 {
+            return;
+        }
+        let width = self.width;
+        let px = &mut self.data.make_mut()[(y * width + x) * 4..][..4];
+        let da = px[3] * (1.0 - a);
+        let out_a = a + da;
+        for c in 0..3 {
+            px[c] = if out_a > 0.0 {
+                (color[c] * a + px[c] * da) / out_a
+            } else {
+                0.0
+            };
+        }
+        px[3] = out_a;
+    }
+    /// Draw an antialiased region given a bounding box and the signed distance to its edge
+    fn fill(
+        &mut self,
+        [top, left, bottom, right]: [f64; 4],
+        color: Color,
+        dist: impl Fn(Point) -> f64,
+    ) {
+        let rows =
+            top.floor().max(0.0) as usize..(bottom.ceil().max(0.0) as usize).min(self.height);
+        let cols = left.floor().max(0.0) as usize..(right.ceil().max(0.0) as usize).min(self.width);
+        for y in rows {
+            for x in cols.clone() {
+                let d = dist([y as f64 + 0.5, x as f64 + 0.5]);
+                self.blend(y, x, color, (0.5 - d).clamp(0.0, 1.0));
+            }
+        }
+    }
+    fn draw_shape(&mut self, shape: &Value, env: &mut Uiua) -> UiuaResult {
+        let Value::Box(arr) = shape else {
+            return Err(env.error(format!(
+                "Shape must be a list of boxes starting with its kind, \
+                but it is {}",
+                shape.type_name_plural()
+            )));
+        };
+        let mut items = arr.data.iter().map(|Boxed(val)| val);
+        let kind = match items.next() {
+            Some(val) => val.as_string(env, "Shape kind must be a string")?,
+            None => return Err(env.error("Shape cannot be empty")),
+        };
+        let Some(geometry) = items.next() else {
+            return Err(env.error(format!("{kind:?} shape is missing its coordinates")));
+        };
+        let geometry = geometry.as_number_array::<f64>(env, "Shape coordinates must be numbers")?;
+        if geometry.data.iter().any(|n| !n.is_finite()) {
+            return Err(env.error("Shape coordinates must be finite"));
+        }
+
+        // Parse options
+        let mut color: Option<Color> = None;
+        let mut width: Option<f64> = None;
+        let mut text: Option<String> = None;
+        for val in items {
+            if let Value::Char(_) = val {
+                if text.is_some() {
+                    return Err(env.error("Cannot set shape text twice"));
+                }
+                text = Some(val.as_string(env, "Shape text must be a string")?);
+                continue;
+            }
+            let nums = val.as_nums(env, "Shape options must be numbers or strings")?;
+            match *val.shape().dims() {
+                [] => {
+                    if width.is_some() {
+                        return Err(env.error("Cannot set shape size twice"));
+                    }
+                    if nums[0].is_nan() || nums[0] <= 0.0 {
+                        return Err(env.error(format!(
+                            "Shape size must be positive, but it is {}",
+                            nums[0]
+                        )));
+                    }
+                    width = Some(nums[0]);
+                }
+                [3] | [4] => {
+                    if color.is_some() {
+                        return Err(env.error("Cannot set shape color twice"));
+                    }
+                    color = Some([
+                        nums[0],
+                        nums[1],
+                        nums[2],
+                        nums.get(3).copied().unwrap_or(1.0),
+                    ]);
+                }
+                _ => {
+                    return Err(env.error(format!(
+                        "Shape options must have shape [], [3], or [4], \
+                        but one has shape {}",
+                        val.shape()
+                    )))
+                }
+            }
+        }
+        let color = color.unwrap_or([1.0; 4]);
+        if text.is_some() && kind != "text" {
+            return Err(env.error(format!("{kind:?} shape cannot have text")));
+        }
+
+        let expect_shape = |expected: &[usize], desc: &str| {
+            if geometry.shape().dims() == expected {
+                Ok(())
+            } else {
+                Err(env.error(format!(
+                    "{kind:?} coordinates must be {desc}, but their shape is {}",
+                    geometry.shape()
+                )))
+            }
+        };
+        let points = |min: usize| {
+            match *geometry.shape().dims() {
+                [n, 2] if n >= min => {}
+                _ => {
+                    return Err(env.error(format!(
+                        "{kind:?} coordinates must be a list of at least {min} [y x] points, \
+                        but their shape is {}",
+                        geometry.shape()
+                    )))
+                }
+            }
+            Ok(geometry
+                .data
+                .chunks_exact(2)
+                .map(|p| [p[0], p[1]])
+                .collect::<Vec<Point>>())
+        };
+        match kind.as_str() {
+            "line" => {
+                let points = points(1)?;
+                let half = width.unwrap_or(1.0) / 2.0;
+                self.fill(bounds(&points, half), color, |p| {
+                    outline_dist(p, &points, false) - half
+                });
+            }
+            "polygon" => {
+                let points = points(1)?;
+                self.polygon(&points, width, color);
+            }
+            "rect" => {
+                expect_shape(&[4], "[y x h w]")?;
+                let [y, x, h, w] = [0, 1, 2, 3].map(|i| geometry.data[i]);
+                self.polygon(
+                    &[[y, x], [y, x + w], [y + h, x + w], [y + h, x]],
+                    width,
+                    color,
+                );
+            }
+            "circle" => {
+                expect_shape(&[3], "[y x r]")?;
+                let [y, x, r] = [0, 1, 2].map(|i| geometry.data[i]);
+                let center = [y, x];
+                let half = width.map_or(0.0, |w| w / 2.0);
+                let bounds = bounds(&[center], r.abs() + half);
+                self.fill(bounds, color, |p| {
+                    let d = dist(p, center) - r.abs();
+                    if width.is_some() {
+                        d.abs() - half
+                    } else {
+                        d
+                    }
+                });
+            }
+            "text" => {
+                expect_shape(&[2], "[y x]")?;
+                let Some(text) = text else {
+                    return Err(env.error("\"text\" shape is missing its text"));
+                };
+                let size = width.unwrap_or(30.0);
+                let mask = env.without_fill(|env| layout_text(size.into(), text.into(), env))?;
+                let mask = mask.as_number_array::<f64>(env, "Text mask must be numbers")?;
+                let [h, w] = match *mask.shape().dims() {
+                    [h, w] => [h, w],
+                    _ => return Ok(()),
+                };
+                let [top, left] = [geometry.data[0].round(), geometry.data[1].round()];
+                for (i, row) in mask.data.chunks_exact(w.max(1)).take(h).enumerate() {
+                    let y = top + i as f64;
+                    if y < 0.0 || y >= self.height as f64 {
+                        continue;
+                    }
+                    for (j, &coverage) in row.iter().enumerate() {
+                        let x = left + j as f64;
+                        if x < 0.0 || x >= self.width as f64 {
+                            continue;
+                        }
+                        self.blend(y as usize, x as usize, color, coverage);
+                    }
+                }
+            }
+            _ => {
+                return Err(env.error(format!(
+                    "Unknown shape kind {kind:?}. Valid kinds are \
+                    \"line\", \"polygon\", \"rect\", \"circle\", and \"text\"."
+                )))
+            }
+        }
+        Ok(())
+    }
+    /// Fill a polygon, or outline it if there is a stroke width
+    fn polygon(&mut self, points: &[Point], width: Option<f64>, color: Color) {
+        if let Some(width) = width {
+            let half = width / 2.0;
+            self.fill(bounds(points, half), color, |p| {
+                outline_dist(p, points, true) - half
+            });
+        } else {
+            self.fill(bounds(points, 0.0), color, |p| {
+                let d = outline_dist(p, points, true);
+                if contains(p, points) {
+                    -d
+                } else {
+                    d
+                }
+            });
+        }
+    }
+}
+
+/// The bounding box of some points with a margin, as top, left, bottom, right
+fn bounds(points: &[Point], margin: f64) -> [f64; 4] {
+    let margin = margin + 1.0;
+    let mut bounds = [
+        f64::INFINITY,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NEG_INFINITY,
+    ];
+    for &[y, x] in points {
+        bounds[0] = bounds[0].min(y - margin);
+        bounds[1] = bounds[1].min(x - margin);
+        bounds[2] = bounds[2].max(y + margin);
+        bounds[3] = bounds[3].max(x + margin);
+    }
+    bounds
+}
+
+fn dist(a: Point, b: Point) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// The distance from a point to the nearest point on a line segment
+fn segment_dist(p: Point, a: Point, b: Point) -> f64 {
+    let d = [b[0] - a[0], b[1] - a[1]];
+    let len2 = d[0] * d[0] + d[1] * d[1];
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * d[0] + (p[1] - a[1]) * d[1]) / len2).clamp(0.0, 1.0)
+    };
+    dist(p, [a[0] + t * d[0], a[1] + t * d[1]])
+}
+
+/// The distance from a point to the nearest point on a path
+fn outline_dist(p: Point, points: &[Point], closed: bool) -> f64 {
+    if points.len() == 1 {
+        return dist(p, points[0]);
+    }
+    let closing = closed.then(|| (points[points.len() - 1], points[0]));
+    (points.windows(2).map(|w| (w[0], w[1])))
+        .chain(closing)
+        .map(|(a, b)| segment_dist(p, a, b))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Whether a point is inside a polygon, using the even-odd rule
+fn contains(p: Point, points: &[Point]) -> bool {
+    let mut inside = false;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if (a[0] > p[0]) != (b[0] > p[0]) {
+            let x = a[1] + (p[0] - a[0]) / (b[0] - a[0]) * (b[1] - a[1]);
+            if p[1] < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
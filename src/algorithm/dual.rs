@@ -0,0 +1,117 @@
+//! Dual number arithmetic for forward-mode differentiation
+//!
+//! A dual number is an array whose first row is a value and whose second row is its derivative.
+
+use std::f64::consts::E;
+
+use crate::{Uiua, UiuaResult, Value};
+
+fn split(dual: Value, env: &Uiua) -> UiuaResult<(Value, Value)> {
+    if dual.row_count() != 2 || dual.rank() == 0 {
+        return Err(env.error(format!(
+            "Dual number must have 2 rows, but its shape is {}. \
+            This is a bug in the interpreter.",
+            dual.shape()
+        )));
+    }
+    Ok((dual.row(0), dual.row(1)))
+}
+
+fn join(value: Value, deriv: Value, env: &Uiua) -> UiuaResult<Value> {
+    value.couple(deriv, true, env)
+}
+
+fn is_zero(val: &Value) -> bool {
+    match val {
+        Value::Num(arr) => arr.data.iter().all(|&x| x == 0.0),
+        Value::Byte(arr) => arr.data.iter().all(|&x| x == 0),
+        _ => false,
+    }
+}
+
+fn ln(val: Value, env: &Uiua) -> UiuaResult<Value> {
+    Value::from(E).log(val, env)
+}
+
+/// `b × a`
+pub fn mul(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let (b0, b1) = split(b, env)?;
+    let value = a0.clone().mul(b0.clone(), env)?;
+    let deriv = a0.mul(b1, env)?.add(a1.mul(b0, env)?, env)?;
+    join(value, deriv, env)
+}
+
+/// `b ÷ a`
+pub fn div(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let (b0, b1) = split(b, env)?;
+    let value = a0.clone().div(b0, env)?;
+    // (b' - a'b/a) / a
+    let deriv = a0.div(a1.mul(value.clone(), env)?.sub(b1, env)?, env)?;
+    join(value, deriv, env)
+}
+
+/// `b ^ a`
+pub fn pow(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let (b0, b1) = split(b, env)?;
+    let value = a0.clone().pow(b0.clone(), env)?;
+    // a·b^(a-1)·b'
+    let lower = Value::from(1).sub(a0.clone(), env)?.pow(b0.clone(), env)?;
+    let mut deriv = a0.mul(lower, env)?.mul(b1, env)?;
+    // The logarithm term is skipped for constant exponents so that negative bases work
+    if !is_zero(&a1) {
+        let log_term = value.clone().mul(ln(b0, env)?, env)?.mul(a1, env)?;
+        deriv = deriv.add(log_term, env)?;
+    }
+    join(value, deriv, env)
+}
+
+/// Logarithm of `b` with base `a`
+pub fn log(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let (b0, b1) = split(b, env)?;
+    let value = a0.clone().log(b0.clone(), env)?;
+    let ln_a = ln(a0.clone(), env)?;
+    // b' / (b·ln(a))
+    let mut deriv = b0.mul(ln_a.clone(), env)?.div(b1, env)?;
+    if !is_zero(&a1) {
+        // a'·log_a(b) / (a·ln(a))
+        let base_term = (a0.mul(ln_a, env)?).div(a1.mul(value.clone(), env)?, env)?;
+        deriv = base_term.sub(deriv, env)?;
+    }
+    join(value, deriv, env)
+}
+
+/// Square root
+pub fn sqrt(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let value = a0.sqrt(env)?;
+    let deriv = (Value::from(2).mul(value.clone(), env)?).div(a1, env)?;
+    join(value, deriv, env)
+}
+
+/// Sine
+pub fn sin(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let value = a0.clone().sin(env)?;
+    let deriv = a0.cos(env)?.mul(a1, env)?;
+    join(value, deriv, env)
+}
+
+/// Cosine
+pub fn cos(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let value = a0.clone().cos(env)?;
+    let deriv = a0.sin(env)?.mul(a1, env)?.neg(env)?;
+    join(value, deriv, env)
+}
+
+/// Absolute value
+pub fn abs(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a0, a1) = split(a, env)?;
+    let value = a0.clone().abs(env)?;
+    let deriv = a0.sign(env)?.mul(a1, env)?;
+    join(value, deriv, env)
+}
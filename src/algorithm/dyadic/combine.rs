@@ -753,11 +753,15 @@ impl<T: ArrayValue> Array<T> {
                 }
                 Err(e) => {
                     let err = || {
-                        Err(C::fill_error(ctx.error(format!(
-                            "Cannot couple arrays with shapes {} and {}{e}",
-                            self.shape(),
-                            other.shape()
-                        ))))
+                        Err(C::fill_error(ctx.shape_error(
+                            format!(
+                                "Cannot couple arrays with shapes {} and {}{e}",
+                                self.shape(),
+                                other.shape()
+                            ),
+                            &self.shape,
+                            &other.shape,
+                        )))
                     };
                     if allow_ext {
                         if self.shape.ends_with(&other.shape) {
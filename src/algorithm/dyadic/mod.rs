@@ -1072,6 +1072,36 @@ impl Value {
     }
 }
 
+impl Value {
+    /// Get the dot product of two lists of numbers
+    ///
+    /// Returns `None` if the fast path does not apply, in which case the
+    /// caller should fall back to [`Primitive::Mul`] and [`Primitive::Reduce`]
+    pub(crate) fn dot_product(&self, other: &Self, env: &Uiua) -> Option<f64> {
+        fn dot<A: Copy + Into<f64>, B: Copy + Into<f64>>(a: &[A], b: &[B]) -> f64 {
+            (a.iter().zip(b))
+                .map(|(&a, &b)| a.into() * b.into())
+                .reduce(|acc, x| acc + x)
+                .unwrap_or(0.0)
+        }
+        if self.rank() != 1
+            || other.rank() != 1
+            || self.row_count() != other.row_count()
+            || env.scalar_fill::<f64>().is_ok()
+            || env.value_fill().is_some()
+        {
+            return None;
+        }
+        Some(match (self, other) {
+            (Value::Num(a), Value::Num(b)) => dot(&a.data, &b.data),
+            (Value::Num(a), Value::Byte(b)) => dot(&a.data, &b.data),
+            (Value::Byte(a), Value::Num(b)) => dot(&a.data, &b.data),
+            (Value::Byte(a), Value::Byte(b)) => dot(&a.data, &b.data),
+            _ => return None,
+        })
+    }
+}
+
 impl Array<f64> {
     pub(crate) fn matrix_mul(&self, other: &Self, env: &Uiua) -> UiuaResult<Self> {
         let (a, b) = (self, other);
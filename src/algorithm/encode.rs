@@ -8,7 +8,7 @@ use serde::*;
 
 use crate::SysBackend;
 #[allow(unused_imports)]
-use crate::{Array, Uiua, UiuaResult, Value};
+use crate::{algorithm::validate_size, Array, Uiua, UiuaErrorKind, UiuaResult, Value};
 
 /// Conversion of a value to some media format based on the value's shape
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -326,6 +326,178 @@ pub fn value_to_image(value: &Value) -> Result<DynamicImage, String> {
     })
 }
 
+/// The default minimum structural similarity for [`Primitive::AssertImage`](crate::Primitive::AssertImage)
+const DEFAULT_MIN_SSIM: f64 = 0.95;
+
+/// Image pixel data with its height, width, and channel count
+struct Pixels {
+    dims: [usize; 3],
+    data: Vec<f64>,
+}
+
+impl Pixels {
+    fn new(value: &Value, name: &str, env: &Uiua) -> UiuaResult<Self> {
+        let dims = match *value.shape().dims() {
+            [h, w] => [h, w, 1],
+            [h, w, c] if (1..=4).contains(&c) => [h, w, c],
+            _ => {
+                return Err(env.error(format!(
+                    "{name} image must be a rank 2 or 3 array with 1 to 4 channels, \
+                    but its shape is {}",
+                    value.shape()
+                )))
+            }
+        };
+        let data = match value {
+            Value::Num(nums) => nums.data.to_vec(),
+            Value::Byte(bytes) => bytes.data.iter().map(|&b| (b > 0) as u8 as f64).collect(),
+            _ => {
+                return Err(env.error(format!(
+                    "{name} image must be a numeric array, but it is {}",
+                    value.type_name_plural()
+                )))
+            }
+        };
+        Ok(Pixels { dims, data })
+    }
+    /// Get the perceived brightness of each pixel
+    fn luma(&self) -> Vec<f64> {
+        let c = self.dims[2];
+        (self.data.chunks_exact(c))
+            .map(|px| match px {
+                [r, g, b, ..] => 0.299 * r + 0.587 * g + 0.114 * b,
+                [l, ..] => *l,
+                [] => 0.0,
+            })
+            .collect()
+    }
+}
+
+/// Compute the structural similarity of each window of two grayscale images
+///
+/// Returns the lowest similarity and the top-left corner of its window
+fn min_ssim(a: &[f64], b: &[f64], height: usize, width: usize) -> (f64, [usize; 2]) {
+    const WINDOW: usize = 8;
+    const STRIDE: usize = 4;
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+    let starts = |len: usize| {
+        let last = len.saturating_sub(WINDOW);
+        (0..=last)
+            .step_by(STRIDE)
+            .chain((last % STRIDE != 0).then_some(last))
+    };
+    let (wh, ww) = (height.min(WINDOW), width.min(WINDOW));
+    let n = (wh * ww) as f64;
+    let mut min = (1.0, [0, 0]);
+    for r in starts(height) {
+        for c in starts(width) {
+            let window = || (r..r + wh).flat_map(|i| (c..c + ww).map(move |j| i * width + j));
+            let mean_a = window().map(|i| a[i]).sum::<f64>() / n;
+            let mean_b = window().map(|i| b[i]).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut cov) = (0.0, 0.0, 0.0);
+            for (x, y) in window().map(|i| (a[i], b[i])) {
+                var_a += (x - mean_a) * (x - mean_a);
+                var_b += (y - mean_b) * (y - mean_b);
+                cov += (x - mean_a) * (y - mean_b);
+            }
+            let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+            let ssim = ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            if ssim < min.0 {
+                min = (ssim, [r, c]);
+            }
+        }
+    }
+    min
+}
+
+/// Assert that two images are perceptually the same
+///
+/// The tolerance is either a per-channel epsilon or a pair of an epsilon and a minimum structural similarity.
+/// The images match if every channel is within the epsilon,
+/// or if every window of the images is at least as structurally similar as the minimum.
+pub(crate) fn assert_image(
+    tolerance: Value,
+    expected: Value,
+    actual: Value,
+    env: &Uiua,
+) -> UiuaResult {
+    let nums = tolerance.as_nums(
+        env,
+        "Tolerance must be an epsilon or a list of an epsilon and a minimum similarity",
+    )?;
+    let (epsilon, min_similarity) = match *nums {
+        [eps] => (eps, DEFAULT_MIN_SSIM),
+        [eps, sim] => (eps, sim),
+        _ => {
+            return Err(env.error(format!(
+                "Tolerance must be 1 or 2 numbers, but it is {}",
+                nums.len()
+            )))
+        }
+    };
+    let expected = Pixels::new(&expected, "Expected", env)?;
+    let actual = Pixels::new(&actual, "Actual", env)?;
+    let fail = |message: String| -> UiuaResult {
+        Err(UiuaErrorKind::Throw(
+            Box::new(message.into()),
+            env.span().clone(),
+            env.inputs().clone().into(),
+        )
+        .into())
+    };
+    if expected.dims != actual.dims {
+        let [eh, ew, ec] = expected.dims;
+        let [ah, aw, ac] = actual.dims;
+        return fail(format!(
+            "Images have different sizes: expected is {eh}×{ew} with {ec} channel(s), \
+            but actual is {ah}×{aw} with {ac} channel(s)"
+        ));
+    }
+    let [height, width, channels] = expected.dims;
+    // Find the pixels that differ by more than the epsilon
+    let mut differing = 0;
+    let mut max_diff = 0f64;
+    let (mut top, mut left, mut bottom, mut right) = (height, width, 0, 0);
+    let pixels = (expected.data.chunks_exact(channels)).zip(actual.data.chunks_exact(channels));
+    for (i, (e, a)) in pixels.enumerate() {
+        let diff = (e.iter().zip(a)).fold(0f64, |acc, (e, a)| acc.max((e - a).abs()));
+        max_diff = max_diff.max(diff);
+        if diff > epsilon || diff.is_nan() {
+            let (r, c) = (i / width, i % width);
+            differing += 1;
+            top = top.min(r);
+            left = left.min(c);
+            bottom = bottom.max(r);
+            right = right.max(c);
+        }
+    }
+    if differing == 0 {
+        return Ok(());
+    }
+    let (similarity, [sr, sc]) = min_ssim(&expected.luma(), &actual.luma(), height, width);
+    if similarity >= min_similarity {
+        return Ok(());
+    }
+    let percent = differing as f64 / (height * width) as f64 * 100.0;
+    let range = |name: &str, start: usize, end: usize| {
+        if start == end {
+            format!("{name} {start}")
+        } else {
+            format!("{name}s {start}-{end}")
+        }
+    };
+    fail(format!(
+        "Images differ in {differing} pixel(s) ({percent:.2}%) in {} and {}\n\
+        Largest channel difference is {max_diff:.4}, but the epsilon is {epsilon}\n\
+        Structural similarity is {similarity:.4} in the window at row {sr} and column {sc}, \
+        but the minimum is {min_similarity}",
+        range("row", top, bottom),
+        range("column", left, right),
+    ))
+}
+
 #[doc(hidden)]
 pub fn value_to_audio_channels(audio: &Value) -> Result<Vec<Vec<f64>>, String> {
     let orig = audio;
@@ -682,11 +854,7 @@ fn layout_text_impl(options: Value, text: Value, env: &Uiua) -> UiuaResult<Value
     use cosmic_text::*;
     use ecow::eco_vec;
 
-    use crate::{
-        algorithm::{validate_size, FillContext},
-        grid_fmt::GridFmt,
-        Boxed, Shape,
-    };
+    use crate::{algorithm::FillContext, grid_fmt::GridFmt, Boxed, Shape};
     struct FontStuff {
         system: FontSystem,
         swash_cache: SwashCache,
@@ -946,3 +1114,301 @@ fn layout_text_impl(options: Value, text: Value, env: &Uiua) -> UiuaResult<Value
         Ok(Array::new(canvas_shape, canvas_data).into())
     })
 }
+
+/// A single field in a [`pack`] layout
+#[derive(Debug, Clone, Copy)]
+struct PackField {
+    code: char,
+    /// The explicit count, if one was given
+    count: Option<usize>,
+    /// The number of bytes the field takes up
+    byte_len: usize,
+}
+
+impl PackField {
+    fn width(code: char) -> usize {
+        match code {
+            'x' | '?' | 'b' | 'B' | 's' => 1,
+            'h' | 'H' => 2,
+            'i' | 'I' | 'f' => 4,
+            'q' | 'Q' | 'd' => 8,
+            _ => unreachable!(),
+        }
+    }
+    fn is_value(&self) -> bool {
+        self.code != 'x'
+    }
+}
+
+/// A parsed [`pack`] layout
+struct PackLayout {
+    big_endian: bool,
+    fields: Vec<PackField>,
+    /// The total number of bytes
+    byte_len: usize,
+}
+
+impl PackLayout {
+    fn parse(layout: &str, env: &Uiua) -> UiuaResult<Self> {
+        let mut chars = layout.chars().filter(|c| !c.is_whitespace()).peekable();
+        let big_endian = match chars.peek() {
+            Some('<') => {
+                chars.next();
+                false
+            }
+            Some('>' | '!') => {
+                chars.next();
+                true
+            }
+            Some('=') => {
+                chars.next();
+                cfg!(target_endian = "big")
+            }
+            _ => false,
+        };
+        let mut fields = Vec::new();
+        while let Some(c) = chars.next() {
+            let mut count = None;
+            let mut code = c;
+            if c.is_ascii_digit() {
+                let mut n = c.to_digit(10).unwrap() as usize;
+                loop {
+                    match chars.next() {
+                        Some(d) if d.is_ascii_digit() => {
+                            n = n
+                                .checked_mul(10)
+                                .and_then(|n| n.checked_add(d.to_digit(10).unwrap() as usize))
+                                .ok_or_else(|| env.error("Pack layout count is too large"))?;
+                        }
+                        Some(c) => {
+                            code = c;
+                            break;
+                        }
+                        None => {
+                            return Err(env.error(format!(
+                                "Pack layout count {n} is not followed by a field code"
+                            )))
+                        }
+                    }
+                }
+                count = Some(n);
+            }
+            if !"x?bBhHiIqQfds".contains(code) {
+                return Err(env.error(format!("Invalid pack layout code {code:?}")));
+            }
+            let byte_len = (PackField::width(code).checked_mul(count.unwrap_or(1)))
+                .ok_or_else(|| env.error("Pack layout is too large"))?;
+            fields.push(PackField {
+                code,
+                count,
+                byte_len,
+            });
+        }
+        let byte_len = (fields.iter())
+            .try_fold(0usize, |sum, field| sum.checked_add(field.byte_len))
+            .ok_or_else(|| env.error("Pack layout is too large"))?;
+        validate_size::<u8>([byte_len], env)?;
+        Ok(PackLayout {
+            big_endian,
+            fields,
+            byte_len,
+        })
+    }
+}
+
+/// Pack values into bytes according to a layout string
+pub(crate) fn pack(layout: &Value, values: &Value, env: &Uiua) -> UiuaResult<Value> {
+    let layout = layout.as_string(env, "Pack layout must be a string")?;
+    let layout = PackLayout::parse(&layout, env)?;
+    let values: Vec<Value> = if values.rank() == 0 {
+        vec![values.clone().unboxed()]
+    } else {
+        values.rows().map(Value::unboxed).collect()
+    };
+    let field_count = layout.fields.iter().filter(|f| f.is_value()).count();
+    if values.len() != field_count {
+        return Err(env.error(format!(
+            "Pack layout has {field_count} field{}, but {} value{} given",
+            if field_count == 1 { "" } else { "s" },
+            values.len(),
+            if values.len() == 1 { " was" } else { "s were" }
+        )));
+    }
+    let mut bytes = Vec::with_capacity(layout.byte_len);
+    let mut values = values.into_iter();
+    for field in &layout.fields {
+        if !field.is_value() {
+            bytes.extend(std::iter::repeat(0).take(field.byte_len));
+            continue;
+        }
+        let value = values.next().unwrap();
+        if field.code == 's' {
+            let len = field.count.unwrap_or(1);
+            let data = if let Value::Char(_) = &value {
+                let s = value.as_string(env, "Packed string field must be a string")?;
+                s.into_bytes()
+            } else {
+                value.as_bytes(env, "Packed string field must be bytes or a string")?
+            };
+            if data.len() > len {
+                return Err(env.error(format!(
+                    "Packed string field has length {len}, but the value has {} bytes",
+                    data.len()
+                )));
+            }
+            bytes.extend(&data);
+            bytes.extend(std::iter::repeat(0).take(len - data.len()));
+            continue;
+        }
+        let nums = match field.count {
+            None => vec![value.as_num(env, "Packed numeric field must be a number")?],
+            Some(n) => {
+                let nums = value.as_nums(env, "Packed numeric field must be a list of numbers")?;
+                if nums.len() != n {
+                    return Err(env.error(format!(
+                        "Packed field {n}{} expects {n} numbers, but got {}",
+                        field.code,
+                        nums.len()
+                    )));
+                }
+                nums
+            }
+        };
+        for n in nums {
+            pack_num(field.code, n, layout.big_endian, &mut bytes, env)?;
+        }
+    }
+    Ok(Array::<u8>::from(bytes.as_slice()).into())
+}
+
+fn pack_num(code: char, n: f64, big_endian: bool, bytes: &mut Vec<u8>, env: &Uiua) -> UiuaResult {
+    macro_rules! int {
+        ($ty:ty) => {{
+            // MAX rounds up when converted to f64, so compare against the exclusive bound
+            let upper = (<$ty>::MAX as u128 + 1) as f64;
+            if n.fract() != 0.0 || n < <$ty>::MIN as f64 || n >= upper {
+                return Err(env.error(format!(
+                    "{n} cannot be packed as {} ({code:?})",
+                    stringify!($ty)
+                )));
+            }
+            let n = n as $ty;
+            bytes.extend(if big_endian {
+                n.to_be_bytes()
+            } else {
+                n.to_le_bytes()
+            });
+        }};
+    }
+    match code {
+        '?' => {
+            if n != 0.0 && n != 1.0 {
+                return Err(env.error(format!("{n} cannot be packed as a boolean")));
+            }
+            bytes.push(n as u8);
+        }
+        'b' => int!(i8),
+        'B' => int!(u8),
+        'h' => int!(i16),
+        'H' => int!(u16),
+        'i' => int!(i32),
+        'I' => int!(u32),
+        'q' => int!(i64),
+        'Q' => int!(u64),
+        'f' => bytes.extend(if big_endian {
+            (n as f32).to_be_bytes()
+        } else {
+            (n as f32).to_le_bytes()
+        }),
+        'd' => bytes.extend(if big_endian {
+            n.to_be_bytes()
+        } else {
+            n.to_le_bytes()
+        }),
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Unpack bytes into a list of boxed values according to a layout string
+pub(crate) fn unpack(layout: &Value, bytes: &Value, env: &Uiua) -> UiuaResult<Value> {
+    let layout = layout.as_string(env, "Pack layout must be a string")?;
+    let layout = PackLayout::parse(&layout, env)?;
+    let bytes = bytes.as_bytes(env, "Unpacked value must be a list of bytes")?;
+    let expected = layout.byte_len;
+    if bytes.len() != expected {
+        return Err(env.error(format!(
+            "Pack layout describes {expected} bytes, but {} were given",
+            bytes.len()
+        )));
+    }
+    let mut rest = bytes.as_slice();
+    let mut values = Vec::new();
+    for field in &layout.fields {
+        let (field_bytes, tail) = rest.split_at(field.byte_len);
+        rest = tail;
+        match field.code {
+            'x' => {}
+            's' => values.push(Array::<u8>::from(field_bytes).into()),
+            code => {
+                let nums: Vec<f64> = (field_bytes.chunks_exact(PackField::width(code)))
+                    .map(|chunk| unpack_num(code, chunk, layout.big_endian))
+                    .collect();
+                let mut value: Value = if field.count.is_some() {
+                    nums.into_iter().collect()
+                } else {
+                    nums[0].into()
+                };
+                value.compress();
+                values.push(value);
+            }
+        }
+    }
+    Ok(values.into_iter().map(crate::Boxed).collect())
+}
+
+fn unpack_num(code: char, chunk: &[u8], big_endian: bool) -> f64 {
+    macro_rules! num {
+        ($ty:ty) => {{
+            let arr = chunk.try_into().unwrap();
+            (if big_endian {
+                <$ty>::from_be_bytes(arr)
+            } else {
+                <$ty>::from_le_bytes(arr)
+            }) as f64
+        }};
+    }
+    match code {
+        '?' | 'B' => chunk[0] as f64,
+        'b' => chunk[0] as i8 as f64,
+        'h' => num!(i16),
+        'H' => num!(u16),
+        'i' => num!(i32),
+        'I' => num!(u32),
+        'q' => num!(i64),
+        'Q' => num!(u64),
+        'f' => num!(f32),
+        'd' => num!(f64),
+        _ => unreachable!(),
+    }
+}
+
+/// Encode bytes as standard base64 with padding
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(CHARS[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
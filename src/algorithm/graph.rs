@@ -0,0 +1,253 @@
+//! Graph algorithms
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{cowslice::CowSlice, Array, Boxed, Primitive, SigNode, Uiua, UiuaResult, Value};
+
+/// A graph discovered by repeatedly calling a neighbors function
+struct Graph {
+    nodes: Vec<Value>,
+    /// The outgoing edges of each node, along with their costs
+    edges: Vec<Vec<(usize, f64)>>,
+}
+
+impl Graph {
+    /// Pop the starting nodes and any additional arguments, then explore the graph
+    fn discover(prim: Primitive, neighbors: SigNode, env: &mut Uiua) -> UiuaResult<Self> {
+        let start = env.pop("nodes")?;
+        let sig = neighbors.sig;
+        if sig.args == 0 || !(1..=2).contains(&sig.outputs) {
+            return Err(env.error(format!(
+                "{} neighbors function must take at least 1 argument \
+                and return either 1 or 2 outputs, but its signature is {sig}",
+                prim.format()
+            )));
+        }
+        let mut args = Vec::with_capacity(sig.args - 1);
+        for i in 0..sig.args - 1 {
+            args.push(env.pop(i + 1)?);
+        }
+
+        let mut nodes = Vec::new();
+        let mut indices: HashMap<Value, usize> = HashMap::new();
+        for node in start.into_rows() {
+            if !indices.contains_key(&node) {
+                indices.insert(node.clone(), nodes.len());
+                nodes.push(node);
+            }
+        }
+
+        let mut edges = Vec::new();
+        while edges.len() < nodes.len() {
+            env.respect_execution_limit()?;
+            for arg in args.iter().rev() {
+                env.push(arg.clone());
+            }
+            env.push(nodes[edges.len()].clone());
+            env.exec(neighbors.clone())?;
+            let costs = if sig.outputs == 2 {
+                let costs = (env.pop("neighbors costs")?)
+                    .as_nums(env, "Costs must be a list of numbers")?;
+                Some(costs)
+            } else {
+                None
+            };
+            let neis = env.pop("neighbors nodes")?;
+            if let Some(costs) = &costs {
+                if costs.len() != neis.row_count() {
+                    return Err(env.error(format!(
+                        "Number of nodes {} does not match number of costs {}",
+                        neis.row_count(),
+                        costs.len(),
+                    )));
+                }
+            }
+            let mut node_edges = Vec::with_capacity(neis.row_count());
+            for (i, nei) in neis.into_rows().enumerate() {
+                let index = if let Some(&index) = indices.get(&nei) {
+                    index
+                } else {
+                    let index = nodes.len();
+                    indices.insert(nei.clone(), index);
+                    nodes.push(nei);
+                    index
+                };
+                let cost = costs.as_ref().map_or(1.0, |costs| costs[i]);
+                node_edges.push((index, cost));
+            }
+            edges.push(node_edges);
+        }
+        Ok(Graph { nodes, edges })
+    }
+    fn node_list(&self, indices: impl IntoIterator<Item = usize>, env: &Uiua) -> UiuaResult<Value> {
+        let rows: Vec<Value> = (indices.into_iter())
+            .map(|i| self.nodes[i].clone())
+            .collect();
+        Value::from_row_values(rows, env)
+    }
+    fn boxed_groups(&self, groups: Vec<Vec<usize>>, env: &Uiua) -> UiuaResult<Value> {
+        let mut boxes = Vec::with_capacity(groups.len());
+        for group in groups {
+            boxes.push(Boxed(self.node_list(group, env)?));
+        }
+        Ok(boxes.into_iter().collect())
+    }
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Find the connected components of a graph, ignoring edge direction
+pub fn components(neighbors: SigNode, env: &mut Uiua) -> UiuaResult {
+    let graph = Graph::discover(Primitive::Components, neighbors, env)?;
+    let mut parents: Vec<usize> = (0..graph.nodes.len()).collect();
+    for (a, edges) in graph.edges.iter().enumerate() {
+        for &(b, _) in edges {
+            let (ra, rb) = (find_root(&mut parents, a), find_root(&mut parents, b));
+            if ra != rb {
+                parents[ra.max(rb)] = ra.min(rb);
+            }
+        }
+    }
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..graph.nodes.len() {
+        groups
+            .entry(find_root(&mut parents, i))
+            .or_default()
+            .push(i);
+    }
+    let val = graph.boxed_groups(groups.into_values().collect(), env)?;
+    env.push(val);
+    Ok(())
+}
+
+/// Sort the nodes of a directed acyclic graph so that every node comes before its neighbors
+pub fn toposort(neighbors: SigNode, env: &mut Uiua) -> UiuaResult {
+    let graph = Graph::discover(Primitive::Toposort, neighbors, env)?;
+    let mut in_degrees = vec![0usize; graph.nodes.len()];
+    for edges in &graph.edges {
+        for &(b, _) in edges {
+            in_degrees[b] += 1;
+        }
+    }
+    let mut queue: VecDeque<usize> = (in_degrees.iter().enumerate())
+        .filter(|(_, &d)| d == 0)
+        .map(|(i, _)| i)
+        .collect();
+    let mut sorted = Vec::with_capacity(graph.nodes.len());
+    while let Some(a) = queue.pop_front() {
+        sorted.push(a);
+        for &(b, _) in &graph.edges[a] {
+            in_degrees[b] -= 1;
+            if in_degrees[b] == 0 {
+                queue.push_back(b);
+            }
+        }
+    }
+    if sorted.len() < graph.nodes.len() {
+        return Err(env.error(format!(
+            "Cannot {} a graph with a cycle",
+            Primitive::Toposort.format()
+        )));
+    }
+    let val = graph.node_list(sorted, env)?;
+    env.push(val);
+    Ok(())
+}
+
+/// Find the strongly-connected components of a directed graph
+///
+/// This uses an iterative version of Tarjan's algorithm
+pub fn strong_components(neighbors: SigNode, env: &mut Uiua) -> UiuaResult {
+    let graph = Graph::discover(Primitive::StrongComponents, neighbors, env)?;
+    let n = graph.nodes.len();
+    let mut index = vec![usize::MAX; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut groups = Vec::new();
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        while let Some(&(v, edge)) = work.last() {
+            if index[v] == usize::MAX {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+            if let Some(&(w, _)) = graph.edges[v].get(edge) {
+                work.last_mut().unwrap().1 += 1;
+                if index[w] == usize::MAX {
+                    work.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+            work.pop();
+            if let Some(&(u, _)) = work.last() {
+                lowlink[u] = lowlink[u].min(lowlink[v]);
+            }
+            if lowlink[v] == index[v] {
+                let mut group = Vec::new();
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    group.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                group.sort_unstable();
+                groups.push(group);
+            }
+        }
+    }
+    // Tarjan's algorithm finds components in reverse topological order
+    groups.reverse();
+    let val = graph.boxed_groups(groups, env)?;
+    env.push(val);
+    Ok(())
+}
+
+/// Find a minimum spanning forest of a graph, ignoring edge direction
+///
+/// This uses Kruskal's algorithm
+pub fn spanning_tree(neighbors: SigNode, env: &mut Uiua) -> UiuaResult {
+    let graph = Graph::discover(Primitive::SpanningTree, neighbors, env)?;
+    let mut edges: Vec<(usize, usize, f64)> = (graph.edges.iter().enumerate())
+        .flat_map(|(a, edges)| edges.iter().map(move |&(b, cost)| (a, b, cost)))
+        .filter(|(a, b, _)| a != b)
+        .collect();
+    edges.sort_by(|(.., a), (.., b)| a.total_cmp(b));
+    let mut parents: Vec<usize> = (0..graph.nodes.len()).collect();
+    let mut tree = Vec::new();
+    for (a, b, _) in edges {
+        let (ra, rb) = (find_root(&mut parents, a), find_root(&mut parents, b));
+        if ra != rb {
+            parents[ra.max(rb)] = ra.min(rb);
+            tree.push(graph.node_list([a, b], env)?);
+        }
+    }
+    let val = if tree.is_empty() {
+        let mut shape = (graph.nodes.first())
+            .map(|node| node.shape().clone())
+            .unwrap_or_default();
+        shape.insert(0, 2);
+        shape.insert(0, 0);
+        Array::<f64>::new(shape, CowSlice::new()).into()
+    } else {
+        Value::from_row_values(tree, env)?
+    };
+    env.push(val);
+    Ok(())
+}
@@ -0,0 +1,386 @@
+//! Interval arithmetic
+//!
+//! An interval is an array whose first row is a lower bound and whose second row is an upper bound.
+//! Every operation rounds its bounds outward so that the true result is always contained in the interval.
+
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use ecow::{eco_vec, EcoVec};
+
+use crate::{
+    algorithm::{
+        pervade::{bin_pervade, InfalliblePervasiveFn},
+        walk::{walk, StackWalker, WalkError, Walker},
+    },
+    Array, Assembly, ImplPrimitive, Node, Primitive, SigNode, Uiua, UiuaError, UiuaResult, Value,
+};
+
+/// Lower and upper bounds with the same shape
+#[derive(Clone)]
+struct Bounds {
+    lo: Array<f64>,
+    hi: Array<f64>,
+}
+
+/// Run a function on intervals
+pub fn interval(f: SigNode, env: &mut Uiua) -> UiuaResult {
+    let mut stack = Vec::with_capacity(f.sig.args);
+    for i in 0..f.sig.args {
+        let arr = match env.pop(i + 1)? {
+            Value::Num(arr) => arr,
+            Value::Byte(arr) => arr.convert(),
+            val => {
+                return Err(env.error(format!(
+                    "Cannot use {} on {}",
+                    Primitive::Interval.format(),
+                    val.type_name_plural()
+                )))
+            }
+        };
+        if arr.row_count() != 2 || arr.rank() == 0 {
+            return Err(env.error(format!(
+                "Interval must have 2 rows, but its shape is {}",
+                arr.shape
+            )));
+        }
+        let (lo, hi) = (arr.row(0), arr.row(1));
+        if lo.data.iter().zip(&hi.data).any(|(l, h)| l > h) {
+            return Err(env.error("Interval's lower bound is greater than its upper bound"));
+        }
+        stack.push(Bounds { lo, hi });
+    }
+    stack.reverse();
+    let mut ctx = Ctx {
+        env,
+        stack,
+        depth: 0,
+    };
+    walk(&mut ctx, &f.node)?;
+    for Bounds { lo, hi } in ctx.stack {
+        ctx.env.push(lo.couple_infallible(hi, false));
+    }
+    Ok(())
+}
+
+struct Ctx<'a> {
+    env: &'a mut Uiua,
+    stack: Vec<Bounds>,
+    depth: usize,
+}
+
+fn next_up(x: f64) -> f64 {
+    if x.is_nan() || x == f64::INFINITY {
+        x
+    } else if x == 0.0 {
+        f64::from_bits(1)
+    } else if x > 0.0 {
+        f64::from_bits(x.to_bits() + 1)
+    } else {
+        f64::from_bits(x.to_bits() - 1)
+    }
+}
+
+fn next_down(x: f64) -> f64 {
+    -next_up(-x)
+}
+
+fn round_out((lo, hi): (f64, f64)) -> (f64, f64) {
+    (next_down(lo), next_up(hi))
+}
+
+/// Bound a rounded result given the sign of its rounding error
+fn directed(x: f64, err: f64) -> (f64, f64) {
+    if !x.is_finite() || err == 0.0 {
+        (x, x)
+    } else if err < 0.0 {
+        (next_down(x), x)
+    } else {
+        (x, next_up(x))
+    }
+}
+
+fn add(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    directed(s, (a - (s - bb)) + (b - bb))
+}
+
+fn mul(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    directed(p, a.mul_add(b, -p))
+}
+
+fn div(a: f64, b: f64) -> (f64, f64) {
+    let q = a / b;
+    let r = (-q).mul_add(b, a);
+    directed(q, r * b.signum())
+}
+
+fn sqrt(x: f64) -> (f64, f64) {
+    let s = x.sqrt();
+    directed(s, (-s).mul_add(s, x))
+}
+
+/// The bounds of a function applied to each pair of endpoints, or NaN if any are NaN
+fn corners(f: fn(f64, f64) -> (f64, f64), al: f64, ah: f64, bl: f64, bh: f64) -> (f64, f64) {
+    let cs = [f(bl, al), f(bl, ah), f(bh, al), f(bh, ah)];
+    if cs.iter().any(|(l, h)| l.is_nan() || h.is_nan()) {
+        return (f64::NAN, f64::NAN);
+    }
+    let lo = cs.iter().map(|&(l, _)| l).fold(f64::INFINITY, f64::min);
+    let hi = cs.iter().map(|&(_, h)| h).fold(f64::NEG_INFINITY, f64::max);
+    (lo, hi)
+}
+
+/// Whether `c + 2πk` is in `[l, h]` for some integer `k`
+fn contains_period(l: f64, h: f64, c: f64) -> bool {
+    c + TAU * ((l - c) / TAU).ceil() <= h
+}
+
+/// The bounds of a sinusoid that peaks at `max_at` and is lowest at `max_at + π`
+fn sinusoid(l: f64, h: f64, f: fn(f64) -> f64, max_at: f64) -> (f64, f64) {
+    if h - l >= TAU {
+        return (-1.0, 1.0);
+    }
+    let (fl, fh) = (f(l), f(h));
+    let (lo, hi) = round_out((fl.min(fh), fl.max(fh)));
+    let lo = if contains_period(l, h, max_at + PI) {
+        -1.0
+    } else {
+        lo.max(-1.0)
+    };
+    let hi = if contains_period(l, h, max_at) {
+        1.0
+    } else {
+        hi.min(1.0)
+    };
+    (lo, hi)
+}
+
+fn sign(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x.signum()
+    }
+}
+
+fn abs(l: f64, h: f64) -> (f64, f64) {
+    if l >= 0.0 {
+        (l, h)
+    } else if h <= 0.0 {
+        (-h, -l)
+    } else {
+        (0.0, (-l).max(h))
+    }
+}
+
+// Binary operations take the top interval `a` and the interval below it `b`,
+// matching the argument order of the pervasive functions.
+
+/// `b ÷ a`
+fn div_interval(al: f64, ah: f64, bl: f64, bh: f64) -> (f64, f64) {
+    if al > 0.0 || ah < 0.0 {
+        corners(div, al, ah, bl, bh)
+    } else {
+        (f64::NEG_INFINITY, f64::INFINITY)
+    }
+}
+
+/// `b ^ a`
+fn pow_interval(al: f64, ah: f64, bl: f64, bh: f64) -> (f64, f64) {
+    let (lo, hi) = corners(|b, a| round_out((b.powf(a), b.powf(a))), al, ah, bl, bh);
+    if !(bl < 0.0 && bh > 0.0 && al == ah && al.fract() == 0.0) {
+        return (lo, hi);
+    }
+    // A base that spans 0 has its extreme values inside the interval
+    match (al > 0.0, al % 2.0 == 0.0) {
+        (true, true) => (0.0, hi),
+        (true, false) => (lo, hi),
+        (false, true) => (lo.max(0.0), f64::INFINITY),
+        (false, false) => (f64::NEG_INFINITY, f64::INFINITY),
+    }
+}
+
+impl Walker for Ctx<'_> {
+    type Error = UiuaError;
+    fn asm(&self) -> &Assembly {
+        &self.env.asm
+    }
+    fn depth(&mut self) -> &mut usize {
+        &mut self.depth
+    }
+    fn error(&self, error: WalkError) -> UiuaError {
+        match error {
+            WalkError::TooDeep => self.env.error(format!(
+                "{}'s function is too deeply nested",
+                Primitive::Interval.format()
+            )),
+            WalkError::NotSupported(what) => self.not_supported(what),
+            WalkError::NoInverse(e) => self.env.error(e),
+        }
+    }
+    fn leaf(&mut self, node: &Node) -> UiuaResult {
+        use Primitive::*;
+        match node {
+            Node::Push(val) => self.push_const(val)?,
+            Node::Prim(prim, _) => self.prim(*prim)?,
+            Node::ImplPrim(ImplPrimitive::Cos, _) => {
+                self.monadic(|l, h| sinusoid(l, h, f64::cos, 0.0))?
+            }
+            Node::ImplPrim(ImplPrimitive::DotProduct, span) => {
+                let add = SigNode::new((2, 1), Node::Prim(Add, *span));
+                walk(
+                    self,
+                    &Node::from_iter([
+                        Node::Prim(Mul, *span),
+                        Node::Mod(Reduce, eco_vec![add], *span),
+                    ]),
+                )?
+            }
+            Node::ImplPrim(prim, _) => return Err(self.not_supported(prim)),
+            Node::Mod(Reduce, args, _) => {
+                let [f] = args.as_slice() else {
+                    return Err(self.not_supported(Reduce.format()));
+                };
+                let f = match f.node {
+                    Node::Prim(prim @ (Add | Mul | Min | Max), _) => prim,
+                    _ => return Err(self.not_supported(format_args!("this {}", Reduce.format()))),
+                };
+                let Bounds { lo, hi } = self.pop()?;
+                if lo.rank() == 0 {
+                    self.stack.push(Bounds { lo, hi });
+                    return Ok(());
+                }
+                if lo.row_count() == 0 {
+                    let identity = match f {
+                        Add => 0.0,
+                        Mul => 1.0,
+                        Min => f64::INFINITY,
+                        _ => f64::NEG_INFINITY,
+                    };
+                    let shape = lo.shape.row();
+                    let arr = Array::new(shape, eco_vec![identity; lo.shape.row_len()]);
+                    self.stack.push(Bounds {
+                        lo: arr.clone(),
+                        hi: arr,
+                    });
+                    return Ok(());
+                }
+                let mut rows = lo.rows().zip(hi.rows());
+                let (lo, hi) = rows.next().unwrap();
+                self.stack.push(Bounds { lo, hi });
+                for (lo, hi) in rows {
+                    self.stack.push(Bounds { lo, hi });
+                    self.prim(f)?;
+                }
+            }
+            Node::Mod(prim, ..) => return Err(self.not_supported(prim.format())),
+            _ => return Err(self.not_supported("this function")),
+        }
+        Ok(())
+    }
+    fn combinator(&mut self, prim: Primitive, args: &[SigNode], _: usize) -> UiuaResult {
+        self.eval_combinator(prim, args)
+    }
+}
+
+impl StackWalker for Ctx<'_> {
+    type Value = Bounds;
+    fn stack(&mut self) -> &mut Vec<Bounds> {
+        &mut self.stack
+    }
+    fn empty_stack(&self) -> UiuaError {
+        self.env.error("Stack was empty when evaluating interval")
+    }
+}
+
+impl Ctx<'_> {
+    fn not_supported(&self, what: impl std::fmt::Display) -> UiuaError {
+        self.env.error(format!(
+            "{} does not support {what}",
+            Primitive::Interval.format()
+        ))
+    }
+    fn push_const(&mut self, val: &Value) -> UiuaResult {
+        let arr = match val {
+            Value::Num(arr) => arr.clone(),
+            Value::Byte(arr) => arr.convert_ref(),
+            val => return Err(self.not_supported(format_args!("{} constants", val.type_name()))),
+        };
+        self.stack.push(Bounds {
+            lo: arr.clone(),
+            hi: arr,
+        });
+        Ok(())
+    }
+    fn monadic(&mut self, f: impl Fn(f64, f64) -> (f64, f64)) -> UiuaResult {
+        let Bounds { mut lo, mut hi } = self.pop()?;
+        for (l, h) in lo
+            .data
+            .as_mut_slice()
+            .iter_mut()
+            .zip(hi.data.as_mut_slice())
+        {
+            (*l, *h) = f(*l, *h);
+        }
+        self.stack.push(Bounds { lo, hi });
+        Ok(())
+    }
+    fn dyadic(&mut self, f: impl Fn(f64, f64, f64, f64) -> (f64, f64)) -> UiuaResult {
+        let a = self.pop()?;
+        let b = self.pop()?;
+        let res = dyadic(a, b, self.env, f)?;
+        self.stack.push(res);
+        Ok(())
+    }
+    fn prim(&mut self, prim: Primitive) -> UiuaResult {
+        use Primitive::*;
+        if self.stack_prim(prim)? {
+            return Ok(());
+        }
+        match prim {
+            Neg => self.monadic(|l, h| (-h, -l))?,
+            Not => self.monadic(|l, h| (add(1.0, -h).0, add(1.0, -l).1))?,
+            Abs => self.monadic(abs)?,
+            Sign => self.monadic(|l, h| (sign(l), sign(h)))?,
+            Sqrt => self.monadic(|l, h| (sqrt(l).0, sqrt(h).1))?,
+            Sin => self.monadic(|l, h| sinusoid(l, h, f64::sin, FRAC_PI_2))?,
+            Floor => self.monadic(|l, h| (l.floor(), h.floor()))?,
+            Ceil => self.monadic(|l, h| (l.ceil(), h.ceil()))?,
+            Round => self.monadic(|l, h| (l.round(), h.round()))?,
+            Add => self.dyadic(|al, ah, bl, bh| (add(bl, al).0, add(bh, ah).1))?,
+            Sub => self.dyadic(|al, ah, bl, bh| (add(bl, -ah).0, add(bh, -al).1))?,
+            Mul => self.dyadic(|al, ah, bl, bh| corners(mul, al, ah, bl, bh))?,
+            Div => self.dyadic(div_interval)?,
+            Pow => self.dyadic(pow_interval)?,
+            Min => self.dyadic(|al, ah, bl, bh| (al.min(bl), ah.min(bh)))?,
+            Max => self.dyadic(|al, ah, bl, bh| (al.max(bl), ah.max(bh)))?,
+            prim => return Err(self.not_supported(prim.format())),
+        }
+        Ok(())
+    }
+}
+
+/// Apply a function to the bounds of two intervals, extending their shapes to match
+fn dyadic(
+    a: Bounds,
+    b: Bounds,
+    env: &Uiua,
+    f: impl Fn(f64, f64, f64, f64) -> (f64, f64),
+) -> UiuaResult<Bounds> {
+    let first = InfalliblePervasiveFn::new(|x: f64, _: f64| x);
+    let al = bin_pervade(a.lo, b.lo.clone(), env, first.clone())?;
+    let ah = bin_pervade(a.hi, b.lo.clone(), env, first.clone())?;
+    let bl = bin_pervade(b.lo, al.clone(), env, first.clone())?;
+    let bh = bin_pervade(b.hi, al.clone(), env, first)?;
+    let (lo, hi): (EcoVec<f64>, EcoVec<f64>) = (al.data.iter())
+        .zip(&ah.data)
+        .zip(bl.data.iter().zip(&bh.data))
+        .map(|((&al, &ah), (&bl, &bh))| f(al, ah, bl, bh))
+        .unzip();
+    Ok(Bounds {
+        lo: Array::new(al.shape.clone(), lo),
+        hi: Array::new(al.shape, hi),
+    })
+}
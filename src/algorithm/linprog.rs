@@ -0,0 +1,205 @@
+//! Linear programming with the simplex method
+
+use crate::{Array, Uiua, UiuaResult};
+
+const EPSILON: f64 = 1e-9;
+
+/// Maximize `c·x` subject to `Ax ≤ b` and `x ≥ 0`
+pub fn linprog(env: &mut Uiua) -> UiuaResult {
+    let objective = (env.pop("objective")?).as_nums(env, "Objective must be a list of numbers")?;
+    let constraints = (env.pop("constraints")?)
+        .as_number_array::<f64>(env, "Constraints must be a matrix of numbers")?;
+    let bounds = (env.pop("bounds")?).as_nums(env, "Bounds must be a list of numbers")?;
+    let n = objective.len();
+    let m = bounds.len();
+    if constraints.shape() != [m, n] {
+        return Err(env.error(format!(
+            "Constraints must have shape {} to match {} objective coefficients \
+            and {} bounds, but its shape is {}",
+            crate::Shape::from([m, n]),
+            n,
+            m,
+            constraints.shape()
+        )));
+    }
+    if let Some(x) = objective
+        .iter()
+        .chain(&bounds)
+        .chain(&constraints.data)
+        .find(|x| !x.is_finite())
+    {
+        return Err(env.error(format!(
+            "Linear program coefficients must be finite, but {x} was found"
+        )));
+    }
+    let rows: Vec<&[f64]> = if n == 0 {
+        vec![&[]; m]
+    } else {
+        constraints.data.chunks_exact(n).collect()
+    };
+    let x = Simplex::new(&objective, &rows, &bounds).solve(env)?;
+    env.push(Array::from(x.as_slice()));
+    Ok(())
+}
+
+/// A simplex tableau
+///
+/// Columns are the original variables, then one slack variable per constraint,
+/// then one artificial variable per constraint with a negative bound, then the right-hand side.
+struct Simplex {
+    rows: Vec<Vec<f64>>,
+    objective: Vec<f64>,
+    basis: Vec<usize>,
+    var_count: usize,
+    /// The first artificial column
+    artificial: usize,
+    rhs: usize,
+}
+
+enum Outcome {
+    Optimal,
+    Unbounded,
+}
+
+impl Simplex {
+    fn new(c: &[f64], a: &[&[f64]], b: &[f64]) -> Self {
+        let n = c.len();
+        let m = b.len();
+        let artificial = n + m;
+        let artificial_count = b.iter().filter(|&&b| b < 0.0).count();
+        let rhs = artificial + artificial_count;
+        let mut rows = Vec::with_capacity(m);
+        let mut basis = Vec::with_capacity(m);
+        let mut next_artificial = artificial;
+        for (i, (a, &b)) in a.iter().zip(b).enumerate() {
+            let mut row = vec![0.0; rhs + 1];
+            // Rows with negative bounds are negated so that the right-hand side is non-negative
+            let sign = if b < 0.0 { -1.0 } else { 1.0 };
+            for (j, &a) in a.iter().enumerate() {
+                row[j] = sign * a;
+            }
+            row[n + i] = sign;
+            row[rhs] = sign * b;
+            if b < 0.0 {
+                row[next_artificial] = 1.0;
+                basis.push(next_artificial);
+                next_artificial += 1;
+            } else {
+                basis.push(n + i);
+            }
+            rows.push(row);
+        }
+        let mut objective = vec![0.0; rhs + 1];
+        for (j, &c) in c.iter().enumerate() {
+            objective[j] = -c;
+        }
+        Simplex {
+            rows,
+            objective,
+            basis,
+            var_count: n,
+            artificial,
+            rhs,
+        }
+    }
+    fn solve(mut self, env: &Uiua) -> UiuaResult<Vec<f64>> {
+        // Phase 1: find a feasible solution by driving the artificial variables to 0
+        if self.artificial < self.rhs {
+            let phase2_objective = std::mem::replace(&mut self.objective, vec![0.0; self.rhs + 1]);
+            for j in self.artificial..self.rhs {
+                self.objective[j] = 1.0;
+            }
+            self.canonicalize_objective();
+            self.run(self.rhs, env)?;
+            if self.objective[self.rhs] < -EPSILON {
+                return Err(env.error("Linear program is infeasible"));
+            }
+            // Pivot any remaining artificial variables out of the basis
+            for i in 0..self.rows.len() {
+                if self.basis[i] >= self.artificial {
+                    if let Some(j) = (0..self.artificial).find(|&j| self.rows[i][j].abs() > EPSILON)
+                    {
+                        self.pivot(i, j);
+                    }
+                }
+            }
+            self.objective = phase2_objective;
+            self.canonicalize_objective();
+        }
+        // Phase 2: optimize the actual objective
+        if let Outcome::Unbounded = self.run(self.artificial, env)? {
+            return Err(env.error("Linear program is unbounded"));
+        }
+        let mut x = vec![0.0; self.var_count];
+        for (row, &b) in self.rows.iter().zip(&self.basis) {
+            if b < self.var_count {
+                x[b] = row[self.rhs];
+            }
+        }
+        Ok(x)
+    }
+    /// Make the objective row's coefficients zero for all basic variables
+    fn canonicalize_objective(&mut self) {
+        for (row, &b) in self.rows.iter().zip(&self.basis) {
+            let factor = self.objective[b];
+            if factor != 0.0 {
+                for (o, r) in self.objective.iter_mut().zip(row) {
+                    *o -= factor * r;
+                }
+            }
+        }
+    }
+    /// Run the simplex method, only allowing columns before `col_limit` to enter the basis
+    ///
+    /// Uses Bland's rule to avoid cycling
+    fn run(&mut self, col_limit: usize, env: &Uiua) -> UiuaResult<Outcome> {
+        loop {
+            env.respect_execution_limit()?;
+            let Some(col) = (0..col_limit).find(|&j| self.objective[j] < -EPSILON) else {
+                return Ok(Outcome::Optimal);
+            };
+            let mut leaving: Option<(usize, f64)> = None;
+            for (i, row) in self.rows.iter().enumerate() {
+                if row[col] <= EPSILON {
+                    continue;
+                }
+                let ratio = row[self.rhs] / row[col];
+                leaving = match leaving {
+                    Some((l, best))
+                        if best < ratio - EPSILON
+                            || (ratio - best).abs() <= EPSILON && self.basis[l] < self.basis[i] =>
+                    {
+                        Some((l, best))
+                    }
+                    _ => Some((i, ratio)),
+                };
+            }
+            let Some((row, _)) = leaving else {
+                return Ok(Outcome::Unbounded);
+            };
+            self.pivot(row, col);
+        }
+    }
+    fn pivot(&mut self, row: usize, col: usize) {
+        let pivot = self.rows[row][col];
+        for x in &mut self.rows[row] {
+            *x /= pivot;
+        }
+        let pivot_row = self.rows[row].clone();
+        for (i, other) in self.rows.iter_mut().enumerate() {
+            let factor = other[col];
+            if i != row && factor != 0.0 {
+                for (o, p) in other.iter_mut().zip(&pivot_row) {
+                    *o -= factor * p;
+                }
+            }
+        }
+        let factor = self.objective[col];
+        if factor != 0.0 {
+            for (o, p) in self.objective.iter_mut().zip(&pivot_row) {
+                *o -= factor * p;
+            }
+        }
+        self.basis[row] = col;
+    }
+}
@@ -333,6 +333,88 @@ impl Value {
         }
         Ok(())
     }
+    /// Get a value from nested maps and arrays by following a path of keys and indices
+    pub fn get_path(&self, path: &Value, env: &Uiua) -> UiuaResult<Value> {
+        let mut val = self.clone().unboxed();
+        for step in path_steps(path, env)? {
+            val = match val.path_index(&step, env)? {
+                Some(i) => val.row(i),
+                None => val.get(&step, env)?,
+            }
+            .unboxed();
+        }
+        Ok(val)
+    }
+    /// Set a value in nested maps and arrays by following a path of keys and indices
+    ///
+    /// Boxes along the path are kept
+    pub fn set_path(&mut self, path: &Value, new: Value, env: &Uiua) -> UiuaResult {
+        let steps = path_steps(path, env)?;
+        self.set_path_impl(&steps, new, env)
+    }
+    fn set_path_impl(&mut self, steps: &[Value], new: Value, env: &Uiua) -> UiuaResult {
+        let Some((step, rest)) = steps.split_first() else {
+            let rebox = self.is_scalar_box() && !new.is_scalar_box();
+            *self = new.boxed_if(rebox);
+            return Ok(());
+        };
+        if self.is_scalar_box() {
+            let mut inner = take(self).unboxed();
+            inner.set_path_impl(steps, new, env)?;
+            *self = Boxed(inner).into();
+            return Ok(());
+        }
+        match self.path_index(step, env)? {
+            Some(i) => {
+                let mut rows: Vec<Value> = take(self).into_rows().collect();
+                rows[i].set_path_impl(rest, new, env)?;
+                *self = Value::from_row_values(rows, env)?;
+            }
+            // A missing key can be set, but not traversed
+            None if rest.is_empty() && self.has_key(step, env)?.data[0] == 0 => {
+                self.insert(step.clone(), new, env)?
+            }
+            None => {
+                let mut val = self.get(step, env)?;
+                val.set_path_impl(rest, new, env)?;
+                self.insert(step.clone(), val, env)?;
+            }
+        }
+        Ok(())
+    }
+    /// Get the row index of a path step, or [`None`] if the value is a map
+    fn path_index(&self, step: &Value, env: &Uiua) -> UiuaResult<Option<usize>> {
+        if self.is_map() {
+            return Ok(None);
+        }
+        let index = step.as_int(env, "Path steps into arrays must be integer indices")?;
+        let len = self.row_count();
+        let i = if index < 0 {
+            index + len as isize
+        } else {
+            index
+        };
+        if i < 0 || i as usize >= len {
+            return Err(env.error(format!("Index {index} is out of bounds of length {len}")));
+        }
+        Ok(Some(i as usize))
+    }
+    fn is_scalar_box(&self) -> bool {
+        matches!(self, Value::Box(arr) if arr.rank() == 0)
+    }
+}
+
+/// Split a path into its steps
+///
+/// Each row of the path is a step. Boxed steps are unboxed.
+/// A string is a single step.
+fn path_steps(path: &Value, env: &Uiua) -> UiuaResult<Vec<Value>> {
+    match path.rank() {
+        0 => Ok(vec![path.clone().unboxed()]),
+        1 if matches!(path, Value::Char(_)) => Ok(vec![path.clone()]),
+        1 => Ok(path.clone().into_rows().map(Value::unboxed).collect()),
+        rank => Err(env.error(format!("Path must be a list, but it has rank {rank}"))),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,390 @@
+//! Exporting and previewing 3D meshes
+
+use std::{collections::HashMap, f64::consts::PI, fmt::Write};
+
+use ecow::EcoVec;
+use serde_json::json;
+
+use crate::{
+    algorithm::{encode::base64, validate_size},
+    Array, Boxed, Shape, Uiua, UiuaResult, Value,
+};
+
+type Vec3 = [f64; 3];
+
+/// A mesh of polygons
+struct Mesh {
+    vertices: Vec<Vec3>,
+    faces: Vec<Vec<usize>>,
+}
+
+/// Encode a mesh into a 3D model format, or render a preview image of it
+pub fn mesh(env: &mut Uiua) -> UiuaResult {
+    let format = env.pop("format")?;
+    let geometry = env.pop("mesh")?;
+    let mesh = Mesh::from_value(geometry, env)?;
+    if let Value::Char(_) = format {
+        let format = format.as_string(env, "Mesh format must be a string")?;
+        let bytes = match format.as_str() {
+            "stl" => mesh.stl(),
+            "obj" => mesh.obj(),
+            "gltf" => mesh.gltf(),
+            "glb" => mesh.glb(),
+            _ => {
+                return Err(env.error(format!(
+                    "Invalid mesh format {format:?}. \
+                    Valid formats are \"stl\", \"obj\", \"gltf\", and \"glb\"."
+                )))
+            }
+        };
+        env.push(Array::<u8>::from(bytes.as_slice()));
+    } else {
+        let size = format.as_nats(env, "Mesh preview size must be a list of 2 natural numbers")?;
+        let &[height, width] = size.as_slice() else {
+            return Err(env.error(format!(
+                "Mesh preview size must be 2 numbers, but it is {} numbers",
+                size.len()
+            )));
+        };
+        validate_size::<f64>([height, width, 4], env)?;
+        let data = mesh.render(height, width);
+        env.push(Array::new(Shape::from_iter([height, width, 4]), data));
+    }
+    Ok(())
+}
+
+impl Mesh {
+    fn from_value(val: Value, env: &Uiua) -> UiuaResult<Self> {
+        match val {
+            Value::Box(arr) if arr.shape().dims() == [2] => {
+                let [Boxed(vertices), Boxed(faces)] = [&arr.data[0], &arr.data[1]];
+                Self::from_arrays(vertices, faces, env)
+            }
+            val if val.rank() == 3 => {
+                let voxels = val.as_number_array::<f64>(env, "Voxels must be numbers")?;
+                Ok(Self::from_voxels(&voxels))
+            }
+            val => Err(env.error(format!(
+                "Mesh must be a list of 2 boxes with vertices and faces \
+                or a rank 3 array of voxels, but it is a rank {} {} array",
+                val.rank(),
+                val.type_name()
+            ))),
+        }
+    }
+    fn from_arrays(vertices: &Value, faces: &Value, env: &Uiua) -> UiuaResult<Self> {
+        let vertices =
+            vertices.as_number_array::<f64>(env, "Mesh vertices must be an array of numbers")?;
+        if !matches!(vertices.shape().dims(), [_, 3] | [0]) {
+            return Err(env.error(format!(
+                "Mesh vertices must be a list of [x y z] points, but their shape is {}",
+                vertices.shape()
+            )));
+        }
+        if vertices.data.iter().any(|n| !n.is_finite()) {
+            return Err(env.error("Mesh vertices must be finite"));
+        }
+        let vertices: Vec<Vec3> = (vertices.data.chunks_exact(3))
+            .map(|v| [v[0], v[1], v[2]])
+            .collect();
+        let faces = faces.as_number_array::<f64>(env, "Mesh faces must be an array of indices")?;
+        let sides = match *faces.shape().dims() {
+            [_, sides] if sides >= 3 => sides,
+            [0] => 3,
+            _ => {
+                return Err(env.error(format!(
+                    "Mesh faces must be a list of rows of at least 3 vertex indices, \
+                    but their shape is {}",
+                    faces.shape()
+                )))
+            }
+        };
+        if let Some(&i) = (faces.data.iter())
+            .find(|&&i| i < 0.0 || i.fract() != 0.0 || i >= vertices.len() as f64)
+        {
+            return Err(env.error(format!(
+                "Face vertex index {i} is invalid for {} vertices",
+                vertices.len()
+            )));
+        }
+        let faces = (faces.data.chunks_exact(sides))
+            .map(|face| face.iter().map(|&i| i as usize).collect())
+            .collect();
+        Ok(Mesh { vertices, faces })
+    }
+    /// Make a mesh of the outer faces of some unit cubes
+    ///
+    /// Faces between two filled voxels are left out
+    fn from_voxels(voxels: &Array<f64>) -> Self {
+        let dims = [0, 1, 2].map(|i| voxels.shape()[i]);
+        let filled = |pos: [isize; 3]| {
+            (pos.iter().zip(dims)).all(|(&p, d)| p >= 0 && (p as usize) < d) && {
+                let i = (pos[0] as usize * dims[1] + pos[1] as usize) * dims[2] + pos[2] as usize;
+                voxels.data[i] != 0.0
+            }
+        };
+        let mut vertices = Vec::new();
+        let mut indices: HashMap<[isize; 3], usize> = HashMap::new();
+        let mut faces = Vec::new();
+        for i in 0..dims[0] as isize {
+            for j in 0..dims[1] as isize {
+                for k in 0..dims[2] as isize {
+                    let pos = [i, j, k];
+                    if !filled(pos) {
+                        continue;
+                    }
+                    for axis in 0..3 {
+                        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+                        for dir in [1, -1] {
+                            let mut neighbor = pos;
+                            neighbor[axis] += dir;
+                            if filled(neighbor) {
+                                continue;
+                            }
+                            // Counter-clockwise when seen from outside
+                            let mut corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+                            if dir < 0 {
+                                corners.reverse();
+                            }
+                            let face = corners.map(|(db, dc)| {
+                                let mut corner = pos;
+                                corner[axis] += (dir > 0) as isize;
+                                corner[b] += db;
+                                corner[c] += dc;
+                                *indices.entry(corner).or_insert_with(|| {
+                                    vertices.push(corner.map(|n| n as f64));
+                                    vertices.len() - 1
+                                })
+                            });
+                            faces.push(face.to_vec());
+                        }
+                    }
+                }
+            }
+        }
+        Mesh { vertices, faces }
+    }
+    /// Split the faces into triangles with fans
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (self.faces.iter())
+            .flat_map(|face| (1..face.len() - 1).map(|i| [face[0], face[i], face[i + 1]]))
+    }
+    fn stl(&self) -> Vec<u8> {
+        let triangles: Vec<_> = self.triangles().collect();
+        let mut bytes = vec![0; 80];
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for tri in triangles {
+            let [a, b, c] = tri.map(|i| self.vertices[i]);
+            let normal = normalize(cross(sub(b, a), sub(c, a)));
+            for n in [normal, a, b, c].into_iter().flatten() {
+                bytes.extend((n as f32).to_le_bytes());
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+    fn obj(&self) -> Vec<u8> {
+        let mut s = String::new();
+        for [x, y, z] in &self.vertices {
+            _ = writeln!(s, "v {x} {y} {z}");
+        }
+        for face in &self.faces {
+            s.push('f');
+            for i in face {
+                _ = write!(s, " {}", i + 1);
+            }
+            s.push('\n');
+        }
+        s.into_bytes()
+    }
+    /// The glTF description of the mesh and its binary buffer
+    fn gltf_parts(&self) -> (serde_json::Value, Vec<u8>) {
+        let triangles: Vec<_> = self.triangles().collect();
+        let mut buffer = Vec::new();
+        for n in self.vertices.iter().flatten() {
+            buffer.extend((*n as f32).to_le_bytes());
+        }
+        let positions_len = buffer.len();
+        for i in triangles.iter().flatten() {
+            buffer.extend((*i as u32).to_le_bytes());
+        }
+        let mut gltf = json!({
+            "asset": { "version": "2.0", "generator": "Uiua" },
+            "scene": 0,
+            "scenes": [{ "nodes": [] }],
+        });
+        if !triangles.is_empty() {
+            let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+            for v in &self.vertices {
+                for i in 0..3 {
+                    min[i] = min[i].min(v[i] as f32);
+                    max[i] = max[i].max(v[i] as f32);
+                }
+            }
+            gltf["scenes"][0]["nodes"] = json!([0]);
+            gltf["nodes"] = json!([{ "mesh": 0 }]);
+            gltf["meshes"] = json!([{
+                "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }]
+            }]);
+            gltf["buffers"] = json!([{ "byteLength": buffer.len() }]);
+            gltf["bufferViews"] = json!([
+                { "buffer": 0, "byteLength": positions_len, "target": 34962 },
+                {
+                    "buffer": 0,
+                    "byteOffset": positions_len,
+                    "byteLength": buffer.len() - positions_len,
+                    "target": 34963
+                },
+            ]);
+            gltf["accessors"] = json!([
+                {
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": self.vertices.len(),
+                    "type": "VEC3",
+                    "min": min,
+                    "max": max,
+                },
+                {
+                    "bufferView": 1,
+                    "componentType": 5125,
+                    "count": triangles.len() * 3,
+                    "type": "SCALAR",
+                },
+            ]);
+        }
+        (gltf, buffer)
+    }
+    fn gltf(&self) -> Vec<u8> {
+        let (mut gltf, buffer) = self.gltf_parts();
+        if let Some(buffers) = gltf.get_mut("buffers") {
+            buffers[0]["uri"] =
+                format!("data:application/octet-stream;base64,{}", base64(&buffer)).into();
+        }
+        serde_json::to_vec(&gltf).unwrap()
+    }
+    fn glb(&self) -> Vec<u8> {
+        let (gltf, mut buffer) = self.gltf_parts();
+        let mut json = serde_json::to_vec(&gltf).unwrap();
+        // Chunks must be 4-byte aligned
+        json.resize(json.len().next_multiple_of(4), b' ');
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+        let mut chunks = vec![(0x4E4F534Au32, json)];
+        if !buffer.is_empty() {
+            chunks.push((0x004E4942, buffer));
+        }
+        let total = 12 + chunks.iter().map(|(_, data)| 8 + data.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(total);
+        bytes.extend(b"glTF");
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend((total as u32).to_le_bytes());
+        for (kind, data) in chunks {
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(kind.to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+    /// Render a shaded preview of the mesh from above at an angle
+    ///
+    /// The z axis points up
+    fn render(&self, height: usize, width: usize) -> EcoVec<f64> {
+        // Rotate the mesh into view space as right, up, and depth
+        let (yaw_sin, yaw_cos) = (PI / 4.0).sin_cos();
+        let (pitch_sin, pitch_cos) = (PI / 6.0).sin_cos();
+        let view: Vec<Vec3> = (self.vertices.iter())
+            .map(|&[x, y, z]| {
+                let (right, away) = (x * yaw_cos - y * yaw_sin, x * yaw_sin + y * yaw_cos);
+                let up = z * pitch_cos + away * pitch_sin;
+                let depth = away * pitch_cos - z * pitch_sin;
+                [right, up, depth]
+            })
+            .collect();
+        // Fit the mesh to the image
+        let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for v in &view {
+            for i in 0..2 {
+                min[i] = min[i].min(v[i]);
+                max[i] = max[i].max(v[i]);
+            }
+        }
+        let span = (max[0] - min[0]) / width as f64;
+        let span = span.max((max[1] - min[1]) / height as f64);
+        let scale = if span > 0.0 { 0.9 / span } else { 1.0 };
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        let screen: Vec<Vec3> = (view.iter())
+            .map(|&[right, up, depth]| {
+                [
+                    (center[1] - up) * scale + height as f64 / 2.0,
+                    (right - center[0]) * scale + width as f64 / 2.0,
+                    depth,
+                ]
+            })
+            .collect();
+
+        let light = normalize([-0.3, 0.5, -1.0]);
+        let mut data = EcoVec::from_iter(std::iter::repeat(0.0).take(height * width * 4));
+        let slice = data.make_mut();
+        let mut depths = vec![f64::INFINITY; height * width];
+        for tri in self.triangles() {
+            let [a, b, c] = tri.map(|i| view[i]);
+            let normal = normalize(cross(sub(b, a), sub(c, a)));
+            let shade = 0.15 + 0.85 * dot(normal, light).abs();
+            let [a, b, c] = tri.map(|i| screen[i]);
+            let area = edge(a, b, c);
+            if area == 0.0 || area.is_nan() {
+                continue;
+            }
+            let rows = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize
+                ..(a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(height);
+            let cols = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize
+                ..(a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(width);
+            for y in rows {
+                for x in cols.clone() {
+                    let p = [y as f64 + 0.5, x as f64 + 0.5, 0.0];
+                    let weights = [edge(b, c, p), edge(c, a, p), edge(a, b, p)].map(|w| w / area);
+                    if weights.iter().any(|&w| w < 0.0) {
+                        continue;
+                    }
+                    let depth = weights[0] * a[2] + weights[1] * b[2] + weights[2] * c[2];
+                    let i = y * width + x;
+                    if depth < depths[i] {
+                        depths[i] = depth;
+                        slice[i * 4..][..4].copy_from_slice(&[shade, shade, shade, 1.0]);
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+/// Twice the signed area of a triangle in screen space
+fn edge(a: Vec3, b: Vec3, p: Vec3) -> f64 {
+    (b[1] - a[1]) * (p[0] - a[0]) - (b[0] - a[0]) * (p[1] - a[1])
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: Vec3) -> Vec3 {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        v.map(|n| n / len)
+    }
+}
@@ -16,21 +16,33 @@ use ecow::{EcoString, EcoVec};
 use tinyvec::TinyVec;
 
 use crate::{
-    cowslice::ecovec_extend_cowslice, Array, ArrayCmp, ArrayValue, Boxed, CodeSpan, Complex,
-    ExactDoubleIterator, Inputs, Ops, PersistentMeta, Primitive, Shape, SigNode, Signature, Span,
-    Uiua, UiuaError, UiuaErrorKind, UiuaResult, Value,
+    cowslice::ecovec_extend_cowslice, random, Array, ArrayCmp, ArrayValue, Boxed, CodeSpan,
+    Complex, ExactDoubleIterator, FormatShape, Inputs, Ops, PersistentMeta, Primitive, Shape,
+    SigNode, Signature, Span, Uiua, UiuaError, UiuaErrorKind, UiuaResult, Value,
 };
 
+pub mod autodiff;
+pub mod code;
+pub mod draw;
+pub mod dual;
 mod dyadic;
 pub mod encode;
+pub mod graph;
+pub mod interval;
+pub mod linprog;
 pub mod loops;
 pub mod map;
+pub mod mesh;
 mod monadic;
+pub mod pattern;
+pub mod pdf;
 pub mod permute;
 pub mod pervade;
 pub mod reduce;
 pub mod stencil;
+pub mod synth;
 pub mod table;
+pub mod walk;
 pub mod zip;
 
 pub(crate) fn get_ops<const N: usize>(
@@ -117,7 +129,13 @@ impl fmt::Display for FillShapeError {
 }
 
 pub fn validate_size<T>(sizes: impl IntoIterator<Item = usize>, env: &Uiua) -> UiuaResult<usize> {
-    validate_size_of::<T>(sizes).map_err(|e| env.error(e))
+    let elements = validate_size_of::<T>(sizes).map_err(|e| env.error(e))?;
+    if let Some(limit) = env.rt.memory_limit {
+        if elements.saturating_mul(size_of::<T>()) > limit {
+            return Err(UiuaErrorKind::MemoryLimit(env.span(), env.inputs().clone().into()).into());
+        }
+    }
+    Ok(elements)
 }
 
 pub fn validate_size_of<T>(sizes: impl IntoIterator<Item = usize>) -> Result<usize, SizeError> {
@@ -150,6 +168,12 @@ pub(crate) fn validate_size_impl(
 pub trait ErrorContext {
     type Error: FillError;
     fn error(&self, msg: impl ToString) -> Self::Error;
+    /// Create an error for arrays whose shapes do not match
+    ///
+    /// Contexts that can show extra info attach a comparison of the shapes.
+    fn shape_error(&self, msg: impl ToString, _a: &[usize], _b: &[usize]) -> Self::Error {
+        self.error(msg)
+    }
 }
 
 impl ErrorContext for Uiua {
@@ -157,6 +181,9 @@ impl ErrorContext for Uiua {
     fn error(&self, msg: impl ToString) -> Self::Error {
         self.error(msg)
     }
+    fn shape_error(&self, msg: impl ToString, a: &[usize], b: &[usize]) -> Self::Error {
+        self.error(msg).with_info([(shape_diff(a, b), None)])
+    }
 }
 
 impl ErrorContext for (&CodeSpan, &Inputs) {
@@ -169,6 +196,76 @@ impl ErrorContext for (&CodeSpan, &Inputs) {
         }
         .into()
     }
+    fn shape_error(&self, msg: impl ToString, a: &[usize], b: &[usize]) -> Self::Error {
+        self.error(msg).with_info([(shape_diff(a, b), None)])
+    }
+}
+
+/// Show two shapes one above the other, point at the first axis
+/// where they differ, and suggest ways to make them compatible
+pub(crate) fn shape_diff(a: &[usize], b: &[usize]) -> String {
+    let common = a.len().min(b.len());
+    let axis = (a.iter().zip(b).position(|(a, b)| a != b)).unwrap_or(common);
+    // Pad shared axes to the same width so that they line up
+    let pad = |sh: &[usize], other: &[usize]| -> Vec<String> {
+        (sh.iter().enumerate())
+            .map(|(i, dim)| {
+                let width = other.get(i).map_or(0, |d| d.to_string().len());
+                format!("{dim:>width$}")
+            })
+            .collect()
+    };
+    let (a_dims, b_dims) = (pad(a, b), pad(b, a));
+    let longer = if a_dims.len() >= b_dims.len() {
+        &a_dims
+    } else {
+        &b_dims
+    };
+    // Point at the last digit of the differing axis
+    let column = (longer.iter().take(axis))
+        .map(|dim| dim.len() + 3)
+        .sum::<usize>()
+        + longer.get(axis).map_or(1, |dim| dim.len());
+    let mut s = if axis < common {
+        format!("Shapes differ at axis {axis}")
+    } else {
+        format!("Shapes differ in rank at axis {axis}")
+    };
+    s.push_str(&format!(
+        "\n  {}\n  {}\n  {}^",
+        FormatShape(&a_dims),
+        FormatShape(&b_dims),
+        " ".repeat(column)
+    ));
+    let mut fixes = Vec::new();
+    if a.len() != b.len() && (a.ends_with(b) || b.ends_with(a)) {
+        fixes.push(format!(
+            "{} the lower-rank array to pair it with each row of the other",
+            Primitive::Fix.format()
+        ));
+    }
+    if axis < common {
+        fixes.push(format!(
+            "{} to pad the shorter axis",
+            Primitive::Fill.format()
+        ));
+    }
+    if a.len() == b.len() && a != b && {
+        let (mut a, mut b) = (a.to_vec(), b.to_vec());
+        a.sort_unstable();
+        b.sort_unstable();
+        a == b
+    } {
+        fixes.push(format!(
+            "{} to reorder the axes",
+            Primitive::Orient.format()
+        ));
+    }
+    if !fixes.is_empty() {
+        s.push_str("\nTry ");
+        s.push_str(&fixes.join(", or "));
+    }
+    s
 }
 
 impl ErrorContext for () {
@@ -421,17 +518,19 @@ where
     {
         Ok(())
     } else {
-        Err(C::fill_error(ctx.error(match (a_err, b_err) {
-            (Some(FillShapeError::Size(e)), _) | (_, Some(FillShapeError::Size(e))) => {
-                e.to_string()
-            }
-            (Some(e), _) | (_, Some(e)) => {
-                format!("Shapes {} and {} do not match{e}", a.shape(), b.shape())
-            }
-            (None, None) => {
-                format!("Shapes {} and {} do not match", a.shape(), b.shape())
-            }
-        })))
+        Err(C::fill_error(match (a_err, b_err) {
+            (Some(FillShapeError::Size(e)), _) | (_, Some(FillShapeError::Size(e))) => ctx.error(e),
+            (Some(e), _) | (_, Some(e)) => ctx.shape_error(
+                format!("Shapes {} and {} do not match{e}", a.shape(), b.shape()),
+                a.shape(),
+                b.shape(),
+            ),
+            (None, None) => ctx.shape_error(
+                format!("Shapes {} and {} do not match", a.shape(), b.shape()),
+                a.shape(),
+                b.shape(),
+            ),
+        }))
     }
 }
 
@@ -602,6 +701,7 @@ pub fn try_(ops: Ops, env: &mut Uiua) -> UiuaResult {
             err.is_case = false;
             return Err(err);
         }
+        let kind = err.kind_tag();
         if handler_sig.args > f_sig.args {
             (env.rt.backend).save_error_color(err.to_string(), err.report().to_string());
             env.push(err.value());
@@ -609,11 +709,91 @@ pub fn try_(ops: Ops, env: &mut Uiua) -> UiuaResult {
         for val in backup {
             env.push(val);
         }
-        env.exec(handler)?;
+        env.rt.handled_errors.push(kind);
+        let res = env.exec(handler);
+        env.rt.handled_errors.pop();
+        res?;
     }
     Ok(())
 }
 
+pub fn retry(ops: Ops, env: &mut Uiua) -> UiuaResult {
+    let [f] = get_ops(ops, env)?;
+    let count = env
+        .pop("retry count")?
+        .as_nat_or_inf(env, "Retry count must be a natural number or infinity")?;
+    let backoff = env
+        .pop("backoff")?
+        .as_nums(env, "Backoff must be a list of numbers")?;
+    let (initial, factor, jitter) = match *backoff {
+        [initial] => (initial, 2.0, 0.0),
+        [initial, factor] => (initial, factor, 0.0),
+        [initial, factor, jitter] => (initial, factor, jitter),
+        _ => {
+            return Err(env.error(format!(
+                "Backoff must have 1 to 3 numbers, but it has {}",
+                backoff.len()
+            )))
+        }
+    };
+    if [initial, factor].iter().any(|n| n.is_nan() || *n < 0.0) {
+        return Err(env.error("Backoff delay and factor must be non-negative"));
+    }
+    if !(0.0..=1.0).contains(&jitter) {
+        return Err(env.error("Backoff jitter must be between 0 and 1"));
+    }
+    if env.stack_height() < f.sig.args {
+        for i in 0..f.sig.args {
+            env.pop(i + 1)?;
+        }
+    }
+    let args = env.clone_stack_top(f.sig.args)?;
+    let mut delay = initial;
+    let mut retries = 0;
+    loop {
+        match env.exec_clean_stack(f.clone()) {
+            Ok(()) => return Ok(()),
+            Err(err) if err.is_case || count.is_some_and(|count| retries >= count) => {
+                return Err(err)
+            }
+            Err(_) => {}
+        }
+        retries += 1;
+        let secs = delay * (1.0 + jitter * (2.0 * random() - 1.0));
+        if secs > 0.0 {
+            env.rt.backend.sleep(secs).map_err(|e| env.error(e))?;
+        }
+        env.respect_execution_limit()?;
+        delay *= factor;
+        for val in &args {
+            env.push(val.clone());
+        }
+    }
+}
+
+pub fn using(ops: Ops, env: &mut Uiua) -> UiuaResult {
+    let [f] = get_ops(ops, env)?;
+    if f.sig.args == 0 {
+        return Err(env.error(format!(
+            "{}'s function must take at least 1 argument, \
+            but its signature is {}",
+            Primitive::Using.format(),
+            f.sig
+        )));
+    }
+    let handle = env.pop("handle")?;
+    if handle.handle_kind().is_none() {
+        return Err(env.error(format!(
+            "{} expects a handle, but the value is not one",
+            Primitive::Using.format()
+        )));
+    }
+    env.push(handle.clone());
+    let res = env.exec(f);
+    let closed = env.close_handle(handle);
+    res.and(closed)
+}
+
 pub fn format(parts: &[EcoString], env: &mut Uiua) -> UiuaResult {
     fn format_val(chars: &mut EcoVec<char>, val: Value) {
         match val {
@@ -813,7 +993,14 @@ pub fn path(
     heuristic: Option<SigNode>,
     env: &mut Uiua,
 ) -> UiuaResult {
-    path_impl(neighbors, is_goal, heuristic, PathMode::All, env)
+    path_impl(
+        neighbors,
+        is_goal,
+        heuristic,
+        PathMode::All,
+        f64::INFINITY,
+        env,
+    )
 }
 
 pub fn path_first(
@@ -822,7 +1009,14 @@ pub fn path_first(
     heuristic: Option<SigNode>,
     env: &mut Uiua,
 ) -> UiuaResult {
-    path_impl(neighbors, is_goal, heuristic, PathMode::First, env)
+    path_impl(
+        neighbors,
+        is_goal,
+        heuristic,
+        PathMode::First,
+        f64::INFINITY,
+        env,
+    )
 }
 
 pub fn path_pop(
@@ -831,7 +1025,29 @@ pub fn path_pop(
     heuristic: Option<SigNode>,
     env: &mut Uiua,
 ) -> UiuaResult {
-    path_impl(neighbors, is_goal, heuristic, PathMode::CostOnly, env)
+    path_impl(
+        neighbors,
+        is_goal,
+        heuristic,
+        PathMode::CostOnly,
+        f64::INFINITY,
+        env,
+    )
+}
+
+/// Find paths whose cost does not exceed a limit
+///
+/// The limit is popped before the starting node
+pub fn path_within(
+    neighbors: SigNode,
+    is_goal: SigNode,
+    heuristic: Option<SigNode>,
+    env: &mut Uiua,
+) -> UiuaResult {
+    let max_cost = env
+        .pop("max cost")?
+        .as_num(env, "Path cost limit must be a number")?;
+    path_impl(neighbors, is_goal, heuristic, PathMode::All, max_cost, env)
 }
 
 enum PathMode {
@@ -845,6 +1061,7 @@ fn path_impl(
     is_goal: SigNode,
     heuristic: Option<SigNode>,
     mode: PathMode,
+    max_cost: f64,
     env: &mut Uiua,
 ) -> UiuaResult {
     let start = env.pop("start")?;
@@ -986,8 +1203,33 @@ fn path_impl(
         args,
     };
 
+    // Without costs or a heuristic, nodes are seen in order of cost,
+    // so a simple queue can be used instead of a heap
+    enum Frontier {
+        Heap(BinaryHeap<NodeCost>),
+        Queue(VecDeque<NodeCost>),
+    }
+    impl Frontier {
+        fn push(&mut self, node: NodeCost) {
+            match self {
+                Frontier::Heap(heap) => heap.push(node),
+                Frontier::Queue(queue) => queue.push_back(node),
+            }
+        }
+        fn pop(&mut self) -> Option<NodeCost> {
+            match self {
+                Frontier::Heap(heap) => heap.pop(),
+                Frontier::Queue(queue) => queue.pop_front(),
+            }
+        }
+    }
+
     // Initialize state
-    let mut to_see = BinaryHeap::new();
+    let mut to_see = if has_costs || env.heuristic.is_some() {
+        Frontier::Heap(BinaryHeap::new())
+    } else {
+        Frontier::Queue(VecDeque::new())
+    };
     let mut backing = vec![start.clone()];
     let mut indices: HashMap<Value, usize> = [(start, 0)].into();
     to_see.push(NodeCost { node: 0, cost: 0.0 });
@@ -1028,6 +1270,9 @@ fn path_impl(
                 index
             };
             let from_curr_nei_cost = curr_cost + nei_cost;
+            if from_curr_nei_cost > max_cost {
+                continue;
+            }
             let curr_nei_cost = full_cost.get(&nei).copied().unwrap_or(f64::INFINITY);
             if from_curr_nei_cost <= curr_nei_cost {
                 let parents = came_from.entry(nei).or_default();
@@ -0,0 +1,132 @@
+//! Matching values against structural patterns
+
+use crate::{Shape, Uiua, UiuaResult, Value};
+
+const PATTERN_KEYS: [&str; 5] = ["shape", "type", "has", "is", "get"];
+const TYPE_NAMES: [&str; 4] = ["number", "complex", "character", "box"];
+
+/// Choose the branch of a [`when`](crate::Primitive::When)
+///
+/// The list of patterns is on top of the stack, and the matched value is below it.
+/// The value is put back, and the index of the first matching pattern is pushed on top.
+/// If there is one more branch than there are patterns, the last branch is the fallback.
+pub fn when_branch(branches: usize, env: &mut Uiua) -> UiuaResult {
+    let patterns = env.pop("patterns")?;
+    let value = env.pop("matched value")?;
+    let patterns = match patterns {
+        Value::Box(arr) if arr.rank() <= 1 => arr,
+        _ => return Err(env.error("Patterns must be a list of boxes")),
+    };
+    let count = patterns.data.len();
+    if count != branches && count + 1 != branches {
+        return Err(env.error(format!(
+            "There are {count} pattern{} but {branches} branch{}. \
+            There must be one pattern per branch, or one fewer \
+            if the last branch is the fallback.",
+            if count == 1 { "" } else { "s" },
+            if branches == 1 { "" } else { "es" },
+        )));
+    }
+    let matched = value.clone().unboxed();
+    let mut failures = Vec::new();
+    let mut index = None;
+    for (i, pattern) in patterns.data.iter().enumerate() {
+        match mismatch(&pattern.0, &matched, env)? {
+            Some(reason) => failures.push(format!(" Pattern {}: {reason}.", i + 1)),
+            None => {
+                index = Some(i);
+                break;
+            }
+        }
+    }
+    let index = match index {
+        Some(index) => index,
+        None if count < branches => count,
+        None => {
+            return Err(env.error(format!(
+                "No pattern matched the value.{}",
+                failures.concat()
+            )))
+        }
+    };
+    env.push(value);
+    env.push(index);
+    Ok(())
+}
+
+/// Get the reason a value does not match a pattern, if any
+///
+/// A pattern that is not a map must be equal to the value.
+/// A map pattern checks the value with each of its keys.
+fn mismatch(pattern: &Value, value: &Value, env: &Uiua) -> UiuaResult<Option<String>> {
+    if !pattern.is_map() {
+        return Ok(
+            (pattern != value).then(|| format!("{} is not {}", value.show(), pattern.show()))
+        );
+    }
+    for (key, check) in pattern.map_kv() {
+        let key = (key.unboxed()).as_string(env, "Pattern keys must be strings")?;
+        let check = check.unboxed();
+        let reason = match key.as_str() {
+            "shape" => {
+                let shape =
+                    check.as_nats(env, "Pattern shape must be a list of natural numbers")?;
+                (*value.shape() != *shape)
+                    .then(|| format!("shape {} is not {}", value.shape(), Shape::from(shape)))
+            }
+            "type" => {
+                let ty = check.as_string(env, "Pattern type must be a string")?;
+                if !TYPE_NAMES.contains(&ty.as_str()) {
+                    return Err(env.error(format!(
+                        "Unknown pattern type {ty:?}. Types are {}",
+                        TYPE_NAMES.join(", ")
+                    )));
+                }
+                (value.type_name() != ty).then(|| format!("type {} is not {ty}", value.type_name()))
+            }
+            "has" => {
+                let keys: Vec<Value> = match check {
+                    check @ Value::Char(_) if check.rank() == 1 => vec![check],
+                    check => check.into_rows().map(Value::unboxed).collect(),
+                };
+                keys.into_iter().find_map(|key| {
+                    (!has_key(value, &key, env)).then(|| format!("key {} is missing", key.show()))
+                })
+            }
+            "is" => (check != *value).then(|| format!("{} is not {}", value.show(), check.show())),
+            "get" => {
+                if !check.is_map() {
+                    return Err(env.error("Pattern get must be a map of keys to patterns"));
+                }
+                let mut reason = None;
+                for (key, sub) in check.map_kv() {
+                    let key = key.unboxed();
+                    if !has_key(value, &key, env) {
+                        reason = Some(format!("key {} is missing", key.show()));
+                        break;
+                    }
+                    let field = value.get(&key, env)?.unboxed();
+                    if let Some(sub_reason) = mismatch(&sub.unboxed(), &field, env)? {
+                        reason = Some(format!("at key {}, {sub_reason}", key.show()));
+                        break;
+                    }
+                }
+                reason
+            }
+            key => {
+                return Err(env.error(format!(
+                    "Unknown pattern key {key:?}. Pattern keys are {}",
+                    PATTERN_KEYS.join(", ")
+                )))
+            }
+        };
+        if reason.is_some() {
+            return Ok(reason);
+        }
+    }
+    Ok(None)
+}
+
+fn has_key(value: &Value, key: &Value, env: &Uiua) -> bool {
+    value.is_map() && (value.has_key(key, env)).is_ok_and(|has| has.data[0] != 0)
+}
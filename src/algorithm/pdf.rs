@@ -0,0 +1,1429 @@
+//! Generating PDF documents and extracting their text and images
+
+use std::{collections::HashMap, fmt::Write};
+
+use ecow::EcoVec;
+use regex::bytes::Regex;
+
+use crate::{Array, Boxed, Shape, Uiua, UiuaResult, Value};
+
+/// Generate a PDF from a list of blocks
+pub(crate) fn pdf_encode(env: &mut Uiua) -> UiuaResult {
+    let doc = env.pop(1)?;
+    let blocks = blocks(doc, env)?;
+    let bytes = Layout::new().lay_out(blocks).finish();
+    env.push(Array::<u8>::from(bytes.as_slice()));
+    Ok(())
+}
+
+/// Extract the text and images of each page of a PDF
+pub(crate) fn pdf_decode(env: &mut Uiua) -> UiuaResult {
+    let bytes = env.pop(1)?.as_bytes(env, "PDF expects bytes")?;
+    let doc = Document::parse(&bytes).map_err(|e| env.error(e))?;
+    let pages = doc.pages();
+    if pages.is_empty() {
+        return Err(env.error("PDF has no pages"));
+    }
+    let mut rows = Vec::with_capacity(pages.len());
+    for page in pages {
+        let (text, images) = doc.page_contents(&page).map_err(|e| env.error(e))?;
+        let images = Array::<Boxed>::from_iter(images.into_iter().map(Value::from).map(Boxed));
+        let mut map: Value =
+            Array::<Boxed>::from_iter([Boxed(text.into()), Boxed(images.into())]).into();
+        map.map(
+            Array::<Boxed>::from_iter(["text", "images"].map(|key| Boxed(key.into()))).into(),
+            env,
+        )?;
+        rows.push(Boxed(map));
+    }
+    env.push(Array::<Boxed>::from_iter(rows));
+    Ok(())
+}
+
+/* Generation */
+
+/// A4 in points
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+const CONTENT_WIDTH: f64 = PAGE_WIDTH - 2.0 * MARGIN;
+const CONTENT_HEIGHT: f64 = PAGE_HEIGHT - 2.0 * MARGIN;
+const FONT_SIZE: f64 = 11.0;
+const LINE_HEIGHT: f64 = 1.3;
+/// The advance of every Courier glyph in ems
+const CHAR_WIDTH: f64 = 0.6;
+const CELL_PADDING: f64 = 4.0;
+
+enum Block {
+    Text(String),
+    Image(PdfImage),
+    Table(Vec<Vec<String>>),
+}
+
+struct PdfImage {
+    width: usize,
+    height: usize,
+    /// Either 1 or 3
+    channels: usize,
+    pixels: Vec<u8>,
+    alpha: Option<Vec<u8>>,
+}
+
+fn blocks(doc: Value, env: &Uiua) -> UiuaResult<Vec<Block>> {
+    match doc {
+        Value::Box(arr) if arr.rank() == 1 => (arr.data.into_iter())
+            .map(|Boxed(val)| block(val, env))
+            .collect(),
+        doc => Ok(vec![block(doc, env)?]),
+    }
+}
+
+fn block(val: Value, env: &Uiua) -> UiuaResult<Block> {
+    Ok(match val {
+        Value::Char(arr) if arr.rank() <= 1 => Block::Text(arr.data.iter().collect()),
+        Value::Char(arr) if arr.rank() == 2 => {
+            let lines: Vec<String> = arr.row_slices().map(|row| row.iter().collect()).collect();
+            Block::Text(lines.join("\n"))
+        }
+        Value::Box(arr) if arr.rank() == 2 => {
+            let width = arr.row_len();
+            let cells: Vec<String> = (arr.data.iter())
+                .map(|Boxed(val)| match val {
+                    Value::Char(arr) if arr.rank() <= 1 => arr.data.iter().collect(),
+                    val => val.format(),
+                })
+                .map(|cell| cell.replace('\n', " "))
+                .collect();
+            Block::Table(cells.chunks(width.max(1)).map(<[_]>::to_vec).collect())
+        }
+        val @ (Value::Num(_) | Value::Byte(_)) if matches!(val.rank(), 2 | 3) => {
+            let arr = val.as_number_array::<f64>(env, "Image must be numbers")?;
+            let (height, width, channels) = match *arr.shape().dims() {
+                [h, w] => (h, w, 1),
+                [h, w, c @ 1..=4] => (h, w, c),
+                _ => {
+                    return Err(env.error(format!(
+                        "Image block must have 1 to 4 color channels, but its shape is {}",
+                        arr.shape()
+                    )))
+                }
+            };
+            let byte = |n: f64| (n * 255.0).round().clamp(0.0, 255.0) as u8;
+            let (color, alpha) = match channels {
+                1 | 2 => (1, channels == 2),
+                _ => (3, channels == 4),
+            };
+            let mut pixels = Vec::with_capacity(width * height * color);
+            let mut alphas = Vec::new();
+            for px in arr.data.chunks_exact(channels) {
+                pixels.extend(px[..color].iter().map(|&n| byte(n)));
+                if alpha {
+                    alphas.push(byte(px[channels - 1]));
+                }
+            }
+            Block::Image(PdfImage {
+                width,
+                height,
+                channels: color,
+                pixels,
+                alpha: alpha.then_some(alphas),
+            })
+        }
+        val => {
+            return Err(env.error(format!(
+                "PDF blocks must be strings, image arrays, or rank 2 box arrays, \
+                but one is a rank {} {} array",
+                val.rank(),
+                val.type_name()
+            )))
+        }
+    })
+}
+
+/// Lays out blocks on pages
+struct Layout {
+    pages: Vec<String>,
+    images: Vec<(PdfImage, usize)>,
+    /// The distance from the top of the content area
+    y: f64,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            pages: vec![String::new()],
+            images: Vec::new(),
+            y: 0.0,
+        }
+    }
+    fn ops(&mut self) -> &mut String {
+        self.pages.last_mut().unwrap()
+    }
+    /// Make sure there is room for something, starting a new page if there isn't
+    fn reserve(&mut self, height: f64) {
+        if self.y > 0.0 && self.y + height > CONTENT_HEIGHT {
+            self.pages.push(String::new());
+            self.y = 0.0;
+        }
+    }
+    /// Get the PDF y coordinate of a distance from the top of the content area
+    fn pdf_y(&self, y: f64) -> f64 {
+        PAGE_HEIGHT - MARGIN - y
+    }
+    fn text(&mut self, x: f64, baseline: f64, size: f64, text: &str) {
+        let y = self.pdf_y(baseline);
+        let text = escape_text(text);
+        _ = writeln!(
+            self.ops(),
+            "BT /F1 {size:.2} Tf {x:.2} {y:.2} Td ({text}) Tj ET"
+        );
+    }
+    fn lay_out(mut self, blocks: Vec<Block>) -> Self {
+        let line = FONT_SIZE * LINE_HEIGHT;
+        for (i, block) in blocks.into_iter().enumerate() {
+            if i > 0 {
+                self.y += line / 2.0;
+            }
+            match block {
+                Block::Text(text) => {
+                    let max_chars = (CONTENT_WIDTH / (CHAR_WIDTH * FONT_SIZE)) as usize;
+                    for line_text in text.lines().flat_map(|l| wrap(l, max_chars)) {
+                        self.reserve(line);
+                        self.text(MARGIN, self.y + FONT_SIZE, FONT_SIZE, &line_text);
+                        self.y += line;
+                    }
+                }
+                Block::Image(image) => {
+                    let (w, h) = (image.width as f64, image.height as f64);
+                    let scale = (CONTENT_WIDTH / w).min(CONTENT_HEIGHT / h).min(1.0);
+                    let (w, h) = (w * scale, h * scale);
+                    self.reserve(h);
+                    let y = self.pdf_y(self.y + h);
+                    let index = self.images.len();
+                    _ = writeln!(
+                        self.ops(),
+                        "q {w:.2} 0 0 {h:.2} {MARGIN} {y:.2} cm /Im{index} Do Q"
+                    );
+                    let page = self.pages.len() - 1;
+                    self.images.push((image, page));
+                    self.y += h;
+                }
+                Block::Table(rows) => {
+                    let cols = rows.iter().map(Vec::len).max().unwrap_or(0);
+                    let widths: Vec<usize> = (0..cols)
+                        .map(|c| {
+                            let cells = rows.iter().filter_map(|row| row.get(c));
+                            cells.map(|s| s.chars().count()).max().unwrap_or(0).max(1)
+                        })
+                        .collect();
+                    // Shrink the font to fit the table on the page
+                    let chars: usize = widths.iter().sum();
+                    let padding = 2.0 * CELL_PADDING * cols as f64;
+                    let size = ((CONTENT_WIDTH - padding) / (CHAR_WIDTH * chars as f64))
+                        .clamp(4.0, FONT_SIZE);
+                    let row_height = size * LINE_HEIGHT + CELL_PADDING;
+                    for row in rows {
+                        self.reserve(row_height);
+                        let mut x = MARGIN;
+                        let top = self.y;
+                        let bottom = self.pdf_y(top + row_height);
+                        for (c, &chars) in widths.iter().enumerate() {
+                            let width = chars as f64 * CHAR_WIDTH * size + 2.0 * CELL_PADDING;
+                            _ = writeln!(
+                                self.ops(),
+                                "0.5 w {x:.2} {bottom:.2} {width:.2} {row_height:.2} re S"
+                            );
+                            if let Some(cell) = row.get(c) {
+                                let baseline = top + CELL_PADDING / 2.0 + size;
+                                self.text(x + CELL_PADDING, baseline, size, cell);
+                            }
+                            x += width;
+                        }
+                        self.y += row_height;
+                    }
+                }
+            }
+        }
+        self
+    }
+    /// Write the PDF file
+    fn finish(self) -> Vec<u8> {
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        let mut add = |obj: Vec<u8>| {
+            objects.push(obj);
+            objects.len()
+        };
+        let catalog = add(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        let pages_id = add(Vec::new());
+        let font = add(b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier \
+            /Encoding /WinAnsiEncoding >>"
+            .to_vec());
+        let mut image_ids = Vec::new();
+        for (image, _) in &self.images {
+            let color_space = if image.channels == 1 {
+                "/DeviceGray"
+            } else {
+                "/DeviceRGB"
+            };
+            let dict = |color_space: &str, extra: &str| {
+                format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                    /ColorSpace {color_space} /BitsPerComponent 8{extra}",
+                    image.width, image.height
+                )
+            };
+            let mask =
+                (image.alpha.as_ref()).map(|alpha| add(stream(&dict("/DeviceGray", ""), alpha)));
+            let extra = mask.map_or(String::new(), |id| format!(" /SMask {id} 0 R"));
+            image_ids.push(add(stream(&dict(color_space, &extra), &image.pixels)));
+        }
+        let mut page_ids = Vec::new();
+        for (page, ops) in self.pages.iter().enumerate() {
+            let contents = add(stream("", ops.as_bytes()));
+            let mut xobjects = String::new();
+            for (i, ((_, image_page), id)) in self.images.iter().zip(&image_ids).enumerate() {
+                if *image_page == page {
+                    _ = write!(xobjects, " /Im{i} {id} 0 R");
+                }
+            }
+            let page = format!(
+                "<< /Type /Page /Parent {pages_id} 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                /Resources << /Font << /F1 {font} 0 R >> /XObject <<{xobjects} >> >> \
+                /Contents {contents} 0 R >>"
+            );
+            page_ids.push(add(page.into_bytes()));
+        }
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{id} 0 R")).collect();
+        objects[pages_id - 1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            page_ids.len()
+        )
+        .into_bytes();
+
+        let mut bytes = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(bytes.len());
+            bytes.extend(format!("{} 0 obj\n", i + 1).as_bytes());
+            bytes.extend(obj);
+            bytes.extend(b"\nendobj\n");
+        }
+        let xref = bytes.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {catalog} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        bytes.extend(table.into_bytes());
+        bytes
+    }
+}
+
+/// Make a stream object, compressing it if possible
+fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+    #[cfg(feature = "pdf")]
+    let (data, filter) = (
+        miniz_oxide::deflate::compress_to_vec_zlib(data, 6),
+        " /Filter /FlateDecode",
+    );
+    #[cfg(not(feature = "pdf"))]
+    let (data, filter) = (data.to_vec(), "");
+    let mut obj = format!("<< {dict}{filter} /Length {} >>\nstream\n", data.len()).into_bytes();
+    obj.extend(data);
+    obj.extend(b"\nendstream");
+    obj
+}
+
+/// Split a line into lines of at most some number of characters, breaking at spaces if possible
+fn wrap(line: &str, max: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut curr = String::new();
+    let mut curr_len = 0;
+    for word in line.split(' ') {
+        let len = word.chars().count();
+        if curr_len > 0 && curr_len + 1 + len > max {
+            lines.push(std::mem::take(&mut curr));
+            curr_len = 0;
+        } else if curr_len > 0 {
+            curr.push(' ');
+            curr_len += 1;
+        }
+        let mut chars = word.chars();
+        let mut len = len;
+        // Break words that are too long for a line
+        while curr_len + len > max {
+            curr.extend(chars.by_ref().take(max - curr_len));
+            lines.push(std::mem::take(&mut curr));
+            len -= max - curr_len;
+            curr_len = 0;
+        }
+        curr.extend(chars);
+        curr_len += len;
+    }
+    lines.push(curr);
+    lines
+}
+
+/// The characters of Windows-1252 that are not in Latin-1
+const WIN_ANSI: [(u8, char); 27] = [
+    (0x80, '€'),
+    (0x82, '‚'),
+    (0x83, 'ƒ'),
+    (0x84, '„'),
+    (0x85, '…'),
+    (0x86, '†'),
+    (0x87, '‡'),
+    (0x88, 'ˆ'),
+    (0x89, '‰'),
+    (0x8A, 'Š'),
+    (0x8B, '‹'),
+    (0x8C, 'Œ'),
+    (0x8E, 'Ž'),
+    (0x91, '‘'),
+    (0x92, '’'),
+    (0x93, '“'),
+    (0x94, '”'),
+    (0x95, '•'),
+    (0x96, '–'),
+    (0x97, '—'),
+    (0x98, '˜'),
+    (0x99, '™'),
+    (0x9A, 'š'),
+    (0x9B, '›'),
+    (0x9C, 'œ'),
+    (0x9E, 'ž'),
+    (0x9F, 'Ÿ'),
+];
+
+/// Escape text for a PDF string in WinAnsiEncoding
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+                continue;
+            }
+            '\t' => {
+                escaped.push_str("    ");
+                continue;
+            }
+            ' '..='~' => {
+                escaped.push(c);
+                continue;
+            }
+            '\u{A0}'..='\u{FF}' => c as u8,
+            c => match WIN_ANSI.iter().find(|(_, wc)| *wc == c) {
+                Some((byte, _)) => *byte,
+                None => b'?',
+            },
+        };
+        _ = write!(escaped, "\\{byte:03o}");
+    }
+    escaped
+}
+
+/* Extraction */
+
+#[derive(Debug, Clone, PartialEq)]
+enum Obj {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(Vec<u8>),
+    Name(String),
+    Array(Vec<Obj>),
+    Dict(HashMap<String, Obj>),
+    Ref(u32),
+    Stream(HashMap<String, Obj>, Vec<u8>),
+    /// A keyword, like a content stream operator
+    Keyword(String),
+}
+
+impl Obj {
+    fn dict(&self) -> Option<&HashMap<String, Obj>> {
+        match self {
+            Obj::Dict(dict) | Obj::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+    fn num(&self) -> Option<f64> {
+        match self {
+            Obj::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+    fn name(&self) -> Option<&str> {
+        match self {
+            Obj::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// A parser for the PDF object syntax
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+fn is_delimiter(b: u8) -> bool {
+    b"()<>[]{}/%".contains(&b)
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Parser { bytes, pos: 0 }
+    }
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == 0 {
+                self.pos += 1;
+            } else if b == b'%' {
+                while !matches!(self.peek(), None | Some(b'\n' | b'\r')) {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+    fn regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == 0 || is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+    /// Parse an object, resolving `n g R` references
+    fn object(&mut self) -> Option<Obj> {
+        let obj = self.simple()?;
+        if let Obj::Num(n) = obj {
+            let start = self.pos;
+            if let Some(Obj::Num(_)) = self.simple() {
+                if let Some(Obj::Keyword(kw)) = self.simple() {
+                    if kw == "R" {
+                        return Some(Obj::Ref(n as u32));
+                    }
+                }
+            }
+            self.pos = start;
+        }
+        Some(obj)
+    }
+    fn simple(&mut self) -> Option<Obj> {
+        self.skip_whitespace();
+        let b = self.peek()?;
+        Some(match b {
+            b'/' => {
+                self.pos += 1;
+                let raw = self.regular();
+                let mut name = Vec::with_capacity(raw.len());
+                let mut i = 0;
+                while i < raw.len() {
+                    // Names can have #xx hex escapes
+                    if raw[i] == b'#' && i + 2 < raw.len() + 1 {
+                        if let Some(byte) = (raw.get(i + 1..i + 3)).and_then(|hex| {
+                            u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+                        }) {
+                            name.push(byte);
+                            i += 3;
+                            continue;
+                        }
+                    }
+                    name.push(raw[i]);
+                    i += 1;
+                }
+                Obj::Name(String::from_utf8_lossy(&name).into_owned())
+            }
+            b'(' => Obj::Str(self.literal_string()),
+            b'<' if self.bytes.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                let mut dict = HashMap::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.bytes[self.pos..].starts_with(b">>") {
+                        self.pos += 2;
+                        break;
+                    }
+                    let Obj::Name(key) = self.simple()? else {
+                        return None;
+                    };
+                    let value = self.object()?;
+                    dict.insert(key, value);
+                }
+                // Check for a stream
+                let mut after = Parser {
+                    bytes: self.bytes,
+                    pos: self.pos,
+                };
+                after.skip_whitespace();
+                if after.bytes[after.pos..].starts_with(b"stream") {
+                    let mut start = after.pos + 6;
+                    if self.bytes.get(start) == Some(&b'\r') {
+                        start += 1;
+                    }
+                    if self.bytes.get(start) == Some(&b'\n') {
+                        start += 1;
+                    }
+                    // Trust the length if it ends where it should, otherwise search for the end
+                    let end = (dict.get("Length").and_then(Obj::num))
+                        .map(|len| start + len as usize)
+                        .filter(|&end| {
+                            let mut p = Parser {
+                                bytes: self.bytes,
+                                pos: end.min(self.bytes.len()),
+                            };
+                            p.skip_whitespace();
+                            p.bytes[p.pos..].starts_with(b"endstream")
+                        })
+                        .or_else(|| {
+                            let rest = &self.bytes[start.min(self.bytes.len())..];
+                            let i = find(rest, b"endstream")?;
+                            let mut end = start + i;
+                            while end > start && matches!(self.bytes[end - 1], b'\r' | b'\n') {
+                                end -= 1;
+                            }
+                            Some(end)
+                        })?;
+                    let data = self.bytes[start..end].to_vec();
+                    self.pos = end;
+                    self.skip_whitespace();
+                    if self.bytes[self.pos..].starts_with(b"endstream") {
+                        self.pos += 9;
+                    }
+                    return Some(Obj::Stream(dict, data));
+                }
+                Obj::Dict(dict)
+            }
+            b'<' => {
+                self.pos += 1;
+                let mut hex = Vec::new();
+                while let Some(b) = self.peek() {
+                    self.pos += 1;
+                    if b == b'>' {
+                        break;
+                    }
+                    if b.is_ascii_hexdigit() {
+                        hex.push(b);
+                    }
+                }
+                if hex.len() % 2 == 1 {
+                    hex.push(b'0');
+                }
+                let bytes = (hex.chunks_exact(2))
+                    .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+                    .collect();
+                Obj::Str(bytes)
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        None => break,
+                        _ => items.push(self.object()?),
+                    }
+                }
+                Obj::Array(items)
+            }
+            b']' | b'>' | b')' | b'{' | b'}' => {
+                self.pos += 1;
+                Obj::Keyword((b as char).into())
+            }
+            _ => {
+                let word = self.regular();
+                if word.is_empty() {
+                    self.pos += 1;
+                    return Some(Obj::Null);
+                }
+                let word = String::from_utf8_lossy(word);
+                match &*word {
+                    "true" => Obj::Bool(true),
+                    "false" => Obj::Bool(false),
+                    "null" => Obj::Null,
+                    _ => match word.parse::<f64>() {
+                        Ok(n) if !word.starts_with(['i', 'I', 'n', 'N']) => Obj::Num(n),
+                        _ => Obj::Keyword(word.into_owned()),
+                    },
+                }
+            }
+        })
+    }
+    fn literal_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut s = Vec::new();
+        let mut depth = 0;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    s.push(b);
+                }
+                b')' if depth == 0 => break,
+                b')' => {
+                    depth -= 1;
+                    s.push(b);
+                }
+                b'\\' => {
+                    let Some(e) = self.peek() else { break };
+                    self.pos += 1;
+                    match e {
+                        b'n' => s.push(b'\n'),
+                        b'r' => s.push(b'\r'),
+                        b't' => s.push(b'\t'),
+                        b'b' => s.push(8),
+                        b'f' => s.push(12),
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut n = (e - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        n = n * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            s.push(n as u8);
+                        }
+                        e => s.push(e),
+                    }
+                }
+                b => s.push(b),
+            }
+        }
+        s
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// A parsed PDF document
+struct Document {
+    objects: HashMap<u32, Obj>,
+}
+
+/// A page and the resources it inherits
+struct Page {
+    dict: HashMap<String, Obj>,
+    resources: Option<Obj>,
+}
+
+impl Document {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(b"%PDF") {
+            return Err("Bytes are not a PDF document".into());
+        }
+        // Objects are found by scanning rather than with the cross-reference table,
+        // which makes damaged files readable and later updates override earlier ones
+        let obj_start = Regex::new(r"(\d+)\s+\d+\s+obj\b").unwrap();
+        let mut objects = HashMap::new();
+        for caps in obj_start.captures_iter(bytes) {
+            let id = String::from_utf8_lossy(&caps[1]).parse::<u32>();
+            let Ok(id) = id else { continue };
+            let mut parser = Parser::new(bytes);
+            parser.pos = caps.get(0).unwrap().end();
+            if let Some(obj) = parser.object() {
+                objects.insert(id, obj);
+            }
+        }
+        let mut doc = Document { objects };
+        // Unpack compressed object streams
+        let streams: Vec<Obj> = (doc.objects.values())
+            .filter(|obj| {
+                let dict = obj.dict();
+                dict.and_then(|d| d.get("Type")).and_then(Obj::name) == Some("ObjStm")
+            })
+            .cloned()
+            .collect();
+        for stream in streams {
+            let Obj::Stream(dict, _) = &stream else {
+                continue;
+            };
+            let Some(data) = doc.stream_data(&stream)? else {
+                continue;
+            };
+            let n = doc.get_num(dict, "N").unwrap_or(0.0) as usize;
+            let first = doc.get_num(dict, "First").unwrap_or(0.0) as usize;
+            let mut header = Parser::new(&data);
+            // Each entry takes at least 4 bytes, so a bogus count cannot over-allocate
+            let mut entries = Vec::with_capacity(n.min(data.len() / 4));
+            for _ in 0..n {
+                let (Some(Obj::Num(id)), Some(Obj::Num(offset))) =
+                    (header.simple(), header.simple())
+                else {
+                    break;
+                };
+                entries.push((id as u32, offset as usize));
+            }
+            for (id, offset) in entries {
+                let mut parser = Parser::new(&data);
+                match first.checked_add(offset) {
+                    Some(pos) if pos < data.len() => parser.pos = pos,
+                    _ => continue,
+                }
+                if let Some(obj) = parser.object() {
+                    doc.objects.entry(id).or_insert(obj);
+                }
+            }
+        }
+        Ok(doc)
+    }
+    fn resolve<'a>(&'a self, mut obj: &'a Obj) -> &'a Obj {
+        for _ in 0..32 {
+            match obj {
+                Obj::Ref(id) => obj = self.objects.get(id).unwrap_or(&Obj::Null),
+                _ => break,
+            }
+        }
+        obj
+    }
+    fn get<'a>(&'a self, dict: &'a HashMap<String, Obj>, key: &str) -> Option<&'a Obj> {
+        dict.get(key).map(|obj| self.resolve(obj))
+    }
+    fn get_num(&self, dict: &HashMap<String, Obj>, key: &str) -> Option<f64> {
+        self.get(dict, key).and_then(Obj::num)
+    }
+    /// Decode the data of a stream
+    ///
+    /// Returns `None` if a filter is not supported
+    fn stream_data(&self, obj: &Obj) -> Result<Option<Vec<u8>>, String> {
+        let Obj::Stream(dict, data) = self.resolve(obj) else {
+            return Ok(None);
+        };
+        let mut data = data.clone();
+        let filters = match self.get(dict, "Filter") {
+            Some(Obj::Name(name)) => vec![name.as_str()],
+            Some(Obj::Array(names)) => names
+                .iter()
+                .filter_map(|name| self.resolve(name).name())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let params = match self.get(dict, "DecodeParms") {
+            Some(Obj::Array(params)) => params.first().map(|p| self.resolve(p)),
+            params => params,
+        };
+        for filter in filters {
+            data = match filter {
+                "FlateDecode" | "Fl" => {
+                    let Some(data) = inflate(&data) else {
+                        return Ok(None);
+                    };
+                    match params.and_then(Obj::dict) {
+                        Some(params) => match self.unpredict(data, params)? {
+                            Some(data) => data,
+                            None => return Ok(None),
+                        },
+                        None => data,
+                    }
+                }
+                "ASCIIHexDecode" | "AHx" => {
+                    let hex: Vec<u8> = (data.iter().copied())
+                        .take_while(|&b| b != b'>')
+                        .filter(u8::is_ascii_hexdigit)
+                        .collect();
+                    (hex.chunks(2))
+                        .map(|pair| {
+                            let s = std::str::from_utf8(pair).unwrap();
+                            u8::from_str_radix(s, 16).unwrap() << (4 * (2 - pair.len()))
+                        })
+                        .collect()
+                }
+                _ => return Ok(None),
+            };
+        }
+        Ok(Some(data))
+    }
+    /// Undo PNG predictors
+    ///
+    /// Returns `None` if the predictor is not supported
+    fn unpredict(
+        &self,
+        data: Vec<u8>,
+        params: &HashMap<String, Obj>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let predictor = self.get_num(params, "Predictor").unwrap_or(1.0) as usize;
+        if predictor < 10 {
+            return Ok((predictor == 1).then_some(data));
+        }
+        let colors = self.get_num(params, "Colors").unwrap_or(1.0) as usize;
+        let bpc = self.get_num(params, "BitsPerComponent").unwrap_or(8.0) as usize;
+        let columns = self.get_num(params, "Columns").unwrap_or(1.0) as usize;
+        let pixel_bits = (colors.checked_mul(bpc)).ok_or("PDF predictor pixels are too large")?;
+        let row_bits =
+            (pixel_bits.checked_mul(columns)).ok_or("PDF predictor rows are too large")?;
+        let bpp = pixel_bits.div_ceil(8).max(1);
+        let row_len = row_bits.div_ceil(8);
+        if !data.is_empty() && row_len >= data.len() {
+            return Err(format!(
+                "PDF predictor rows are {row_len} bytes, \
+                but the stream only has {} bytes",
+                data.len()
+            ));
+        }
+        let mut out = Vec::with_capacity(data.len());
+        let mut prev = vec![0u8; row_len];
+        for row in data.chunks(row_len + 1) {
+            let Some((&filter, row)) = row.split_first() else {
+                return Ok(None);
+            };
+            let mut curr = row.to_vec();
+            curr.resize(row_len, 0);
+            for i in 0..row_len {
+                let left = if i >= bpp { curr[i - bpp] } else { 0 };
+                let up = prev[i];
+                let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+                curr[i] = curr[i].wrapping_add(match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => {
+                        let p = left as i16 + up as i16 - up_left as i16;
+                        let (pa, pb, pc) = (
+                            (p - left as i16).abs(),
+                            (p - up as i16).abs(),
+                            (p - up_left as i16).abs(),
+                        );
+                        if pa <= pb && pa <= pc {
+                            left
+                        } else if pb <= pc {
+                            up
+                        } else {
+                            up_left
+                        }
+                    }
+                    _ => return Ok(None),
+                });
+            }
+            out.extend(&curr);
+            prev = curr;
+        }
+        Ok(Some(out))
+    }
+    /// Get the pages in order
+    fn pages(&self) -> Vec<Page> {
+        let catalog = (self.objects.values())
+            .filter_map(Obj::dict)
+            .find(|dict| dict.get("Type").and_then(Obj::name) == Some("Catalog"));
+        let mut pages = Vec::new();
+        if let Some(root) = catalog.and_then(|catalog| self.get(catalog, "Pages")) {
+            self.collect_pages(root, None, &mut pages, 0);
+        }
+        pages
+    }
+    fn collect_pages(
+        &self,
+        node: &Obj,
+        resources: Option<&Obj>,
+        pages: &mut Vec<Page>,
+        depth: usize,
+    ) {
+        let Some(dict) = node.dict() else {
+            return;
+        };
+        if depth > 64 {
+            return;
+        }
+        let resources = self.get(dict, "Resources").or(resources);
+        match self.get(dict, "Kids") {
+            Some(Obj::Array(kids)) => {
+                for kid in kids {
+                    self.collect_pages(self.resolve(kid), resources, pages, depth + 1);
+                }
+            }
+            _ => pages.push(Page {
+                dict: dict.clone(),
+                resources: resources.cloned(),
+            }),
+        }
+    }
+    /// Extract the text and images of a page
+    fn page_contents(&self, page: &Page) -> Result<(String, Vec<Array<f64>>), String> {
+        let mut data = Vec::new();
+        match self.get(&page.dict, "Contents") {
+            Some(Obj::Array(streams)) => {
+                for stream in streams {
+                    data.extend(self.stream_data(stream)?.unwrap_or_default());
+                    data.push(b'\n');
+                }
+            }
+            Some(stream) => data = self.stream_data(stream)?.unwrap_or_default(),
+            None => {}
+        }
+        let mut extractor = Extractor {
+            doc: self,
+            text: String::new(),
+            images: Vec::new(),
+            seen_images: Vec::new(),
+            line_y: None,
+            moved: false,
+        };
+        extractor.run(&data, page.resources.as_ref(), 0)?;
+        Ok((extractor.text.trim().into(), extractor.images))
+    }
+}
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    #[cfg(feature = "pdf")]
+    {
+        use miniz_oxide::inflate::{
+            decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit,
+        };
+        // Deflate cannot expand data by more than this ratio,
+        // so anything bigger is malformed
+        let limit = data.len().saturating_mul(1032);
+        decompress_to_vec_zlib_with_limit(data, limit)
+            .or_else(|_| decompress_to_vec_with_limit(data, limit))
+            .ok()
+    }
+    #[cfg(not(feature = "pdf"))]
+    {
+        _ = data;
+        None
+    }
+}
+
+/// Walks content streams to extract text and images
+struct Extractor<'a> {
+    doc: &'a Document,
+    text: String,
+    images: Vec<Array<f64>>,
+    seen_images: Vec<*const Obj>,
+    line_y: Option<f64>,
+    moved: bool,
+}
+
+impl Extractor<'_> {
+    fn run(&mut self, content: &[u8], resources: Option<&Obj>, depth: usize) -> Result<(), String> {
+        if depth > 8 {
+            return Ok(());
+        }
+        let doc = self.doc;
+        let resources = resources.map(|r| doc.resolve(r)).and_then(Obj::dict);
+        let resource = |kind: &str, name: &str| {
+            let dict = doc.get(resources?, kind)?.dict()?;
+            doc.get(dict, name)
+        };
+        let mut parser = Parser::new(content);
+        let mut operands: Vec<Obj> = Vec::new();
+        let mut font: Option<FontDecoder> = None;
+        let (mut x, mut y, mut leading) = (0.0, 0.0, 0.0);
+        while let Some(obj) = parser.object() {
+            let Obj::Keyword(op) = obj else {
+                operands.push(obj);
+                continue;
+            };
+            let num = |i: usize| operands.get(i).and_then(Obj::num).unwrap_or(0.0);
+            match op.as_str() {
+                "BT" => (x, y) = (0.0, 0.0),
+                "Tf" => {
+                    font = (operands.first().and_then(Obj::name))
+                        .and_then(|name| resource("Font", name))
+                        .and_then(Obj::dict)
+                        .map(|dict| FontDecoder::new(doc, dict))
+                        .transpose()?;
+                }
+                "TL" => leading = num(0),
+                "Td" | "TD" => {
+                    if op == "TD" {
+                        leading = -num(1);
+                    }
+                    (x, y) = (x + num(0), y + num(1));
+                    self.move_to(y);
+                }
+                "Tm" => {
+                    (x, y) = (num(4), num(5));
+                    self.move_to(y);
+                }
+                "T*" => {
+                    y -= leading;
+                    self.move_to(y);
+                }
+                "Tj" | "'" | "\"" => {
+                    if op != "Tj" {
+                        y -= leading;
+                        self.move_to(y);
+                    }
+                    if let Some(Obj::Str(s)) = operands.last() {
+                        self.show(s, font.as_ref());
+                    }
+                }
+                "TJ" => {
+                    if let Some(Obj::Array(items)) = operands.first() {
+                        for item in items {
+                            match item {
+                                Obj::Str(s) => self.show(s, font.as_ref()),
+                                // Big negative adjustments are gaps between words
+                                Obj::Num(n) if *n < -200.0 && !self.text.ends_with([' ', '\n']) => {
+                                    self.text.push(' ')
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                "Do" => {
+                    let name = operands.first().and_then(Obj::name);
+                    if let Some(xobject) = name.and_then(|name| resource("XObject", name)) {
+                        self.xobject(xobject, depth)?;
+                    }
+                }
+                "BI" => {
+                    // Skip inline images
+                    if let Some(i) = find(&content[parser.pos..], b"ID") {
+                        parser.pos += i + 2;
+                        let rest = &content[parser.pos..];
+                        let end = (rest
+                            .windows(3)
+                            .position(|w| w[0].is_ascii_whitespace() && &w[1..] == b"EI"))
+                        .map_or(rest.len(), |i| i + 3);
+                        parser.pos += end;
+                    }
+                }
+                _ => {}
+            }
+            _ = x;
+            operands.clear();
+        }
+        Ok(())
+    }
+    /// Note a change in text position
+    fn move_to(&mut self, y: f64) {
+        self.moved = true;
+        if self.line_y.is_some_and(|line_y| (line_y - y).abs() > 1.0) {
+            self.line_y = Some(y);
+            if !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+        } else if self.line_y.is_none() {
+            self.line_y = Some(y);
+        }
+    }
+    fn show(&mut self, s: &[u8], font: Option<&FontDecoder>) {
+        if self.moved && !self.text.is_empty() && !self.text.ends_with([' ', '\n']) {
+            self.text.push(' ');
+        }
+        self.moved = false;
+        match font {
+            Some(font) => font.decode(s, &mut self.text),
+            None => self.text.extend(s.iter().map(|&b| win_ansi_char(b))),
+        }
+    }
+    fn xobject(&mut self, xobject: &Obj, depth: usize) -> Result<(), String> {
+        let Obj::Stream(dict, _) = xobject else {
+            return Ok(());
+        };
+        match self.doc.get(dict, "Subtype").and_then(Obj::name) {
+            Some("Image") => {
+                if self.seen_images.contains(&(xobject as *const Obj)) {
+                    return Ok(());
+                }
+                self.seen_images.push(xobject);
+                if let Some(image) = self.doc.image(xobject)? {
+                    self.images.push(image);
+                }
+            }
+            Some("Form") => {
+                if let Some(data) = self.doc.stream_data(xobject)? {
+                    self.run(&data, dict.get("Resources"), depth + 1)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Document {
+    /// Decode an image XObject
+    ///
+    /// Returns `None` if the image format is not supported
+    fn image(&self, obj: &Obj) -> Result<Option<Array<f64>>, String> {
+        let Obj::Stream(dict, raw) = obj else {
+            return Ok(None);
+        };
+        let filter = match self.get(dict, "Filter") {
+            Some(Obj::Name(name)) => Some(name.as_str()),
+            Some(Obj::Array(names)) => names.last().and_then(|n| self.resolve(n).name()),
+            _ => None,
+        };
+        if filter == Some("DCTDecode") {
+            #[cfg(feature = "image")]
+            return Ok(crate::encode::image_bytes_to_array(raw, false).ok());
+            #[cfg(not(feature = "image"))]
+            {
+                _ = raw;
+                return Ok(None);
+            }
+        }
+        let (Some(width), Some(height)) =
+            (self.get_num(dict, "Width"), self.get_num(dict, "Height"))
+        else {
+            return Ok(None);
+        };
+        let (width, height) = (width as usize, height as usize);
+        if self.get_num(dict, "BitsPerComponent") != Some(8.0) {
+            return Ok(None);
+        }
+        let channels = match self.get(dict, "ColorSpace") {
+            Some(Obj::Name(name)) => match name.as_str() {
+                "DeviceGray" | "G" => 1,
+                "DeviceRGB" | "RGB" => 3,
+                "DeviceCMYK" | "CMYK" => 4,
+                _ => return Ok(None),
+            },
+            Some(Obj::Array(items)) if items.first().and_then(Obj::name) == Some("ICCBased") => {
+                let profile = (items.get(1))
+                    .and_then(|profile| self.resolve(profile).dict())
+                    .and_then(|profile| self.get_num(profile, "N"));
+                match profile {
+                    Some(n) => n as usize,
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        let len = (width.checked_mul(height))
+            .and_then(|n| n.checked_mul(channels))
+            .ok_or_else(|| format!("PDF image of {width}×{height} pixels is too large"))?;
+        let Some(data) = self.stream_data(obj)? else {
+            return Ok(None);
+        };
+        if data.len() < len {
+            return Ok(None);
+        }
+        let data = &data[..len];
+        let (shape, data): (Shape, EcoVec<f64>) = match channels {
+            1 => (
+                Shape::from_iter([height, width]),
+                data.iter().map(|&b| b as f64 / 255.0).collect(),
+            ),
+            3 => (
+                Shape::from_iter([height, width, 3]),
+                data.iter().map(|&b| b as f64 / 255.0).collect(),
+            ),
+            4 => (
+                Shape::from_iter([height, width, 3]),
+                (data.chunks_exact(4))
+                    .flat_map(|cmyk| {
+                        let k = 1.0 - cmyk[3] as f64 / 255.0;
+                        [0, 1, 2].map(|i| (1.0 - cmyk[i] as f64 / 255.0) * k)
+                    })
+                    .collect(),
+            ),
+            _ => return Ok(None),
+        };
+        Ok(Some(Array::new(shape, data)))
+    }
+}
+
+fn win_ansi_char(b: u8) -> char {
+    match WIN_ANSI.iter().find(|(wb, _)| *wb == b) {
+        Some((_, c)) => *c,
+        None => b as char,
+    }
+}
+
+/// Maps character codes in a font to text
+struct FontDecoder {
+    code_len: usize,
+    map: HashMap<u32, String>,
+}
+
+impl FontDecoder {
+    fn new(doc: &Document, font: &HashMap<String, Obj>) -> Result<Self, String> {
+        let mut decoder = FontDecoder {
+            code_len: 1,
+            map: HashMap::new(),
+        };
+        if doc.get(font, "Subtype").and_then(Obj::name) == Some("Type0") {
+            decoder.code_len = 2;
+        }
+        // Simple fonts can override codes with glyph names
+        let encoding = doc.get(font, "Encoding").and_then(Obj::dict);
+        if let Some(Obj::Array(diffs)) = encoding.and_then(|enc| doc.get(enc, "Differences")) {
+            let mut code = 0;
+            for item in diffs {
+                match doc.resolve(item) {
+                    Obj::Num(n) => code = *n as u32,
+                    Obj::Name(name) => {
+                        if let Some(text) = glyph_name_text(name) {
+                            decoder.map.insert(code, text);
+                        }
+                        code += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(obj) = font.get("ToUnicode") {
+            if let Some(cmap) = doc.stream_data(obj)? {
+                decoder.parse_cmap(&cmap);
+            }
+        }
+        Ok(decoder)
+    }
+    fn parse_cmap(&mut self, cmap: &[u8]) {
+        let mut parser = Parser::new(cmap);
+        let mut args: Vec<Obj> = Vec::new();
+        let code = |bytes: &[u8]| bytes.iter().fold(0u32, |n, &b| n << 8 | b as u32);
+        let set_len = |len: usize, code_len: &mut usize| *code_len = len.max(1);
+        while let Some(obj) = parser.object() {
+            let Obj::Keyword(kw) = obj else {
+                args.push(obj);
+                continue;
+            };
+            match kw.as_str() {
+                "endbfchar" => {
+                    for pair in args.chunks_exact(2) {
+                        if let [Obj::Str(src), Obj::Str(dst)] = pair {
+                            set_len(src.len(), &mut self.code_len);
+                            self.map.insert(code(src), utf16_text(dst));
+                        }
+                    }
+                }
+                "endbfrange" => {
+                    for triple in args.chunks_exact(3) {
+                        let [Obj::Str(lo), Obj::Str(hi), dst] = triple else {
+                            continue;
+                        };
+                        set_len(lo.len(), &mut self.code_len);
+                        let (lo, hi) = (code(lo), code(hi));
+                        for (i, c) in (lo..=hi.min(lo + 0xFFFF)).enumerate() {
+                            let text = match dst {
+                                Obj::Str(start) => {
+                                    let mut units = start.clone();
+                                    // Increment the last byte of the destination
+                                    if let Some(last) = units.last_mut() {
+                                        *last = last.wrapping_add(i as u8);
+                                    }
+                                    utf16_text(&units)
+                                }
+                                Obj::Array(dsts) => match dsts.get(i) {
+                                    Some(Obj::Str(dst)) => utf16_text(dst),
+                                    _ => continue,
+                                },
+                                _ => continue,
+                            };
+                            self.map.insert(c, text);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            args.clear();
+        }
+    }
+    fn decode(&self, bytes: &[u8], out: &mut String) {
+        for chunk in bytes.chunks(self.code_len) {
+            let code = chunk.iter().fold(0u32, |n, &b| n << 8 | b as u32);
+            match self.map.get(&code) {
+                Some(text) => out.push_str(text),
+                None if self.code_len == 1 => out.push(win_ansi_char(code as u8)),
+                None => out.push(char::REPLACEMENT_CHARACTER),
+            }
+        }
+    }
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = (bytes.chunks(2))
+        .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Get the text of a glyph name used in a font encoding
+fn glyph_name_text(name: &str) -> Option<String> {
+    if name.chars().count() == 1 {
+        return Some(name.into());
+    }
+    if let Some(hex) = name.strip_prefix("uni") {
+        let units: Vec<u16> = (hex.as_bytes().chunks(4))
+            .map(|chunk| u16::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok())
+            .collect::<Option<_>>()?;
+        return Some(String::from_utf16_lossy(&units));
+    }
+    const DIGITS: [&str; 10] = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    ];
+    if let Some(i) = DIGITS.iter().position(|&d| d == name) {
+        return Some(i.to_string());
+    }
+    Some(
+        match name {
+            "space" => " ",
+            "exclam" => "!",
+            "quotedbl" => "\"",
+            "numbersign" => "#",
+            "dollar" => "$",
+            "percent" => "%",
+            "ampersand" => "&",
+            "quotesingle" => "'",
+            "quoteright" => "’",
+            "quoteleft" => "‘",
+            "quotedblleft" => "“",
+            "quotedblright" => "”",
+            "parenleft" => "(",
+            "parenright" => ")",
+            "asterisk" => "*",
+            "plus" => "+",
+            "comma" => ",",
+            "hyphen" | "minus" => "-",
+            "period" => ".",
+            "slash" => "/",
+            "colon" => ":",
+            "semicolon" => ";",
+            "less" => "<",
+            "equal" => "=",
+            "greater" => ">",
+            "question" => "?",
+            "at" => "@",
+            "bracketleft" => "[",
+            "backslash" => "\\",
+            "bracketright" => "]",
+            "underscore" => "_",
+            "braceleft" => "{",
+            "bar" => "|",
+            "braceright" => "}",
+            "endash" => "–",
+            "emdash" => "—",
+            "bullet" => "•",
+            "ellipsis" => "…",
+            "fi" => "fi",
+            "fl" => "fl",
+            "ff" => "ff",
+            _ => return None,
+        }
+        .into(),
+    )
+}
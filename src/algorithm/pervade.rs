@@ -9,7 +9,7 @@ use ecow::eco_vec;
 use crate::{algorithm::loops::flip, array::*, Uiua, UiuaError, UiuaResult, Value};
 use crate::{Complex, Shape};
 
-use super::{multi_output, FillContext, MultiOutput};
+use super::{multi_output, ErrorContext, FillContext, MultiOutput};
 
 pub trait PervasiveFn<A, B> {
     type Output;
@@ -95,9 +95,11 @@ fn derive_new_shape(
                         }
                         Ok(_) => ad.max(bd),
                         Err(e) => {
-                            return Err(
-                                env.error(format!("Shapes {ash} and {bsh} are not compatible{e}"))
-                            )
+                            return Err(env.shape_error(
+                                format!("Shapes {ash} and {bsh} are not compatible{e}"),
+                                ash,
+                                bsh,
+                            ))
                         }
                     }
                 } else {
@@ -109,9 +111,11 @@ fn derive_new_shape(
                         }
                         Ok(_) => ad.max(bd),
                         Err(e) => {
-                            return Err(
-                                env.error(format!("Shapes {ash} and {bsh} are not compatible{e}"))
-                            )
+                            return Err(env.shape_error(
+                                format!("Shapes {ash} and {bsh} are not compatible{e}"),
+                                ash,
+                                bsh,
+                            ))
                         }
                     }
                 }
@@ -308,9 +312,11 @@ where
                                 pervade_dim(ad, bd)
                             }
                             Some(e) => {
-                                return Err(env.error(format!(
-                                    "Shapes {ash} and {bsh} are not compatible{e}"
-                                )))
+                                return Err(env.shape_error(
+                                    format!("Shapes {ash} and {bsh} are not compatible{e}"),
+                                    ash,
+                                    bsh,
+                                ))
                             }
                         }
                     }
@@ -512,6 +512,7 @@ fn generic_reduce_inner(
                         return Ok(reduce_singleton(&f.node, xs, process));
                     }
                 }
+                let start = value_fill.is_none() as usize;
                 let mut rows = xs.into_rows();
                 let mut acc = (value_fill.cloned())
                     .or_else(|| rows.next())
@@ -520,20 +521,25 @@ fn generic_reduce_inner(
                     })?;
                 acc = process(acc);
                 env.without_fill(|env| -> UiuaResult<Value> {
-                    for row in rows {
-                        env.push(process(row));
+                    for (r, row) in rows.enumerate() {
+                        let row = process(row);
+                        let shapes = [acc.shape().clone(), row.shape().clone()];
+                        env.push(row);
                         env.push(acc);
-                        env.exec(f.clone())?;
+                        (env.exec(f.clone()))
+                            .map_err(|e| e.in_iteration(Primitive::Reduce, start + r, shapes))?;
                         acc = env.pop("reduced function result")?;
                     }
                     Ok(acc)
                 })
             } else {
                 let mut new_rows = Vec::with_capacity(xs.row_count());
+                let row_shape = xs.shape().row();
                 env.without_fill(|env| -> UiuaResult {
-                    for row in xs.into_rows() {
+                    for (r, row) in xs.into_rows().enumerate() {
                         env.push(row);
-                        let val = generic_reduce_inner(f.clone(), depth - 1, process, env)?;
+                        let val = generic_reduce_inner(f.clone(), depth - 1, process, env)
+                            .map_err(|e| e.in_iteration(Primitive::Rows, r, [row_shape.clone()]))?;
                         new_rows.push(val);
                     }
                     Ok(())
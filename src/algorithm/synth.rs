@@ -0,0 +1,278 @@
+//! Audio synthesis with oscillators, envelopes, and filters
+
+use std::f64::consts::{FRAC_1_SQRT_2, TAU};
+
+use ecow::EcoVec;
+
+use crate::{Array, Uiua, UiuaResult};
+
+fn sample_rate(env: &Uiua) -> f64 {
+    env.rt.backend.audio_sample_rate() as f64
+}
+
+/// Generate a band-limited waveform at some frequencies for some times in seconds
+pub fn oscillator(env: &mut Uiua) -> UiuaResult {
+    let wave = (env.pop("waveform")?).as_string(env, "Waveform must be a string")?;
+    let freqs = (env.pop("frequency")?)
+        .as_number_array::<f64>(env, "Frequency must be an array of numbers")?;
+    let times =
+        (env.pop("times")?).as_number_array::<f64>(env, "Times must be an array of numbers")?;
+    let wave: fn(f64, f64) -> f64 = match wave.as_str() {
+        "sine" => |phase, _| (TAU * phase).sin(),
+        "saw" => |phase, dt| 2.0 * phase - 1.0 - poly_blep(phase, dt),
+        "square" => |phase, dt| {
+            let naive = if phase < 0.5 { 1.0 } else { -1.0 };
+            naive + poly_blep(phase, dt) - poly_blep((phase + 0.5).fract(), dt)
+        },
+        "triangle" => |phase, dt| {
+            // Shifted so that it starts at 0 and rises like a sine wave
+            let phase = (phase + 0.25).fract();
+            let naive = 1.0 - 4.0 * (phase - 0.5).abs();
+            naive + 4.0 * dt * (poly_blamp(phase, dt) - poly_blamp((phase + 0.5).fract(), dt))
+        },
+        _ => {
+            return Err(env.error(format!(
+                "Unknown waveform {wave:?}. \
+                Valid waveforms are \"sine\", \"saw\", \"square\", and \"triangle\"."
+            )))
+        }
+    };
+    let nyquist = sample_rate(env) / 2.0;
+    let mut data = EcoVec::with_capacity(freqs.element_count() * times.element_count());
+    for &freq in &freqs.data {
+        // Harmonics above the Nyquist frequency would only alias
+        if freq.abs() >= nyquist || freq.is_nan() {
+            data.extend(times.data.iter().map(|_| 0.0));
+            continue;
+        }
+        // A negative frequency is the same wave inverted
+        let (sign, freq) = if freq < 0.0 {
+            (-1.0, -freq)
+        } else {
+            (1.0, freq)
+        };
+        let dt = freq / (2.0 * nyquist);
+        data.extend((times.data.iter()).map(|&t| sign * wave((freq * t).rem_euclid(1.0), dt)));
+    }
+    let mut shape = freqs.shape().clone();
+    shape.extend(times.shape().iter().copied());
+    env.push(Array::new(shape, data));
+    Ok(())
+}
+
+/// The residual of a band-limited step with the discontinuity at phase 0
+fn poly_blep(phase: f64, dt: f64) -> f64 {
+    if phase < dt {
+        let t = phase / dt;
+        2.0 * t - t * t - 1.0
+    } else if phase > 1.0 - dt {
+        let t = (phase - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// The residual of a band-limited ramp with the corner at phase 0
+fn poly_blamp(phase: f64, dt: f64) -> f64 {
+    if phase < dt {
+        let t = phase / dt - 1.0;
+        -t * t * t / 3.0
+    } else if phase > 1.0 - dt {
+        let t = (phase - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+/// Call a function on each channel of some audio samples
+///
+/// Like in [`value_to_audio_channels`](crate::encode::value_to_audio_channels),
+/// a rank 2 array's rows are channels only if there are more than 5 columns
+fn map_channels(samples: &mut Array<f64>, env: &Uiua, mut f: impl FnMut(&mut [f64])) -> UiuaResult {
+    let (channels, len, channel_rows) = match *samples.shape().dims() {
+        [len] => (1, len, true),
+        [ch, len] if len > 5 => (ch, len, true),
+        [len, ch] => (ch, len, false),
+        _ => {
+            return Err(env.error(format!(
+                "Audio samples must be a rank 1 or 2 array, but its shape is {}",
+                samples.shape()
+            )))
+        }
+    };
+    let data = samples.data.as_mut_slice();
+    if channel_rows {
+        for channel in data.chunks_exact_mut(len.max(1)) {
+            f(channel);
+        }
+    } else {
+        let mut channel = vec![0.0; len];
+        for c in 0..channels {
+            for (i, sample) in channel.iter_mut().enumerate() {
+                *sample = data[i * channels + c];
+            }
+            f(&mut channel);
+            for (i, sample) in channel.iter().enumerate() {
+                data[i * channels + c] = *sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply an attack-decay-sustain-release envelope to some audio samples
+pub fn envelope(env: &mut Uiua) -> UiuaResult {
+    let adsr = (env.pop("envelope")?).as_nums(
+        env,
+        "Envelope must be a list of attack time, decay time, sustain level, and release time",
+    )?;
+    let mut samples = (env.pop("samples")?)
+        .as_number_array::<f64>(env, "Audio samples must be an array of numbers")?;
+    let &[attack, decay, sustain, release] = adsr.as_slice() else {
+        return Err(env.error(format!(
+            "Envelope must have 4 numbers for attack time, decay time, \
+            sustain level, and release time, but it has {}",
+            adsr.len()
+        )));
+    };
+    if let Some(t) = [attack, decay, release]
+        .into_iter()
+        .find(|t| t.is_nan() || *t < 0.0)
+    {
+        return Err(env.error(format!(
+            "Envelope times must be non-negative, but one is {t}"
+        )));
+    }
+    let sr = sample_rate(env);
+    let (attack, decay, release) = (attack * sr, decay * sr, release * sr);
+    map_channels(&mut samples, env, |channel| {
+        let len = channel.len() as f64;
+        let level = |i: f64| {
+            if i < attack {
+                i / attack
+            } else if i < attack + decay {
+                1.0 - (1.0 - sustain) * (i - attack) / decay
+            } else {
+                sustain
+            }
+        };
+        // The release starts from wherever the envelope is when the note ends
+        let release_start = (len - release).max(0.0);
+        let release_level = level(release_start);
+        for (i, sample) in channel.iter_mut().enumerate() {
+            let i = i as f64;
+            *sample *= if i < release_start {
+                level(i)
+            } else {
+                release_level * (len - i) / (len - release_start)
+            };
+        }
+    })?;
+    env.push(samples);
+    Ok(())
+}
+
+/// Run some audio samples through a biquad filter
+pub fn biquad(env: &mut Uiua) -> UiuaResult {
+    let kind = (env.pop("filter kind")?).as_string(env, "Filter kind must be a string")?;
+    let params = (env.pop("filter parameters")?).as_nums(
+        env,
+        "Filter parameters must be a list of frequency, Q, and gain",
+    )?;
+    let mut samples = (env.pop("samples")?)
+        .as_number_array::<f64>(env, "Audio samples must be an array of numbers")?;
+    let (freq, q, gain) = match *params {
+        [freq] => (freq, FRAC_1_SQRT_2, 0.0),
+        [freq, q] => (freq, q, 0.0),
+        [freq, q, gain] => (freq, q, gain),
+        _ => {
+            return Err(env.error(format!(
+                "Filter parameters must be 1 to 3 numbers for frequency, Q, and gain, \
+                but there are {}",
+                params.len()
+            )))
+        }
+    };
+    let sr = sample_rate(env);
+    if freq.is_nan() || freq <= 0.0 || freq >= sr / 2.0 {
+        return Err(env.error(format!(
+            "Filter frequency must be between 0 and {} (half the sample rate), but it is {freq}",
+            sr / 2.0
+        )));
+    }
+    if q.is_nan() || q <= 0.0 {
+        return Err(env.error(format!("Filter Q must be positive, but it is {q}")));
+    }
+    // Coefficients from Robert Bristow-Johnson's Audio EQ Cookbook
+    let w0 = TAU * freq / sr;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * q);
+    let a = 10f64.powf(gain / 40.0);
+    let shelf = 2.0 * a.sqrt() * alpha;
+    let [b0, b1, b2, a0, a1, a2] = match kind.as_str() {
+        "lowpass" => {
+            let b = (1.0 - cos) / 2.0;
+            [b, 2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha]
+        }
+        "highpass" => {
+            let b = (1.0 + cos) / 2.0;
+            [b, -2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha]
+        }
+        "bandpass" => [alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        "notch" => [1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        "allpass" => [
+            1.0 - alpha,
+            -2.0 * cos,
+            1.0 + alpha,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        ],
+        "peak" => [
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        ],
+        "lowshelf" => [
+            a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+            (a + 1.0) + (a - 1.0) * cos + shelf,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - shelf,
+        ],
+        "highshelf" => [
+            a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+            (a + 1.0) - (a - 1.0) * cos + shelf,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - shelf,
+        ],
+        _ => {
+            return Err(env.error(format!(
+                "Unknown filter kind {kind:?}. Valid kinds are \"lowpass\", \"highpass\", \
+                \"bandpass\", \"notch\", \"allpass\", \"peak\", \"lowshelf\", and \"highshelf\"."
+            )))
+        }
+    };
+    let [b0, b1, b2, a1, a2] = [b0, b1, b2, a1, a2].map(|c| c / a0);
+    map_channels(&mut samples, env, |channel| {
+        // Transposed direct form II
+        let (mut z1, mut z2) = (0.0, 0.0);
+        for sample in channel {
+            let x = *sample;
+            let y = b0 * x + z1;
+            z1 = b1 * x - a1 * y + z2;
+            z2 = b2 * x - a2 * y;
+            *sample = y;
+        }
+    })?;
+    env.push(samples);
+    Ok(())
+}
@@ -30,35 +30,32 @@ pub(crate) fn table_impl(f: SigNode, env: &mut Uiua) -> UiuaResult {
             if n == 2 && xs.rank() <= 1 && ys.rank() <= 1 {
                 table_list(f, xs, ys, env)
             } else {
-                if let [Node::Prim(Primitive::Mul, _), Node::Mod(Primitive::Reduce, args, _)] =
-                    f.node.as_slice()
-                {
-                    if let [sn] = args.as_slice() {
-                        if let Some((Primitive::Add, _)) = sn.node.as_flipped_primitive() {
-                            match (&xs, &ys) {
-                                (Value::Num(a), Value::Num(b)) => {
-                                    return a.matrix_mul(b, env).map(|val| env.push(val))
-                                }
-                                (Value::Num(a), Value::Byte(b)) => {
-                                    return a
-                                        .matrix_mul(&b.convert_ref(), env)
-                                        .map(|val| env.push(val))
-                                }
-                                (Value::Byte(a), Value::Num(b)) => {
-                                    return a
-                                        .convert_ref()
-                                        .matrix_mul(b, env)
-                                        .map(|val| env.push(val))
-                                }
-                                (Value::Byte(a), Value::Byte(b)) => {
-                                    return a
-                                        .convert_ref()
-                                        .matrix_mul(&b.convert_ref(), env)
-                                        .map(|val| env.push(val))
-                                }
-                                _ => {}
-                            }
+                let is_matrix_mul = match f.node.as_slice() {
+                    [Node::ImplPrim(ImplPrimitive::DotProduct, _)] => true,
+                    [Node::Prim(Primitive::Mul, _), Node::Mod(Primitive::Reduce, args, _)] => {
+                        matches!(args.as_slice(), [sn]
+                            if matches!(sn.node.as_flipped_primitive(), Some((Primitive::Add, _))))
+                    }
+                    _ => false,
+                };
+                if is_matrix_mul {
+                    match (&xs, &ys) {
+                        (Value::Num(a), Value::Num(b)) => {
+                            return a.matrix_mul(b, env).map(|val| env.push(val))
+                        }
+                        (Value::Num(a), Value::Byte(b)) => {
+                            return a.matrix_mul(&b.convert_ref(), env).map(|val| env.push(val))
+                        }
+                        (Value::Byte(a), Value::Num(b)) => {
+                            return a.convert_ref().matrix_mul(b, env).map(|val| env.push(val))
+                        }
+                        (Value::Byte(a), Value::Byte(b)) => {
+                            return a
+                                .convert_ref()
+                                .matrix_mul(&b.convert_ref(), env)
+                                .map(|val| env.push(val))
                         }
+                        _ => {}
                     }
                 }
                 generic_table(f, xs, ys, env)
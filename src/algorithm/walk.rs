@@ -0,0 +1,173 @@
+//! Walking simple functions one node at a time
+//!
+//! This is shared by [`interval`](super::interval::interval), [`gradient`](super::autodiff::gradient),
+//! and the dual number derivative of the algebra system.
+//! Function calls and the normal functions of custom inverses are inlined,
+//! and stack combinators are either evaluated on a [`StackWalker`]'s own stack or rewritten.
+
+use crate::{Assembly, Node, Primitive, SigNode};
+
+/// The maximum depth of nested function calls that will be walked
+pub const MAX_DEPTH: usize = 100;
+
+/// Why a node could not be walked
+pub enum WalkError {
+    /// Function calls were nested more than [`MAX_DEPTH`] deep
+    TooDeep,
+    /// The node is not supported
+    NotSupported(String),
+    /// A custom inverse's normal function failed to compile
+    NoInverse(String),
+}
+
+/// A way of walking the nodes of a function
+pub trait Walker {
+    type Error;
+    fn asm(&self) -> &Assembly;
+    /// The current depth of nested function calls
+    fn depth(&mut self) -> &mut usize;
+    fn error(&self, error: WalkError) -> Self::Error;
+    /// Walk a node that is not a run of nodes, a function call, a stack combinator, or a custom inverse
+    fn leaf(&mut self, node: &Node) -> Result<(), Self::Error>;
+    /// Walk one of [`Primitive::Dip`], [`Primitive::Gap`], [`Primitive::On`], [`Primitive::By`],
+    /// [`Primitive::Both`], [`Primitive::Bracket`], or [`Primitive::Fork`]
+    fn combinator(
+        &mut self,
+        prim: Primitive,
+        args: &[SigNode],
+        span: usize,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Walk a node
+pub fn walk<W: Walker>(w: &mut W, node: &Node) -> Result<(), W::Error> {
+    use Primitive::*;
+    match node {
+        Node::Run(nodes) => {
+            for node in nodes {
+                walk(w, node)?;
+            }
+        }
+        Node::Call(f, _) => {
+            if *w.depth() >= MAX_DEPTH {
+                return Err(w.error(WalkError::TooDeep));
+            }
+            let node = w.asm()[f].clone();
+            *w.depth() += 1;
+            let res = walk(w, &node);
+            *w.depth() -= 1;
+            res?
+        }
+        Node::Mod(prim @ (Dip | Gap | On | By | Both | Bracket | Fork), args, span) => {
+            w.combinator(*prim, args, *span)?
+        }
+        Node::CustomInverse(cust, _) => match &cust.normal {
+            Ok(normal) if !cust.is_obverse => walk(w, &normal.node)?,
+            Ok(_) => return Err(w.error(WalkError::NotSupported("custom inverses".into()))),
+            Err(e) => return Err(w.error(WalkError::NoInverse(e.clone()))),
+        },
+        node => w.leaf(node)?,
+    }
+    Ok(())
+}
+
+/// A [`Walker`] that evaluates functions on a stack of its own values
+pub trait StackWalker: Walker + Sized {
+    type Value: Clone;
+    fn stack(&mut self) -> &mut Vec<Self::Value>;
+    /// The error for popping from an empty stack
+    fn empty_stack(&self) -> Self::Error;
+    fn pop(&mut self) -> Result<Self::Value, Self::Error> {
+        match self.stack().pop() {
+            Some(val) => Ok(val),
+            None => Err(self.empty_stack()),
+        }
+    }
+    fn pop_n(&mut self, n: usize) -> Result<Vec<Self::Value>, Self::Error> {
+        let mut args = Vec::with_capacity(n);
+        for _ in 0..n {
+            args.push(self.pop()?);
+        }
+        Ok(args)
+    }
+    /// Evaluate a primitive that only moves values on the stack
+    ///
+    /// Returns whether the primitive is one of these.
+    fn stack_prim(&mut self, prim: Primitive) -> Result<bool, Self::Error> {
+        use Primitive::*;
+        match prim {
+            Identity => {}
+            Pop => _ = self.pop()?,
+            Dup => {
+                let a = self.pop()?;
+                self.stack().extend([a.clone(), a]);
+            }
+            Flip => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                self.stack().extend([a, b]);
+            }
+            Over => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                self.stack().extend([b.clone(), a, b]);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+    /// Evaluate a stack combinator by moving values on the stack
+    fn eval_combinator(&mut self, prim: Primitive, args: &[SigNode]) -> Result<(), Self::Error> {
+        use Primitive::*;
+        match (prim, args) {
+            (Dip, [f]) => {
+                let a = self.pop()?;
+                walk(self, &f.node)?;
+                self.stack().push(a);
+            }
+            (Gap, [f]) => {
+                self.pop()?;
+                walk(self, &f.node)?;
+            }
+            (On, [f]) => {
+                let a = self.pop()?;
+                self.stack().push(a.clone());
+                walk(self, &f.node)?;
+                self.stack().push(a);
+            }
+            (By, [f]) => {
+                let args = self.pop_n(f.sig.args)?;
+                self.stack().extend(args.last().cloned());
+                self.stack().extend(args.into_iter().rev());
+                walk(self, &f.node)?;
+            }
+            (Both, [f]) => {
+                let args = self.pop_n(f.sig.args)?;
+                walk(self, &f.node)?;
+                self.stack().extend(args.into_iter().rev());
+                walk(self, &f.node)?;
+            }
+            (Bracket, [f, g]) => {
+                let args = self.pop_n(f.sig.args)?;
+                walk(self, &g.node)?;
+                self.stack().extend(args.into_iter().rev());
+                walk(self, &f.node)?;
+            }
+            (Fork, [f, g]) => {
+                let n = f.sig.args.max(g.sig.args);
+                let args = self.pop_n(n)?;
+                self.stack()
+                    .extend(args[..g.sig.args].iter().rev().cloned());
+                walk(self, &g.node)?;
+                self.stack()
+                    .extend(args[..f.sig.args].iter().rev().cloned());
+                walk(self, &f.node)?;
+            }
+            (prim, _) => {
+                let error = WalkError::NotSupported(prim.format().to_string());
+                return Err(self.error(error));
+            }
+        }
+        Ok(())
+    }
+}
@@ -625,9 +625,15 @@ pub fn rows1(f: SigNode, mut xs: Value, inv: bool, env: &mut Uiua) -> UiuaResult
                 }
             }
         } else {
-            for row in xs.into_rows() {
+            let prim = if inv {
+                Primitive::Inventory
+            } else {
+                Primitive::Rows
+            };
+            let row_shape = xs.shape().row();
+            for (r, row) in xs.into_rows().enumerate() {
                 env.push(row.unboxed_if(inv));
-                env.exec(f.clone())?;
+                (env.exec(f.clone())).map_err(|e| e.in_iteration(prim, r, [row_shape.clone()]))?;
                 for i in 0..outputs {
                     new_rows[i].push(env.pop("rows' function result")?.boxed_if(inv));
                 }
@@ -640,6 +646,11 @@ pub fn rows1(f: SigNode, mut xs: Value, inv: bool, env: &mut Uiua) -> UiuaResult
 
 fn rows2(f: SigNode, mut xs: Value, mut ys: Value, inv: bool, env: &mut Uiua) -> UiuaResult {
     let outputs = f.sig.outputs;
+    let prim = if inv {
+        Primitive::Inventory
+    } else {
+        Primitive::Rows
+    };
     let both_scalar = xs.rank() == 0 && ys.rank() == 0;
     match (xs.row_count(), ys.row_count()) {
         (_, 1) => {
@@ -660,10 +671,12 @@ fn rows2(f: SigNode, mut xs: Value, mut ys: Value, inv: bool, env: &mut Uiua) ->
                         }
                     }
                 } else {
-                    for x in xs.into_rows() {
+                    let shapes = [xs.shape().row(), ys.shape().clone()];
+                    for (r, x) in xs.into_rows().enumerate() {
                         env.push(ys.clone().unboxed_if(inv));
                         env.push(x.unboxed_if(inv));
-                        env.exec(f.clone())?;
+                        (env.exec(f.clone()))
+                            .map_err(|e| e.in_iteration(prim, r, shapes.clone()))?;
                         for i in 0..outputs {
                             new_rows[i].push(env.pop("rows's function result")?.boxed_if(inv));
                         }
@@ -691,10 +704,12 @@ fn rows2(f: SigNode, mut xs: Value, mut ys: Value, inv: bool, env: &mut Uiua) ->
                         }
                     }
                 } else {
-                    for y in ys.into_rows() {
+                    let shapes = [xs.shape().clone(), ys.shape().row()];
+                    for (r, y) in ys.into_rows().enumerate() {
                         env.push(y.unboxed_if(inv));
                         env.push(xs.clone());
-                        env.exec(f.clone())?;
+                        (env.exec(f.clone()))
+                            .map_err(|e| e.in_iteration(prim, r, shapes.clone()))?;
                         for i in 0..outputs {
                             new_rows[i].push(env.pop("rows's function result")?.boxed_if(inv));
                         }
@@ -708,12 +723,7 @@ fn rows2(f: SigNode, mut xs: Value, mut ys: Value, inv: bool, env: &mut Uiua) ->
             if a != b {
                 return Err(env.error(format!(
                     "Cannot {} arrays with different number of rows {a} and {b}",
-                    if inv {
-                        Primitive::Inventory
-                    } else {
-                        Primitive::Rows
-                    }
-                    .format(),
+                    prim.format(),
                 )));
             }
             if !inv {
@@ -754,10 +764,12 @@ fn rows2(f: SigNode, mut xs: Value, mut ys: Value, inv: bool, env: &mut Uiua) ->
                         }
                     }
                 } else {
-                    for (x, y) in xs.into_rows().zip(ys.into_rows()) {
+                    let shapes = [xs.shape().row(), ys.shape().row()];
+                    for (r, (x, y)) in xs.into_rows().zip(ys.into_rows()).enumerate() {
                         env.push(y.unboxed_if(inv));
                         env.push(x.unboxed_if(inv));
-                        env.exec(f.clone())?;
+                        (env.exec(f.clone()))
+                            .map_err(|e| e.in_iteration(prim, r, shapes.clone()))?;
                         for i in 0..outputs {
                             new_rows[i].push(env.pop("rows's function result")?.boxed_if(inv));
                         }
@@ -777,6 +789,7 @@ fn rowsn(f: SigNode, args: Vec<Value>, inv: bool, env: &mut Uiua) -> UiuaResult
     } else {
         Primitive::Rows
     };
+    let shapes: Vec<Shape> = args.iter().map(|arg| arg.shape().row()).collect();
     let FixedRowsData {
         mut rows,
         row_count,
@@ -787,14 +800,14 @@ fn rowsn(f: SigNode, args: Vec<Value>, inv: bool, env: &mut Uiua) -> UiuaResult
     } = fixed_rows(prim.format(), outputs, args, env)?;
     let mut new_values = multi_output(outputs, Vec::new());
     env.without_fill(|env| -> UiuaResult {
-        for _ in 0..row_count {
+        for r in 0..row_count {
             for arg in rows.iter_mut().rev() {
                 match arg {
                     Ok(rows) => env.push(rows.next().unwrap().unboxed_if(inv)),
                     Err(row) => env.push(row.clone().unboxed_if(inv)),
                 }
             }
-            env.exec(f.clone())?;
+            (env.exec(f.clone())).map_err(|e| e.in_iteration(prim, r, shapes.clone()))?;
             for i in 0..outputs {
                 new_values[i].push(env.pop("rows's function result")?.boxed_if(inv));
             }
@@ -1,6 +1,9 @@
 use std::{
+    collections::{BTreeSet, HashMap},
     fmt,
     hash::{DefaultHasher, Hash, Hasher},
+    iter::once,
+    mem::{discriminant, take},
     ops::{Index, IndexMut},
     path::PathBuf,
     str::FromStr,
@@ -13,11 +16,15 @@ use serde::*;
 
 use crate::{
     compile::{LocalName, Module},
-    is_ident_char, CodeSpan, FunctionId, InputSrc, IntoInputSrc, Node, SigNode, Signature, Span,
-    Uiua, UiuaResult, Value,
+    is_ident_char, CodeSpan, FunctionId, Ident, InputSrc, IntoInputSrc, Node, Primitive, Purity,
+    SigNode, Signature, Span, StackHeights, Uiua, UiuaResult, Value,
 };
 
 /// A compiled Uiua assembly
+///
+/// Cloning an assembly is cheap. Its nodes, functions, bindings, and inputs are reference-counted
+/// and shared between clones until one of them is modified,
+/// so one program can be run by many [`Uiua`] instances at once with [`Uiua::run_shared`].
 #[derive(Clone)]
 pub struct Assembly {
     /// The top-level node
@@ -31,6 +38,31 @@ pub struct Assembly {
     pub inputs: Inputs,
     pub(crate) dynamic_functions: EcoVec<DynFn>,
     pub(crate) test_assert_count: usize,
+    pub(crate) test_blocks: EcoVec<TestBlock>,
+    pub(crate) bench_blocks: EcoVec<BenchBlock>,
+}
+
+/// A test block between `---`s
+#[derive(Debug, Clone)]
+pub struct TestBlock {
+    /// The name given after `test`, if any
+    pub name: Option<Ident>,
+    /// The span of the whole block
+    pub span: CodeSpan,
+    /// Whether the block is marked with `# Should fail!`
+    pub should_fail: bool,
+    /// Whether the block is marked with `# Snapshot!`
+    pub snapshot: bool,
+}
+
+impl TestBlock {
+    /// Get the block's name, or its location if it is unnamed
+    pub fn display_name(&self) -> EcoString {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("test at {}", self.span).into(),
+        }
+    }
 }
 
 /// A Uiua function
@@ -94,11 +126,68 @@ impl<'de> Deserialize<'de> for Function {
     }
 }
 
+/// A benchmark block in a `bench` scope
+#[derive(Debug, Clone)]
+pub struct BenchBlock {
+    /// The name given after `bench`, if any
+    pub name: Option<Ident>,
+    /// The span of the whole block
+    pub span: CodeSpan,
+    /// The code that is run for each iteration
+    pub(crate) node: Node,
+}
+
+impl BenchBlock {
+    /// Get the block's name, or its location if it is unnamed
+    pub fn display_name(&self) -> EcoString {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("bench at {}", self.span).into(),
+        }
+    }
+}
+
 impl Assembly {
+    /// Get the test blocks in the order they appear
+    pub fn test_blocks(&self) -> &[TestBlock] {
+        &self.test_blocks
+    }
+    /// Get the benchmark blocks in the order they appear
+    pub fn bench_blocks(&self) -> &[BenchBlock] {
+        &self.bench_blocks
+    }
     /// Get the [`SigNode`] for a function
     pub fn sig_node(&self, f: &Function) -> SigNode {
         SigNode::new(f.sig, self[f].clone())
     }
+    /// Get the heights of the virtual stack around every node that has a code span
+    ///
+    /// Heights in top-level code are counted from the start of the program.
+    /// Heights in functions are counted from the bottom of the function's arguments.
+    /// Code whose signature cannot be checked is skipped.
+    ///
+    /// Entries are sorted by their spans' positions.
+    pub fn stack_heights(&self) -> Vec<(CodeSpan, StackHeights)> {
+        let mut heights: Vec<_> = (once(&self.root).chain(&self.functions))
+            .filter_map(|node| node.stack_heights().ok())
+            .flatten()
+            .filter_map(|heights| match self.spans.get(heights.span)? {
+                Span::Code(span) => Some((span.clone(), heights)),
+                Span::Builtin => None,
+            })
+            .collect();
+        heights.sort_by(|(a, _), (b, _)| {
+            (a.src.cmp(&b.src)).then(a.start.byte_pos.cmp(&b.start.byte_pos))
+        });
+        heights
+            .dedup_by(|(a, ah), (b, bh)| a == b && (ah.before, ah.after) == (bh.before, bh.after));
+        heights
+    }
+    /// Find the most recently defined binding with a name
+    pub fn find_binding(&self, name: &str) -> Option<&BindingInfo> {
+        (self.bindings.iter().rev())
+            .find(|binding| binding.span.try_as_str(&self.inputs, |s| s == name) == Some(true))
+    }
     /// Add a function to the assembly
     pub fn add_function(&mut self, id: FunctionId, sig: Signature, root: Node) -> Function {
         let mut hasher = DefaultHasher::new();
@@ -160,6 +249,274 @@ impl Assembly {
         let span = self.spans[span].clone();
         self.add_binding_at(local, BindingKind::Const(value), span.code(), meta);
     }
+    /// Check if a recompiled assembly only changes the bodies of bound functions
+    ///
+    /// The bindings must have the same names, kinds, and signatures,
+    /// and the top-level code must be the same.
+    pub fn can_hot_swap(&self, new: &Assembly) -> bool {
+        fn normalized(root: &Node) -> Node {
+            let mut root = root.clone();
+            visit_nodes_mut(&mut root, &mut |node| {
+                if let Node::Call(f, _) = node {
+                    f.index = 0;
+                    f.hash = 0;
+                }
+            });
+            root
+        }
+        self.bindings.len() == new.bindings.len()
+            && (self.bindings.iter().zip(&new.bindings)).all(|(old, new_binding)| {
+                let same_kind = match (&old.kind, &new_binding.kind) {
+                    (BindingKind::Func(a), BindingKind::Func(b)) => a.id == b.id && a.sig == b.sig,
+                    (BindingKind::Import(a), BindingKind::Import(b)) => a == b,
+                    (BindingKind::IndexMacro(a), BindingKind::IndexMacro(b)) => a == b,
+                    (BindingKind::CodeMacro(a), BindingKind::CodeMacro(b)) => a == b,
+                    (a, b) => discriminant(a) == discriminant(b),
+                };
+                let name = |binding: &BindingInfo, inputs: &Inputs| {
+                    (binding.span).try_as_str(inputs, |s| s.to_string())
+                };
+                same_kind
+                    && old.public == new_binding.public
+                    && name(old, &self.inputs) == name(new_binding, &new.inputs)
+            })
+            && normalized(&self.root) == normalized(&new.root)
+    }
+    /// Swap the function bodies of a recompiled assembly into this one
+    ///
+    /// This only works if [`Assembly::can_hot_swap`] is true, and returns whether it did.
+    /// Bound constants keep their current values.
+    ///
+    /// The old function indices are kept valid and point to the new bodies,
+    /// so code that is already running calls the new functions
+    /// the next time it calls them.
+    ///
+    /// Functions and spans that already exist reuse their old indices,
+    /// so swapping in the same code again does not grow the assembly.
+    pub fn hot_swap(&mut self, new: Assembly) -> bool {
+        if !self.can_hot_swap(&new) {
+            return false;
+        }
+        // Map new function indices to old ones
+        let mut function_map: Vec<Option<usize>> = vec![None; new.functions.len()];
+        for (old, binding) in self.bindings.iter().zip(&new.bindings) {
+            if let (BindingKind::Func(old_f), BindingKind::Func(new_f)) = (&old.kind, &binding.kind)
+            {
+                function_map[new_f.index] = Some(old_f.index);
+            }
+        }
+        let mut taken: BTreeSet<usize> = function_map.iter().flatten().copied().collect();
+        let old_ids = self.function_ids();
+        for (id, index) in new.function_ids() {
+            if function_map[index].is_some() {
+                continue;
+            }
+            if let Some(&old) = old_ids.get(&id) {
+                if taken.insert(old) {
+                    function_map[index] = Some(old);
+                }
+            }
+        }
+        let mut next = self.functions.len();
+        let function_map: Vec<usize> = (function_map.into_iter())
+            .map(|index| {
+                index.unwrap_or_else(|| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect();
+        // Map new span indices to old ones
+        let mut span_indices: HashMap<Span, usize> = HashMap::new();
+        for (i, span) in self.spans.iter().enumerate() {
+            span_indices.entry(span.clone()).or_insert(i);
+        }
+        let span_map: Vec<usize> = (new.spans.into_iter())
+            .map(|span| {
+                *span_indices.entry(span.clone()).or_insert_with(|| {
+                    self.spans.push(span);
+                    self.spans.len() - 1
+                })
+            })
+            .collect();
+        // Swap in the new bodies
+        for (i, mut node) in new.functions.into_iter().enumerate() {
+            visit_nodes_mut(&mut node, &mut |node| {
+                if let Node::Call(f, _) = node {
+                    f.index = function_map[f.index];
+                }
+                if !matches!(node, Node::Run(_)) {
+                    if let Some(span) = node.span_mut() {
+                        *span = span_map[*span];
+                    }
+                }
+            });
+            let index = function_map[i];
+            if index < self.functions.len() {
+                self.functions.make_mut()[index] = node;
+            } else {
+                self.functions.push(node);
+            }
+        }
+        for (old, mut binding) in self.bindings.make_mut().iter_mut().zip(new.bindings) {
+            match (&old.kind, &mut binding.kind) {
+                (BindingKind::Func(_), BindingKind::Func(new_f)) => {
+                    new_f.index = function_map[new_f.index];
+                }
+                (BindingKind::Const(_), _) => binding.kind = old.kind.clone(),
+                _ => {}
+            }
+            *old = binding;
+        }
+        self.inputs = new.inputs;
+        true
+    }
+    /// Get the indices of functions by their ids
+    ///
+    /// Ids that refer to more than one function are left out.
+    fn function_ids(&self) -> HashMap<FunctionId, usize> {
+        let mut ids: HashMap<FunctionId, Option<usize>> = HashMap::new();
+        for node in once(&self.root).chain(&self.functions) {
+            visit_nodes(node, &mut |node| {
+                if let Node::Call(f, _) = node {
+                    let index = ids.entry(f.id.clone()).or_insert(Some(f.index));
+                    if *index != Some(f.index) {
+                        *index = None;
+                    }
+                }
+            });
+        }
+        (ids.into_iter())
+            .filter_map(|(id, index)| Some((id, index?)))
+            .collect()
+    }
+    /// Remove functions, bindings, and spans that cannot be reached from the root
+    ///
+    /// Top-level code that only computes the value of an unused constant is removed as well.
+    ///
+    /// Functions, bindings, and spans are renumbered, so this should only be done
+    /// once compilation is finished, such as before serializing to `.uasm`.
+    pub fn remove_dead_code(&mut self) {
+        // Benchmarks are never run from built assemblies
+        self.bench_blocks = EcoVec::new();
+        // Remove the top-level code of unused constants
+        loop {
+            let (bindings, _) = self.reachable(false);
+            let mut nodes: Vec<Node> = take(&mut self.root).into_vec().into_iter().collect();
+            let mut changed = false;
+            let mut i = 0;
+            while i < nodes.len() {
+                let Node::BindGlobal { index, span } = nodes[i] else {
+                    i += 1;
+                    continue;
+                };
+                if bindings.contains(&index) {
+                    i += 1;
+                    continue;
+                }
+                changed = true;
+                let value_start = (0..i).rev().find(|&start| {
+                    Node::from(&nodes[start..i])
+                        .sig()
+                        .is_ok_and(|sig| sig == (0, 1))
+                });
+                match value_start {
+                    Some(start) if Node::from(&nodes[start..i]).is_pure(Purity::Pure, self) => {
+                        nodes.drain(start..=i);
+                        i = start;
+                    }
+                    _ => {
+                        nodes[i] = Node::Prim(Primitive::Pop, span);
+                        i += 1;
+                    }
+                }
+            }
+            self.root = nodes.into_iter().collect();
+            if !changed {
+                break;
+            }
+        }
+
+        // Renumber everything that is still reachable
+        let (bindings, functions) = self.reachable(true);
+        let binding_map: HashMap<usize, usize> = (bindings.iter().enumerate())
+            .map(|(new, &old)| (old, new))
+            .collect();
+        let function_map: HashMap<usize, usize> = (functions.iter().enumerate())
+            .map(|(new, &old)| (old, new))
+            .collect();
+        self.bindings = (bindings.iter())
+            .map(|&i| {
+                let mut binding = self.bindings[i].clone();
+                if let BindingKind::Func(f) = &mut binding.kind {
+                    f.index = function_map[&f.index];
+                }
+                binding
+            })
+            .collect();
+        self.functions = (functions.iter())
+            .map(|&i| self.functions[i].clone())
+            .collect();
+        let mut spans = BTreeSet::from([0]);
+        for node in once(&self.root).chain(&self.functions) {
+            visit_nodes(node, &mut |node| {
+                if !matches!(node, Node::Run(_)) {
+                    spans.extend(node.span());
+                }
+            });
+        }
+        let span_map: HashMap<usize, usize> = (spans.iter().enumerate())
+            .map(|(new, &old)| (old, new))
+            .collect();
+        self.spans = spans.iter().map(|&i| self.spans[i].clone()).collect();
+        let mut remap = |node: &mut Node| {
+            match node {
+                Node::Run(_) => return,
+                Node::Call(f, _) => f.index = function_map[&f.index],
+                Node::CallGlobal(index, _)
+                | Node::CallMacro { index, .. }
+                | Node::BindGlobal { index, .. } => *index = binding_map[index],
+                _ => {}
+            }
+            if let Some(span) = node.span_mut() {
+                *span = span_map[span];
+            }
+        };
+        visit_nodes_mut(&mut self.root, &mut remap);
+        for node in self.functions.make_mut() {
+            visit_nodes_mut(node, &mut remap);
+        }
+    }
+    /// Get the indices of the bindings and functions reachable from the root
+    fn reachable(&self, count_binds: bool) -> (BTreeSet<usize>, BTreeSet<usize>) {
+        let mut bindings = BTreeSet::new();
+        let mut functions = BTreeSet::new();
+        let mut queue = vec![&self.root];
+        while let Some(node) = queue.pop() {
+            visit_nodes(node, &mut |node| {
+                let index = match node {
+                    Node::Call(f, _) => {
+                        if functions.insert(f.index) {
+                            queue.push(&self.functions[f.index]);
+                        }
+                        return;
+                    }
+                    Node::CallGlobal(index, _) | Node::CallMacro { index, .. } => *index,
+                    Node::BindGlobal { index, .. } if count_binds => *index,
+                    _ => return,
+                };
+                if !bindings.insert(index) {
+                    return;
+                }
+                if let Some(BindingKind::Func(f)) = self.bindings.get(index).map(|b| &b.kind) {
+                    if functions.insert(f.index) {
+                        queue.push(&self.functions[f.index]);
+                    }
+                }
+            });
+        }
+        (bindings, functions)
+    }
     /// Parse a `.uasm` file into an assembly
     pub fn from_uasm(src: &str) -> Result<Self, String> {
         let rest = src;
@@ -243,12 +600,14 @@ impl Assembly {
             functions,
             spans,
             inputs: Inputs {
-                files,
+                files: files.into(),
                 strings,
                 ..Inputs::default()
             },
             dynamic_functions: EcoVec::new(),
             test_assert_count: 0,
+            test_blocks: EcoVec::new(),
+            bench_blocks: EcoVec::new(),
         })
     }
     /// Serialize the assembly into a `.uasm` file
@@ -295,7 +654,7 @@ impl Assembly {
         }
 
         uasm.push_str("\nFILES\n");
-        for entry in &self.inputs.files {
+        for entry in self.inputs.files.iter() {
             let key = entry.key();
             let value = entry.value();
             uasm.push_str(&format!("{}: {:?}\n", key.display(), value));
@@ -313,6 +672,57 @@ impl Assembly {
     }
 }
 
+/// Call a function on a node and all of its descendants
+fn visit_nodes<'a>(node: &'a Node, f: &mut impl FnMut(&'a Node)) {
+    f(node);
+    match node {
+        Node::Run(nodes) => nodes.iter().for_each(|node| visit_nodes(node, f)),
+        Node::Mod(_, args, _) | Node::ImplMod(_, args, _) => {
+            (args.iter()).for_each(|arg| visit_nodes(&arg.node, f))
+        }
+        Node::Switch { branches, .. } => (branches.iter()).for_each(|br| visit_nodes(&br.node, f)),
+        Node::Array { inner, .. } | Node::NoInline(inner) | Node::TrackCaller(inner) => {
+            visit_nodes(inner, f)
+        }
+        Node::CustomInverse(cust, _) => {
+            let under = (cust.under.iter()).flat_map(|(before, after)| [before, after]);
+            (cust.normal.as_ref().ok().into_iter())
+                .chain(&cust.un)
+                .chain(under)
+                .chain(&cust.anti)
+                .for_each(|sn| visit_nodes(&sn.node, f))
+        }
+        _ => {}
+    }
+}
+
+/// Call a function on a node and all of its descendants, mutably
+fn visit_nodes_mut(node: &mut Node, f: &mut impl FnMut(&mut Node)) {
+    f(node);
+    match node {
+        Node::Run(nodes) => (nodes.make_mut().iter_mut()).for_each(|node| visit_nodes_mut(node, f)),
+        Node::Mod(_, args, _) | Node::ImplMod(_, args, _) => {
+            (args.make_mut().iter_mut()).for_each(|arg| visit_nodes_mut(&mut arg.node, f))
+        }
+        Node::Switch { branches, .. } => {
+            (branches.make_mut().iter_mut()).for_each(|br| visit_nodes_mut(&mut br.node, f))
+        }
+        Node::Array { inner, .. } | Node::NoInline(inner) | Node::TrackCaller(inner) => {
+            visit_nodes_mut(Arc::make_mut(inner), f)
+        }
+        Node::CustomInverse(cust, _) => {
+            let cust = Arc::make_mut(cust);
+            let under = (cust.under.iter_mut()).flat_map(|(before, after)| [before, after]);
+            (cust.normal.as_mut().ok().into_iter())
+                .chain(&mut cust.un)
+                .chain(under)
+                .chain(&mut cust.anti)
+                .for_each(|sn| visit_nodes_mut(&mut sn.node, f))
+        }
+        _ => {}
+    }
+}
+
 impl Index<&Function> for Assembly {
     type Output = Node;
     #[track_caller]
@@ -346,6 +756,8 @@ impl Default for Assembly {
             dynamic_functions: EcoVec::new(),
             inputs: Inputs::default(),
             test_assert_count: 0,
+            test_blocks: EcoVec::new(),
+            bench_blocks: EcoVec::new(),
         }
     }
 }
@@ -378,6 +790,8 @@ pub struct BindingMeta {
     pub counts: Option<BindingCounts>,
     /// The deprecation message
     pub deprecation: Option<EcoString>,
+    /// The span of the whole definition
+    pub source: Option<CodeSpan>,
 }
 
 /// A kind of global binding
@@ -630,18 +1044,21 @@ impl From<&str> for DocComment {
 }
 
 /// A repository of code strings input to the compiler
+///
+/// The maps are shared between clones until one of them is modified.
+/// Use [`Arc::make_mut`] to add to them.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Inputs {
     /// A map of file paths to their string contents
     #[serde(skip_serializing_if = "DashMap::is_empty")]
-    pub files: DashMap<PathBuf, EcoString>,
+    pub files: Arc<DashMap<PathBuf, EcoString>>,
     /// A list of input strings without paths
     #[serde(skip_serializing_if = "EcoVec::is_empty")]
     pub strings: EcoVec<EcoString>,
     /// A map of spans to macro strings
     #[serde(skip)]
-    pub macros: DashMap<CodeSpan, EcoString>,
+    pub macros: Arc<DashMap<CodeSpan, EcoString>>,
 }
 
 impl Inputs {
@@ -653,7 +1070,7 @@ impl Inputs {
         let src = src.into_input_src(self.strings.len());
         match &src {
             InputSrc::File(path) => {
-                self.files.insert(path.to_path_buf(), input.into());
+                Arc::make_mut(&mut self.files).insert(path.to_path_buf(), input.into());
             }
             InputSrc::Str(i) => {
                 while self.strings.len() <= *i {
@@ -662,7 +1079,7 @@ impl Inputs {
                 self.strings.make_mut()[*i] = input.into();
             }
             InputSrc::Macro(span) => {
-                self.macros.insert((**span).clone(), input.into());
+                Arc::make_mut(&mut self.macros).insert((**span).clone(), input.into());
             }
         }
         src
@@ -764,3 +1181,124 @@ impl fmt::Debug for Assembly {
             .finish()
     }
 }
+
+#[cfg(test)]
+#[test]
+fn shared_assembly() {
+    use crate::Compiler;
+    let asm = (Compiler::new().load_str("F ← ×2\nF ⇡5")).unwrap().finish();
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let asm = asm.clone();
+            std::thread::spawn(move || {
+                let mut env = Uiua::with_safe_sys();
+                env.run_shared(&asm).unwrap();
+                env.pop_nums().unwrap()
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), [0.0, 2.0, 4.0, 6.0, 8.0]);
+    }
+    let mut modified = asm.clone();
+    assert_eq!(modified.functions.as_ptr(), asm.functions.as_ptr());
+    assert!(Arc::ptr_eq(&modified.inputs.files, &asm.inputs.files));
+    modified.root = Node::default();
+    let mut env = Uiua::with_safe_sys();
+    env.run_shared(&modified).unwrap();
+    assert!(env.stack().is_empty());
+    env.run_shared(&asm).unwrap();
+    assert_eq!(env.pop_nums().unwrap(), [0.0, 2.0, 4.0, 6.0, 8.0]);
+}
+
+#[cfg(test)]
+#[test]
+fn dead_code_removal() {
+    use crate::{Compiler, PreEvalMode, Uiua, Value};
+    let code = "\
+Big ← ⇡1000
+Unused ← /+ Big
+F ← ×2
+G ← +F 1
+H ← -F 1
+G 5";
+    let mut comp = Compiler::new();
+    comp.pre_eval_mode(PreEvalMode::Lazy)
+        .load_str(code)
+        .unwrap();
+    let mut asm = comp.finish();
+    let (bindings, functions) = (asm.bindings.len(), asm.functions.len());
+    asm.remove_dead_code();
+    assert!(asm.bindings.len() < bindings);
+    assert!(asm.functions.len() < functions);
+    let asm = Assembly::from_uasm(&asm.to_uasm()).unwrap();
+    let mut env = Uiua::with_safe_sys();
+    env.run_asm(asm).unwrap();
+    assert_eq!(env.take_stack(), [Value::from(7)]);
+}
+
+#[cfg(test)]
+#[test]
+fn stack_heights() {
+    use crate::{Compiler, PreEvalMode};
+    let mut comp = Compiler::new();
+    comp.pre_eval_mode(PreEvalMode::Lazy)
+        .load_str("F ← +1 ⊙×\nF 1 2 3\n[⊟.]")
+        .unwrap();
+    let asm = comp.finish();
+    let heights: Vec<_> = (asm.stack_heights().into_iter())
+        .map(|(span, h)| (span.as_str(&asm.inputs, str::to_string), h.before, h.after))
+        .collect();
+    let expected = [
+        ("+", 3, 2),
+        ("⊙", 3, 2),
+        ("×", 2, 1),
+        ("F", 3, 2),
+        ("[⊟.]", 2, 2),
+        ("⊟", 3, 2),
+        (".", 2, 3),
+    ];
+    assert_eq!(heights.len(), expected.len());
+    for ((text, before, after), (exp_text, exp_before, exp_after)) in heights.iter().zip(expected) {
+        assert_eq!(
+            (text.as_str(), *before, *after),
+            (exp_text, exp_before, exp_after)
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn hot_reload() {
+    use crate::{Compiler, PreEvalMode, Uiua, Value};
+    let compile = |code: &str| {
+        let mut comp = Compiler::new();
+        comp.pre_eval_mode(PreEvalMode::Lazy)
+            .load_str(code)
+            .unwrap();
+        comp.finish()
+    };
+    let old = compile("X ← 5\nF ← +1\nF X");
+    let new = compile("X ← 5\nF ← +10\nF X");
+    assert!(old.can_hot_swap(&new));
+    assert!(!old.can_hot_swap(&compile("X ← 5\nF ← +1\nF 6")));
+    assert!(!old.can_hot_swap(&compile("X ← 5\nF ← +\nF X")));
+    assert!(!old.can_hot_swap(&compile("X ← 5\nG ← +1\nG X")));
+    let mut env = Uiua::with_safe_sys();
+    env.hot_reloader().reload(new.clone());
+    env.run_asm(old.clone()).unwrap();
+    assert_eq!(env.take_stack(), [Value::from(15)]);
+
+    // Repeated swaps do not keep growing the assembly
+    let mut asm = old.clone();
+    assert!(asm.hot_swap(new.clone()));
+    assert!(asm.hot_swap(old.clone()));
+    let (functions, spans) = (asm.functions.len(), asm.spans.len());
+    for _ in 0..10 {
+        assert!(asm.hot_swap(new.clone()));
+        assert!(asm.hot_swap(old.clone()));
+    }
+    assert_eq!(asm.functions.len(), functions);
+    assert_eq!(asm.spans.len(), spans);
+    assert_eq!(asm.functions.len(), old.functions.len());
+}
@@ -77,8 +77,10 @@ pub struct ScopedModule {
 pub enum ModuleKind {
     /// A named module
     Named(Sp<Ident>),
-    /// A test scope
-    Test,
+    /// A test scope, with an optional name
+    Test(Option<Sp<Ident>>),
+    /// A benchmark scope, with an optional name
+    Bench(Option<Sp<Ident>>),
 }
 
 /// An import
@@ -287,6 +289,7 @@ pub enum Word {
     Primitive(Primitive),
     Modified(Box<Modified>),
     Placeholder(usize),
+    NamedPlaceholder(Ident),
     Comment(String),
     Spaces,
     BreakLine,
@@ -339,6 +342,7 @@ impl PartialEq for Word {
                         .eq(b.code_operands().map(|w| &w.value))
             }
             (Self::Placeholder(_), Self::Placeholder(_)) => false,
+            (Self::NamedPlaceholder(_), Self::NamedPlaceholder(_)) => false,
             (Self::Comment(a), Self::Comment(b)) => a == b,
             _ => discriminant(self) == discriminant(other),
         }
@@ -414,6 +418,7 @@ impl fmt::Debug for Word {
             Word::Spaces => write!(f, "' '"),
             Word::Comment(comment) => write!(f, "# {comment}"),
             Word::Placeholder(op) => write!(f, "^{op}"),
+            Word::NamedPlaceholder(name) => write!(f, "^{name}"),
             Word::BreakLine => write!(f, "break_line"),
             Word::FlipLine => write!(f, "unbreak_line"),
             Word::SemanticComment(comment) => write!(f, "{comment}"),
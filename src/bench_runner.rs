@@ -0,0 +1,292 @@
+//! Running the benchmark blocks in a file and comparing them to a baseline
+
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use ecow::EcoString;
+use serde::*;
+
+use crate::{CodeSpan, Compiler, RunMode, Uiua, UiuaResult};
+
+/// Runs each benchmark block in a file repeatedly and reports how long it takes
+///
+/// The code outside of benchmark blocks is run once first, so it can define the
+/// bindings the benchmarks use. Each benchmark is then run a few times to warm up,
+/// followed by a number of timed samples. Fast benchmarks are run several times per
+/// sample so that each sample is long enough to be timed accurately.
+pub struct BenchRunner {
+    compiler: Compiler,
+    runtime: Uiua,
+    filter: Option<String>,
+    warmup: usize,
+    samples: usize,
+    baseline: Option<BenchBaseline>,
+}
+
+/// The results of running benchmarks
+#[derive(Debug, Clone, Default)]
+pub struct BenchReport {
+    /// The results of the benchmarks that were run, in the order they appear
+    pub benches: Vec<BenchResult>,
+    /// The number of benchmarks that did not match the filter
+    pub filtered_out: usize,
+}
+
+/// The result of running a single benchmark block
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    /// The benchmark's name, or its location if it is unnamed
+    pub name: EcoString,
+    /// The span of the benchmark block
+    pub span: CodeSpan,
+    /// The timing statistics
+    pub stats: BenchStats,
+    /// The mean time from the baseline, if there is one for this benchmark
+    pub baseline: Option<f64>,
+}
+
+/// Timing statistics for a benchmark
+///
+/// All times are in seconds per iteration
+#[derive(Debug, Clone, Default)]
+pub struct BenchStats {
+    /// The time of each sample
+    pub samples: Vec<f64>,
+    /// The number of iterations in each sample
+    pub iterations: usize,
+}
+
+/// Mean benchmark times saved from a previous run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchBaseline {
+    means: BTreeMap<String, f64>,
+}
+
+/// The minimum time in seconds a single sample should take
+const MIN_SAMPLE_TIME: f64 = 0.001;
+
+impl BenchRunner {
+    /// Create a new benchmark runner that compiles with the given compiler and runs with the given runtime
+    pub fn new(compiler: Compiler, runtime: Uiua) -> Self {
+        BenchRunner {
+            compiler,
+            runtime,
+            filter: None,
+            warmup: 3,
+            samples: 20,
+            baseline: None,
+        }
+    }
+    /// Only run benchmarks whose names contain the given string
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+    /// Set the number of untimed iterations run before sampling
+    pub fn warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+    /// Set the number of timed samples
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+    /// Compare the results to a baseline
+    pub fn baseline(mut self, baseline: BenchBaseline) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+    /// Run the benchmarks in a file
+    pub fn run_file(self, path: impl AsRef<Path>) -> UiuaResult<BenchReport> {
+        let path = path.as_ref().to_path_buf();
+        self.run(|comp| comp.load_file(path))
+    }
+    /// Run the benchmarks in a string
+    pub fn run_str(self, input: &str) -> UiuaResult<BenchReport> {
+        self.run(|comp| comp.load_str(input))
+    }
+    fn run(
+        mut self,
+        load: impl FnOnce(&mut Compiler) -> UiuaResult<&mut Compiler>,
+    ) -> UiuaResult<BenchReport> {
+        self.compiler.mode(RunMode::Normal);
+        let asm = load(&mut self.compiler)?.finish();
+        let blocks = asm.bench_blocks.clone();
+        let env = &mut self.runtime;
+        env.run_asm(asm)?;
+        env.take_stack();
+        let mut report = BenchReport::default();
+        for block in blocks {
+            let name = block.display_name();
+            if let Some(filter) = &self.filter {
+                if !name.contains(filter.as_str()) {
+                    report.filtered_out += 1;
+                    continue;
+                }
+            }
+            let mut run = |iterations: usize| -> UiuaResult<f64> {
+                let start = env.backend().now();
+                for _ in 0..iterations {
+                    env.exec(block.node.clone())?;
+                    env.take_stack();
+                }
+                Ok((env.backend().now() - start) / iterations as f64)
+            };
+            // Warm up and estimate how many iterations fit in a sample
+            let mut estimate = run(1)?;
+            for _ in 1..self.warmup {
+                estimate = estimate.min(run(1)?);
+            }
+            let iterations = if estimate > 0.0 {
+                ((MIN_SAMPLE_TIME / estimate).ceil() as usize).clamp(1, 1_000_000)
+            } else {
+                1000
+            };
+            let samples = (0..self.samples)
+                .map(|_| run(iterations))
+                .collect::<UiuaResult<_>>()?;
+            let baseline = (self.baseline.as_ref()).and_then(|b| b.get(&name));
+            report.benches.push(BenchResult {
+                name,
+                span: block.span,
+                stats: BenchStats {
+                    samples,
+                    iterations,
+                },
+                baseline,
+            });
+        }
+        Ok(report)
+    }
+}
+
+impl BenchStats {
+    /// The mean time
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len().max(1) as f64
+    }
+    /// The median time
+    pub fn median(&self) -> f64 {
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        match sorted.len() {
+            0 => 0.0,
+            n if n % 2 == 0 => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+            n => sorted[n / 2],
+        }
+    }
+    /// The sample standard deviation of the times
+    pub fn stddev(&self) -> f64 {
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let variance = (self.samples.iter())
+            .map(|s| (s - mean).powi(2))
+            .sum::<f64>()
+            / (self.samples.len() - 1) as f64;
+        variance.sqrt()
+    }
+}
+
+impl BenchResult {
+    /// The relative change in mean time from the baseline
+    ///
+    /// `0.1` means 10% slower, and `-0.1` means 10% faster.
+    pub fn change(&self) -> Option<f64> {
+        let baseline = self.baseline?;
+        (baseline > 0.0).then(|| (self.stats.mean() - baseline) / baseline)
+    }
+}
+
+impl BenchBaseline {
+    /// Create a baseline from the results of a run
+    pub fn from_report(report: &BenchReport) -> Self {
+        BenchBaseline {
+            means: (report.benches.iter())
+                .map(|bench| (bench.name.to_string(), bench.stats.mean()))
+                .collect(),
+        }
+    }
+    /// Load a baseline from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    /// Save the baseline to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, json)
+    }
+    /// Get the mean time of a benchmark
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.means.get(name).copied()
+    }
+}
+
+/// Format a time in seconds with an appropriate unit
+fn format_duration(secs: f64) -> String {
+    if secs >= 1.0 {
+        format!("{secs:.2}s")
+    } else if secs >= 1e-3 {
+        format!("{:.2}ms", secs * 1e3)
+    } else if secs >= 1e-6 {
+        format!("{:.2}µs", secs * 1e6)
+    } else {
+        format!("{:.0}ns", secs * 1e9)
+    }
+}
+
+impl fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean {}, median {}, stddev {} ({} samples of {} iterations)",
+            format_duration(self.mean()),
+            format_duration(self.median()),
+            format_duration(self.stddev()),
+            self.samples.len(),
+            self.iterations
+        )
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn bench_runner() {
+    use crate::{BenchBaseline, BenchRunner, Compiler, Uiua};
+    let code = "\
+X ← ⇡10
+┌─╴bench Sum
+  /+X
+└─╴
+┌─╴bench Product
+  /×+1X
+└─╴";
+    let report = BenchRunner::new(Compiler::new(), Uiua::with_safe_sys())
+        .warmup(1)
+        .samples(3)
+        .run_str(code)
+        .unwrap();
+    let names: Vec<_> = report.benches.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, ["Sum", "Product"]);
+    assert!(report.benches.iter().all(|b| b.stats.samples.len() == 3));
+    assert!(report.benches.iter().all(|b| b.change().is_none()));
+
+    let baseline = BenchBaseline::from_report(&report);
+    let report = BenchRunner::new(Compiler::new(), Uiua::with_safe_sys())
+        .warmup(1)
+        .samples(3)
+        .filter("Sum")
+        .baseline(baseline)
+        .run_str(code)
+        .unwrap();
+    assert_eq!(report.benches.len(), 1);
+    assert_eq!(report.filtered_out, 1);
+    assert!(report.benches[0].change().is_some());
+
+    // Bench scopes are not run normally
+    let mut env = Uiua::with_safe_sys();
+    env.run_str(code).unwrap();
+    assert!(env.take_stack().is_empty());
+}
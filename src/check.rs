@@ -7,15 +7,15 @@ use std::{
     collections::HashMap,
     fmt,
     hash::{DefaultHasher, Hash, Hasher},
-    iter::repeat,
+    iter::{self, repeat},
     slice,
 };
 
 use serde::*;
 
 use crate::{
-    algorithm::validate_size_of, Array, ArrayLen, ImplPrimitive, Node, Primitive, SigNode,
-    Signature, SysOp, Value,
+    algorithm::{pervade, validate_size_of},
+    Array, ArrayLen, FormatShape, ImplPrimitive, Node, Primitive, SigNode, Signature, SysOp, Value,
 };
 
 impl Node {
@@ -36,6 +36,45 @@ impl Node {
     pub fn clean_sig(&self) -> Option<Signature> {
         nodes_clean_sig(slice::from_ref(self))
     }
+    /// Find type and shape errors that will occur whenever this node is run
+    ///
+    /// Only errors involving values whose types and shapes can be
+    /// determined without running the code are reported.
+    pub fn type_errors(&self) -> Vec<SigCheckError> {
+        let mut env = VirtualEnv::new();
+        env.types = Some(TypeChecker::default());
+        _ = env.node(self);
+        env.types.map(|types| types.errors).unwrap_or_default()
+    }
+    /// Get the heights of the virtual stack before and after each node that has a span
+    ///
+    /// Heights are counted from the bottom of this node's arguments.
+    /// Entries are in the order the nodes would be run.
+    pub fn stack_heights(&self) -> Result<Vec<StackHeights>, SigCheckError> {
+        let mut env = VirtualEnv::new();
+        env.heights = Some(Vec::new());
+        env.node(self)?;
+        let args = env.stack.sig().args as i32;
+        let height = |h: i32| (h + args).max(0) as usize;
+        Ok((env.heights.unwrap_or_default().into_iter())
+            .map(|(span, before, after)| StackHeights {
+                span,
+                before: height(before),
+                after: height(after),
+            })
+            .collect())
+    }
+}
+
+/// The heights of the virtual stack before and after a node is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StackHeights {
+    /// The span index of the node
+    pub span: usize,
+    /// The stack height before the node is run
+    pub before: usize,
+    /// The stack height after the node is run
+    pub after: usize,
 }
 
 pub fn nodes_sig(nodes: &[Node]) -> Result<Signature, SigCheckError> {
@@ -84,6 +123,16 @@ struct VirtualEnv {
     stack: Stack,
     under: Stack,
     array_depth: usize,
+    /// Type checking state, if types are being checked
+    types: Option<TypeChecker>,
+    /// The span index and stack heights before and after each node, if they are being recorded
+    heights: Option<Vec<(usize, i32, i32)>>,
+}
+
+#[derive(Debug, Default)]
+struct TypeChecker {
+    errors: Vec<SigCheckError>,
+    fill_depth: usize,
 }
 
 #[derive(Debug, Default)]
@@ -126,33 +175,56 @@ impl Stack {
     }
 }
 
+/// An error that occurs when checking a function's signature
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SigCheckError {
+    /// The error message
     pub message: String,
+    /// The kind of error
     pub kind: SigCheckErrorKind,
+    /// The span index of the node that caused the error, if known
+    pub span: Option<usize>,
 }
 
+/// The kind of a [`SigCheckError`]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum SigCheckErrorKind {
+    /// The signature is incorrect
     Incorrect,
+    /// A loop takes more values than it returns, so it would consume the whole stack
     LoopOverreach,
-    LoopVariable { args: usize },
+    /// A loop returns more values than it takes, so its signature depends on its iteration count
+    LoopVariable {
+        /// The number of arguments before the loop
+        args: usize,
+    },
+    /// A function has no inverse
     NoInverse,
 }
 
 impl SigCheckError {
+    /// Make this a [`SigCheckErrorKind::LoopOverreach`] error
     pub fn loop_overreach(self) -> Self {
         Self {
             kind: SigCheckErrorKind::LoopOverreach,
             ..self
         }
     }
+    /// Make this a [`SigCheckErrorKind::LoopVariable`] error
     pub fn loop_variable(self, args: usize) -> Self {
         Self {
             kind: SigCheckErrorKind::LoopVariable { args },
             ..self
         }
     }
+    /// Set the span of the node that caused the error if it is not already set
+    pub fn at_span(self, span: Option<usize>) -> Self {
+        Self {
+            span: self.span.or(span),
+            ..self
+        }
+    }
+    /// Make this a [`SigCheckErrorKind::NoInverse`] error
     pub fn no_inverse(self) -> Self {
         Self {
             kind: SigCheckErrorKind::NoInverse,
@@ -166,6 +238,7 @@ impl<'a> From<&'a str> for SigCheckError {
         Self {
             message: s.to_string(),
             kind: SigCheckErrorKind::Incorrect,
+            span: None,
         }
     }
 }
@@ -175,6 +248,7 @@ impl From<String> for SigCheckError {
         Self {
             message: s,
             kind: SigCheckErrorKind::Incorrect,
+            span: None,
         }
     }
 }
@@ -189,6 +263,7 @@ impl fmt::Display for SigCheckError {
 enum BasicValue {
     Num(f64),
     Arr(Vec<Self>),
+    Typed(ValueType),
     Other,
 }
 
@@ -199,15 +274,123 @@ impl BasicValue {
         } else if let Some(n) = value.as_byte_array().and_then(Array::as_scalar) {
             BasicValue::Num(*n as f64)
         } else if value.rank() == 1 {
+            let scalar = |elem| BasicValue::Typed(ValueType::scalar(elem));
             BasicValue::Arr(match value {
                 Value::Num(n) => n.data.iter().map(|n| BasicValue::Num(*n)).collect(),
                 Value::Byte(b) => b.data.iter().map(|b| BasicValue::Num(*b as f64)).collect(),
-                Value::Complex(c) => c.data.iter().map(|_| BasicValue::Other).collect(),
-                Value::Char(c) => c.data.iter().map(|_| BasicValue::Other).collect(),
-                Value::Box(b) => b.data.iter().map(|_| BasicValue::Other).collect(),
+                Value::Complex(c) => c.data.iter().map(|_| scalar(ElemType::Complex)).collect(),
+                Value::Char(c) => c.data.iter().map(|_| scalar(ElemType::Char)).collect(),
+                Value::Box(b) => b.data.iter().map(|_| scalar(ElemType::Box)).collect(),
             })
         } else {
-            BasicValue::Other
+            BasicValue::Typed(ValueType {
+                elem: ElemType::of(value),
+                shape: Some(value.shape().to_vec()),
+            })
+        }
+    }
+    /// Get the element type and shape of this value, if they are known
+    fn ty(&self) -> Option<ValueType> {
+        match self {
+            BasicValue::Num(_) => Some(ValueType::scalar(ElemType::Num)),
+            BasicValue::Typed(ty) => Some(ty.clone()),
+            BasicValue::Arr(items) => {
+                let mut tys = items.iter().map(BasicValue::ty);
+                let Some(first) = tys.next() else {
+                    return Some(ValueType {
+                        elem: ElemType::Num,
+                        shape: Some(vec![0]),
+                    });
+                };
+                let first = first?;
+                let mut elem = first.elem;
+                let mut shape = first.shape;
+                for ty in tys {
+                    let ty = ty?;
+                    elem = match (elem, ty.elem) {
+                        (a, b) if a == b => a,
+                        (ElemType::Num, ElemType::Complex) | (ElemType::Complex, ElemType::Num) => {
+                            ElemType::Complex
+                        }
+                        _ => return None,
+                    };
+                    if shape != ty.shape {
+                        shape = None;
+                    }
+                }
+                let shape = shape.map(|shape| iter::once(items.len()).chain(shape).collect());
+                Some(ValueType { elem, shape })
+            }
+            BasicValue::Other => None,
+        }
+    }
+    fn is_scalar(&self) -> bool {
+        self.ty()
+            .and_then(|ty| ty.shape)
+            .is_some_and(|shape| shape.is_empty())
+    }
+}
+
+/// The element type of an array, as tracked by the type checker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ElemType {
+    Num,
+    Complex,
+    Char,
+    Box,
+}
+
+impl ElemType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Num(_) | Value::Byte(_) => ElemType::Num,
+            Value::Complex(_) => ElemType::Complex,
+            Value::Char(_) => ElemType::Char,
+            Value::Box(_) => ElemType::Box,
+        }
+    }
+}
+
+impl fmt::Display for ElemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElemType::Num => write!(f, "number"),
+            ElemType::Complex => write!(f, "complex"),
+            ElemType::Char => write!(f, "character"),
+            ElemType::Box => write!(f, "box"),
+        }
+    }
+}
+
+/// The element type and, if known, the shape of a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ValueType {
+    elem: ElemType,
+    shape: Option<Vec<usize>>,
+}
+
+impl ValueType {
+    fn scalar(elem: ElemType) -> Self {
+        ValueType {
+            elem,
+            shape: Some(Vec::new()),
+        }
+    }
+}
+
+impl BasicValue {
+    /// Get the rows of a list of numbers
+    ///
+    /// Other lists may have been joined with arrays of a different rank,
+    /// so their row counts are not reliable.
+    fn rows(&self) -> Option<&[Self]> {
+        match self {
+            BasicValue::Arr(items)
+                if items.iter().all(|item| matches!(item, BasicValue::Num(_))) =>
+            {
+                Some(items)
+            }
+            _ => None,
         }
     }
 }
@@ -222,13 +405,18 @@ impl FromIterator<f64> for BasicValue {
 }
 
 impl VirtualEnv {
-    fn from_nodes(nodes: &[Node]) -> Result<Self, SigCheckError> {
-        // println!("\ncheck sig: {nodes:?}");
-        let mut env = VirtualEnv {
+    fn new() -> Self {
+        VirtualEnv {
             stack: Stack::default(),
             under: Stack::default(),
             array_depth: 0,
-        };
+            types: None,
+            heights: None,
+        }
+    }
+    fn from_nodes(nodes: &[Node]) -> Result<Self, SigCheckError> {
+        // println!("\ncheck sig: {nodes:?}");
+        let mut env = VirtualEnv::new();
         env.nodes(nodes)?;
         Ok(env)
     }
@@ -239,12 +427,32 @@ impl VirtualEnv {
         nodes.iter().try_for_each(|node| self.node(node))
     }
     fn node(&mut self, node: &Node) -> Result<(), SigCheckError> {
+        let height = self.stack.height;
+        // Reserve the entry first so that outer nodes come before their inner nodes.
+        // Runs are skipped, as their span is just that of their first node.
+        let entry = (self.heights.as_mut()).and_then(|heights| {
+            if let Node::Run(_) = node {
+                return None;
+            }
+            let span = node.span()?;
+            heights.push((span, height, height));
+            Some(heights.len() - 1)
+        });
+        let res = (self.node_impl(node)).map_err(|e| e.at_span(node.span()));
+        if let Some((heights, i)) = self.heights.as_mut().zip(entry) {
+            heights[i].2 = self.stack.height;
+        }
+        res
+    }
+    fn node_impl(&mut self, node: &Node) -> Result<(), SigCheckError> {
         use ImplPrimitive::*;
         use Primitive::*;
         match node {
             Node::Run(nodes) => nodes.iter().try_for_each(|node| self.node(node))?,
             Node::Push(val) => self.push(BasicValue::from_val(val)),
-            Node::Array { len, inner, .. } => match len {
+            Node::Array {
+                len, inner, boxed, ..
+            } => match len {
                 ArrayLen::Static(len) if *len < 100 => {
                     self.array_depth += 1;
                     self.node(inner)?;
@@ -257,7 +465,14 @@ impl VirtualEnv {
                     self.stack.height = bottom;
                     self.stack.set_min_height();
                     items.reverse();
-                    self.push(BasicValue::Arr(items));
+                    if *boxed {
+                        self.push(BasicValue::Typed(ValueType {
+                            elem: ElemType::Box,
+                            shape: Some(vec![items.len()]),
+                        }));
+                    } else {
+                        self.push(BasicValue::Arr(items));
+                    }
                 }
                 ArrayLen::Static(len) => {
                     self.array_depth += 1;
@@ -274,8 +489,12 @@ impl VirtualEnv {
             Node::CustomInverse(cust, _) => self.handle_sig(cust.sig()?),
             Node::Dynamic(dy) => self.handle_sig(dy.sig),
             &Node::Switch {
-                sig, under_cond, ..
+                ref branches,
+                sig,
+                under_cond,
+                ..
             } => {
+                check_switch_branches(branches, sig)?;
                 let cond = self.pop();
                 if under_cond {
                     self.under.push(cond);
@@ -323,66 +542,40 @@ impl VirtualEnv {
                 let args = neighbors.args.max(is_goal.args).saturating_sub(1);
                 self.handle_args_outputs(args, has_costs as usize);
             }
-            Node::Prim(prim, _) => match prim {
-                Dup => {
-                    let val = self.pop();
-                    self.push(val.clone());
-                    self.push(val);
-                }
-                Flip => {
-                    let a = self.pop();
-                    let b = self.pop();
-                    self.push(a);
-                    self.push(b);
-                }
-                Pop => {
-                    self.pop();
-                }
-                Over => {
-                    let a = self.pop();
-                    let b = self.pop();
-                    self.push(b.clone());
-                    self.push(a);
-                    self.push(b);
-                }
-                Around => {
-                    let a = self.pop();
-                    let b = self.pop();
-                    self.push(a.clone());
-                    self.push(b);
-                    self.push(a);
-                }
-                Join => {
-                    let a = self.pop();
-                    let b = self.pop();
-                    match (a, b) {
-                        (BasicValue::Arr(mut a), BasicValue::Arr(b)) => {
-                            a.extend(b);
-                            self.push(BasicValue::Arr(a));
-                        }
-                        (BasicValue::Arr(mut a), b) => {
-                            a.push(b);
-                            self.push(BasicValue::Arr(a));
-                        }
-                        (a, BasicValue::Arr(mut b)) => {
-                            b.insert(0, a);
-                            self.push(BasicValue::Arr(b));
-                        }
-                        (a, b) => {
-                            self.push(BasicValue::Arr(vec![a, b]));
-                        }
+            Node::ImplMod(BoundedPath, args, _) => {
+                let _max_cost = self.pop();
+                let _start = self.pop();
+                let [neighbors, is_goal] = get_args(args)?;
+                let has_costs = neighbors.outputs == 2;
+                let args = neighbors.args.max(is_goal.args).saturating_sub(1);
+                self.handle_args_outputs(args, 1 + has_costs as usize);
+            }
+            Node::ImplMod(BoundedAstar, args, _) => {
+                let _max_cost = self.pop();
+                let _start = self.pop();
+                let [neighbors, heuristic, is_goal] = get_args(args)?;
+                let has_costs = neighbors.outputs == 2;
+                let args = neighbors
+                    .args
+                    .max(heuristic.args)
+                    .max(is_goal.args)
+                    .saturating_sub(1);
+                self.handle_args_outputs(args, 1 + has_costs as usize);
+            }
+            Node::Mod(Components | Toposort | StrongComponents | SpanningTree, args, _) => {
+                let _nodes = self.pop();
+                let [neighbors] = get_args(args)?;
+                self.handle_args_outputs(neighbors.args.saturating_sub(1), 1);
+            }
+            &Node::Prim(prim, span) => {
+                let ty = self.prim_type(prim, span);
+                self.prim(prim)?;
+                if let Some(ty) = ty {
+                    if let Some(top @ BasicValue::Other) = self.stack.stack.last_mut() {
+                        *top = BasicValue::Typed(ty);
                     }
                 }
-                prim => {
-                    let args = prim
-                        .args()
-                        .ok_or_else(|| format!("{prim} has indeterminate args"))?;
-                    let outputs = prim
-                        .outputs()
-                        .ok_or_else(|| format!("{prim} has indeterminate outputs"))?;
-                    self.handle_args_outputs(args, outputs);
-                }
-            },
+            }
             Node::ImplPrim(prim, _) => {
                 let args = prim
                     .args()
@@ -421,6 +614,22 @@ impl VirtualEnv {
                     let [sig] = get_args(args)?;
                     self.handle_args_outputs(sig.args, 1);
                 }
+                Timeout => {
+                    let [sig] = get_args(args)?;
+                    self.handle_args_outputs(sig.args + 1, sig.outputs);
+                }
+                Using => {
+                    let [sig] = get_args(args)?;
+                    self.handle_args_outputs(sig.args.max(1), sig.outputs);
+                }
+                Retry => {
+                    let [sig] = get_args(args)?;
+                    self.handle_args_outputs(sig.args + 2, sig.outputs);
+                }
+                Update => {
+                    let [sig] = get_args(args)?;
+                    self.handle_args_outputs(sig.args.max(1), sig.outputs.saturating_sub(1));
+                }
                 Repeat => {
                     let [f] = get_args_nodes(args)?;
                     let n = self.pop();
@@ -453,8 +662,8 @@ impl VirtualEnv {
                     self.handle_sig(sig.anti().unwrap_or(sig));
                 }
                 Fold => {
-                    let [f] = get_args(args)?;
-                    self.handle_sig(f);
+                    let [f] = get_args_nodes(args)?;
+                    self.fold(f)?;
                 }
                 Try => {
                     let [f_sig, _handler_sig] = get_args(args)?;
@@ -465,10 +674,14 @@ impl VirtualEnv {
                     self.handle_sig(f);
                 }
                 Fill => self.fill(args)?,
-                Content | Memo | Comptime => {
+                Content | Memo | Comptime | Interval => {
                     let [f] = get_args(args)?;
                     self.handle_sig(f);
                 }
+                Gradient => {
+                    let [f] = get_args(args)?;
+                    self.handle_args_outputs(f.args, f.args);
+                }
                 Dump => {
                     let [_] = get_args(args)?;
                 }
@@ -523,14 +736,13 @@ impl VirtualEnv {
                     for _ in 0..f.sig.args {
                         args.push(self.pop());
                     }
-                    let x = args.last().cloned();
+                    if let Some(x) = args.last() {
+                        self.push(x.clone());
+                    }
                     for arg in args.into_iter().rev() {
                         self.push(arg);
                     }
                     self.node(&f.node)?;
-                    if let Some(x) = x {
-                        self.push(x);
-                    }
                 }
                 Above | Below => {
                     let [f] = get_args(args)?;
@@ -544,10 +756,22 @@ impl VirtualEnv {
                     let [f] = get_args(args)?;
                     self.handle_sig(f);
                 }
+                Sys(SysOp::Lines) => {
+                    let [f] = get_args(args)?;
+                    self.handle_sig(f);
+                }
+                Sys(SysOp::Chunks | SysOp::TeeStream) => {
+                    let [f] = get_args(args)?;
+                    self.handle_args_outputs(f.args + 1, f.outputs);
+                }
                 Sys(SysOp::AudioStream) => {
                     let [f] = get_args(args)?;
                     self.handle_args_outputs(f.args.saturating_sub(1), f.outputs.saturating_sub(1));
                 }
+                Sys(SysOp::AudioCallback) => {
+                    let [f] = get_args(args)?;
+                    self.handle_args_outputs(f.args.saturating_sub(2), f.outputs.saturating_sub(1));
+                }
                 prim if prim.modifier_args().is_some() => {
                     if let Some(sig) = prim.sig() {
                         self.handle_sig(sig);
@@ -656,6 +880,123 @@ impl VirtualEnv {
         // println!("{node:?} -> {} ({})", self.stack.sig(), self.under.sig());
         Ok(())
     }
+    fn prim(&mut self, prim: Primitive) -> Result<(), SigCheckError> {
+        use Primitive::*;
+        match prim {
+            Dup => {
+                let val = self.pop();
+                self.push(val.clone());
+                self.push(val);
+            }
+            Flip => {
+                let a = self.pop();
+                let b = self.pop();
+                self.push(a);
+                self.push(b);
+            }
+            Pop => {
+                self.pop();
+            }
+            Over => {
+                let a = self.pop();
+                let b = self.pop();
+                self.push(b.clone());
+                self.push(a);
+                self.push(b);
+            }
+            Around => {
+                let a = self.pop();
+                let b = self.pop();
+                self.push(a.clone());
+                self.push(b);
+                self.push(a);
+            }
+            Join => {
+                let a = self.pop();
+                let b = self.pop();
+                // Only scalars can be known to become a single row
+                let val = match (a, b) {
+                    (BasicValue::Arr(mut a), BasicValue::Arr(b)) => {
+                        a.extend(b);
+                        BasicValue::Arr(a)
+                    }
+                    (BasicValue::Arr(mut a), b) if b.is_scalar() => {
+                        a.push(b);
+                        BasicValue::Arr(a)
+                    }
+                    (a, BasicValue::Arr(mut b)) if a.is_scalar() => {
+                        b.insert(0, a);
+                        BasicValue::Arr(b)
+                    }
+                    (a, b) if a.is_scalar() && b.is_scalar() => BasicValue::Arr(vec![a, b]),
+                    _ => BasicValue::Other,
+                };
+                self.push(val);
+            }
+            Len => {
+                let val = self.pop();
+                let len = match val {
+                    BasicValue::Num(_) => BasicValue::Num(1.0),
+                    val => (val.rows())
+                        .map_or(BasicValue::Other, |rows| BasicValue::Num(rows.len() as f64)),
+                };
+                self.push(len);
+            }
+            Neg | Abs => {
+                let val = match self.pop() {
+                    BasicValue::Num(n) if prim == Neg => BasicValue::Num(-n),
+                    BasicValue::Num(n) => BasicValue::Num(n.abs()),
+                    _ => BasicValue::Other,
+                };
+                self.push(val);
+            }
+            Add | Sub | Mul | Div | Modulus | Min | Max => {
+                let a = self.pop();
+                let b = self.pop();
+                let val = match (a, b) {
+                    (BasicValue::Num(a), BasicValue::Num(b)) => BasicValue::Num(match prim {
+                        Add => pervade::add::num_num(a, b),
+                        Sub => pervade::sub::num_num(a, b),
+                        Mul => pervade::mul::num_num(a, b),
+                        Div => pervade::div::num_num(a, b),
+                        Modulus => pervade::modulus::num_num(a, b),
+                        Min => pervade::min::num_num(a, b),
+                        _ => pervade::max::num_num(a, b),
+                    }),
+                    _ => BasicValue::Other,
+                };
+                self.push(val);
+            }
+            prim => {
+                let args = prim
+                    .args()
+                    .ok_or_else(|| format!("{prim} has indeterminate args"))?;
+                let outputs = prim
+                    .outputs()
+                    .ok_or_else(|| format!("{prim} has indeterminate outputs"))?;
+                self.handle_args_outputs(args, outputs);
+            }
+        }
+        Ok(())
+    }
+    /// Check the types of a primitive's arguments and get the type of its output
+    fn prim_type(&mut self, prim: Primitive, span: usize) -> Option<ValueType> {
+        let types = self.types.as_mut()?;
+        let arg = |i: usize| {
+            let stack = &self.stack.stack;
+            (stack.len().checked_sub(i + 1)).and_then(|i| stack[i].ty())
+        };
+        match prim_type(prim, arg(0), arg(1), types.fill_depth > 0) {
+            Ok(ty) => ty,
+            Err(message) => {
+                let error = SigCheckError::from(message).at_span(Some(span));
+                if !types.errors.contains(&error) {
+                    types.errors.push(error);
+                }
+                None
+            }
+        }
+    }
     fn push(&mut self, val: BasicValue) {
         self.stack.push(val);
     }
@@ -678,13 +1019,40 @@ impl VirtualEnv {
     fn handle_sig(&mut self, sig: Signature) {
         self.handle_args_outputs(sig.args, sig.outputs)
     }
+    fn fold(&mut self, f: &SigNode) -> Result<(), SigCheckError> {
+        let sig = f.sig;
+        if sig.args != sig.outputs + 1 {
+            self.handle_sig(sig);
+            return Ok(());
+        }
+        let arr = self.pop();
+        // If the rows are known, the function is checked for each one.
+        // This keeps under stack changes consistent with a repeat over the row count.
+        match arr.rows() {
+            Some(rows) if (1..=100).contains(&rows.len()) => {
+                for row in rows.iter().cloned() {
+                    self.push(row);
+                    self.node(&f.node)?;
+                }
+            }
+            _ => self.handle_args_outputs(sig.outputs, sig.outputs),
+        }
+        Ok(())
+    }
     fn fill(&mut self, args: &[SigNode]) -> Result<(), SigCheckError> {
         let [fill, f] = get_args_nodes(args)?;
         if fill.sig.outputs > 0 || fill.sig.args > 0 && fill.sig.outputs != 0 {
             self.node(&fill.node)?;
         }
         self.handle_args_outputs(fill.sig.outputs, 0);
-        self.node(&f.node)
+        if let Some(types) = &mut self.types {
+            types.fill_depth += 1;
+        }
+        let res = self.node(&f.node);
+        if let Some(types) = &mut self.types {
+            types.fill_depth -= 1;
+        }
+        res
     }
     fn repeat(
         &mut self,
@@ -759,6 +1127,202 @@ impl VirtualEnv {
     }
 }
 
+/// Check the types of a primitive's arguments and get the type of its output
+///
+/// `a` is the top of the stack. Only errors that will occur regardless
+/// of the values' contents are reported.
+fn prim_type(
+    prim: Primitive,
+    a: Option<ValueType>,
+    b: Option<ValueType>,
+    fill: bool,
+) -> Result<Option<ValueType>, String> {
+    use ElemType::{Char, Num};
+    use Primitive::*;
+    Ok(match prim {
+        Not | Sign | Neg | Abs | Sqrt | Floor | Ceil | Round => {
+            let Some(a) = a else {
+                return Ok(None);
+            };
+            let elem = match (prim, a.elem) {
+                (Not | Sqrt | Floor | Ceil | Round, Char) => {
+                    return Err(format!(
+                        "{} is not defined for character arrays",
+                        prim.format()
+                    ))
+                }
+                (Neg | Abs, Char) => Char,
+                (_, ElemType::Box) => ElemType::Box,
+                (_, Num) => Num,
+                _ => return Ok(None),
+            };
+            Some(ValueType {
+                elem,
+                shape: a.shape,
+            })
+        }
+        Add | Sub | Mul | Div | Modulus | Pow | Log | Min | Max | Atan | Complex | Eq | Ne | Lt
+        | Le | Gt | Ge => {
+            let (Some(a), Some(b)) = (a, b) else {
+                return Ok(None);
+            };
+            let undefined = match (prim, a.elem, b.elem) {
+                (Add | Mul, Char, Char) => true,
+                (Sub, Char, Num | ElemType::Complex) => true,
+                (Div | Pow, Char, Num | ElemType::Complex | Char) => true,
+                (Modulus | Log | Atan | Complex, Char, Num | ElemType::Complex | Char) => true,
+                (Modulus | Log | Atan | Complex, Num, Char) => true,
+                (Min | Max, Char, Num | ElemType::Complex) | (Min | Max, Num, Char) => true,
+                _ => false,
+            };
+            if undefined {
+                return Err(format!(
+                    "{} is not defined for {} and {} arrays",
+                    prim.format(),
+                    a.elem,
+                    b.elem
+                ));
+            }
+            let shape = match (a.shape, b.shape) {
+                (Some(ash), Some(bsh)) => match pervasive_shape(&ash, &bsh) {
+                    Some(shape) => Some(shape),
+                    None if fill => None,
+                    None => {
+                        return Err(format!(
+                            "{} is used with arrays of shapes {} and {}, \
+                            which are not compatible",
+                            prim.format(),
+                            FormatShape(&ash),
+                            FormatShape(&bsh)
+                        ))
+                    }
+                },
+                _ => None,
+            };
+            let elem = match (prim, a.elem, b.elem) {
+                (_, ElemType::Box, _) | (_, _, ElemType::Box) => ElemType::Box,
+                (Eq | Ne | Lt | Le | Gt | Ge, ..) => Num,
+                (Add, Char, _) | (Add, _, Char) | (Sub, Num, Char) => Char,
+                (Sub, Char, Char) => Num,
+                (Complex, ..) => ElemType::Complex,
+                (_, Num, Num) => Num,
+                _ => return Ok(None),
+            };
+            Some(ValueType { elem, shape })
+        }
+        Join | Couple => {
+            let (Some(a), Some(b)) = (a, b) else {
+                return Ok(None);
+            };
+            let elem = match (a.elem, b.elem) {
+                (x, y) if x == y => x,
+                (Char, Num) | (Num, Char) => {
+                    return Err(format!(
+                        "{} is not defined for {} and {} arrays",
+                        prim.format(),
+                        a.elem,
+                        b.elem
+                    ))
+                }
+                (ElemType::Box, _) | (_, ElemType::Box) => return Ok(None),
+                _ => ElemType::Complex,
+            };
+            let (Some(ash), Some(bsh)) = (a.shape, b.shape) else {
+                return Ok(None);
+            };
+            let shape = if prim == Couple {
+                let (short, long) = if ash.len() <= bsh.len() {
+                    (&ash, &bsh)
+                } else {
+                    (&bsh, &ash)
+                };
+                (long.starts_with(short))
+                    .then(|| iter::once(2).chain(long.iter().copied()).collect())
+            } else {
+                match ash.len() as isize - bsh.len() as isize {
+                    _ if ash.is_empty() && bsh.is_empty() => Some(vec![2]),
+                    0 if ash[1..] == bsh[1..] => Some(
+                        iter::once(ash[0] + bsh[0])
+                            .chain(ash[1..].iter().copied())
+                            .collect(),
+                    ),
+                    0 => None,
+                    1 if ash[1..] == bsh => {
+                        Some(iter::once(ash[0] + 1).chain(bsh.iter().copied()).collect())
+                    }
+                    -1 if bsh[1..] == ash => {
+                        Some(iter::once(bsh[0] + 1).chain(ash.iter().copied()).collect())
+                    }
+                    1 | -1 if !ash.is_empty() && !bsh.is_empty() => None,
+                    _ => return Ok(None),
+                }
+            };
+            match shape {
+                Some(shape) => Some(ValueType {
+                    elem,
+                    shape: Some(shape),
+                }),
+                None if fill => None,
+                None => {
+                    return Err(format!(
+                        "{} is used with arrays of shapes {} and {}, \
+                        which are not compatible",
+                        prim.format(),
+                        FormatShape(&ash),
+                        FormatShape(&bsh)
+                    ))
+                }
+            }
+        }
+        Box => Some(ValueType::scalar(ElemType::Box)),
+        Len | Type | Match => Some(ValueType::scalar(Num)),
+        Shape | Where | IndexOf => Some(ValueType {
+            elem: Num,
+            shape: None,
+        }),
+        _ => None,
+    })
+}
+
+/// Get the shape of the result of a pervasive operation,
+/// or [`None`] if the shapes are not compatible
+fn pervasive_shape(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    let mut shape = if a.len() >= b.len() { a } else { b }.to_vec();
+    for (i, (&x, &y)) in a.iter().zip(b).enumerate() {
+        if x == 1 {
+            shape[i] = y;
+        } else if y != 1 && x != y {
+            return None;
+        }
+    }
+    Some(shape)
+}
+
+/// Check that every switch branch fits the switch's signature
+///
+/// Branches that assert are skipped, as they may never return.
+/// The error points at the first incompatible branch.
+fn check_switch_branches(branches: &[SigNode], sig: Signature) -> Result<(), SigCheckError> {
+    let fits = |branch: &SigNode| {
+        branch.sig.fits_switch(sig)
+            || (branch.node.iter()).any(|node| matches!(node, Node::Prim(Primitive::Assert, _)))
+    };
+    let Some(offending) = branches.iter().find(|branch| !fits(branch)) else {
+        return Ok(());
+    };
+    let mut message = format!(
+        "Switch branches are incompatible with the switch's signature {sig}. \
+        Branch signatures:"
+    );
+    for (i, branch) in branches.iter().enumerate() {
+        message.push_str(&format!("\n  {i}: {}", branch.sig));
+        if !fits(branch) {
+            message.push_str(" (incompatible)");
+        }
+    }
+    Err(SigCheckError::from(message).at_span(offending.node.span()))
+}
+
 fn get_args_nodes<const N: usize>(args: &[SigNode]) -> Result<[&SigNode; N], SigCheckError> {
     if args.len() != N {
         return Err(format!("Expected {} arguments, but got {}", N, args.len()).into());
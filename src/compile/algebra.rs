@@ -5,10 +5,11 @@ use ecow::eco_vec;
 use serde::*;
 
 use crate::{
-    Assembly, Complex,
+    algorithm::walk::{walk, WalkError, Walker},
+    Array, Assembly, Complex,
     ImplPrimitive::*,
     Node::{self, *},
-    Primitive::*,
+    Primitive::{self, *},
     SigNode, Value,
 };
 
@@ -134,13 +135,24 @@ pub fn algebraic_inverse(nodes: &[Node], asm: &Assembly) -> Result<Node, Option<
 pub fn derivative(node: &Node, asm: &Assembly) -> AlgebraResult<Node> {
     dbgln!("derivative of {node:?}");
     let data = nodes_expr(node, asm);
-    let expr = data.expr.inspect_err(|e| dbgln!("{e:?}"))?;
-    dbgln!("experession: {expr:?}");
-    let deriv = expr_deriv(expr).ok_or(AlgebraError::TooComplex)?;
-    dbgln!("derivative: {deriv:?}");
-    let node = expr_to_node(deriv, data.any_complex, asm);
-    dbgln!("derivative node: {node:?}");
-    Ok(node)
+    let symbolic = data.expr.and_then(|expr| {
+        dbgln!("experession: {expr:?}");
+        let deriv = expr_deriv(expr).ok_or(AlgebraError::TooComplex)?;
+        dbgln!("derivative: {deriv:?}");
+        Ok(expr_to_node(deriv, data.any_complex, asm))
+    });
+    match symbolic {
+        Ok(node) => {
+            dbgln!("derivative node: {node:?}");
+            Ok(node)
+        }
+        Err(e) => {
+            dbgln!("{e:?}");
+            // Fall back to differentiating with dual numbers,
+            // which handles the product and chain rules
+            dual_derivative(node, asm).map_err(|_| e)
+        }
+    }
 }
 
 pub fn integral(node: &Node, asm: &Assembly) -> AlgebraResult<Node> {
@@ -155,6 +167,122 @@ pub fn integral(node: &Node, asm: &Assembly) -> AlgebraResult<Node> {
     Ok(node)
 }
 
+/// Differentiate a function by rewriting it to operate on dual numbers
+///
+/// Each value becomes an array whose first row is the original value
+/// and whose second row is its derivative.
+fn dual_derivative(node: &Node, asm: &Assembly) -> AlgebraResult<Node> {
+    let sig = node.sig().map_err(|_| AlgebraError::InterpreterBug)?;
+    if sig.args != 1 {
+        return Err(AlgebraError::TooManyVariables);
+    }
+    match sig.outputs {
+        0 => return Err(AlgebraError::NoOutput),
+        1 => {}
+        _ => return Err(AlgebraError::TooManyOutputs),
+    }
+    let span = asm.spans.len() - 1;
+    let mut dual = Node::from_iter([
+        Prim(Dup, span),
+        Node::new_push(0.0),
+        Prim(Mul, span),
+        Node::new_push(1.0),
+        Prim(Add, span),
+        Prim(Flip, span),
+        Prim(Couple, span),
+    ]);
+    let mut rewriter = DualRewriter {
+        asm,
+        depth: 0,
+        node: Node::empty(),
+    };
+    walk(&mut rewriter, node)?;
+    dual.push(rewriter.node);
+    dual.push(Node::new_push(1.0));
+    dual.push(Prim(Select, span));
+    dbgln!("dual derivative node: {dual:?}");
+    Ok(dual)
+}
+
+/// Rewrites a function to operate on dual numbers
+struct DualRewriter<'a> {
+    asm: &'a Assembly,
+    depth: usize,
+    /// The rewritten nodes
+    node: Node,
+}
+
+impl Walker for DualRewriter<'_> {
+    type Error = AlgebraError;
+    fn asm(&self) -> &Assembly {
+        self.asm
+    }
+    fn depth(&mut self) -> &mut usize {
+        &mut self.depth
+    }
+    fn error(&self, error: WalkError) -> AlgebraError {
+        match error {
+            WalkError::TooDeep => AlgebraError::TooComplex,
+            WalkError::NotSupported(what) => AlgebraError::NotSupported(what),
+            WalkError::NoInverse(_) => AlgebraError::NoInverse,
+        }
+    }
+    fn leaf(&mut self, node: &Node) -> AlgebraResult {
+        let node = match node {
+            Push(val) => {
+                let arr = match val {
+                    Value::Num(arr) => arr.clone(),
+                    Value::Byte(arr) => arr.convert_ref(),
+                    _ => return Err(AlgebraError::NonReal),
+                };
+                let mut shape = arr.shape.clone();
+                shape.insert(0, 2);
+                let mut data = arr.data;
+                data.extend_repeat(&0.0, arr.shape.elements());
+                Node::new_push(Array::new(shape, data))
+            }
+            Prim(prim, span) => {
+                let span = *span;
+                match prim {
+                    Identity | Pop | Dup | Flip | Over | Neg | Add | Sub => node.clone(),
+                    Not => Node::from_iter([
+                        Prim(Neg, span),
+                        Node::new_push(Array::from([1.0, 0.0].as_slice())),
+                        Prim(Add, span),
+                    ]),
+                    Mul => ImplPrim(DualMul, span),
+                    Div => ImplPrim(DualDiv, span),
+                    Pow => ImplPrim(DualPow, span),
+                    Log => ImplPrim(DualLog, span),
+                    Sqrt => ImplPrim(DualSqrt, span),
+                    Sin => ImplPrim(DualSin, span),
+                    Abs => ImplPrim(DualAbs, span),
+                    prim => return Err(AlgebraError::NotSupported(prim.format().to_string())),
+                }
+            }
+            ImplPrim(Cos, span) => ImplPrim(DualCos, *span),
+            ImplPrim(prim, _) => return Err(AlgebraError::NotSupported(prim.to_string())),
+            Mod(prim, ..) => return Err(AlgebraError::NotSupported(prim.format().to_string())),
+            CopyToUnder(..) | PushUnder(..) | PopUnder(..) => node.clone(),
+            node => return Err(AlgebraError::NotSupported(format!("{node:?}"))),
+        };
+        self.node.push(node);
+        Ok(())
+    }
+    fn combinator(&mut self, prim: Primitive, args: &[SigNode], span: usize) -> AlgebraResult {
+        let outer = take(&mut self.node);
+        let mut rewritten = Vec::with_capacity(args.len());
+        for sn in args {
+            walk(self, &sn.node)?;
+            rewritten.push(SigNode::new(sn.sig, take(&mut self.node)));
+        }
+        self.node = outer;
+        let args = rewritten.into_iter().collect();
+        self.node.push(Mod(prim, args, span));
+        Ok(())
+    }
+}
+
 fn expr_deriv(expr: Expr) -> Option<Expr> {
     let mut deriv = Expr::default();
     for (term, mut coef) in expr.0 {
@@ -168,7 +296,8 @@ fn expr_deriv(expr: Expr) -> Option<Expr> {
                 *deriv.0.entry(Term::X(x)).or_default() += coef;
             }
             Term::Div(expr) => {
-                *deriv.0.entry(Term::Div(expr.pow(2.0.into())?)).or_default() += coef
+                let prime = expr_deriv(expr.clone())?.as_constant()?;
+                *deriv.0.entry(Term::Div(expr.pow(2.0.into())?)).or_default() -= coef * prime
             }
             Term::Log(base, expr) => {
                 let prime = expr_deriv(expr.clone())?.as_constant()?;
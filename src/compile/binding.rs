@@ -1,21 +1,25 @@
 //! Compiler code for bindings
 
-use crate::BindingMeta;
+use crate::{BenchBlock, BindingMeta, TestBlock};
 
 use super::*;
 
 impl Compiler {
-    pub(super) fn binding(&mut self, binding: Binding, prelude: BindingPrelude) -> UiuaResult {
+    pub(super) fn binding(&mut self, mut binding: Binding, prelude: BindingPrelude) -> UiuaResult {
         let public = binding.public;
 
         // Alias re-bound imports
+        // Binding an existing name to itself re-exports it
         if ident_modifier_args(&binding.name.value) == 0
             && binding.words.iter().filter(|w| w.value.is_code()).count() == 1
         {
+            let rebinds_existing = (self.find_name(&binding.name.value, false)).is_some();
             if let Some(r) = binding.words.iter().find_map(|w| match &w.value {
                 Word::Ref(r)
                     if ident_modifier_args(&r.name.value) == 0
-                        && !(r.path.is_empty() && r.name.value == binding.name.value) =>
+                        && !(r.path.is_empty()
+                            && r.name.value == binding.name.value
+                            && !rebinds_existing) =>
                 {
                     Some(r)
                 }
@@ -37,6 +41,7 @@ impl Compiler {
             }
         }
 
+        let source = binding.span();
         let name = binding.name.value;
         let span = &binding.name.span;
 
@@ -67,10 +72,12 @@ impl Compiler {
             comment,
             deprecation,
             counts: Some(binding.counts),
+            source: Some(source),
         };
 
         // Handle macro
         let ident_margs = ident_modifier_args(&name);
+        self.number_placeholders(&mut binding.words)?;
         let max_placeholder = max_placeholder(&binding.words);
         if binding.code_macro {
             if max_placeholder.is_some() {
@@ -242,6 +249,7 @@ impl Compiler {
                         }
                     }
                 }
+                comp.check_types(&node);
                 if prelude.track_caller {
                     node = Node::TrackCaller(node.into());
                 }
@@ -261,15 +269,14 @@ impl Compiler {
             });
 
         // Compile the body
-        self.current_bindings.push(CurrentBinding {
-            name: name.clone(),
-            signature: binding.signature.as_ref().map(|s| s.value),
-            recurses: 0,
-            global_index: local.index,
-        });
         let no_code_words = binding.words.iter().all(|w| !w.value.is_code());
-        let node = self.words(binding.words);
-        let self_referenced = self.current_bindings.pop().unwrap().recurses > 0;
+        let declared_sig = binding.signature.as_ref().map(|s| s.value);
+        let (node, self_referenced) =
+            if declared_sig.is_none() && words_reference(&binding.words, &name) {
+                self.recursive_binding_body(&name, local.index, binding.words)
+            } else {
+                self.binding_body(&name, declared_sig, local.index, binding.words)
+            };
         let mut node = match node {
             Ok(node) => node,
             Err(e) => {
@@ -377,23 +384,47 @@ impl Compiler {
                 );
             }
             Err(e) => self.add_error(
-                binding.name.span.clone(),
+                self.sig_error_span(&e, &binding.name.span),
                 format!("Cannot infer function signature: {e}"),
             ),
         }
         Ok(())
     }
     pub(super) fn module(&mut self, m: Sp<ScopedModule>, prelude: BindingPrelude) -> UiuaResult {
+        let span = m.span;
         let m = m.value;
         let scope_kind = match &m.kind {
             ModuleKind::Named(name) => ScopeKind::Module(name.value.clone()),
-            ModuleKind::Test => ScopeKind::Test,
+            ModuleKind::Test(_) => ScopeKind::Test,
+            ModuleKind::Bench(_) => ScopeKind::Bench,
         };
-        let (module, ()) = self.in_scope(scope_kind, |comp| {
+        let root_len = self.asm.root.len();
+        let outer_test_block = self.current_test_block;
+        if let ModuleKind::Test(name) = &m.kind {
+            self.current_test_block = Some(self.asm.test_blocks.len());
+            self.asm.test_blocks.push(TestBlock {
+                name: name.as_ref().map(|name| name.value.clone()),
+                span: span.clone(),
+                should_fail: false,
+                snapshot: false,
+            });
+        }
+        let res = self.in_scope(scope_kind, |comp| {
             comp.items(m.items, false)?;
             comp.end_enum()?;
             Ok(())
-        })?;
+        });
+        self.current_test_block = outer_test_block;
+        let (module, ()) = res?;
+        if let ModuleKind::Bench(name) = &m.kind {
+            let node = Node::from(&self.asm.root[root_len..]);
+            self.asm.root.truncate(root_len);
+            self.asm.bench_blocks.push(BenchBlock {
+                name: name.as_ref().map(|name| name.value.clone()),
+                span,
+                node,
+            });
+        }
         match m.kind {
             ModuleKind::Named(name) => {
                 // Add imports
@@ -434,7 +465,7 @@ impl Compiler {
                 self.scope.names.insert(name.value.clone(), local);
                 (self.code_meta.global_references).insert(name.span.clone(), local.index);
             }
-            ModuleKind::Test => {
+            ModuleKind::Test(_) => {
                 if let Some(line) = &m.imports {
                     self.add_error(
                         line.tilde_span.clone(),
@@ -442,6 +473,14 @@ impl Compiler {
                     );
                 }
             }
+            ModuleKind::Bench(_) => {
+                if let Some(line) = &m.imports {
+                    self.add_error(
+                        line.tilde_span.clone(),
+                        "Items cannot be imported from bench modules",
+                    );
+                }
+            }
         }
         Ok(())
     }
@@ -459,7 +498,7 @@ impl Compiler {
             self.next_global += 1;
             let local = LocalName {
                 index: global_index,
-                public: true,
+                public: false,
             };
             self.asm.add_binding_at(
                 local,
@@ -486,14 +525,15 @@ impl Compiler {
                     item.value.clone(),
                     LocalName {
                         index: local.index,
-                        public: true,
+                        public: false,
                     },
                 );
             } else {
+                let suggestion = self.did_you_mean(&item.value, self.imports.get(&module_path));
                 self.add_error(
                     item.span.clone(),
                     format!(
-                        "`{}` not found in module {}",
+                        "`{}` not found in module {}{suggestion}",
                         item.value,
                         module_path.display()
                     ),
@@ -502,6 +542,62 @@ impl Compiler {
         }
         Ok(())
     }
+    /// Compile the body of a binding, returning whether it references itself
+    fn binding_body(
+        &mut self,
+        name: &Ident,
+        signature: Option<Signature>,
+        global_index: usize,
+        words: Vec<Sp<Word>>,
+    ) -> (UiuaResult<Node>, bool) {
+        self.current_bindings.push(CurrentBinding {
+            name: name.clone(),
+            signature,
+            recurses: 0,
+            global_index,
+        });
+        let node = self.words(words);
+        let self_referenced = self.current_bindings.pop().unwrap().recurses > 0;
+        (node, self_referenced)
+    }
+    /// Compile the body of a recursive binding that has no declared signature
+    ///
+    /// A signature is assumed for the recursive calls, and the body is compiled with it.
+    /// If the body's signature is different, the body is recompiled assuming that signature instead.
+    /// This repeats until the signatures agree. If they never do, the next common signature is assumed.
+    /// Each attempt recompiles the body, so only a few are made before the signature must be declared.
+    /// Failed attempts are rolled back with a [`BodyCheckpoint`].
+    fn recursive_binding_body(
+        &mut self,
+        name: &Ident,
+        global_index: usize,
+        words: Vec<Sp<Word>>,
+    ) -> (UiuaResult<Node>, bool) {
+        const STARTS: [(usize, usize); 4] = [(1, 1), (2, 1), (1, 2), (2, 2)];
+        const MAX_ATTEMPTS: usize = 6;
+        let checkpoint = BodyCheckpoint::new(self);
+        let mut tried = HashSet::new();
+        for (args, outputs) in STARTS {
+            let mut assumed = Signature::new(args, outputs);
+            while tried.len() < MAX_ATTEMPTS && tried.insert(assumed) {
+                let (node, _) = self.binding_body(name, Some(assumed), global_index, words.clone());
+                let sig = match &node {
+                    Ok(node) if self.errors.len() == checkpoint.errors => node.sig().ok(),
+                    _ => None,
+                };
+                if sig == Some(assumed) {
+                    return (node, true);
+                }
+                checkpoint.restore(self);
+                let Some(sig) = sig else {
+                    break;
+                };
+                assumed = sig;
+            }
+        }
+        // Compiling without a signature reports the error at the recursive call
+        self.binding_body(name, None, global_index, words)
+    }
     fn analyze_macro_body(&mut self, macro_name: &str, words: &[Sp<Word>], recursive: &mut bool) {
         for word in words {
             let mut path_locals = None;
@@ -584,3 +680,62 @@ impl Compiler {
         }
     }
 }
+
+/// Whether some words reference a name that is not in a module
+fn words_reference(words: &[Sp<Word>], name: &Ident) -> bool {
+    let mut found = false;
+    recurse_words(words, &mut |word| {
+        if let Word::Ref(r) = &word.value {
+            found |= r.path.is_empty() && &r.name.value == name;
+        }
+    });
+    found
+}
+
+/// The compiler state that compiling a binding body can change
+///
+/// This is much cheaper to restore than a clone of the whole [`Compiler`].
+struct BodyCheckpoint {
+    functions: usize,
+    dynamic_functions: usize,
+    spans: usize,
+    bindings: usize,
+    test_assert_count: usize,
+    next_global: usize,
+    errors: usize,
+    diagnostics: BTreeSet<Diagnostic>,
+    deprecated_prim_errors: HashSet<Primitive>,
+    experimental_error: bool,
+    fill_sig_error: bool,
+}
+
+impl BodyCheckpoint {
+    fn new(comp: &Compiler) -> Self {
+        BodyCheckpoint {
+            functions: comp.asm.functions.len(),
+            dynamic_functions: comp.asm.dynamic_functions.len(),
+            spans: comp.asm.spans.len(),
+            bindings: comp.asm.bindings.len(),
+            test_assert_count: comp.asm.test_assert_count,
+            next_global: comp.next_global,
+            errors: comp.errors.len(),
+            diagnostics: comp.diagnostics.clone(),
+            deprecated_prim_errors: comp.deprecated_prim_errors.clone(),
+            experimental_error: comp.scope.experimental_error,
+            fill_sig_error: comp.scope.fill_sig_error,
+        }
+    }
+    fn restore(&self, comp: &mut Compiler) {
+        comp.asm.functions.truncate(self.functions);
+        comp.asm.dynamic_functions.truncate(self.dynamic_functions);
+        comp.asm.spans.truncate(self.spans);
+        comp.asm.bindings.truncate(self.bindings);
+        comp.asm.test_assert_count = self.test_assert_count;
+        comp.next_global = self.next_global;
+        comp.errors.truncate(self.errors);
+        comp.diagnostics.clone_from(&self.diagnostics);
+        (comp.deprecated_prim_errors).clone_from(&self.deprecated_prim_errors);
+        comp.scope.experimental_error = self.experimental_error;
+        comp.scope.fill_sig_error = self.fill_sig_error;
+    }
+}
@@ -256,6 +256,7 @@ pub static ANTI_PATTERNS: &[&dyn InvertPattern] = &[
     &(Select, AntiSelect),
     &(Pick, AntiPick),
     &(Base, AntiBase),
+    &(Pack, AntiPack),
     &MatrixDivPat,
     &NoUnder(AntiCouplePat),
     &AntiFillPat,
@@ -1036,10 +1037,14 @@ inverse!(PrimPat, input, _, Prim(prim, span), {
         ImageEncode => ImplPrim(ImageDecode, span),
         Sys(SysOp::Clip) => ImplPrim(UnClip, span),
         Sys(SysOp::RawMode) => ImplPrim(UnRawMode, span),
+        Sys(SysOp::PathJoin) => Prim(Sys(SysOp::PathSplit), span),
+        Sys(SysOp::PathSplit) => Prim(Sys(SysOp::PathJoin), span),
         Json => ImplPrim(UnJson, span),
         Binary => ImplPrim(UnBinary, span),
+        Ast => ImplPrim(UnAst, span),
         Csv => ImplPrim(UnCsv, span),
         Xlsx => ImplPrim(UnXlsx, span),
+        Pdf => ImplPrim(UnPdf, span),
         Fft => ImplPrim(UnFft, span),
         DateTime => ImplPrim(UnDatetime, span),
         Trace => ImplPrim(
@@ -1079,8 +1084,10 @@ inverse!(ImplPrimPat, input, _, ImplPrim(prim, span), {
         UnBox => Prim(Box, span),
         UnJson => Prim(Json, span),
         UnBinary => Prim(Binary, span),
+        UnAst => Prim(Ast, span),
         UnCsv => Prim(Csv, span),
         UnXlsx => Prim(Xlsx, span),
+        UnPdf => Prim(Pdf, span),
         UnFft => Prim(Fft, span),
         ImageDecode => Prim(ImageEncode, span),
         GifDecode => Prim(GifEncode, span),
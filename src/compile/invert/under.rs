@@ -166,6 +166,11 @@ static UNDER_PATTERNS: &[&dyn UnderPattern] = &[
     // Map control
     &MaybeVal((Get, (CopyUnd(2), Get), (PopUnd(1), Flip, PopUnd(1), Insert))),
     &Stash(2, Remove, UndoRemove),
+    &MaybeVal((
+        GetPath,
+        (CopyUnd(2), GetPath),
+        (PopUnd(1), Flip, PopUnd(1), SetPath),
+    )),
     &MaybeVal((Insert, (CopyUnd(3), Insert), (PopUnd(3), UndoInsert))),
     // Shaping
     &(Fix, (Fix), (UndoFix)),
@@ -244,6 +249,7 @@ static UNDER_PATTERNS: &[&dyn UnderPattern] = &[
     &MaybeVal(Store1Copy(Sys(SysOp::TcpAccept), Sys(SysOp::Close))),
     &MaybeVal(Store1Copy(Sys(SysOp::TcpListen), Sys(SysOp::Close))),
     &MaybeVal(Store1Copy(Sys(SysOp::TlsListen), Sys(SysOp::Close))),
+    &MaybeVal(Store1Copy(Sys(SysOp::SerialOpen), Sys(SysOp::Close))),
     &MaybeVal(Stash(1, Sys(SysOp::FReadAllStr), Sys(SysOp::FWriteAll))),
     &MaybeVal(Stash(1, Sys(SysOp::FReadAllBytes), Sys(SysOp::FWriteAll))),
     &MaybeVal((
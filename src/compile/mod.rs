@@ -3,6 +3,7 @@ mod binding;
 mod data;
 pub(crate) mod invert;
 mod modifier;
+mod native;
 pub(crate) mod optimize;
 mod pre_eval;
 
@@ -18,6 +19,7 @@ use std::{
     path::{Path, PathBuf},
     slice,
     sync::Arc,
+    time::Duration,
 };
 
 use ecow::{eco_vec, EcoString, EcoVec};
@@ -34,12 +36,13 @@ use crate::{
     lsp::{CodeMeta, ImportSrc, SetInverses, SigDecl},
     parse::{flip_unsplit_lines, max_placeholder, parse, split_words},
     Array, ArrayLen, Assembly, BindingKind, BindingMeta, Boxed, CustomInverse, Diagnostic,
-    DiagnosticKind, DocComment, DocCommentSig, Function, FunctionId, GitTarget, Ident,
-    ImplPrimitive, InputSrc, IntoInputSrc, IntoSysBackend, Node, PrimClass, Primitive, Purity,
-    RunMode, SemanticComment, SigNode, Signature, SysBackend, Uiua, UiuaError, UiuaErrorKind,
-    UiuaResult, Value, CONSTANTS, EXAMPLE_UA, SUBSCRIPT_DIGITS, VERSION,
+    DiagnosticKind, DiagnosticLevel, DocComment, DocCommentSig, Function, FunctionId, GitTarget,
+    Ident, ImplPrimitive, InputSrc, IntoInputSrc, IntoSysBackend, Node, PrimClass, Primitive,
+    Purity, RunMode, SafeSys, SemanticComment, SigNode, Signature, SysBackend, Uiua, UiuaError,
+    UiuaErrorKind, UiuaResult, Value, CONSTANTS, EXAMPLE_UA, SUBSCRIPT_DIGITS, TARGET, TARGETS,
+    VERSION,
 };
-pub use pre_eval::PreEvalMode;
+pub use {native::NativeModule, pre_eval::PreEvalMode};
 
 /// The Uiua compiler
 #[derive(Clone)]
@@ -56,6 +59,10 @@ pub struct Compiler {
     higher_scopes: Vec<Scope>,
     /// Determines which How test scopes are run
     mode: RunMode,
+    /// If set, only the test block with this index is run
+    only_test_block: Option<usize>,
+    /// The index of the test block being compiled
+    current_test_block: Option<usize>,
     /// The paths of files currently being imported (used to detect import cycles)
     current_imports: Vec<PathBuf>,
     /// The bindings of imported files
@@ -76,14 +83,36 @@ pub struct Compiler {
     diagnostics: BTreeSet<Diagnostic>,
     /// Print diagnostics as they are encountered
     pub(crate) print_diagnostics: bool,
+    /// Levels of diagnostic kinds for the whole project
+    diagnostic_levels: HashMap<DiagnosticKind, DiagnosticLevel>,
+    /// Levels of diagnostic kinds set by semantic comments
+    diagnostic_rules: Vec<DiagnosticRule>,
     /// Whether to evaluate comptime code
     comptime: bool,
+    /// Whether comptime code is run with a [`SafeSys`] backend
+    comptime_sandbox: bool,
+    /// The limit on the duration of each compile-time evaluation
+    comptime_limit: Option<Duration>,
+    /// The limit on the number of operations in each compile-time evaluation
+    comptime_op_budget: Option<usize>,
     /// The comptime mode
     pre_eval_mode: PreEvalMode,
     /// The interpreter used for comptime code
     macro_env: Uiua,
     /// Start addresses
     start_addrs: Vec<usize>,
+    /// Registered modules of Rust functions
+    native_modules: HashMap<EcoString, NativeModule>,
+}
+
+/// A diagnostic level set by a semantic comment
+#[derive(Clone)]
+struct DiagnosticRule {
+    src: InputSrc,
+    /// The first and last lines the rule applies to
+    lines: (u16, u16),
+    kind: DiagnosticKind,
+    level: DiagnosticLevel,
 }
 
 impl Default for Compiler {
@@ -96,6 +125,8 @@ impl Default for Compiler {
             scope: Scope::default(),
             higher_scopes: Vec::new(),
             mode: RunMode::All,
+            only_test_block: None,
+            current_test_block: None,
             current_imports: Vec::new(),
             imports: HashMap::new(),
             index_macros: HashMap::new(),
@@ -106,10 +137,16 @@ impl Default for Compiler {
             deprecated_prim_errors: HashSet::new(),
             diagnostics: BTreeSet::new(),
             print_diagnostics: false,
+            diagnostic_levels: HashMap::new(),
+            diagnostic_rules: Vec::new(),
             comptime: true,
+            comptime_sandbox: true,
+            comptime_limit: None,
+            comptime_op_budget: Some(Self::DEFAULT_COMPTIME_OP_BUDGET),
             pre_eval_mode: PreEvalMode::default(),
             macro_env: Uiua::default(),
             start_addrs: Vec::new(),
+            native_modules: HashMap::new(),
         }
     }
 }
@@ -120,6 +157,7 @@ struct BindingPrelude {
     track_caller: bool,
     no_inline: bool,
     deprecation: Option<EcoString>,
+    cfg: Option<(EcoString, CodeSpan)>,
 }
 
 /// A Uiua module
@@ -186,6 +224,8 @@ pub(crate) struct Scope {
     pub experimental: bool,
     /// Whether an error has been emitted for experimental features
     experimental_error: bool,
+    /// Whether to check for type and shape errors
+    pub type_check: bool,
     /// Whether an error has been emitted for fill function signatures
     fill_sig_error: bool,
     /// The stack height between top-level statements
@@ -204,6 +244,8 @@ enum ScopeKind {
     Temp(Option<MacroLocal>),
     /// A test scope between `---`s
     Test,
+    /// A benchmark scope
+    Bench,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -228,6 +270,7 @@ impl Default for Scope {
             data_variants: 0,
             experimental: false,
             experimental_error: false,
+            type_check: false,
             fill_sig_error: false,
             stack_height: Ok(0),
         }
@@ -244,6 +287,8 @@ pub struct LocalName {
 }
 
 impl Compiler {
+    /// The default limit on the number of operations in each compile-time evaluation
+    pub const DEFAULT_COMPTIME_OP_BUDGET: usize = 10_000_000;
     /// Create a new compiler
     pub fn new() -> Self {
         Self::default()
@@ -275,6 +320,20 @@ impl Compiler {
     pub fn code_meta_mut(&mut self) -> &mut CodeMeta {
         &mut self.code_meta
     }
+    /// Get the top-level bindings of the compiled code as a module
+    pub fn top_level_module(&self) -> Module {
+        Module {
+            comment: self.scope.comment.clone(),
+            names: self.scope.names.clone(),
+            experimental: self.scope.experimental,
+        }
+    }
+    /// Get the paths of the files that have been imported
+    pub fn imported_files(&self) -> impl Iterator<Item = &Path> {
+        (self.imports.keys())
+            .filter(|path| !self.native_modules.contains_key(&*path.to_string_lossy()))
+            .map(|path| path.as_path())
+    }
     /// Take a completed assembly from the compiler
     pub fn finish(&mut self) -> Assembly {
         take(&mut self.asm)
@@ -284,6 +343,33 @@ impl Compiler {
         self.comptime = comptime;
         self
     }
+    /// Set whether `comptime` code and code macros are run with a [`SafeSys`] backend
+    ///
+    /// When this is off, they use the compiler's backend, so they can read files and
+    /// access the network at compile time.
+    ///
+    /// Defaults to true
+    pub fn comptime_sandbox(&mut self, sandbox: bool) -> &mut Self {
+        self.comptime_sandbox = sandbox;
+        self
+    }
+    /// Limit the duration of each evaluation of `comptime` code or a code macro
+    ///
+    /// Whether code compiles then depends on the speed of the machine,
+    /// so this is best left to interactive tools.
+    ///
+    /// Defaults to no limit
+    pub fn comptime_limit(&mut self, limit: Option<Duration>) -> &mut Self {
+        self.comptime_limit = limit;
+        self
+    }
+    /// Limit the number of operations in each evaluation of `comptime` code or a code macro
+    ///
+    /// Defaults to [`Compiler::DEFAULT_COMPTIME_OP_BUDGET`]
+    pub fn comptime_op_budget(&mut self, budget: Option<usize>) -> &mut Self {
+        self.comptime_op_budget = budget;
+        self
+    }
     /// Set the [`PreEvalMode`]
     pub fn pre_eval_mode(&mut self, mode: PreEvalMode) -> &mut Self {
         self.pre_eval_mode = mode;
@@ -303,11 +389,35 @@ impl Compiler {
         self.mode = mode;
         self
     }
+    /// Only run the test block with the given index
+    ///
+    /// Blocks are indexed in the order they appear, as in [`Assembly::test_blocks`].
+    /// Other test blocks are compiled but not run.
+    /// Code outside of test blocks is still run.
+    /// An index of `usize::MAX` runs no test blocks.
+    pub fn only_test_block(&mut self, index: Option<usize>) -> &mut Self {
+        self.only_test_block = index;
+        self
+    }
     /// Enable experimental features
     pub fn experimental(&mut self, experimental: bool) -> &mut Self {
         self.scope.experimental = experimental;
         self
     }
+    /// Set how a kind of diagnostic is reported
+    ///
+    /// This can be overridden for a file or line with `# Allow!` and `# Deny!` comments.
+    pub fn diagnostic_level(&mut self, kind: DiagnosticKind, level: DiagnosticLevel) -> &mut Self {
+        self.diagnostic_levels.insert(kind, level);
+        self
+    }
+    /// Enable compile-time checking for type and shape errors
+    ///
+    /// This can also be enabled for a single file with a `# Type check!` comment.
+    pub fn type_check(&mut self, type_check: bool) -> &mut Self {
+        self.scope.type_check = type_check;
+        self
+    }
     /// Get the backend
     pub fn backend(&self) -> Arc<dyn SysBackend> {
         self.macro_env.rt.backend.clone()
@@ -335,7 +445,7 @@ impl Compiler {
             .map_err(|e| UiuaErrorKind::Load(path.into(), e.into()))?
             .into();
         // _ = crate::lsp::Spans::from_input(&input);
-        self.asm.inputs.files.insert(path.into(), input.clone());
+        Arc::make_mut(&mut self.asm.inputs.files).insert(path.into(), input.clone());
         self.load_impl(&input, InputSrc::File(path.into()))
     }
     /// Compile a Uiua file from a string
@@ -393,10 +503,10 @@ impl Compiler {
     fn load_impl(&mut self, input: &str, src: InputSrc) -> UiuaResult<&mut Self> {
         let node_start = self.asm.root.len();
         let (items, errors, diagnostics) = parse(input, src.clone(), &mut self.asm.inputs);
-        for diagnostic in diagnostics {
-            self.emit_diagnostic_impl(diagnostic);
-        }
         if !errors.is_empty() {
+            for diagnostic in diagnostics {
+                self.emit_diagnostic_impl(diagnostic);
+            }
             return Err(UiuaErrorKind::Parse(errors, self.asm.inputs.clone().into()).into());
         }
         if let InputSrc::File(path) = &src {
@@ -415,6 +525,10 @@ impl Compiler {
         self.start_addrs.push(&base as *const u8 as usize);
         let res = self.catching_crash(input, |env| env.items(items, false));
         self.start_addrs.pop();
+        // Parse diagnostics are emitted after compiling so that `# Allow!` comments apply to them
+        for diagnostic in diagnostics {
+            self.emit_diagnostic_impl(diagnostic);
+        }
 
         // Optimize root
         self.asm.root.optimize();
@@ -426,6 +540,11 @@ impl Compiler {
                 self.errors.extend(errs);
                 self.asm.functions.make_mut()[i].optimize();
             }
+            let mut root = self.asm.functions[i].clone();
+            if self.pre_eval_nested(&mut root) {
+                root.optimize();
+                self.asm.functions.make_mut()[i] = root;
+            }
         }
         // dbg!(&self.asm.root);
 
@@ -551,6 +670,10 @@ code:
         must_run: bool,
         prelude: &mut BindingPrelude,
     ) -> UiuaResult {
+        if !matches!(item, Item::Words(_)) && !self.cfg_matches(prelude) {
+            *prelude = BindingPrelude::default();
+            return Ok(());
+        }
         match item {
             Item::Module(m) => self.module(m, take(prelude)),
             Item::Words(lines) => self.top_level_words(lines, from_macro, must_run, true, prelude),
@@ -559,6 +682,33 @@ code:
             Item::Data(data) => self.data_def(data, true, take(prelude)),
         }
     }
+    /// Check if the targets in a `# Cfg!` comment include the current target
+    fn cfg_matches(&mut self, prelude: &BindingPrelude) -> bool {
+        let Some((targets, span)) = &prelude.cfg else {
+            return true;
+        };
+        if targets.trim().is_empty() {
+            self.add_error(
+                span.clone(),
+                "# Cfg! must be followed by at least one target",
+            );
+            return false;
+        }
+        let mut matches = false;
+        for target in targets.split_whitespace() {
+            if !TARGETS.contains(&target) {
+                self.add_error(
+                    span.clone(),
+                    format!(
+                        "Unknown target `{target}`. Valid targets are {}.",
+                        TARGETS.map(|t| format!("`{t}`")).join(" and ")
+                    ),
+                );
+            }
+            matches |= target == TARGET;
+        }
+        matches
+    }
     /// Compile top-level words
     fn top_level_words(
         &mut self,
@@ -601,6 +751,9 @@ code:
                     Word::SemanticComment(SemanticComment::Deprecated(s)) => {
                         prelude.deprecation = Some(s.clone())
                     }
+                    Word::SemanticComment(SemanticComment::Cfg(s)) => {
+                        prelude.cfg = Some((s.clone(), word.span.clone()))
+                    }
                     _ => *prelude = BindingPrelude::default(),
                 }
             } else {
@@ -608,7 +761,14 @@ code:
             }
         }
         let in_test = self.scopes().any(|sc| sc.kind == ScopeKind::Test);
+        // Benchmark code is always compiled, but it is moved out of the root afterward
+        let in_bench = self.scopes().any(|sc| sc.kind == ScopeKind::Bench);
+        // Code outside of test blocks is always run
+        let skip_test = (self.current_test_block)
+            .is_some_and(|current| self.only_test_block.is_some_and(|only| only != current));
         let can_run = match self.mode {
+            _ if in_bench => true,
+            _ if skip_test => false,
             RunMode::Normal => !in_test,
             RunMode::Test => in_test,
             RunMode::All => true,
@@ -625,7 +785,10 @@ code:
                 })
             };
             if line.is_empty()
-                || !(can_run || must_run || assert_later() || words_should_run_anyway(&line))
+                || !(can_run
+                    || must_run
+                    || (!skip_test && assert_later())
+                    || words_should_run_anyway(&line))
             {
                 continue;
             }
@@ -648,11 +811,15 @@ code:
             let error_count_after = self.errors.len();
 
             line_node.optimize();
+            self.check_types(&line_node);
             match line_node.sig() {
                 Ok(sig) => {
                     // Update scope stack height
                     if let Ok(height) = &mut self.scope.stack_height {
                         *height = (*height + sig.outputs).saturating_sub(sig.args);
+                        if !from_macro {
+                            (self.code_meta.top_level_heights).insert(span.clone(), *height);
+                        }
                         // Compile test assert
                         if self.mode != RunMode::Normal
                             && !from_macro
@@ -681,6 +848,7 @@ code:
                     // - there are at least as many push nodes preceding the current line as there are arguments to the line
                     // - the words create no bindings
                     if precomp
+                        && !in_bench
                         && error_count_after == error_count_before
                         && self.pre_eval_mode > PreEvalMode::Line
                         && !line_node.is_empty()
@@ -708,7 +876,8 @@ code:
                                 self.code_meta.top_level_values.insert(span, vals);
                             }
                             // Truncate root
-                            self.asm.root.truncate(self.asm.root.len() - sig.args);
+                            let root_len = self.asm.root.len();
+                            self.asm.root.truncate(root_len - sig.args);
                             // Set line node to the pre-evaluated node
                             line_node = node;
                         }
@@ -717,7 +886,7 @@ code:
                 Err(e) if matches!(e.kind, SigCheckErrorKind::LoopVariable { .. }) => {
                     self.scope.stack_height = Err(span.sp(e))
                 }
-                Err(e) => self.add_error(span, e),
+                Err(e) => self.add_error(self.sig_error_span(&e, &span), e),
             }
             self.asm.root.push(line_node)
         }
@@ -776,6 +945,9 @@ code:
     }
     /// Import a module
     pub(crate) fn import_module(&mut self, path_str: &str, span: &CodeSpan) -> UiuaResult<PathBuf> {
+        if let Some(path) = self.import_native_module(path_str.trim(), span)? {
+            return Ok(path);
+        }
         // Resolve path
         let (path, file_kind) = if let Some(mut url) = path_str.trim().strip_prefix("git:") {
             if url.contains("branch:") && url.contains("commit:") {
@@ -825,6 +997,16 @@ code:
                 .load_git_module(&url, target)
                 .map_err(|e| self.error(span.clone(), e))?;
             (path, FileScopeKind::Git)
+        } else if let Some(name) = path_str.trim().strip_prefix("pkg:") {
+            // Package import
+            let path = self
+                .backend()
+                .load_package(name.trim())
+                .map_err(|e| self.error(span.clone(), e))?;
+            self.code_meta
+                .import_srcs
+                .insert(span.clone(), ImportSrc::File(path.clone()));
+            (path, FileScopeKind::Git)
         } else {
             // Normal import
             let path = self.resolve_import_path(Path::new(path_str));
@@ -856,7 +1038,10 @@ code:
                 swap(self, &mut comp);
                 self.macro_env.rt.backend = comp.macro_env.rt.backend;
                 self.asm.inputs.strings = comp.asm.inputs.strings;
-                self.asm.inputs.files.extend(comp.asm.inputs.files);
+                let files = Arc::make_mut(&mut self.asm.inputs.files);
+                for entry in comp.asm.inputs.files.iter() {
+                    files.insert(entry.key().clone(), entry.value().clone());
+                }
                 self.scope.experimental = comp.scope.experimental;
                 self.diagnostics.extend(comp.diagnostics);
             } else {
@@ -983,6 +1168,20 @@ code:
                 sem = Some(words.pop().unwrap().span.sp(com));
             }
         }
+        if let Some(sem) = &sem {
+            if let SemanticComment::Allow(kinds) | SemanticComment::Deny(kinds) = &sem.value {
+                // A comment on its own line applies to the rest of the file
+                let lines = match words.first() {
+                    Some(first) => (first.span.start.line, sem.span.end.line),
+                    None => (sem.span.start.line, u16::MAX),
+                };
+                let level = match sem.value {
+                    SemanticComment::Allow(_) => DiagnosticLevel::Allow,
+                    _ => DiagnosticLevel::Deny,
+                };
+                self.diagnostic_rules(kinds, lines, level, &sem.span);
+            }
+        }
         // Right-to-left
         words.reverse();
 
@@ -1137,7 +1336,7 @@ code:
         const MAX_RECURSION_DEPTH: usize =
             match (cfg!(target_arch = "wasm32"), cfg!(debug_assertions)) {
                 (false, false) => (512 + 256 + 64) * 1024 * 2,
-                (false, true) => (512 + 256 + 64) * 1024,
+                (false, true) => 1024 * 1024,
                 (true, false) => 512 * 1024,
                 (true, true) => 512 * 1024,
             };
@@ -1332,7 +1531,7 @@ code:
                         }
                         _ => {
                             return Err(self.error(
-                                word.span.clone(),
+                                self.sig_error_span(&e, &word.span),
                                 format!("Cannot infer array signature: {e}"),
                             ))
                         }
@@ -1411,7 +1610,7 @@ code:
             }
             Word::Primitive(p) => self.primitive(p, word.span),
             Word::Modified(m) => self.modified(*m, None)?,
-            Word::Placeholder(_) => {
+            Word::Placeholder(_) | Word::NamedPlaceholder(_) => {
                 // We could error here, but it's easier to handle it higher up
                 Node::empty()
             }
@@ -1507,7 +1706,34 @@ code:
             }
             SemanticComment::NoInline => Node::NoInline(inner.into()),
             SemanticComment::TrackCaller => Node::TrackCaller(inner.into()),
-            SemanticComment::Deprecated(_) => inner,
+            SemanticComment::Deprecated(_)
+            | SemanticComment::Cfg(_)
+            | SemanticComment::Allow(_)
+            | SemanticComment::Deny(_) => inner,
+            SemanticComment::TypeCheck => {
+                self.scope.type_check = true;
+                inner
+            }
+            SemanticComment::ShouldFail => {
+                if let Some(i) = self.current_test_block {
+                    self.asm.test_blocks.make_mut()[i].should_fail = true;
+                } else {
+                    self.add_error(span, "# Should fail! can only be used in test blocks");
+                }
+                inner
+            }
+            SemanticComment::Snapshot => {
+                match self.current_test_block {
+                    Some(i) if self.asm.test_blocks[i].name.is_some() => {
+                        self.asm.test_blocks.make_mut()[i].snapshot = true
+                    }
+                    Some(_) => {
+                        self.add_error(span, "# Snapshot! can only be used in named test blocks")
+                    }
+                    None => self.add_error(span, "# Snapshot! can only be used in test blocks"),
+                }
+                inner
+            }
             SemanticComment::Boo => {
                 self.add_error(span, "The compiler is scared!");
                 inner
@@ -1530,7 +1756,11 @@ code:
             } else {
                 Err(self.error(
                     r.name.span.clone(),
-                    format!("Item `{}` not found", r.name.value,),
+                    format!(
+                        "Item `{}` not found{}",
+                        r.name.value,
+                        self.did_you_mean(&r.name.value, Some(module))
+                    ),
                 ))
             }
         } else if let Some(local) = self.find_name(&r.name.value, r.in_macro_arg) {
@@ -1540,7 +1770,11 @@ code:
         } else {
             Err(self.error(
                 r.name.span.clone(),
-                format!("Unknown identifier `{}`", r.name.value),
+                format!(
+                    "Unknown identifier `{}`{}",
+                    r.name.value,
+                    self.did_you_mean(&r.name.value, None)
+                ),
             ))
         }
     }
@@ -1573,6 +1807,55 @@ code:
             None
         }
     }
+    /// Suggest names similar to an unknown one
+    ///
+    /// If a module is given, only its items are considered.
+    /// Otherwise, names in scope, constants, and primitives are.
+    pub(crate) fn did_you_mean(&self, name: &str, module: Option<&Module>) -> String {
+        let mut candidates: Vec<&str> = Vec::new();
+        if let Some(module) = module {
+            candidates.extend(module.names.keys().map(|name| name.as_str()));
+        } else {
+            candidates.extend(self.scope.names.keys().map(|name| name.as_str()));
+            let mut hit_file = matches!(self.scope.kind, ScopeKind::File(_));
+            for scope in self.higher_scopes.iter().rev() {
+                if matches!(scope.kind, ScopeKind::File(_)) {
+                    if hit_file {
+                        break;
+                    }
+                    hit_file = true;
+                }
+                candidates.extend(scope.names.keys().map(|name| name.as_str()));
+            }
+            candidates.extend(CONSTANTS.iter().map(|def| def.name));
+            candidates.extend(
+                Primitive::non_deprecated()
+                    .filter(|prim| !prim.is_experimental() || self.scope.experimental)
+                    .map(|prim| prim.name())
+                    .filter(|name| name.chars().all(|c| c.is_alphabetic())),
+            );
+        }
+        let lower = name.to_lowercase();
+        let len = name.chars().count();
+        let max_distance = len.max(3) / 3;
+        // A name shouldn't be suggested if every character would have to change
+        let mut similar: Vec<(usize, &str)> = (candidates.into_iter())
+            .filter(|&cand| cand != name)
+            .map(|cand| (edit_distance(&lower, &cand.to_lowercase()), cand))
+            .filter(|&(dist, cand)| dist <= max_distance && dist < len.min(cand.chars().count()))
+            .collect();
+        similar.sort();
+        similar.dedup_by_key(|(_, cand)| *cand);
+        let similar: Vec<_> = (similar.iter().take(3))
+            .map(|(_, cand)| format!("`{cand}`"))
+            .collect();
+        match similar.as_slice() {
+            [] => String::new(),
+            [a] => format!(". Did you mean {a}?"),
+            [a, b] => format!(". Did you mean {a} or {b}?"),
+            [rest @ .., last] => format!(". Did you mean {}, or {last}?", rest.join(", ")),
+        }
+    }
     fn ref_path(
         &self,
         path: &[RefComponent],
@@ -1587,7 +1870,11 @@ code:
             .ok_or_else(|| {
                 self.error(
                     first.module.span.clone(),
-                    format!("Unknown import `{}`", first.module.value),
+                    format!(
+                        "Unknown import `{}`{}",
+                        first.module.value,
+                        self.did_you_mean(&first.module.value, None)
+                    ),
                 )
             })?;
         path_locals.push(module_local);
@@ -1693,8 +1980,8 @@ code:
                     return Err(self.error(
                         span,
                         format!(
-                            "Recursive function `{ident}` must have a \
-                            signature declared after the ←."
+                            "The signature of recursive function `{ident}` \
+                            could not be inferred, so it must be declared after the ←."
                         ),
                     ));
                 };
@@ -1717,7 +2004,8 @@ code:
                         .resolve(self.scope_file_path(), &*self.backend()),
                 )
             } else {
-                return Err(self.error(span, format!("Unknown identifier `{ident}`")));
+                let suggestion = self.did_you_mean(&ident, None);
+                return Err(self.error(span, format!("Unknown identifier `{ident}`{suggestion}")));
             },
         )
     }
@@ -1789,7 +2077,12 @@ code:
                 }
                 Some(sig)
             }
-            Err(e) => return Err(self.error(span, format!("Cannot infer function signature: {e}"))),
+            Err(e) => {
+                return Err(self.error(
+                    self.sig_error_span(&e, &span),
+                    format!("Cannot infer function signature: {e}"),
+                ))
+            }
         };
         if let Some(sig) = sig {
             self.code_meta.function_sigs.insert(
@@ -1811,7 +2104,6 @@ code:
         let mut rigid_indices = Vec::new();
         let mut flex_indices = Vec::new();
         for (i, branch) in branches.into_iter().enumerate() {
-            let span = branch.span.clone();
             let SigNode { node, sig } = self.word_sig(branch)?;
             let is_flex = node
                 .iter()
@@ -1833,7 +2125,7 @@ code:
                             || !sub.sig().is_ok_and(|sig| sig == (0, 2))
                     })
                 });
-            br.push((SigNode::new(sig, node), span));
+            br.push(SigNode::new(sig, node));
             if is_flex {
                 flex_indices.push(i);
             } else {
@@ -1842,41 +2134,42 @@ code:
         }
         let mut rigid_funcs = rigid_indices.into_iter().map(|i| &br[i]);
         let mut sig = None;
-        if let Some((arg, _)) = rigid_funcs.next() {
+        if let Some(arg) = rigid_funcs.next() {
             sig = Some(arg.sig);
             let sig = sig.as_mut().unwrap();
             // Compile remaining branches
-            for (arg, span) in rigid_funcs {
+            // Incompatible branches are reported when the switch's signature is checked
+            for arg in rigid_funcs {
                 if arg.sig.is_compatible_with(*sig) {
                     *sig = sig.max_with(arg.sig);
                 } else if arg.sig.outputs == sig.outputs {
                     sig.args = sig.args.max(arg.sig.args)
-                } else {
-                    self.add_error(
-                        span.clone(),
-                        format!(
-                            "Switch branch's signature {} is \
-                            incompatible with previous branches {sig}",
-                            arg.sig
-                        ),
-                    );
                 }
             }
         }
         let mut flex_funcs = flex_indices.into_iter().map(|i| &br[i]);
-        let mut sig = sig.unwrap_or_else(|| flex_funcs.next().unwrap().0.sig);
-        for (arg, _) in flex_funcs {
+        let mut sig = sig.unwrap_or_else(|| flex_funcs.next().unwrap().sig);
+        for arg in flex_funcs {
             sig.args = sig.args.max(arg.sig.args);
         }
 
         let span = self.add_span(span.clone());
         Ok(Node::Switch {
-            branches: br.into_iter().map(|(arg, _)| arg).collect(),
+            branches: br,
             sig,
             span,
             under_cond: false,
         })
     }
+    fn when_branches(&mut self, branches: Vec<Sp<Word>>, span: CodeSpan) -> UiuaResult<Node> {
+        let count = branches.len();
+        let switch = self.switch(branches, span.clone())?;
+        let span = self.add_span(span);
+        Ok(Node::from_iter([
+            Node::ImplPrim(ImplPrimitive::WhenBranch(count), span),
+            switch,
+        ]))
+    }
     fn handle_primitive_deprecation(&mut self, prim: Primitive, span: &CodeSpan) {
         if let Some(suggestion) = prim.deprecation_suggestion() {
             if !self.deprecated_prim_errors.insert(prim) {
@@ -1944,6 +2237,7 @@ code:
                                 | Primitive::Each
                                 | Primitive::Inventory
                                 | Primitive::Stencil
+                                | Primitive::Path
                         ) {
                             self.add_error(
                                 m.modifier.span.clone().merge(n.span.clone()),
@@ -2253,7 +2547,63 @@ impl Compiler {
         let inputs = self.asm.inputs.clone();
         self.emit_diagnostic_impl(Diagnostic::new(message.into(), span, kind, inputs));
     }
+    fn diagnostic_rules(
+        &mut self,
+        kinds: &str,
+        lines: (u16, u16),
+        level: DiagnosticLevel,
+        span: &CodeSpan,
+    ) {
+        if kinds.is_empty() {
+            self.add_error(span.clone(), "Expected at least one diagnostic kind");
+        }
+        for name in kinds.split_whitespace() {
+            let kinds = if name == "all" {
+                DiagnosticKind::ALL.to_vec()
+            } else {
+                match name.parse() {
+                    Ok(kind) => vec![kind],
+                    Err(e) => {
+                        self.add_error(span.clone(), e);
+                        continue;
+                    }
+                }
+            };
+            for kind in kinds {
+                self.diagnostic_rules.push(DiagnosticRule {
+                    src: span.src.clone(),
+                    lines,
+                    kind,
+                    level,
+                });
+            }
+        }
+    }
+    /// Get the level of a diagnostic
+    ///
+    /// Later rules take precedence over earlier ones, so a line's rule overrides its file's
+    fn level_of(&self, diagnostic: &Diagnostic) -> DiagnosticLevel {
+        let rule = match &diagnostic.span {
+            Span::Code(span) => (self.diagnostic_rules.iter().rev()).find(|rule| {
+                rule.kind == diagnostic.kind
+                    && rule.src == span.src
+                    && (rule.lines.0..=rule.lines.1).contains(&span.start.line)
+            }),
+            Span::Builtin => None,
+        };
+        (rule.map(|rule| rule.level))
+            .or_else(|| self.diagnostic_levels.get(&diagnostic.kind).copied())
+            .unwrap_or_default()
+    }
     fn emit_diagnostic_impl(&mut self, diagnostic: Diagnostic) {
+        match self.level_of(&diagnostic) {
+            DiagnosticLevel::Allow => return,
+            DiagnosticLevel::Warn => {}
+            DiagnosticLevel::Deny => {
+                self.add_error(diagnostic.span, diagnostic.message);
+                return;
+            }
+        }
         if self.print_diagnostics {
             println!("{}", diagnostic.report()); // Allow println
         } else {
@@ -2322,6 +2672,7 @@ impl Compiler {
                 }
             }
             self.emit_diagnostic(message, DiagnosticKind::Warning, span.clone());
+            self.code_meta.deprecated_refs.insert(span.clone());
         }
         // Validate public
         if local.public {
@@ -2404,11 +2755,46 @@ impl Compiler {
     fn sig_of(&self, node: &Node, span: &CodeSpan) -> UiuaResult<Signature> {
         node.sig().map_err(|e| {
             self.error(
-                span.clone(),
+                self.sig_error_span(&e, span),
                 format!("Cannot infer function signature: {e}"),
             )
         })
     }
+    /// Emit diagnostics for type and shape errors if type checking is enabled
+    pub(crate) fn check_types(&mut self, node: &Node) {
+        if !self.scopes_to_file().any(|sc| sc.type_check) {
+            return;
+        }
+        for e in node.type_errors() {
+            if let Some(Span::Code(span)) = e.span.map(|i| self.get_span(i)) {
+                self.emit_diagnostic(e.message, DiagnosticKind::Warning, span);
+            }
+        }
+    }
+    /// Get the span of the code that caused a signature error,
+    /// or the given span if it is unknown
+    fn sig_error_span(&self, e: &SigCheckError, span: &CodeSpan) -> CodeSpan {
+        match e.span.map(|i| self.get_span(i)) {
+            Some(Span::Code(sp)) => sp,
+            _ => span.clone(),
+        }
+    }
+}
+
+/// The Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ac) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, &bc) in b.iter().enumerate() {
+            let sub = prev[j] + (ac != bc) as usize;
+            curr[j + 1] = sub.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
 }
 
 fn words_look_pervasive(words: &[Sp<Word>]) -> bool {
@@ -2488,3 +2874,103 @@ fn line_sig(line: &[Sp<Word>]) -> Option<Sp<DocCommentSig>> {
             _ => None,
         })
 }
+
+#[cfg(test)]
+#[test]
+fn deprecated_bindings() {
+    use crate::{Compiler, DiagnosticKind, Span, SpanKind, Spans};
+    let code = "\
+# Deprecated! Use G instead
+F ← +1
+G ← +1
+F 1
+G 2";
+    let mut comp = Compiler::new();
+    comp.load_str(code).unwrap();
+    let warnings: Vec<_> = (comp.take_diagnostics().into_iter())
+        .filter(|diag| diag.kind == DiagnosticKind::Warning)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "F is deprecated. Use G instead.");
+    let Span::Code(span) = &warnings[0].span else {
+        panic!("deprecation warning has no code span");
+    };
+    assert!(comp.code_meta().deprecated_refs.contains(span));
+    let spans = Spans::from_input(code).spans;
+    assert!(spans.iter().any(|sp| matches!(&sp.value,
+        SpanKind::Ident { docs: Some(docs), .. } if docs.meta.deprecation.is_some())));
+}
+
+#[cfg(test)]
+#[test]
+fn top_level_heights() {
+    use crate::Compiler;
+    let mut comp = Compiler::new();
+    comp.load_str("F ← +1\n1 2\nF\nX ← 5\n[X X]").unwrap();
+    let heights: Vec<_> = comp
+        .code_meta()
+        .top_level_heights
+        .values()
+        .copied()
+        .collect();
+    assert_eq!(heights, [2, 2, 3]);
+}
+
+#[cfg(test)]
+#[test]
+fn cfg_comments() {
+    use crate::{Uiua, Value, TARGET};
+    let code = "\
+# Cfg! native
+F ← \"native\"
+# Cfg! web
+F ← \"web\"
+# Cfg! web native
+G ← Target
+F G";
+    let mut env = Uiua::with_safe_sys();
+    env.run_str(code).unwrap();
+    let stack = env.take_stack();
+    assert_eq!(stack, [Value::from(TARGET), Value::from(TARGET)]);
+    assert!(Uiua::with_safe_sys()
+        .run_str("# Cfg! linux\nF ← 1")
+        .is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn did_you_mean() {
+    use crate::Uiua;
+    let message = |code: &str| {
+        let Err(err) = Uiua::with_safe_sys().run_str(code) else {
+            panic!("Program should fail");
+        };
+        err.to_string()
+    };
+    assert!(message("Foo ← 1\nFoe").ends_with("Did you mean `Foo`?"));
+    assert!(message("revrse [1 2]").ends_with("Did you mean `reverse`?"));
+    assert!(message("┌─╴M\n  Bar ← 5\n└─╴\nM~Baz").ends_with("Did you mean `Bar`?"));
+    assert!(!message("X").contains("Did you mean"));
+}
+
+#[cfg(test)]
+#[test]
+fn diagnostic_levels() {
+    use crate::{Compiler, DiagnosticKind, DiagnosticLevel};
+    let count = |comp: &mut Compiler, code: &str| {
+        comp.load_str(code).unwrap_or_else(|e| panic!("{e}"));
+        comp.take_diagnostics().len()
+    };
+    assert_eq!(count(&mut Compiler::new(), "@a_@b\n@c_@d"), 2);
+    assert_eq!(
+        count(&mut Compiler::new(), "@a_@b # Allow! advice\n@c_@d"),
+        1
+    );
+    assert_eq!(count(&mut Compiler::new(), "@a_@b\n# Allow! all\n@c_@d"), 1);
+    let mut comp = Compiler::new();
+    comp.diagnostic_level(DiagnosticKind::Advice, DiagnosticLevel::Allow);
+    assert_eq!(count(&mut comp, "@a_@b"), 0);
+    assert!(Compiler::new().load_str("# Deny! advice\n@a_@b").is_err());
+    assert!(Compiler::new().load_str("# Deny! style\n@a_@b").is_ok());
+    assert!(Compiler::new().load_str("# Allow! nothing\n1").is_err());
+}
@@ -128,6 +128,15 @@ impl Compiler {
                     .collect(),
                 modifier.span.clone(),
             ),
+            Modifier::Primitive(Primitive::When) => {
+                self.handle_primitive_experimental(Primitive::When, &modifier.span);
+                self.when_branches(
+                    (pack.branches.iter().cloned())
+                        .map(|sp| sp.map(Word::Func))
+                        .collect(),
+                    modifier.span.clone(),
+                )
+            }
             Modifier::Primitive(Primitive::Obverse) => {
                 let mut nodes = Vec::new();
                 let mut spans = Vec::new();
@@ -278,6 +287,13 @@ impl Compiler {
                 }
                 args.make_mut().swap(1, 2);
                 let span = self.add_span(modifier.span.clone());
+                if let Some(n) =
+                    subscript.and_then(|n| self.subscript_n(n, Primitive::Path.format()))
+                {
+                    let mut node = Node::ImplMod(ImplPrimitive::BoundedAstar, args, span);
+                    node.prepend(Node::new_push(n.value));
+                    return Ok(Some(node));
+                }
                 Ok(Node::Mod(Primitive::Astar, args, span))
             }
             m if m.args() >= 2 => {
@@ -863,10 +879,15 @@ impl Compiler {
                 let span = self.add_span(modified.modifier.span.clone());
                 Node::Mod(Primitive::Try, eco_vec![tried, handler], span)
             }
+            UpdatePath => self.update_path(modified)?,
             Switch => self.switch(
                 modified.code_operands().cloned().collect(),
                 modified.modifier.span.clone(),
             )?,
+            When => self.when_branches(
+                modified.code_operands().cloned().collect(),
+                modified.modifier.span.clone(),
+            )?,
             Fill => {
                 let mut operands = modified.code_operands().rev().cloned();
 
@@ -1164,9 +1185,43 @@ impl Compiler {
                     }
                 }
             }
+            Path if subscript.is_some() => return self.bounded_path(modified, subscript),
             _ => return Ok(None),
         }))
     }
+    // These are kept out of `inline_modifier` so that they do not grow its stack frame,
+    // which is part of every level of nested compilation
+    fn update_path(&mut self, modified: &Modified) -> UiuaResult<Node> {
+        // Desugar to under getpath
+        let span = modified.modifier.span.clone();
+        let f = modified.code_operands().next().unwrap().clone();
+        self.modified(
+            Modified {
+                modifier: span.clone().sp(Modifier::Primitive(Primitive::Under)),
+                operands: vec![span.sp(Word::Primitive(Primitive::GetPath)), f],
+                pack_expansion: false,
+            },
+            None,
+        )
+    }
+    fn bounded_path(
+        &mut self,
+        modified: &Modified,
+        subscript: Option<Sp<Subscript>>,
+    ) -> UiuaResult<Option<Node>> {
+        let Some(n) = subscript.and_then(|n| self.subscript_n(n, Primitive::Path.format())) else {
+            return Ok(None);
+        };
+        let (neighbors, is_goal, ..) = self.dyadic_modifier_ops(modified)?;
+        let span = self.add_span(modified.modifier.span.clone());
+        let mut node = Node::ImplMod(
+            ImplPrimitive::BoundedPath,
+            eco_vec![neighbors, is_goal],
+            span,
+        );
+        node.prepend(Node::new_push(n.value));
+        Ok(Some(node))
+    }
     // Compile an inline macro
     fn inline_macro(
         &mut self,
@@ -1199,6 +1254,7 @@ impl Compiler {
             self.code_macro(None, span, operands, code_mac)?
         } else {
             // Expand
+            self.number_placeholders(&mut words)?;
             self.expand_index_macro(None, &mut words, operands, span.clone(), true)?;
             // Compile
             let node = self.suppress_diagnostics(|comp| comp.words(words))?;
@@ -1401,6 +1457,7 @@ impl Compiler {
             }
 
             let span = self.add_span(modifier_span.clone());
+            let backend = self.enter_comptime();
             let env = &mut self.macro_env;
             swap(&mut env.asm, &mut self.asm);
             env.rt.call_stack.last_mut().unwrap().call_span = span;
@@ -1415,7 +1472,8 @@ impl Compiler {
             let enabled =
                 crate::sys::native::set_output_enabled(self.pre_eval_mode != PreEvalMode::Lsp);
 
-            let res = (|| -> UiuaResult {
+            let settings = env.rt.format_settings;
+            let res = settings.scoped(|| -> UiuaResult {
                 env.exec(mac.root)?;
 
                 let val = env.pop("macro result")?;
@@ -1435,16 +1493,21 @@ impl Compiler {
                     code.push_str(&s);
                 }
                 Ok(())
-            })();
-
-            if let Err(e) = res {
-                self.errors.push(e);
-            }
+            });
 
             #[cfg(feature = "native_sys")]
             crate::sys::native::set_output_enabled(enabled);
 
             swap(&mut env.asm, &mut self.asm);
+            self.exit_comptime(backend);
+
+            if let Err(e) = res {
+                let e = match self.comptime_limit_message(&e) {
+                    Some(message) => self.error(modifier_span.clone(), message),
+                    None => e,
+                };
+                self.errors.push(e);
+            }
             Ok(())
         })()
         .map_err(|e| e.trace_macro(mac_name.clone(), modifier_span.clone()))?;
@@ -1505,6 +1568,29 @@ impl Compiler {
         (self.code_meta.macro_expansions).insert(span, (name, formatted));
         Ok(())
     }
+    /// Replace named placeholders with numbered ones
+    ///
+    /// Names are numbered in the order that they first appear
+    pub(super) fn number_placeholders(&self, words: &mut [Sp<Word>]) -> UiuaResult {
+        let mut names: IndexMap<Ident, usize> = IndexMap::new();
+        let mut numbered = None;
+        recurse_words_mut(words, &mut |word| match &word.value {
+            Word::NamedPlaceholder(name) => {
+                let len = names.len();
+                let n = *names.entry(name.clone()).or_insert(len);
+                word.value = Word::Placeholder(n);
+            }
+            Word::Placeholder(_) => numbered = Some(word.span.clone()),
+            _ => {}
+        });
+        match numbered {
+            Some(span) if !names.is_empty() => Err(self.error(
+                span,
+                "Named and numbered placeholders cannot be used in the same macro",
+            )),
+            _ => Ok(()),
+        }
+    }
     fn replace_placeholders(&self, words: &mut Vec<Sp<Word>>, initial: &[Sp<Word>]) -> UiuaResult {
         let mut error = None;
         recurse_words_mut(words, &mut |word| match &mut word.value {
@@ -1596,11 +1682,15 @@ impl Compiler {
         }
         let asm_root_len = comp.asm.root.len();
         comp.asm.root.push(sn.node);
+        let backend = comp.enter_comptime();
         let res = comp.macro_env.run_asm(comp.asm.clone());
         let stack = comp.macro_env.take_stack();
+        comp.exit_comptime(backend);
         let values = if let Err(e) = res {
             if self.errors.is_empty() {
-                self.add_error(span.clone(), format!("Compile-time evaluation failed: {e}"));
+                let message = (self.comptime_limit_message(&e))
+                    .unwrap_or_else(|| format!("Compile-time evaluation failed: {e}"));
+                self.add_error(span.clone(), message);
             }
             vec![Value::default(); sn.sig.outputs]
         } else {
@@ -1615,6 +1705,37 @@ impl Compiler {
         }
         Ok(node)
     }
+    /// Prepare the macro environment to run compile-time code
+    ///
+    /// Returns the backend to restore with [`Compiler::exit_comptime`]
+    fn enter_comptime(&mut self) -> Arc<dyn SysBackend> {
+        let env = &mut self.macro_env;
+        env.rt.execution_limit = self.comptime_limit.map(|limit| limit.as_secs_f64());
+        env.rt.op_budget = self.comptime_op_budget;
+        let backend = env.rt.backend.clone();
+        if self.comptime_sandbox {
+            env.rt.backend = Arc::new(SafeSys::default());
+        }
+        env.start_execution();
+        backend
+    }
+    fn exit_comptime(&mut self, backend: Arc<dyn SysBackend>) {
+        self.macro_env.rt.backend = backend;
+    }
+    /// Describe an error caused by compile-time evaluation exceeding one of its limits
+    fn comptime_limit_message(&self, e: &UiuaError) -> Option<String> {
+        match e.kind {
+            UiuaErrorKind::Timeout(..) => Some(format!(
+                "Compile-time evaluation took longer than the limit of {:?}",
+                self.comptime_limit?
+            )),
+            UiuaErrorKind::OpLimit(..) => Some(format!(
+                "Compile-time evaluation executed more than the limit of {} operations",
+                self.comptime_op_budget?
+            )),
+            _ => None,
+        }
+    }
     /// Run a function in a temporary scope with the given names.
     /// Newly created bindings will be added to the current scope after the function is run.
     fn temp_scope<T>(
@@ -1640,3 +1761,48 @@ impl Compiler {
         res
     }
 }
+
+#[cfg(test)]
+#[test]
+fn comptime_limits() {
+    use crate::{Compiler, SafeSys};
+    let compile = |code: &str, f: &dyn Fn(&mut Compiler)| {
+        let mut comp = Compiler::with_backend(SafeSys::default());
+        f(&mut comp);
+        comp.load_str(code).map(drop).map_err(|e| e.to_string())
+    };
+    let forever = "comptime(⍢(+1)(1) 0)";
+    let err = compile(forever, &|comp| {
+        comp.comptime_op_budget(Some(1000));
+    })
+    .unwrap_err();
+    assert!(err.contains("limit of 1000 operations"), "{err}");
+    let err = compile(forever, &|comp| {
+        comp.comptime_op_budget(None)
+            .comptime_limit(Some(std::time::Duration::from_millis(50)));
+    })
+    .unwrap_err();
+    assert!(err.contains("took longer than the limit"), "{err}");
+    let err = compile("F! ←^ ⋅⍢(+1)(1) 0\nF!(+)", &|comp| {
+        comp.comptime_op_budget(Some(1000));
+    })
+    .unwrap_err();
+    assert!(err.contains("limit of 1000 operations"), "{err}");
+    compile("comptime(/+⇡100)", &|comp| {
+        comp.comptime_op_budget(Some(1000));
+    })
+    .unwrap();
+}
+
+#[cfg(all(test, feature = "native_sys"))]
+#[test]
+fn comptime_sandbox() {
+    use crate::{Compiler, NativeSys};
+    let code = r#"comptime(&fras "Cargo.toml")"#;
+    let Err(err) = Compiler::with_backend(NativeSys).load_str(code).map(drop) else {
+        panic!("reading a file at compile time should fail");
+    };
+    assert!(err.to_string().contains("not supported"), "{err}");
+    let mut comp = Compiler::with_backend(NativeSys);
+    comp.comptime_sandbox(false).load_str(code).unwrap();
+}
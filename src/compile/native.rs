@@ -0,0 +1,176 @@
+//! Modules of Rust functions that Uiua code can import
+
+use std::{path::PathBuf, sync::Arc};
+
+use ecow::EcoString;
+
+use crate::is_ident_char;
+
+use super::*;
+
+type NativeFn = Arc<dyn Fn(&mut Uiua) -> UiuaResult + Send + Sync + 'static>;
+
+/// A module of Rust functions that Uiua code can import
+///
+/// Register it with [`Compiler::register_module`].
+/// Importing it with `~` binds its functions like those of a Uiua file.
+/// ```
+/// use uiua::*;
+///
+/// let module = NativeModule::new("Functions for working with volume")
+///     .function("Louder", (1, 1), "Double a volume\n? Volume", |env| {
+///         let volume = env.pop_num()?;
+///         env.push(volume * 2.0);
+///         Ok(())
+///     });
+/// let mut comp = Compiler::new();
+/// comp.register_module("audio", module);
+/// comp.load_str("Audio ~ \"audio\"\nAudio~Louder 3").unwrap();
+///
+/// let mut uiua = Uiua::with_safe_sys();
+/// uiua.run_asm(comp.finish()).unwrap();
+/// assert_eq!(uiua.pop_num().unwrap(), 6.0);
+/// ```
+#[derive(Clone, Default)]
+pub struct NativeModule {
+    comment: Option<EcoString>,
+    functions: Vec<NativeFunction>,
+}
+
+#[derive(Clone)]
+struct NativeFunction {
+    name: Ident,
+    sig: Signature,
+    doc: EcoString,
+    f: NativeFn,
+}
+
+impl NativeModule {
+    /// Create a new module with a top-level comment
+    pub fn new(comment: impl Into<EcoString>) -> Self {
+        let comment = comment.into();
+        NativeModule {
+            comment: (!comment.is_empty()).then_some(comment),
+            functions: Vec::new(),
+        }
+    }
+    /// Add a function to the module
+    ///
+    /// The documentation is shown like a binding's doc comment.
+    pub fn function(
+        mut self,
+        name: impl Into<Ident>,
+        signature: impl Into<Signature>,
+        doc: impl Into<EcoString>,
+        f: impl Fn(&mut Uiua) -> UiuaResult + Send + Sync + 'static,
+    ) -> Self {
+        self.functions.push(NativeFunction {
+            name: name.into(),
+            sig: signature.into(),
+            doc: doc.into(),
+            f: Arc::new(f),
+        });
+        self
+    }
+    /// Get the names and signatures of the module's functions
+    pub fn functions(&self) -> impl Iterator<Item = (&str, Signature)> {
+        (self.functions.iter()).map(|f| (f.name.as_str(), f.sig))
+    }
+}
+
+impl Compiler {
+    /// Register a [`NativeModule`] that can be imported by name
+    ///
+    /// Registered modules take precedence over files with the same name.
+    pub fn register_module(
+        &mut self,
+        name: impl Into<EcoString>,
+        module: NativeModule,
+    ) -> &mut Self {
+        self.native_modules.insert(name.into(), module);
+        self
+    }
+    /// Import a registered native module if there is one with the given name
+    pub(super) fn import_native_module(
+        &mut self,
+        name: &str,
+        span: &CodeSpan,
+    ) -> UiuaResult<Option<PathBuf>> {
+        let Some(native) = self.native_modules.get(name).cloned() else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(name);
+        if self.imports.contains_key(&path) {
+            return Ok(Some(path));
+        }
+        let (mut module, ()) = self.in_scope(ScopeKind::File(FileScopeKind::Source), |comp| {
+            for nf in native.functions {
+                if nf.name.is_empty() || !nf.name.chars().all(is_ident_char) {
+                    return Err(comp.error(
+                        span.clone(),
+                        format!("Native function name `{}` is not valid", nf.name),
+                    ));
+                }
+                let mut function = comp.create_function(nf.sig, move |env| (nf.f)(env));
+                function.id = FunctionId::Named(nf.name.clone());
+                let index = comp.next_global;
+                comp.next_global += 1;
+                let local = LocalName {
+                    index,
+                    public: true,
+                };
+                let meta = BindingMeta {
+                    comment: (!nf.doc.is_empty()).then(|| DocComment::from(nf.doc.as_str())),
+                    ..Default::default()
+                };
+                comp.scope.names.insert(nf.name, local);
+                comp.asm.bind_function(local, function, 0, meta);
+            }
+            Ok(())
+        })?;
+        module.comment = native.comment;
+        self.imports.insert(path.clone(), module);
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn native_module() {
+    use crate::{BindingKind, Compiler, NativeModule, Uiua};
+    let module = NativeModule::new("Counting").function(
+        "Count",
+        (1, 2),
+        "Count the rows of an array\n? Array",
+        |env| {
+            let val = env.pop(1)?;
+            let count = val.row_count();
+            env.push(val);
+            env.push(count);
+            Ok(())
+        },
+    );
+    let mut comp = Compiler::new();
+    comp.register_module("count", module.clone());
+    comp.load_str("~ \"count\" ~ Count\nCount [1 2 3]").unwrap();
+    let asm = comp.finish();
+    let binding = (asm.bindings.iter())
+        .find(|b| matches!(&b.kind, BindingKind::Func(f) if f.id == "Count"))
+        .unwrap();
+    let comment = binding.meta.comment.as_ref().unwrap();
+    assert_eq!(comment.text, "Count the rows of an array");
+    assert!(comment.sig.is_some());
+    let mut env = Uiua::with_safe_sys();
+    env.run_asm(asm).unwrap();
+    assert_eq!(env.pop_int().unwrap(), 3);
+
+    let mut comp = Compiler::new();
+    comp.register_module("count", module);
+    assert!(comp.load_str("C ~ \"count\"\nC~Missing 1").is_err());
+    let mut comp = Compiler::new();
+    comp.register_module(
+        "bad",
+        NativeModule::new("").function("a b", (0, 0), "", |_| Ok(())),
+    );
+    assert!(comp.load_str("~ \"bad\"").is_err());
+}
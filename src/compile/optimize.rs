@@ -97,6 +97,8 @@ static OPTIMIZATIONS: &[&dyn Optimization] = &[
     &((1, Flip, Div, Pow), Root),
     &((-1, Pow), (1, Flip, Div)),
     &((2, Pow), (Dup, Mul)),
+    &((Mul, ModOf(Reduce, Add)), DotProduct),
+    &DipIdentity,
     &ByToDup,
     &InlineCustomInverse,
     &TransposeOpt,
@@ -442,6 +444,32 @@ opt!(
     ),
 );
 
+/// Removes `⊙∘` when the nodes before it are known to leave at least 2 values,
+/// so that it cannot fail on a stack that is too short
+#[derive(Debug)]
+struct DipIdentity;
+impl Optimization for DipIdentity {
+    fn match_and_replace(&self, nodes: &mut EcoVec<Node>) -> bool {
+        // The number of values the nodes so far are known to have left on the stack
+        let mut known = 0;
+        for i in 0..nodes.len() {
+            if let Mod(Dip, args, _) = &nodes[i] {
+                if let [f] = args.as_slice() {
+                    if known >= 2 && f.node.iter().all(|node| matches!(node, Prim(Identity, _))) {
+                        replace_nodes(nodes, i, 1, Node::empty());
+                        return true;
+                    }
+                }
+            }
+            known = match nodes[i].clean_sig() {
+                Some(sig) => known.saturating_sub(sig.args) + sig.outputs,
+                None => 0,
+            };
+        }
+        false
+    }
+}
+
 #[derive(Debug)]
 struct ByToDup;
 impl Optimization for ByToDup {
@@ -555,6 +583,25 @@ where
     }
 }
 
+/// Matches a modifier whose only function is matched by the inner pattern
+#[derive(Debug)]
+struct ModOf<A>(Primitive, A);
+impl<A> OptPattern for ModOf<A>
+where
+    A: OptPattern,
+{
+    fn match_nodes(&self, nodes: &[Node]) -> Option<(usize, Option<usize>)> {
+        let [Node::Mod(prim, args, span), ..] = nodes else {
+            return None;
+        };
+        let [f] = args.as_slice() else {
+            return None;
+        };
+        let inner = f.node.as_slice();
+        (*prim == self.0 && self.1.match_nodes(inner)?.0 == inner.len()).then_some((1, Some(*span)))
+    }
+}
+
 trait OptReplace: Debug + Sync {
     fn replacement_node(&self, span: usize) -> Node;
 }
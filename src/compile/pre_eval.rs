@@ -137,6 +137,52 @@ impl Compiler {
         }
         new.map(|new| (new, errors))
     }
+    /// Pre-evaluate constant sections of the functions nested in a node
+    ///
+    /// This lets calls to pure bindings on constant data be folded even
+    /// when they are passed to a modifier. Errors are ignored, as nested
+    /// functions may never be called.
+    ///
+    /// This is only done in [`PreEvalMode::Normal`], as it is purely an
+    /// optimization and other modes may evaluate impure code.
+    ///
+    /// Returns whether the node was changed
+    pub(super) fn pre_eval_nested(&self, node: &mut Node) -> bool {
+        if self.pre_eval_mode != PreEvalMode::Normal {
+            return false;
+        }
+        let mut changed = false;
+        match node {
+            Node::Run(nodes) => {
+                for node in nodes.make_mut() {
+                    changed |= self.pre_eval_nested(node);
+                }
+            }
+            // Filled functions may give different results when evaluated without the fill
+            Node::Mod(Primitive::Fill, ..) | Node::ImplMod(ImplPrimitive::UnFill, ..) => {}
+            Node::Mod(_, args, _)
+            | Node::ImplMod(_, args, _)
+            | Node::Switch { branches: args, .. } => {
+                for arg in args.make_mut() {
+                    changed |= self.pre_eval_nested(&mut arg.node);
+                    if let Some((node, _)) = self.pre_eval(&arg.node) {
+                        arg.node = node;
+                        changed = true;
+                    }
+                }
+            }
+            Node::Array { inner, .. } => {
+                let inner = Arc::make_mut(inner);
+                changed |= self.pre_eval_nested(inner);
+                if let Some((node, _)) = self.pre_eval(inner) {
+                    *inner = node;
+                    changed = true;
+                }
+            }
+            _ => {}
+        }
+        changed
+    }
     pub(super) fn comptime_node(&self, node: &Node) -> UiuaResult<Option<Vec<Value>>> {
         if node.iter().all(|node| matches!(node, Node::Push(_))) {
             return Ok(Some(
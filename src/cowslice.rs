@@ -2,13 +2,22 @@
 
 use std::{
     borrow::Borrow,
+    cell::{Cell, RefCell},
     cmp::Ordering,
+    collections::HashMap,
     fmt,
     hash::{Hash, Hasher},
+    mem::{size_of, take},
     ops::{Bound, Deref, RangeBounds},
     ptr,
+    sync::{
+        atomic::{self, AtomicIsize, AtomicUsize},
+        Arc,
+    },
 };
 
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use serde::*;
 
 macro_rules! cowslice {
@@ -26,6 +35,174 @@ macro_rules! cowslice {
 pub(crate) use cowslice;
 use ecow::EcoVec;
 
+/// The number of memory trackers and allocation scopes that are active on any thread
+///
+/// Nothing is tracked while this is zero, so untracked runs only pay for loading it
+static TRACKING: AtomicUsize = AtomicUsize::new(0);
+
+/// Buffers allocated while memory was being tracked, by address,
+/// with the tracker they count against and their size in bytes
+///
+/// Frees are attributed to the tracker that allocated the buffer, even on another thread
+static BUFFERS: Lazy<Mutex<TrackedBuffers>> = Lazy::new(Default::default);
+type TrackedBuffers = HashMap<usize, (Arc<MemoryTracker>, isize)>;
+
+/// The bytes of array data allocated while one thread was tracking memory
+#[derive(Default)]
+pub(crate) struct MemoryTracker {
+    /// The bytes currently allocated
+    bytes: AtomicIsize,
+    /// The most bytes allocated since the last check
+    peak: AtomicIsize,
+}
+
+impl MemoryTracker {
+    fn add(&self, bytes: isize) {
+        let curr = self.bytes.fetch_add(bytes, atomic::Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(curr, atomic::Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    /// The bytes allocated and number of allocations in the current tracking scope
+    static ALLOCATED: Cell<Option<(isize, usize)>> = const { Cell::new(None) };
+    /// The tracker for this thread, while memory is being tracked
+    static LIVE: RefCell<Option<Arc<MemoryTracker>>> = const { RefCell::new(None) };
+}
+
+fn tracking() -> bool {
+    TRACKING.load(atomic::Ordering::Relaxed) > 0
+}
+
+/// Start tracking the bytes of array data that are allocated
+///
+/// Returns `false` if memory was already being tracked
+pub(crate) fn start_memory_tracking() -> bool {
+    LIVE.with_borrow_mut(|live| {
+        if live.is_some() {
+            return false;
+        }
+        *live = Some(Arc::default());
+        TRACKING.fetch_add(1, atomic::Ordering::Relaxed);
+        true
+    })
+}
+
+/// Stop tracking memory, returning the bytes still allocated
+///
+/// Buffers that are still allocated are counted against the `heir` tracker if there is one,
+/// such as the tracker of the thread that receives a spawned thread's values.
+/// Otherwise, they are no longer tracked.
+pub(crate) fn end_memory_tracking(heir: Option<Arc<MemoryTracker>>) -> usize {
+    let Some(tracker) = LIVE.take() else {
+        return 0;
+    };
+    TRACKING.fetch_sub(1, atomic::Ordering::Relaxed);
+    let mut buffers = BUFFERS.lock();
+    buffers.retain(|_, (owner, bytes)| {
+        if !Arc::ptr_eq(owner, &tracker) {
+            return true;
+        }
+        let Some(heir) = &heir else {
+            return false;
+        };
+        heir.add(*bytes);
+        *owner = heir.clone();
+        true
+    });
+    tracker.bytes.load(atomic::Ordering::Relaxed) as usize
+}
+
+/// Get this thread's memory tracker, if memory is being tracked
+pub(crate) fn memory_tracker() -> Option<Arc<MemoryTracker>> {
+    LIVE.with_borrow(Clone::clone)
+}
+
+/// Get the bytes of array data currently allocated, if memory is being tracked
+pub(crate) fn live_bytes() -> Option<usize> {
+    LIVE.with_borrow(|live| {
+        let tracker = live.as_ref()?;
+        Some(tracker.bytes.load(atomic::Ordering::Relaxed) as usize)
+    })
+}
+
+/// Get the most bytes of array data that were allocated at once since the last call
+///
+/// This includes buffers that have since been freed
+pub(crate) fn take_peak_bytes() -> Option<usize> {
+    LIVE.with_borrow(|live| {
+        let tracker = live.as_ref()?;
+        let bytes = tracker.bytes.load(atomic::Ordering::Relaxed);
+        Some(tracker.peak.swap(bytes, atomic::Ordering::Relaxed) as usize)
+    })
+}
+
+/// Count a buffer that was just allocated against this thread's tracker
+fn track_buffer<T>(data: &EcoVec<T>) {
+    if !tracking() {
+        return;
+    }
+    let bytes = (data.capacity() * size_of::<T>()) as isize;
+    if bytes == 0 {
+        return;
+    }
+    LIVE.with_borrow(|live| {
+        if let Some(tracker) = live {
+            tracker.add(bytes);
+            let entry = (tracker.clone(), bytes);
+            if let Some((owner, bytes)) = BUFFERS.lock().insert(data.as_ptr() as usize, entry) {
+                owner.add(-bytes);
+            }
+        }
+    });
+}
+
+/// Stop counting a buffer at the given address, because it is being freed or handed off
+fn untrack_buffer(addr: usize) {
+    if !tracking() {
+        return;
+    }
+    if let Some((owner, bytes)) = BUFFERS.lock().remove(&addr) {
+        owner.add(-bytes);
+    }
+}
+
+/// Start tracking allocations, returning the counts of the enclosing scope
+pub(crate) fn start_alloc_scope() -> Option<(isize, usize)> {
+    TRACKING.fetch_add(1, atomic::Ordering::Relaxed);
+    ALLOCATED.replace(Some((0, 0)))
+}
+
+/// Stop tracking allocations and restore the enclosing scope
+///
+/// Returns the bytes allocated and number of allocations in this scope,
+/// not including those of any nested scopes
+pub(crate) fn end_alloc_scope(outer: Option<(isize, usize)>) -> (isize, usize) {
+    TRACKING.fetch_sub(1, atomic::Ordering::Relaxed);
+    ALLOCATED.replace(outer).unwrap_or_default()
+}
+
+/// Record a change in the number of allocated elements of type `T`
+///
+/// Negative lengths are buffers that have been handed off to something
+/// that may make them back into a [`CowSlice`]
+fn track_alloc<T>(len: isize) {
+    if len == 0 || !tracking() {
+        return;
+    }
+    ALLOCATED.with(|allocated| {
+        if let Some((bytes, count)) = allocated.get() {
+            let new_bytes = bytes + len * size_of::<T>() as isize;
+            let new_count = if len > 0 {
+                count + 1
+            } else {
+                count.saturating_sub(1)
+            };
+            allocated.set(Some((new_bytes, new_count)));
+        }
+    });
+}
+
 /// The backing buffer for Uiua's arrays' data
 ///
 /// `CowSlice`s are reference-counted buffers that also have associated start and end indices.
@@ -42,8 +219,11 @@ impl<T> CowSlice<T> {
     }
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
+        track_alloc::<T>(capacity as isize);
+        let data = EcoVec::with_capacity(capacity);
+        track_buffer(&data);
         Self {
-            data: EcoVec::with_capacity(capacity),
+            data,
             start: 0,
             end: 0,
         }
@@ -65,12 +245,43 @@ impl<T> CowSlice<T> {
             && self.start == other.start
             && self.end == other.end
     }
+    /// Take the buffer out of tracking
+    ///
+    /// The buffer is counted as freed if this is its last reference
+    fn take_data(&mut self) -> EcoVec<T> {
+        if self.data.is_unique() {
+            untrack_buffer(self.data.as_ptr() as usize);
+        }
+        take(&mut self.data)
+    }
+    /// Count the buffer again if `f` reallocated it
+    fn retrack<R>(&mut self, f: impl FnOnce(&mut EcoVec<T>) -> R) -> R {
+        let (addr, capacity) = (self.data.as_ptr() as usize, self.data.capacity());
+        let res = f(&mut self.data);
+        track_alloc::<T>(self.data.capacity() as isize - capacity as isize);
+        if tracking() && (self.data.as_ptr() as usize, self.data.capacity()) != (addr, capacity) {
+            untrack_buffer(addr);
+            track_buffer(&self.data);
+        }
+        res
+    }
+}
+
+impl<T> Drop for CowSlice<T> {
+    fn drop(&mut self) {
+        if tracking() {
+            self.take_data();
+        }
+    }
 }
 
 impl<T: Clone> CowSlice<T> {
     pub fn from_elem(elem: T, len: usize) -> Self {
+        track_alloc::<T>(len as isize);
+        let data = EcoVec::from_elem(elem, len);
+        track_buffer(&data);
         Self {
-            data: EcoVec::from_elem(elem, len),
+            data,
             start: 0,
             end: len,
         }
@@ -84,7 +295,9 @@ impl<T: Clone> CowSlice<T> {
     pub fn as_mut_slice(&mut self) -> &mut [T] {
         if !self.data.is_unique() {
             let mut new_data = EcoVec::with_capacity(self.len());
+            track_alloc::<T>(self.len() as isize);
             new_data.extend_from_slice(&*self);
+            track_buffer(&new_data);
             self.data = new_data;
             self.start = 0;
             self.end = self.data.len();
@@ -146,7 +359,7 @@ impl<T: Clone> CowSlice<T> {
         F: FnOnce(&mut EcoVec<T>) -> R,
     {
         if self.data.is_unique() && self.start == 0 && self.end == self.data.len() {
-            let res = f(&mut self.data);
+            let res = self.retrack(f);
             self.end = self.data.len();
             res
         } else {
@@ -162,7 +375,7 @@ impl<T: Clone> CowSlice<T> {
         F: FnOnce(&mut EcoVec<T>) -> R,
     {
         if self.data.is_unique() && self.end == self.data.len() {
-            let res = f(&mut self.data);
+            let res = self.retrack(f);
             self.end = self.data.len();
             res
         } else {
@@ -305,6 +518,58 @@ fn cow_slice_modify() {
     assert_eq!(sub, [2, 3, 5]);
 }
 
+#[test]
+fn cow_slice_track_alloc() {
+    let outer = start_alloc_scope();
+    let mut slice = CowSlice::<u64>::with_capacity(4);
+    slice.extend_from_slice(&[1, 2, 3, 4]);
+    let vec = EcoVec::from(slice);
+    let slice = CowSlice::from(vec);
+    let copy = slice.slice(1..3);
+    assert_eq!(copy.len(), 2);
+    assert_eq!(end_alloc_scope(outer), (32, 1));
+}
+
+#[test]
+fn cow_slice_track_memory() {
+    assert!(start_memory_tracking());
+    let slice = CowSlice::<u64>::from([1, 2, 3, 4]);
+    let copy = slice.clone();
+    drop(slice);
+    assert_eq!(live_bytes(), Some(32));
+    drop(copy);
+    assert_eq!(live_bytes(), Some(0));
+    assert_eq!(take_peak_bytes(), Some(32));
+    assert_eq!(take_peak_bytes(), Some(0));
+    assert_eq!(end_memory_tracking(None), 0);
+}
+
+#[test]
+fn cow_slice_track_memory_across_threads() {
+    assert!(start_memory_tracking());
+    let heir = memory_tracker();
+    let untracked = std::thread::spawn(|| CowSlice::<u64>::from([1, 2, 3, 4]))
+        .join()
+        .unwrap();
+    let (kept, freed) = std::thread::spawn(move || {
+        assert!(start_memory_tracking());
+        let kept = CowSlice::<u64>::from([1, 2, 3, 4]);
+        let freed = CowSlice::<u64>::from([5, 6, 7, 8]);
+        assert_eq!(live_bytes(), Some(64));
+        assert_eq!(end_memory_tracking(heir), 64);
+        (kept, freed)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(live_bytes(), Some(64));
+    drop(freed);
+    drop(untracked);
+    assert_eq!(live_bytes(), Some(32));
+    drop(kept);
+    assert_eq!(live_bytes(), Some(0));
+    assert_eq!(end_memory_tracking(None), 0);
+}
+
 impl<T> Default for CowSlice<T> {
     fn default() -> Self {
         Self {
@@ -347,7 +612,7 @@ fn cow_slice_deref_mut() {
 impl<T: Clone> From<CowSlice<T>> for Vec<T> {
     fn from(mut slice: CowSlice<T>) -> Self {
         if slice.data.is_unique() && slice.start == 0 && slice.end == slice.data.len() {
-            slice.data.into_iter().collect()
+            slice.take_data().into_iter().collect()
         } else {
             slice.to_vec()
         }
@@ -355,7 +620,11 @@ impl<T: Clone> From<CowSlice<T>> for Vec<T> {
 }
 
 impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
-    fn from(data: EcoVec<T>) -> Self {
+    fn from(mut data: EcoVec<T>) -> Self {
+        if data.is_unique() {
+            track_alloc::<T>(data.capacity() as isize);
+            track_buffer(&data);
+        }
         Self {
             start: 0,
             end: data.len(),
@@ -367,7 +636,8 @@ impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
 impl<T: Clone> From<CowSlice<T>> for EcoVec<T> {
     fn from(mut slice: CowSlice<T>) -> Self {
         if slice.data.is_unique() && slice.start == 0 && slice.end == slice.data.len() {
-            slice.data
+            track_alloc::<T>(-(slice.data.capacity() as isize));
+            slice.take_data()
         } else {
             slice.as_slice().into()
         }
@@ -376,20 +646,26 @@ impl<T: Clone> From<CowSlice<T>> for EcoVec<T> {
 
 impl<'a, T: Clone> From<&'a [T]> for CowSlice<T> {
     fn from(slice: &'a [T]) -> Self {
+        track_alloc::<T>(slice.len() as isize);
+        let data = EcoVec::from(slice);
+        track_buffer(&data);
         Self {
             start: 0,
             end: slice.len(),
-            data: slice.into(),
+            data,
         }
     }
 }
 
 impl<T: Clone, const N: usize> From<[T; N]> for CowSlice<T> {
     fn from(array: [T; N]) -> Self {
+        track_alloc::<T>(N as isize);
+        let data = EcoVec::from(array);
+        track_buffer(&data);
         Self {
             start: 0,
             end: N,
-            data: array.into(),
+            data,
         }
     }
 }
@@ -459,9 +735,9 @@ impl<T: Hash> Hash for CowSlice<T> {
 impl<T: Clone> IntoIterator for CowSlice<T> {
     type Item = T;
     type IntoIter = CowSliceIntoIter<T>;
-    fn into_iter(self) -> Self::IntoIter {
+    fn into_iter(mut self) -> Self::IntoIter {
         CowSliceIntoIter {
-            data: self.data,
+            data: self.take_data(),
             start: self.start,
             end: self.end,
         }
@@ -0,0 +1,575 @@
+//! Uiua's Debug Adapter Protocol (DAP) implementation
+//!
+//! The adapter communicates over stdin and stdout.
+//! Output from the program being debugged is sent to the editor as output events.
+
+use std::{
+    collections::HashMap,
+    io::{stdin, stdout, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicI64},
+        Arc,
+    },
+};
+
+use crossbeam_channel::{Receiver, TryRecvError};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+
+use crate::{
+    sys::native::set_output_redirect, CodeSpan, FunctionId, InputSrc, PreEvalMode, Span,
+    TraceFrame, Uiua, UiuaErrorKind, UiuaResult, Value,
+};
+
+/// The id of the only thread the adapter reports
+const THREAD_ID: i64 = 1;
+/// The variables reference of the stack scope
+const STACK_REF: i64 = 1;
+/// The variables reference of the bindings scope
+const BINDINGS_REF: i64 = 2;
+/// The maximum number of rows to show when expanding an array
+const MAX_ROWS: usize = 1000;
+
+/// Run the debug adapter on stdin and stdout
+pub fn run_debug_adapter() {
+    let out = Arc::new(Output::default());
+    let (send, recv) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        let mut stdin = stdin().lock();
+        while let Some(request) = read_message(&mut stdin) {
+            if send.send(request).is_err() {
+                break;
+            }
+        }
+    });
+    let session = Arc::new(Mutex::new(Session {
+        out: out.clone(),
+        requests: recv,
+        program: None,
+        stop_on_entry: false,
+        configured: false,
+        breakpoints: HashMap::new(),
+        function_breakpoints: Vec::new(),
+        canonical: HashMap::new(),
+        mode: StepMode::Continue,
+        prev_line: 0,
+        prev_depth: 0,
+        var_values: Vec::new(),
+    }));
+
+    // Wait for the program to be launched
+    let program = loop {
+        let mut session = session.lock();
+        if let (Some(program), true) = (&session.program, session.configured) {
+            break program.clone();
+        }
+        let Ok(request) = session.requests.recv() else {
+            return;
+        };
+        if !session.handle_config(&request) {
+            match request.command.as_str() {
+                "disconnect" | "terminate" => {
+                    out.respond(&request, json!({}));
+                    return;
+                }
+                _ => out.respond_err(&request, "The program has not been launched"),
+            }
+        }
+    };
+
+    // Run the program
+    let redirect_out = out.clone();
+    set_output_redirect(Some(Box::new(move |s, stderr| {
+        let category = if stderr { "stderr" } else { "stdout" };
+        redirect_out.event("output", json!({ "category": category, "output": s }));
+    })));
+    let hook_session = session.clone();
+    let mut env = Uiua::with_native_sys()
+        .with_file_path(&program)
+        .with_exec_hook(move |env, span| hook_session.lock().hook(env, span));
+    let res = env.compile_run(|comp| comp.pre_eval_mode(PreEvalMode::Lazy).load_file(&program));
+    set_output_redirect(None);
+    let exit_code = match res {
+        Ok(_) => {
+            let stack: String = (env.take_stack().iter())
+                .map(|val| format!("{}\n", val.show()))
+                .collect();
+            out.event("output", json!({ "category": "console", "output": stack }));
+            0
+        }
+        Err(e) if matches!(e.kind, UiuaErrorKind::Interrupted) => 0,
+        Err(e) => {
+            let output = format!("{}\n", e.report());
+            out.event("output", json!({ "category": "stderr", "output": output }));
+            1
+        }
+    };
+    out.event("exited", json!({ "exitCode": exit_code }));
+    out.event("terminated", json!({}));
+
+    // Wait for the editor to disconnect
+    let session = session.lock();
+    while let Ok(request) = session.requests.recv() {
+        match request.command.as_str() {
+            "disconnect" | "terminate" => {
+                out.respond(&request, json!({}));
+                break;
+            }
+            "threads" => out.respond(&request, json!({ "threads": [] })),
+            _ => out.respond_err(&request, "The program has finished"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    seq: i64,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    arguments: Json,
+}
+
+/// Read a single request, skipping other messages
+fn read_message(input: &mut impl BufRead) -> Option<Request> {
+    loop {
+        let mut len = None;
+        loop {
+            let mut line = String::new();
+            if input.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(n) = line.strip_prefix("Content-Length:") {
+                len = n.trim().parse().ok();
+            }
+        }
+        let mut body = vec![0; len?];
+        input.read_exact(&mut body).ok()?;
+        match serde_json::from_slice::<Request>(&body) {
+            Ok(request) if request.ty == "request" => return Some(request),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    seq: AtomicI64,
+    lock: Mutex<()>,
+}
+
+impl Output {
+    fn send(&self, mut message: Json) {
+        message["seq"] = (self.seq.fetch_add(1, atomic::Ordering::Relaxed) + 1).into();
+        let body = message.to_string();
+        let _lock = self.lock.lock();
+        let mut stdout = stdout().lock();
+        _ = write!(stdout, "Content-Length: {}\r\n\r\n{body}", body.len());
+        _ = stdout.flush();
+    }
+    fn respond(&self, request: &Request, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": true,
+            "command": request.command,
+            "body": body,
+        }));
+    }
+    fn respond_err(&self, request: &Request, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": false,
+            "command": request.command,
+            "message": message,
+        }));
+    }
+    fn event(&self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
+
+enum StepMode {
+    /// Stop before the next instruction
+    Instruction,
+    /// Stop when the line changes without entering a function
+    Line { line: u16, depth: usize },
+    /// Stop when the current function returns
+    Out { depth: usize },
+    /// Stop only at breakpoints
+    Continue,
+}
+
+struct Session {
+    out: Arc<Output>,
+    requests: Receiver<Request>,
+    program: Option<PathBuf>,
+    stop_on_entry: bool,
+    configured: bool,
+    /// Line breakpoints for each file
+    breakpoints: HashMap<PathBuf, Vec<u16>>,
+    /// Breakpoints on binding names
+    function_breakpoints: Vec<String>,
+    /// Canonicalized file paths
+    canonical: HashMap<PathBuf, PathBuf>,
+    mode: StepMode,
+    prev_line: u16,
+    prev_depth: usize,
+    /// Values that can be expanded, indexed by variables reference
+    var_values: Vec<Value>,
+}
+
+impl Session {
+    /// Handle a request that configures the session, returning whether it was handled
+    fn handle_config(&mut self, request: &Request) -> bool {
+        let args = &request.arguments;
+        match request.command.as_str() {
+            "initialize" => {
+                self.out.respond(
+                    request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsFunctionBreakpoints": true,
+                        "supportsEvaluateForHovers": true,
+                        "supportsTerminateRequest": true,
+                    }),
+                );
+                self.out.event("initialized", json!({}));
+            }
+            "launch" => {
+                let Some(program) = args["program"].as_str() else {
+                    self.out.respond_err(request, "No program was specified");
+                    return true;
+                };
+                self.program = Some(program.into());
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                self.mode = if self.stop_on_entry {
+                    StepMode::Instruction
+                } else {
+                    StepMode::Continue
+                };
+                self.out.respond(request, json!({}));
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.out.respond(request, json!({}));
+            }
+            "setBreakpoints" => {
+                let path = args["source"]["path"].as_str().unwrap_or_default();
+                let path = self.canonicalize(Path::new(path));
+                let lines: Vec<u16> = (args["breakpoints"].as_array().into_iter().flatten())
+                    .filter_map(|bp| bp["line"].as_u64())
+                    .map(|line| line as u16)
+                    .collect();
+                let breakpoints: Vec<_> = (lines.iter())
+                    .map(|line| json!({ "verified": true, "line": line }))
+                    .collect();
+                self.breakpoints.insert(path, lines);
+                self.out
+                    .respond(request, json!({ "breakpoints": breakpoints }));
+            }
+            "setFunctionBreakpoints" => {
+                self.function_breakpoints = (args["breakpoints"].as_array().into_iter())
+                    .flatten()
+                    .filter_map(|bp| bp["name"].as_str().map(Into::into))
+                    .collect();
+                let breakpoints: Vec<_> = (self.function_breakpoints.iter())
+                    .map(|_| json!({ "verified": true }))
+                    .collect();
+                self.out
+                    .respond(request, json!({ "breakpoints": breakpoints }));
+            }
+            "setExceptionBreakpoints" => self.out.respond(request, json!({})),
+            "threads" => self.out.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+            _ => return false,
+        }
+        true
+    }
+    fn canonicalize(&mut self, path: &Path) -> PathBuf {
+        if let Some(canonical) = self.canonical.get(path) {
+            return canonical.clone();
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.into());
+        self.canonical.insert(path.into(), canonical.clone());
+        canonical
+    }
+    /// Called before executing each piece of code
+    fn hook(&mut self, env: &Uiua, span: &CodeSpan) -> UiuaResult {
+        // Handle requests that arrived while running
+        loop {
+            let request = match self.requests.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(UiuaErrorKind::Interrupted.into()),
+            };
+            if self.handle_config(&request) {
+                continue;
+            }
+            match request.command.as_str() {
+                "pause" => {
+                    self.mode = StepMode::Instruction;
+                    self.out.respond(&request, json!({}));
+                }
+                "disconnect" | "terminate" => {
+                    self.out.respond(&request, json!({}));
+                    return Err(UiuaErrorKind::Interrupted.into());
+                }
+                _ => self.out.respond_err(&request, "The program is running"),
+            }
+        }
+
+        let frames = env.call_stack();
+        let depth = frames.len();
+        let line = span.start.line;
+        let path = match &span.src {
+            InputSrc::File(path) => Some(self.canonicalize(path)),
+            _ => None,
+        };
+        let entered_line = path.is_some() && line != self.prev_line;
+        let entered = frames.get(self.prev_depth.min(depth)..).unwrap_or_default();
+        let hit_line = entered_line
+            && (path.as_ref())
+                .and_then(|path| self.breakpoints.get(path))
+                .is_some_and(|lines| lines.contains(&line));
+        let hit_function = (self.function_breakpoints.iter()).any(|name| {
+            (entered.iter()).any(|frame| frame.id.as_ref().is_some_and(|id| *id == name.as_str()))
+        });
+        if path.is_some() {
+            self.prev_line = line;
+        }
+        self.prev_depth = depth;
+        let reason = if hit_line || hit_function {
+            if hit_line {
+                "breakpoint"
+            } else {
+                "function breakpoint"
+            }
+        } else {
+            match self.mode {
+                StepMode::Instruction if self.stop_on_entry => "entry",
+                StepMode::Instruction => "step",
+                StepMode::Line {
+                    line: prev_line,
+                    depth: prev_depth,
+                } if depth < prev_depth
+                    || depth == prev_depth && path.is_some() && line != prev_line =>
+                {
+                    "step"
+                }
+                StepMode::Out { depth: prev_depth } if depth < prev_depth => "step",
+                _ => return Ok(()),
+            }
+        };
+        self.stop_on_entry = false;
+        self.stopped(env, span, &frames, reason)
+    }
+    /// Handle requests while stopped
+    fn stopped(
+        &mut self,
+        env: &Uiua,
+        span: &CodeSpan,
+        frames: &[TraceFrame],
+        reason: &str,
+    ) -> UiuaResult {
+        self.var_values.clear();
+        self.out.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+        loop {
+            let Ok(request) = self.requests.recv() else {
+                return Err(UiuaErrorKind::Interrupted.into());
+            };
+            if self.handle_config(&request) {
+                continue;
+            }
+            let args = &request.arguments;
+            let depth = frames.len();
+            let mode = match request.command.as_str() {
+                "continue" => Some(StepMode::Continue),
+                "next" => Some(StepMode::Line {
+                    line: span.start.line,
+                    depth,
+                }),
+                "stepIn" => Some(StepMode::Instruction),
+                "stepOut" => Some(StepMode::Out { depth }),
+                _ => None,
+            };
+            if let Some(mode) = mode {
+                self.mode = mode;
+                self.out
+                    .respond(&request, json!({ "allThreadsContinued": true }));
+                return Ok(());
+            }
+            match request.command.as_str() {
+                "pause" => self.out.respond(&request, json!({})),
+                "disconnect" | "terminate" => {
+                    self.out.respond(&request, json!({}));
+                    return Err(UiuaErrorKind::Interrupted.into());
+                }
+                "stackTrace" => {
+                    let stack_frames = self.stack_frames(span, frames);
+                    let total = stack_frames.len();
+                    self.out.respond(
+                        &request,
+                        json!({ "stackFrames": stack_frames, "totalFrames": total }),
+                    );
+                }
+                "scopes" => self.out.respond(
+                    &request,
+                    json!({ "scopes": [
+                        { "name": "Stack", "variablesReference": STACK_REF, "expensive": false },
+                        { "name": "Bindings", "variablesReference": BINDINGS_REF, "expensive": false },
+                    ] }),
+                ),
+                "variables" => {
+                    let reference = args["variablesReference"].as_i64().unwrap_or(0);
+                    let variables = self.variables(env, reference);
+                    self.out
+                        .respond(&request, json!({ "variables": variables }));
+                }
+                "evaluate" => {
+                    let expression = args["expression"].as_str().unwrap_or_default();
+                    match evaluate(env, expression) {
+                        Ok(Some(val)) => {
+                            let body = json!({
+                                "result": describe(&val),
+                                "variablesReference": self.reference(&val),
+                            });
+                            self.out.respond(&request, body)
+                        }
+                        Ok(None) => self.out.respond(
+                            &request,
+                            json!({ "result": "(empty stack)", "variablesReference": 0 }),
+                        ),
+                        Err(e) => self.out.respond_err(&request, &e),
+                    }
+                }
+                _ => self
+                    .out
+                    .respond_err(&request, &format!("Unsupported request {}", request.command)),
+            }
+        }
+    }
+    fn stack_frames(&mut self, span: &CodeSpan, frames: &[TraceFrame]) -> Vec<Json> {
+        // Each frame's call span is in the function of the frame before it
+        let mut locations = vec![(frames.last(), span.clone())];
+        for i in (1..frames.len()).rev() {
+            if let Span::Code(call_span) = &frames[i].span {
+                locations.push((Some(&frames[i - 1]), call_span.clone()));
+            }
+        }
+        (locations.into_iter().enumerate())
+            .map(|(i, (frame, span))| {
+                let name = match frame.and_then(|frame| frame.id.as_ref()) {
+                    Some(FunctionId::Main) | None if i + 1 == frames.len() => "main".into(),
+                    Some(id) => id.to_string(),
+                    None => "<anonymous>".into(),
+                };
+                let source = match &span.src {
+                    InputSrc::File(path) => {
+                        let path = self.canonicalize(path);
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        json!({ "name": name, "path": path })
+                    }
+                    _ => json!({ "name": span.to_string() }),
+                };
+                json!({
+                    "id": i,
+                    "name": name,
+                    "source": source,
+                    "line": span.start.line,
+                    "column": span.start.col,
+                    "endLine": span.end.line,
+                    "endColumn": span.end.col,
+                })
+            })
+            .collect()
+    }
+    /// Get a variables reference for a value, or 0 if it cannot be expanded
+    fn reference(&mut self, val: &Value) -> i64 {
+        if val.rank() == 0 && !matches!(val, Value::Box(_)) {
+            return 0;
+        }
+        self.var_values.push(val.clone());
+        (self.var_values.len() + BINDINGS_REF as usize) as i64
+    }
+    fn variables(&mut self, env: &Uiua, reference: i64) -> Vec<Json> {
+        let mut named: Vec<(String, Value)> = Vec::new();
+        match reference {
+            STACK_REF => {
+                for (i, val) in env.stack().iter().rev().enumerate() {
+                    let name = match &val.meta().label {
+                        Some(label) => format!("{i}: {label}"),
+                        None => i.to_string(),
+                    };
+                    named.push((name, val.clone()));
+                }
+            }
+            BINDINGS_REF => {
+                let mut bindings: Vec<_> = env.bound_values().into_iter().collect();
+                bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+                named.extend(bindings.into_iter().map(|(name, val)| (name.into(), val)));
+            }
+            _ => {
+                let index = reference as usize - BINDINGS_REF as usize - 1;
+                let Some(val) = self.var_values.get(index).cloned() else {
+                    return Vec::new();
+                };
+                if let Value::Box(arr) = &val {
+                    if arr.rank() == 0 {
+                        named.push(("0".into(), arr.data[0].0.clone()));
+                    }
+                }
+                if named.is_empty() {
+                    let rows = val.rows().take(MAX_ROWS).enumerate();
+                    named.extend(rows.map(|(i, row)| (i.to_string(), row)));
+                }
+            }
+        }
+        (named.into_iter())
+            .map(|(name, val)| {
+                json!({
+                    "name": name,
+                    "value": describe(&val),
+                    "type": val.type_name(),
+                    "variablesReference": self.reference(&val),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Describe a value on a single line
+fn describe(val: &Value) -> String {
+    let shown = val.show();
+    if shown.contains('\n') {
+        format!("{} {}", val.shape(), val.type_name_plural())
+    } else {
+        shown
+    }
+}
+
+/// Evaluate some code on a copy of the stack and return the top value
+fn evaluate(env: &Uiua, code: &str) -> Result<Option<Value>, String> {
+    let mut rt = Uiua::with_safe_sys();
+    for val in env.stack() {
+        rt.push(val.clone());
+    }
+    match rt.run_str(code) {
+        Ok(_) => Ok(rt.take_stack().pop()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,280 @@
+//! Converting Uiua values into Rust data with [`serde`]
+//!
+//! This is the inverse of [`ser`](crate::ser):
+//! - [Map](crate::Primitive::Map) arrays deserialize as structs or maps
+//! - Arrays of rank 1 or higher deserialize as sequences of their rows
+//! - Boxes are unboxed
+//! - `NaN` deserializes as `None`
+//! - Strings deserialize as unit enum variants, and single-entry maps deserialize as other enum variants
+//!
+//! ```
+//! # use serde::Deserialize;
+//! #[derive(Deserialize)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! let mut uiua = uiua::Uiua::with_safe_sys();
+//! uiua.run_str(r#"map {"x" "y"} [1 2]"#).unwrap();
+//! let point: Point = uiua::de::from_value(&uiua.pop("point").unwrap()).unwrap();
+//! assert_eq!((point.x, point.y), (1.0, 2.0));
+//! ```
+
+use std::fmt;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+use crate::{value::ExactDoubleIterator, Value};
+
+/// Convert a Uiua value into a Rust value
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(value.clone()))
+}
+
+/// An error that occurs when converting between Rust and Uiua values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(pub(crate) String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// A [`serde::Deserializer`] that reads Uiua values
+#[derive(Debug, Clone)]
+pub struct Deserializer(Value);
+
+impl Deserializer {
+    /// Create a new deserializer for a value
+    ///
+    /// Scalar boxes are unboxed.
+    pub fn new(mut value: Value) -> Self {
+        while let Value::Box(arr) = &value {
+            if arr.rank() != 0 {
+                break;
+            }
+            value = arr.data[0].0.clone();
+        }
+        Deserializer(value)
+    }
+    fn scalar_num(&self) -> Option<f64> {
+        match &self.0 {
+            Value::Num(arr) if arr.rank() == 0 => Some(arr.data[0]),
+            Value::Byte(arr) if arr.rank() == 0 => Some(arr.data[0] as f64),
+            _ => None,
+        }
+    }
+    fn string(&self) -> Option<String> {
+        match &self.0 {
+            Value::Char(arr) if arr.rank() <= 1 => Some(arr.data.iter().collect()),
+            _ => None,
+        }
+    }
+    fn error(&self, expected: &str) -> Error {
+        Error(format!(
+            "Expected {expected}, but found a {} array of shape {}",
+            self.0.type_name(),
+            self.0.shape()
+        ))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_map() {
+            return visitor.visit_map(Map(self.0.map_kv().into_iter(), None));
+        }
+        match &self.0 {
+            Value::Num(arr) if arr.rank() == 0 => {
+                let n = arr.data[0];
+                if n.is_nan() {
+                    visitor.visit_unit()
+                } else if n.fract() != 0.0 || n.abs() > 2f64.powi(53) {
+                    visitor.visit_f64(n)
+                } else if n < 0.0 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_u64(n as u64)
+                }
+            }
+            Value::Byte(arr) if arr.rank() == 0 => {
+                if arr.meta().flags.is_boolean() {
+                    visitor.visit_bool(arr.data[0] != 0)
+                } else {
+                    visitor.visit_u8(arr.data[0])
+                }
+            }
+            Value::Char(arr) if arr.rank() == 0 => visitor.visit_char(arr.data[0]),
+            Value::Char(arr) if arr.rank() == 1 => visitor.visit_string(arr.data.iter().collect()),
+            Value::Complex(_) => Err(Error("Complex numbers cannot be deserialized".into())),
+            _ => visitor.visit_seq(Seq(self.0.into_rows())),
+        }
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.scalar_num() {
+            Some(n) if n == 0.0 || n == 1.0 => visitor.visit_bool(n == 1.0),
+            _ => Err(self.error("a boolean")),
+        }
+    }
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.scalar_num() {
+            Some(n) => visitor.visit_f64(n),
+            None => Err(self.error("a number")),
+        }
+    }
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.string() {
+            Some(s) => visitor.visit_string(s),
+            None => Err(self.error("a string")),
+        }
+    }
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self.0 {
+            Value::Byte(arr) if arr.rank() == 1 => visitor.visit_byte_buf(arr.data.to_vec()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.scalar_num() {
+            Some(n) if n.is_nan() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if let Some(variant) = self.string() {
+            return visitor.visit_enum(variant.into_deserializer());
+        }
+        if self.0.is_map() && self.0.row_count() == 1 {
+            let (key, value) = self.0.map_kv().into_iter().next().unwrap();
+            return visitor.visit_enum(Enum(key, value));
+        }
+        Err(self.error("a string or a map with one entry"))
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct Seq(Box<dyn ExactDoubleIterator<Item = Value>>);
+
+impl<'de> SeqAccess<'de> for Seq {
+    type Error = Error;
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        (self.0.next())
+            .map(|row| seed.deserialize(Deserializer::new(row)))
+            .transpose()
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct Map(std::vec::IntoIter<(Value, Value)>, Option<Value>);
+
+impl<'de> MapAccess<'de> for Map {
+    type Error = Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.0.next() else {
+            return Ok(None);
+        };
+        self.1 = Some(value);
+        seed.deserialize(Deserializer::new(key)).map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value =
+            (self.1.take()).ok_or_else(|| Error("Map value requested before key".into()))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct Enum(Value, Value);
+
+impl<'de> EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Deserializer;
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(Deserializer::new(self.0))?;
+        Ok((variant, Deserializer::new(self.1)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -0,0 +1,195 @@
+//! Generate documentation for Uiua modules
+//!
+//! Docs are built from the doc comments and signatures of a module's public bindings.
+//! Modules and items a module imports are private.
+//! A module re-exports one by binding its name to itself, as in `Square ← Square`.
+//!
+//! ```
+//! use uiua::{docgen::ModuleDocs, Compiler};
+//!
+//! let mut comp = Compiler::new();
+//! comp.load_str("# Add one to a number\n# ? X\nIncr ← +1").unwrap();
+//! let docs = ModuleDocs::new("incr", &comp);
+//! let md = docs.to_markdown();
+//! assert!(md.contains("## `Incr`"));
+//! assert!(md.contains("Add one to a number"));
+//! ```
+
+use std::{fmt::Write, path::PathBuf};
+
+use ecow::EcoString;
+
+use crate::{BindingKind, Compiler, DocComment, Ident, Module, Signature};
+
+/// The documentation of a module
+#[derive(Debug, Clone)]
+pub struct ModuleDocs {
+    /// The name of the module
+    pub name: EcoString,
+    /// The module's top-level comment
+    pub comment: Option<EcoString>,
+    /// The module's public items, in definition order
+    pub items: Vec<ItemDocs>,
+}
+
+/// The documentation of an item in a module
+#[derive(Debug, Clone)]
+pub struct ItemDocs {
+    /// The name of the item
+    pub name: Ident,
+    /// What kind of item it is
+    pub kind: ItemDocsKind,
+    /// The item's doc comment
+    pub comment: Option<DocComment>,
+    /// The deprecation message
+    pub deprecation: Option<EcoString>,
+}
+
+/// A kind of documented item
+#[derive(Debug, Clone)]
+pub enum ItemDocsKind {
+    /// A constant
+    Constant,
+    /// A function with a signature
+    Function(Signature),
+    /// A scoped module
+    Module(ModuleDocs),
+    /// An imported module
+    Import(PathBuf),
+    /// An index macro with a number of arguments
+    IndexMacro(usize),
+    /// A code macro
+    CodeMacro,
+}
+
+impl ModuleDocs {
+    /// Collect the docs for the top level of the code loaded into a compiler
+    pub fn new(name: impl Into<EcoString>, comp: &Compiler) -> Self {
+        Self::from_module(name.into(), &comp.top_level_module(), comp)
+    }
+    fn from_module(name: EcoString, module: &Module, comp: &Compiler) -> Self {
+        let bindings = &comp.assembly().bindings;
+        let items = (module.names.iter())
+            .filter(|(_, local)| local.public)
+            .filter_map(|(name, local)| {
+                let binding = &bindings[local.index];
+                let kind = match &binding.kind {
+                    BindingKind::Const(_) => ItemDocsKind::Constant,
+                    BindingKind::Func(f) => ItemDocsKind::Function(f.sig),
+                    BindingKind::Module(module) => {
+                        ItemDocsKind::Module(Self::from_module(name.clone(), module, comp))
+                    }
+                    BindingKind::Import(path) => ItemDocsKind::Import(path.clone()),
+                    BindingKind::IndexMacro(n) => ItemDocsKind::IndexMacro(*n),
+                    BindingKind::CodeMacro(_) => ItemDocsKind::CodeMacro,
+                    BindingKind::Error => return None,
+                };
+                Some(ItemDocs {
+                    name: name.clone(),
+                    kind,
+                    comment: binding.meta.comment.clone(),
+                    deprecation: binding.meta.deprecation.clone(),
+                })
+            })
+            .collect();
+        ModuleDocs {
+            name,
+            comment: module.comment.clone(),
+            items,
+        }
+    }
+    /// Render the docs as Markdown
+    ///
+    /// Code blocks in doc comments without a language are marked as Uiua.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        self.write_markdown(&mut md, 1);
+        md
+    }
+    fn write_markdown(&self, md: &mut String, depth: usize) {
+        let heading = "#".repeat(depth.min(6));
+        _ = writeln!(md, "{heading} {}\n", self.name);
+        if let Some(comment) = &self.comment {
+            write_doc_text(md, comment);
+        }
+        if depth == 1 && !self.items.is_empty() {
+            for item in &self.items {
+                _ = writeln!(md, "- [`{}`](#{})", item.name, item.name.to_lowercase());
+            }
+            md.push('\n');
+        }
+        let heading = "#".repeat((depth + 1).min(6));
+        for item in &self.items {
+            if let ItemDocsKind::Module(module) = &item.kind {
+                module.write_markdown(md, depth + 1);
+                continue;
+            }
+            _ = writeln!(md, "{heading} `{}`\n", item.name);
+            let sig = item.comment.as_ref().and_then(|c| c.sig.as_ref());
+            match (&item.kind, sig) {
+                (ItemDocsKind::Function(_) | ItemDocsKind::Constant, Some(sig)) => {
+                    let sig = sig.to_string();
+                    _ = writeln!(md, "```uiua\n{} {}\n```\n", item.name, sig.trim());
+                }
+                (ItemDocsKind::Function(sig), None) => _ = writeln!(md, "Function `{sig}`\n"),
+                (ItemDocsKind::Constant, None) => md.push_str("Constant\n\n"),
+                (ItemDocsKind::Import(path), _) => {
+                    _ = writeln!(md, "Imported from `{}`\n", path.display())
+                }
+                (ItemDocsKind::IndexMacro(n), _) => {
+                    _ = writeln!(md, "Index macro with {n} argument(s)\n")
+                }
+                (ItemDocsKind::CodeMacro, _) => md.push_str("Code macro\n\n"),
+                (ItemDocsKind::Module(_), _) => unreachable!(),
+            }
+            if let Some(deprecation) = &item.deprecation {
+                _ = writeln!(md, "**Deprecated**: {deprecation}\n");
+            }
+            if let Some(comment) = &item.comment {
+                write_doc_text(md, &comment.text);
+            }
+        }
+    }
+}
+
+fn write_doc_text(md: &mut String, text: &str) {
+    let mut in_block = false;
+    for line in text.lines() {
+        if line.starts_with("```") {
+            if !in_block && line.trim() == "```" {
+                md.push_str("```uiua\n");
+                in_block = true;
+                continue;
+            }
+            in_block = !in_block;
+        }
+        md.push_str(line);
+        md.push('\n');
+    }
+    if in_block {
+        md.push_str("```\n");
+    }
+    if !text.trim().is_empty() {
+        md.push('\n');
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn module_docs() {
+    use crate::Compiler;
+    let mut comp = Compiler::new();
+    comp.load_str(
+        "# Shapes\n\n# Double\n# ```\n# Dbl 2\n# ```\nDbl ← ×2\nHidden ↚ 1\n\
+        ┌─╴Geo\n  # Area of a circle\n  # ? R\n  Area ← ×π×.\n└─╴",
+    )
+    .unwrap();
+    let docs = ModuleDocs::new("shapes", &comp);
+    let names: Vec<_> = docs.items.iter().map(|item| item.name.as_str()).collect();
+    assert_eq!(names, ["Dbl", "Geo"]);
+    let md = docs.to_markdown();
+    assert!(md.starts_with("# shapes\n\nShapes\n"), "{md}");
+    assert!(md.contains("Function `|1.1`"), "{md}");
+    assert!(md.contains("```uiua\nDbl 2\n```"), "{md}");
+    assert!(md.contains("### `Area`\n\n```uiua\nArea ? R\n```"), "{md}");
+}
@@ -1,13 +1,16 @@
-use std::{convert::Infallible, error::Error, fmt, io, mem::take, path::PathBuf, sync::Arc};
+use std::{
+    convert::Infallible, error::Error, fmt, io, mem::take, path::PathBuf, str::FromStr, sync::Arc,
+};
 
 use colored::*;
+use ecow::EcoString;
 
 use crate::{
     function::FunctionId,
     lex::{Sp, Span},
     parse::ParseError,
     value::Value,
-    CodeSpan, Ident, InputSrc, Inputs,
+    CodeSpan, Ident, InputSrc, Inputs, Primitive, Shape,
 };
 
 /// An error produced when running/compiling/formatting a Uiua program
@@ -26,6 +29,19 @@ pub struct UiuaError {
     pub multi: Vec<Self>,
     /// Additional info about the error
     pub infos: Vec<(String, Option<Span>)>,
+    /// Details that only some errors have
+    ///
+    /// These are boxed so that they do not grow every [`UiuaResult`]
+    pub details: Option<Box<ErrorDetails>>,
+}
+
+/// Details that only some [`UiuaError`]s have
+#[derive(Debug, Clone, Default)]
+pub struct ErrorDetails {
+    /// A tag classifying the error, set by `raise`
+    pub tag: Option<EcoString>,
+    /// The iterations of modifiers the error occurred in, innermost first
+    pub iterations: Vec<IterationFrame>,
 }
 
 /// The kind of an error produced when running/compiling/formatting a Uiua program
@@ -50,6 +66,10 @@ pub enum UiuaErrorKind {
     Throw(Box<Value>, Span, Box<Inputs>),
     /// Maximum execution time exceeded
     Timeout(Span, Box<Inputs>),
+    /// Maximum memory usage exceeded
+    MemoryLimit(Span, Box<Inputs>),
+    /// Maximum number of operations exceeded
+    OpLimit(Span, Box<Inputs>),
     /// The compiler panicked
     CompilerPanic(String),
     /// The program was interrupted
@@ -72,7 +92,34 @@ impl From<UiuaErrorKind> for UiuaError {
             is_case: false,
             multi: Vec::new(),
             infos: Vec::new(),
+            details: None,
+        }
+    }
+}
+
+/// An iteration of a modifier that an error occurred in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IterationFrame {
+    /// The iterating modifier
+    pub prim: Primitive,
+    /// The index of the iteration
+    pub index: usize,
+    /// The shapes of the arguments passed to the modifier's function
+    pub shapes: Vec<Shape>,
+}
+
+impl fmt::Display for IterationFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  in iteration {} of {}", self.index, self.prim.format())?;
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let sep = if i == 0 {
+                " with argument shapes "
+            } else {
+                ", "
+            };
+            write!(f, "{sep}{shape}")?;
         }
+        Ok(())
     }
 }
 
@@ -104,8 +151,13 @@ impl fmt::Display for UiuaError {
                 Ok(())
             }
             UiuaErrorKind::Run { message: error, .. } => write!(f, "{error}"),
-            UiuaErrorKind::Throw(value, span, _) => write!(f, "{span}: {value}"),
+            UiuaErrorKind::Throw(value, span, _) => match self.tag() {
+                Some(tag) => write!(f, "{span}: {tag}: {value}"),
+                None => write!(f, "{span}: {value}"),
+            },
             UiuaErrorKind::Timeout(..) => write!(f, "Maximum execution time exceeded"),
+            UiuaErrorKind::MemoryLimit(..) => write!(f, "Maximum memory usage exceeded"),
+            UiuaErrorKind::OpLimit(..) => write!(f, "Maximum number of operations exceeded"),
             UiuaErrorKind::CompilerPanic(message) => message.fmt(f),
             UiuaErrorKind::Interrupted => write!(f, "# Program interrupted"),
         }
@@ -125,6 +177,23 @@ impl UiuaError {
             _ => self.to_string().into(),
         }
     }
+    /// Get the kind of the error as a string
+    ///
+    /// This is the tag given to `raise`, or a classification of a built-in error.
+    pub fn kind_tag(&self) -> EcoString {
+        if let Some(tag) = self.tag() {
+            return tag.clone();
+        }
+        match &self.kind {
+            UiuaErrorKind::Throw(..) => "assert",
+            UiuaErrorKind::Timeout(..) => "timeout",
+            UiuaErrorKind::MemoryLimit(..) => "memory",
+            UiuaErrorKind::OpLimit(..) => "ops",
+            UiuaErrorKind::Interrupted => "interrupted",
+            _ => "error",
+        }
+        .into()
+    }
     /// Turn the error into a multi-error
     pub fn into_multi(mut self) -> Vec<Self> {
         let mut multi = take(&mut self.multi);
@@ -140,6 +209,11 @@ impl UiuaError {
         error.multi.extend(iter);
         error
     }
+    /// Whether the error is a [`SYS_PENDING`](crate::SYS_PENDING) system call
+    pub(crate) fn is_pending(&self) -> bool {
+        matches!(&self.kind, UiuaErrorKind::Run { message, .. }
+            if message.value == crate::SYS_PENDING)
+    }
     /// Mark the error as fill-related
     pub(crate) fn fill(mut self) -> Self {
         self.is_fill = true;
@@ -170,6 +244,39 @@ impl UiuaError {
             _ => {}
         }
     }
+    /// Get the tag classifying the error, if it was set by `raise`
+    pub fn tag(&self) -> Option<&EcoString> {
+        self.details.as_ref()?.tag.as_ref()
+    }
+    /// Get the iterations of modifiers the error occurred in, innermost first
+    pub fn iterations(&self) -> &[IterationFrame] {
+        match &self.details {
+            Some(details) => &details.iterations,
+            None => &[],
+        }
+    }
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        self.details.get_or_insert_with(Default::default)
+    }
+    /// Set the tag classifying the error
+    pub fn with_tag(mut self, tag: impl Into<EcoString>) -> Self {
+        self.details_mut().tag = Some(tag.into());
+        self
+    }
+    /// Record an iteration of a modifier that the error occurred in
+    pub(crate) fn in_iteration(
+        mut self,
+        prim: Primitive,
+        index: usize,
+        shapes: impl IntoIterator<Item = Shape>,
+    ) -> Self {
+        self.details_mut().iterations.push(IterationFrame {
+            prim,
+            index,
+            shapes: shapes.into_iter().collect(),
+        });
+        self
+    }
     /// Make a Load error
     pub fn load(path: PathBuf, error: io::Error) -> Self {
         UiuaErrorKind::Load(path, Arc::new(error)).into()
@@ -296,13 +403,17 @@ impl UiuaError {
                 report
             }
             UiuaErrorKind::Throw(message, span, inputs) => {
-                Report::new_multi(kind, inputs, [(&message, span.clone())])
+                let message = match self.tag() {
+                    Some(tag) => format!("{tag}: {message}"),
+                    None => message.to_string(),
+                };
+                Report::new_multi(kind, inputs, [(message, span.clone())])
+            }
+            UiuaErrorKind::Timeout(span, inputs)
+            | UiuaErrorKind::MemoryLimit(span, inputs)
+            | UiuaErrorKind::OpLimit(span, inputs) => {
+                Report::new_multi(kind, inputs, [(self.to_string(), span.clone())])
             }
-            UiuaErrorKind::Timeout(span, inputs) => Report::new_multi(
-                kind,
-                inputs,
-                [("Maximum execution time exceeded", span.clone())],
-            ),
             UiuaErrorKind::CompilerPanic(message) => Report::new(kind, message),
             UiuaErrorKind::Load(..) | UiuaErrorKind::Format(..) => {
                 Report::new(kind, self.to_string())
@@ -315,12 +426,20 @@ impl UiuaError {
             }
         };
         report = report.trace(&self.trace);
+        for frame in self.iterations() {
+            report.fragments.push(ReportFragment::Newline);
+            report
+                .fragments
+                .push(ReportFragment::Plain(frame.to_string()));
+        }
         let default_inputs = Inputs::default();
         let inputs = match &self.kind {
             UiuaErrorKind::Parse(_, inputs)
             | UiuaErrorKind::Run { inputs, .. }
             | UiuaErrorKind::Throw(_, _, inputs)
-            | UiuaErrorKind::Timeout(_, inputs) => inputs,
+            | UiuaErrorKind::Timeout(_, inputs)
+            | UiuaErrorKind::MemoryLimit(_, inputs)
+            | UiuaErrorKind::OpLimit(_, inputs) => inputs,
             _ => &default_inputs,
         };
         for (info, span) in &self.infos {
@@ -393,6 +512,36 @@ pub enum DiagnosticKind {
     Warning,
 }
 
+impl DiagnosticKind {
+    /// All diagnostic kinds
+    pub const ALL: [Self; 4] = [Self::Info, Self::Style, Self::Advice, Self::Warning];
+}
+
+impl FromStr for DiagnosticKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "info" => DiagnosticKind::Info,
+            "style" => DiagnosticKind::Style,
+            "advice" => DiagnosticKind::Advice,
+            "warning" => DiagnosticKind::Warning,
+            _ => return Err(format!("Unknown diagnostic kind `{s}`")),
+        })
+    }
+}
+
+/// How a kind of diagnostic is reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiagnosticLevel {
+    /// Don't report the diagnostic
+    Allow,
+    /// Report the diagnostic normally
+    #[default]
+    Warn,
+    /// Report the diagnostic as a compile error
+    Deny,
+}
+
 impl fmt::Display for Diagnostic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.message.fmt(f)
@@ -683,3 +832,58 @@ impl fmt::Display for Report {
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[test]
+fn error_kinds() {
+    use crate::{Uiua, Value};
+    let mut env = Uiua::with_safe_sys();
+    let Err(err) = env.run_str("# Experimental!\nraise \"custom\" 5") else {
+        panic!("raise should fail");
+    };
+    assert_eq!(err.kind_tag(), "custom");
+    assert_eq!(err.value(), Value::from(5));
+}
+
+#[cfg(test)]
+#[test]
+fn iteration_frames() {
+    use crate::{Primitive, Shape, Uiua};
+    let mut env = Uiua::with_safe_sys();
+    let Err(err) = env.run_str("≡(/(⍤\"big\" <10 . +)) [1_2_3 4_5_6]") else {
+        panic!("Program should fail");
+    };
+    let frames: Vec<_> = (err.iterations().iter())
+        .map(|frame| (frame.prim, frame.index, frame.shapes.clone()))
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (Primitive::Reduce, 2, vec![Shape::SCALAR, Shape::SCALAR]),
+            (Primitive::Rows, 1, vec![Shape::from(3)]),
+        ]
+    );
+    let report = err.report().to_string();
+    assert!(report.contains("in iteration 1 of"), "{report}");
+}
+
+#[cfg(test)]
+#[test]
+fn shape_mismatch_info() {
+    use crate::{Primitive, Uiua};
+    let info = |code: &str| {
+        let Err(err) = Uiua::with_safe_sys().run_str(code) else {
+            panic!("Program should fail");
+        };
+        assert!(err.to_string().lines().count() == 1, "{err}");
+        err.infos.into_iter().next().unwrap().0
+    };
+    let pervade = info("+ ⇡3_4 ⇡3_5");
+    assert!(pervade.contains("differ at axis 1"), "{pervade}");
+    assert!(pervade.contains("[3 × 4 × 2]\n  [3 × 5 × 2]\n       ^"));
+    assert!(pervade.contains(&Primitive::Fill.format().to_string()));
+    let couple = info("⊟ ⇡2_3 ⇡3_2");
+    assert!(couple.contains(&Primitive::Orient.format().to_string()));
+    let fix = info("+ [1 2] ⇡3_2");
+    assert!(fix.contains(&Primitive::Fix.format().to_string()));
+}
@@ -8,6 +8,7 @@ use std::{
     fmt::Display,
     fs,
     iter::repeat,
+    ops::Range,
     path::{Path, PathBuf},
     time::Duration,
 };
@@ -17,12 +18,12 @@ use InlineMacro;
 
 use crate::{
     ast::*,
-    grid_fmt::GridFmt,
+    grid_fmt::{FormatSettings, GridFmt},
     is_ident_char, is_ident_start,
-    lex::{CodeSpan, Loc, Sp},
+    lex::{lex, CodeSpan, Loc, Sp, Token},
     parse::{flip_unsplit_lines, parse, split_words, trim_spaces},
     Compiler, Handle, Ident, InputSrc, Inputs, PreEvalMode, Primitive, RunMode, SafeSys, Signature,
-    SysBackend, Uiua, UiuaErrorKind, UiuaResult, Value, SUBSCRIPT_DIGITS,
+    SysBackend, TlsClientSettings, Uiua, UiuaErrorKind, UiuaResult, Value, SUBSCRIPT_DIGITS,
 };
 
 trait ConfigValue: Sized {
@@ -222,6 +223,11 @@ create_config!(
     (align_comments, bool, true),
     /// Whether to indent item imports
     (indent_item_imports, bool, true),
+    /// The maximum width of a line before it is wrapped
+    ///
+    /// Long lines are wrapped by moving the body of a modifier's function or function pack onto its own indented lines.
+    /// `0` means lines are never wrapped.
+    (max_line_width, usize, 0),
 );
 
 /// The source from which to populate the formatter configuration.
@@ -339,6 +345,64 @@ impl FormatOutput {
         }
         (pos, pos)
     }
+    /// Map a cursor position in formatted code back to a position in the unformatted code
+    pub fn unmap_char_pos(&self, pos: u32) -> u32 {
+        for (span, (start, end)) in &self.glyph_map {
+            if (start.char_pos..=end.char_pos).contains(&pos) {
+                let same_len = span.char_count() == end.char_pos - start.char_pos;
+                return if same_len {
+                    span.start.char_pos + pos - start.char_pos
+                } else if pos == end.char_pos {
+                    span.end.char_pos
+                } else {
+                    span.start.char_pos
+                };
+            }
+        }
+        // Between glyphs, keep the distance from the previous glyph
+        let prev = (self.glyph_map.iter()).rfind(|(_, (_, end))| end.char_pos <= pos);
+        let next = (self.glyph_map.iter()).find(|(_, (start, _))| start.char_pos >= pos);
+        let unmapped = prev.map_or(pos, |(span, (_, end))| {
+            span.end.char_pos + pos - end.char_pos
+        });
+        next.map_or(unmapped, |(span, _)| unmapped.min(span.start.char_pos))
+    }
+    /// Map a span in formatted code back to the span of the unformatted code it came from
+    ///
+    /// This is useful for showing errors from running formatted code against the code as it was written.
+    pub fn unmap_span(&self, span: &CodeSpan) -> CodeSpan {
+        let Some((first, _)) = self.glyph_map.first() else {
+            return span.clone();
+        };
+        let src = first.src.clone();
+        let input = self.inputs.get(&src);
+        let start = self.unmap_char_pos(span.start.char_pos);
+        let end = self.unmap_char_pos(span.end.char_pos).max(start);
+        let loc_at = |char_pos: u32| {
+            let mut loc = Loc {
+                line: 1,
+                col: 1,
+                byte_pos: 0,
+                char_pos: 0,
+            };
+            for c in input.chars().take(char_pos as usize) {
+                if c == '\n' {
+                    loc.line += 1;
+                    loc.col = 1;
+                } else {
+                    loc.col += 1;
+                }
+                loc.char_pos += 1;
+                loc.byte_pos += c.len_utf8() as u32;
+            }
+            loc
+        };
+        CodeSpan {
+            start: loc_at(start),
+            end: loc_at(end),
+            src,
+        }
+    }
 }
 
 #[test]
@@ -371,6 +435,45 @@ fn map_char_pos() {
     assert_eq!(output.map_char_pos(34), (31, 31));
 }
 
+#[test]
+#[cfg(test)]
+fn unmap_span() {
+    let input = "reverse range 5 # Reversed
+\"hello\" first [1 2 3]
+";
+    let output = format_str(input, &FormatConfig::default()).unwrap();
+    assert_eq!(output.output, "⇌ ⇡ 5 # Reversed\n\"hello\" ⊢ [1 2 3]\n");
+    // Glyphs map back to their names
+    assert_eq!(output.unmap_char_pos(0), 0);
+    assert_eq!(output.unmap_char_pos(1), 7);
+    assert_eq!(output.unmap_char_pos(2), 8);
+    assert_eq!(output.unmap_char_pos(4), 14);
+    // Positions in strings map to the same character
+    assert_eq!(output.unmap_char_pos(18), 28);
+    // Spans are rebuilt with lines and columns in the unformatted code
+    let mut inputs = Inputs::default();
+    let (_, _, src) = lex(&output.output, InputSrc::Str(0), &mut inputs);
+    let formatted_span = |start: u32, end: u32, line: u16, col: u16| CodeSpan {
+        src: src.clone(),
+        start: Loc {
+            line,
+            col,
+            byte_pos: 0,
+            char_pos: start,
+        },
+        end: Loc {
+            line,
+            col: col + (end - start) as u16,
+            byte_pos: 0,
+            char_pos: end,
+        },
+    };
+    let first = output.unmap_span(&formatted_span(25, 26, 2, 9));
+    assert_eq!((first.start.line, first.start.col), (2, 9));
+    assert_eq!(first.start.char_pos..first.end.char_pos, 35..40);
+    first.as_str(&output.inputs, |s| assert_eq!(s, "first"));
+}
+
 /// Format Uiua code
 ///
 /// The path is used for error reporting
@@ -401,6 +504,7 @@ fn format_impl(input: &str, src: InputSrc, config: &FormatConfig) -> UiuaResult<
             prev_import_function: None,
             output_comments: None,
             eval_output_comments: true,
+            checking_line: false,
         }
         .format_top_items(&items);
         let formatted = FormatOutput {
@@ -423,6 +527,211 @@ fn format_impl(input: &str, src: InputSrc, config: &FormatConfig) -> UiuaResult<
     }
 }
 
+/// Format Uiua code and spell it using only ASCII
+///
+/// Glyphs are replaced with their names or ASCII spellings.
+/// Strings, characters, and comments are left as they are.
+/// Formatting the result normally turns it back into glyphs.
+pub fn format_ascii(input: &str, config: &FormatConfig) -> UiuaResult<String> {
+    let formatted = format_str(input, config)?.output;
+    let mut inputs = Inputs::default();
+    let (tokens, _, _) = lex(&formatted, InputSrc::Str(0), &mut inputs);
+    let mut ascii = String::with_capacity(formatted.len());
+    let mut end = 0;
+    for token in tokens {
+        let range = token.span.byte_range();
+        ascii.push_str(&formatted[end..range.start]);
+        end = range.end;
+        let text = &formatted[range];
+        if text.is_ascii() {
+            ascii.push_str(text);
+            continue;
+        }
+        let spelled: Cow<str> = match &token.value {
+            Token::Glyph(prim) => match prim.ascii() {
+                Some(token) => token.to_string().into(),
+                None => prim.name().into(),
+            },
+            Token::Number => text
+                .replace('¯', "`")
+                .replace('η', "eta")
+                .replace('π', "pi")
+                .replace('τ', "tau")
+                .replace('∞', "infinity")
+                .into(),
+            Token::Ident(_) | Token::Subscr(_) => ascii_subscripts(text).into(),
+            Token::Simple(token) => token.to_string().into(),
+            Token::LeftArrow | Token::LeftStrokeArrow | Token::LeftArrowTilde => {
+                // Names change length when spelled in ASCII,
+                // so the padding that aligns binding arrows is dropped
+                let trimmed = ascii.trim_end_matches(' ').len();
+                if trimmed < ascii.len() {
+                    ascii.truncate(trimmed);
+                    ascii.push(' ');
+                }
+                if let Token::LeftArrow = token.value {
+                    "=".into()
+                } else {
+                    "=~".into()
+                }
+            }
+            Token::OpenModule | Token::CloseModule => "---".into(),
+            _ => text.into(),
+        };
+        // Keep names from running into the words around them
+        if spelled.starts_with(|c: char| c.is_ascii_alphabetic())
+            && ascii.ends_with(|c: char| c.is_alphanumeric())
+        {
+            ascii.push(' ');
+        }
+        ascii.push_str(&spelled);
+        if spelled.ends_with(|c: char| c.is_ascii_alphabetic())
+            && formatted[end..].starts_with(|c: char| c.is_alphabetic() || c.is_ascii_digit())
+        {
+            ascii.push(' ');
+        }
+    }
+    ascii.push_str(&formatted[end..]);
+    Ok(ascii)
+}
+
+/// Spell the subscripts and double exclamation points of an identifier in ASCII
+fn ascii_subscripts(ident: &str) -> String {
+    let mut ascii = String::with_capacity(ident.len());
+    let mut in_subscript = false;
+    for c in ident.chars() {
+        if let Some(digit) = SUBSCRIPT_DIGITS.iter().position(|&d| d == c) {
+            if !in_subscript {
+                ascii.push_str("__");
+                in_subscript = true;
+            }
+            ascii.push(char::from(b'0' + digit as u8));
+        } else {
+            match c {
+                '₋' => ascii.push_str("__`"),
+                '⌞' => ascii.push_str("__<"),
+                '⌟' => ascii.push_str("__>"),
+                '‼' => ascii.push_str("!!"),
+                c => ascii.push(c),
+            }
+            in_subscript = c == '₋';
+        }
+    }
+    ascii
+}
+
+/// A replacement of part of some unformatted code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatEdit {
+    /// The byte range in the unformatted code to replace
+    pub range: Range<usize>,
+    /// The text to replace it with
+    pub text: String,
+}
+
+/// Format Uiua code and get the edits that turn it into the formatted code
+///
+/// Only the parts of the code that change are included in the edits,
+/// so applying them does not touch the rest of the code.
+pub fn format_edits(input: &str, config: &FormatConfig) -> UiuaResult<Vec<FormatEdit>> {
+    let formatted = format_str(input, config)?;
+    Ok(diff_edits(input, &formatted.output))
+}
+
+/// Format a byte range of Uiua code and get the edits that format it
+///
+/// The whole input is parsed so that the range is formatted the same way it would be in the whole file.
+/// Only edits that touch the range are returned.
+pub fn format_range(
+    input: &str,
+    range: Range<usize>,
+    config: &FormatConfig,
+) -> UiuaResult<Vec<FormatEdit>> {
+    let mut edits = format_edits(input, config)?;
+    edits.retain(|edit| edit.range.start <= range.end && range.start <= edit.range.end);
+    Ok(edits)
+}
+
+/// Get the minimal edits that turn one string into another
+///
+/// Lines are matched first, and then each changed group of lines is trimmed to the characters that differ.
+fn diff_edits(old: &str, new: &str) -> Vec<FormatEdit> {
+    /// Beyond this many line comparisons, changed lines are not matched up
+    const MAX_TABLE_SIZE: usize = 1 << 22;
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    // Skip common leading and trailing lines
+    let prefix = (old_lines.iter().zip(&new_lines))
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = (old_lines[prefix..].iter().rev())
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+    // Match the remaining lines with a longest common subsequence
+    let (n, m) = (old_mid.len(), new_mid.len());
+    let mut pairs = Vec::new();
+    if (n + 1) * (m + 1) <= MAX_TABLE_SIZE {
+        let mut table = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                table[i * (m + 1) + j] = if old_mid[i] == new_mid[j] {
+                    table[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    table[(i + 1) * (m + 1) + j].max(table[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_mid[i] == new_mid[j] {
+                pairs.push((i, j));
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * (m + 1) + j] >= table[i * (m + 1) + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    pairs.push((n, m));
+    // Turn each run of unmatched lines into an edit
+    let mut old_pos: usize = old_lines[..prefix].iter().map(|line| line.len()).sum();
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (pi, pj) in pairs {
+        if i < pi || j < pj {
+            let old_text: String = old_mid[i..pi].concat();
+            let new_text: String = new_mid[j..pj].concat();
+            let start = (old_text.char_indices().zip(new_text.chars()))
+                .find(|((_, a), b)| a != b)
+                .map_or(old_text.len().min(new_text.len()), |((i, _), _)| i);
+            let start = (0..=start)
+                .rev()
+                .find(|&i| old_text.is_char_boundary(i) && new_text.is_char_boundary(i))
+                .unwrap_or(0);
+            let end = (old_text[start..].chars().rev())
+                .zip(new_text[start..].chars().rev())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c.len_utf8())
+                .sum::<usize>();
+            edits.push(FormatEdit {
+                range: old_pos + start..old_pos + old_text.len() - end,
+                text: new_text[start..new_text.len() - end].into(),
+            });
+            old_pos += old_text.len();
+        }
+        if pi < n {
+            old_pos += old_mid[pi].len();
+        }
+        (i, j) = (pi + 1, pj + 1);
+    }
+    edits
+}
+
 /// Format Uiua code in a file at the given path
 ///
 /// This modifies the file
@@ -459,6 +768,7 @@ pub(crate) fn format_words(words: &[Sp<Word>], inputs: &Inputs) -> String {
         prev_import_function: None,
         output_comments: None,
         eval_output_comments: false,
+        checking_line: false,
     };
     formatter.format_words(words, true, 0);
     formatter.output
@@ -475,6 +785,7 @@ pub(crate) fn format_word(word: &Sp<Word>, inputs: &Inputs) -> String {
         prev_import_function: None,
         output_comments: None,
         eval_output_comments: false,
+        checking_line: false,
     };
     formatter.format_word(word, 0);
     formatter.output
@@ -490,6 +801,7 @@ struct Formatter<'a> {
     prev_import_function: Option<Ident>,
     output_comments: Option<HashMap<usize, Vec<Vec<Value>>>>,
     eval_output_comments: bool,
+    checking_line: bool,
 }
 
 type GlyphMap = Vec<(CodeSpan, (Loc, Loc))>;
@@ -619,7 +931,17 @@ impl Formatter<'_> {
                 self.output.push_str("┌─╴");
                 match &m.value.kind {
                     ModuleKind::Named(name) => self.push(&name.span, &name.value),
-                    ModuleKind::Test => self.output.push_str("test"),
+                    ModuleKind::Test(name) | ModuleKind::Bench(name) => {
+                        let keyword = match &m.value.kind {
+                            ModuleKind::Bench(_) => "bench",
+                            _ => "test",
+                        };
+                        self.output.push_str(keyword);
+                        if let Some(name) = name {
+                            self.output.push(' ');
+                            self.push(&name.span, &name.value);
+                        }
+                    }
                 }
                 if let Some(line) = &m.value.imports {
                     self.output.push(' ');
@@ -686,7 +1008,7 @@ impl Formatter<'_> {
                     .unwrap_or_else(|| binding.arrow_span.clone());
                 let mut lines = flip_unsplit_lines(split_words(binding.words.clone()));
                 if lines.len() == 1 {
-                    self.format_words(&lines[0], true, depth);
+                    self.format_line(&lines[0], depth);
                 } else {
                     lines.push(Vec::new());
                     self.format_words(
@@ -911,7 +1233,7 @@ impl Formatter<'_> {
     fn format_word(&mut self, word: &Sp<Word>, depth: usize) {
         match &word.value {
             Word::Number(Ok(n)) => {
-                let grid_str = n.grid_string(false);
+                let grid_str = FormatSettings::with_default(|| n.grid_string(false));
                 let formatted = word.span.as_str(self.inputs, |s| {
                     if !s.contains('/')
                         && !grid_str.contains('…')
@@ -961,9 +1283,10 @@ impl Formatter<'_> {
             }
             Word::Number(Err(s)) => self.push(&word.span, s),
             Word::Label(label) => self.push(&word.span, &format!("${label}")),
-            Word::Char(_) | Word::String(_) | Word::FormatString(_) => self
-                .output
-                .push_str(&self.inputs.get(&word.span.src)[word.span.byte_range()]),
+            Word::Char(_) | Word::String(_) | Word::FormatString(_) => {
+                let text = &self.inputs.get(&word.span.src)[word.span.byte_range()];
+                self.push(&word.span, text)
+            }
             Word::MultilineString(lines) => {
                 let curr_line_pos = if self.output.ends_with('\n') {
                     0
@@ -1117,6 +1440,7 @@ impl Formatter<'_> {
                 self.format_words(&m.operands, true, depth);
             }
             Word::Placeholder(i) => self.push(&word.span, &format!("^{i}")),
+            Word::NamedPlaceholder(name) => self.push(&word.span, &format!("^{name}")),
             Word::Subscripted(sub) => match &sub.word.value {
                 Word::Modified(m) => {
                     self.format_modifier(&m.modifier, depth);
@@ -1328,7 +1652,7 @@ impl Formatter<'_> {
             && !prevent_compact
             && !lines[0].iter().any(|word| word_is_multiline(&word.value))
         {
-            self.format_words(&lines[0], true, depth);
+            self.format_line(&lines[0], depth);
             return;
         }
         // Remove trailing empty lines
@@ -1374,9 +1698,47 @@ impl Formatter<'_> {
                     }
                 }
             }
-            self.format_words(line, true, depth);
+            self.format_line(line, depth);
         }
     }
+    /// Format a line of words, wrapping it if it is longer than the maximum line width
+    ///
+    /// Only lines that start on their own output line are checked.
+    /// Lines nested inside them are checked once wrapping puts them on their own line.
+    fn format_line(&mut self, words: &[Sp<Word>], depth: usize) {
+        let line_start = self.output.rfind('\n').map_or(0, |i| i + 1);
+        let starts_line = self.output[line_start..].trim().is_empty();
+        if self.config.max_line_width == 0
+            || self.checking_line && !starts_line
+            || (words.iter()).any(|word| matches!(word.value, Word::OutputComment { .. }))
+        {
+            self.format_words(words, true, depth);
+            return;
+        }
+        let output_len = self.output.len();
+        let glyph_map_len = self.glyph_map.len();
+        let comments_len = self.end_of_line_comments.len();
+        let was_checking = self.checking_line;
+        self.checking_line = true;
+        let mut words = Cow::Borrowed(words);
+        loop {
+            self.format_words(&words, true, depth);
+            let formatted = &self.output[line_start..];
+            let first = formatted.split('\n').next().unwrap_or_default();
+            let last = formatted.split('\n').next_back().unwrap_or_default();
+            if first.chars().count().max(last.chars().count()) <= self.config.max_line_width {
+                break;
+            }
+            let Some(wrapped) = wrap_widest(&words) else {
+                break;
+            };
+            self.output.truncate(output_len);
+            self.glyph_map.truncate(glyph_map_len);
+            self.end_of_line_comments.truncate(comments_len);
+            words = Cow::Owned(wrapped);
+        }
+        self.checking_line = was_checking;
+    }
     fn format_modifier(&mut self, modifier: &Sp<Modifier>, depth: usize) {
         match &modifier.value {
             Modifier::Primitive(prim) => self.format_primitive(*prim, &modifier.span),
@@ -1466,6 +1828,56 @@ impl Formatter<'_> {
     }
 }
 
+/// Wrap the widest word in a line that can be wrapped
+fn wrap_widest(words: &[Sp<Word>]) -> Option<Vec<Sp<Word>>> {
+    let (i, wrapped) = (words.iter().enumerate())
+        .filter_map(|(i, word)| wrap_word(&word.value).map(|wrapped| (i, wrapped)))
+        .max_by_key(|(i, _)| (words[*i].span.char_count(), usize::MAX - i))?;
+    let mut words = words.to_vec();
+    words[i].value = wrapped;
+    Some(words)
+}
+
+/// Wrap a single-line function or function pack, possibly as a modifier operand
+///
+/// A function's body is moved onto its own indented line.
+/// Each branch of a function pack is put on its own line.
+fn wrap_word(word: &Word) -> Option<Word> {
+    match word {
+        Word::Func(func) if func.lines.len() == 1 && !func.lines[0].is_empty() => {
+            if words_are_multiline(&func.lines[0]) {
+                return None;
+            }
+            let mut func = func.clone();
+            let line = func.lines.pop().unwrap();
+            func.lines = vec![Vec::new(), line, Vec::new()];
+            Some(Word::Func(func))
+        }
+        Word::Pack(pack)
+            if pack.branches.len() > 1
+                && (pack.branches.iter()).all(|br| {
+                    br.value.lines.len() <= 1
+                        && !(br.value.lines.iter()).any(|line| words_are_multiline(line))
+                }) =>
+        {
+            let mut pack = pack.clone();
+            let last = pack.branches.len() - 1;
+            for br in &mut pack.branches[..last] {
+                br.value.lines.push(Vec::new());
+            }
+            Some(Word::Pack(pack))
+        }
+        Word::Modified(m) => {
+            let (i, wrapped) = (m.operands.iter().enumerate())
+                .find_map(|(i, word)| wrap_word(&word.value).map(|wrapped| (i, wrapped)))?;
+            let mut m = m.clone();
+            m.operands[i].value = wrapped;
+            Some(Word::Modified(m))
+        }
+        _ => None,
+    }
+}
+
 fn words_are_multiline(words: &[Sp<Word>]) -> bool {
     if let Some((last, words)) = words.split_last() {
         words.iter().any(|word| word_is_multiline(&word.value))
@@ -1521,7 +1933,7 @@ pub(crate) fn word_is_multiline(word: &Word) -> bool {
                     _ => false,
                 }
         }
-        Word::Placeholder(_) => false,
+        Word::Placeholder(_) | Word::NamedPlaceholder(_) => false,
         Word::Subscripted(sub) => word_is_multiline(&sub.word.value),
         Word::Comment(_) => true,
         Word::Spaces => false,
@@ -1617,8 +2029,8 @@ impl SysBackend for FormatterBackend {
     fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
         native().tcp_connect(addr)
     }
-    fn tls_connect(&self, addr: &str) -> Result<Handle, String> {
-        native().tls_connect(addr)
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        native().tls_connect(addr, settings)
     }
     fn timezone(&self) -> Result<f64, String> {
         native().timezone()
@@ -1791,3 +2203,142 @@ F₁
     let formatted = format_str(input, &FormatConfig::default()).unwrap().output;
     assert_eq!(formatted, output);
 }
+
+#[test]
+#[cfg(test)]
+fn formatter_max_line_width() {
+    let config = FormatConfig::default().with_max_line_width(30);
+    for (input, output) in [
+        (
+            "/+ ≡(⊂⊃(↙2|↘3) ⇌ ⊏⍏. ⊂ [1 2 3]) ⇡10\n",
+            "\
+/+ ≡(
+  ⊂⊃(↙2|↘3) ⇌ ⊏⍏. ⊂ [1 2 3]
+) ⇡10
+",
+        ),
+        (
+            "F ← ⍥(+1 × 2 ⊂ [1 2 3 4] ⇌ ⊏⍏.)5\n",
+            "\
+F ← ⍥(
+  +1 × 2 ⊂ [1 2 3 4] ⇌ ⊏⍏.
+)5
+",
+        ),
+        (
+            "⊃(⊂ [1 2 3] ⇌ ⊏⍏. ⇡5|⊂ [4 5 6] ⇌ ⊏⍖. ⇡5)\n",
+            "\
+⊃(⊂ [1 2 3] ⇌ ⊏⍏. ⇡5
+| ⊂ [4 5 6] ⇌ ⊏⍖. ⇡5)
+",
+        ),
+        (
+            "≡(⊂⊃(↙2 ⇌ ⊏⍏.|↘3 ⇌ ⊏⍖.) ⊂ [1 2 3 4 5 6])\n",
+            "\
+≡(
+  ⊂⊃(
+    ↙2 ⇌ ⊏⍏.
+  | ↘3 ⇌ ⊏⍖.
+  ) ⊂ [1 2 3 4 5 6]
+)
+",
+        ),
+        (
+            "+ 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16\n",
+            "+ 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16\n",
+        ),
+    ] {
+        let formatted = format_str(input, &config).unwrap().output;
+        assert_eq!(formatted, output);
+        let reformatted = format_str(&formatted, &config).unwrap().output;
+        assert_eq!(reformatted, formatted, "formatting is not idempotent");
+        let unwrapped = format_str(input, &FormatConfig::default()).unwrap().output;
+        assert_eq!(unwrapped, input);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn format_edits_are_minimal() {
+    fn apply(input: &str, edits: &[FormatEdit]) -> String {
+        let mut output = input.to_string();
+        for edit in edits.iter().rev() {
+            output.replace_range(edit.range.clone(), &edit.text);
+        }
+        output
+    }
+    let config = FormatConfig::default();
+    let input = "\
+F ← +1
+x ← reverse range 5
+y ← 3
+G ← (
+first x
+)
+z ← [1 2 3]
+";
+    let formatted = format_str(input, &config).unwrap().output;
+    let edits = format_edits(input, &config).unwrap();
+    assert_eq!(apply(input, &edits), formatted);
+    assert_eq!(
+        edits,
+        [
+            FormatEdit {
+                range: 15..28,
+                text: "⇌ ⇡".into()
+            },
+            FormatEdit {
+                range: 47..52,
+                text: "  ⊢".into()
+            }
+        ]
+    );
+    assert!(format_edits(&formatted, &config).unwrap().is_empty());
+    // Only edits touching the range are returned
+    let func = input.find("G ←").unwrap()..input.find("z ←").unwrap();
+    let edits = format_range(input, func, &config).unwrap();
+    assert_eq!(edits.len(), 1);
+    assert!(apply(input, &edits).contains("x ← reverse range 5"));
+}
+
+#[test]
+#[cfg(test)]
+fn format_ascii_round_trip() {
+    let input = "\
+# Sum of squares ⇌
+F₂ ← /+ⁿ2
+G ↚ ⊂⊃(↙₂|⍥₋₁⇌) ¯1
+X‼ ← ^0^1
+┌─╴M
+  A ← ×π ∞ \"⇌⍏\"
+└─╴
+⊟₃ 1 2 3
+≠1_2 ¯3/4
+";
+    let ascii = format_ascii(input, &FormatConfig::default()).unwrap();
+    assert_eq!(
+        ascii,
+        "\
+# Sum of squares ⇌
+F__2 = /+power 2
+G =~ join fork(take__2|repeat__`1 reverse) `1
+X!! = ^0^1
+---M
+  A = *pi infinity \"⇌⍏\"
+---
+couple__3 1 2 3
+!=1_2 `3/4
+"
+    );
+    // Formatting the ASCII code gives the same tokens as the original code
+    let tokens = |code: &str| {
+        let code = format_str(code, &FormatConfig::default()).unwrap().output;
+        let (tokens, errors, _) = lex(&code, InputSrc::Str(0), &mut Inputs::default());
+        assert!(errors.is_empty());
+        (tokens.into_iter())
+            .map(|token| token.value)
+            .filter(|token| *token != Token::Spaces)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(tokens(&ascii), tokens(input));
+}
@@ -43,6 +43,13 @@ impl Signature {
     pub fn is_subset_of(self, other: Self) -> bool {
         self.is_compatible_with(other) && self.args <= other.args
     }
+    /// Check if a switch branch with this signature can be used in a switch with another signature
+    ///
+    /// Arguments that the branch does not use are discarded.
+    pub fn fits_switch(self, switch: Self) -> bool {
+        self.args <= switch.args
+            && (self.is_compatible_with(switch) || self.outputs == switch.outputs)
+    }
     /// Get the signature that has the maximum of the arguments and outputs of this signature and another
     pub fn max_with(self, other: Self) -> Self {
         Self::new(self.args.max(other.args), self.outputs.max(other.outputs))
@@ -1,6 +1,7 @@
 //! Pretty printing Uiua arrays
 
 use std::{
+    cell::Cell,
     f64::consts::{PI, TAU},
     iter::once,
     mem::take,
@@ -25,6 +26,136 @@ pub struct GridFmtParams {
     pub depth: usize,
 }
 
+/// Settings for how arrays and numbers are formatted
+///
+/// These are set with the `&nf` and `&elide` system functions.
+/// They are stored in the [`Uiua`](crate::Uiua) runtime and only apply to the current thread while it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatSettings {
+    /// The maximum number of digits after the decimal point
+    ///
+    /// `None` uses the shortest representation that round-trips.
+    pub precision: Option<usize>,
+    /// The power of 10 at which numbers switch to scientific notation
+    ///
+    /// Numbers with a magnitude below `10^-n` are also shown in scientific notation.
+    pub scientific: Option<u32>,
+    /// The character used to separate groups of thousands
+    pub separator: Option<char>,
+    /// The number of rows to show at the start and end of each axis
+    ///
+    /// The rows in between are replaced with a marker that says how many were hidden.
+    /// `Some(0)` is treated like `None`.
+    pub elide: Option<usize>,
+}
+
+thread_local! {
+    static FORMAT_SETTINGS: Cell<FormatSettings> = Cell::new(FormatSettings::default());
+}
+
+impl FormatSettings {
+    /// Get the format settings for the current thread
+    pub fn get() -> Self {
+        FORMAT_SETTINGS.with(Cell::get)
+    }
+    /// Set the format settings for the current thread
+    pub fn set(self) {
+        FORMAT_SETTINGS.with(|fs| fs.set(self))
+    }
+    /// Run a function with these format settings, then restore the previous ones
+    pub fn scoped<T>(self, f: impl FnOnce() -> T) -> T {
+        let prev = FORMAT_SETTINGS.with(|fs| fs.replace(self));
+        let res = f();
+        prev.set();
+        res
+    }
+    /// Run a function with the default format settings
+    pub(crate) fn with_default<T>(f: impl FnOnce() -> T) -> T {
+        Self::default().scoped(f)
+    }
+    fn is_scientific(&self, positive: f64) -> bool {
+        let Some(n) = self.scientific else {
+            return false;
+        };
+        let limit = 10f64.powi(n as i32);
+        positive >= limit || positive != 0.0 && positive < 1.0 / limit
+    }
+    fn scientific(&self, positive: f64) -> String {
+        let s = if let Some(precision) = self.precision {
+            format!("{positive:.precision$e}")
+        } else {
+            format!("{positive:e}")
+        };
+        let (mantissa, exponent) = s.split_once('e').unwrap();
+        let mantissa = trim_decimal(mantissa);
+        format!("{mantissa}e{}", exponent.replace('-', "¯"))
+    }
+    /// Get the number of rows to show at each end of an axis, if it is elided
+    fn elided(&self, len: usize) -> Option<usize> {
+        self.elide.filter(|&n| n > 0 && len > 2 * n + 1)
+    }
+    fn group(&self, s: String) -> String {
+        let Some(sep) = self.separator else {
+            return s;
+        };
+        let (int, rest) = s.split_at(s.find('.').unwrap_or(s.len()));
+        let mut grouped = String::with_capacity(s.len() + int.len() / 3);
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                grouped.push(sep);
+            }
+            grouped.push(c);
+        }
+        grouped.push_str(rest);
+        grouped
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn format_settings_stay_in_runtime() {
+    let third = Value::from(1.0 / 3.0);
+    let default = third.show();
+    let mut env = crate::Uiua::with_safe_sys();
+    env.run_str("&nf 2 ∞ \"\" 1/3").unwrap();
+    // The settings do not leak into the rest of the thread
+    assert_eq!(third.show(), default);
+    // But they can still be used to show the program's values
+    let shown = (env.format_settings()).scoped(|| env.pop("value").unwrap().show());
+    assert_eq!(shown, "0.33");
+}
+
+#[cfg(test)]
+#[test]
+fn elide_zero() {
+    let mut env = crate::Uiua::with_safe_sys();
+    assert!(env.run_str("&elide 0").is_err());
+    let settings = FormatSettings {
+        elide: Some(0),
+        ..Default::default()
+    };
+    env.run_str("↯3_2⇡6").unwrap();
+    let matrix = env.pop("matrix").unwrap();
+    let shown = settings.scoped(|| matrix.show());
+    assert_eq!(shown, matrix.show());
+}
+
+fn thousands(n: usize) -> String {
+    let settings = FormatSettings {
+        separator: Some(','),
+        ..Default::default()
+    };
+    settings.group(n.to_string())
+}
+
+fn trim_decimal(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
 pub trait GridFmt {
     fn fmt_grid(&self, params: GridFmtParams) -> Grid;
     fn grid_string(&self, label: bool) -> String {
@@ -58,6 +189,7 @@ impl GridFmt for f64 {
         let positive = f.abs();
         let is_neg = f.is_sign_negative();
         let minus = if is_neg { "¯" } else { "" };
+        let nf = FormatSettings::get();
         let s = if (positive - PI).abs() <= f64::EPSILON {
             format!("{minus}π")
         } else if (positive - TAU).abs() <= f64::EPSILON {
@@ -72,8 +204,12 @@ impl GridFmt for f64 {
             "⊥".into()
         } else if f.to_bits() == WILDCARD_NAN.to_bits() {
             "W".into()
-        } else if positive.fract() == 0.0 || positive.is_nan() {
+        } else if positive.is_nan() {
             format!("{minus}{positive}")
+        } else if nf.is_scientific(positive) {
+            format!("{minus}{}", nf.scientific(positive))
+        } else if positive.fract() == 0.0 {
+            format!("{minus}{}", nf.group(positive.to_string()))
         } else if let Some((num, denom, approx)) =
             [1u8, 2, 3, 4, 5, 6, 8, 9, 12].iter().find_map(|&denom| {
                 let num = (positive * denom as f64) / TAU;
@@ -93,6 +229,10 @@ impl GridFmt for f64 {
             } else {
                 format!("{prefix}{minus}{num}τ/{denom}")
             }
+        } else if let Some(precision) = nf.precision {
+            let pos_formatted = format!("{positive:.precision$}");
+            let pos_formatted = nf.group(trim_decimal(&pos_formatted).into());
+            format!("{minus}{pos_formatted}")
         } else {
             let mut pos_formatted = positive.to_string();
             if pos_formatted.len() >= 17 {
@@ -126,6 +266,7 @@ impl GridFmt for f64 {
                     }
                 }
             }
+            let pos_formatted = nf.group(pos_formatted);
             if is_neg {
                 format!("{minus}{pos_formatted}")
             } else {
@@ -196,7 +337,17 @@ impl GridFmt for Value {
                 return grid;
             } else {
                 // Normal box list
-                for Boxed(val) in &b.data {
+                let elided = FormatSettings::get().elided(b.data.len());
+                for (i, Boxed(val)) in b.data.iter().enumerate() {
+                    if let Some(n) = elided {
+                        if i == n {
+                            let marker = format!("⋯ {} more ⋯", thousands(b.data.len() - 2 * n));
+                            item_lines.push(marker.chars().collect());
+                        }
+                        if (n..b.data.len() - n).contains(&i) {
+                            continue;
+                        }
+                    }
                     let grid = val.fmt_grid(GridFmtParams {
                         boxed: false,
                         depth: params.depth + 1,
@@ -351,9 +502,10 @@ impl<T: GridFmt + ArrayValue> GridFmt for Array<T> {
             }
 
             // Default array formatting
+            let mut markers = Vec::new();
             let mut metagrid = metagrid.unwrap_or_else(|| {
                 let mut metagrid = Metagrid::new();
-                fmt_array(&self.shape, &self.data, params, &mut metagrid);
+                fmt_array(&self.shape, &self.data, params, &mut metagrid, &mut markers);
                 metagrid
             });
 
@@ -434,6 +586,22 @@ impl<T: GridFmt + ArrayValue> GridFmt for Array<T> {
                 }
                 grid.extend(subrows);
             }
+            // Fill in the markers for elided rows
+            for (meta_row, count) in markers {
+                let line = &mut grid[row_heights[..meta_row].iter().sum::<usize>()];
+                let width = line.len();
+                let count = thousands(count);
+                let marker = [format!("⋮ {count} rows ⋮"), format!("⋮ {count} ⋮")]
+                    .into_iter()
+                    .find(|marker| marker.chars().count() <= width)
+                    .unwrap_or_else(|| "⋮".into());
+                let pre = (width.saturating_sub(marker.chars().count())) / 2;
+                *line = (once(' ').cycle().take(pre))
+                    .chain(marker.chars())
+                    .chain(once(' ').cycle())
+                    .take(width.max(1))
+                    .collect();
+            }
             // Outline the grid
             let grid_row_count = grid.len();
             if self.rank() == 0 && self.is_map() {
@@ -605,7 +773,17 @@ fn shape_row<T: ArrayValue>(shape: &[usize]) -> Vec<char> {
 const MAX_RANK: usize = 10;
 
 fn requires_summary<T: ArrayValue>(shape: &[usize]) -> bool {
-    shape.iter().product::<usize>() > T::summary_min_elems() || shape.len() > MAX_RANK
+    let settings = FormatSettings::get();
+    let shown: usize = (shape.iter().enumerate())
+        .map(|(i, &dim)| {
+            if i + 1 == shape.len() && T::compress_list_grid() {
+                dim
+            } else {
+                settings.elided(dim).map_or(dim, |n| 2 * n)
+            }
+        })
+        .product();
+    shown > T::summary_min_elems() || shape.len() > MAX_RANK
 }
 
 fn fmt_array<T: GridFmt + ArrayValue>(
@@ -613,6 +791,7 @@ fn fmt_array<T: GridFmt + ArrayValue>(
     data: &[T],
     params: GridFmtParams,
     metagrid: &mut Metagrid,
+    markers: &mut Vec<(usize, usize)>,
 ) {
     if data.is_empty() {
         metagrid.push(vec![vec![shape_row::<T>(shape)]]);
@@ -652,7 +831,17 @@ fn fmt_array<T: GridFmt + ArrayValue>(
                 .collect();
             row.push(vec![s.chars().collect()]);
         } else {
+            let elided = FormatSettings::get().elided(data.len());
             for (i, val) in data.iter().enumerate() {
+                if let Some(n) = elided {
+                    if i == n {
+                        let marker = format!(" ⋯ {} more ⋯", thousands(data.len() - 2 * n));
+                        row.push(vec![marker.chars().collect()]);
+                    }
+                    if (n..data.len() - n).contains(&i) {
+                        continue;
+                    }
+                }
                 let mut grid = val.fmt_grid(GridFmtParams {
                     boxed: false,
                     ..params
@@ -673,15 +862,35 @@ fn fmt_array<T: GridFmt + ArrayValue>(
     }
     let row_shape = &shape[1..];
     let cell_size = data.len() / cell_count;
-    let row_height: usize = row_shape.iter().rev().skip(1).product();
+    let settings = FormatSettings::get();
+    let row_height: usize = (row_shape.iter().rev().skip(1))
+        .map(|&dim| settings.elided(dim).map_or(dim, |n| 2 * n + 1))
+        .product();
     let max_height = if terminal_size().is_some() { 100 } else { 300 };
+    let elided = settings.elided(cell_count);
+    let push_separators = |metagrid: &mut Metagrid| {
+        for _ in 0..rank - 2 {
+            metagrid.push(vec![vec![vec![' ']]; metagrid.last().unwrap().len()]);
+        }
+    };
+    let mut shown = 0;
     for (i, cell) in data.chunks(cell_size).enumerate() {
-        if i > 0 && rank > 2 {
-            for _ in 0..rank - 2 {
+        if let Some(n) = elided {
+            if i == n {
+                push_separators(metagrid);
+                markers.push((metagrid.len(), cell_count - 2 * n));
                 metagrid.push(vec![vec![vec![' ']]; metagrid.last().unwrap().len()]);
             }
+            if (n..cell_count - n).contains(&i) {
+                continue;
+            }
         }
-        fmt_array(row_shape, cell, params, metagrid);
+        if i > 0 {
+            push_separators(metagrid);
+        }
+        let i = shown;
+        shown += 1;
+        fmt_array(row_shape, cell, params, metagrid, markers);
         if T::compress_list_grid() && rank == 2 {
             let (left, right) = T::grid_fmt_delims(false);
             for grid in metagrid.last_mut().unwrap() {
@@ -803,3 +1012,286 @@ fn pad_grid_min(width: usize, height: usize, grid: &mut Grid) {
         }
     }
 }
+
+/// The header and body cells of an array rendered as a table
+type TableCells = (Option<Vec<String>>, Vec<Vec<String>>);
+
+impl Value {
+    /// Render a rank-`0`, `1`, or `2` array as an HTML table
+    ///
+    /// The keys of map arrays are used as column headers, and their values as columns.
+    pub fn to_html_table(&self) -> Result<String, String> {
+        let (header, body) = self.table_cells()?;
+        let mut s = String::from("<table>\n");
+        let mut push_row = |row: &[String], tag: &str| {
+            s.push_str("  <tr>");
+            for cell in row {
+                let cell = html_escape(cell);
+                if cell.contains('\n') {
+                    s.push_str(&format!("<{tag}><pre>{cell}</pre></{tag}>"));
+                } else {
+                    s.push_str(&format!("<{tag}>{cell}</{tag}>"));
+                }
+            }
+            s.push_str("</tr>\n");
+        };
+        if let Some(header) = &header {
+            push_row(header, "th");
+        }
+        for row in &body {
+            push_row(row, "td");
+        }
+        s.push_str("</table>");
+        Ok(s)
+    }
+    /// Render a rank-`0`, `1`, or `2` array as a Markdown table
+    ///
+    /// The keys of map arrays are used as column headers, and their values as columns.
+    /// Markdown tables always have a header row, so it is left empty for other arrays.
+    pub fn to_markdown_table(&self) -> Result<String, String> {
+        let (header, body) = self.table_cells()?;
+        let width = (header.iter().chain(&body))
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let header = header.unwrap_or_else(|| vec![String::new(); width]);
+        let mut s = String::new();
+        let mut push_row = |row: &[String]| {
+            s.push('|');
+            for i in 0..width {
+                let cell = row.get(i).map(String::as_str).unwrap_or_default();
+                let cell = cell.replace('|', "\\|").replace('\n', "<br>");
+                s.push_str(&format!(" {cell} |"));
+            }
+            s.push('\n');
+        };
+        push_row(&header);
+        push_row(&vec!["---".into(); width]);
+        for row in &body {
+            push_row(row);
+        }
+        s.pop();
+        Ok(s)
+    }
+    fn table_cells(&self) -> Result<TableCells, String> {
+        if self.is_map() {
+            let mut header = Vec::new();
+            let mut columns = Vec::new();
+            for (k, v) in self.map_kv() {
+                header.push(table_cell(&k));
+                let v = v.unboxed();
+                columns.push(if v.rank() == 0 {
+                    vec![table_cell(&v)]
+                } else {
+                    v.rows().map(|row| table_cell(&row)).collect()
+                });
+            }
+            let height = columns.iter().map(Vec::len).max().unwrap_or(0);
+            let body = (0..height)
+                .map(|i| {
+                    (columns.iter())
+                        .map(|col| col.get(i).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            return Ok((Some(header), body));
+        }
+        let body = match self.rank() {
+            0 => vec![vec![table_cell(self)]],
+            1 => self
+                .rows()
+                .map(|row| match row.unboxed() {
+                    row if row.rank() == 0 || is_string(&row) => vec![table_cell(&row)],
+                    row => row.rows().map(|cell| table_cell(&cell)).collect(),
+                })
+                .collect(),
+            2 => self
+                .rows()
+                .map(|row| row.rows().map(|cell| table_cell(&cell)).collect())
+                .collect(),
+            n => return Err(format!("Cannot make a table from a rank-{n} array")),
+        };
+        Ok((None, body))
+    }
+}
+
+fn is_string(val: &Value) -> bool {
+    matches!(val, Value::Char(arr) if arr.rank() == 1)
+}
+
+fn table_cell(val: &Value) -> String {
+    match val {
+        Value::Box(arr) if arr.rank() == 0 => table_cell(&arr.data[0].0),
+        Value::Char(arr) if arr.rank() <= 1 => arr.data.iter().collect(),
+        val => val.show(),
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const NUMBER_COLOR: &str = "\x1b[38;2;235;136;68m";
+const STRING_COLOR: &str = "\x1b[36m";
+const DEFAULT_COLOR: &str = "\x1b[39m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const NORMAL_INTENSITY: &str = "\x1b[22m";
+
+/// Add ANSI color codes to a formatted grid
+///
+/// Numbers and characters are colored like they are in code,
+/// fill elements and borders are dimmed, and map keys are bold.
+pub(crate) fn color_grid_string(s: &str) -> String {
+    let mut colored = String::with_capacity(s.len() * 2);
+    for (i, line) in s.split('\n').enumerate() {
+        if i > 0 {
+            colored.push('\n');
+        }
+        color_grid_line(line, &mut colored);
+    }
+    colored
+}
+
+fn is_grid_border(c: char) -> bool {
+    "╭─╷╯╓╟╜→⋮⋯".contains(c)
+}
+
+fn is_grid_sep(c: char) -> bool {
+    c.is_whitespace() || "[]{}⟦⟧()".contains(c) || is_grid_border(c)
+}
+
+fn color_grid_line(line: &str, out: &mut String) {
+    let chars: Vec<char> = line.chars().collect();
+    let key_end = (line.find(" → ")).map(|end| line[..end].chars().count());
+    let key_start = key_end
+        .and_then(|_| (chars.iter()).position(|&c| !c.is_whitespace() && !is_grid_border(c)));
+    let push_colored = |out: &mut String, color: &str, s: &[char], reset: &str| {
+        out.push_str(color);
+        out.extend(s);
+        out.push_str(reset);
+    };
+    let mut i = 0;
+    let mut bold = false;
+    while i < chars.len() {
+        let in_key =
+            key_start.is_some_and(|start| start <= i) && key_end.is_some_and(|end| i < end);
+        if in_key != bold {
+            out.push_str(if in_key { BOLD } else { NORMAL_INTENSITY });
+            bold = in_key;
+        }
+        let c = chars[i];
+        let prev_is_sep = i == 0 || is_grid_sep(chars[i - 1]);
+        let mut end = i + 1;
+        if c == '"' || c == '⌜' {
+            // Quotes in strings are not escaped, so a string only ends at a quote before a separator
+            let close = if c == '"' { '"' } else { '⌟' };
+            while end < chars.len()
+                && !(chars[end] == close
+                    && (chars.get(end + 1)).map_or(true, |&c| c == close || is_grid_sep(c)))
+            {
+                end += 1;
+            }
+            end = (end + 1).min(chars.len());
+            push_colored(out, STRING_COLOR, &chars[i..end], DEFAULT_COLOR);
+        } else if c == '@' && end < chars.len() {
+            end += 1;
+            if chars[i + 1] == '\\' {
+                while end < chars.len() && !is_grid_sep(chars[end]) {
+                    end += 1;
+                }
+            }
+            push_colored(out, STRING_COLOR, &chars[i..end], DEFAULT_COLOR);
+        } else if c.is_ascii_digit() || prev_is_sep && "¯∞πτη~ℂ".contains(c) {
+            while end < chars.len()
+                && (chars[end].is_ascii_digit() || ".e¯∞πτη/i+…".contains(chars[end]))
+            {
+                end += 1;
+            }
+            push_colored(out, NUMBER_COLOR, &chars[i..end], DEFAULT_COLOR);
+        } else if c.is_alphabetic() {
+            while end < chars.len() && chars[end].is_alphanumeric() {
+                end += 1;
+            }
+            let word = &chars[i..end];
+            if word == ['N', 'a', 'N'] {
+                push_colored(out, NUMBER_COLOR, word, DEFAULT_COLOR);
+            } else if !in_key
+                && word == ['W']
+                && prev_is_sep
+                && chars.get(end).map_or(true, |&c| is_grid_sep(c))
+            {
+                push_colored(out, DIM, word, NORMAL_INTENSITY);
+            } else {
+                out.extend(word);
+            }
+        } else if !in_key && is_grid_border(c) {
+            while end < chars.len() && is_grid_border(chars[end]) {
+                end += 1;
+            }
+            push_colored(out, DIM, &chars[i..end], NORMAL_INTENSITY);
+        } else if !in_key
+            && "_⊥".contains(c)
+            && prev_is_sep
+            && chars.get(end).map_or(true, |&c| is_grid_sep(c))
+        {
+            push_colored(out, DIM, &chars[i..end], NORMAL_INTENSITY);
+        } else {
+            out.push(c);
+        }
+        i = end;
+    }
+    if bold {
+        out.push_str(NORMAL_INTENSITY);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn grid_fmt_snapshots() {
+    use crate::{test_runner::update_snapshots, Compiler, TestRunner};
+    let report = TestRunner::new(Compiler::new())
+        .update_snapshots(update_snapshots())
+        .run_file("tests_special/grid_fmt.ua")
+        .unwrap();
+    for test in &report.tests {
+        if let Some(error) = &test.error {
+            panic!("{}", error.report());
+        }
+    }
+    assert!(report.tests.iter().all(|t| t.snapshot.is_some()));
+}
+
+#[cfg(test)]
+#[test]
+fn table_export() {
+    use crate::{Uiua, Value};
+    let mut env = Uiua::with_safe_sys();
+    env.run_str("map {\"Name\" \"Age\"} {{\"Ann\" \"<Bob>\"} [31 27]}")
+        .unwrap();
+    let table = env.pop(1).unwrap();
+    assert_eq!(
+        table.to_markdown_table().unwrap(),
+        "| Name | Age |\n| --- | --- |\n| Ann | 31 |\n| <Bob> | 27 |"
+    );
+    assert_eq!(
+        table.to_html_table().unwrap(),
+        "<table>\n  <tr><th>Name</th><th>Age</th></tr>\n  \
+        <tr><td>Ann</td><td>31</td></tr>\n  \
+        <tr><td>&lt;Bob&gt;</td><td>27</td></tr>\n</table>"
+    );
+    assert!(Value::from(0).to_html_table().is_ok());
+    env.run_str("°△2_2_2").unwrap();
+    assert!(env.pop(1).unwrap().to_markdown_table().is_err());
+}
@@ -0,0 +1,335 @@
+//! Compiling hot functions to native code
+//!
+//! Functions are interpreted until they have been called [`THRESHOLD`] times.
+//! Then, if their bodies only do scalar math and stack manipulation,
+//! they are compiled with Cranelift.
+//! Calls to compiled functions whose arguments are all plain numbers run the native code.
+//! All other calls fall back to the interpreter.
+//!
+//! Each runtime owns the code it compiles, which is freed when the runtime is dropped.
+
+use std::{collections::HashMap, mem::transmute};
+
+use cranelift_codegen::{
+    ir::{types::F64, AbiParam, FuncRef, InstBuilder, MemFlags},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+use parking_lot::Mutex;
+
+use crate::{algorithm::pervade, Function, Node, Primitive, Signature, Uiua, Value};
+
+/// The number of calls after which a function is compiled
+const THRESHOLD: u32 = 100;
+/// The maximum number of arguments or outputs of a compiled function
+const MAX_VALUES: usize = 16;
+
+/// A compiled function
+///
+/// It reads its arguments from the start of the buffer and writes its outputs over them.
+pub(crate) type NativeFn = unsafe extern "C" fn(*mut f64);
+
+fn new_module() -> Option<JITModule> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").ok()?;
+    let isa = (cranelift_native::builder().ok()?)
+        .finish(settings::Flags::new(flags))
+        .ok()?;
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbol("uiua_min", min as *const u8);
+    builder.symbol("uiua_max", max as *const u8);
+    Some(JITModule::new(builder))
+}
+
+extern "C" fn min(a: f64, b: f64) -> f64 {
+    pervade::min::num_num(a, b)
+}
+
+extern "C" fn max(a: f64, b: f64) -> f64 {
+    pervade::max::num_num(a, b)
+}
+
+/// The functions a runtime has called, and whether they have been compiled
+#[derive(Default)]
+pub(crate) struct Jit {
+    functions: HashMap<Function, Tier>,
+    /// Holds the compiled code
+    ///
+    /// Code is only freed when the `Jit` is dropped,
+    /// so functions stay valid after they are removed from `functions`.
+    /// It is only locked to make the runtime [`Sync`].
+    module: Option<Mutex<JITModule>>,
+}
+
+/// Compiled code is not shared, so a clone starts over
+impl Clone for Jit {
+    fn clone(&self) -> Self {
+        Jit::default()
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            self.functions.clear();
+            // SAFETY: Pointers to the module's functions are only kept in `functions`
+            unsafe { module.into_inner().free_memory() };
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Tier {
+    /// The function has been interpreted this many times
+    Counting(u32),
+    Native(NativeFn),
+    /// The function cannot be compiled
+    Interpreted,
+}
+
+impl Jit {
+    /// Forget all compiled functions, for when function bodies change
+    pub fn clear(&mut self) {
+        self.functions.clear();
+    }
+    /// The number of functions that have been compiled
+    #[cfg(test)]
+    pub fn native_count(&self) -> usize {
+        (self.functions.values())
+            .filter(|tier| matches!(tier, Tier::Native(_)))
+            .count()
+    }
+}
+
+impl Uiua {
+    /// Get the native code for a function if it is hot and can be compiled
+    pub(crate) fn native_code(&mut self, f: &Function) -> Option<NativeFn> {
+        let jit = &mut self.rt.jit;
+        let tier = jit.functions.entry(f.clone()).or_insert(Tier::Counting(0));
+        match tier {
+            Tier::Native(native) => Some(*native),
+            Tier::Interpreted => None,
+            Tier::Counting(calls) if *calls + 1 < THRESHOLD => {
+                *calls += 1;
+                None
+            }
+            Tier::Counting(_) => {
+                let module = match &mut jit.module {
+                    Some(module) => Some(module),
+                    None => new_module().map(|module| jit.module.insert(Mutex::new(module))),
+                };
+                let native =
+                    module.and_then(|module| compile(module.get_mut(), &self.asm[f], f.sig));
+                *tier = native.map_or(Tier::Interpreted, Tier::Native);
+                native
+            }
+        }
+    }
+    /// Run a function's native code if its arguments are plain numbers
+    ///
+    /// Returns whether the code was run
+    pub(crate) fn run_native(&mut self, native: NativeFn, sig: Signature) -> bool {
+        let Signature { args, outputs } = sig;
+        let Some(bottom) = self.rt.stack.len().checked_sub(args) else {
+            return false;
+        };
+        let mut buffer = [0.0; MAX_VALUES];
+        for (n, value) in buffer.iter_mut().zip(&self.rt.stack[bottom..]) {
+            match value {
+                Value::Num(arr) if arr.rank() == 0 && arr.meta().is_default() => *n = arr.data[0],
+                _ => return false,
+            }
+        }
+        // SAFETY: The function only accesses the buffer, which fits all of its arguments and outputs
+        unsafe { native(buffer.as_mut_ptr()) };
+        self.rt.stack.truncate(bottom);
+        (self.rt.stack).extend(buffer[..outputs].iter().map(|&n| Value::from(n)));
+        true
+    }
+}
+
+/// Compile a function body, if it only uses scalar math and stack manipulation
+fn compile(module: &mut JITModule, node: &Node, sig: Signature) -> Option<NativeFn> {
+    if sig.args > MAX_VALUES || sig.outputs > MAX_VALUES {
+        return None;
+    }
+    let mut ctx = module.make_context();
+    let ptr_type = module.target_config().pointer_type();
+    ctx.func.signature.params.push(AbiParam::new(ptr_type));
+    let mut extremum_sig = module.make_signature();
+    extremum_sig.params = vec![AbiParam::new(F64); 2];
+    extremum_sig.returns = vec![AbiParam::new(F64)];
+    let min = (module.declare_function("uiua_min", Linkage::Import, &extremum_sig)).ok()?;
+    let max = (module.declare_function("uiua_max", Linkage::Import, &extremum_sig)).ok()?;
+    let min = module.declare_func_in_func(min, &mut ctx.func);
+    let max = module.declare_func_in_func(max, &mut ctx.func);
+
+    let mut fn_ctx = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_ctx);
+    let block = builder.create_block();
+    builder.append_block_params_for_function_params(block);
+    builder.switch_to_block(block);
+    builder.seal_block(block);
+    let buffer = builder.block_params(block)[0];
+    let flags = MemFlags::trusted();
+    let stack = (0..sig.args)
+        .map(|i| (builder.ins()).load(F64, flags, buffer, 8 * i as i32))
+        .collect();
+    let mut emitter = Emitter {
+        builder,
+        stack,
+        min,
+        max,
+    };
+    if !emitter.node(node) || emitter.stack.len() != sig.outputs {
+        return None;
+    }
+    let Emitter {
+        mut builder, stack, ..
+    } = emitter;
+    for (i, val) in stack.into_iter().enumerate() {
+        (builder.ins()).store(flags, val, buffer, 8 * i as i32);
+    }
+    builder.ins().return_(&[]);
+    builder.finalize();
+
+    let id = module
+        .declare_anonymous_function(&ctx.func.signature)
+        .ok()?;
+    module.define_function(id, &mut ctx).ok()?;
+    module.finalize_definitions().ok()?;
+    let code = module.get_finalized_function(id);
+    // SAFETY: The function was defined with this signature
+    Some(unsafe { transmute::<*const u8, NativeFn>(code) })
+}
+
+/// Emits instructions for nodes, keeping track of the stack at compile time
+struct Emitter<'a> {
+    builder: FunctionBuilder<'a>,
+    stack: Vec<cranelift_codegen::ir::Value>,
+    min: FuncRef,
+    max: FuncRef,
+}
+
+impl Emitter<'_> {
+    /// Returns whether the node could be compiled
+    fn node(&mut self, node: &Node) -> bool {
+        use Primitive::*;
+        match node {
+            Node::Run(nodes) => nodes.iter().all(|node| self.node(node)),
+            Node::Push(Value::Num(arr)) if arr.rank() == 0 && arr.meta().is_default() => {
+                let n = self.builder.ins().f64const(arr.data[0]);
+                self.stack.push(n);
+                true
+            }
+            Node::Prim(prim, _) => self.prim(*prim),
+            Node::Mod(Fork, args, _) => {
+                let [f, g] = args.as_slice() else {
+                    return false;
+                };
+                let n = f.sig.args.max(g.sig.args);
+                let Some(bottom) = self.stack.len().checked_sub(n) else {
+                    return false;
+                };
+                let vals = self.stack.split_off(bottom);
+                self.stack.extend_from_slice(&vals[n - g.sig.args..]);
+                if !self.node(&g.node) {
+                    return false;
+                }
+                self.stack.extend_from_slice(&vals[n - f.sig.args..]);
+                self.node(&f.node)
+            }
+            Node::Mod(prim, args, _) => {
+                let [f] = args.as_slice() else {
+                    return false;
+                };
+                match prim {
+                    Dip => {
+                        let Some(val) = self.stack.pop() else {
+                            return false;
+                        };
+                        let compiled = self.node(&f.node);
+                        self.stack.push(val);
+                        compiled
+                    }
+                    Gap => self.stack.pop().is_some() && self.node(&f.node),
+                    On => {
+                        let Some(&val) = self.stack.last() else {
+                            return false;
+                        };
+                        let compiled = self.node(&f.node);
+                        self.stack.push(val);
+                        compiled
+                    }
+                    By => {
+                        let Some(i) = self.stack.len().checked_sub(f.sig.args.max(1)) else {
+                            return false;
+                        };
+                        self.stack.insert(i, self.stack[i]);
+                        self.node(&f.node)
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+    fn prim(&mut self, prim: Primitive) -> bool {
+        use Primitive::*;
+        let height = self.stack.len();
+        let ins = self.builder.ins();
+        match prim {
+            Identity => return height >= 1,
+            Dup if height >= 1 => self.stack.push(self.stack[height - 1]),
+            Flip if height >= 2 => self.stack.swap(height - 1, height - 2),
+            Over if height >= 2 => self.stack.push(self.stack[height - 2]),
+            Around if height >= 2 => self.stack.insert(height - 2, self.stack[height - 1]),
+            Pop if height >= 1 => _ = self.stack.pop(),
+            Neg | Abs | Sqrt | Floor | Ceil if height >= 1 => {
+                let a = self.stack[height - 1];
+                self.stack[height - 1] = match prim {
+                    Neg => ins.fneg(a),
+                    Abs => ins.fabs(a),
+                    Sqrt => ins.sqrt(a),
+                    Floor => ins.floor(a),
+                    _ => ins.ceil(a),
+                };
+            }
+            Add | Sub | Mul | Div | Min | Max if height >= 2 => {
+                let a = self.stack.pop().unwrap();
+                let b = self.stack.pop().unwrap();
+                let res = match prim {
+                    Add => ins.fadd(b, a),
+                    Sub => ins.fsub(b, a),
+                    Mul => ins.fmul(b, a),
+                    Div => ins.fdiv(b, a),
+                    _ => {
+                        let f = if prim == Min { self.min } else { self.max };
+                        let call = ins.call(f, &[a, b]);
+                        self.builder.inst_results(call)[0]
+                    }
+                };
+                self.stack.push(res);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn jit_tier() {
+    // Random numbers keep the loops from being evaluated at compile time
+    let code = "F ← +×2⊙(÷3-1)\nG ← ⊂1\n⍥(F 1.5)200 ×0⚂\nF 1 [1 2]\n⍥G 200 ▽0⚂";
+    let mut env = Uiua::with_safe_sys();
+    env.run_str(code).unwrap();
+    assert_eq!(env.rt.jit.native_count(), 1);
+    // Operation budgets see every instruction, so nothing is compiled
+    let mut interpreted = Uiua::with_safe_sys().with_op_budget(usize::MAX);
+    interpreted.run_str(code).unwrap();
+    assert_eq!(interpreted.rt.jit.native_count(), 0);
+    assert_eq!(env.take_stack(), interpreted.take_stack());
+}
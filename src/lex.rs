@@ -584,6 +584,7 @@ pub enum Token {
     Simple(AsciiToken),
     Glyph(Primitive),
     Placeholder(usize),
+    NamedPlaceholder(Ident),
     Subscr(Subscript),
     LeftArrow,
     LeftStrokeArrow,
@@ -651,6 +652,12 @@ impl Token {
             _ => None,
         }
     }
+    pub(crate) fn as_named_placeholder(&self) -> Option<Ident> {
+        match self {
+            Token::NamedPlaceholder(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
     pub(crate) fn as_semantic_comment(&self) -> Option<SemanticComment> {
         match self {
             Token::SemanticComment(sc) => Some(sc.clone()),
@@ -713,6 +720,7 @@ impl fmt::Display for Token {
             Token::OpenModule => write!(f, "┌─╴"),
             Token::CloseModule => write!(f, "└─╴"),
             Token::Placeholder(i) => write!(f, "^{i}"),
+            Token::NamedPlaceholder(name) => write!(f, "^{name}"),
         }
     }
 }
@@ -804,6 +812,18 @@ pub enum SemanticComment {
     TrackCaller,
     /// Mark a function as deprecated
     Deprecated(EcoString),
+    /// Check for type and shape errors at compile time
+    TypeCheck,
+    /// Mark a test block as expected to fail
+    ShouldFail,
+    /// Compare a test block's resulting stack to a stored snapshot
+    Snapshot,
+    /// Only compile the next item for the given targets
+    Cfg(EcoString),
+    /// Suppress the given diagnostic kinds
+    Allow(EcoString),
+    /// Turn the given diagnostic kinds into errors
+    Deny(EcoString),
     #[doc(hidden)]
     Boo,
 }
@@ -818,6 +838,12 @@ impl fmt::Display for SemanticComment {
             SemanticComment::TrackCaller => write!(f, "# Track caller!"),
             SemanticComment::Deprecated(s) if s.is_empty() => write!(f, "# Deprecated!"),
             SemanticComment::Deprecated(s) => write!(f, "# Deprecated! {s}"),
+            SemanticComment::TypeCheck => write!(f, "# Type check!"),
+            SemanticComment::ShouldFail => write!(f, "# Should fail!"),
+            SemanticComment::Snapshot => write!(f, "# Snapshot!"),
+            SemanticComment::Cfg(s) => write!(f, "# Cfg! {s}"),
+            SemanticComment::Allow(s) => write!(f, "# Allow! {s}"),
+            SemanticComment::Deny(s) => write!(f, "# Deny! {s}"),
             SemanticComment::Boo => write!(f, "# Boo!"),
         }
     }
@@ -1038,8 +1064,21 @@ impl<'a> Lexer<'a> {
                 "*" => self.end(Star, start),
                 "%" => self.end(Percent, start),
                 "^" => {
+                    let after_arrow = (self.tokens.back()).is_some_and(|tok| {
+                        matches!(tok.value, LeftArrow | LeftArrowTilde | LeftStrokeArrow)
+                    });
                     if let Some(x) = self.next_char_if(|c| c.chars().all(|c| c.is_ascii_digit())) {
                         self.end(Placeholder(x.parse().unwrap()), start)
+                    } else if let Some(c) = (!after_arrow)
+                        .then(|| self.next_char_if_all(is_ident_start))
+                        .flatten()
+                    {
+                        // Named placeholder
+                        let mut name = c.to_string();
+                        while let Some(c) = self.next_char_if_all(is_ident_char) {
+                            name.push_str(c);
+                        }
+                        self.end(NamedPlaceholder(name.into()), start)
                     } else {
                         self.end(Caret, start)
                     }
@@ -1088,10 +1127,19 @@ impl<'a> Lexer<'a> {
                             "Experimental!" => self.end(Experimental, start),
                             "No inline!" => self.end(NoInline, start),
                             "Track caller!" => self.end(TrackCaller, start),
+                            "Type check!" => self.end(TypeCheck, start),
+                            "Should fail!" => self.end(ShouldFail, start),
+                            "Snapshot!" => self.end(Snapshot, start),
                             "Boo!" => self.end(Boo, start),
                             s => {
                                 if let Some(suf) = s.strip_prefix("Deprecated!") {
                                     self.end(Deprecated(suf.trim().into()), start);
+                                } else if let Some(suf) = s.strip_prefix("Cfg!") {
+                                    self.end(Cfg(suf.trim().into()), start);
+                                } else if let Some(suf) = s.strip_prefix("Allow!") {
+                                    self.end(Allow(suf.trim().into()), start);
+                                } else if let Some(suf) = s.strip_prefix("Deny!") {
+                                    self.end(Deny(suf.trim().into()), start);
                                 } else {
                                     self.end(Comment, start);
                                 }
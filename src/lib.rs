@@ -147,31 +147,45 @@ mod algorithm;
 mod array;
 mod assembly;
 pub mod ast;
+mod bench_runner;
 mod boxed;
 mod check;
 mod compile;
 mod complex;
 mod cowslice;
+#[cfg(feature = "dap")]
+pub mod dap;
+pub mod de;
+pub mod docgen;
 mod error;
 mod ffi;
 mod fill;
 pub mod format;
 mod function;
 mod grid_fmt;
+#[cfg(feature = "jit")]
+mod jit;
 mod lex;
 pub mod lsp;
+mod memo_cache;
+#[cfg(feature = "native_sys")]
+pub mod package;
 mod parse;
 mod primitive;
 #[doc(hidden)]
 pub mod profile;
+mod profiler;
 mod run;
+pub mod ser;
 mod shape;
 #[cfg(feature = "stand")]
 #[doc(hidden)]
 pub mod stand;
 mod sys;
+mod test_runner;
 mod tree;
 mod types;
+mod uasm;
 mod value;
 #[cfg(feature = "window")]
 #[doc(hidden)]
@@ -182,21 +196,28 @@ pub use self::{
     algorithm::encode,
     array::*,
     assembly::*,
+    bench_runner::{BenchBaseline, BenchReport, BenchResult, BenchRunner, BenchStats},
     boxed::*,
+    check::{SigCheckError, SigCheckErrorKind, StackHeights},
     compile::*,
     complex::*,
     error::*,
     ffi::*,
     function::*,
+    grid_fmt::FormatSettings,
     lex::is_ident_char,
     lex::*,
     lsp::{SpanKind, Spans},
+    memo_cache::MemoCache,
     parse::{ident_modifier_args, parse, ParseError},
     primitive::*,
+    profiler::{AllocProfile, AllocStats, Profile},
     run::*,
     shape::*,
     sys::*,
+    test_runner::{Snapshot, SnapshotStatus, TestOutcome, TestReport, TestResult, TestRunner},
     tree::*,
+    uasm::{AssemblyLoadError, ASSEMBLY_FORMAT_VERSION},
     value::*,
 };
 
@@ -207,6 +228,18 @@ use ecow::EcoString;
 /// The Uiua version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The target the interpreter was built for, either `native` or `web`
+///
+/// Items preceded by a `# Cfg!` semantic comment are only compiled for the targets it lists.
+pub const TARGET: &str = if cfg!(target_arch = "wasm32") {
+    "web"
+} else {
+    "native"
+};
+
+/// All targets that can be named in a `# Cfg!` semantic comment
+pub const TARGETS: [&str; 2] = ["native", "web"];
+
 /// A Uiua identifier
 pub type Ident = EcoString;
 
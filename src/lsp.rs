@@ -7,6 +7,7 @@ use std::{
     fmt,
     path::PathBuf,
     slice,
+    time::Duration,
 };
 
 use crate::{
@@ -38,7 +39,7 @@ pub enum SpanKind {
     Label,
     Signature,
     Whitespace,
-    Placeholder(usize),
+    Placeholder(Option<usize>),
     Delimiter,
     FuncDelim(Signature, SetInverses),
     MacroDelim(usize),
@@ -172,6 +173,8 @@ pub struct CodeMeta {
     pub top_level_names: HashMap<Ident, LocalName>,
     /// A map of the spans of top-level lines to values
     pub top_level_values: HashMap<CodeSpan, Vec<Value>>,
+    /// A map of the spans of top-level lines to the stack height after them
+    pub top_level_heights: BTreeMap<CodeSpan, usize>,
     /// A map of strand spans
     pub strands: BTreeMap<CodeSpan, Vec<CodeSpan>>,
     /// A map of inner array spans
@@ -182,6 +185,8 @@ pub struct CodeMeta {
     pub import_srcs: HashMap<CodeSpan, ImportSrc>,
     /// A map of obverse spans to their set inverses
     pub obverses: HashMap<CodeSpan, SetInverses>,
+    /// The spans of references to deprecated bindings
+    pub deprecated_refs: HashSet<CodeSpan>,
 }
 
 /// Data for the signature of a function
@@ -276,6 +281,8 @@ impl Spanner {
     fn new(src: InputSrc, input: &str, backend: impl SysBackend) -> Self {
         let mut compiler = Compiler::with_backend(backend);
         compiler.pre_eval_mode(PreEvalMode::Lsp);
+        // Keep slow compile-time code from stalling the editor
+        compiler.comptime_limit(Some(Duration::from_secs(5)));
         let errors = match compiler.load_str_src(input, src.clone()) {
             Ok(_) => Vec::new(),
             Err(e) => e.into_multi(),
@@ -306,7 +313,7 @@ impl Spanner {
                                 original: true,
                             }));
                         }
-                        ModuleKind::Test => {}
+                        ModuleKind::Test(_) | ModuleKind::Bench(_) => {}
                     }
                     if let Some(line) = &m.value.imports {
                         spans.push(line.tilde_span.clone().sp(SpanKind::Delimiter));
@@ -694,7 +701,10 @@ impl Spanner {
                     spans.push(word.span.clone().sp(SpanKind::OutputComment))
                 }
                 Word::Placeholder(op) => {
-                    spans.push(word.span.clone().sp(SpanKind::Placeholder(*op)))
+                    spans.push(word.span.clone().sp(SpanKind::Placeholder(Some(*op))))
+                }
+                Word::NamedPlaceholder(_) => {
+                    spans.push(word.span.clone().sp(SpanKind::Placeholder(None)))
                 }
                 #[allow(clippy::match_single_binding)]
                 Word::Subscripted(sub) => {
@@ -806,7 +816,14 @@ pub use server::run_language_server;
 
 #[cfg(feature = "lsp")]
 mod server {
-    use std::{char::decode_utf16, env::current_dir, path::Path, sync::Arc};
+    use std::{
+        char::decode_utf16,
+        env::{current_dir, set_current_dir},
+        fs,
+        iter::once,
+        path::Path,
+        sync::Arc,
+    };
 
     use dashmap::DashMap;
     use tower_lsp::{
@@ -821,9 +838,10 @@ mod server {
     use super::*;
 
     use crate::{
-        format::{format_str, FormatConfig},
+        format::{format_edits, format_range, FormatConfig, FormatEdit},
         is_ident_char,
         lex::{lex, Loc},
+        package::MODULES_DIR,
         primitive::{PrimClass, PrimDocFragment},
         subscript, AsciiToken, Assembly, BindingInfo, NativeSys, PrimDocLine, Span, Token,
         UiuaErrorKind,
@@ -881,6 +899,7 @@ mod server {
                 let (service, socket) = LspService::new(|client| Backend {
                     client,
                     docs: DashMap::new(),
+                    index: Arc::new(DashMap::new()),
                 });
                 Server::new(stdin, stdout, socket)
                     .concurrency_level(1)
@@ -892,6 +911,20 @@ mod server {
     struct Backend {
         client: Client,
         docs: DashMap<Url, Arc<LspDoc>>,
+        index: Arc<WorkspaceIndex>,
+    }
+
+    /// The bindings defined in each file of the workspace, keyed by canonical path
+    ///
+    /// This includes files that are not open, as well as git modules in the modules directory.
+    type WorkspaceIndex = DashMap<PathBuf, Vec<IndexedBinding>>;
+
+    struct IndexedBinding {
+        name: String,
+        kind: SymbolKind,
+        public: bool,
+        range: Range,
+        comment: Option<String>,
     }
 
     const UIUA_NUMBER_STT: SemanticTokenType = SemanticTokenType::new("uiua_number");
@@ -944,8 +977,20 @@ mod server {
 
     #[tower_lsp::async_trait]
     impl LanguageServer for Backend {
-        async fn initialize(&self, _params: InitializeParams) -> Result<InitializeResult> {
+        async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
             self.debug("Initializing Uiua language server").await;
+            #[allow(deprecated)]
+            let mut roots: Vec<PathBuf> = (params.workspace_folders.iter().flatten())
+                .map(|folder| uri_path(&folder.uri))
+                .chain(params.root_uri.as_ref().map(uri_path))
+                .collect();
+            roots.dedup();
+            // Git modules are cloned relative to the current directory
+            if let Some(root) = roots.first() {
+                _ = set_current_dir(root);
+            }
+            let index = Arc::clone(&self.index);
+            tokio::task::spawn_blocking(move || index_workspace(&index, &roots));
             // self.client
             //     .log_message(
             //         MessageType::INFO,
@@ -964,6 +1009,7 @@ mod server {
                         ..Default::default()
                     }),
                     document_formatting_provider: Some(OneOf::Left(true)),
+                    document_range_formatting_provider: Some(OneOf::Left(true)),
                     document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
                         first_trigger_character: ' '.to_string(),
                         more_trigger_character: Some(
@@ -982,7 +1028,7 @@ mod server {
                                         .into_iter()
                                         .chain(GENERIC_SEMANTIC_TOKEN_TYPES)
                                         .collect(),
-                                    token_modifiers: vec![],
+                                    token_modifiers: vec![SemanticTokenModifier::DEPRECATED],
                                 },
                                 range: Some(false),
                                 full: Some(SemanticTokensFullOptions::Bool(true)),
@@ -993,6 +1039,7 @@ mod server {
                     definition_provider: Some(OneOf::Left(true)),
                     declaration_provider: Some(DeclarationCapability::Simple(true)),
                     references_provider: Some(OneOf::Left(true)),
+                    workspace_symbol_provider: Some(OneOf::Left(true)),
                     diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
                         DiagnosticOptions {
                             inter_file_dependencies: true,
@@ -1014,15 +1061,15 @@ mod server {
 
         async fn did_open(&self, params: DidOpenTextDocumentParams) {
             let path = uri_path(&params.text_document.uri);
-            self.docs.insert(
-                params.text_document.uri,
-                LspDoc::new(&path, params.text_document.text).into(),
-            );
+            let doc = LspDoc::new(&path, params.text_document.text);
+            index_doc(&self.index, &doc, &path);
+            self.docs.insert(params.text_document.uri, doc.into());
         }
 
         async fn did_change(&self, params: DidChangeTextDocumentParams) {
             let path = uri_path(&params.text_document.uri);
             let doc = LspDoc::new(&path, params.content_changes[0].text.clone());
+            index_doc(&self.index, &doc, &path);
             self.docs.insert(params.text_document.uri, doc.into());
         }
 
@@ -1144,6 +1191,13 @@ mod server {
                         }
                         _ => {}
                     }
+                    if let Some(suggestion) = &docs.meta.deprecation {
+                        value.push_str("\n\n**Deprecated**");
+                        if !suggestion.is_empty() {
+                            value.push_str(": ");
+                            value.push_str(suggestion);
+                        }
+                    }
                     if let Some(comment) = &docs.meta.comment {
                         value.push_str("\n\n");
                         if let Some(sig) = &comment.sig {
@@ -1205,13 +1259,26 @@ mod server {
                 if span.contains_line_col(line, col) && span.src == path {
                     match src {
                         ImportSrc::Git(url) => {
+                            let mut value = format!("[View Git repository]({url})");
+                            let lib =
+                                git_module_path(url).and_then(|path| path.canonicalize().ok());
+                            if let Some(bindings) = lib.and_then(|lib| self.index.get(&lib)) {
+                                for binding in bindings.iter().filter(|b| b.public) {
+                                    value.push_str(&format!("\n- `{}`", binding.name));
+                                    let summary = (binding.comment.as_deref())
+                                        .and_then(|comment| comment.lines().next());
+                                    if let Some(summary) = summary {
+                                        value.push_str(&format!(" - {summary}"));
+                                    }
+                                }
+                            }
                             return Ok(Some(Hover {
                                 contents: HoverContents::Markup(MarkupContent {
                                     kind: MarkupKind::Markdown,
-                                    value: format!("[View Git repository]({url})"),
+                                    value,
                                 }),
                                 range: Some(uiua_span_to_lsp(span, &doc.asm.inputs)),
-                            }))
+                            }));
                         }
                         ImportSrc::File(_) => {}
                     };
@@ -1259,6 +1326,8 @@ mod server {
                         range: uiua_span_to_lsp(span, &doc.asm.inputs),
                         new_text: name,
                     })),
+                    tags: (binding.meta.deprecation.is_some())
+                        .then(|| vec![CompletionItemTag::DEPRECATED]),
                     ..Default::default()
                 }
             }
@@ -1456,14 +1525,32 @@ mod server {
             } else {
                 return Ok(None);
             };
-            match format_str(&doc.input, &FormatConfig::find().unwrap_or_default()) {
-                Ok(formatted) => {
-                    let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX));
-                    Ok(Some(vec![TextEdit {
-                        range,
-                        new_text: formatted.output,
-                    }]))
+            match format_edits(&doc.input, &FormatConfig::find().unwrap_or_default()) {
+                Ok(edits) => Ok(Some(format_text_edits(edits, &doc.input))),
+                Err(e) => {
+                    let mut error = Error::parse_error();
+                    error.message = e.to_string().into();
+                    Err(error)
                 }
+            }
+        }
+
+        async fn range_formatting(
+            &self,
+            params: DocumentRangeFormattingParams,
+        ) -> Result<Option<Vec<TextEdit>>> {
+            let Some(doc) = self.doc(&params.text_document.uri) else {
+                return Ok(None);
+            };
+            let (Some(start), Some(end)) = (
+                lsp_pos_to_byte(params.range.start, &doc.input),
+                lsp_pos_to_byte(params.range.end, &doc.input),
+            ) else {
+                return Ok(None);
+            };
+            let config = FormatConfig::find().unwrap_or_default();
+            match format_range(&doc.input, start..end, &config) {
+                Ok(edits) => Ok(Some(format_text_edits(edits, &doc.input))),
                 Err(e) => {
                     let mut error = Error::parse_error();
                     error.message = e.to_string().into();
@@ -1708,12 +1795,14 @@ mod server {
                     start.character
                 };
                 let length = span.as_str(&doc.asm.inputs, |s| s.encode_utf16().count()) as u32;
+                let deprecated = matches!(&sp.value, SpanKind::Ident { docs: Some(docs), .. }
+                    if docs.meta.deprecation.is_some());
                 let token = SemanticToken {
                     delta_line: start.line - prev_line,
                     delta_start,
                     length,
                     token_type,
-                    token_modifiers_bitset: 0,
+                    token_modifiers_bitset: deprecated as u32,
                 };
                 tokens.push(token);
                 prev_line = start.line;
@@ -1897,6 +1986,15 @@ mod server {
                 }));
             }
 
+            // Extract to binding
+            if params.range.start != params.range.end {
+                if let Some(action) =
+                    extract_to_binding(&doc, &params.text_document.uri, params.range)
+                {
+                    actions.push(CodeActionOrCommand::CodeAction(action));
+                }
+            }
+
             // Add experimental
             if !doc.input.contains("# Experimental!") {
                 for error in &doc.errors {
@@ -1938,64 +2036,31 @@ mod server {
         }
 
         async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-            let Some(doc) = (self.docs).get(&params.text_document_position.text_document.uri)
-            else {
+            let uri = &params.text_document_position.text_document.uri;
+            let Some(doc) = self.doc(uri) else {
                 return Ok(None);
             };
-            let (line, col) = lsp_pos_to_uiua(params.text_document_position.position, &doc.input);
-            let path = uri_path(&params.text_document_position.text_document.uri);
-            let mut binding: Option<(&BindingInfo, usize)> = None;
-            // Check for span in bindings
-            for (i, gb) in doc.asm.bindings.iter().enumerate() {
-                if gb.span.contains_line_col(line, col) && gb.span.src == path {
-                    binding = Some((gb, i));
-                    break;
-                }
-            }
-            // Check for span in binding references
-            if binding.is_none() {
-                for (name_span, index) in &doc.code_meta.global_references {
-                    if name_span.contains_line_col(line, col) && name_span.src == path {
-                        binding = Some((&doc.asm.bindings[*index], *index));
-                        break;
-                    }
-                }
+            let new_name = params.new_name.trim_end_matches('!');
+            if new_name.is_empty() || !new_name.chars().all(is_ident_char) {
+                return Err(Error::invalid_params(format!(
+                    "{:?} is not a valid binding name",
+                    params.new_name
+                )));
             }
-            let Some((binding, index)) = binding else {
+            let (line, col) = lsp_pos_to_uiua(params.text_document_position.position, &doc.input);
+            let Some(binding) = binding_at(&doc, &uri_path(uri), line, col) else {
                 return Ok(None);
             };
             // Collect edits
             let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
-            changes.insert(
-                match &binding.span.src {
-                    InputSrc::Str(_) | InputSrc::Macro(_) => {
-                        params.text_document_position.text_document.uri.clone()
-                    }
-                    InputSrc::File(file) => path_to_uri(file)?,
-                },
-                vec![TextEdit {
-                    range: uiua_span_to_lsp(&binding.span, &doc.asm.inputs),
+            for location in self.binding_references(uri, &doc, binding, true)? {
+                changes.entry(location.uri).or_default().push(TextEdit {
+                    range: location.range,
                     new_text: params.new_name.clone(),
-                }],
-            );
-            for entry in &self.docs {
-                let uri = entry.key();
-                let doc = entry.value();
-                for (name_span, idx) in &doc.code_meta.global_references {
-                    if *idx == index {
-                        let uri = match &name_span.src {
-                            InputSrc::Str(_) | InputSrc::Macro(_) => uri.clone(),
-                            InputSrc::File(file) => path_to_uri(file)?,
-                        };
-                        changes.entry(uri).or_default().push(TextEdit {
-                            range: uiua_span_to_lsp(name_span, &doc.asm.inputs),
-                            new_text: params.new_name.clone(),
-                        });
-                    }
-                }
+                });
             }
             Ok(Some(WorkspaceEdit {
-                changes: Some(changes.clone()),
+                changes: Some(changes),
                 document_changes: None,
                 change_annotations: None,
             }))
@@ -2039,7 +2104,14 @@ mod server {
                                 range: Range::new(Position::new(0, 0), Position::new(0, 0)),
                             })))
                         }
-                        ImportSrc::Git(_) => {}
+                        ImportSrc::Git(url) => {
+                            if let Some(path) = git_module_path(url) {
+                                return Ok(Some(GotoDefinitionResponse::Scalar(Location {
+                                    uri: path_to_uri(&path)?,
+                                    range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                                })));
+                            }
+                        }
                     };
                 }
             }
@@ -2164,10 +2236,13 @@ mod server {
                     | crate::DiagnosticKind::Info => DiagnosticSeverity::INFORMATION,
                 };
                 if let Span::Code(span) = &diag.span {
+                    let deprecated = diag.kind == crate::DiagnosticKind::Warning
+                        && doc.code_meta.deprecated_refs.contains(span);
                     diagnostics.push(Diagnostic {
                         severity: Some(sev),
                         range: uiua_span_to_lsp(span, &doc.asm.inputs),
                         message: diag.message.clone(),
+                        tags: deprecated.then(|| vec![DiagnosticTag::DEPRECATED]),
                         ..Default::default()
                     });
                 }
@@ -2196,6 +2271,7 @@ mod server {
                         "inlineSignatureHints",
                         "inlineHintMinLength",
                         "values",
+                        "stackHeights",
                     ]
                     .iter()
                     .map(|s| ConfigurationItem {
@@ -2206,25 +2282,15 @@ mod server {
                 )
                 .await
                 .unwrap_or_default();
-            let (binding_sigs, inline_sigs, min_length, show_values) = if let [serde_json::Value::Bool(
-                    binding_sigs,
-                ), serde_json::Value::Bool(
-                    inline_sigs,
-                ), serde_json::Value::Number(
-                    min_length,
-                ), serde_json::Value::Bool(
-                    show_values,
-                )] = config.as_slice()
-            {
-                (
-                    *binding_sigs,
-                    *inline_sigs,
-                    min_length.as_u64().unwrap_or(1) as usize,
-                    *show_values,
-                )
-            } else {
-                (true, true, 3, true)
+            let bool_config = |i: usize, default: bool| {
+                (config.get(i).and_then(serde_json::Value::as_bool)).unwrap_or(default)
             };
+            let binding_sigs = bool_config(0, true);
+            let inline_sigs = bool_config(1, true);
+            let min_length =
+                (config.get(2).and_then(serde_json::Value::as_u64)).map_or(3, |n| n as usize);
+            let show_values = bool_config(3, true);
+            let show_heights = bool_config(4, false);
             let path = uri_path(&params.text_document.uri);
             // Signature hints
             let mut hints = Vec::new();
@@ -2330,38 +2396,41 @@ mod server {
                     });
                 }
             }
+            // Stack heights
+            if show_heights {
+                for (span, height) in &doc.code_meta.top_level_heights {
+                    if span.src != path {
+                        continue;
+                    }
+                    hints.push(InlayHint {
+                        text_edits: None,
+                        position: uiua_span_to_lsp(span, &doc.asm.inputs).end,
+                        label: InlayHintLabel::String(format!("height {height}")),
+                        kind: None,
+                        tooltip: Some(InlayHintTooltip::String(
+                            "The height of the stack after this line".into(),
+                        )),
+                        padding_left: Some(true),
+                        padding_right: None,
+                        data: None,
+                    });
+                }
+            }
 
             Ok(Some(hints))
         }
 
         async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
-            let Some(doc) = (self.docs).get(&params.text_document_position.text_document.uri)
-            else {
+            let uri = &params.text_document_position.text_document.uri;
+            let Some(doc) = self.doc(uri) else {
                 return Ok(None);
             };
             let (line, col) = lsp_pos_to_uiua(params.text_document_position.position, &doc.input);
-            let path = uri_path(&params.text_document_position.text_document.uri);
-            for (i, binfo) in doc.asm.bindings.iter().enumerate() {
-                if binfo.span.contains_line_col(line, col) && binfo.span.src == path {
-                    let mut locations = Vec::new();
-                    for entry in &self.docs {
-                        let uri = entry.key();
-                        let doc = entry.value();
-                        for (name_span, idx) in &doc.code_meta.global_references {
-                            if *idx == i {
-                                let uri = match &name_span.src {
-                                    InputSrc::Str(_) | InputSrc::Macro(_) => uri.clone(),
-                                    InputSrc::File(file) => path_to_uri(file)?,
-                                };
-                                let range = uiua_span_to_lsp(name_span, &doc.asm.inputs);
-                                locations.push(Location { uri, range });
-                            }
-                        }
-                    }
-                    return Ok(Some(locations));
-                }
-            }
-            Ok(None)
+            let Some(binding) = binding_at(&doc, &uri_path(uri), line, col) else {
+                return Ok(None);
+            };
+            let include_declaration = params.context.include_declaration;
+            (self.binding_references(uri, &doc, binding, include_declaration)).map(Some)
         }
 
         async fn inline_value(
@@ -2384,6 +2453,44 @@ mod server {
             Ok(Some(inline_values))
         }
 
+        async fn symbol(
+            &self,
+            params: WorkspaceSymbolParams,
+        ) -> Result<Option<Vec<SymbolInformation>>> {
+            let query = params.query.to_lowercase();
+            let mut matches = Vec::new();
+            for entry in self.index.iter() {
+                let Ok(uri) = Url::from_file_path(entry.key()) else {
+                    continue;
+                };
+                let container = (entry.key().file_name()).map(|name| name.to_string_lossy());
+                for binding in entry.value() {
+                    let Some(rank) = symbol_match_rank(&binding.name, &query) else {
+                        continue;
+                    };
+                    #[allow(deprecated)]
+                    let info = SymbolInformation {
+                        name: binding.name.clone(),
+                        kind: binding.kind,
+                        tags: None,
+                        deprecated: None,
+                        location: Location {
+                            uri: uri.clone(),
+                            range: binding.range,
+                        },
+                        container_name: container.as_ref().map(|name| name.to_string()),
+                    };
+                    matches.push((rank, info));
+                }
+            }
+            matches.sort_by(|(ra, a), (rb, b)| {
+                (ra.cmp(rb))
+                    .then_with(|| a.name.cmp(&b.name))
+                    .then_with(|| a.location.uri.as_str().cmp(b.location.uri.as_str()))
+            });
+            Ok(Some(matches.into_iter().map(|(_, info)| info).collect()))
+        }
+
         async fn shutdown(&self) -> Result<()> {
             Ok(())
         }
@@ -2413,6 +2520,53 @@ mod server {
                     .collect::<Vec<_>>()
             })
         }
+        /// Find every reference to a binding in all open documents
+        ///
+        /// Bindings are matched by where they are defined, so references in
+        /// files that import the binding's module are found as long as one of
+        /// those files is open. References spelled differently from the binding,
+        /// like the new name in an alias, are skipped.
+        fn binding_references(
+            &self,
+            uri: &Url,
+            doc: &LspDoc,
+            binding: &BindingInfo,
+            include_declaration: bool,
+        ) -> Result<Vec<Location>> {
+            let definition = span_location_key(&binding.span, uri);
+            let name = binding.span.as_str(&doc.asm.inputs, |s| s.to_string());
+            let mut seen = HashSet::new();
+            let mut locations = Vec::new();
+            let mut push = |span: &CodeSpan, doc_uri: &Url, inputs: &Inputs| -> Result<()> {
+                if !seen.insert(span_location_key(span, doc_uri)) {
+                    return Ok(());
+                }
+                let uri = match &span.src {
+                    InputSrc::File(file) => path_to_uri(file)?,
+                    InputSrc::Str(_) | InputSrc::Macro(_) => doc_uri.clone(),
+                };
+                let range = uiua_span_to_lsp(span, inputs);
+                locations.push(Location { uri, range });
+                Ok(())
+            };
+            if include_declaration {
+                push(&binding.span, uri, &doc.asm.inputs)?;
+            }
+            for entry in &self.docs {
+                let (doc_uri, doc) = (entry.key(), entry.value());
+                for (span, index) in &doc.code_meta.global_references {
+                    let is_reference = (doc.asm.bindings.get(*index))
+                        .is_some_and(|b| span_location_key(&b.span, doc_uri) == definition);
+                    if is_reference
+                        && !matches!(span.src, InputSrc::Macro(_))
+                        && span.try_as_str(&doc.asm.inputs, |s| s == name) == Some(true)
+                    {
+                        push(span, doc_uri, &doc.asm.inputs)?;
+                    }
+                }
+            }
+            Ok(locations)
+        }
         async fn debug(&self, message: impl Into<String>) {
             self.client
                 .log_message(MessageType::INFO, message.into())
@@ -2420,6 +2574,106 @@ mod server {
         }
     }
 
+    /// Index every Uiua file in the workspace roots, including git modules
+    fn index_workspace(index: &WorkspaceIndex, roots: &[PathBuf]) {
+        let mut files = Vec::new();
+        for root in roots {
+            collect_ua_files(root, &mut files);
+        }
+        for path in files {
+            if index.contains_key(&path) {
+                continue;
+            }
+            let Ok(input) = fs::read_to_string(&path) else {
+                continue;
+            };
+            index_doc(index, &LspDoc::new(&path, input), &path);
+        }
+    }
+
+    fn collect_ua_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" {
+                    collect_ua_files(&path, files);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "ua") {
+                files.extend(path.canonicalize());
+            }
+        }
+    }
+
+    /// Replace the index entries of every file a document defines bindings in
+    ///
+    /// This includes the files of modules the document imports.
+    fn index_doc(index: &WorkspaceIndex, doc: &LspDoc, doc_path: &Path) {
+        let mut files: HashMap<PathBuf, Vec<IndexedBinding>> = HashMap::new();
+        if let Ok(path) = doc_path.canonicalize() {
+            files.insert(path, Vec::new());
+        }
+        for binding in &doc.asm.bindings {
+            let kind = match &binding.kind {
+                BindingKind::Const(_) => SymbolKind::CONSTANT,
+                BindingKind::Func(_) | BindingKind::IndexMacro(_) | BindingKind::CodeMacro(_) => {
+                    SymbolKind::FUNCTION
+                }
+                BindingKind::Import(_) | BindingKind::Module(_) => SymbolKind::MODULE,
+                BindingKind::Error => continue,
+            };
+            let path = match &binding.span.src {
+                InputSrc::File(file) => file.canonicalize(),
+                InputSrc::Str(_) => doc_path.canonicalize(),
+                InputSrc::Macro(_) => continue,
+            };
+            let Ok(path) = path else {
+                continue;
+            };
+            files.entry(path).or_default().push(IndexedBinding {
+                name: binding.span.as_str(&doc.asm.inputs, |s| s.to_string()),
+                kind,
+                public: binding.public,
+                range: uiua_span_to_lsp(&binding.span, &doc.asm.inputs),
+                comment: (binding.meta.comment.as_ref()).map(|comment| comment.text.to_string()),
+            });
+        }
+        for (path, bindings) in files {
+            index.insert(path, bindings);
+        }
+    }
+
+    /// Rank how well a binding name matches a workspace symbol query
+    ///
+    /// Lower is better. Names that do not contain the query's characters in order do not match.
+    fn symbol_match_rank(name: &str, query: &str) -> Option<usize> {
+        let lower = name.to_lowercase();
+        if lower == query {
+            return Some(0);
+        }
+        if lower.starts_with(query) {
+            return Some(1);
+        }
+        if lower.contains(query) {
+            return Some(2);
+        }
+        let mut chars = lower.chars();
+        (query.chars().all(|q| chars.any(|c| c == q))).then_some(3)
+    }
+
+    /// Get the path of the `lib.ua` of a cloned git module
+    fn git_module_path(url: &str) -> Option<PathBuf> {
+        let mut parts = url.trim_end_matches('/').rsplitn(3, '/');
+        let repo = parts.next()?;
+        let owner = parts.next()?;
+        let path = Path::new(MODULES_DIR).join(owner).join(repo).join("lib.ua");
+        path.exists().then_some(path)
+    }
+
     fn path_to_uri(path: &Path) -> Result<Url> {
         Url::from_file_path(
             path.canonicalize()
@@ -2428,6 +2682,174 @@ mod server {
         .map_err(|_| Error::invalid_params("Invalid file path"))
     }
 
+    /// Make a code action that extracts the selected code into a new binding
+    ///
+    /// The binding is put on its own line before the item that contains the selection.
+    /// The action is only offered if the file still compiles after the extraction.
+    fn extract_to_binding(doc: &LspDoc, uri: &Url, range: Range) -> Option<CodeAction> {
+        let input = doc.input.as_str();
+        let start = lsp_pos_to_byte(range.start, input)?;
+        let end = lsp_pos_to_byte(range.end, input)?;
+        let selected = input.get(start..end)?.trim();
+        if selected.is_empty() {
+            return None;
+        }
+        let start = start + input[start..end].find(selected)?;
+        let end = start + selected.len();
+        // Find where to put the binding
+        let (items, errors, _) = parse(input, (), &mut Inputs::default());
+        if !errors.is_empty() {
+            return None;
+        }
+        let line_start = item_start_containing(&items, start, end)
+            .map(|item_start| input[..item_start].rfind('\n').map_or(0, |i| i + 1))?;
+        let indent: String = input[line_start..]
+            .chars()
+            .take_while(|&c| c == ' ')
+            .collect();
+        let name = (once(String::new()).chain(('A'..='Z').map(String::from)))
+            .map(|suffix| format!("Extracted{suffix}"))
+            .find(|name| !input.contains(name.as_str()))?;
+        // Compile the extracted code to get its signature
+        let binding_line = |sig: &str| format!("{indent}{name} ← {sig}{selected}\n");
+        let extracted = format!(
+            "{}{}{}{name}{}",
+            &input[..line_start],
+            binding_line(""),
+            &input[line_start..start],
+            &input[end..]
+        );
+        let mut comp = Compiler::with_backend(NativeSys);
+        comp.pre_eval_mode(PreEvalMode::Lsp);
+        (comp.load_str_src(&extracted, uri_path(uri).as_path())).ok()?;
+        let binding_start = line_start + indent.len();
+        let sig = (comp.asm.bindings.iter())
+            .find(|binding| {
+                binding.span.start.byte_pos as usize == binding_start
+                    && binding.span.as_str(&comp.asm.inputs, |s| s == name)
+            })
+            .and_then(|binding| match &binding.kind {
+                BindingKind::Func(f) => Some(format!("{} ", f.sig)),
+                BindingKind::Const(_) => Some(String::new()),
+                _ => None,
+            })?;
+        let insert_pos = byte_to_lsp_pos(line_start, input);
+        let edits = vec![
+            TextEdit {
+                range: Range::new(insert_pos, insert_pos),
+                new_text: binding_line(&sig),
+            },
+            TextEdit {
+                range: Range::new(byte_to_lsp_pos(start, input), byte_to_lsp_pos(end, input)),
+                new_text: name,
+            },
+        ];
+        Some(CodeAction {
+            title: "Extract to binding".into(),
+            kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+            edit: Some(WorkspaceEdit {
+                changes: Some([(uri.clone(), edits)].into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Find the start of the innermost binding or line of code that contains a byte range
+    fn item_start_containing(items: &[Item], start: usize, end: usize) -> Option<usize> {
+        let contains = |span: &CodeSpan| {
+            span.start.byte_pos as usize <= start && end <= span.end.byte_pos as usize
+        };
+        for item in items {
+            match item {
+                Item::Words(lines) => {
+                    for line in lines {
+                        let (Some(first), Some(last)) = (line.first(), line.last()) else {
+                            continue;
+                        };
+                        let span = first.span.clone().merge(last.span.clone());
+                        if contains(&span) {
+                            return Some(span.start.byte_pos as usize);
+                        }
+                    }
+                }
+                Item::Binding(binding) if contains(&binding.span()) => {
+                    // The name cannot be extracted
+                    let body = binding.words.first()?;
+                    return (body.span.start.byte_pos as usize <= start)
+                        .then_some(binding.name.span.start.byte_pos as usize);
+                }
+                Item::Module(module) if contains(&module.span) => {
+                    return item_start_containing(&module.value.items, start, end);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn lsp_pos_to_byte(pos: Position, input: &str) -> Option<usize> {
+        let line = input.split('\n').nth(pos.line as usize)?;
+        let line_start = line.as_ptr() as usize - input.as_ptr() as usize;
+        let mut utf16_col = 0;
+        for (i, c) in line.char_indices() {
+            if utf16_col >= pos.character as usize {
+                return Some(line_start + i);
+            }
+            utf16_col += c.len_utf16();
+        }
+        Some(line_start + line.len())
+    }
+
+    fn format_text_edits(edits: Vec<FormatEdit>, input: &str) -> Vec<TextEdit> {
+        (edits.into_iter())
+            .map(|edit| TextEdit {
+                range: Range::new(
+                    byte_to_lsp_pos(edit.range.start, input),
+                    byte_to_lsp_pos(edit.range.end, input),
+                ),
+                new_text: edit.text,
+            })
+            .collect()
+    }
+
+    fn byte_to_lsp_pos(byte: usize, input: &str) -> Position {
+        let before = &input[..byte];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count();
+        Position::new(
+            line as u32,
+            before[line_start..].encode_utf16().count() as u32,
+        )
+    }
+
+    /// Get the binding defined or referenced at a position
+    fn binding_at<'a>(
+        doc: &'a LspDoc,
+        path: &Path,
+        line: usize,
+        col: usize,
+    ) -> Option<&'a BindingInfo> {
+        let at = |span: &CodeSpan| span.contains_line_col(line, col) && span.src == *path;
+        (doc.asm.bindings.iter().find(|binding| at(&binding.span))).or_else(|| {
+            (doc.code_meta.global_references.iter())
+                .find(|(span, _)| at(span))
+                .and_then(|(_, index)| doc.asm.bindings.get(*index))
+        })
+    }
+
+    /// Identify a span by its file and byte range
+    ///
+    /// Different documents may refer to the same file by different paths,
+    /// so this is used to compare spans from different documents.
+    fn span_location_key(span: &CodeSpan, doc_uri: &Url) -> (PathBuf, u32, u32) {
+        let path = match &span.src {
+            InputSrc::File(file) => file.canonicalize().unwrap_or_else(|_| file.to_path_buf()),
+            InputSrc::Str(_) | InputSrc::Macro(_) => uri_path(doc_uri),
+        };
+        (path, span.start.byte_pos, span.end.byte_pos)
+    }
+
     fn uri_path(uri: &Url) -> PathBuf {
         let path = uri.path().replace("/c%3A", "C:");
         let path = PathBuf::from(path);
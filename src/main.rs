@@ -2,17 +2,20 @@
 compile_error!("To compile the uiua interpreter binary, you must enable the `binary` feature flag");
 
 use std::{
+    collections::{HashSet, VecDeque},
     env,
     error::Error,
+    ffi::OsString,
     fmt, fs,
     io::{self, stderr, stdin, stdout, BufRead, Write},
     path::{is_separator, Path, PathBuf},
     process::{exit, Child, Command, Stdio},
+    str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::channel,
     },
-    thread::sleep,
+    thread::{self, sleep},
     time::{Duration, Instant},
 };
 
@@ -21,18 +24,34 @@ use colored::*;
 use notify::{EventKind, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use rustyline::{error::ReadlineError, DefaultEditor};
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    DefaultEditor, Editor, Helper,
+};
 use terminal_size::terminal_size;
 use uiua::{
-    format::{format_file, format_str, FormatConfig, FormatConfigSource},
+    default_color_output,
+    docgen::ModuleDocs,
+    format::{format_ascii, format_file, format_str, FormatConfig, FormatConfigSource},
+    lex,
     lsp::BindingDocsKind,
-    print_stack, Assembly, CodeSpan, Compiler, NativeSys, PreEvalMode, PrimClass, PrimDocFragment,
-    PrimDocLine, Primitive, RunMode, SafeSys, SpanKind, Spans, Uiua, UiuaError, UiuaErrorKind,
-    UiuaResult, CONSTANTS,
+    package::{self, Dependency, Lockfile, Manifest, VersionReq},
+    print_stack, AsciiToken, Assembly, BenchBaseline, BenchRunner, CodeSpan, Compiler,
+    DiagnosticKind, DiagnosticLevel, InputSrc, Inputs, IntoSysBackend, MemoCache, NativeSys,
+    PreEvalMode, PrimClass, PrimDocFragment, PrimDocLine, Primitive, Recording, ReplaySys, RunMode,
+    SafeSys, SnapshotStatus, SpanKind, Spans, TestOutcome, TestRunner, Token, Uiua, UiuaError,
+    UiuaErrorKind, UiuaResult, Value, CONSTANTS,
 };
 
 static PRESSED_CTRL_C: AtomicBool = AtomicBool::new(false);
 static WATCH_CHILD: Lazy<Mutex<Option<Child>>> = Lazy::new(Default::default);
+/// The exit code `uiua run --hot` uses to ask `uiua watch` to restart it
+const HOT_RESTART_CODE: i32 = 75;
 
 fn fail<T>(e: UiuaError) -> T {
     println!("{}", e.report());
@@ -109,9 +128,13 @@ fn main() {
     // Run stand-alone
     #[cfg(feature = "stand")]
     if let Some(asm) = &*uiua::stand::STAND_ASM {
-        let mut rt = Uiua::with_native_sys().with_args(env::args().skip(1).collect());
+        let color = default_color_output();
+        let mut rt = Uiua::with_native_sys()
+            .with_args(env::args().skip(1).collect())
+            .with_color_output(color);
         rt.run_asm(asm.clone()).unwrap_or_else(fail);
-        print_stack(&rt.take_stack(), true);
+        rt.format_settings()
+            .scoped(|| print_stack(&rt.take_stack(), color));
         return;
     }
 
@@ -126,7 +149,19 @@ fn main() {
         .filter(|arg| arg.ends_with(".ua") || arg.contains(is_separator))
     {
         let args = args.collect();
-        run(path.as_ref(), args, false, None, None, None, false);
+        run(
+            path.as_ref(),
+            args,
+            InstrumentOptions::default(),
+            DiagnosticOptions::default(),
+            ComptimeOptions::default(),
+            None,
+            None,
+            None,
+            false,
+            false,
+            false,
+        );
         return;
     }
 
@@ -144,12 +179,23 @@ fn main() {
             path,
             formatter_options,
             io,
+            ascii,
+            check,
         }) => {
             let config =
                 FormatConfig::from_source(formatter_options.format_config_source, path.as_deref())
                     .unwrap_or_else(fail);
 
-            if io {
+            if check {
+                // Errors exit with 2 so they can be told apart from files that need formatting
+                let changed = check_format(path, io, &config, ascii).unwrap_or_else(|e| {
+                    println!("{}", e.report());
+                    exit(2)
+                });
+                if changed {
+                    exit(1);
+                }
+            } else if io {
                 let mut buffer = String::new();
                 let mut code = String::new();
                 let stdin = stdin();
@@ -164,12 +210,16 @@ fn main() {
                     }
                     code.push_str(&buffer);
                 }
-                let formatted = format_str(&code, &config).unwrap_or_else(fail);
-                print!("{}", formatted.output);
+                if ascii {
+                    print!("{}", format_ascii(&code, &config).unwrap_or_else(fail));
+                } else {
+                    let formatted = format_str(&code, &config).unwrap_or_else(fail);
+                    print!("{}", formatted.output);
+                }
             } else if let Some(path) = path {
-                format_single_file(path, &config).unwrap_or_else(fail);
+                format_single_file(path, &config, ascii).unwrap_or_else(fail);
             } else {
-                format_multi_files(&config).unwrap_or_else(fail);
+                format_multi_files(&config, ascii).unwrap_or_else(fail);
             }
         }
         Some(Comm::Run {
@@ -177,14 +227,39 @@ fn main() {
             no_format,
             no_color,
             formatter_options,
-            time_instrs,
+            instrument_options,
+            diagnostic_options,
+            comptime_options,
             limit,
             mode,
             #[cfg(feature = "audio")]
             audio_options,
             window,
+            hot,
+            expr,
+            lines,
+            whole,
+            #[cfg(feature = "bundle")]
+            bundle,
             args,
         }) => {
+            #[cfg(not(feature = "bundle"))]
+            let bundle = false;
+            if let Some(expr) = expr {
+                #[cfg(feature = "audio")]
+                setup_audio(audio_options);
+                run_expr(
+                    &expr,
+                    lines,
+                    whole,
+                    args,
+                    diagnostic_options,
+                    comptime_options,
+                    limit,
+                    no_color,
+                );
+                return;
+            }
             let path = if let Some(path) = path {
                 path
             } else {
@@ -202,14 +277,27 @@ fn main() {
             run(
                 &path,
                 args,
-                time_instrs,
+                instrument_options,
+                diagnostic_options,
+                comptime_options,
                 limit,
                 mode,
                 (!no_format).then_some(formatter_options),
                 no_color,
+                hot,
+                bundle,
             );
         }
-        Some(Comm::Build { path, output }) => {
+        Some(Comm::Build {
+            path,
+            output,
+            text,
+            comptime_options,
+            #[cfg(feature = "stand")]
+            exe,
+            #[cfg(feature = "stand")]
+            runtime,
+        }) => {
             let path = if let Some(path) = path {
                 path
             } else {
@@ -221,14 +309,40 @@ fn main() {
                     }
                 }
             };
-            let assembly = Compiler::with_backend(NativeSys)
+            let mut comp = Compiler::with_backend(NativeSys);
+            comptime_options.apply(&mut comp);
+            let mut assembly = comp
                 .mode(RunMode::Normal)
                 .print_diagnostics(true)
                 .load_file(&path)
                 .unwrap_or_else(fail)
                 .finish();
+            assembly.remove_dead_code();
+            #[cfg(feature = "stand")]
+            if exe || runtime.is_some() {
+                let runtime = match &runtime {
+                    Some(runtime) => fs::read(runtime),
+                    None => env::current_exe().and_then(fs::read),
+                };
+                let runtime = match runtime {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        eprintln!("Failed to read runtime: {e}");
+                        exit(1);
+                    }
+                };
+                let bytes = uiua::stand::embed_asm(runtime, &assembly);
+                let output =
+                    output.unwrap_or_else(|| path.with_extension(env::consts::EXE_EXTENSION));
+                write_exe(&output, bytes);
+                return;
+            }
             let output = output.unwrap_or_else(|| path.with_extension("uasm"));
-            let uasm = assembly.to_uasm();
+            let uasm = if text {
+                assembly.to_uasm().into_bytes()
+            } else {
+                assembly.to_uasm_bytes()
+            };
             if let Err(e) = fs::write(output, uasm) {
                 eprintln!("Failed to write assembly: {e}");
             }
@@ -243,7 +357,10 @@ fn main() {
         }) => {
             #[cfg(feature = "audio")]
             setup_audio(audio_options);
-            let mut rt = Uiua::with_native_sys().with_args(args);
+            let color = !no_color && default_color_output();
+            let mut rt = Uiua::with_native_sys()
+                .with_args(args)
+                .with_color_output(color);
             rt.compile_run(|comp| {
                 comp.mode(RunMode::Normal)
                     .experimental(experimental)
@@ -251,11 +368,16 @@ fn main() {
                     .load_str(&code)
             })
             .unwrap_or_else(fail);
-            print_stack(&rt.take_stack(), !no_color);
+            rt.format_settings()
+                .scoped(|| print_stack(&rt.take_stack(), color));
         }
         Some(Comm::Test {
             path,
             formatter_options,
+            filter,
+            jobs,
+            report,
+            update_snapshots,
             args,
         }) => {
             let path = if let Some(path) = path {
@@ -273,27 +395,45 @@ fn main() {
                 FormatConfig::from_source(formatter_options.format_config_source, Some(&path))
                     .unwrap_or_else(fail);
             format_file(&path, &config).unwrap_or_else(fail);
-            let mut rt = Uiua::with_native_sys()
-                .with_file_path(&path)
-                .with_args(args);
-            let res = rt.compile_run(|comp| {
-                comp.mode(RunMode::Test)
-                    .print_diagnostics(true)
-                    .load_file(path)
-            });
-            if let Err(e) = &res {
-                println!("{}", e.report());
-            }
-            rt.print_reports();
-            if res.is_err() {
-                exit(1);
-            }
+            run_tests(&path, args, filter, jobs, report, update_snapshots);
+        }
+        Some(Comm::Bench {
+            path,
+            filter,
+            warmup,
+            samples,
+            baseline,
+            save_baseline,
+            args,
+        }) => {
+            let path = if let Some(path) = path {
+                path
+            } else {
+                match working_file_path() {
+                    Ok(path) => path,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    }
+                }
+            };
+            run_benches(
+                &path,
+                args,
+                filter,
+                warmup,
+                samples,
+                baseline,
+                save_baseline,
+            );
         }
         Some(Comm::Watch {
             no_format,
             no_color,
             formatter_options,
             clear,
+            hot,
+            memo_cache,
             window,
             args,
             stdin_file,
@@ -305,6 +445,8 @@ fn main() {
                 color: !no_color,
                 format_config_source: formatter_options.format_config_source,
                 clear,
+                hot,
+                memo_cache,
                 args,
                 stdin_file,
             })
@@ -315,6 +457,8 @@ fn main() {
         }
         #[cfg(feature = "lsp")]
         Some(Comm::Lsp) => uiua::lsp::run_language_server(),
+        #[cfg(feature = "dap")]
+        Some(Comm::Dap) => uiua::dap::run_debug_adapter(),
         Some(Comm::Repl {
             file,
             formatter_options,
@@ -331,14 +475,35 @@ fn main() {
 
             #[cfg(feature = "audio")]
             setup_audio(audio_options);
-            let mut rt = Uiua::with_native_sys().with_args(args);
+            let color = default_color_output();
+            let mut rt = Uiua::with_native_sys()
+                .with_args(args)
+                .with_color_output(color);
             let mut compiler = Compiler::with_backend(NativeSys);
             compiler.mode(RunMode::Normal).print_diagnostics(true);
             if let Some(file) = file {
                 compiler.load_file(file).unwrap_or_else(fail);
                 rt.run_compiler(&mut compiler).unwrap_or_else(fail);
             }
-            repl(rt, compiler, true, stack, config);
+            repl(rt, compiler, color, stack, config);
+        }
+        Some(Comm::Debug {
+            path,
+            breakpoints,
+            args,
+        }) => {
+            let path = if let Some(path) = path {
+                path
+            } else {
+                match working_file_path() {
+                    Ok(path) => path,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    }
+                }
+            };
+            debug(&path, args, breakpoints);
         }
         Some(Comm::Update {
             main,
@@ -374,8 +539,13 @@ fn main() {
                         eprintln!("Failed to update modules: {e}");
                     }
                 }
+                ModuleCommand::Lock => lock_packages(None).unwrap_or_else(fail_package),
+                ModuleCommand::Install => install_packages().unwrap_or_else(fail_package),
             }
         }
+        Some(Comm::Add { package, name }) => {
+            add_package(&package, name).unwrap_or_else(fail_package)
+        }
         #[cfg(feature = "stand")]
         Some(Comm::Stand { main, name }) => {
             let main = main.unwrap_or_else(|| "main.ua".into());
@@ -393,22 +563,7 @@ fn main() {
                         })
                         .unwrap_or_else(|| "program".into());
                     let path = PathBuf::from(name).with_extension(env::consts::EXE_EXTENSION);
-                    #[allow(clippy::needless_borrows_for_generic_args)]
-                    if let Err(e) = fs::write(&path, bytes) {
-                        eprintln!("Failed to write executable: {e}");
-                        exit(1);
-                    }
-                    // Set executable permissions on Unix
-                    #[cfg(unix)]
-                    if let Err(e) = (|| {
-                        use std::os::unix::fs::PermissionsExt;
-                        let mut perms = fs::metadata(&path)?.permissions();
-                        perms.set_mode(0o755);
-                        fs::set_permissions(&path, perms)
-                    })() {
-                        eprintln!("Failed to set executable permissions: {e}");
-                        exit(1);
-                    }
+                    write_exe(&path, bytes);
                 }
                 Err(e) => {
                     eprintln!("Failed to build executable: {e}");
@@ -416,8 +571,18 @@ fn main() {
                 }
             }
         }
-        Some(Comm::Doc { name }) => doc(&name),
-        Some(Comm::Check { path }) => check(path).unwrap_or_else(fail),
+        Some(Comm::Doc { name, output, html }) => match name {
+            Some(name)
+                if !name.ends_with(".ua")
+                    && !Path::new(&name).is_dir()
+                    && output.is_none()
+                    && !html =>
+            {
+                doc(&name)
+            }
+            name => module_doc(name.map(PathBuf::from), output, html),
+        },
+        Some(Comm::Check { path, types, trace }) => check(path, types, trace).unwrap_or_else(fail),
         Some(Comm::Find { path, text, raw }) => find(path, text, raw).unwrap_or_else(fail),
         None => {
             set_use_window(app.window);
@@ -444,58 +609,461 @@ fn main() {
     }
 }
 
+/// Run a file, swapping in new function bodies when it changes
+///
+/// If a change is not just to function bodies, the process exits with [`HOT_RESTART_CODE`].
+fn run_hot(
+    rt: &mut Uiua,
+    path: &Path,
+    mode: RunMode,
+    diagnostics: DiagnosticOptions,
+    comptime: ComptimeOptions,
+) -> UiuaResult {
+    let path = path.to_path_buf();
+    let compile = move || -> UiuaResult<Assembly> {
+        let mut comp = Compiler::with_backend(NativeSys);
+        diagnostics.apply(&mut comp);
+        comptime.apply(&mut comp);
+        comp.mode(mode).print_diagnostics(true).load_file(&path)?;
+        Ok(comp.finish())
+    };
+    let asm = compile()?;
+    let (send, recv) = channel();
+    let watcher = notify::recommended_watcher(send).and_then(|mut watcher| {
+        watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+        Ok(watcher)
+    });
+    match watcher {
+        Ok(watcher) => {
+            let reloader = rt.hot_reloader();
+            let mut current = asm.clone();
+            thread::spawn(move || {
+                let _watcher = watcher;
+                while let Ok(event) = recv.recv() {
+                    let is_change = |event: &notify::Result<notify::Event>| {
+                        event.as_ref().is_ok_and(|event| {
+                            matches!(event.kind, EventKind::Modify(_))
+                                && (event.paths.iter())
+                                    .any(|path| path.extension().is_some_and(|ext| ext == "ua"))
+                        })
+                    };
+                    if !is_change(&event) {
+                        continue;
+                    }
+                    // Wait for the rest of the writes
+                    sleep(Duration::from_millis(50));
+                    recv.try_iter().for_each(drop);
+                    let new = match compile() {
+                        Ok(new) => new,
+                        Err(e) => {
+                            println!("{}", e.report());
+                            continue;
+                        }
+                    };
+                    if !current.can_hot_swap(&new) {
+                        exit(HOT_RESTART_CODE);
+                    }
+                    current = new.clone();
+                    reloader.reload(new);
+                }
+            });
+        }
+        Err(e) => eprintln!("Failed to watch for changes: {e}"),
+    }
+    rt.run_asm(asm)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run(
     path: &Path,
     args: Vec<String>,
-    time_instrs: bool,
+    instrument: InstrumentOptions,
+    diagnostics: DiagnosticOptions,
+    comptime: ComptimeOptions,
     limit: Option<f64>,
     mode: Option<RunMode>,
     formatter_options: Option<FormatterOptions>,
     no_color: bool,
+    hot: bool,
+    bundle: bool,
 ) {
-    let mut rt = Uiua::with_native_sys()
+    let sys = NativeSys.into_sys_backend();
+    #[cfg(feature = "bundle")]
+    let sys = if bundle {
+        let bytes = fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read bundle: {e}");
+            exit(1)
+        });
+        (uiua::BundleSys::from_zip(sys, &bytes))
+            .unwrap_or_else(|e| {
+                eprintln!("{e}");
+                exit(1)
+            })
+            .into_sys_backend()
+    } else {
+        sys
+    };
+    // A bundle's program is its main.ua
+    let path = if bundle { Path::new("main.ua") } else { path };
+    let backend = if let Some(replay) = &instrument.replay {
+        let recording = Recording::load(replay).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1)
+        });
+        ReplaySys::replay(sys, recording).into_sys_backend()
+    } else if let Some(path) = &instrument.record {
+        (ReplaySys::record(sys).save_on_exit(path)).into_sys_backend()
+    } else {
+        sys
+    };
+    let color = !no_color && default_color_output();
+    let mut rt = Uiua::with_backend(backend)
+        .with_color_output(color)
         .with_file_path(path)
         .with_args(args)
-        .time_instrs(time_instrs)
+        .time_instrs(instrument.time_instrs)
+        .with_stack_history(instrument.keep_history.unwrap_or(0))
+        .with_deterministic(instrument.deterministic)
         .maybe_with_execution_limit(limit.map(Duration::from_secs_f64));
+    if instrument.profile || instrument.flame.is_some() {
+        rt = rt.with_profiling(Duration::from_micros(100));
+    }
+    if instrument.profile_allocs {
+        rt = rt.with_alloc_profiling();
+    }
+    if let Some(dir) = &instrument.memo_cache {
+        rt = rt.with_memo_cache(MemoCache::new(dir));
+    }
     if path.extension().is_some_and(|ext| ext == "uasm") {
-        let uasm = match fs::read_to_string(path) {
-            Ok(json) => json,
+        let uasm = match fs::read(path) {
+            Ok(bytes) => bytes,
             Err(e) => {
                 eprintln!("Failed to read assembly: {e}");
                 return;
             }
         };
-        let assembly = match Assembly::from_uasm(&uasm) {
+        let assembly = match Assembly::from_uasm_bytes(&uasm) {
             Ok(assembly) => assembly,
             Err(e) => {
-                eprintln!("Failed to parse assembly: {e}");
+                eprintln!("Failed to load assembly: {e}");
                 return;
             }
         };
-        rt.run_asm(assembly).unwrap_or_else(fail);
+        if let Err(e) = rt.run_asm(assembly) {
+            print_stack_history(&rt);
+            fail(e)
+        }
     } else {
-        if let Some(formatter_options) = formatter_options {
+        if let Some(formatter_options) = formatter_options.filter(|_| !bundle) {
             let config =
                 FormatConfig::from_source(formatter_options.format_config_source, Some(path))
                     .unwrap_or_else(fail);
             format_file(path, &config).unwrap_or_else(fail);
         }
         let mode = mode.unwrap_or(RunMode::Normal);
-        let res = rt.compile_run(|comp| comp.mode(mode).print_diagnostics(true).load_file(path));
+        let res = if hot {
+            run_hot(&mut rt, path, mode, diagnostics, comptime)
+        } else if bundle {
+            let input = (rt.backend().file_read_all(path))
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .unwrap_or_else(|| {
+                    eprintln!("Bundle does not have a valid main.ua");
+                    exit(1)
+                });
+            (rt.compile_run(|comp| {
+                diagnostics.apply(comp);
+                comptime.apply(comp);
+                comp.mode(mode)
+                    .print_diagnostics(true)
+                    .load_str_src(&input, path)
+            }))
+            .map(drop)
+        } else {
+            (rt.compile_run(|comp| {
+                diagnostics.apply(comp);
+                comptime.apply(comp);
+                comp.mode(mode).print_diagnostics(true).load_file(path)
+            }))
+            .map(drop)
+        };
         if let Err(e) = &res {
+            print_stack_history(&rt);
             println!("{}", e.report());
         }
         rt.print_reports();
         if res.is_err() {
+            finish_instruments(&mut rt, &instrument);
             exit(1);
         }
     }
-    print_stack(&rt.take_stack(), !no_color);
+    finish_instruments(&mut rt, &instrument);
+    rt.format_settings()
+        .scoped(|| print_stack(&rt.take_stack(), color));
     #[cfg(feature = "raw_mode")]
     rawrrr::disable_raw();
 }
 
+/// Run an expression, optionally on stdin
+///
+/// With `lines`, the expression is run once for each line of stdin.
+/// With `whole`, it is run once on all of stdin.
+/// In both cases, the input is pushed as a string, and each value left
+/// on the stack is printed on its own line the way `&p` would print it.
+#[allow(clippy::too_many_arguments)]
+fn run_expr(
+    code: &str,
+    lines: bool,
+    whole: bool,
+    args: Vec<String>,
+    diagnostics: DiagnosticOptions,
+    comptime: ComptimeOptions,
+    limit: Option<f64>,
+    no_color: bool,
+) {
+    let color = !no_color && default_color_output();
+    let mut rt = Uiua::with_native_sys()
+        .with_args(args)
+        .with_color_output(color)
+        .maybe_with_execution_limit(limit.map(Duration::from_secs_f64));
+    let mut comp = Compiler::with_backend(NativeSys);
+    diagnostics.apply(&mut comp);
+    comptime.apply(&mut comp);
+    let asm = comp
+        .mode(RunMode::Normal)
+        .print_diagnostics(true)
+        .load_str(code)
+        .unwrap_or_else(fail)
+        .finish();
+    if !lines && !whole {
+        rt.run_asm(asm).unwrap_or_else(fail);
+        rt.format_settings()
+            .scoped(|| print_stack(&rt.take_stack(), color));
+        return;
+    }
+    let mut out = stdout().lock();
+    let mut run_input = |input: String| {
+        rt.push(input);
+        rt.run_shared(&asm).unwrap_or_else(fail);
+        for val in rt.take_stack() {
+            if writeln!(out, "{}", val.format()).is_err() {
+                // The output was closed, as by `head`
+                exit(0);
+            }
+        }
+    };
+    if lines {
+        for line in stdin().lock().lines() {
+            let line = line.unwrap_or_else(|e| {
+                eprintln!("Failed to read stdin: {e}");
+                exit(1)
+            });
+            run_input(line);
+        }
+    } else {
+        let mut input = String::new();
+        if let Err(e) = io::Read::read_to_string(&mut stdin().lock(), &mut input) {
+            eprintln!("Failed to read stdin: {e}");
+            exit(1);
+        }
+        run_input(input);
+    }
+}
+
+fn run_tests(
+    path: &Path,
+    args: Vec<String>,
+    filter: Option<String>,
+    jobs: usize,
+    report_path: Option<PathBuf>,
+    update_snapshots: bool,
+) {
+    let runtime_path = path.to_path_buf();
+    let mut compiler = Compiler::with_backend(NativeSys);
+    compiler.print_diagnostics(true);
+    let mut runner = TestRunner::new(compiler)
+        .jobs(jobs)
+        .update_snapshots(update_snapshots);
+    runner = if jobs > 1 {
+        runner.with_runtime(move || {
+            (Uiua::with_safe_sys())
+                .with_file_path(&runtime_path)
+                .with_args(args.clone())
+        })
+    } else {
+        runner.with_runtime(move || {
+            (Uiua::with_native_sys())
+                .with_file_path(&runtime_path)
+                .with_args(args.clone())
+        })
+    };
+    if let Some(filter) = filter {
+        runner = runner.filter(filter);
+    }
+    let report = runner.run_file(path).unwrap_or_else(fail);
+    for test in &report.tests {
+        let time = format!("({:.2}ms)", test.time * 1000.0).bright_black();
+        let name = &test.name;
+        match test.outcome {
+            TestOutcome::Passed => match test.snapshot {
+                Some(SnapshotStatus::Created) => {
+                    println!(
+                        "{} {name} {time} {}",
+                        "✓".green(),
+                        "snapshot created".yellow()
+                    )
+                }
+                Some(SnapshotStatus::Updated) => {
+                    println!(
+                        "{} {name} {time} {}",
+                        "✓".green(),
+                        "snapshot updated".yellow()
+                    )
+                }
+                _ => println!("{} {name} {time}", "✓".green()),
+            },
+            TestOutcome::FailedAsExpected => {
+                println!(
+                    "{} {name} {time} {}",
+                    "✓".green(),
+                    "failed as expected".bright_black()
+                )
+            }
+            TestOutcome::Failed => println!("{} {name} {time}", "✗".red()),
+            TestOutcome::PassedUnexpectedly => {
+                println!("{} {name} {time} {}", "✗".red(), "should have failed".red())
+            }
+        }
+        if !test.outcome.is_success() {
+            if let Some(error) = &test.error {
+                println!("{}", error.report());
+            }
+            print!("{}", test.stdout);
+            eprint!("{}", test.stderr);
+        }
+    }
+    let summary = report.to_string();
+    if report.tests.is_empty() {
+        println!("No tests were run");
+    } else if report.is_success() {
+        println!("{}", summary.green());
+    } else {
+        println!("{}", summary.red());
+    }
+    if let Some(report_path) = report_path {
+        let contents = if report_path.extension().is_some_and(|ext| ext == "xml") {
+            report.to_junit(&path.to_string_lossy())
+        } else {
+            report.to_json()
+        };
+        if let Err(e) = fs::write(&report_path, contents) {
+            eprintln!("Failed to write report: {e}");
+        }
+    }
+    if !report.is_success() {
+        exit(1);
+    }
+}
+
+fn run_benches(
+    path: &Path,
+    args: Vec<String>,
+    filter: Option<String>,
+    warmup: usize,
+    samples: usize,
+    baseline_path: Option<PathBuf>,
+    save_baseline: Option<PathBuf>,
+) {
+    let mut compiler = Compiler::with_backend(NativeSys);
+    compiler.print_diagnostics(true);
+    let runtime = Uiua::with_native_sys().with_file_path(path).with_args(args);
+    let mut runner = BenchRunner::new(compiler, runtime)
+        .warmup(warmup)
+        .samples(samples);
+    if let Some(filter) = filter {
+        runner = runner.filter(filter);
+    }
+    if let Some(baseline_path) = baseline_path {
+        match BenchBaseline::load(&baseline_path) {
+            Ok(baseline) => runner = runner.baseline(baseline),
+            Err(e) => {
+                eprintln!("Failed to load baseline {}: {e}", baseline_path.display());
+                exit(1);
+            }
+        }
+    }
+    let report = runner.run_file(path).unwrap_or_else(fail);
+    if report.benches.is_empty() {
+        println!("No benchmarks were run");
+    }
+    for bench in &report.benches {
+        println!("{}", bench.name.bold());
+        println!("  {}", bench.stats);
+        if let Some(change) = bench.change() {
+            let percent = format!("{:+.1}%", change * 100.0);
+            let percent = if change > 0.05 {
+                percent.red()
+            } else if change < -0.05 {
+                percent.green()
+            } else {
+                percent.bright_black()
+            };
+            println!("  {percent} compared to baseline");
+        }
+    }
+    if let Some(save_path) = save_baseline {
+        if let Err(e) = BenchBaseline::from_report(&report).save(&save_path) {
+            eprintln!("Failed to save baseline: {e}");
+            exit(1);
+        }
+    }
+}
+
+fn print_stack_history(rt: &Uiua) {
+    let history = rt.stack_history();
+    if history.is_empty() {
+        return;
+    }
+    eprintln!("Stack history, oldest first:");
+    for (i, snapshot) in history.iter().enumerate() {
+        match history.len() - 1 - i {
+            0 => eprintln!("\nAt {} (latest):", snapshot.span),
+            1 => eprintln!("\n1 line earlier, at {}:", snapshot.span),
+            steps => eprintln!("\n{steps} lines earlier, at {}:", snapshot.span),
+        }
+        if snapshot.stack.is_empty() {
+            eprintln!("  (empty)");
+        }
+        for value in &snapshot.stack {
+            for line in value.show().lines() {
+                eprintln!("  {line}");
+            }
+        }
+    }
+    eprintln!();
+}
+
+fn finish_instruments(rt: &mut Uiua, instrument: &InstrumentOptions) {
+    if let Some(path) = &instrument.record {
+        if let Some(sys) = rt.downcast_backend::<ReplaySys>() {
+            if let Err(e) = sys.recording().save(path) {
+                eprintln!("{e}");
+            }
+        }
+    }
+    if let Some(profile) = rt.take_profile() {
+        eprintln!("{profile}");
+        if let Some(path) = &instrument.flame {
+            if let Err(e) = fs::write(path, profile.folded()) {
+                eprintln!("Failed to write folded stacks: {e}");
+            }
+        }
+    }
+    if let Some(profile) = rt.take_alloc_profile() {
+        eprintln!("{profile}");
+    }
+}
+
 #[derive(Debug)]
 enum NoWorkingFile {
     NoFile,
@@ -519,6 +1087,25 @@ impl fmt::Display for NoWorkingFile {
     }
 }
 
+#[cfg(feature = "stand")]
+fn write_exe(path: &Path, bytes: Vec<u8>) {
+    if let Err(e) = fs::write(path, bytes) {
+        eprintln!("Failed to write executable: {e}");
+        exit(1);
+    }
+    // Set executable permissions on Unix
+    #[cfg(unix)]
+    if let Err(e) = (|| {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(path, perms)
+    })() {
+        eprintln!("Failed to set executable permissions: {e}");
+        exit(1);
+    }
+}
+
 fn working_file_path() -> Result<PathBuf, NoWorkingFile> {
     let main_in_src = PathBuf::from("src/main.ua");
     let main = if main_in_src.exists() {
@@ -551,6 +1138,8 @@ struct WatchArgs {
     color: bool,
     format_config_source: FormatConfigSource,
     clear: bool,
+    hot: bool,
+    memo_cache: Option<PathBuf>,
     args: Vec<String>,
     stdin_file: Option<PathBuf>,
 }
@@ -563,12 +1152,68 @@ impl Default for WatchArgs {
             color: true,
             format_config_source: FormatConfigSource::SearchFile,
             clear: false,
+            hot: false,
+            memo_cache: None,
             args: Vec::new(),
             stdin_file: None,
         }
     }
 }
 
+/// The files that each file run by `uiua watch` imports, directly or transitively
+#[derive(Default)]
+struct ImportGraph {
+    roots: Vec<(PathBuf, HashSet<PathBuf>)>,
+}
+
+impl ImportGraph {
+    /// Compile a file without running it to find the files it imports
+    fn update(&mut self, root: &Path) {
+        let mut comp = Compiler::with_backend(NativeSys);
+        comp.pre_eval_mode(PreEvalMode::Lazy).comptime(false);
+        let failed = comp.load_file(root).is_err();
+        let imports: HashSet<PathBuf> = (comp.imported_files())
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        let root = root.canonicalize().unwrap_or_else(|_| root.into());
+        match self.roots.iter_mut().find(|(r, _)| *r == root) {
+            // A file that fails to compile may not have gotten to all of its imports
+            Some((_, deps)) if failed => deps.extend(imports),
+            Some((_, deps)) => *deps = imports,
+            None => self.roots.push((root, imports)),
+        }
+    }
+    /// Get the files that have been run that are affected by a change to a file
+    fn affected(&self, changed: &Path) -> Vec<PathBuf> {
+        let changed = changed.canonicalize().unwrap_or_else(|_| changed.into());
+        (self.roots.iter())
+            .filter(|(root, deps)| *root == changed || deps.contains(&changed))
+            .map(|(root, _)| root.clone())
+            .collect()
+    }
+}
+
+/// Print whether each file run after a change passed or failed
+fn print_watch_results(results: &[(PathBuf, bool)], color: bool) {
+    if results.len() < 2 {
+        return;
+    }
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut line = String::new();
+    for (path, success) in results {
+        let path = pathdiff::diff_paths(path, &cwd).unwrap_or_else(|| path.clone());
+        let mark = match (success, color) {
+            (true, true) => "✓".green().to_string(),
+            (false, true) => "✗".red().to_string(),
+            (true, false) => "✓".into(),
+            (false, false) => "✗".into(),
+        };
+        line.push_str(&format!("{mark} {}  ", path.display()));
+    }
+    let passed = results.iter().filter(|(_, success)| *success).count();
+    println!("{}/{} passed: {}", passed, results.len(), line.trim_end());
+}
+
 impl WatchArgs {
     fn watch(self) -> Result<(), Box<dyn Error>> {
         let WatchArgs {
@@ -577,6 +1222,8 @@ impl WatchArgs {
             color,
             format_config_source,
             clear,
+            hot,
+            memo_cache,
             args,
             stdin_file,
         } = self;
@@ -598,7 +1245,7 @@ impl WatchArgs {
             socket.set_nonblocking(true)?;
             (socket, port)
         };
-        let run = |path: &Path, stdin_file: Option<&PathBuf>| -> io::Result<()> {
+        let run = |path: &Path, stdin_file: Option<&PathBuf>| -> io::Result<bool> {
             if let Some(mut child) = WATCH_CHILD.lock().take() {
                 _ = child.kill();
                 print_watching();
@@ -635,6 +1282,12 @@ impl WatchArgs {
                             com.arg("run")
                                 .arg(path)
                                 .args((!color).then_some("--no-color"))
+                                .args(hot.then_some("--hot"))
+                                .args(memo_cache.as_ref().map(|dir| {
+                                    let mut arg = OsString::from("--memo-cache=");
+                                    arg.push(dir);
+                                    arg
+                                }))
                                 .args([
                                     "--no-format",
                                     "--mode",
@@ -657,7 +1310,7 @@ impl WatchArgs {
                                 .spawn()
                                 .unwrap()
                         });
-                        return Ok(());
+                        return Ok(true);
                     }
                     Err(e) => {
                         if let UiuaErrorKind::Format(..) = e.kind {
@@ -665,17 +1318,40 @@ impl WatchArgs {
                         } else {
                             clear_watching();
                             println!("{}", e.report());
-                            print_watching();
-                            return Ok(());
+                            return Ok(false);
                         }
                     }
                 }
             }
             println!("Failed to format file after {TRIES} tries");
+            Ok(false)
+        };
+        // Files waiting to be run because of the last change, and the results of those already run
+        let mut graph = ImportGraph::default();
+        let mut queue = VecDeque::new();
+        let mut results: Vec<(PathBuf, bool)> = Vec::new();
+        let mut last_path: Option<PathBuf> = None;
+        let run_queue = |queue: &mut VecDeque<PathBuf>,
+                         results: &mut Vec<(PathBuf, bool)>,
+                         graph: &mut ImportGraph,
+                         last_path: &mut Option<PathBuf>|
+         -> io::Result<()> {
+            while let Some(path) = queue.pop_front() {
+                graph.update(&path);
+                let started = run(&path, stdin_file.as_ref())?;
+                *last_path = Some(path.clone());
+                if started {
+                    return Ok(());
+                }
+                results.push((path, false));
+            }
+            print_watch_results(results, color);
+            print_watching();
             Ok(())
         };
         if let Some(path) = initial_path {
-            run(&path, stdin_file.as_ref())?;
+            queue.push_back(path);
+            run_queue(&mut queue, &mut results, &mut graph, &mut last_path)?;
         }
         let mut last_time = Instant::now();
         loop {
@@ -688,7 +1364,15 @@ impl WatchArgs {
                 .filter(|path| path.extension().map_or(false, |ext| ext == "ua"))
                 .last()
             {
-                if last_time.elapsed() > Duration::from_millis(100) {
+                // A hot-reloading child handles changes itself
+                let child_running = (WATCH_CHILD.lock().as_mut())
+                    .is_some_and(|child| child.try_wait().is_ok_and(|status| status.is_none()));
+                if hot && child_running {
+                    if let (Some(config), true) = (&config, format) {
+                        _ = format_file(&path, config);
+                    }
+                    last_time = Instant::now();
+                } else if last_time.elapsed() > Duration::from_millis(100) {
                     if clear {
                         if cfg!(target_os = "windows") {
                             _ = Command::new("cmd").args(["/C", "cls"]).status();
@@ -696,15 +1380,35 @@ impl WatchArgs {
                             _ = Command::new("clear").status();
                         }
                     }
-                    run(&path, stdin_file.as_ref())?;
+                    // Rerun every file that depends on the changed one
+                    let mut affected = graph.affected(&path);
+                    if affected.is_empty() {
+                        affected.push(path);
+                    }
+                    queue = affected.into();
+                    results.clear();
+                    run_queue(&mut queue, &mut results, &mut graph, &mut last_path)?;
                     last_time = Instant::now();
                 }
             }
             let mut child = WATCH_CHILD.lock();
             if let Some(ch) = &mut *child {
-                if ch.try_wait()?.is_some() {
-                    print_watching();
+                if let Some(status) = ch.try_wait()? {
                     *child = None;
+                    if hot && status.code() == Some(HOT_RESTART_CODE) {
+                        drop(child);
+                        if let Some(path) = &last_path {
+                            run(path, stdin_file.as_ref())?;
+                        }
+                        last_time = Instant::now();
+                        continue;
+                    }
+                    drop(child);
+                    if let Some(path) = &last_path {
+                        results.push((path.clone(), status.success()));
+                    }
+                    run_queue(&mut queue, &mut results, &mut graph, &mut last_path)?;
+                    continue;
                 }
                 #[cfg(feature = "audio")]
                 {
@@ -748,8 +1452,12 @@ enum Comm {
         no_color: bool,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
-        #[clap(long, help = "Emit the duration of each instruction's execution")]
-        time_instrs: bool,
+        #[clap(flatten)]
+        instrument_options: InstrumentOptions,
+        #[clap(flatten)]
+        diagnostic_options: DiagnosticOptions,
+        #[clap(flatten)]
+        comptime_options: ComptimeOptions,
         #[clap(long, short = 'l', help = "Set an execution limit in seconds")]
         limit: Option<f64>,
         #[clap(long, help = "Run the file in a specific mode")]
@@ -764,14 +1472,57 @@ enum Comm {
                     Set UIUA_WINDOW=1 to always use a window."
         )]
         window: bool,
+        #[clap(long, hide = true)]
+        hot: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "path",
+            help = "Run an expression instead of a file"
+        )]
+        expr: Option<String>,
+        #[clap(
+            long,
+            requires = "expr",
+            conflicts_with = "whole",
+            help = "Run the expression on each line of stdin and print what it leaves on the stack"
+        )]
+        lines: bool,
+        #[clap(
+            long,
+            requires = "expr",
+            help = "Run the expression on all of stdin and print what it leaves on the stack"
+        )]
+        whole: bool,
+        #[cfg(feature = "bundle")]
+        #[clap(
+            long,
+            conflicts_with = "hot",
+            help = "Run the main.ua of a zip archive, whose other files can be read by the program"
+        )]
+        bundle: bool,
         #[clap(trailing_var_arg = true, help = "Arguments to pass to the program")]
         args: Vec<String>,
     },
-    #[clap(about = "Build an assembly (the .uasm format is currently unstable)")]
+    #[clap(about = "Build an assembly")]
     Build {
         path: Option<PathBuf>,
         #[clap(short, long, help = "The path to the output file")]
         output: Option<PathBuf>,
+        #[clap(long, help = "Write the assembly in the unstable text format")]
+        text: bool,
+        #[clap(flatten)]
+        comptime_options: ComptimeOptions,
+        #[cfg(feature = "stand")]
+        #[clap(long, help = "Build a standalone executable instead of an assembly")]
+        exe: bool,
+        #[cfg(feature = "stand")]
+        #[clap(
+            long,
+            help = "The Uiua executable to embed the assembly in. \
+                    Implies --exe. Defaults to the current executable"
+        )]
+        runtime: Option<PathBuf>,
     },
     #[clap(about = "Evaluate an expression and print its output")]
     Eval {
@@ -791,19 +1542,80 @@ enum Comm {
         path: Option<PathBuf>,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
+        #[clap(long, help = "Only run tests whose names contain this string")]
+        filter: Option<String>,
+        #[clap(
+            short,
+            long,
+            default_value_t = 1,
+            help = "The number of tests to run at once. \
+                    With more than 1, each test gets an isolated backend \
+                    with no access to files or the network."
+        )]
+        jobs: usize,
+        #[clap(
+            long,
+            help = "Write a report to a file. \
+                    The report is JUnit XML if the path ends in .xml, and JSON otherwise."
+        )]
+        report: Option<PathBuf>,
+        #[clap(long, help = "Overwrite snapshots that do not match")]
+        update_snapshots: bool,
         #[clap(trailing_var_arg = true, help = "Arguments to pass to the program")]
         args: Vec<String>,
     },
-    #[clap(about = "Run .ua files in the current directory when they change")]
-    Watch {
-        #[clap(long, help = "Don't format the file before running")]
-        no_format: bool,
+    #[clap(about = "Run the benchmark scopes in a file")]
+    Bench {
+        path: Option<PathBuf>,
+        #[clap(long, help = "Only run benchmarks whose names contain this string")]
+        filter: Option<String>,
+        #[clap(
+            long,
+            default_value_t = 3,
+            help = "The number of untimed runs of each benchmark before sampling"
+        )]
+        warmup: usize,
+        #[clap(
+            long,
+            default_value_t = 20,
+            help = "The number of timed samples of each benchmark"
+        )]
+        samples: usize,
+        #[clap(
+            long,
+            help = "Compare the results to a baseline saved with --save-baseline"
+        )]
+        baseline: Option<PathBuf>,
+        #[clap(long, help = "Save the mean times to a file to use as a baseline")]
+        save_baseline: Option<PathBuf>,
+        #[clap(trailing_var_arg = true, help = "Arguments to pass to the program")]
+        args: Vec<String>,
+    },
+    #[clap(about = "Run .ua files in the current directory when they change")]
+    Watch {
+        #[clap(long, help = "Don't format the file before running")]
+        no_format: bool,
         #[clap(long, help = "Don't colorize stack output")]
         no_color: bool,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
         #[clap(long, help = "Clear the terminal on file change")]
         clear: bool,
+        #[clap(
+            long,
+            help = "Keep the program running with its state when only function bodies change"
+        )]
+        hot: bool,
+        #[clap(
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = MemoCache::DEFAULT_DIR,
+            help = "Remember the outputs of pure memoized functions across runs \
+                    in a cache directory"
+        )]
+        memo_cache: Option<PathBuf>,
         #[clap(
             short,
             long,
@@ -821,18 +1633,49 @@ enum Comm {
         path: Option<PathBuf>,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
-        #[clap(long, help = "Format lines read from stdin")]
+        #[clap(
+            long,
+            visible_alias = "stdin",
+            help = "Format code read from stdin and write it to stdout"
+        )]
         io: bool,
+        #[clap(long, help = "Spell glyphs with their ASCII names")]
+        ascii: bool,
+        #[clap(
+            long,
+            help = "Don't write any files. Instead, print a diff of what \
+                    formatting would change and exit with 1 if there are changes \
+                    or 2 if there is an error."
+        )]
+        check: bool,
     },
-    #[clap(about = "Show the documentation for a function, modifier, or constant")]
+    #[clap(
+        about = "Show the documentation for a function, modifier, or constant, \
+                 or generate documentation for a module from its doc comments"
+    )]
     Doc {
-        #[clap(help = "The name of the function, modifier, or constant")]
-        name: String,
+        #[clap(help = "The name of the function, modifier, or constant, \
+                       or the .ua file or package directory to document")]
+        name: Option<String>,
+        #[clap(short, long, help = "The path to write module documentation to")]
+        output: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "Generate module documentation as HTML instead of Markdown"
+        )]
+        html: bool,
     },
     #[clap(about = "Check that Uiua files compile")]
     Check {
         #[clap(help = "The path to a file or directory to check")]
         path: Option<PathBuf>,
+        #[clap(long, help = "Check for type and shape errors")]
+        types: bool,
+        #[clap(
+            long,
+            help = "Print the stack height before and after each piece of code"
+        )]
+        trace: bool,
     },
     #[clap(about = "Find some Uiua code that matches the given unformatted text")]
     Find {
@@ -856,6 +1699,19 @@ enum Comm {
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Step through a Uiua file in an interactive debugger")]
+    Debug {
+        path: Option<PathBuf>,
+        #[clap(
+            short,
+            long = "break",
+            help = "Set a breakpoint at a line number or binding name. \
+                    If any are set, the program runs until one is hit."
+        )]
+        breakpoints: Vec<String>,
+        #[clap(trailing_var_arg = true, help = "Arguments to pass to the program")]
+        args: Vec<String>,
+    },
     #[clap(about = "Update Uiua by installing with Cargo")]
     Update {
         #[clap(long, help = "Install from the main branch instead of crates.io")]
@@ -870,6 +1726,16 @@ enum Comm {
         #[clap(subcommand)]
         command: Option<ModuleCommand>,
     },
+    #[clap(about = "Add a package to uiua.toml and lock it")]
+    Add {
+        #[clap(help = "The package's git URL, optionally followed by @ and a version requirement")]
+        package: String,
+        #[clap(
+            long,
+            help = "The name to import the package by. Defaults to the repository name."
+        )]
+        name: Option<String>,
+    },
     #[cfg(feature = "stand")]
     #[clap(about = "Create a standalone executable")]
     Stand {
@@ -881,6 +1747,9 @@ enum Comm {
     #[cfg(feature = "lsp")]
     #[clap(about = "Run the Language Server")]
     Lsp,
+    #[cfg(feature = "dap")]
+    #[clap(about = "Run the Debug Adapter")]
+    Dap,
 }
 
 #[derive(Subcommand)]
@@ -892,6 +1761,10 @@ enum ModuleCommand {
         #[clap(help = "The module to update")]
         module: Option<PathBuf>,
     },
+    #[clap(about = "Resolve the dependencies in uiua.toml and write uiua.lock")]
+    Lock,
+    #[clap(about = "Install the packages in uiua.lock")]
+    Install,
 }
 
 #[derive(clap::Args)]
@@ -904,6 +1777,119 @@ struct FormatterOptions {
     format_config_source: FormatConfigSource,
 }
 
+#[derive(clap::Args, Default)]
+struct InstrumentOptions {
+    #[clap(long, help = "Emit the duration of each instruction's execution")]
+    time_instrs: bool,
+    #[clap(long, help = "Report where the program spends its time")]
+    profile: bool,
+    #[clap(
+        long,
+        help = "Write the profile as folded stacks for flamegraph tools. \
+                Implies --profile."
+    )]
+    flame: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Report the array data allocated by each part of the program"
+    )]
+    profile_allocs: bool,
+    #[clap(
+        long,
+        conflicts_with = "replay",
+        help = "Record the program's inputs (time, random seed, stdin, files, \
+                network, etc.) to a file so that the run can be replayed"
+    )]
+    record: Option<PathBuf>,
+    #[clap(long, help = "Replay the inputs recorded with --record")]
+    replay: Option<PathBuf>,
+    #[clap(
+        long,
+        conflicts_with = "replay",
+        help = "Fix the random seed, freeze the clock, and sort directory \
+                listings so that the program's output is reproducible"
+    )]
+    deterministic: bool,
+    #[clap(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = MemoCache::DEFAULT_DIR,
+        help = "Remember the outputs of pure memoized functions across runs \
+                in a cache directory"
+    )]
+    memo_cache: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "LINES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        help = "Keep snapshots of the stack at the start of recently run lines, \
+                and show them if the program fails"
+    )]
+    keep_history: Option<usize>,
+}
+
+#[derive(clap::Args, Clone, Default)]
+struct DiagnosticOptions {
+    #[clap(
+        long,
+        value_name = "KIND",
+        help = "Don't report diagnostics of this kind (one of info, style, advice, warning, or all)"
+    )]
+    allow: Vec<DiagnosticKindArg>,
+    #[clap(
+        long,
+        value_name = "KIND",
+        help = "Report diagnostics of this kind as errors (one of info, style, advice, warning, or all)"
+    )]
+    deny: Vec<DiagnosticKindArg>,
+}
+
+#[derive(Clone)]
+struct DiagnosticKindArg(Vec<DiagnosticKind>);
+
+impl FromStr for DiagnosticKindArg {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            Ok(DiagnosticKindArg(DiagnosticKind::ALL.to_vec()))
+        } else {
+            s.parse().map(|kind| DiagnosticKindArg(vec![kind]))
+        }
+    }
+}
+
+impl DiagnosticOptions {
+    fn apply(&self, comp: &mut Compiler) {
+        for (kinds, level) in [
+            (&self.allow, DiagnosticLevel::Allow),
+            (&self.deny, DiagnosticLevel::Deny),
+        ] {
+            for kind in kinds.iter().flat_map(|arg| &arg.0) {
+                comp.diagnostic_level(*kind, level);
+            }
+        }
+    }
+}
+
+#[derive(clap::Args, Clone, Copy, Default)]
+struct ComptimeOptions {
+    #[clap(
+        long,
+        help = "Let comptime code and code macros read files and access the network"
+    )]
+    unsandboxed_comptime: bool,
+}
+
+impl ComptimeOptions {
+    fn apply(&self, comp: &mut Compiler) {
+        comp.comptime_sandbox(!self.unsandboxed_comptime);
+    }
+}
+
 #[cfg(feature = "audio")]
 #[derive(clap::Args)]
 struct AudioOptions {
@@ -1073,18 +2059,242 @@ fn update(main: bool, check: bool, mut features: Vec<String>) {
     }
 }
 
-fn format_single_file(path: PathBuf, config: &FormatConfig) -> Result<(), UiuaError> {
-    format_file(path, config)?;
+fn format_single_file(path: PathBuf, config: &FormatConfig, ascii: bool) -> Result<(), UiuaError> {
+    if ascii {
+        let input =
+            fs::read_to_string(&path).map_err(|e| UiuaErrorKind::Load(path.clone(), e.into()))?;
+        let output = format_ascii(&input, config)?;
+        if output != input {
+            fs::write(&path, output).map_err(|e| UiuaErrorKind::Format(path, e.into()))?;
+        }
+    } else {
+        format_file(path, config)?;
+    }
     Ok(())
 }
 
-fn format_multi_files(config: &FormatConfig) -> Result<(), UiuaError> {
+/// Print a diff for each file whose formatting would change, returning whether any would
+fn check_format(
+    path: Option<PathBuf>,
+    stdin_input: bool,
+    config: &FormatConfig,
+    ascii: bool,
+) -> UiuaResult<bool> {
+    let format = |input: &str| -> UiuaResult<String> {
+        if ascii {
+            format_ascii(input, config)
+        } else {
+            format_str(input, config).map(|formatted| formatted.output)
+        }
+    };
+    let color = default_color_output();
+    if stdin_input {
+        let mut input = String::new();
+        if let Err(e) = io::Read::read_to_string(&mut stdin().lock(), &mut input) {
+            eprintln!("Failed to read stdin: {e}");
+            exit(1);
+        }
+        let output = format(&input)?;
+        print!("{}", unified_diff(&input, &output, "<stdin>", color));
+        return Ok(output != input);
+    }
+    let paths = match path {
+        Some(path) => vec![path],
+        None => uiua_files_in(".".as_ref())?,
+    };
+    let mut changed = false;
+    for path in paths {
+        let input =
+            fs::read_to_string(&path).map_err(|e| UiuaErrorKind::Load(path.clone(), e.into()))?;
+        let output = format(&input)?;
+        if output != input {
+            changed = true;
+            let name = path.display().to_string();
+            print!("{}", unified_diff(&input, &output, &name, color));
+        }
+    }
+    Ok(changed)
+}
+
+/// Make a unified diff of the lines of two strings
+///
+/// Lines keep their endings, so differences in line endings or in the final newline are shown.
+fn unified_diff(old: &str, new: &str, name: &str, color: bool) -> String {
+    const CONTEXT: usize = 3;
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    // Only diff the part between the common prefix and suffix
+    let prefix = (old.iter().zip(&new)).take_while(|(a, b)| a == b).count();
+    let suffix = (old[prefix..].iter().rev())
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    // Longest common subsequence lengths of suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    // Each edit is a line, its kind, and its index in the old and new lines
+    let mut edits: Vec<(char, &str, usize, usize)> = Vec::new();
+    for (i, &line) in old.iter().enumerate().take(prefix) {
+        edits.push((' ', line, i, i));
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push((' ', a[i], prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(('-', a[i], prefix + i, prefix + j));
+            i += 1;
+        } else {
+            edits.push(('+', b[j], prefix + i, prefix + j));
+            j += 1;
+        }
+    }
+    for k in 0..suffix {
+        let (oi, ni) = (prefix + a.len() + k, prefix + b.len() + k);
+        edits.push((' ', old[oi], oi, ni));
+    }
+    // Group changes into hunks with some context around them
+    let mut out = String::new();
+    let changes: Vec<usize> = (edits.iter().enumerate())
+        .filter(|(_, (kind, ..))| *kind != ' ')
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return out;
+    }
+    out.push_str(&format!("--- {name}\n+++ {name} (formatted)\n"));
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k];
+        while k < changes.len() && changes[k] <= end + 2 * CONTEXT + 1 {
+            end = changes[k];
+            k += 1;
+        }
+        let end = (end + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+        let old_count = hunk.iter().filter(|e| e.0 != '+').count();
+        let new_count = hunk.iter().filter(|e| e.0 != '-').count();
+        let header = format!(
+            "@@ -{},{old_count} +{},{new_count} @@",
+            hunk[0].2 + 1,
+            hunk[0].3 + 1
+        );
+        out.push_str(&if color {
+            header.cyan().to_string()
+        } else {
+            header
+        });
+        out.push('\n');
+        for &(kind, line, ..) in hunk {
+            let (text, newline) = match line.strip_suffix('\n') {
+                Some(text) => (text, true),
+                None => (line, false),
+            };
+            let text = format!("{kind}{text}");
+            let text = match kind {
+                '+' if color => text.green().to_string(),
+                '-' if color => text.red().to_string(),
+                _ => text,
+            };
+            out.push_str(&text);
+            out.push('\n');
+            if !newline {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+fn format_multi_files(config: &FormatConfig, ascii: bool) -> Result<(), UiuaError> {
     for path in uiua_files_in(".".as_ref())? {
-        format_file(path, config)?;
+        format_single_file(path, config, ascii)?;
     }
     Ok(())
 }
 
+const REPL_HELP: &str = "
+clear - Clear the stack
+exit  - Exit the repl
+help  - Show this message
+_     - Push the values from the most recent line back onto the stack
+_N    - Push the values from N lines ago back onto the stack
+
+Lines with unclosed brackets continue onto the next line.
+Press ctrl+R to search the history.
+";
+
+/// The maximum number of previous results kept for recall
+const REPL_RESULTS: usize = 100;
+
+/// Line editor helper that keeps reading lines while brackets are unclosed
+struct ReplHelper;
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let (tokens, ..) = lex(ctx.input(), (), &mut Inputs::default());
+        let depth = tokens.iter().fold(0i32, |depth, token| match &token.value {
+            Token::Simple(
+                AsciiToken::OpenParen | AsciiToken::OpenBracket | AsciiToken::OpenCurly,
+            )
+            | Token::OpenModule => depth + 1,
+            Token::Simple(
+                AsciiToken::CloseParen | AsciiToken::CloseBracket | AsciiToken::CloseCurly,
+            )
+            | Token::CloseModule => depth - 1,
+            _ => depth,
+        });
+        Ok(if depth > 0 {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Parse a `_` or `_N` REPL command into how many results back to recall
+fn parse_recall(s: &str) -> Option<usize> {
+    let n = s.strip_prefix('_')?;
+    if n.is_empty() {
+        Some(1)
+    } else {
+        n.parse().ok().filter(|&n| n > 0)
+    }
+}
+
+/// The file the REPL's history is saved to between sessions
+fn repl_history_path() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".uiua_history"))
+}
+
 fn repl(mut env: Uiua, mut compiler: Compiler, color: bool, stack: bool, config: FormatConfig) {
     env = env.with_interrupt_hook(|| PRESSED_CTRL_C.swap(false, Ordering::Relaxed));
     compiler.pre_eval_mode(PreEvalMode::Line);
@@ -1092,19 +2302,20 @@ fn repl(mut env: Uiua, mut compiler: Compiler, color: bool, stack: bool, config:
         "Uiua {} (end with ctrl+C, type `help` for a list of commands)\n",
         env!("CARGO_PKG_VERSION")
     );
-    let mut line_reader = DefaultEditor::new().expect("Failed to read from Stdin");
+    let mut line_reader: Editor<ReplHelper, DefaultHistory> =
+        Editor::new().expect("Failed to read from Stdin");
+    line_reader.set_helper(Some(ReplHelper));
+    let history_path = repl_history_path();
+    if let Some(path) = &history_path {
+        _ = line_reader.load_history(path);
+    }
+    let mut results: Vec<Vec<Value>> = Vec::new();
     loop {
         let mut code = match line_reader.readline("    ") {
             Ok(code) => {
                 match code.trim() {
                     "help" => {
-                        println!(
-                            "\n\
-                            clear - Clear the stack \n\
-                            exit  - Exit the repl \n\
-                            help  - Show this message \n\
-                            "
-                        );
+                        println!("{REPL_HELP}");
                         continue;
                     }
                     "clear" | "cls" => {
@@ -1113,7 +2324,22 @@ fn repl(mut env: Uiua, mut compiler: Compiler, color: bool, stack: bool, config:
                         continue;
                     }
                     "exit" => break,
-                    _ => {}
+                    recall => {
+                        if let Some(n) = parse_recall(recall) {
+                            _ = line_reader.add_history_entry(recall);
+                            match results.iter().rev().nth(n - 1) {
+                                Some(values) => {
+                                    for val in values.clone() {
+                                        env.push(val);
+                                    }
+                                    env.format_settings()
+                                        .scoped(|| print_stack(env.stack(), color));
+                                }
+                                None => eprintln!("No result from {n} line(s) ago"),
+                            }
+                            continue;
+                        }
+                    }
                 }
                 code
             }
@@ -1144,7 +2370,14 @@ fn repl(mut env: Uiua, mut compiler: Compiler, color: bool, stack: bool, config:
 
         match res {
             Ok(()) => {
-                print_stack(env.stack(), color);
+                env.format_settings()
+                    .scoped(|| print_stack(env.stack(), color));
+                if !env.stack().is_empty() {
+                    if results.len() == REPL_RESULTS {
+                        results.remove(0);
+                    }
+                    results.push(env.stack().to_vec());
+                }
                 if !stack {
                     env.take_stack();
                 }
@@ -1156,11 +2389,283 @@ fn repl(mut env: Uiua, mut compiler: Compiler, color: bool, stack: bool, config:
                     env.push(val);
                 }
                 eprintln!("{}", e.report());
-                print_stack(env.stack(), color);
+                env.format_settings()
+                    .scoped(|| print_stack(env.stack(), color));
             }
         }
         compiler.assembly_mut().root.clear();
     }
+    if let Some(path) = &history_path {
+        _ = line_reader.append_history(path);
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Breakpoint {
+    Line(u16),
+    Binding(String),
+}
+
+impl Breakpoint {
+    fn parse(s: &str) -> Self {
+        match s.parse() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Binding(s.into()),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "line {line}"),
+            Breakpoint::Binding(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+enum StepMode {
+    /// Stop before the next instruction
+    Instruction,
+    /// Stop when the line changes without entering a function
+    Line { line: u16, depth: usize },
+    /// Stop only at breakpoints
+    Continue,
+}
+
+struct Debugger {
+    path: PathBuf,
+    editor: DefaultEditor,
+    mode: StepMode,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<String>,
+    prev_line: u16,
+    prev_depth: usize,
+}
+
+const DEBUG_HELP: &str = "
+step, s      - Run the next instruction
+next, n      - Run until the next line, stepping over function calls
+continue, c  - Run until a breakpoint is hit
+break, b     - Set a breakpoint at a line number or binding name, or list breakpoints
+delete, d    - Delete a breakpoint by number, or all breakpoints
+watch, w     - Add an expression to evaluate at every stop, or list watches
+unwatch, u   - Delete a watch by number, or all watches
+stack, p     - Show the stack
+where, bt    - Show the call stack
+list, l      - Show the code around the current instruction
+quit, q      - Stop the program
+help, h      - Show this message
+
+Anything else is evaluated as Uiua code on a copy of the stack
+";
+
+fn debug(path: &Path, args: Vec<String>, breakpoints: Vec<String>) {
+    let breakpoints: Vec<_> = breakpoints.iter().map(|bp| Breakpoint::parse(bp)).collect();
+    let debugger = Mutex::new(Debugger {
+        path: path.into(),
+        editor: DefaultEditor::new().expect("Failed to read from Stdin"),
+        mode: if breakpoints.is_empty() {
+            StepMode::Instruction
+        } else {
+            StepMode::Continue
+        },
+        breakpoints,
+        watches: Vec::new(),
+        prev_line: 0,
+        prev_depth: 0,
+    });
+    println!(
+        "Debugging {} (type `help` for a list of commands)",
+        path.display()
+    );
+    let mut rt = Uiua::with_native_sys()
+        .with_file_path(path)
+        .with_args(args)
+        .with_exec_hook(move |env, span| debugger.lock().stop(env, span));
+    let res = rt.compile_run(|comp| {
+        comp.pre_eval_mode(PreEvalMode::Lazy)
+            .print_diagnostics(true)
+            .load_file(path)
+    });
+    rt.print_reports();
+    match res {
+        Ok(_) => {
+            println!("Program finished");
+            rt.format_settings()
+                .scoped(|| print_stack(&rt.take_stack(), true));
+        }
+        Err(e) if matches!(e.kind, UiuaErrorKind::Interrupted) => {}
+        Err(e) => {
+            println!("{}", e.report());
+            exit(1);
+        }
+    }
+}
+
+impl Debugger {
+    fn stop(&mut self, env: &Uiua, span: &CodeSpan) -> UiuaResult {
+        let frames = env.call_stack();
+        let depth = frames.len();
+        let line = span.start.line;
+        let in_file = matches!(&span.src, InputSrc::File(path) if **path == *self.path);
+        let entered_line = in_file && line != self.prev_line;
+        let entered = frames.get(self.prev_depth.min(depth)..).unwrap_or_default();
+        let hit = (self.breakpoints.iter()).position(|bp| match bp {
+            Breakpoint::Line(bp_line) => entered_line && *bp_line == line,
+            Breakpoint::Binding(name) => (entered.iter())
+                .any(|frame| frame.id.as_ref().is_some_and(|id| *id == name.as_str())),
+        });
+        if in_file {
+            self.prev_line = line;
+        }
+        self.prev_depth = depth;
+        let stop = hit.is_some()
+            || match self.mode {
+                StepMode::Instruction => true,
+                StepMode::Line {
+                    line: prev_line,
+                    depth: prev_depth,
+                } => depth < prev_depth || depth == prev_depth && in_file && line != prev_line,
+                StepMode::Continue => false,
+            };
+        if !stop {
+            return Ok(());
+        }
+        if let Some(i) = hit {
+            println!("Breakpoint {}: {}", i + 1, self.breakpoints[i]);
+        }
+        show_code(env, span, 0);
+        self.show_watches(env);
+        loop {
+            let input = match self.editor.readline("(debug) ") {
+                Ok(input) => input,
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => {
+                    return Err(UiuaErrorKind::Interrupted.into())
+                }
+                Err(_) => panic!("Failed to read from Stdin"),
+            };
+            let input = input.trim();
+            if !input.is_empty() {
+                _ = self.editor.add_history_entry(input);
+            }
+            let (command, arg) = (input.split_once(' '))
+                .map(|(command, arg)| (command, arg.trim()))
+                .unwrap_or((input, ""));
+            match command {
+                "" | "s" | "step" => {
+                    self.mode = StepMode::Instruction;
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    self.mode = StepMode::Line { line, depth };
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.mode = StepMode::Continue;
+                    return Ok(());
+                }
+                "b" | "break" if arg.is_empty() => {
+                    for (i, bp) in self.breakpoints.iter().enumerate() {
+                        println!("{}: {bp}", i + 1);
+                    }
+                }
+                "b" | "break" => {
+                    let bp = Breakpoint::parse(arg);
+                    if !self.breakpoints.contains(&bp) {
+                        self.breakpoints.push(bp);
+                    }
+                }
+                "d" | "delete" => remove_numbered(&mut self.breakpoints, arg),
+                "w" | "watch" if arg.is_empty() => self.show_watches(env),
+                "w" | "watch" => {
+                    self.watches.push(arg.into());
+                    show_eval(env, arg);
+                }
+                "u" | "unwatch" => remove_numbered(&mut self.watches, arg),
+                "p" | "stack" => {
+                    if env.stack().is_empty() {
+                        println!("(empty stack)");
+                    } else {
+                        env.format_settings()
+                            .scoped(|| print_stack(env.stack(), true));
+                    }
+                }
+                "bt" | "where" => {
+                    for frame in frames.iter().rev() {
+                        match &frame.id {
+                            Some(id) => println!("  in {id} at {}", frame.span),
+                            None => println!("  at {}", frame.span),
+                        }
+                    }
+                }
+                "l" | "list" => show_code(env, span, 3),
+                "q" | "quit" => return Err(UiuaErrorKind::Interrupted.into()),
+                "h" | "help" => println!("{DEBUG_HELP}"),
+                _ => show_eval(env, input),
+            }
+        }
+    }
+    fn show_watches(&self, env: &Uiua) {
+        for (i, watch) in self.watches.iter().enumerate() {
+            print!("{}: {watch} = ", i + 1);
+            show_eval(env, watch);
+        }
+    }
+}
+
+/// Remove the item with the given 1-based number, or all items if there is no number
+fn remove_numbered<T>(items: &mut Vec<T>, arg: &str) {
+    if arg.is_empty() {
+        items.clear();
+    } else if let Some(i) = (arg.parse::<usize>().ok()).filter(|&i| 0 < i && i <= items.len()) {
+        items.remove(i - 1);
+    } else {
+        println!("No item {arg}");
+    }
+}
+
+/// Evaluate some code on a copy of the stack and show the top value
+fn show_eval(env: &Uiua, code: &str) {
+    let mut rt = Uiua::with_safe_sys();
+    for val in env.stack() {
+        rt.push(val.clone());
+    }
+    match rt.run_str(code) {
+        Ok(_) => match rt.take_stack().pop() {
+            Some(val) => println!("{}", val.show()),
+            None => println!("(empty stack)"),
+        },
+        Err(e) => println!("{}", e.report()),
+    }
+}
+
+/// Show the line of a span, and `context` lines around it
+fn show_code(env: &Uiua, span: &CodeSpan, context: usize) {
+    println!("{}", span.to_string().bright_black());
+    let src = env.inputs().get(&span.src);
+    let line = span.start.line as usize;
+    let first = line.saturating_sub(context).max(1);
+    for (i, text) in src
+        .lines()
+        .enumerate()
+        .skip(first - 1)
+        .take(line + context + 1 - first)
+    {
+        let n = i + 1;
+        let marker = if n == line { ">" } else { " " };
+        println!("{marker}{n:>4} | {text}");
+        if n == line {
+            let start = span.start.col.saturating_sub(1) as usize;
+            let len = if span.end.line == span.start.line {
+                span.end.col.saturating_sub(span.start.col).max(1) as usize
+            } else {
+                text.chars().count().saturating_sub(start).max(1)
+            };
+            let underline = "^".repeat(len);
+            println!("      | {}{}", " ".repeat(start), underline.bright_green());
+        }
+    }
 }
 
 const NOADIC: Color = Color::Red;
@@ -1307,7 +2812,93 @@ fn update_modules(modules: &[PathBuf]) -> io::Result<()> {
     Ok(())
 }
 
-fn check(path: Option<PathBuf>) -> UiuaResult {
+fn fail_package<T>(e: String) -> T {
+    eprintln!("{}", e.red());
+    exit(1)
+}
+
+fn add_package(package: &str, name: Option<String>) -> Result<(), String> {
+    let (git, req) = match package.rsplit_once('@') {
+        Some((git, req)) if !req.contains(['/', ':']) => (git, Some(req.parse::<VersionReq>()?)),
+        _ => (package, None),
+    };
+    let git = package::normalize_git_url(git);
+    let name = name.unwrap_or_else(|| git.rsplit('/').next().unwrap_or(&git).into());
+    let req = if let Some(req) = req {
+        req
+    } else {
+        let latest = (package::fetch_versions(&git)?.into_iter())
+            .map(|v| v.version)
+            .max()
+            .ok_or_else(|| format!("{git} has no version tags"))?;
+        VersionReq::compatible(latest)
+    };
+    let mut manifest = if Path::new(package::MANIFEST_FILE).exists() {
+        Manifest::load(package::MANIFEST_FILE)?
+    } else {
+        Manifest::default()
+    };
+    println!("{} {name} {req}", "Adding".bold().bright_green());
+    (manifest.dependencies).insert(name, Dependency { git, version: req });
+    lock_packages(Some(&manifest))?;
+    manifest.save(package::MANIFEST_FILE)
+}
+
+fn lock_packages(manifest: Option<&Manifest>) -> Result<(), String> {
+    let loaded;
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => {
+            loaded = Manifest::load(package::MANIFEST_FILE)?;
+            &loaded
+        }
+    };
+    let lockfile = Lockfile::resolve(manifest)?;
+    for package in &lockfile.packages {
+        println!(
+            "{} {} {}",
+            "Locked".bold().bright_green(),
+            package.name,
+            package.version
+        );
+    }
+    lockfile.save(package::LOCKFILE)
+}
+
+fn install_packages() -> Result<(), String> {
+    let lockfile = Lockfile::load(package::LOCKFILE)?;
+    for package in &lockfile.packages {
+        let path = package::install(package)?;
+        println!(
+            "{} {} {} to {}",
+            "Installed".bold().bright_green(),
+            package.name,
+            package.version,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Print the stack heights before and after each spanned node in a file
+fn print_stack_heights(asm: &Assembly, path: &Path) {
+    println!();
+    for (span, heights) in asm.stack_heights() {
+        if span.src != *path {
+            continue;
+        }
+        let text = span.as_str(&asm.inputs, |s| s.lines().next().unwrap_or("").to_string());
+        println!(
+            "{:>5} {} → {}  {}",
+            format!("{}:{}", span.start.line, span.start.col),
+            heights.before,
+            heights.after,
+            text.bright_white()
+        );
+    }
+}
+
+fn check(path: Option<PathBuf>, types: bool, trace: bool) -> UiuaResult {
     let paths = uiua_files(path.as_deref())?;
     let path_count = paths.len();
     let mut successes = 0;
@@ -1326,10 +2917,26 @@ fn check(path: Option<PathBuf>) -> UiuaResult {
         );
         stdout().flush().unwrap();
         let mut comp = Compiler::with_backend(NativeSys);
-        if let Err(e) = comp.load_file(path) {
+        comp.type_check(types);
+        if trace {
+            // Keep the code of constant lines so that it can be traced
+            comp.pre_eval_mode(PreEvalMode::Lazy);
+        }
+        let res = comp.load_file(&path).map(drop);
+        if types {
+            for diag in comp.take_diagnostics() {
+                if diag.kind == DiagnosticKind::Warning {
+                    println!("\n{}", diag.report());
+                }
+            }
+        }
+        if let Err(e) = res {
             println!("\n{}", e.report());
         } else {
             successes += 1;
+            if trace {
+                print_stack_heights(comp.assembly(), &path);
+            }
         }
     }
     let message = format!(
@@ -1355,6 +2962,55 @@ fn check(path: Option<PathBuf>) -> UiuaResult {
     Ok(())
 }
 
+fn module_doc(path: Option<PathBuf>, output: Option<PathBuf>, html: bool) {
+    let path = match path {
+        Some(path) if path.is_dir() => path.join("lib.ua"),
+        Some(path) => path,
+        None if Path::new("lib.ua").exists() => PathBuf::from("lib.ua"),
+        None => working_file_path().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1)
+        }),
+    };
+    // A package's lib.ua is named after the package directory
+    let name = if path.file_name().is_some_and(|name| name == "lib.ua") {
+        (path.canonicalize().ok())
+            .and_then(|path| Some(path.parent()?.file_name()?.to_string_lossy().into_owned()))
+    } else {
+        None
+    }
+    .or_else(|| Some(path.file_stem()?.to_string_lossy().into_owned()))
+    .unwrap_or_default();
+    let mut comp = Compiler::with_backend(NativeSys);
+    comp.print_diagnostics(true)
+        .load_file(&path)
+        .unwrap_or_else(fail);
+    let markdown = ModuleDocs::new(name.as_str(), &comp).to_markdown();
+    let text = if html {
+        let mut options = comrak::Options::default();
+        options.extension.header_ids = Some(String::new());
+        let body = comrak::markdown_to_html(&markdown, &options);
+        format!(
+            "<!DOCTYPE html><html><head>\
+            <meta charset=\"utf-8\">\
+            <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+            <link rel=\"stylesheet\" href=\"https://uiua.org/styles.css\">\
+            <title>{name}</title>\
+            </head><body><div id=top>{body}</div></body></html>"
+        )
+    } else {
+        markdown
+    };
+    if let Some(output) = output {
+        if let Err(e) = fs::write(&output, text) {
+            eprintln!("Failed to write {}: {e}", output.display());
+            exit(1);
+        }
+    } else {
+        print!("{text}");
+    }
+}
+
 fn find(path: Option<PathBuf>, mut text: String, raw: bool) -> UiuaResult {
     if raw {
         colored::control::set_override(false);
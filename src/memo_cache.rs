@@ -0,0 +1,275 @@
+//! A disk-backed cache for [`memo`](crate::Primitive::Memo)
+
+use std::{
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use crate::{Array, Boxed, Uiua, Value};
+
+/// The extension of memo cache entry files
+const EXTENSION: &str = "memo";
+
+/// A disk-backed cache for [`memo`](crate::Primitive::Memo)
+///
+/// With a cache, the outputs of pure memoized functions are remembered across runs.
+/// Each entry is keyed by a hash of the function, including the functions and constants it uses,
+/// and a hash of its arguments.
+/// The arguments are stored with the outputs and compared when an entry is read,
+/// so a hash collision is a cache miss rather than a wrong result.
+/// When the cache grows past its size limit, the least recently used entries are evicted.
+///
+/// Use it with [`Uiua::with_memo_cache`](crate::Uiua::with_memo_cache).
+#[derive(Debug, Clone)]
+pub struct MemoCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl MemoCache {
+    /// The directory `uiua run --memo-cache` uses by default
+    pub const DEFAULT_DIR: &'static str = ".uiua-memo";
+    /// The default size limit, in bytes
+    pub const DEFAULT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+    /// Create a cache that stores entries in a directory
+    ///
+    /// The directory is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        MemoCache {
+            dir: dir.into(),
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+    /// Set the size limit, in bytes
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+    /// Get the cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// Get the size limit, in bytes
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+    /// Get the number of entries and their total size in bytes
+    pub fn usage(&self) -> io::Result<(usize, u64)> {
+        let entries = self.entries()?;
+        Ok((entries.len(), entries.iter().map(|(_, len, _)| len).sum()))
+    }
+    /// Remove all entries
+    pub fn clear(&self) -> io::Result<()> {
+        for (_, _, path) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+    fn path(&self, func: u128, args: &[Value]) -> PathBuf {
+        let mut hasher = StableHasher::default();
+        args.hash(&mut hasher);
+        let args = hasher.finish128();
+        self.dir
+            .join(format!("{func:032x}-{args:032x}.{EXTENSION}"))
+    }
+    /// Get the outputs of an entry, marking it as recently used
+    pub(crate) fn get(&self, func: u128, args: &[Value], env: &Uiua) -> Option<Vec<Value>> {
+        let path = self.path(func, args);
+        let bytes = fs::read(&path).ok()?;
+        let Value::Box(entry) = Value::from_binary(&bytes, env).ok()? else {
+            return None;
+        };
+        let [Boxed(Value::Box(stored_args)), Boxed(Value::Box(outputs))] =
+            <[Boxed; 2]>::try_from(entry.data.into_iter().collect::<Vec<_>>()).ok()?
+        else {
+            return None;
+        };
+        if stored_args.row_count() != args.len()
+            || (stored_args.data.iter())
+                .zip(args)
+                .any(|(Boxed(a), b)| a != b)
+        {
+            return None;
+        }
+        if let Ok(file) = File::options().write(true).open(&path) {
+            _ = file.set_modified(SystemTime::now());
+        }
+        Some(outputs.data.into_iter().map(|Boxed(val)| val).collect())
+    }
+    /// Store an entry, evicting old ones if the cache is too big
+    pub(crate) fn insert(
+        &self,
+        func: u128,
+        args: &[Value],
+        outputs: &[Value],
+        env: &Uiua,
+    ) -> io::Result<()> {
+        let boxed = |vals: &[Value]| -> Value {
+            Array::<Boxed>::from_iter(vals.iter().cloned().map(Boxed)).into()
+        };
+        let entry: Value =
+            Array::<Boxed>::from_iter([Boxed(boxed(args)), Boxed(boxed(outputs))]).into();
+        let bytes = entry
+            .to_binary(env)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if bytes.len() as u64 > self.max_bytes {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(func, args);
+        // Write to a temporary file first so that other runs never see a partial entry
+        let temp = path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temp, bytes)
+            .and_then(|_| fs::rename(&temp, &path))
+            .inspect_err(|_| _ = fs::remove_file(&temp))?;
+        self.evict()
+    }
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        entries.sort_unstable_by_key(|(modified, ..)| *modified);
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(path) {
+                Ok(()) => total -= len,
+                // Another run may have evicted it already
+                Err(e) if e.kind() == io::ErrorKind::NotFound => total -= len,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+    fn entries(&self) -> io::Result<Vec<(SystemTime, u64, PathBuf)>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for entry in dir {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                // It may have been removed since the directory was read
+                let Ok(meta) = fs::metadata(&path) else {
+                    continue;
+                };
+                entries.push((meta.modified()?, meta.len(), path));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// A 128-bit FNV-1a hasher
+///
+/// Unlike [`DefaultHasher`](std::collections::hash_map::DefaultHasher),
+/// its output does not change between Rust releases, so it can name files that outlive a run.
+#[derive(Debug, Clone)]
+pub(crate) struct StableHasher(u128);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0x6c62272e07bb014262b821756295c58d)
+    }
+}
+
+impl StableHasher {
+    pub fn finish128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+    // Integers are written little-endian and at a fixed width,
+    // so that the hash is the same on every platform
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64)
+    }
+    fn finish(&self) -> u64 {
+        (self.0 ^ (self.0 >> 64)) as u64
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn memo_cache() {
+    use crate::{Array, Boxed, MemoCache, PreEvalMode, Uiua, Value};
+    let dir = std::env::temp_dir().join(format!("uiua_memo_cache_{}", std::process::id()));
+    let cache = MemoCache::new(&dir);
+    let run = |code: &str| {
+        let mut env = Uiua::with_safe_sys().with_memo_cache(cache.clone());
+        env.compile_run(|comp| comp.pre_eval_mode(PreEvalMode::Lazy).load_str(code))
+            .unwrap_or_else(|e| panic!("{code}: {e}"));
+        env.pop(1).unwrap()
+    };
+    assert_eq!(run("F ← memo(×2)\nF 5"), Value::from(10));
+    assert_eq!(cache.usage().unwrap().0, 1);
+    // Replace the entry to check that the next run reads it
+    let env = Uiua::with_safe_sys();
+    let entry = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+    let write_fake = |arg: i32, output: i32| {
+        let list = |val: i32| Boxed(Array::<Boxed>::from_iter([Boxed(val.into())]).into());
+        let fake = Value::from(Array::<Boxed>::from_iter([list(arg), list(output)]));
+        std::fs::write(entry.path(), fake.to_binary(&env).unwrap()).unwrap();
+    };
+    write_fake(5, 99);
+    assert_eq!(run("F ← memo(×2)\nF 5"), Value::from(99));
+    // An entry whose stored arguments differ is not used
+    write_fake(6, 99);
+    assert_eq!(run("F ← memo(×2)\nF 5"), Value::from(10));
+    assert_eq!(cache.usage().unwrap().0, 1);
+    // Changing the function, a constant it uses, or the arguments misses the cache
+    assert_eq!(run("F ← memo(×3)\nF 5"), Value::from(15));
+    assert_eq!(run("N ← 6\nF ← memo(×N)\nF 5"), Value::from(30));
+    assert_eq!(run("N ← 4\nF ← memo(×N)\nF 5"), Value::from(20));
+    assert_eq!(run("F ← memo(×2)\nF 6"), Value::from(12));
+    assert_eq!(cache.usage().unwrap().0, 5);
+    // Impure functions are not cached
+    run("F ← memo(+⚂)\nF 5");
+    assert_eq!(cache.usage().unwrap().0, 5);
+    // Old entries are evicted when the cache is too big
+    let (_, size) = cache.usage().unwrap();
+    let small = cache.clone().with_max_bytes(size);
+    let mut env = Uiua::with_safe_sys().with_memo_cache(small.clone());
+    env.compile_run(|comp| {
+        comp.pre_eval_mode(PreEvalMode::Lazy)
+            .load_str("F ← memo(×2)\nF 7")
+    })
+    .unwrap();
+    let (count, new_size) = small.usage().unwrap();
+    assert_eq!(count, 5);
+    assert!(new_size <= size);
+    cache.clear().unwrap();
+    assert_eq!(cache.usage().unwrap(), (0, 0));
+    std::fs::remove_dir_all(dir).unwrap();
+}
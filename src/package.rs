@@ -0,0 +1,649 @@
+//! Versioned packages built on top of git modules
+//!
+//! A project lists its dependencies in a `uiua.toml` manifest:
+//!
+//! ```toml
+//! [package]
+//! name = "my-project"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! base = { git = "https://github.com/owner/base", version = "^1.2" }
+//! ```
+//!
+//! The published versions of a package are the tags of its git repository
+//! that look like versions, such as `v1.2.3` or `1.2.3`.
+//! Resolving the manifest picks the newest version of each package that satisfies
+//! every requirement on it, including those from other packages' manifests,
+//! and records the result in a `uiua.lock` lockfile.
+//!
+//! Locked packages are cloned into `uiua-modules/<owner>/<repo>@<version>`
+//! and can be imported by name with `~ "pkg:name"`.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use serde::*;
+
+/// The name of the package manifest file
+pub const MANIFEST_FILE: &str = "uiua.toml";
+/// The name of the package lockfile
+pub const LOCKFILE: &str = "uiua.lock";
+/// The directory packages are installed into
+pub const MODULES_DIR: &str = "uiua-modules";
+
+/// A package version of the form `major.minor.patch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major version
+    pub major: u64,
+    /// The minor version
+    pub minor: u64,
+    /// The patch version
+    pub patch: u64,
+}
+
+/// A requirement on a package's version
+///
+/// This is a comma-separated list of comparisons, all of which must match.
+/// The supported operators are `^`, `~`, `=`, `>`, `>=`, `<`, and `<=`.
+/// A version with no operator is treated as `^`, and `*` matches any version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Comparator {
+    op: VersionOp,
+    version: Version,
+    /// The number of version parts that were given
+    parts: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VersionOp {
+    Caret,
+    Tilde,
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+/// A `uiua.toml` package manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Information about the package itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<PackageInfo>,
+    /// The package's dependencies by name
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+/// The `[package]` section of a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageInfo {
+    /// The package's name
+    pub name: String,
+    /// The package's version
+    pub version: Version,
+}
+
+/// A dependency in a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dependency {
+    /// The URL of the dependency's git repository
+    pub git: String,
+    /// The versions of the dependency that may be used
+    pub version: VersionReq,
+}
+
+/// A `uiua.lock` lockfile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// The resolved packages, sorted by name
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A package pinned to an exact version and commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    /// The package's name
+    pub name: String,
+    /// The URL of the package's git repository
+    pub git: String,
+    /// The resolved version
+    pub version: Version,
+    /// The commit the version's tag points to
+    pub commit: String,
+}
+
+/// A version of a package that is available to install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvailableVersion {
+    /// The version
+    pub version: Version,
+    /// The commit the version's tag points to
+    pub commit: String,
+}
+
+impl Version {
+    /// Create a new version
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+    /// Parse a version that may be missing its minor or patch parts
+    ///
+    /// Returns the version and the number of parts that were given.
+    fn parse_partial(s: &str) -> Result<(Self, usize), String> {
+        let s = s.trim();
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let mut nums = [0; 3];
+        let mut parts = 0;
+        for (i, part) in s.split('.').enumerate() {
+            if i == 3 {
+                return Err(format!("Invalid version `{s}`: too many parts"));
+            }
+            nums[i] = (part.parse())
+                .map_err(|_| format!("Invalid version `{s}`: `{part}` is not a number"))?;
+            parts += 1;
+        }
+        Ok((Version::new(nums[0], nums[1], nums[2]), parts))
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (version, parts) = Version::parse_partial(s)?;
+        if parts != 3 {
+            return Err(format!("Invalid version `{s}`: expected major.minor.patch"));
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl VersionReq {
+    /// A requirement that matches any version
+    pub const ANY: Self = VersionReq {
+        comparators: Vec::new(),
+    };
+    /// A requirement compatible with the given version, as with `^`
+    pub fn compatible(version: Version) -> Self {
+        VersionReq {
+            comparators: vec![Comparator {
+                op: VersionOp::Caret,
+                version,
+                parts: 3,
+            }],
+        }
+    }
+    /// Check if a version satisfies the requirement
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+    }
+}
+
+impl Comparator {
+    fn matches(&self, v: &Version) -> bool {
+        let req = self.version;
+        match self.op {
+            VersionOp::Caret => *v >= req && *v < self.caret_bound(),
+            VersionOp::Tilde => *v >= req && *v < self.tilde_bound(),
+            VersionOp::Exact => match self.parts {
+                1 => v.major == req.major,
+                2 => v.major == req.major && v.minor == req.minor,
+                _ => *v == req,
+            },
+            VersionOp::Greater => *v > req,
+            VersionOp::GreaterEq => *v >= req,
+            VersionOp::Less => *v < req,
+            VersionOp::LessEq => *v <= req,
+        }
+    }
+    /// The first version that a `^` requirement excludes
+    fn caret_bound(&self) -> Version {
+        let Version {
+            major,
+            minor,
+            patch,
+        } = self.version;
+        if major > 0 || self.parts == 1 {
+            Version::new(major + 1, 0, 0)
+        } else if minor > 0 || self.parts == 2 {
+            Version::new(0, minor + 1, 0)
+        } else {
+            Version::new(0, 0, patch + 1)
+        }
+    }
+    /// The first version that a `~` requirement excludes
+    fn tilde_bound(&self) -> Version {
+        let Version { major, minor, .. } = self.version;
+        if self.parts == 1 {
+            Version::new(major + 1, 0, 0)
+        } else {
+            Version::new(major, minor + 1, 0)
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(VersionReq::ANY);
+        }
+        let mut comparators = Vec::new();
+        for part in s.split(',') {
+            let part = part.trim();
+            let (op, rest) = [
+                (">=", VersionOp::GreaterEq),
+                ("<=", VersionOp::LessEq),
+                (">", VersionOp::Greater),
+                ("<", VersionOp::Less),
+                ("=", VersionOp::Exact),
+                ("^", VersionOp::Caret),
+                ("~", VersionOp::Tilde),
+            ]
+            .into_iter()
+            .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((VersionOp::Caret, part));
+            let (version, parts) = Version::parse_partial(rest)
+                .map_err(|e| format!("Invalid version requirement `{s}`: {e}"))?;
+            comparators.push(Comparator { op, version, parts });
+        }
+        Ok(VersionReq { comparators })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.comparators.is_empty() {
+            return write!(f, "*");
+        }
+        for (i, c) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            let op = match c.op {
+                VersionOp::Caret => "^",
+                VersionOp::Tilde => "~",
+                VersionOp::Exact => "=",
+                VersionOp::Greater => ">",
+                VersionOp::GreaterEq => ">=",
+                VersionOp::Less => "<",
+                VersionOp::LessEq => "<=",
+            };
+            let Version {
+                major,
+                minor,
+                patch,
+            } = c.version;
+            match c.parts {
+                1 => write!(f, "{op}{major}")?,
+                2 => write!(f, "{op}{major}.{minor}")?,
+                _ => write!(f, "{op}{major}.{minor}.{patch}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for VersionReq {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionReq {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Manifest {
+    /// Load a manifest from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid manifest {}: {e}", path.display()))
+    }
+    /// Save the manifest to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+impl Lockfile {
+    /// Load a lockfile from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid lockfile {}: {e}", path.display()))
+    }
+    /// Save the lockfile to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let text =
+            format!("# This file is generated by `uiua module lock`. Do not edit it.\n\n{text}");
+        fs::write(path, text).map_err(|e| e.to_string())
+    }
+    /// Get a locked package by name
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+    /// Resolve a manifest's dependencies using git
+    ///
+    /// Packages are installed as they are resolved so that their own manifests can be read.
+    pub fn resolve(manifest: &Manifest) -> Result<Self, String> {
+        Self::resolve_with(manifest, fetch_versions, |locked| {
+            let path = install(locked)?.join(MANIFEST_FILE);
+            if path.exists() {
+                Manifest::load(path)
+            } else {
+                Ok(Manifest::default())
+            }
+        })
+    }
+    /// Resolve a manifest's dependencies
+    ///
+    /// `versions` lists the available versions at a git URL, and
+    /// `manifest_of` gets the manifest of a locked package.
+    pub fn resolve_with(
+        manifest: &Manifest,
+        mut versions: impl FnMut(&str) -> Result<Vec<AvailableVersion>, String>,
+        mut manifest_of: impl FnMut(&LockedPackage) -> Result<Manifest, String>,
+    ) -> Result<Self, String> {
+        struct Requirement {
+            req: VersionReq,
+            /// The package that made the requirement, or `None` for the root manifest
+            from: Option<String>,
+        }
+        let mut gits: BTreeMap<String, String> = BTreeMap::new();
+        let mut reqs: BTreeMap<String, Vec<Requirement>> = BTreeMap::new();
+        let add_deps = |gits: &mut BTreeMap<String, String>,
+                        reqs: &mut BTreeMap<String, Vec<Requirement>>,
+                        deps: &BTreeMap<String, Dependency>,
+                        from: Option<&str>|
+         -> Result<(), String> {
+            for (name, dep) in deps {
+                let git = normalize_git_url(&dep.git);
+                match gits.get(name) {
+                    Some(existing) if *existing != git => {
+                        return Err(format!(
+                            "Package `{name}` is required from both {existing} and {git}"
+                        ))
+                    }
+                    Some(_) => {}
+                    None => {
+                        gits.insert(name.clone(), git);
+                    }
+                }
+                reqs.entry(name.clone()).or_default().push(Requirement {
+                    req: dep.version.clone(),
+                    from: from.map(Into::into),
+                });
+            }
+            Ok(())
+        };
+        add_deps(&mut gits, &mut reqs, &manifest.dependencies, None)?;
+        let mut available: HashMap<String, Vec<AvailableVersion>> = HashMap::new();
+        let mut chosen: BTreeMap<String, LockedPackage> = BTreeMap::new();
+        // Re-pick packages until every chosen version satisfies every requirement
+        for _ in 0..1000 {
+            let Some(name) = reqs
+                .iter()
+                .filter(|(_, reqs)| !reqs.is_empty())
+                .find(|(name, reqs)| {
+                    chosen
+                        .get(*name)
+                        .map_or(true, |c| !reqs.iter().all(|r| r.req.matches(&c.version)))
+                })
+                .map(|(name, _)| name.clone())
+            else {
+                // Drop packages that are no longer required by anything
+                chosen.retain(|name, _| reqs.get(name).is_some_and(|r| !r.is_empty()));
+                return Ok(Lockfile {
+                    packages: chosen.into_values().collect(),
+                });
+            };
+            let git = gits[&name].clone();
+            if !available.contains_key(&git) {
+                let mut list = versions(&git)?;
+                list.sort_by_key(|v| Reverse(v.version));
+                available.insert(git.clone(), list);
+            }
+            let name_reqs = &reqs[&name];
+            let Some(pick) = (available[&git].iter())
+                .find(|v| name_reqs.iter().all(|r| r.req.matches(&v.version)))
+            else {
+                let reqs: Vec<String> = (name_reqs.iter())
+                    .map(|r| match &r.from {
+                        Some(from) => format!("{} (from {from})", r.req),
+                        None => r.req.to_string(),
+                    })
+                    .collect();
+                return Err(format!(
+                    "No version of `{name}` satisfies {}",
+                    reqs.join(" and ")
+                ));
+            };
+            let locked = LockedPackage {
+                name: name.clone(),
+                git,
+                version: pick.version,
+                commit: pick.commit.clone(),
+            };
+            // Replace the requirements of the previously chosen version
+            for reqs in reqs.values_mut() {
+                reqs.retain(|r| r.from.as_ref() != Some(&name));
+            }
+            let deps = manifest_of(&locked)?.dependencies;
+            add_deps(&mut gits, &mut reqs, &deps, Some(&name))?;
+            chosen.insert(name, locked);
+        }
+        Err("Package resolution did not finish".into())
+    }
+}
+
+impl LockedPackage {
+    /// The path the package is installed to
+    pub fn path(&self) -> PathBuf {
+        let mut parts = self.git.trim_end_matches('/').rsplit('/');
+        let repo = parts.next().unwrap_or(&self.name);
+        let owner = parts.next().unwrap_or("_");
+        Path::new(MODULES_DIR)
+            .join(owner)
+            .join(format!("{repo}@{}", self.version))
+    }
+}
+
+/// Normalize a git URL so that equivalent URLs compare equal
+pub fn normalize_git_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+    if url.contains("://") {
+        url.into()
+    } else {
+        format!("https://{url}")
+    }
+}
+
+/// List the versions published as tags of a git repository
+pub fn fetch_versions(url: &str) -> Result<Vec<AvailableVersion>, String> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", url])
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to list versions of {url}: {err}"));
+    }
+    let mut versions: BTreeMap<Version, AvailableVersion> = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((commit, tag)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some(tag) = tag.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        // Annotated tags are listed twice, and the peeled one has the commit
+        let (tag, peeled) = match tag.strip_suffix("^{}") {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let Ok(version) = tag.parse::<Version>() else {
+            continue;
+        };
+        if peeled || !versions.contains_key(&version) {
+            let commit = commit.into();
+            versions.insert(version, AvailableVersion { version, commit });
+        }
+    }
+    Ok(versions.into_values().collect())
+}
+
+/// Install a locked package if it is not already installed
+///
+/// Returns the path to the package's directory
+pub fn install(package: &LockedPackage) -> Result<PathBuf, String> {
+    let path = package.path();
+    if path.exists() {
+        return Ok(path);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let path_str = path.to_string_lossy();
+    let run = |args: &[&str]| -> Result<(), String> {
+        let output = Command::new("git")
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    };
+    run(&["clone", "--quiet", &package.git, &path_str])
+        .map_err(|e| format!("Failed to clone {}: {e}", package.git))?;
+    if let Err(e) = run(&["-C", &path_str, "checkout", "--quiet", &package.commit]) {
+        _ = fs::remove_dir_all(&path);
+        return Err(format!(
+            "Failed to check out {} {}: {e}",
+            package.name, package.version
+        ));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+#[test]
+fn package_resolution() {
+    use std::collections::BTreeMap;
+    let v = |s: &str| s.parse::<Version>().unwrap();
+    let req = |s: &str| s.parse::<VersionReq>().unwrap();
+    assert!(req("1.2").matches(&v("1.9.0")));
+    assert!(!req("1.2").matches(&v("2.0.0")));
+    assert!(!req("^0.2.3").matches(&v("0.3.0")));
+    assert!(req("~1.2.3").matches(&v("1.2.9")));
+    assert!(!req("~1.2.3").matches(&v("1.3.0")));
+    assert!(req(">=1.0, <1.5").matches(&v("1.4.2")));
+    assert!(req("=1").matches(&v("1.7.0")));
+    assert!(req("*").matches(&v("0.0.1")));
+    assert_eq!(req("~1.2").to_string(), "~1.2");
+    assert!("1.x".parse::<VersionReq>().is_err());
+
+    let available = |url: &str| -> Result<Vec<AvailableVersion>, String> {
+        let versions: &[&str] = match url {
+            "https://example.com/a/base" => &["1.0.0", "1.1.0", "1.2.0", "2.0.0"],
+            "https://example.com/a/util" => &["0.1.0", "0.2.0"],
+            _ => return Err(format!("unknown {url}")),
+        };
+        Ok((versions.iter())
+            .map(|s| AvailableVersion {
+                version: v(s),
+                commit: format!("commit-{s}"),
+            })
+            .collect())
+    };
+    let manifest_of = |p: &LockedPackage| -> Result<Manifest, String> {
+        let mut dependencies = BTreeMap::new();
+        if p.name == "util" && p.version == v("0.2.0") {
+            dependencies.insert(
+                "base".into(),
+                Dependency {
+                    git: "example.com/a/base.git".into(),
+                    version: req("<1.2"),
+                },
+            );
+        }
+        Ok(Manifest {
+            package: None,
+            dependencies,
+        })
+    };
+    let dep = |git: &str, version: &str| Dependency {
+        git: git.into(),
+        version: req(version),
+    };
+    let mut manifest = Manifest::default();
+    (manifest.dependencies).insert("base".into(), dep("example.com/a/base", "1"));
+    (manifest.dependencies).insert("util".into(), dep("example.com/a/util", "*"));
+    let lockfile = Lockfile::resolve_with(&manifest, available, manifest_of).unwrap();
+    let versions: Vec<_> = (lockfile.packages.iter())
+        .map(|p| (p.name.as_str(), p.version.to_string()))
+        .collect();
+    assert_eq!(
+        versions,
+        [("base", "1.1.0".to_string()), ("util", "0.2.0".to_string())]
+    );
+    assert_eq!(lockfile.get("base").unwrap().commit, "commit-1.1.0");
+
+    (manifest.dependencies).insert("base".into(), dep("example.com/a/base", "^1.2"));
+    let err = Lockfile::resolve_with(&manifest, available, manifest_of).unwrap_err();
+    assert!(err.contains("No version of `base`"), "{err}");
+}
@@ -357,9 +357,16 @@ impl Parser<'_> {
             return None;
         }
         let kind = match name {
-            Some(name) if name.value == "test" => ModuleKind::Test,
+            Some(name) if name.value == "test" => {
+                while self.exact(Spaces).is_some() {}
+                ModuleKind::Test(self.ident())
+            }
+            Some(name) if name.value == "bench" => {
+                while self.exact(Spaces).is_some() {}
+                ModuleKind::Bench(self.ident())
+            }
             Some(name) => ModuleKind::Named(name),
-            None => ModuleKind::Test,
+            None => ModuleKind::Test(None),
         };
         // Imports
         while self.exact(Spaces).is_some() {}
@@ -1176,6 +1183,8 @@ impl Parser<'_> {
             s.map(Into::into).map(Word::String)
         } else if let Some(op) = self.next_token_map(Token::as_placeholder) {
             op.map(Word::Placeholder)
+        } else if let Some(name) = self.next_token_map(Token::as_named_placeholder) {
+            name.map(Word::NamedPlaceholder)
         } else if let Some(label) = self.next_token_map(Token::as_label) {
             label.map(Into::into).map(Word::Label)
         } else if let Some(frags) = self.next_token_map(Token::as_format_string) {
@@ -1687,6 +1696,7 @@ pub(crate) fn max_placeholder(words: &[Sp<Word>]) -> Option<usize> {
     for word in words {
         match &word.value {
             Word::Placeholder(i) => set(Some(*i)),
+            Word::NamedPlaceholder(_) => set(Some(0)),
             Word::Strand(items) => set(max_placeholder(items)),
             Word::Array(arr) => {
                 for line in &arr.lines {
@@ -186,6 +186,8 @@ constant!(
     ("Arch", System, std::env::consts::ARCH),
     /// The executable file extension
     ("ExeExt", System, std::env::consts::EXE_EXTENSION),
+    /// The target Uiua is running on, either `"native"` or `"web"`
+    ("Target", System, crate::TARGET),
     /// The file extension for shared libraries
     ("DllExt", System, std::env::consts::DLL_EXTENSION),
     /// The primary path separator character
@@ -2752,6 +2754,51 @@ primitive!(
     /// ex! ⍤. =8 9
     /// Errors thrown by [assert] can be caught with [try].
     (2(0), Assert, Misc, ("assert", '⍤'), Impure),
+    /// Throw an error with a kind
+    ///
+    /// Expects a kind string and a payload value.
+    /// The payload is passed to [try]'s handler like a value thrown by [assert].
+    /// ex! # Experimental!
+    ///   : raise "not found" {"config.ua"}
+    /// In the handler, [errorkind] gets the kind, so different errors can be handled differently.
+    /// ex: # Experimental!
+    ///   : Load ← ⨬(raise "not found" "No file" 0|raise "parse" "Bad syntax" 0|"ok")
+    ///   : ≡(□⍣Load errorkind) 0_1_2
+    (2(0), Raise, Misc, "raise", Impure),
+    /// Get the kind of the error being handled by [try]
+    ///
+    /// Errors thrown by [raise] have the kind they were given.
+    /// Other errors are classified as `"assert"`, `"sys"`, `"timeout"`, or the general `"error"`.
+    /// ex: # Experimental!
+    ///   : ⍣(⍤"Oops" 0 5)errorkind
+    /// ex: # Experimental!
+    ///   : ⍣(⊢[])errorkind
+    /// The handler can take both the kind and the payload.
+    /// ex: # Experimental!
+    ///   : ⍣(raise "parse" "Unexpected token" 0)(⊟□errorkind □)
+    /// [errorkind] throws an error if no error is being handled.
+    /// ex! # Experimental!
+    ///   : errorkind
+    (0, ErrorKind, Misc, "errorkind", Impure),
+    /// Throw an error if two images are not perceptually the same
+    ///
+    /// Expects a tolerance, an expected image, and an actual image.
+    /// The tolerance is the largest difference allowed in any color channel of any pixel.
+    /// ex: # Experimental!
+    ///   : Img ← ÷40 ⊞+.⇡20
+    ///   : assertimage 0.01 Img +0.005 Img
+    /// If some pixels differ by more than that, the images still match if they are structurally similar everywhere.
+    /// This allows tests of generated images to pass even if floating point rounding differs between platforms.
+    /// By default, each 8×8 window of the images must have a structural similarity of at least `0.95`. A different minimum can be given as the second number of the tolerance.
+    /// ex: # Experimental!
+    ///   : Img ← ÷40 ⊞+.⇡20
+    ///   : assertimage 0.01_0.99 Img ⍜⊡(+0.02) 3_4 Img
+    /// If the images do not match, the error describes the region that differs.
+    /// ex! # Experimental!
+    ///   : Img ← ÷40 ⊞+.⇡20
+    ///   : assertimage 0.01 Img ⍜(↙3)(×0.5) Img
+    /// Like [assert], errors thrown by [assertimage] can be caught with [try].
+    (3(0), AssertImage, Misc, "assertimage", Impure),
     /// Generate a random number in the range `[0, 1)`
     ///
     /// If you need a seeded random number, use [gen].
@@ -2775,7 +2822,85 @@ primitive!(
     /// ex: F ← memo(+⌊×10⚂)
     ///   : ∵F [1 1 2 2 3 3]
     /// In general, this should only be used with functions that perform a potentially expensive calculation.
+    ///
+    /// The cache only lasts for one run. Running with `uiua run --memo-cache` or `uiua watch --memo-cache` also stores the results of pure functions on disk so that they are reused across runs.
     ([1], Memo, OtherModifier, "memo"),
+    /// Call a function with a time limit
+    ///
+    /// Expects a duration in seconds.
+    /// If the function does not finish before the duration has passed, an error is thrown.
+    /// ex! # Experimental!
+    ///   : timeout(⍢(+1|1)) 0.01 0
+    /// The error can be caught with [try].
+    /// ex: # Experimental!
+    ///   : ⍣timeout(⍢(+1|1))$"Took too long" 0.01 0
+    /// The time limit is checked between each instruction, so it works for long-running computations.
+    /// However, a blocking system function like [&sl] will not be stopped early.
+    ([1], Timeout, OtherModifier, "timeout", Impure),
+    /// Call a function again if it fails
+    ///
+    /// Expects a retry count and a backoff schedule.
+    /// If the function throws an error, it is called again with the same arguments, up to the retry count.
+    /// If it still fails, the last error is thrown.
+    /// ex: # Experimental!
+    ///   : C ← cell 0
+    ///   : retry(⍤"Not yet" ≥3 update(+1.) C) 5 0
+    ///   : update. C
+    /// ex! # Experimental!
+    ///   : C ← cell 0
+    ///   : retry(⍤"Not yet" ≥3 update(+1.) C) 2 0
+    /// The backoff schedule is a list of 1 to 3 numbers: the delay before the first retry in seconds, the factor the delay is multiplied by after each retry, and a jitter fraction.
+    /// The factor defaults to `2`, and the jitter defaults to `0`.
+    /// A jitter of `0.1` randomly lengthens or shortens each delay by up to 10%, which keeps many threads from retrying at the same time.
+    /// ex: # Experimental!
+    ///   : retry(&fras) 3 [0.5 2 0.1] "example.txt"
+    ([1], Retry, OtherModifier, "retry", Impure),
+    /// Call a function with a handle and close the handle afterward
+    ///
+    /// Expects a handle, such as one returned by [&fo] or [channel].
+    /// The handle is passed to the function as its first argument.
+    /// The handle is closed with [&cl] when the function returns, even if it throws an error.
+    /// ex: # Experimental!
+    ///   : using(&rs 11) &fo "example.txt"
+    /// Using the handle after it is closed is an error.
+    /// ex! # Experimental!
+    ///   : H ← &fo "example.txt"
+    ///   : using(&rs 3) H
+    ///   : &rs 3 H
+    ([1], Using, OtherModifier, "using", Impure),
+    /// Call the function whose pattern matches a value
+    ///
+    /// Expects a list of boxed patterns and a value.
+    /// Each pattern corresponds to one of the functions in [when]'s function pack.
+    /// The value is checked against each pattern in order, and the function of the first pattern that matches is called on it.
+    /// A pattern that is not a map matches a value that is the same.
+    /// ex: # Experimental!
+    ///   : when("one"|"two"|"three") {1 2 3} 2
+    /// A map pattern checks the structure of the value. It can have these keys:
+    /// - `shape` - the value must have this shape
+    /// - `type` - the value must have this type, one of `"number"`, `"complex"`, `"character"`, or `"box"`
+    /// - `has` - the value must be a map with these keys
+    /// - `is` - the value must be the same as this
+    /// - `get` - a map of keys to patterns. The value must be a map with these keys, and the values at the keys must match the patterns.
+    /// An empty map pattern matches any value.
+    /// ex: # Experimental!
+    ///   : P ← {map{"type"}{"character"} map{"shape"}{[2]} map{}{}}
+    ///   : ⊃(when(⧻|+°⊟|¯) P [3 4]|when(⧻|+°⊟|¯) P "hi"|when(⧻|+°⊟|¯) P 5)
+    /// A boxed value is unboxed before it is checked, but the function still gets the boxed value.
+    /// ex: # Experimental!
+    ///   : ≡when(⋅1|⋅0) {map{"type"}{"number"}} {1 "a" 2}
+    /// [when] makes it easy to handle different kinds of map records.
+    /// ex: # Experimental!
+    ///   : Area ← when(×°⊟°□get"size"|×π×.°□get"radius") {map{"has"}{"size"} map{"get"}□map{"kind"}{"circle"}}
+    ///   : Area map {"kind" "radius"} {"circle" 2}
+    ///   : Area map {"size"} {[3 4]}
+    /// If there is one more function than there are patterns, the last function is called when no pattern matches.
+    /// ex: # Experimental!
+    ///   : when("zero"|"other") {0} 5
+    /// Otherwise, it is an error if no pattern matches. The error says why each pattern did not match.
+    /// ex! # Experimental!
+    ///   : when(∘|∘) {map{"shape"}{[2]} map{"has"}{"a"}} [1 2 3]
+    ([1], When, OtherModifier, "when"),
     /// Run a function at compile time
     ///
     /// ex: F ← (⌊×10[⚂⚂⚂])
@@ -2859,6 +2984,80 @@ primitive!(
     /// If no value is available, then an error is thrown.
     /// The error can be caught with [try].
     (1, TryRecv, Thread, "tryrecv", Impure),
+    /// Create a channel for sending values between threads
+    ///
+    /// Expects a capacity. If it is [infinity], the channel is unbounded.
+    /// Otherwise, [send]ing to a full channel blocks until a value is received.
+    /// The returned handle can be used with [send], [recv], and [tryrecv] in place of a thread id.
+    /// ex: # Experimental!
+    ///   : Ch ← channel ∞
+    ///   : send Ch 5
+    ///   : send Ch "hi"
+    ///   : recv Ch
+    ///   : recv Ch
+    /// Unlike thread ids, channel handles can be used from any thread, so many threads can share the same channel.
+    /// ex: # Experimental!
+    ///   : Ch ← channel ∞
+    ///   : ◌wait≡spawn(send Ch ×10) ⇡3
+    ///   : ⍆[recv Ch recv Ch recv Ch]
+    /// A channel stays open until it is closed with [&cl], even after the threads that send to it finish.
+    /// Once a channel is closed, [send]ing to it is an error, and [recv] throws an error after the remaining values are received.
+    /// ex: # Experimental!
+    ///   : Ch ← channel ∞
+    ///   : ◌spawn(&cl Ch ⍥(send Ch 1)3)
+    ///   : [recv Ch recv Ch recv Ch]
+    ///   : ⍣(recv Ch)⋅"closed"
+    (1, Channel, Thread, "channel", Impure),
+    /// Receive a value from whichever of several channels has one first
+    ///
+    /// Expects a list of channel handles created with [channel].
+    /// Blocks until one of the channels has a value, then pushes the index of that channel and the received value.
+    /// ex: # Experimental!
+    ///   : A ← channel ∞
+    ///   : B ← channel ∞
+    ///   : send B "from b"
+    ///   : recvany {A B}
+    (1(2), RecvAny, Thread, "recvany", Impure),
+    /// Create a mutable cell that can be shared between threads
+    ///
+    /// Expects an initial value and pushes a handle to the cell.
+    /// The value in the cell can be read and changed with [update].
+    /// ex: # Experimental!
+    ///   : C ← cell 0
+    ///   : update(+1) C
+    ///   : update(+1) C
+    ///   : update. C
+    /// Unlike values collected with [wait], a cell can accumulate results while threads are still running.
+    /// ex: # Experimental!
+    ///   : C ← cell []
+    ///   : ◌wait≡spawn(update⊂ C) ⇡5
+    ///   : ⍆update. C
+    (1, Cell, Thread, "cell", Impure),
+    /// Atomically update the value in a [cell]
+    ///
+    /// Expects a cell handle created with [cell].
+    /// The cell's value is passed to the function, and the function's top output becomes the cell's new value.
+    /// Any other outputs are left on the stack.
+    /// ex: # Experimental!
+    ///   : C ← cell 5
+    ///   : update(×2) C
+    ///   : update. C
+    /// This allows reading the value before or after it is changed.
+    /// ex: # Experimental!
+    ///   : C ← cell 5
+    ///   : update(+1.) C
+    ///   : update. C
+    /// If the function takes more arguments, they are taken from below the handle.
+    /// ex: # Experimental!
+    ///   : C ← cell 5
+    ///   : update+ C 10
+    ///   : update. C
+    /// The cell is locked while the function runs, so no other thread can change it in the meantime.
+    /// [update]ing the same cell inside the function is an error.
+    /// ex! # Experimental!
+    ///   : C ← cell 5
+    ///   : update(+ update(+1) C) C
+    ([1], Update, Thread, "update", Impure),
     /// Generate an array of random numbers with a seed
     ///
     /// The first argument is the shape, the second argument is the seed. The returned array will have the given shape where each element is in the range [0, 1).
@@ -3163,6 +3362,60 @@ primitive!(
     ///
     /// See also: [insert], [has], [get]
     (2, Remove, Map, "remove"),
+    /// Get a value from nested maps and arrays by following a path
+    ///
+    /// The first argument is the path. Each row of the path is a step, either a key of a map or an index into an array.
+    /// Boxes are removed after each step.
+    /// ex: # Experimental!
+    ///   : getpath {"b" 1} map {"a" "b"} {1 [4 5 6]}
+    /// Use a boxed list if the steps have different types.
+    /// ex: # Experimental!
+    ///   : User ← map {"name" "tags"} {"Ann" {"admin" "dev"}}
+    ///   : getpath {"tags" 0} User
+    /// A string is a single key.
+    /// ex: # Experimental!
+    ///   : getpath "name" map {"name" "tags"} {"Ann" {"admin" "dev"}}
+    /// Negative indices count from the end.
+    /// ex: # Experimental!
+    ///   : getpath [1 ¯1] [1_2_3 4_5_6]
+    /// A missing key or an index that is out of bounds is an error.
+    /// ex! # Experimental!
+    ///   : getpath {"c"} map {"a" "b"} 1_2
+    ///
+    /// [under][getpath] modifies the value at the path. Boxes along the path are kept.
+    /// ex: # Experimental!
+    ///   : ⍜(getpath {"b" 1}|×10) map {"a" "b"} {1 [4 5 6]}
+    ///
+    /// See also: [setpath], [updatepath], [get]
+    (2, GetPath, Map, "getpath"),
+    /// Set a value in nested maps and arrays by following a path
+    ///
+    /// The first argument is the path, as in [getpath]. The second argument is the new value.
+    /// ex: # Experimental!
+    ///   : setpath {"b" 1} 0 map {"a" "b"} {1 [4 5 6]}
+    /// The last step of the path may be a key that is not in the map yet.
+    /// ex: # Experimental!
+    ///   : setpath {"inner" "c"} 3 map {"inner"} {map {"a" "b"} 1_2}
+    /// If the old value was boxed, the new value is boxed too.
+    /// ex: # Experimental!
+    ///   : setpath [1] "hi" {"a" "b" "c"}
+    ///
+    /// See also: [getpath], [updatepath], [insert]
+    (3, SetPath, Map, "setpath"),
+    /// Call a function on a value in nested maps and arrays by following a path
+    ///
+    /// The first argument is the path, as in [getpath].
+    /// [updatepath] is equivalent to [under][getpath].
+    /// ex: # Experimental!
+    ///   : Users ← {map {"name" "age"} {"Ann" 30} map {"name" "age"} {"Bo" 25}}
+    ///   : updatepath(+1) {1 "age"} Users
+    ///   : getpath {1 "age"} .
+    /// Boxes along the path are kept.
+    /// ex: # Experimental!
+    ///   : updatepath(⊂4) {"list"} map {"list" "name"} {[1 2 3] "nums"}
+    ///
+    /// See also: [getpath], [setpath]
+    ([1], UpdatePath, Map, "updatepath"),
     /// Debug print all stack values without popping them
     ///
     /// This is equivalent to [dump][identity], but is easier to type.
@@ -3250,6 +3503,118 @@ primitive!(
     /// At the moment, this is only useful for debugging.
     /// While theoretically, it could be used in a macro to choose a branch of a [switch] appropriate for the function, this is not yet possible because of the way that macros and signature checking work.
     (0(2)[1], Sig, Comptime, "signature"),
+    /// Get the doc comment of a binding
+    ///
+    /// The binding is given by its name as a string.
+    /// ex: # Experimental!
+    ///   :
+    ///   : # Add one to a number
+    ///   : Incr ← +1
+    ///   : docs "Incr"
+    /// If the binding has no doc comment, the result is an empty string.
+    ///
+    /// This is most useful in a code macro, whose operands are passed as boxed strings.
+    /// ex: # Experimental!
+    ///   :
+    ///   : # Add one to a number
+    ///   : Incr ← +1
+    ///   : Docs! ←^ repr docs⊢
+    ///   : Docs!Incr
+    ///
+    /// If several bindings have the same name, the most recently defined one is used.
+    ///
+    /// See also: [source], [arity]
+    (1, Docstring, Comptime, "docs", Mutating),
+    /// Get the source code of a binding
+    ///
+    /// The binding is given by its name as a string.
+    /// The result includes the binding's name and arrow.
+    /// ex: # Experimental!
+    ///   : Avg ← ÷⊃⧻/+
+    ///   : source "Avg"
+    ///
+    /// If several bindings have the same name, the most recently defined one is used.
+    ///
+    /// See also: [docs], [arity]
+    (1, Source, Comptime, "source", Mutating),
+    /// Get the signature of a binding
+    ///
+    /// The binding is given by its name as a string.
+    /// The result is a list of the number of arguments and the number of outputs.
+    /// ex: # Experimental!
+    ///   : Avg ← ÷⊃⧻/+
+    ///   : arity "Avg"
+    /// Constants have no arguments and one output.
+    /// ex: # Experimental!
+    ///   : X ← 5
+    ///   : arity "X"
+    /// Modules and macros do not have signatures.
+    /// ex! # Experimental!
+    ///   : F! ← ^0 1
+    ///   : arity "F!"
+    ///
+    /// If several bindings have the same name, the most recently defined one is used.
+    ///
+    /// See also: [docs], [source]
+    (1, Arity, Comptime, "arity", Mutating),
+    /// Parse code into an abstract syntax tree
+    ///
+    /// Each node of the tree is a map with these keys:
+    /// - `kind` - the kind of node, like `"line"`, `"number"`, `"primitive"`, or `"modified"`
+    /// - `span` - the start and end character positions of the node in the code
+    /// - `code` - the code of the node
+    /// - `children` - a list of the boxed child nodes
+    /// ex: # Experimental!
+    ///   : ast "+1"
+    /// ex: # Experimental!
+    ///   : ≡◇(get "kind") °□get "children" °□◇⊢get "children" ast "/+ [1 2 3]"
+    /// Code that fails to parse is an error.
+    /// ex! # Experimental!
+    ///   : ast "(+"
+    ///
+    /// [un][ast] turns a tree back into code.
+    /// The code of a node with children is its own code, with each child's span replaced with the child's code.
+    /// This makes it possible to transform code by changing the code of nodes.
+    /// ex: # Experimental!
+    ///   : First! ← ⍜(°□◇⊢get "children"|^0)
+    ///   : °ast First!First!(insert "code" □"×") ast "+1 2"
+    ///
+    /// See also: [eval]
+    (1, Ast, Misc, "ast"),
+    /// Evaluate code in a sandbox
+    ///
+    /// The first argument is the code, either as a string or as a tree from [ast].
+    /// The second argument is a list of arguments. The first one is on top of the stack.
+    /// The result is a boxed list of the values left on the stack, with the top one first.
+    /// ex: # Experimental!
+    ///   : eval "⊟+" {1 2 5}
+    /// ex: # Experimental!
+    ///   : eval °ast ast "⇡5" {}
+    /// The code cannot use bindings from the surrounding code, and it cannot access files or the network.
+    /// Evaluation is limited in time and in the number of operations it can run.
+    /// ex! # Experimental!
+    ///   : eval "⍢(+1)(1) 0" {}
+    ///
+    /// See also: [ast]
+    (2, Eval, Misc, "eval", Impure),
+    /// Generate a unique binding name
+    ///
+    /// The argument is a prefix for the name. The result is the prefix followed by a subscript number.
+    /// ex: # Experimental!
+    ///   : gensym "Tmp"
+    /// The name is different every time, and it is never the name of an existing binding.
+    /// ex: # Experimental!
+    ///   : Tmp₁ ← 5
+    ///   : [⍥(gensym "Tmp")3]
+    ///
+    /// This is useful in code macros that generate bindings, so that helper bindings do not collide with the user's names.
+    /// ex: # Experimental!
+    ///   : Double‼ ←^ $"_ ← _\n_ ← ×2 _" ⊃(⊙⋅∘|⋅∘|∘) gensym"Tmp" °{⊙∘}
+    ///   : Double‼X5
+    ///   : X
+    ///
+    /// See also: [quote]
+    (1, Gensym, Comptime, "gensym", Impure),
     /// Run the Fast Fourier Transform on an array
     ///
     /// The Fast Fourier Transform (FFT) is an optimized algorithm for computing the Discrete Fourier Transform (DFT). The DFT is a transformation that converts a signal from the time domain to the frequency domain.
@@ -3274,6 +3639,26 @@ primitive!(
     ///   : ⬚0↙ &asr °⊚       # Put 1 in buffer for each frequency
     ///   : ◌°ℂ °fft          # Run inverse FFT and get the real part
     (1, Fft, Misc, "fft"),
+    /// Solve a linear program
+    ///
+    /// Expects an objective, a matrix of constraints, and a list of bounds.
+    /// [linprog] finds the list of non-negative numbers `x` that maximizes the sum of the objective times `x`, such that each row of the constraints times `x` sums to at most the corresponding bound.
+    /// In other words, it maximizes `/+×Objective x` where `≤Bounds/+×Constraints¤x`.
+    ///
+    /// Here, we maximize `3x + 5y` such that `x ≤ 4`, `2y ≤ 12`, and `3x + 2y ≤ 18`.
+    /// ex: # Experimental!
+    ///   : linprog [3 5] [1_0 0_2 3_2] [4 12 18]
+    /// To minimize instead, [negate] the objective.
+    /// ex: # Experimental!
+    ///   : linprog ¯[1 1] [¯1_¯2 ¯3_¯1] [¯4 ¯6]
+    /// A "greater or equal" constraint can be expressed by [negate]ing both its row of the constraints and its bound.
+    ///
+    /// If no solution satisfies the constraints, or if the objective can grow without limit, it is an error.
+    /// ex! # Experimental!
+    ///   : linprog [1] [[1] [¯1]] [1 ¯2]
+    /// ex! # Experimental!
+    ///   : linprog [1 1] [[1 ¯1]] [1]
+    (3, LinProg, Misc, "linprog"),
     /// Find shortest paths in a graph
     ///
     /// [astar] is deprecated in favor of [path].
@@ -3381,6 +3766,11 @@ primitive!(
     /// The heuristic function `absolute value``reduce``complex``subtract` calculates the euclidean distance between two points.
     /// ex: ⊢ path(+A₂¤|≍|⌵/ℂ-) 0_0 3_5
     /// With a good heuristic, A* is generally faster than [path], which uses a [Dijkstra](https://en.wikipedia.org/wiki/Dijkstra%27s_algorithm)-like algorithm.
+    /// If the neighbors function does not return costs and there is no heuristic, [path] uses a simple [breadth-first search](https://en.wikipedia.org/wiki/Breadth-first_search).
+    ///
+    /// A subscript sets a maximum cost. Paths that would cost more than the maximum are not explored.
+    /// ex: ⊢ path₁₀(+A₂¤)≍ 0_0 3_5
+    /// ex: path₇(+A₂¤)≍ 0_0 3_5
     ///
     /// Pathfinding isn't just good for solving problems with grids or graphs.
     /// Anything that involves finding a series of steps to get from one point to another is a good candidate for [path].
@@ -3388,6 +3778,63 @@ primitive!(
     /// ex: ⊢path(+⊙¤ ⊂¯.⊞=. °⊏)≍ "thud" "uiua"
     /// [path] is designed to be maximally flexible, so it can be used with graphs or grids or any other structure.
     ((2)[2], Path, Misc, "path"),
+    /// Find the connected components of a graph
+    ///
+    /// Expects a function and a list of nodes.
+    /// The function should return an array of the neighbors of a node, like the neighbors function of [path].
+    /// Costs may also be returned, but they are ignored.
+    /// When called, [components] will pop any additional arguments its function needs from the stack.
+    /// Nodes discovered as neighbors are added to the graph even if they are not in the initial list.
+    ///
+    /// The result is a list of [box]ed arrays of nodes. Edge direction is ignored.
+    /// Here, the graph is given as an adjacency list, where each row contains the neighbors of the node at that index.
+    /// ex: # Experimental!
+    ///   : components(°□⊡) ⇡6 {[1] [2] [] [4] [] []}
+    /// The neighbors function can also search an edge list.
+    /// ex: # Experimental!
+    ///   : Edges ← [1_2 2_3 5_6 6_4]
+    ///   : components(▽⊃(=⊙(⊢⍉)|⋅(⊣⍉))⊙Edges) [1 2 3 4 5 6]
+    ((1)[1], Components, Misc, "components"),
+    /// Sort the nodes of a graph so that every node comes before its neighbors
+    ///
+    /// Expects a function and a list of nodes.
+    /// The function should return an array of the neighbors of a node, like the neighbors function of [path].
+    /// When called, [toposort] will pop any additional arguments its function needs from the stack.
+    /// Nodes discovered as neighbors are added to the graph even if they are not in the initial list.
+    ///
+    /// ex: # Experimental!
+    ///   : toposort(°□⊡) ⇡5 {[] [0 3] [1] [0] [2]}
+    /// If the graph contains a cycle, it is an error.
+    /// ex! # Experimental!
+    ///   : toposort(°□⊡) ⇡3 {[1] [2] [0]}
+    ((1)[1], Toposort, Misc, "toposort"),
+    /// Find the strongly-connected components of a graph
+    ///
+    /// Expects a function and a list of nodes.
+    /// The function should return an array of the neighbors of a node, like the neighbors function of [path].
+    /// When called, [strongcomponents] will pop any additional arguments its function needs from the stack.
+    /// Nodes discovered as neighbors are added to the graph even if they are not in the initial list.
+    ///
+    /// The result is a list of [box]ed arrays of nodes. Every node in a component can reach every other node in that component.
+    /// The components are in topological order.
+    /// ex: # Experimental!
+    ///   : strongcomponents(°□⊡) ⇡6 {[1] [2] [0 3] [4] [5] [3]}
+    ((1)[1], StrongComponents, Misc, "strongcomponents"),
+    /// Find a minimum spanning tree of a graph
+    ///
+    /// Expects a function and a list of nodes.
+    /// The function should return 1 or 2 arrays of equal [length], like the neighbors function of [path].
+    /// - An array of the neighboring nodes must always be returned.
+    /// - An array of costs may be returned above the nodes array on the stack. If ommitted, all costs are assumed to be 1.
+    /// When called, [spanningtree] will pop any additional arguments its function needs from the stack.
+    /// Nodes discovered as neighbors are added to the graph even if they are not in the initial list.
+    ///
+    /// The result is a list of edges, each of which is a pair of nodes. Edge direction is ignored.
+    /// If the graph is not connected, a minimum spanning tree is found for each component.
+    /// ex: # Experimental!
+    ///   : Costs ← [0_4_1 4_0_2 1_2_0]
+    ///   : spanningtree(⊃(▽⊸≠0|⊚≠0)⊡⊙Costs) ⇡3
+    ((1)[1], SpanningTree, Misc, "spanningtree"),
     /// Calculate the derivative of a mathematical expression
     ///
     /// Basic polynomials are supported, along with [sine] and [logarithm].
@@ -3404,14 +3851,21 @@ primitive!(
     /// ex: # Experimental!
     ///   : # sin(x) → cos(x)
     ///   : ⍜×⁅1e3 ∂∿ ×τ÷⟜⇡8
-    /// Most derivatives that would require the chain or product rule are not supported.
-    /// ex! # Experimental!
+    /// Derivatives that require the chain or product rule are also supported.
+    /// ex: # Experimental!
     ///   : # xsin(x)  →  sin(x) + xcos(x)
     ///   : ∂(×∿.) ×τ÷⟜⇡8
-    /// They do work if the inner derivative is a constant.
     /// ex: # Experimental!
-    ///   : # sin(2x) → 2cos(2x)
-    ///   : ∂(∿×2) ×τ÷⟜⇡8
+    ///   : # sin(x²) → 2xcos(x²)
+    ///   : ∂(∿×.) [0 1 2]
+    /// This makes it easy to write numeric methods like Newton's method without deriving formulas by hand.
+    /// ex: # Experimental!
+    ///   : # Find √2 as the root of x² - 2
+    ///   : F ← -2×.
+    ///   : ⍥(-⊸(÷⊃∂F F))10 1
+    /// Only functions made of stack manipulation and math primitives like [add], [multiply], [divide], [power], [logarithm], [sqrt], [absolute value], and [sine] can be differentiated.
+    /// ex! # Experimental!
+    ///   : ∂(/+×.) [1 2 3]
     ///
     /// See also: [integral]
     ([1], Derivative, Misc, ("derivative", '∂')),
@@ -3438,6 +3892,56 @@ primitive!(
     ///
     /// See also: [derivative]
     ([1], Integral, Misc, ("integral", '∫')),
+    /// Calculate the gradient of a function with respect to its arguments
+    ///
+    /// The function is run while recording the operations it performs. The recording is then traversed backwards to find how much each argument affects the result.
+    /// This is known as reverse-mode automatic differentiation.
+    /// ex: # Experimental!
+    ///   : # x² → 2x
+    ///   : gradient(×.) 3
+    /// If the function returns an array, the gradient of the sum of its elements is calculated. This makes it easy to find a derivative at many points at once.
+    /// ex: # Experimental!
+    ///   : # xsin(x)  →  sin(x) + xcos(x)
+    ///   : gradient(×∿.) [0 1 2]
+    /// If the function takes multiple arguments, a gradient is returned for each one.
+    /// ex: # Experimental!
+    ///   : # ab + c
+    ///   : gradient(+×) 2 3 4
+    /// Arrays that are extended to match another array's shape have their gradients summed.
+    /// ex: # Experimental!
+    ///   : gradient(/+×) 2 [1 2 3]
+    /// Pervasive math functions like [add], [multiply], [divide], [power], [logarithm], [sqrt], [sine], [absolute value], [minimum], and [maximum] are supported, along with [reduce][add] and stack manipulation.
+    /// Functions like [floor] and comparisons are treated as having a gradient of 0.
+    ///
+    /// [gradient] can be used for gradient descent.
+    /// ex: # Experimental!
+    ///   : # Fit y = mx to some points
+    ///   : Xs ← [1 2 3 4]
+    ///   : Ys ← [2 4.1 5.9 8.2]
+    ///   : Loss ← /+ⁿ2-Ys×Xs
+    ///   : ⍥(-×0.01 gradient Loss .)100 0
+    ///
+    /// See also: [derivative]
+    ([1], Gradient, Misc, "gradient"),
+    /// Run a function on intervals
+    ///
+    /// Each argument is an array whose first row is a lower bound and whose second row is an upper bound.
+    /// The function's outputs are intervals in the same form that are guaranteed to contain every possible result.
+    /// ex: # Experimental!
+    ///   : interval(×.) [1 2]
+    /// Bounds are rounded outward after every operation, so floating-point error is accounted for.
+    /// ex: # Experimental!
+    ///   : interval(+) [0.1 0.1] [0.2 0.2]
+    /// ex: # Experimental!
+    ///   : /-interval(+) [0.1 0.1] [0.2 0.2]
+    /// The bounds of each argument can be arrays.
+    /// ex: # Experimental!
+    ///   : interval(∿) [1_2_3 2_3_4]
+    /// Because each use of an argument is treated independently, an interval can be wider than necessary.
+    /// ex: # Experimental!
+    ///   : interval(-.) [1 2]
+    /// Pervasive math functions like [add], [subtract], [multiply], [divide], [power], [sqrt], [sine], [absolute value], [floor], [minimum], and [maximum] are supported, along with [reduce] with [add], [multiply], [minimum], or [maximum] and stack manipulation.
+    ([1], Interval, Misc, "interval"),
     /// Encode an array into a JSON string
     ///
     /// ex: json [1 2 3]
@@ -3502,6 +4006,32 @@ primitive!(
     /// While it is not useful to display the output bytes here, we can see how the result of decoding works:
     /// ex: °xlsx xlsx . ↯3_6⇡18
     (1, Xlsx, Encoding, "xlsx"),
+    /// Encode an array into an HTML table
+    ///
+    /// The input array must be at most rank-`2`.
+    /// ex: # Experimental!
+    ///   : html [1_2 3_4]
+    /// Strings and [box]ed values are shown as single cells.
+    /// ex: # Experimental!
+    ///   : html {"Hi" 5 "<b>"}
+    /// The keys of a [map] array become the column headers, and its values become the columns.
+    /// ex: # Experimental!
+    ///   : html map {"Name" "Age"} {{"Ann" "Bob"} [31 27]}
+    ///
+    /// See also: [markdown]
+    (1, Html, Encoding, "html"),
+    /// Encode an array into a Markdown table
+    ///
+    /// The input array must be at most rank-`2`.
+    /// Because Markdown tables require a header row, it is left empty unless the array is a [map].
+    /// ex: # Experimental!
+    ///   : markdown [1_2 3_4]
+    /// The keys of a [map] array become the column headers, and its values become the columns.
+    /// ex: # Experimental!
+    ///   : markdown map {"Name" "Age"} {{"Ann" "Bob"} [31 27]}
+    ///
+    /// See also: [html]
+    (1, Markdown, Encoding, "markdown"),
     /// Encode an array into a compact binary representation
     ///
     /// This is useful for saving arrays to files.
@@ -3542,6 +4072,34 @@ primitive!(
     /// ex: # Experimental!
     ///   : ÷∩⧻⟜binary ℂ0 ⇡256
     (1, Binary, Encoding, "binary"),
+    /// Pack values into bytes according to a layout string
+    ///
+    /// The first argument is the layout, and the second is a list of values, one per field.
+    /// Each field in the layout is a single character code, optionally preceded by a count.
+    /// - `x` - a pad byte, which takes no value
+    /// - `?` - a boolean byte
+    /// - `b` `B` - a signed or unsigned 8-bit integer
+    /// - `h` `H` - a signed or unsigned 16-bit integer
+    /// - `i` `I` - a signed or unsigned 32-bit integer
+    /// - `q` `Q` - a signed or unsigned 64-bit integer
+    /// - `f` `d` - a 32-bit or 64-bit float
+    /// - `s` - a fixed-length byte string
+    ///
+    /// A count on a numeric code makes the field a list of that many numbers. A count on `s` is its length in bytes. Shorter strings are padded with `0`s.
+    /// The layout may begin with `<` for little-endian (the default), `>` or `!` for big-endian, or `=` for native byte order.
+    /// ex: # Experimental!
+    ///   : pack "Bhx" [1 ¯2]
+    /// ex: # Experimental!
+    ///   : pack ">I3B5s" {1000 [1 2 3] "Uiua"}
+    /// ex! # Experimental!
+    ///   : pack "B" [256]
+    ///
+    /// Use [anti][pack] to unpack bytes into a list of boxed values.
+    /// ex: # Experimental!
+    ///   : ⌝pack ">I3B5s" pack ">I3B5s" {1000 [1 2 3] "Uiua"}
+    ///
+    /// [pack] is useful for reading and writing binary file formats and network protocols.
+    (2, Pack, Encoding, "pack"),
     /// Convert a value to its code representation
     ///
     /// ex: repr π
@@ -3605,6 +4163,49 @@ primitive!(
     ///
     /// See also: [&ap]
     (3, AudioEncode, Encoding, "audio"),
+    /// Generate a band-limited waveform
+    ///
+    /// Expects a waveform, a frequency in Hz, and an array of times in seconds.
+    /// The waveform can be `"sine"`, `"saw"`, `"square"`, or `"triangle"`.
+    /// ex: # Experimental!
+    ///   : oscillator "saw" 220 ÷⟜⇡&asr
+    /// Unlike waves made with arithmetic, the sharp corners of [oscillator]'s waves are smoothed just enough that they do not alias at the audio sample rate. A jump passes through its midpoint.
+    /// ex: # Experimental!
+    ///   : oscillator "square" 1 ÷8⇡8
+    /// If the frequency is an array, there is one wave for each frequency. This makes chords easy.
+    /// ex: # Experimental!
+    ///   : ÷3/+ oscillator "triangle" [220 277 330] ÷⟜⇡&asr
+    /// Frequencies of at least half the sample rate produce silence.
+    ///
+    /// See also: [envelope], [biquad]
+    (3, Oscillator, Encoding, "oscillator", Impure),
+    /// Apply an attack-decay-sustain-release envelope to audio samples
+    ///
+    /// Expects a list of 4 numbers and some audio samples.
+    /// The numbers are the attack time, the decay time, the sustain level, and the release time. Times are in seconds.
+    /// The volume rises from `0` to `1` during the attack, falls to the sustain level during the decay, and falls to `0` during the release at the end of the samples.
+    /// ex: # Experimental!
+    ///   : envelope [0.01 0.1 0.6 0.3] oscillator "saw" 220 ÷⟜⇡&asr
+    /// If the samples end before the decay does, the release starts from wherever the volume is.
+    /// ex: # Experimental!
+    ///   : envelope [0.2 0.2 0.5 0.05] oscillator "square" 330 ÷⟜⇡÷4&asr
+    ///
+    /// See also: [oscillator], [biquad]
+    (2, Envelope, Encoding, "envelope", Impure),
+    /// Filter audio samples with a biquad filter
+    ///
+    /// Expects a filter kind, a list of filter parameters, and some audio samples.
+    /// The kind can be `"lowpass"`, `"highpass"`, `"bandpass"`, `"notch"`, `"allpass"`, `"peak"`, `"lowshelf"`, or `"highshelf"`.
+    /// The parameters are the cutoff or center frequency in Hz, the Q, and the gain in decibels. The Q defaults to `√½`. The gain only affects `"peak"` and the shelves and defaults to `0`.
+    /// ex: # Experimental!
+    ///   : biquad "lowpass" 800 oscillator "saw" 110 ÷⟜⇡&asr
+    /// A higher Q makes a sharper peak at the frequency.
+    /// ex: # Experimental!
+    ///   : biquad "bandpass" 1200_8 oscillator "square" 110 ÷⟜⇡&asr
+    /// Each channel of multi-channel audio is filtered separately.
+    ///
+    /// See also: [oscillator], [envelope]
+    (3, Biquad, Encoding, "biquad", Impure),
     /// Render text into an image array
     ///
     /// In the most basic usage, the first argument is a font size and the second argument is the text to render.
@@ -3642,6 +4243,97 @@ primitive!(
     /// ex: # Experimental!
     ///   : ⬚[1 0 0] layout {100 0_1_0} "Green on Red!"
     (2, Layout, Encoding, "layout", Impure),
+    /// Draw shapes and text onto an image
+    ///
+    /// The first argument is a shape or list of shapes. The second argument is the canvas.
+    /// The result is always an RGBA image, so it can be passed to [&ims] or [gif].
+    ///
+    /// A shape is a list of boxes. The first is the kind of shape and the second is its coordinates.
+    /// Like with indexing, points are `[y x]`.
+    /// - `"line"` - a list of points to connect
+    /// - `"polygon"` - a list of corner points
+    /// - `"rect"` - `[y x h w]`
+    /// - `"circle"` - `[y x r]`
+    /// - `"text"` - the `[y x]` of the top-left corner
+    ///
+    /// The canvas can be an image or a `[h w]` size for a new transparent canvas.
+    /// ex: # Experimental!
+    ///   : draw {"circle" [50 50 40] [1 0 0]} [100 100]
+    /// Shapes are drawn in order, with later ones on top. Colors may have an alpha channel.
+    /// ex: # Experimental!
+    ///   : draw {
+    ///   :   {"rect" [10 10 60 80] [0 0.5 1]}
+    ///   :   {"polygon" [10_50 90_10 90_90] [1 0.8 0 0.7]}
+    ///   : } [100 100]
+    /// The rest of the items in a shape are options.
+    /// The first array of 3 or 4 numbers is the color. It defaults to white.
+    /// A scalar is the stroke width. Lines default to a width of `1`. Giving another shape a width draws only its outline.
+    /// ex: # Experimental!
+    ///   : draw {
+    ///   :   {"line" [10_10 90_50 10_90] 0_1_0 5}
+    ///   :   {"circle" [50 50 40] 3}
+    ///   : } [100 100]
+    /// For `"text"`, the scalar is the font size (default 30), and a string is the text to draw.
+    /// ex: # Experimental!
+    ///   : draw {"text" [5 10] "Uiua!" 40 1_0.5_0} [50 130]
+    /// Drawing onto an existing image composites the shapes over it.
+    /// ex: # Experimental!
+    ///   : draw {"circle" [16 16 10] 0_0_0_0.5} ⊞+.÷64⇡32
+    /// [fill] sets the background color of a new canvas.
+    /// ex: # Experimental!
+    ///   : ⬚[0 0 0.3] draw {"circle" [25 25 20] 1_1_0} [50 50]
+    ///
+    /// See also: [layout]
+    (2, Draw, Encoding, "draw", Impure),
+    /// Encode a 3D mesh into a byte array with the specified format
+    ///
+    /// The first argument is the format, and the second is the mesh.
+    ///
+    /// A mesh is a list of 2 boxes. The first is a list of `[x y z]` vertices.
+    /// The second is a list of faces. Each face is a row of indices into the vertices, ordered counter-clockwise when seen from outside.
+    /// Faces with more than 3 vertices are split into triangles for formats that need them.
+    /// ex: # Experimental!
+    ///   : Verts ← [0_0_0 1_0_0 0_1_0 0_0_1]
+    ///   : Faces ← [0_2_1 0_1_3 0_3_2 1_2_3]
+    ///   : °utf₈ mesh "obj" {Verts Faces}
+    ///
+    /// The mesh can also be a rank 3 array of voxels. Each nonzero element at index `[x y z]` becomes a unit cube from `[x y z]` to `[x+1 y+1 z+1]`.
+    /// Only the outer faces of the cubes are kept.
+    /// ex: # Experimental!
+    ///   : ⧻ mesh "stl" ↯2_2_2 1
+    ///
+    /// Supported formats are `stl`, `obj`, `gltf`, and `glb`.
+    /// The bytes can be written to a file with [&fwa] and opened in other 3D tools.
+    ///
+    /// Instead of a format, the first argument can be a `[h w]` size to render a preview image, looking down at an angle with the z axis pointing up.
+    /// ex: # Experimental!
+    ///   : mesh [100 100] ≤12 ⊞+⟜(⊞+.) ×. -3.5⇡8
+    ///
+    /// See also: [img], [draw]
+    (2, Mesh, Encoding, "mesh"),
+    /// Encode a document as PDF bytes
+    ///
+    /// The argument is a list of boxed blocks. Each block is laid out below the previous one, starting new pages as needed.
+    /// - A string is a paragraph of text. Long lines are wrapped.
+    /// - A rank 2 numeric array, or rank 3 with 1 to 4 color channels, is an image.
+    /// - A rank 2 box array is a table.
+    /// A single block can also be passed without boxing it in a list.
+    /// ex: # Experimental!
+    ///   : ⧻ pdf "Hello, World!"
+    ///
+    /// [un][pdf] extracts the text and images of each page of a PDF. Each page is a map with `text` and `images` keys.
+    /// ex: # Experimental!
+    ///   : °pdf pdf {"Uiua" [1_2 3_4] [{"a" "b"} {1 2}]}
+    /// ex: # Experimental!
+    ///   : get "text" °□⊢ °pdf pdf {"Title" "Some text on the page"}
+    /// Images come back as arrays.
+    /// ex: # Experimental!
+    ///   : get "images" °□⊢ °pdf pdf ⊞×.÷4⇡4
+    ///
+    /// Only the standard fonts are used for generated text, so characters outside of Windows-1252 are replaced with `?`.
+    ///
+    /// See also: [xlsx], [draw]
+    (1, Pdf, Encoding, "pdf"),
 );
 
 macro_rules! impl_primitive {
@@ -3673,6 +4365,7 @@ macro_rules! impl_primitive {
             UndoRotate(usize),
             ReduceDepth(usize),
             StackN { n: usize, inverse: bool },
+            WhenBranch(usize),
         }
 
         impl ImplPrimitive {
@@ -3687,6 +4380,7 @@ macro_rules! impl_primitive {
                     ImplPrimitive::UndoRotate(n) => *n + 1,
                     ImplPrimitive::ReduceDepth(_) => 1,
                     ImplPrimitive::StackN { n, .. } => *n,
+                    ImplPrimitive::WhenBranch(_) => 2,
                     _ => return None
                 })
             }
@@ -3697,6 +4391,7 @@ macro_rules! impl_primitive {
                     ImplPrimitive::UndoReverse { n, .. } => *n,
                     ImplPrimitive::UndoRotate(n) => *n,
                     ImplPrimitive::StackN { n, .. } => *n,
+                    ImplPrimitive::WhenBranch(_) => 2,
                     _ if self.modifier_args().is_some() => return None,
                     _ => 1
                 })
@@ -3756,8 +4451,10 @@ impl_primitive!(
     (1, UnSort, Impure),
     (1, UnJson),
     (1, UnBinary),
+    (1, UnAst),
     (1, UnCsv),
     (1, UnXlsx),
+    (1, UnPdf),
     (1, UnFft),
     (1, UnDatetime),
     (2, ProgressiveIndexOf),
@@ -3773,6 +4470,7 @@ impl_primitive!(
     (1, UndoFix),
     (2, UndoUnbits),
     (2, AntiBase),
+    (2, AntiPack),
     (3, UndoSelect),
     (3, UndoPick),
     (3, UndoTake),
@@ -3819,10 +4517,13 @@ impl_primitive!(
     ((1)[3], AstarPop),
     ((2)[2], PathFirst),
     ((1)[2], PathPop),
+    ((2)[2], BoundedPath),
+    ((2)[3], BoundedAstar),
     (2[1], SplitByScalar),
     (2[1], SplitBy),
     (2[1], SplitByKeepEmpty),
     (2, MatrixDiv),
+    (2, DotProduct),
     // Implementation details
     (1, Utf16),
     ([2], RepeatWithInverse),
@@ -3830,6 +4531,14 @@ impl_primitive!(
     (2(1), ValidateType),
     (2(0), ValidateTypeConsume),
     (2(0), TestAssert, Impure),
+    (2, DualMul),
+    (2, DualDiv),
+    (2, DualPow),
+    (2, DualLog),
+    (1, DualSqrt),
+    (1, DualSin),
+    (1, DualCos),
+    (1, DualAbs),
     /// Validate that a non-boxed variant field has a valid type and rank
     (1, ValidateNonBoxedVariant),
     (2(1), ValidateVariant),
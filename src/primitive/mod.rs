@@ -14,6 +14,7 @@ use std::{
     collections::HashMap,
     f64::consts::{PI, TAU},
     fmt,
+    hash::Hash,
     sync::{
         atomic::{self, AtomicUsize},
         OnceLock,
@@ -31,9 +32,10 @@ use crate::{
     boxed::Boxed,
     encode,
     lex::{AsciiToken, SUBSCRIPT_DIGITS},
+    memo_cache::StableHasher,
     sys::*,
     value::*,
-    FunctionId, Ops, Shape, Signature, Uiua, UiuaErrorKind, UiuaResult,
+    FunctionId, Node, Ops, Purity, Shape, SigNode, Signature, Uiua, UiuaErrorKind, UiuaResult,
 };
 
 /// Categories of primitives
@@ -212,8 +214,10 @@ impl fmt::Display for ImplPrimitive {
             UnSort => write!(f, "{Un}{Sort}"),
             UnJson => write!(f, "{Un}{Json}"),
             UnBinary => write!(f, "{Un}{Binary}"),
+            UnAst => write!(f, "{Un}{Ast}"),
             UnCsv => write!(f, "{Un}{Csv}"),
             UnXlsx => write!(f, "{Un}{Xlsx}"),
+            UnPdf => write!(f, "{Un}{Pdf}"),
             UnFft => write!(f, "{Un}{Fft}"),
             UnDatetime => write!(f, "{Un}{DateTime}"),
             UnBoth => write!(f, "{Un}{Both}"),
@@ -226,6 +230,7 @@ impl fmt::Display for ImplPrimitive {
             ProgressiveIndexOf => write!(f, "{Un}{By}{Select}"),
             UndoUnbits => write!(f, "{Under}{Un}{Bits}"),
             AntiBase => write!(f, "{Under}{Base}"),
+            AntiPack => write!(f, "{Anti}{Pack}"),
             UndoReverse { n, .. } => write!(f, "{Under}{Reverse}({n})"),
             UndoTransposeN(n, _) => write!(f, "{Under}{Transpose}({n})"),
             UndoRotate(n) => write!(f, "{Under}{Rotate}({n})"),
@@ -277,10 +282,20 @@ impl fmt::Display for ImplPrimitive {
             AstarPop => write!(f, "{Pop}{Astar}"),
             PathFirst => write!(f, "{First}{Path}"),
             PathPop => write!(f, "{Pop}{Path}"),
+            BoundedPath | BoundedAstar => write!(f, "{Path}"),
             SplitByScalar => write!(f, "{Partition}{Box}{By}{Ne}"),
             SplitBy => write!(f, "{Partition}{Box}{Not}{By}{Mask}"),
             SplitByKeepEmpty => write!(f, "{Un}{Reduce}$\"_…_\""),
             MatrixDiv => write!(f, "{Anti}{Under}{Transpose}({Reduce}{Add}{Mul})"),
+            DotProduct => write!(f, "{Reduce}{Add}{Mul}"),
+            DualMul => write!(f, "{Derivative}{Mul}"),
+            DualDiv => write!(f, "{Derivative}{Div}"),
+            DualPow => write!(f, "{Derivative}{Pow}"),
+            DualLog => write!(f, "{Derivative}{Log}"),
+            DualSqrt => write!(f, "{Derivative}{Sqrt}"),
+            DualSin => write!(f, "{Derivative}{Sin}"),
+            DualCos => write!(f, "{Derivative}{Cos}"),
+            DualAbs => write!(f, "{Derivative}{Abs}"),
             &ReduceDepth(n) => {
                 for _ in 0..n {
                     write!(f, "{Rows}")?;
@@ -312,6 +327,7 @@ impl fmt::Display for ImplPrimitive {
                     .collect();
                 write!(f, "{Stack}{n_str}")
             }
+            WhenBranch(_) => write!(f, "{When}"),
             RepeatWithInverse => write!(f, "{Repeat}"),
             RepeatCountConvergence => write!(f, "{Un}{Repeat}"),
             ValidateType => write!(f, "{Un}…{Type}{Dup}"),
@@ -519,11 +535,24 @@ impl Primitive {
         matches!(
             self,
             (Reach | Off | Backward | Above | Around)
-                | (Or | Base | Fft | Layout | Binary)
+                | (Or | Base | Fft | Layout | Binary | Pack | LinProg)
+                | (Html | Markdown)
                 | Astar
-                | (Derivative | Integral)
-                | Sys(Ffi | MemCopy | MemFree | TlsListen | Breakpoint)
+                | (Components | Toposort | StrongComponents | SpanningTree)
+                | (Derivative | Integral | Gradient | Interval)
+                | Sys(Ffi | MemCopy | MemFree | TlsListen | TlsAlpn | Breakpoint)
+                | Sys(LogDebug | LogInfo | LogWarn | LogError)
+                | Sys(Mouse | Gamepad | SmtpSend)
+                | Sys(SerialPorts | SerialOpen | SerialRead | SerialWrite)
                 | (Stringify | Quote | Sig)
+                | (Docstring | Source | Arity)
+                | (Ast | Eval | Gensym)
+                | (GetPath | SetPath | UpdatePath)
+                | (Channel | RecvAny | Cell | Update)
+                | (Timeout | Retry | Using | When)
+                | (Raise | ErrorKind | AssertImage)
+                | (Oscillator | Envelope | Biquad)
+                | (Draw | Mesh | Pdf)
         )
     }
     /// Check if this primitive is deprecated
@@ -888,6 +917,72 @@ impl Primitive {
                     .into());
                 }
             }
+            Primitive::Raise => {
+                let kind = env.pop(1)?.as_string(env, "Error kind must be a string")?;
+                let payload = env.pop(2)?;
+                return Err(UiuaErrorKind::Throw(
+                    payload.into(),
+                    env.span().clone(),
+                    env.asm.inputs.clone().into(),
+                )
+                .error()
+                .with_tag(kind));
+            }
+            Primitive::Docstring | Primitive::Source | Primitive::Arity => {
+                let name = env.pop(1)?;
+                let name = name.as_string(env, "Binding name must be a string")?;
+                let name = name.trim();
+                let binding = (env.asm.find_binding(name))
+                    .ok_or_else(|| env.error(format!("No binding named `{name}`")))?;
+                let val = match self {
+                    Primitive::Docstring => Value::from(
+                        (binding.meta.comment.as_ref()).map_or("", |comment| &comment.text),
+                    ),
+                    Primitive::Source => (binding.meta.source.as_ref())
+                        .unwrap_or(&binding.span)
+                        .as_str(&env.asm.inputs, |s| Value::from(s)),
+                    _ => {
+                        let sig = binding.kind.sig().ok_or_else(|| {
+                            env.error(format!("`{name}` is not a function or constant"))
+                        })?;
+                        [sig.args, sig.outputs]
+                            .into_iter()
+                            .map(|n| n as f64)
+                            .collect()
+                    }
+                };
+                env.push(val);
+            }
+            Primitive::Gensym => {
+                let prefix = (env.pop(1)?).as_string(env, "Name prefix must be a string")?;
+                if prefix.is_empty() || !prefix.chars().all(crate::is_ident_start) {
+                    return Err(
+                        env.error(format!("Name prefix must be letters, but it is {prefix:?}"))
+                    );
+                }
+                let name = loop {
+                    env.rt.gensym_count += 1;
+                    let sub: String = (env.rt.gensym_count.to_string().chars())
+                        .map(|c| SUBSCRIPT_DIGITS[(c as u32 as u8 - b'0') as usize])
+                        .collect();
+                    let name = format!("{prefix}{sub}");
+                    if env.asm.find_binding(&name).is_none() {
+                        break name;
+                    }
+                };
+                env.push(name);
+            }
+            Primitive::ErrorKind => {
+                let kind = (env.rt.handled_errors.last().cloned())
+                    .ok_or_else(|| env.error("No error is being handled"))?;
+                env.push(kind.as_str());
+            }
+            Primitive::AssertImage => {
+                let tolerance = env.pop(1)?;
+                let expected = env.pop(2)?;
+                let actual = env.pop(3)?;
+                encode::assert_image(tolerance, expected, actual, env)?;
+            }
             Primitive::Rand => env.push(random()),
             Primitive::Gen => env.dyadic_rr_env(Value::gen)?,
             Primitive::Tag => {
@@ -916,9 +1011,25 @@ impl Primitive {
                 let id = env.pop(1)?;
                 env.try_recv(id)?;
             }
-            Primitive::Now => env.push(env.rt.backend.now()),
+            Primitive::Channel => {
+                let capacity = env.pop(1)?;
+                env.create_channel(capacity)?;
+            }
+            Primitive::RecvAny => {
+                let channels = env.pop(1)?;
+                env.recv_any(channels)?;
+            }
+            Primitive::Cell => {
+                let init = env.pop(1)?;
+                env.create_cell(init)?;
+            }
+            Primitive::Now => env.push(env.now()),
             Primitive::TimeZone => {
-                let o = env.rt.backend.timezone().map_err(|e| env.error(e))?;
+                let o = if env.is_deterministic() {
+                    0.0
+                } else {
+                    env.rt.backend.timezone().map_err(|e| env.error(e))?
+                };
                 env.push(o);
             }
             Primitive::DateTime => env.monadic_ref_env(Value::datetime)?,
@@ -940,6 +1051,18 @@ impl Primitive {
                 let val = map.get(&key, env)?;
                 env.push(val);
             }
+            Primitive::GetPath => {
+                let path = env.pop("path")?;
+                let val = env.pop("value")?;
+                env.push(val.get_path(&path, env)?);
+            }
+            Primitive::SetPath => {
+                let path = env.pop("path")?;
+                let new = env.pop("new value")?;
+                let mut val = env.pop("value")?;
+                val.set_path(&path, new, env)?;
+                env.push(val);
+            }
             Primitive::Remove => {
                 let key = env.pop("key")?;
                 let mut map = env.pop("map")?;
@@ -957,15 +1080,31 @@ impl Primitive {
             Primitive::Regex => regex(env)?,
             Primitive::Json => env.monadic_ref_env(Value::to_json_string)?,
             Primitive::Binary => env.monadic_ref_env(Value::to_binary)?,
+            Primitive::Ast => code::ast(env)?,
+            Primitive::Eval => code::eval(env)?,
+            Primitive::Pack => env.dyadic_rr_env(encode::pack)?,
             Primitive::Csv => env.monadic_ref_env(Value::to_csv)?,
             Primitive::Xlsx => {
                 env.monadic_ref_env(|value, env| value.to_xlsx(env).map(EcoVec::from))?
             }
+            Primitive::Html => {
+                env.monadic_ref_env(|value, env| value.to_html_table().map_err(|e| env.error(e)))?
+            }
+            Primitive::Markdown => env.monadic_ref_env(|value, env| {
+                value.to_markdown_table().map_err(|e| env.error(e))
+            })?,
             Primitive::ImageEncode => encode::image_encode(env)?,
             Primitive::GifEncode => encode::gif_encode(env)?,
             Primitive::AudioEncode => encode::audio_encode(env)?,
             Primitive::Layout => env.dyadic_oo_env(encode::layout_text)?,
+            Primitive::Draw => draw::draw(env)?,
+            Primitive::Mesh => mesh::mesh(env)?,
+            Primitive::Pdf => pdf::pdf_encode(env)?,
+            Primitive::Oscillator => synth::oscillator(env)?,
+            Primitive::Envelope => synth::envelope(env)?,
+            Primitive::Biquad => synth::biquad(env)?,
             Primitive::Fft => algorithm::fft(env)?,
+            Primitive::LinProg => linprog::linprog(env)?,
             Primitive::Stringify
             | Primitive::Quote
             | Primitive::Sig
@@ -974,13 +1113,20 @@ impl Primitive {
             | Primitive::Anti
             | Primitive::Under
             | Primitive::Obverse
-            | Primitive::Switch => {
+            | Primitive::Switch
+            | Primitive::UpdatePath => {
                 return Err(env.error(format!(
                     "{} was not inlined. This is a bug in the interpreter",
                     self.format()
                 )))
             }
-            Primitive::Sys(io) => io.run(env)?,
+            Primitive::Sys(io) => io.run_or_suspend(env).map_err(|e| {
+                if e.tag().is_none() && matches!(e.kind, UiuaErrorKind::Run { .. }) {
+                    e.with_tag("sys")
+                } else {
+                    e
+                }
+            })?,
             prim => {
                 return Err(env.error(if prim.modifier_args().is_some() {
                     format!(
@@ -1119,6 +1265,30 @@ impl Primitive {
                 let [neighbors, is_goal] = get_ops(ops, env)?;
                 algorithm::path(neighbors, is_goal, None, env)?;
             }
+            Primitive::Components => {
+                let [neighbors] = get_ops(ops, env)?;
+                graph::components(neighbors, env)?;
+            }
+            Primitive::Toposort => {
+                let [neighbors] = get_ops(ops, env)?;
+                graph::toposort(neighbors, env)?;
+            }
+            Primitive::StrongComponents => {
+                let [neighbors] = get_ops(ops, env)?;
+                graph::strong_components(neighbors, env)?;
+            }
+            Primitive::SpanningTree => {
+                let [neighbors] = get_ops(ops, env)?;
+                graph::spanning_tree(neighbors, env)?;
+            }
+            Primitive::Gradient => {
+                let [f] = get_ops(ops, env)?;
+                autodiff::gradient(f, env)?;
+            }
+            Primitive::Interval => {
+                let [f] = get_ops(ops, env)?;
+                interval::interval(f, env)?;
+            }
             Primitive::Memo => {
                 let [f] = get_ops(ops, env)?;
                 let mut args = Vec::with_capacity(f.sig.args);
@@ -1137,16 +1307,40 @@ impl Primitive {
                     }
                 }
                 drop(memo);
-                for arg in args.iter().rev() {
-                    env.push(arg.clone());
-                }
-                env.exec(f.node.clone())?;
-                let outputs = env.clone_stack_top(f.sig.outputs)?;
+                // Pure functions can use the disk cache
+                let func_hash = (env.rt.memo_cache.is_some()
+                    && f.node.is_pure(Purity::Pure, &env.asm))
+                .then(|| {
+                    let mut hasher = StableHasher::default();
+                    (f.node.deep_hash(&env.asm, &mut hasher)).then(|| hasher.finish128())
+                })
+                .flatten();
+                let cached = func_hash.and_then(|func| {
+                    let outputs = env.rt.memo_cache.as_ref()?.get(func, &args, env)?;
+                    (outputs.len() == f.sig.outputs).then_some(outputs)
+                });
+                let outputs = if let Some(outputs) = cached {
+                    for val in outputs.iter().cloned() {
+                        env.push(val);
+                    }
+                    outputs
+                } else {
+                    for arg in args.iter().rev() {
+                        env.push(arg.clone());
+                    }
+                    env.exec(f.node.clone())?;
+                    let outputs = env.clone_stack_top(f.sig.outputs)?;
+                    if let (Some(func), Some(cache)) = (func_hash, &env.rt.memo_cache) {
+                        // Failing to cache should not fail the program
+                        _ = cache.insert(func, &args, &outputs, env);
+                    }
+                    outputs
+                };
                 let mut memo = env.rt.memo.get_or_default().borrow_mut();
                 memo.borrow_mut()
                     .entry(f.node)
                     .or_default()
-                    .insert(args, outputs.clone());
+                    .insert(args, outputs);
             }
             Primitive::Spawn => {
                 let [f] = get_ops(ops, env)?;
@@ -1156,6 +1350,16 @@ impl Primitive {
                 let [f] = get_ops(ops, env)?;
                 env.spawn(f.sig.args, true, f)?;
             }
+            Primitive::Update => {
+                let [f] = get_ops(ops, env)?;
+                env.update_cell(f)?;
+            }
+            Primitive::Retry => algorithm::retry(ops, env)?,
+            Primitive::Using => algorithm::using(ops, env)?,
+            Primitive::Timeout => {
+                let [f] = get_ops(ops, env)?;
+                env.with_timeout(f)?;
+            }
             Primitive::Sys(op) => op.run_mod(ops, env)?,
             prim => {
                 return Err(env.error(if prim.modifier_args().is_some() {
@@ -1262,6 +1466,7 @@ impl ImplPrimitive {
             ImplPrimitive::UnFix => env.monadic_mut_env(Value::unfix)?,
             ImplPrimitive::UnShape => env.monadic_ref_env(Value::unshape)?,
             ImplPrimitive::StackN { n, inverse } => stack_n(env, *n, *inverse)?,
+            &ImplPrimitive::WhenBranch(branches) => pattern::when_branch(branches, env)?,
             ImplPrimitive::UnStack => stack(env, true)?,
             ImplPrimitive::Primes => env.monadic_ref_env(Value::primes)?,
             ImplPrimitive::UnBox => {
@@ -1288,6 +1493,7 @@ impl ImplPrimitive {
                 let val = Value::from_binary(&bytes, env)?;
                 env.push(val);
             }
+            ImplPrimitive::UnAst => code::unast(env)?,
             ImplPrimitive::UnCsv => {
                 let csv = env.pop(1)?.as_string(env, "CSV expects a string")?;
                 let val = Value::from_csv(&csv, env)?;
@@ -1298,6 +1504,7 @@ impl ImplPrimitive {
                 let val = Value::from_xlsx(&xlsx, env)?;
                 env.push(val);
             }
+            ImplPrimitive::UnPdf => pdf::pdf_decode(env)?,
             ImplPrimitive::UnFft => algorithm::unfft(env)?,
             ImplPrimitive::UnDatetime => env.monadic_ref_env(Value::undatetime)?,
             ImplPrimitive::ProgressiveIndexOf => env.dyadic_rr_env(Value::progressive_index_of)?,
@@ -1315,6 +1522,27 @@ impl ImplPrimitive {
                     .map_err(|e| env.error(e))?;
             }
             ImplPrimitive::MatrixDiv => env.dyadic_rr_env(Value::matrix_div)?,
+            ImplPrimitive::DotProduct => {
+                let a = env.pop(1)?;
+                let b = env.pop(2)?;
+                if let Some(product) = a.dot_product(&b, env) {
+                    env.push(product);
+                } else {
+                    env.push(b);
+                    env.push(a);
+                    env.dyadic_oo_env(Value::mul)?;
+                    let add = SigNode::new((2, 1), Node::Prim(Primitive::Add, env.span_index()));
+                    reduce::reduce_impl(add, 0, env)?;
+                }
+            }
+            ImplPrimitive::DualMul => env.dyadic_oo_env(dual::mul)?,
+            ImplPrimitive::DualDiv => env.dyadic_oo_env(dual::div)?,
+            ImplPrimitive::DualPow => env.dyadic_oo_env(dual::pow)?,
+            ImplPrimitive::DualLog => env.dyadic_oo_env(dual::log)?,
+            ImplPrimitive::DualSqrt => env.monadic_env(dual::sqrt)?,
+            ImplPrimitive::DualSin => env.monadic_env(dual::sin)?,
+            ImplPrimitive::DualCos => env.monadic_env(dual::cos)?,
+            ImplPrimitive::DualAbs => env.monadic_env(dual::abs)?,
             // Unders
             ImplPrimitive::UndoUnbits => {
                 let orig_shape = env.pop(1)?;
@@ -1322,6 +1550,7 @@ impl ImplPrimitive {
                 env.push(val.undo_un_bits(&orig_shape, env)?);
             }
             ImplPrimitive::AntiBase => env.dyadic_rr_env(Value::antibase)?,
+            ImplPrimitive::AntiPack => env.dyadic_rr_env(encode::unpack)?,
             &ImplPrimitive::UndoReverse { n, all } => {
                 env.require_height(n)?;
                 let end = env.stack_height() - n;
@@ -1743,6 +1972,14 @@ impl ImplPrimitive {
                 let [neighbors, is_goal] = get_ops(ops, env)?;
                 algorithm::path_pop(neighbors, is_goal, None, env)?;
             }
+            ImplPrimitive::BoundedPath => {
+                let [neighbors, is_goal] = get_ops(ops, env)?;
+                algorithm::path_within(neighbors, is_goal, None, env)?;
+            }
+            ImplPrimitive::BoundedAstar => {
+                let [neighbors, heuristic, is_goal] = get_ops(ops, env)?;
+                algorithm::path_within(neighbors, is_goal, Some(heuristic), env)?;
+            }
             &ImplPrimitive::ReduceDepth(depth) => reduce::reduce(ops, depth, env)?,
             ImplPrimitive::RepeatWithInverse => loops::repeat(ops, true, false, env)?,
             ImplPrimitive::RepeatCountConvergence => loops::repeat(ops, false, true, env)?,
@@ -2171,7 +2408,7 @@ impl PrimExample {
     /// Get the example's output
     pub fn output(&self) -> &UiuaResult<Vec<String>> {
         self.output.get_or_init(|| {
-            let mut env = Uiua::with_safe_sys();
+            let mut env = Uiua::with_safe_sys().with_deterministic(true);
             match env.run_str(&self.input) {
                 Ok(_) => Ok(env.take_stack().into_iter().map(|val| val.show()).collect()),
                 Err(e) => Err(e),
@@ -2338,6 +2575,7 @@ fn parse_doc_line_fragments(mut line: &str) -> Vec<PrimDocFragment> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::IntoSysBackend;
 
     #[test]
     fn name_collisions() {
@@ -2358,8 +2596,7 @@ mod tests {
             for line in &prim.doc().lines {
                 if let PrimDocLine::Example(ex) = line {
                     if [
-                        "&sl", "&tcpc", "&tlsc", "&ast", "&clip", "&fo", "&fc", "&fde", "&ftr",
-                        "&fld", "&fif", "&fras", "&frab", "&fmd", "timezone", "&b",
+                        "&sl", "&tcpc", "&tlsc", "&ast", "&clip", "&frab", "&b", "&smtp", "&serial",
                     ]
                     .iter()
                     .any(|prim| ex.input.contains(prim))
@@ -2367,7 +2604,11 @@ mod tests {
                         continue;
                     }
                     println!("{prim} example:\n{}", ex.input); // Allow println
-                    let mut env = Uiua::with_backend(SafeSys::with_thread_spawning());
+                    let backend =
+                        MemFsBackend::wrap(SafeSys::with_thread_spawning().into_sys_backend())
+                            .with_file("example.txt", EXAMPLE_TXT)
+                            .with_file("example.ua", EXAMPLE_UA);
+                    let mut env = Uiua::with_backend(backend).with_deterministic(true);
                     match env.run_str(&ex.input) {
                         Ok(mut comp) => {
                             if let Some(diag) = comp.take_diagnostics().into_iter().next() {
@@ -2717,4 +2958,70 @@ mod tests {
 
         std::fs::write("uiua.tmLanguage.json", text).expect("Failed to write grammar file");
     }
+
+    #[test]
+    fn reflection() {
+        use crate::{Uiua, Value};
+        let mut env = Uiua::with_safe_sys();
+        let code = "\
+# Experimental!
+# Add one to a number
+Incr ← +1
+Avg ← ÷⊃⧻/+
+Sigs! ←^ repr arity⊢
+Sigs!Avg
+arity \"Incr\"
+source \"Avg\"
+docs \"Avg\"
+docs \"Incr\"";
+        env.run_str(code).unwrap();
+        let stack = env.take_stack();
+        let strings: Vec<String> = (stack[2..].iter())
+            .map(|val| val.as_string(&env, "").unwrap())
+            .collect();
+        assert_eq!(stack[0], Value::from([1.0, 1.0]));
+        assert_eq!(stack[1], Value::from([1.0, 1.0]));
+        assert_eq!(strings, ["Avg ← ÷⊃⧻/+", "", "Add one to a number"]);
+        let Err(err) = env.run_str("# Experimental!\narity \"Nope\"") else {
+            panic!("reflecting on a missing binding should fail");
+        };
+        assert!(err.to_string().contains("No binding named `Nope`"), "{err}");
+    }
+
+    #[test]
+    fn doc_example_snapshots() {
+        use crate::{test_runner::update_snapshots, PrimDocLine, Snapshot, SnapshotStatus};
+        use Primitive::*;
+        // Only examples whose outputs exercise grid formatting are snapshotted,
+        // so that editing other docs does not change the snapshot
+        let prims = [Box, Couple, Fill, Fix, Join, Map, Reshape, Table, Transpose];
+        let mut actual = String::new();
+        for prim in prims {
+            for line in &prim.doc().lines {
+                let PrimDocLine::Example(ex) = line else {
+                    continue;
+                };
+                actual.push_str(&format!("## {}\n{}\n", prim.name(), ex.input()));
+                match ex.output_strings() {
+                    Ok(outputs) => {
+                        for output in outputs {
+                            actual.push_str(output);
+                            actual.push('\n');
+                        }
+                    }
+                    Err(e) => actual.push_str(&format!("Error: {e}\n")),
+                }
+                actual.push('\n');
+            }
+        }
+        let snapshot = Snapshot::new("tests_special/snapshots/doc_examples.txt");
+        let status = snapshot.check(&actual, update_snapshots()).unwrap();
+        if let SnapshotStatus::Mismatched { .. } = status {
+            panic!(
+                "Doc example outputs do not match {}. \
+                Run with UIUA_UPDATE_SNAPSHOTS=1 to update it.",
+                snapshot.path().display()
+            );
+        }
+    }
 }
@@ -0,0 +1,370 @@
+//! Sampling and allocation profilers for Uiua code
+//!
+//! Unlike the [`profile`](crate::profile) module, which profiles the interpreter itself,
+//! these measure where a Uiua program spends its time and memory.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{self, Write},
+    mem::take,
+    ops::AddAssign,
+};
+
+use ecow::EcoString;
+
+use crate::{CodeSpan, FunctionId, Node, Span, Uiua};
+
+/// Collects samples while a program runs
+#[derive(Debug, Clone)]
+pub(crate) struct Profiler {
+    /// The minimum time between samples in seconds
+    interval: f64,
+    /// The time at which the last sample was taken
+    last_sample: f64,
+    sample_count: usize,
+    total_time: f64,
+    stacks: HashMap<Vec<EcoString>, f64>,
+    primitives: HashMap<EcoString, f64>,
+    spans: HashMap<usize, f64>,
+    functions: HashMap<FunctionId, f64>,
+}
+
+/// Tracks the array data allocated by each span
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AllocProfiler {
+    spans: HashMap<usize, (Option<EcoString>, AllocStats)>,
+    primitives: HashMap<EcoString, AllocStats>,
+    total: AllocStats,
+}
+
+/// The part of a sample that comes from the node that was executing
+pub(crate) struct Leaf {
+    prim: Option<EcoString>,
+    span: Option<usize>,
+}
+
+impl Leaf {
+    pub(crate) fn new(node: &Node) -> Self {
+        let prim = match node {
+            Node::Prim(prim, _) | Node::Mod(prim, ..) => Some(prim.name().into()),
+            Node::ImplPrim(prim, _) => Some(prim.to_string().into()),
+            Node::ImplMod(prim, ..) => Some(prim.to_string().into()),
+            _ => None,
+        };
+        Leaf {
+            prim,
+            span: node.span(),
+        }
+    }
+}
+
+impl Profiler {
+    pub(crate) fn new(interval: f64) -> Self {
+        Profiler {
+            interval,
+            last_sample: 0.0,
+            sample_count: 0,
+            total_time: 0.0,
+            stacks: HashMap::new(),
+            primitives: HashMap::new(),
+            spans: HashMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+    /// Mark the start of execution
+    pub(crate) fn start(&mut self, now: f64) {
+        self.last_sample = now;
+    }
+}
+
+impl Uiua {
+    /// Take a sample if enough time has passed since the last one
+    ///
+    /// The sample is attributed to the node that just finished executing,
+    /// inside all of the functions currently on the call stack.
+    pub(crate) fn sample(&mut self, leaf: Leaf) {
+        let now = self.rt.backend.now();
+        let Some(profiler) = &mut self.rt.profiler else {
+            return;
+        };
+        let elapsed = now - profiler.last_sample;
+        if elapsed < profiler.interval {
+            return;
+        }
+        profiler.last_sample = now;
+        profiler.sample_count += 1;
+        profiler.total_time += elapsed;
+        let ids: Vec<&FunctionId> = (self.rt.call_stack.iter())
+            .filter_map(|frame| frame.id.as_ref())
+            .collect();
+        let mut stack: Vec<EcoString> = ids.iter().map(|id| id.to_string().into()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            // Recursive functions only count once
+            if !ids[..i].contains(id) {
+                *profiler.functions.entry((*id).clone()).or_default() += elapsed;
+            }
+        }
+        if let Some(prim) = leaf.prim {
+            *profiler.primitives.entry(prim.clone()).or_default() += elapsed;
+            stack.push(prim);
+        }
+        if let Some(span) = leaf.span {
+            *profiler.spans.entry(span).or_default() += elapsed;
+        }
+        *profiler.stacks.entry(stack).or_default() += elapsed;
+    }
+    /// Record the allocations made by a node, not including those of nested nodes
+    pub(crate) fn record_allocs(&mut self, leaf: &Leaf, (bytes, count): (isize, usize)) {
+        let Some(profiler) = &mut self.rt.alloc_profiler else {
+            return;
+        };
+        if bytes <= 0 {
+            return;
+        }
+        let stats = AllocStats {
+            bytes: bytes as usize,
+            count,
+        };
+        profiler.total += stats;
+        if let Some(prim) = &leaf.prim {
+            *profiler.primitives.entry(prim.clone()).or_default() += stats;
+        }
+        if let Some(span) = leaf.span {
+            let entry = profiler.spans.entry(span).or_default();
+            entry.0 = entry.0.take().or_else(|| leaf.prim.clone());
+            entry.1 += stats;
+        }
+    }
+    /// Take the allocation profile collected while running
+    ///
+    /// Returns `None` if allocation profiling was not enabled with
+    /// [`Uiua::with_alloc_profiling`]
+    pub fn take_alloc_profile(&mut self) -> Option<AllocProfile> {
+        let profiler = take(self.rt.alloc_profiler.as_deref_mut()?);
+        let mut spans: HashMap<CodeSpan, (Option<EcoString>, AllocStats)> = HashMap::new();
+        for (span, (prim, stats)) in profiler.spans {
+            if let Some(Span::Code(span)) = self.asm.spans.get(span) {
+                let entry = spans.entry(span.clone()).or_default();
+                entry.0 = entry.0.take().or(prim);
+                entry.1 += stats;
+            }
+        }
+        let mut spans: Vec<_> = (spans.into_iter())
+            .map(|(span, (prim, stats))| (span, prim, stats))
+            .collect();
+        spans.sort_by_key(|(_, _, stats)| Reverse(stats.bytes));
+        let mut primitives: Vec<_> = profiler.primitives.into_iter().collect();
+        primitives.sort_by_key(|(_, stats)| Reverse(stats.bytes));
+        Some(AllocProfile {
+            total: profiler.total,
+            spans,
+            primitives,
+        })
+    }
+    /// Take the profile collected while running
+    ///
+    /// Returns `None` if profiling was not enabled with [`Uiua::with_profiling`]
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        let interval = self.rt.profiler.as_ref()?.interval;
+        let profiler = self.rt.profiler.replace(Profiler::new(interval).into())?;
+        fn sorted<T>(map: impl IntoIterator<Item = (T, f64)>) -> Vec<(T, f64)> {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            entries
+        }
+        let mut spans = HashMap::new();
+        for (span, time) in profiler.spans {
+            if let Some(Span::Code(span)) = self.asm.spans.get(span) {
+                *spans.entry(span.clone()).or_default() += time;
+            }
+        }
+        Some(Profile {
+            sample_count: profiler.sample_count,
+            total_time: profiler.total_time,
+            primitives: sorted(profiler.primitives),
+            spans: sorted(spans),
+            functions: sorted(profiler.functions),
+            stacks: sorted(profiler.stacks),
+        })
+    }
+}
+
+/// The results of profiling a program
+///
+/// All times are in seconds.
+/// Every list is sorted from most to least time.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// The number of samples taken
+    pub sample_count: usize,
+    /// The total time covered by all samples
+    pub total_time: f64,
+    /// The time spent running each primitive
+    pub primitives: Vec<(EcoString, f64)>,
+    /// The time spent running the code at each span
+    pub spans: Vec<(CodeSpan, f64)>,
+    /// The time spent in each function, including the functions it calls
+    pub functions: Vec<(FunctionId, f64)>,
+    /// The time spent in each unique call stack
+    pub stacks: Vec<(Vec<EcoString>, f64)>,
+}
+
+impl Profile {
+    /// Format the call stacks in the folded format used by flamegraph tools
+    ///
+    /// Each line is a `;`-separated call stack followed by the time in microseconds
+    pub fn folded(&self) -> String {
+        let mut s = String::new();
+        for (stack, time) in &self.stacks {
+            let micros = (time * 1e6).round() as u64;
+            if micros == 0 {
+                continue;
+            }
+            let frames: Vec<_> = stack.iter().map(|frame| frame.replace(";", ":")).collect();
+            writeln!(s, "{} {micros}", frames.join(";")).unwrap();
+        }
+        s
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MAX_ROWS: usize = 10;
+        let total = self.total_time;
+        writeln!(
+            f,
+            "Profiled {:.2}ms with {} samples",
+            total * 1000.0,
+            self.sample_count
+        )?;
+        let row = |f: &mut fmt::Formatter, name: &dyn fmt::Display, time: f64| {
+            let percent = if total > 0.0 {
+                time / total * 100.0
+            } else {
+                0.0
+            };
+            writeln!(f, "  {:>10.2}ms {percent:>5.1}%  {name}", time * 1000.0)
+        };
+        if !self.functions.is_empty() {
+            writeln!(f, "\nFunctions:")?;
+            for (id, time) in self.functions.iter().take(MAX_ROWS) {
+                row(f, id, *time)?;
+            }
+        }
+        if !self.primitives.is_empty() {
+            writeln!(f, "\nPrimitives:")?;
+            for (prim, time) in self.primitives.iter().take(MAX_ROWS) {
+                row(f, prim, *time)?;
+            }
+        }
+        if !self.spans.is_empty() {
+            writeln!(f, "\nSpans:")?;
+            for (span, time) in self.spans.iter().take(MAX_ROWS) {
+                row(f, span, *time)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The number of bytes and allocations of array data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// The number of bytes allocated
+    pub bytes: usize,
+    /// The number of allocations
+    pub count: usize,
+}
+
+impl AddAssign for AllocStats {
+    fn add_assign(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.count += other.count;
+    }
+}
+
+impl fmt::Display for AllocStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+        let mut size = self.bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        let plural = if self.count == 1 { "" } else { "s" };
+        let size = if unit == 0 {
+            format!("{}B", self.bytes)
+        } else {
+            format!("{size:.1}{}", UNITS[unit])
+        };
+        write!(f, "{size:>9} in {:>6} allocation{plural}", self.count)
+    }
+}
+
+/// The array data allocated by a program
+///
+/// Allocations are attributed to the innermost span that made them.
+/// Every list is sorted from most to fewest bytes.
+#[derive(Debug, Clone, Default)]
+pub struct AllocProfile {
+    /// The total allocated by the program
+    pub total: AllocStats,
+    /// The allocations made by the code at each span,
+    /// along with the primitive at that span if there is one
+    pub spans: Vec<(CodeSpan, Option<EcoString>, AllocStats)>,
+    /// The allocations made by each primitive
+    pub primitives: Vec<(EcoString, AllocStats)>,
+}
+
+impl fmt::Display for AllocProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MAX_ROWS: usize = 10;
+        writeln!(f, "Allocated {}", self.total)?;
+        if !self.spans.is_empty() {
+            writeln!(f, "\nTop allocation sites:")?;
+            for (span, prim, stats) in self.spans.iter().take(MAX_ROWS) {
+                write!(f, "  {stats}  {span}")?;
+                if let Some(prim) = prim {
+                    write!(f, " ({prim})")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        if !self.primitives.is_empty() {
+            writeln!(f, "\nPrimitives:")?;
+            for (prim, stats) in self.primitives.iter().take(MAX_ROWS) {
+                writeln!(f, "  {stats}  {prim}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn sampling_profiler() {
+    use crate::Uiua;
+    let mut env = Uiua::with_safe_sys().with_profiling(std::time::Duration::ZERO);
+    env.run_str("F ← /+⇡\nF ⌈×1e5⚂").unwrap();
+    let profile = env.take_profile().unwrap();
+    assert!(profile.sample_count > 0);
+    assert!((profile.functions.iter()).any(|(id, _)| *id == "F"));
+    assert!((profile.primitives.iter()).any(|(prim, _)| prim == "range"));
+    assert!(profile.folded().contains("main;F;range "));
+    assert!(Uiua::with_safe_sys().take_profile().is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn alloc_profiler() {
+    use crate::Uiua;
+    let mut env = Uiua::with_safe_sys().with_alloc_profiling();
+    env.run_str("⇡+1e4⌊×10⚂").unwrap();
+    let profile = env.take_alloc_profile().unwrap();
+    let (_, prim, stats) = &profile.spans[0];
+    assert_eq!(prim.as_deref(), Some("range"));
+    assert!(stats.bytes >= 80_000);
+    assert!(Uiua::with_safe_sys().take_alloc_profile().is_none());
+}
@@ -3,28 +3,44 @@
 use std::{
     cell::RefCell,
     cmp::Ordering,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
     hash::Hash,
+    marker::PhantomData,
     mem::{size_of, take},
     panic::{catch_unwind, AssertUnwindSafe},
     path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
     time::Duration,
 };
 
-use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use crossbeam_channel::{Receiver, Select, Sender, TryRecvError};
+use ecow::EcoString;
+use parking_lot::{Mutex, ReentrantMutex};
 use thread_local::ThreadLocal;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::RNG;
 use crate::{
     algorithm::{self, validate_size_impl},
+    cowslice::{
+        end_alloc_scope, end_memory_tracking, live_bytes, start_alloc_scope, start_memory_tracking,
+        take_peak_bytes,
+    },
     fill::Fill,
     invert::match_format_pattern,
     lex::Span,
-    Array, ArrayLen, Assembly, BindingKind, BindingMeta, Boxed, CodeSpan, Compiler, Function,
-    FunctionId, Ident, Inputs, IntoSysBackend, LocalName, Node, Primitive, Report, SafeSys,
-    SigNode, Signature, SysBackend, TraceFrame, UiuaError, UiuaErrorKind, UiuaResult, Value,
-    VERSION,
+    profiler::{AllocProfiler, Leaf, Profiler},
+    seed_random,
+    sys::{Handle, HandleKind},
+    Array, ArrayLen, Assembly, BindingKind, BindingMeta, Boxed, CodeSpan, Compiler, FormatSettings,
+    Function, FunctionId, Ident, Inputs, IntoSysBackend, LocalName, MemoCache, Node, Primitive,
+    Report, SafeSys, SigNode, Signature, SysBackend, TraceFrame, UiuaError, UiuaErrorKind,
+    UiuaResult, Value, VERSION,
 };
 
 /// The Uiua interpreter
@@ -58,18 +74,61 @@ pub(crate) struct Runtime {
     pub(crate) execution_limit: Option<f64>,
     /// The time at which execution started
     pub(crate) execution_start: f64,
+    /// The time of the virtual clock, if execution is deterministic
+    pub(crate) virtual_time: Option<f64>,
+    /// A limit on the number of bytes of array data allocated at once
+    pub(crate) memory_limit: Option<usize>,
+    /// The bytes of array data still allocated at the end of the last run
+    memory_usage: usize,
+    /// A limit on the number of nodes executed
+    pub(crate) op_budget: Option<usize>,
+    /// The number of nodes executed
+    ops_executed: usize,
+    /// The number of names generated by [`Primitive::Gensym`]
+    pub(crate) gensym_count: usize,
     /// The recursion limit
     recursion_limit: usize,
+    /// Deadlines set by [`Primitive::Timeout`], the earliest last
+    deadlines: Vec<f64>,
+    /// The kinds of errors being handled by [`Primitive::Try`]
+    pub(crate) handled_errors: Vec<EcoString>,
     /// Whether the program was interrupted
     pub(crate) interrupted: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+    /// The state of a program that can be suspended at pending system calls
+    pub(crate) suspension: Option<Box<Suspension>>,
+    /// A hook to call before executing code at a span
+    exec_hook: Option<ExecHook>,
+    /// Counts calls to functions and holds their native code
+    #[cfg(feature = "jit")]
+    pub(crate) jit: crate::jit::Jit,
+    /// Recompiled assemblies to swap in at the next function call
+    hot_reload: Option<Receiver<Assembly>>,
     /// Whether to print the time taken to execute each instruction
     time_instrs: bool,
     /// The time at which the last instruction was executed
     last_time: f64,
+    /// The sampling profiler
+    pub(crate) profiler: Option<Box<Profiler>>,
+    /// The allocation profiler
+    pub(crate) alloc_profiler: Option<Box<AllocProfiler>>,
+    /// Snapshots of the stack at the start of recently executed lines
+    stack_history: VecDeque<StackSnapshot>,
+    /// The maximum number of stack snapshots to keep
+    history_capacity: usize,
+    /// Whether values shown with `&s` are colored
+    pub(crate) color_output: bool,
+    /// The format settings set by `&nf` and `&elide`
+    pub(crate) format_settings: FormatSettings,
     /// Arguments passed from the command line
     cli_arguments: Vec<String>,
     /// File that was passed to the interpreter for execution
     cli_file_path: PathBuf,
+    /// The directory relative paths are resolved against
+    ///
+    /// If this is `None`, the backend's working directory is used
+    pub(crate) working_dir: Option<PathBuf>,
+    /// Environment variables set, or unset if `None`, for this interpreter and the commands it runs
+    pub(crate) env_vars: BTreeMap<String, Option<String>>,
     /// Code for unevaluated pure constants, in case they are needed for macros
     ///
     /// This should only be used in the compile-time environment
@@ -82,6 +141,12 @@ pub(crate) struct Runtime {
     pub(crate) output_comments: HashMap<usize, Vec<Vec<Value>>>,
     /// Memoized values
     pub(crate) memo: Arc<ThreadLocal<RefCell<MemoMap>>>,
+    /// The disk cache for pure memoized functions
+    pub(crate) memo_cache: Option<MemoCache>,
+    /// Channels created with [`Primitive::Channel`], shared with spawned threads
+    channels: Arc<Mutex<HashMap<u64, SharedChannel>>>,
+    /// Cells created with [`Primitive::Cell`], shared with spawned threads
+    cells: Arc<Mutex<HashMap<u64, SharedCell>>>,
     /// The results of tests
     pub(crate) test_results: Vec<UiuaResult>,
     /// Reports to print
@@ -90,6 +155,39 @@ pub(crate) struct Runtime {
 
 type MemoMap = HashMap<Node, HashMap<Vec<Value>, Vec<Value>>>;
 
+/// A hook that is called before executing each piece of code that has a span
+///
+/// Returning an error stops execution with that error.
+/// See [`Uiua::with_exec_hook`].
+pub type ExecHook = Arc<dyn Fn(&Uiua, &CodeSpan) -> UiuaResult + Send + Sync>;
+
+/// A handle for swapping recompiled code into a running program
+///
+/// See [`Uiua::hot_reloader`].
+#[derive(Clone)]
+pub struct HotReloader(Sender<Assembly>);
+
+impl HotReloader {
+    /// Send a recompiled assembly to the running program
+    ///
+    /// It is swapped in the next time the program calls a function,
+    /// as long as [`Assembly::can_hot_swap`] allows it.
+    pub fn reload(&self, asm: Assembly) {
+        _ = self.0.send(asm);
+    }
+}
+
+/// The stack as it was when execution reached a line
+///
+/// See [`Uiua::with_stack_history`].
+#[derive(Debug, Clone)]
+pub struct StackSnapshot {
+    /// The span of the first code executed on the line
+    pub span: CodeSpan,
+    /// The stack before that code was executed
+    pub stack: Vec<Value>,
+}
+
 impl AsRef<Assembly> for Uiua {
     fn as_ref(&self) -> &Assembly {
         &self.asm
@@ -115,12 +213,60 @@ pub(crate) struct StackFrame {
     pub(crate) start_height: usize,
 }
 
+/// The state of a program that can be suspended at pending system calls
+#[derive(Clone, Default)]
+pub(crate) struct Suspension {
+    /// The number of nodes being executed that cannot be resumed partway through
+    barriers: usize,
+    /// The work left to do when the program is resumed, in order
+    ///
+    /// This is `Some` while the program is suspended
+    rest: Option<Vec<Resume>>,
+}
+
+/// Work left to do in a suspended program
+#[derive(Clone)]
+enum Resume {
+    /// Execute a node
+    Node(Node),
+    /// Return from a function call
+    Return(StackFrame),
+}
+
+impl Resume {
+    fn into_node(self) -> Node {
+        match self {
+            Resume::Node(node) => node,
+            Resume::Return(_) => unreachable!("function returns are handled separately"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Channel {
     pub send: Sender<Value>,
     pub recv: Receiver<Value>,
 }
 
+/// A channel created with [`Primitive::Channel`]
+///
+/// Only the runtime holds the sending side between sends, so closing the channel
+/// disconnects it once the values that were already sent have been received.
+#[derive(Debug, Clone)]
+struct SharedChannel {
+    send: Option<Sender<Value>>,
+    recv: Receiver<Value>,
+}
+
+/// A cell created with [`Primitive::Cell`]
+///
+/// The value is borrowed mutably while it is being updated,
+/// so the thread updating it cannot update it again.
+type SharedCell = Arc<ReentrantMutex<RefCell<Value>>>;
+
+/// The seed of the random number generator when execution is deterministic
+const DETERMINISTIC_SEED: u64 = 0;
+
 #[derive(Debug, Clone)]
 struct ThisThread {
     pub parent: Option<Channel>,
@@ -196,10 +342,24 @@ impl Default for Runtime {
             backend: Arc::new(SafeSys::default()),
             time_instrs: false,
             last_time: 0.0,
+            profiler: None,
+            alloc_profiler: None,
+            stack_history: VecDeque::new(),
+            history_capacity: 0,
+            color_output: false,
+            format_settings: FormatSettings::default(),
             cli_arguments: Vec::new(),
             cli_file_path: PathBuf::new(),
+            working_dir: None,
+            env_vars: BTreeMap::new(),
             execution_limit: None,
             execution_start: 0.0,
+            virtual_time: None,
+            memory_limit: None,
+            memory_usage: 0,
+            op_budget: None,
+            ops_executed: 0,
+            gensym_count: 0,
             #[cfg(debug_assertions)]
             recursion_limit: 20,
             #[cfg(not(debug_assertions))]
@@ -207,10 +367,20 @@ impl Default for Runtime {
                 .ok()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(100),
+            deadlines: Vec::new(),
+            handled_errors: Vec::new(),
             interrupted: None,
+            suspension: None,
+            exec_hook: None,
+            #[cfg(feature = "jit")]
+            jit: Default::default(),
+            hot_reload: None,
             thread: ThisThread::default(),
             output_comments: HashMap::new(),
             memo: Arc::new(ThreadLocal::new()),
+            memo_cache: None,
+            channels: Arc::default(),
+            cells: Arc::default(),
             unevaluated_constants: HashMap::new(),
             test_results: Vec::new(),
             reports: Vec::new(),
@@ -218,6 +388,19 @@ impl Default for Runtime {
     }
 }
 
+impl Runtime {
+    /// Whether something needs to see every instruction that is executed
+    #[cfg(feature = "jit")]
+    fn traces_instructions(&self) -> bool {
+        self.op_budget.is_some()
+            || self.exec_hook.is_some()
+            || self.history_capacity > 0
+            || self.profiler.is_some()
+            || self.alloc_profiler.is_some()
+            || self.time_instrs
+    }
+}
+
 impl Uiua {
     /// Create a new Uiua runtime with the standard IO backend
     #[cfg(feature = "native_sys")]
@@ -277,6 +460,111 @@ impl Uiua {
         self.rt.time_instrs = time_instrs;
         self
     }
+    /// Enable the sampling profiler
+    ///
+    /// A sample is taken at most once every `interval`.
+    /// The results can be retrieved with [`Uiua::take_profile`].
+    pub fn with_profiling(mut self, interval: Duration) -> Self {
+        self.rt.profiler = Some(Profiler::new(interval.as_secs_f64()).into());
+        self
+    }
+    /// Set a hook to call before executing each piece of code that has a span
+    ///
+    /// This can be used to implement debuggers.
+    /// The hook is not called in threads spawned by the program.
+    pub fn with_exec_hook(
+        mut self,
+        hook: impl Fn(&Uiua, &CodeSpan) -> UiuaResult + Send + Sync + 'static,
+    ) -> Self {
+        self.rt.exec_hook = Some(Arc::new(hook));
+        self
+    }
+    /// Get a handle for swapping new function bodies into the running program
+    ///
+    /// This allows editing a program without losing its state.
+    /// Code that is not in a function is never rerun.
+    /// Like the exec hook, this does not affect threads spawned by the program.
+    pub fn hot_reloader(&mut self) -> HotReloader {
+        let (send, recv) = crossbeam_channel::unbounded();
+        self.rt.hot_reload = Some(recv);
+        HotReloader(send)
+    }
+    /// Keep snapshots of the stack at the start of the most recently executed lines
+    ///
+    /// At most `capacity` snapshots are kept. They are kept after an error,
+    /// so they can be used to see how the stack got into a bad state.
+    /// The snapshots can be retrieved with [`Uiua::stack_history`].
+    pub fn with_stack_history(mut self, capacity: usize) -> Self {
+        self.rt.history_capacity = capacity;
+        self
+    }
+    /// Set whether values shown with [`&s`](SysOp::Show) are colored with ANSI escape codes
+    ///
+    /// This is off by default.
+    /// The native interpreter turns it on when stdout is a terminal and `NO_COLOR` is not set.
+    pub fn with_color_output(mut self, color: bool) -> Self {
+        self.rt.color_output = color;
+        self
+    }
+    /// Get the format settings set by `&nf` and `&elide`
+    ///
+    /// These only apply while the program runs.
+    /// Use [`FormatSettings::scoped`] to show values the way the program would.
+    pub fn format_settings(&self) -> FormatSettings {
+        self.rt.format_settings
+    }
+    /// Get the kept stack snapshots, from oldest to newest
+    ///
+    /// This is empty unless enabled with [`Uiua::with_stack_history`]
+    pub fn stack_history(&self) -> &VecDeque<StackSnapshot> {
+        &self.rt.stack_history
+    }
+    /// Get the stack snapshot from `steps` lines before the most recent one
+    pub fn stack_history_at(&self, steps: usize) -> Option<&StackSnapshot> {
+        let history = &self.rt.stack_history;
+        history.get(history.len().checked_sub(steps + 1)?)
+    }
+    /// Enable the allocation profiler
+    ///
+    /// This tracks the bytes of array data allocated by each span.
+    /// The results can be retrieved with [`Uiua::take_alloc_profile`].
+    pub fn with_alloc_profiling(mut self) -> Self {
+        self.rt.alloc_profiler = Some(Default::default());
+        self
+    }
+    /// Remember the outputs of pure [`memo`](Primitive::Memo)ized functions in a disk cache
+    ///
+    /// This lets expensive computations be reused across runs.
+    /// Functions that are not pure only use the in-memory cache.
+    pub fn with_memo_cache(mut self, cache: MemoCache) -> Self {
+        self.rt.memo_cache = Some(cache);
+        self
+    }
+    /// Set whether execution is deterministic
+    ///
+    /// In deterministic mode, each run seeds the random number generator with the same seed,
+    /// [`now`](Primitive::Now) reads a virtual clock that starts at `0` and only advances
+    /// with [`&sl`](SysOp::Sleep), [`timezone`](Primitive::TimeZone) is `0`,
+    /// and directory listings are sorted.
+    /// This makes outputs reproducible.
+    ///
+    /// This is off by default.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.rt.virtual_time = deterministic.then_some(0.0);
+        self
+    }
+    /// Check whether execution is deterministic
+    ///
+    /// See [`Uiua::with_deterministic`]
+    pub fn is_deterministic(&self) -> bool {
+        self.rt.virtual_time.is_some()
+    }
+    /// Get the current time in seconds since the Unix epoch
+    ///
+    /// This is the virtual time if execution is deterministic
+    pub(crate) fn now(&self) -> f64 {
+        (self.rt.virtual_time).unwrap_or_else(|| self.rt.backend.now())
+    }
     /// Limit the execution duration
     pub fn with_execution_limit(mut self, limit: Duration) -> Self {
         self.rt.execution_limit = Some(limit.as_secs_f64());
@@ -287,6 +575,24 @@ impl Uiua {
         self.rt.execution_limit = limit.map(|limit| limit.as_secs_f64());
         self
     }
+    /// Limit the number of bytes of array data the program may have allocated at once
+    ///
+    /// Exceeding the limit is an error.
+    /// Allocations are counted as they happen, so this includes intermediate arrays.
+    /// Each thread spawned by the program is limited separately.
+    /// Arrays that are too large to fit within the limit are not created at all.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.rt.memory_limit = Some(bytes);
+        self
+    }
+    /// Limit the number of operations that can be executed
+    ///
+    /// Each function, modifier, and value push counts as one operation.
+    /// Exceeding the budget is an error.
+    pub fn with_op_budget(mut self, ops: usize) -> Self {
+        self.rt.op_budget = Some(ops);
+        self
+    }
     /// Set the recursion limit
     ///
     /// Default is 100 for release builds and 20 for debug builds
@@ -317,6 +623,27 @@ impl Uiua {
     pub fn file_path(&self) -> &Path {
         self.rt.cli_file_path.as_path()
     }
+    /// Set the directory that relative paths are resolved against
+    ///
+    /// This does not change the working directory of the process,
+    /// so multiple interpreters can each have their own.
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.rt.working_dir = Some(dir.into());
+        self
+    }
+    /// Get the directory that relative paths are resolved against
+    ///
+    /// Returns `None` if it has not been set with [`Uiua::with_working_dir`] or `&cd`
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.rt.working_dir.as_deref()
+    }
+    /// Set an environment variable for this interpreter and the commands it runs
+    ///
+    /// This does not change the environment of the process.
+    pub fn with_var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        (self.rt.env_vars).insert(name.into(), Some(value.into()));
+        self
+    }
     /// Get the input code
     pub fn inputs(&self) -> &Inputs {
         &self.asm.inputs
@@ -359,45 +686,109 @@ impl Uiua {
             }
         }
     }
+    /// Run a Uiua assembly that may be shared with other interpreters
+    ///
+    /// The assembly is not copied unless the program modifies it,
+    /// so one compiled program can be run on many threads at once.
+    pub fn run_shared(&mut self, asm: &Assembly) -> UiuaResult {
+        self.run_asm(asm.clone())
+    }
     /// Run a Uiua assembly
     pub fn run_asm(&mut self, asm: Assembly) -> UiuaResult {
         fn run_asm(env: &mut Uiua, asm: Assembly) -> UiuaResult {
             env.asm = asm;
-            env.rt.execution_start = env.rt.backend.now();
-            let mut res = env
-                .catching_crash(|env| env.exec(env.asm.root.clone()))
-                .unwrap_or_else(Err);
-            let mut push_error = |te: UiuaError| match &mut res {
-                Ok(()) => res = Err(te),
-                Err(e) => e.multi.push(te),
-            };
-            if env.asm.test_assert_count > 0 {
-                let total_run = env.rt.test_results.len();
-                let not_run = env.asm.test_assert_count.saturating_sub(total_run);
-                let mut successes = 0;
-                for res in env.rt.test_results.drain(..) {
-                    match res {
-                        Ok(()) => successes += 1,
-                        Err(e) => push_error(e),
-                    }
-                }
-                (env.rt.reports).push(Report::tests(successes, total_run - successes, not_run));
+            env.start_execution();
+            let tracking_memory = env.rt.memory_limit.is_some() && start_memory_tracking();
+            if env.rt.virtual_time.is_some() {
+                env.rt.virtual_time = Some(0.0);
+                seed_random(DETERMINISTIC_SEED);
             }
-            if res.is_err() {
-                env.rt = Runtime {
-                    backend: env.rt.backend.clone(),
-                    execution_limit: env.rt.execution_limit,
-                    time_instrs: env.rt.time_instrs,
-                    output_comments: take(&mut env.rt.output_comments),
-                    reports: take(&mut env.rt.reports),
-                    stack: take(&mut env.rt.stack),
-                    ..Runtime::default()
-                };
+            if let Some(profiler) = &mut env.rt.profiler {
+                profiler.start(env.rt.execution_start);
             }
-            res
+            env.run_to_end(tracking_memory, |env| env.exec(env.asm.root.clone()))
         }
         run_asm(self, asm)
     }
+    /// Continue a program that was suspended at a pending system call
+    pub(crate) fn resume(&mut self) -> UiuaResult {
+        let rest = (self.rt.suspension.as_mut())
+            .and_then(|susp| susp.rest.take())
+            .unwrap_or_default();
+        self.run_to_end(false, |env| env.resume_rest(rest))
+    }
+    /// Whether the program is suspended at a pending system call
+    pub(crate) fn is_suspended(&self) -> bool {
+        (self.rt.suspension.as_ref()).is_some_and(|susp| susp.rest.is_some())
+    }
+    /// Run some of the program, then finish the run unless it was suspended
+    fn run_to_end(
+        &mut self,
+        tracking_memory: bool,
+        f: impl FnOnce(&mut Self) -> UiuaResult,
+    ) -> UiuaResult {
+        let settings = self.rt.format_settings;
+        let mut res = settings
+            .scoped(|| self.catching_crash(f))
+            .unwrap_or_else(Err);
+        if self.is_suspended() {
+            if matches!(&res, Err(e) if e.is_pending()) {
+                return res;
+            }
+            // The program was stopped by something else while suspending
+            self.rt.suspension.as_mut().unwrap().rest = None;
+        }
+        let mut push_error = |te: UiuaError| match &mut res {
+            Ok(()) => res = Err(te),
+            Err(e) => e.multi.push(te),
+        };
+        if self.asm.test_assert_count > 0 {
+            let total_run = self.rt.test_results.len();
+            let not_run = self.asm.test_assert_count.saturating_sub(total_run);
+            let mut successes = 0;
+            for res in self.rt.test_results.drain(..) {
+                match res {
+                    Ok(()) => successes += 1,
+                    Err(e) => push_error(e),
+                }
+            }
+            self.rt
+                .reports
+                .push(Report::tests(successes, total_run - successes, not_run));
+        }
+        if res.is_err() {
+            self.rt = Runtime {
+                backend: self.rt.backend.clone(),
+                execution_limit: self.rt.execution_limit,
+                virtual_time: self.rt.virtual_time,
+                memory_limit: self.rt.memory_limit,
+                op_budget: self.rt.op_budget,
+                time_instrs: self.rt.time_instrs,
+                profiler: take(&mut self.rt.profiler),
+                alloc_profiler: take(&mut self.rt.alloc_profiler),
+                stack_history: take(&mut self.rt.stack_history),
+                history_capacity: self.rt.history_capacity,
+                color_output: self.rt.color_output,
+                format_settings: self.rt.format_settings,
+                exec_hook: self.rt.exec_hook.clone(),
+                suspension: take(&mut self.rt.suspension),
+                hot_reload: self.rt.hot_reload.clone(),
+                output_comments: take(&mut self.rt.output_comments),
+                reports: take(&mut self.rt.reports),
+                stack: take(&mut self.rt.stack),
+                working_dir: take(&mut self.rt.working_dir),
+                env_vars: take(&mut self.rt.env_vars),
+                memo_cache: take(&mut self.rt.memo_cache),
+                channels: self.rt.channels.clone(),
+                cells: self.rt.cells.clone(),
+                ..Runtime::default()
+            };
+        }
+        if tracking_memory {
+            self.rt.memory_usage = end_memory_tracking(None);
+        }
+        res
+    }
     fn catching_crash<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> UiuaResult<T> {
         match catch_unwind(AssertUnwindSafe(|| f(self))) {
             Ok(res) => Ok(res),
@@ -425,7 +816,13 @@ pub trait Exec {
 
 impl Exec for Node {
     fn exec(self, uiua: &mut Uiua) -> UiuaResult {
-        uiua.exec_impl(self)
+        if uiua.rt.suspension.is_none() || uiua.can_suspend_in(&self) {
+            return uiua.exec_impl(self);
+        }
+        uiua.rt.suspension.as_mut().unwrap().barriers += 1;
+        let res = uiua.exec_impl(self);
+        uiua.rt.suspension.as_mut().unwrap().barriers -= 1;
+        res
     }
 }
 
@@ -466,12 +863,27 @@ impl Uiua {
         // }
         // println!("\n    {node:?}");
 
+        if !matches!(node, Node::Run(_)) {
+            self.rt.ops_executed += 1;
+            if let Some(hook) = &self.rt.exec_hook {
+                if let Some(Span::Code(span)) = node.span().map(|i| &self.asm.spans[i]) {
+                    hook.clone()(self, span)?;
+                }
+            }
+            if self.rt.history_capacity > 0 {
+                self.snapshot_stack(&node);
+            }
+        }
+        let leaf = (self.rt.profiler.is_some() || self.rt.alloc_profiler.is_some())
+            .then(|| Leaf::new(&node));
+        let alloc_scope = (self.rt.alloc_profiler.is_some() && !matches!(node, Node::Run(_)))
+            .then(start_alloc_scope);
         if self.rt.time_instrs {
             formatted_node = format!("{node:?}");
             self.rt.last_time = self.rt.backend.now();
         }
         let res = match node {
-            Node::Run(nodes) => nodes.into_iter().try_for_each(|node| self.exec(node)),
+            Node::Run(nodes) => self.exec_all(nodes),
             Node::Prim(prim, span) => self.with_prim_span(span, Some(prim), |env| prim.run(env)),
             Node::ImplPrim(prim, span) => self.with_span(span, |env| prim.run(env)),
             Node::Mod(prim, args, span) => {
@@ -736,10 +1148,42 @@ impl Uiua {
             );
             self.rt.last_time = self.rt.backend.now();
         }
+        if let Some(leaf) = leaf {
+            if let Some(outer) = alloc_scope {
+                self.record_allocs(&leaf, end_alloc_scope(outer));
+            }
+            self.sample(leaf);
+        }
         self.respect_execution_limit()?;
         res
     }
-    /// Timeout if an execution limit is set and has been exceeded
+    /// Take a stack snapshot if the node is on a different line than the last snapshot
+    fn snapshot_stack(&mut self, node: &Node) {
+        let Some(Span::Code(span)) = node.span().map(|i| &self.asm.spans[i]) else {
+            return;
+        };
+        let history = &mut self.rt.stack_history;
+        if let Some(last) = history.back() {
+            if last.span.src == span.src && last.span.start.line == span.start.line {
+                return;
+            }
+        }
+        if history.len() >= self.rt.history_capacity {
+            history.pop_front();
+        }
+        history.push_back(StackSnapshot {
+            span: span.clone(),
+            stack: self.rt.stack.clone(),
+        });
+    }
+    /// Start measuring the execution time and operation count from now
+    pub(crate) fn start_execution(&mut self) {
+        self.rt.execution_start = self.rt.backend.now();
+        self.rt.ops_executed = 0;
+    }
+    /// Error if an execution limit is set and has been exceeded
+    ///
+    /// This checks the time limit, the memory limit, and the operation budget
     pub fn respect_execution_limit(&self) -> UiuaResult {
         if let Some(limit) = self.rt.execution_limit {
             let elapsed = self.rt.backend.now() - self.rt.execution_start;
@@ -749,6 +1193,25 @@ impl Uiua {
                 );
             }
         }
+        if let Some(budget) = self.rt.op_budget {
+            if self.rt.ops_executed > budget {
+                return Err(
+                    UiuaErrorKind::OpLimit(self.span(), self.inputs().clone().into()).into(),
+                );
+            }
+        }
+        if let Some(limit) = self.rt.memory_limit {
+            if take_peak_bytes().is_some_and(|peak| peak > limit) {
+                return Err(
+                    UiuaErrorKind::MemoryLimit(self.span(), self.inputs().clone().into()).into(),
+                );
+            }
+        }
+        if let Some(&deadline) = self.rt.deadlines.last() {
+            if self.rt.backend.now() > deadline {
+                return Err(self.error("Function timed out"));
+            }
+        }
         if let Some(hook) = &self.rt.interrupted {
             if hook() {
                 return Err(UiuaErrorKind::Interrupted.into());
@@ -756,6 +1219,13 @@ impl Uiua {
         }
         Ok(())
     }
+    /// Get the number of bytes of array data allocated by the program
+    ///
+    /// This is only measured when a [memory limit](Uiua::with_memory_limit) is set.
+    /// Outside of a run, this is the data that was still allocated when the last run ended.
+    pub fn memory_usage(&self) -> usize {
+        live_bytes().unwrap_or(self.rt.memory_usage)
+    }
     pub(crate) fn with_span<T>(
         &mut self,
         span: usize,
@@ -832,18 +1302,32 @@ impl Uiua {
         res
     }
     fn call_with_span(&mut self, f: &Function, call_span: usize) -> UiuaResult {
+        if let Some(new) = (self.rt.hot_reload.as_ref()).and_then(|recv| recv.try_iter().last()) {
+            self.asm.hot_swap(new);
+            #[cfg(feature = "jit")]
+            self.rt.jit.clear();
+        }
+        #[cfg(feature = "jit")]
+        let native = if self.rt.traces_instructions() {
+            None
+        } else {
+            self.native_code(f)
+        };
         self.without_fill(|env| {
-            env.exec_with_frame_span(
-                env.asm[f].clone(),
-                StackFrame {
-                    sig: f.sig,
-                    id: Some(f.id.clone()),
-                    call_span,
-                    start_height: env.stack_height(),
-                    ..Default::default()
-                },
+            let frame = StackFrame {
+                sig: f.sig,
+                id: Some(f.id.clone()),
                 call_span,
-            )
+                start_height: env.stack_height(),
+                ..Default::default()
+            };
+            env.in_frame(frame, call_span, |env| {
+                #[cfg(feature = "jit")]
+                if native.is_some_and(|native| env.run_native(native, f.sig)) {
+                    return Ok(());
+                }
+                env.exec(env.asm[f].clone())
+            })
         })
     }
     fn exec_with_span(&mut self, sn: SigNode, call_span: usize) -> UiuaResult {
@@ -862,14 +1346,26 @@ impl Uiua {
         &mut self,
         node: Node,
         frame: StackFrame,
+        call_span: usize,
+    ) -> UiuaResult {
+        self.in_frame(frame, call_span, |env| env.exec(node))
+    }
+    fn in_frame(
+        &mut self,
+        frame: StackFrame,
         _call_span: usize,
+        f: impl FnOnce(&mut Self) -> UiuaResult,
     ) -> UiuaResult {
-        let start_height = self.rt.stack.len();
+        let start_height = frame.start_height;
         let sig = frame.sig;
         self.rt.call_stack.push(frame);
-        let res = self.exec(node);
+        let res = f(self);
         let frame = self.rt.call_stack.pop().unwrap();
         if let Err(mut err) = res {
+            if self.is_suspended() {
+                self.suspend_with([Resume::Return(frame)]);
+                return Err(err);
+            }
             // Trace errors
             let span = self.asm.spans[frame.call_span].clone();
             if frame.track_caller {
@@ -893,6 +1389,63 @@ impl Uiua {
         }
         Ok(())
     }
+    /// Execute nodes in order, saving the ones that are left if the program is suspended
+    fn exec_all(&mut self, nodes: impl IntoIterator<Item = Node>) -> UiuaResult {
+        let mut nodes = nodes.into_iter();
+        let res = nodes.by_ref().try_for_each(|node| self.exec(node));
+        if res.is_err() && self.is_suspended() {
+            self.suspend_with(nodes.map(Resume::Node));
+        }
+        res
+    }
+    /// Whether a pending system call inside a node can suspend the program
+    ///
+    /// Only sequences of nodes and function calls can be resumed partway through.
+    fn can_suspend_in(&self, node: &Node) -> bool {
+        match node {
+            Node::Run(_) | Node::Call(..) | Node::Prim(Primitive::Sys(_), _) => true,
+            Node::CallGlobal(index, _) => (self.asm.bindings.get(*index))
+                .is_some_and(|binding| matches!(binding.kind, BindingKind::Func(_))),
+            _ => false,
+        }
+    }
+    /// Start suspending the program at a pending system call, if it can be resumed
+    ///
+    /// Returns whether the program is suspending
+    pub(crate) fn start_suspending(&mut self, call: Node) -> bool {
+        match &mut self.rt.suspension {
+            Some(susp) if susp.barriers == 0 => {
+                susp.rest = Some(vec![Resume::Node(call)]);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Save work left to do when the program is resumed
+    fn suspend_with(&mut self, rest: impl IntoIterator<Item = Resume>) {
+        if let Some(susp_rest) = self.rt.suspension.as_mut().and_then(|s| s.rest.as_mut()) {
+            susp_rest.extend(rest);
+        }
+    }
+    /// Do the work left in a suspended program
+    fn resume_rest(&mut self, mut rest: Vec<Resume>) -> UiuaResult {
+        // Re-enter the outermost function call first
+        let Some(i) = (rest.iter()).rposition(|r| matches!(r, Resume::Return(_))) else {
+            return self.exec_all(rest.into_iter().map(Resume::into_node));
+        };
+        let after = rest.split_off(i + 1);
+        let Some(Resume::Return(frame)) = rest.pop() else {
+            unreachable!()
+        };
+        let call_span = frame.call_span;
+        let res =
+            self.without_fill(|env| env.in_frame(frame, call_span, |env| env.resume_rest(rest)));
+        if res.is_err() {
+            self.suspend_with(after);
+            return res;
+        }
+        self.exec_all(after.into_iter().map(Resume::into_node))
+    }
     pub(crate) fn span_index(&self) -> usize {
         self.rt.call_stack.last().map_or(0, |frame| {
             (frame.spans.last())
@@ -1124,6 +1677,17 @@ impl Uiua {
     pub fn stack(&self) -> &[Value] {
         &self.rt.stack
     }
+    /// Get the functions currently being called, from outermost to innermost
+    ///
+    /// Each frame's span is where its function was called
+    pub fn call_stack(&self) -> Vec<TraceFrame> {
+        (self.rt.call_stack.iter())
+            .map(|frame| TraceFrame {
+                id: frame.id.clone(),
+                span: self.asm.spans[frame.call_span].clone(),
+            })
+            .collect()
+    }
     /// Get a mutable reference to the stack data
     pub fn stack_mut(&mut self) -> &mut [Value] {
         &mut self.rt.stack
@@ -1152,6 +1716,36 @@ impl Uiua {
         }
         bindings
     }
+    /// Get a handle to a bound function with Rust argument and return types
+    ///
+    /// Fails if there is no function with the name or if its signature does not match the types.
+    /// ```
+    /// use uiua::*;
+    ///
+    /// let mut uiua = Uiua::with_safe_sys();
+    /// uiua.run_str("Avg ← ÷⊃⧻/+").unwrap();
+    /// let avg = uiua.bind_function::<(Value,), (f64,)>("Avg").unwrap();
+    /// let (res,) = avg.call(&mut uiua, ([1, 2, 6].into(),)).unwrap();
+    /// assert_eq!(res, 3.0);
+    /// ```
+    pub fn bind_function<A: IntoValues, R: FromValues>(
+        &self,
+        name: &str,
+    ) -> UiuaResult<TypedFunction<A, R>> {
+        let function = (self.bound_functions().remove(name))
+            .ok_or_else(|| self.error(format!("No function named `{name}` is bound")))?;
+        let expected = Signature::new(A::COUNT, R::COUNT);
+        if function.sig != expected {
+            return Err(self.error(format!(
+                "`{name}` has signature {}, but the handle expects {expected}",
+                function.sig
+            )));
+        }
+        Ok(TypedFunction {
+            function,
+            _types: PhantomData,
+        })
+    }
     /// Clone `n` values from the top of the stack
     ///
     /// Values are cloned in the order they were pushed
@@ -1416,15 +2010,39 @@ impl Uiua {
                 array_depth: 0,
                 time_instrs: self.rt.time_instrs,
                 last_time: self.rt.last_time,
+                profiler: None,
+                alloc_profiler: None,
+                stack_history: VecDeque::new(),
+                history_capacity: 0,
+                color_output: self.rt.color_output,
+                format_settings: self.rt.format_settings,
                 cli_arguments: self.rt.cli_arguments.clone(),
                 cli_file_path: self.rt.cli_file_path.clone(),
+                working_dir: self.rt.working_dir.clone(),
+                env_vars: self.rt.env_vars.clone(),
                 backend: self.rt.backend.clone(),
                 execution_limit: self.rt.execution_limit,
                 execution_start: self.rt.execution_start,
+                virtual_time: self.rt.virtual_time,
+                memory_limit: self.rt.memory_limit,
+                memory_usage: 0,
+                op_budget: self.rt.op_budget,
+                ops_executed: 0,
+                gensym_count: self.rt.gensym_count,
                 recursion_limit: self.rt.recursion_limit,
+                deadlines: self.rt.deadlines.clone(),
+                handled_errors: Vec::new(),
                 interrupted: self.rt.interrupted.clone(),
+                suspension: None,
+                exec_hook: None,
+                #[cfg(feature = "jit")]
+                jit: Default::default(),
+                hot_reload: None,
                 output_comments: HashMap::new(),
                 memo: self.rt.memo.clone(),
+                memo_cache: self.rt.memo_cache.clone(),
+                channels: self.rt.channels.clone(),
+                cells: self.rt.cells.clone(),
                 unevaluated_constants: HashMap::new(),
                 test_results: Vec::new(),
                 reports: Vec::new(),
@@ -1433,12 +2051,31 @@ impl Uiua {
         };
         #[cfg(not(target_arch = "wasm32"))]
         let recv = {
+            use rand::Rng;
+            // Deterministic threads get their seeds from the spawning thread
+            let seed = (self.rt.virtual_time.is_some())
+                .then(|| RNG.with_borrow_mut(|rng| rng.gen::<u64>()));
             let (send, recv) = crossbeam_channel::unbounded();
+            // Values the thread returns count against the spawning thread's memory
+            let heir = crate::cowslice::memory_tracker();
+            let run = move || {
+                if let Some(seed) = seed {
+                    seed_random(seed);
+                }
+                let tracking_memory = env.rt.memory_limit.is_some() && start_memory_tracking();
+                let settings = env.rt.format_settings;
+                let res = settings.scoped(|| env.exec(f).map(|_| env.take_stack()));
+                if tracking_memory {
+                    drop(env);
+                    end_memory_tracking(heir);
+                }
+                _ = send.send(res);
+            };
             if _pool {
-                rayon::spawn(move || _ = send.send(env.exec(f).map(|_| env.take_stack())));
+                rayon::spawn(run);
             } else {
                 std::thread::Builder::new()
-                    .spawn(move || _ = send.send(env.exec(f).map(|_| env.take_stack())))
+                    .spawn(run)
                     .map_err(|e| self.error(format!("Error spawning thread: {e}")))?;
             }
             recv
@@ -1533,6 +2170,13 @@ impl Uiua {
         Ok(())
     }
     pub(crate) fn send(&self, id: Value, value: Value) -> UiuaResult {
+        if let Some(send) = self.channel_sender(&id)? {
+            return if cfg!(target_arch = "wasm32") {
+                (send.try_send(value)).map_err(|_| self.error("Channel is full"))
+            } else {
+                (send.send(value)).map_err(|_| self.error("Channel closed"))
+            };
+        }
         if cfg!(target_arch = "wasm32") {
             return Err(self.error("send is not supported in this environment"));
         }
@@ -1546,6 +2190,15 @@ impl Uiua {
         Ok(())
     }
     pub(crate) fn recv(&mut self, id: Value) -> UiuaResult {
+        if let Some(recv) = self.channel_receiver(&id)? {
+            let value = if cfg!(target_arch = "wasm32") {
+                (recv.try_recv()).map_err(|_| self.error("No value available"))?
+            } else {
+                (recv.recv()).map_err(|_| self.error("Channel closed"))?
+            };
+            self.push(value);
+            return Ok(());
+        }
         if cfg!(target_arch = "wasm32") {
             return Err(self.error("recv is not supported in this environment"));
         }
@@ -1568,6 +2221,15 @@ impl Uiua {
         Ok(())
     }
     pub(crate) fn try_recv(&mut self, id: Value) -> UiuaResult {
+        if let Some(recv) = self.channel_receiver(&id)? {
+            let value = match recv.try_recv() {
+                Ok(value) => value,
+                Err(TryRecvError::Empty) => return Err(self.error("No value available")),
+                Err(TryRecvError::Disconnected) => return Err(self.error("Channel closed")),
+            };
+            self.push(value);
+            return Ok(());
+        }
         if cfg!(target_arch = "wasm32") {
             return Err(self.error("try_recv is not supported in this environment"));
         }
@@ -1586,6 +2248,163 @@ impl Uiua {
         self.push(value);
         Ok(())
     }
+    pub(crate) fn create_channel(&mut self, capacity: Value) -> UiuaResult {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let capacity = capacity.as_nat_or_inf(
+            self,
+            "Channel capacity must be a natural number or infinity",
+        )?;
+        let (send, recv) = match capacity {
+            Some(n) => crossbeam_channel::bounded(n),
+            None => crossbeam_channel::unbounded(),
+        };
+        let id = NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed);
+        let channel = SharedChannel {
+            send: Some(send),
+            recv,
+        };
+        (self.rt.channels.lock()).insert(id, channel);
+        self.push(Handle(id).value(HandleKind::Channel));
+        Ok(())
+    }
+    pub(crate) fn recv_any(&mut self, channels: Value) -> UiuaResult {
+        let requirement = "Channels must be a list of channel handles";
+        let handles: Vec<Value> = match channels {
+            Value::Box(arr) if arr.rank() <= 1 => (arr.data.into_iter())
+                .map(|Boxed(v)| match v {
+                    // Handles that were boxed again, such as in `{A B}`
+                    v @ Value::Box(_) => v,
+                    v => Boxed(v).into(),
+                })
+                .collect(),
+            val => vec![val],
+        };
+        let mut receivers = Vec::with_capacity(handles.len());
+        for handle in &handles {
+            receivers.push(
+                self.channel_receiver(handle)?
+                    .ok_or_else(|| self.error(requirement))?,
+            );
+        }
+        if receivers.is_empty() {
+            return Err(self.error("Cannot receive from an empty list of channels"));
+        }
+        let mut select = Select::new();
+        for recv in &receivers {
+            select.recv(recv);
+        }
+        let op = if cfg!(target_arch = "wasm32") {
+            (select.try_select()).map_err(|_| self.error("No value available"))?
+        } else {
+            select.select()
+        };
+        let index = op.index();
+        let value = (op.recv(&receivers[index])).map_err(|_| self.error("Channel closed"))?;
+        self.push(index);
+        self.push(value);
+        Ok(())
+    }
+    pub(crate) fn with_timeout(&mut self, f: SigNode) -> UiuaResult {
+        let secs = self
+            .pop("timeout duration")?
+            .as_num(self, "Timeout duration must be a number")?;
+        if secs.is_nan() || secs < 0.0 {
+            return Err(self.error("Timeout duration must be a non-negative number"));
+        }
+        let mut deadline = self.rt.backend.now() + secs;
+        if let Some(&outer) = self.rt.deadlines.last() {
+            deadline = deadline.min(outer);
+        }
+        self.rt.deadlines.push(deadline);
+        let res = self.exec(f);
+        self.rt.deadlines.pop();
+        res
+    }
+    pub(crate) fn create_cell(&mut self, init: Value) -> UiuaResult {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed);
+        (self.rt.cells.lock()).insert(id, Arc::new(ReentrantMutex::new(RefCell::new(init))));
+        self.push(Handle(id).value(HandleKind::Cell));
+        Ok(())
+    }
+    pub(crate) fn update_cell(&mut self, f: SigNode) -> UiuaResult {
+        if f.sig.args == 0 || f.sig.outputs == 0 {
+            return Err(self.error(format!(
+                "{}'s function must take and return at least 1 value, \
+                but its signature is {}",
+                Primitive::Update.format(),
+                f.sig
+            )));
+        }
+        let handle = self.pop("cell handle")?;
+        let cell = (self.handle_id(&handle, HandleKind::Cell)?)
+            .and_then(|id| self.rt.cells.lock().get(&id).cloned())
+            .ok_or_else(|| self.error("Expected a cell handle"))?;
+        let guard = cell.lock();
+        let Ok(mut value) = guard.try_borrow_mut() else {
+            return Err(self.error(format!(
+                "Cannot {} a cell while it is already being updated",
+                Primitive::Update.format()
+            )));
+        };
+        self.push(value.clone());
+        self.exec(f)?;
+        *value = self.pop("updated value")?;
+        Ok(())
+    }
+    /// Get the sending side of the channel a value refers to, if it is a channel handle
+    fn channel_sender(&self, handle: &Value) -> UiuaResult<Option<Sender<Value>>> {
+        let Some(id) = self.handle_id(handle, HandleKind::Channel)? else {
+            return Ok(None);
+        };
+        let send = (self.rt.channels.lock().get(&id)).map(|channel| channel.send.clone());
+        match send {
+            Some(Some(send)) => Ok(Some(send)),
+            Some(None) => Err(self.error("Channel closed")),
+            None => Err(self.error("Invalid channel handle")),
+        }
+    }
+    /// Get the receiving side of the channel a value refers to, if it is a channel handle
+    ///
+    /// Only the receiver is cloned, so waiting on it does not keep the channel open.
+    fn channel_receiver(&self, handle: &Value) -> UiuaResult<Option<Receiver<Value>>> {
+        let Some(id) = self.handle_id(handle, HandleKind::Channel)? else {
+            return Ok(None);
+        };
+        let recv = (self.rt.channels.lock().get(&id)).map(|channel| channel.recv.clone());
+        recv.map(Some)
+            .ok_or_else(|| self.error("Invalid channel handle"))
+    }
+    /// Get the id of a handle if it is of the given kind
+    fn handle_id(&self, handle: &Value, kind: HandleKind) -> UiuaResult<Option<u64>> {
+        if handle.handle_kind() != Some(&kind) {
+            return Ok(None);
+        }
+        handle.as_handle(self, "").map(|Handle(id)| Some(id))
+    }
+    /// Close a handle
+    ///
+    /// Channels stop accepting values, but the values already sent can still be received.
+    /// Cells are dropped. Other handles are closed by the backend.
+    pub(crate) fn close_handle(&mut self, handle: Value) -> UiuaResult {
+        match handle.handle_kind() {
+            Some(HandleKind::Channel) => {
+                let Handle(id) = handle.as_handle(self, "")?;
+                if let Some(channel) = self.rt.channels.lock().get_mut(&id) {
+                    channel.send = None;
+                }
+            }
+            Some(HandleKind::Cell) => {
+                let Handle(id) = handle.as_handle(self, "")?;
+                self.rt.cells.lock().remove(&id);
+            }
+            _ => {
+                let handle = handle.as_handle(self, "")?;
+                self.rt.backend.close(handle).map_err(|e| self.error(e))?;
+            }
+        }
+        Ok(())
+    }
     fn channel(&self, id: usize) -> UiuaResult<&Channel> {
         Ok(if id == 0 {
             self.rt
@@ -1661,3 +2480,284 @@ where
         self().arg_name()
     }
 }
+
+/// A handle to a bound Uiua function with Rust argument and return types
+///
+/// Created with [`Uiua::bind_function`].
+/// `A` and `R` are tuples. The first element of each is the top of the stack.
+pub struct TypedFunction<A, R> {
+    function: Function,
+    _types: PhantomData<fn(A) -> R>,
+}
+
+impl<A, R> Clone for TypedFunction<A, R> {
+    fn clone(&self) -> Self {
+        TypedFunction {
+            function: self.function.clone(),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<A, R> fmt::Debug for TypedFunction<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.function.fmt(f)
+    }
+}
+
+impl<A: IntoValues, R: FromValues> TypedFunction<A, R> {
+    /// Get the underlying function
+    pub fn function(&self) -> &Function {
+        &self.function
+    }
+    /// Call the function with some arguments
+    ///
+    /// If the call fails, the arguments are removed from the stack.
+    pub fn call(&self, env: &mut Uiua, args: A) -> UiuaResult<R> {
+        let bottom = env.stack_height();
+        args.push_values(env);
+        if let Err(e) = env.call(&self.function) {
+            env.truncate_stack(bottom);
+            return Err(e);
+        }
+        R::pop_values(env)
+    }
+}
+
+/// A tuple of values that can be pushed as the arguments of a [`TypedFunction`]
+pub trait IntoValues {
+    /// The number of values
+    const COUNT: usize;
+    /// Push the values so that the first is on top of the stack
+    fn push_values(self, env: &mut Uiua);
+}
+
+/// A tuple of values that can be popped as the outputs of a [`TypedFunction`]
+pub trait FromValues: Sized {
+    /// The number of values
+    const COUNT: usize;
+    /// Pop the values, starting with the top of the stack
+    fn pop_values(env: &mut Uiua) -> UiuaResult<Self>;
+}
+
+macro_rules! tuple_values {
+    ($n:literal $(,$T:ident)*) => {
+        impl<$($T: Into<Value>),*> IntoValues for ($($T,)*) {
+            const COUNT: usize = $n;
+            #[allow(non_snake_case, unused_variables)]
+            fn push_values(self, env: &mut Uiua) {
+                let ($($T,)*) = self;
+                let values: Vec<Value> = vec![$($T.into()),*];
+                env.push_all(values.into_iter().rev());
+            }
+        }
+        impl<$($T),*> FromValues for ($($T,)*)
+        where
+            $($T: TryFrom<Value>, $T::Error: fmt::Display,)*
+        {
+            const COUNT: usize = $n;
+            #[allow(unused_mut, unused_variables)]
+            fn pop_values(env: &mut Uiua) -> UiuaResult<Self> {
+                let mut values = env.take_n($n)?.into_iter().rev();
+                Ok(($($T::try_from(values.next().unwrap()).map_err(|e| {
+                    env.error(format!("Invalid function output: {e}"))
+                })?,)*))
+            }
+        }
+    };
+}
+
+tuple_values!(0);
+tuple_values!(1, A);
+tuple_values!(2, A, B);
+tuple_values!(3, A, B, C);
+tuple_values!(4, A, B, C, D);
+tuple_values!(5, A, B, C, D, E);
+tuple_values!(6, A, B, C, D, E, F);
+
+#[cfg(test)]
+#[test]
+fn execution_limits() {
+    use crate::{Uiua, UiuaErrorKind, Value};
+    let Err(err) = Uiua::with_safe_sys()
+        .with_op_budget(100)
+        .run_str("⍥(+1)1e6 0")
+    else {
+        panic!("expected an error");
+    };
+    assert!(matches!(err.kind, UiuaErrorKind::OpLimit(..)), "{err}");
+    let Err(err) = Uiua::with_safe_sys()
+        .with_memory_limit(1_000_000)
+        .run_str("⇡1e7")
+    else {
+        panic!("expected an error");
+    };
+    assert!(matches!(err.kind, UiuaErrorKind::MemoryLimit(..)), "{err}");
+    let mut env = Uiua::with_safe_sys().with_memory_limit(1_000_000);
+    env.run_str("⇡1e4").unwrap();
+    assert_eq!(env.memory_usage(), 80_000);
+    // Freed data does not count against the limit
+    let mut env = Uiua::with_safe_sys().with_memory_limit(1_000_000);
+    env.run_str("⍥(◌⇡1e5)100 0").unwrap();
+    assert_eq!(env.take_stack(), [Value::from(0)]);
+}
+
+#[cfg(test)]
+#[test]
+fn exec_hook() {
+    use crate::{PreEvalMode, Uiua, UiuaErrorKind};
+    use std::sync::{Arc, Mutex};
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let hook_seen = seen.clone();
+    let mut env = Uiua::with_safe_sys().with_exec_hook(move |env, span| {
+        let depth = env.call_stack().len();
+        let text = span.as_str(env.inputs(), str::to_string);
+        let stop = text == "⇌";
+        hook_seen.lock().unwrap().push((text, depth));
+        if stop {
+            Err(UiuaErrorKind::Interrupted.into())
+        } else {
+            Ok(())
+        }
+    });
+    let res = env.compile_run(|comp| {
+        comp.pre_eval_mode(PreEvalMode::Lazy)
+            .load_str("F ← ×2\nF ⚂\n⇌[1 2]\n¯1")
+    });
+    assert!(matches!(res, Err(e) if matches!(e.kind, UiuaErrorKind::Interrupted)));
+    let seen = seen.lock().unwrap();
+    let texts: Vec<_> = seen.iter().map(|(text, _)| text.as_str()).collect();
+    assert_eq!(texts, ["⚂", "F", "×", "⇌"]);
+    assert!(seen[2].1 > seen[1].1);
+}
+
+#[cfg(all(test, feature = "native_sys"))]
+#[test]
+fn deterministic() {
+    use crate::{Uiua, Value};
+    let code = "[⚂ ⚂ now &sl 0.25 now timezone wait spawn⚂]";
+    let run = || {
+        let mut env = Uiua::with_native_sys().with_deterministic(true);
+        env.run_str(code).unwrap();
+        env.pop(1).unwrap()
+    };
+    let first = run();
+    assert_eq!(first, run());
+    let Value::Num(arr) = &first else {
+        panic!("expected numbers, got {first:?}");
+    };
+    assert_eq!(arr.data[2..5], [0.25, 0.0, 0.0]);
+    assert_ne!(arr.data[0], arr.data[1]);
+    let mut env = Uiua::with_safe_sys();
+    assert!(!env.is_deterministic());
+    env.run_str("now").unwrap();
+    assert!(env.pop(1).unwrap().as_num(&env, "").unwrap() > 0.0);
+}
+
+#[cfg(test)]
+#[test]
+fn stack_history() {
+    use crate::{PreEvalMode, Uiua, Value};
+    let mut env = Uiua::with_safe_sys().with_stack_history(2);
+    let res = env.compile_run(|comp| {
+        comp.pre_eval_mode(PreEvalMode::Lazy)
+            .load_str("1\n+1\n¯\n⊡5")
+    });
+    assert!(res.is_err());
+    assert_eq!(env.stack_history().len(), 2);
+    let latest = env.stack_history_at(0).unwrap();
+    assert_eq!(latest.span.start.line, 4);
+    let previous = env.stack_history_at(1).unwrap();
+    assert_eq!(previous.span.start.line, 3);
+    assert_eq!(previous.stack, [Value::from(2)]);
+    assert!(env.stack_history_at(2).is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn typed_function() {
+    use crate::{Uiua, Value};
+    let mut env = Uiua::with_safe_sys();
+    env.run_str("Sub ← -\nSplit ← ⊃⌊(◿1)\nName ← $\"_ _\"")
+        .unwrap();
+    let sub = env.bind_function::<(f64, f64), (f64,)>("Sub").unwrap();
+    assert_eq!(sub.call(&mut env, (1.0, 5.0)).unwrap(), (4.0,));
+    let split = env.bind_function::<(f64,), (i64, f64)>("Split").unwrap();
+    assert_eq!(split.call(&mut env, (2.5,)).unwrap(), (2, 0.5));
+    let name = (env.bind_function::<(&str, &str), (String,)>("Name")).unwrap();
+    let (name,) = name.call(&mut env, ("Jo", "Smith")).unwrap();
+    assert_eq!(name, "Jo Smith");
+    let bad = env.bind_function::<(f64, f64), (String,)>("Sub").unwrap();
+    assert!(bad.call(&mut env, (1.0, 2.0)).is_err());
+    let sub = env
+        .bind_function::<(Value, Value), (Value,)>("Sub")
+        .unwrap();
+    assert!(sub
+        .call(&mut env, ([1, 2].into(), [1, 2, 3].into()))
+        .is_err());
+    assert!(env.stack().is_empty());
+    assert!((env.bind_function::<(f64,), (f64,)>("Sub")).is_err());
+    assert!((env.bind_function::<(), ()>("Missing")).is_err());
+}
+
+#[cfg(all(test, feature = "native_sys"))]
+#[test]
+fn working_dir_and_vars() {
+    use crate::{Uiua, Value};
+    let dir = std::env::temp_dir().join(format!("uiua_working_dir_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/data.txt"), "data").unwrap();
+    let process_dir = std::env::current_dir().unwrap();
+    let mut env = Uiua::with_native_sys()
+        .with_var("UIUA_TEST_DIR", dir.to_string_lossy())
+        .with_var("HOME", dir.join("sub").to_string_lossy());
+    let code = "\
+        &cd \"${UIUA_TEST_DIR}\"\n\
+        &cd \"sub/../sub/.\"\n\
+        &cwd\n\
+        &fras \"data.txt\"\n\
+        &fe \"~/data.txt\"\n\
+        &fe \"$UIUA_TEST_DIR/sub/data.txt\"\n\
+        &fe \"$$UIUA_TEST_DIR\"\n\
+        &setvar \"UIUA_TEST_GREETING\" \"hello\"\n\
+        &var \"UIUA_TEST_GREETING\"\n\
+        &unsetvar \"UIUA_TEST_DIR\"\n\
+        ⍣(&var \"UIUA_TEST_DIR\"|\"unset\")";
+    env.run_str(code).unwrap_or_else(|e| panic!("{e}"));
+    let stack = env.take_stack();
+    let expected: [Value; 7] = [
+        dir.join("sub").to_string_lossy().into_owned().into(),
+        "data".into(),
+        true.into(),
+        true.into(),
+        false.into(),
+        "hello".into(),
+        "unset".into(),
+    ];
+    assert_eq!(stack, expected);
+    assert_eq!(env.working_dir(), Some(dir.join("sub").as_path()));
+    // The process is unaffected
+    assert_eq!(std::env::current_dir().unwrap(), process_dir);
+    assert!(std::env::var("UIUA_TEST_GREETING").is_err());
+
+    #[cfg(unix)]
+    {
+        let code =
+            "&runc {\"sh\" \"-c\" \"echo $UIUA_TEST_GREETING; echo ${UIUA_TEST_DIR-none}; ls\"}";
+        env.run_str(code).unwrap_or_else(|e| panic!("{e}"));
+        let stack = env.take_stack();
+        assert_eq!(stack[1], Value::from("hello\nnone\ndata.txt\n"));
+    }
+
+    for code in [
+        "&cd \"data.txt\"",
+        "&cd \"does_not_exist\"",
+        "&fras \"$UIUA_TEST_DIR/sub/data.txt\"",
+        "&fras \"${UIUA_TEST_GREETING\"",
+        "&setvar \"\" \"value\"",
+        "&setvar \"A=B\" \"value\"",
+    ] {
+        assert!(env.run_str(code).is_err(), "{code}");
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
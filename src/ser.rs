@@ -0,0 +1,404 @@
+//! Converting Rust data into Uiua values with [`serde`]
+//!
+//! Values are built the same way [`json`](crate::Primitive::Json) decodes JSON:
+//! - Structs and maps become [map](crate::Primitive::Map) arrays
+//! - Sequences of scalars of the same type become lists, and other sequences become lists of boxes
+//! - Strings become character lists
+//! - `None` and `()` become `NaN`
+//! - Unit enum variants become their name, and other enum variants become a single-entry map from their name to their contents
+//!
+//! ```
+//! # use serde::Serialize;
+//! #[derive(Serialize)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! let value = uiua::ser::to_value(&Point { x: 1.0, y: 2.0 }).unwrap();
+//! let mut uiua = uiua::Uiua::with_safe_sys();
+//! uiua.push(value);
+//! uiua.run_str(r#"get "y""#).unwrap();
+//! assert_eq!(uiua.pop_num().unwrap(), 2.0);
+//! ```
+
+use ecow::EcoVec;
+use serde::{ser, Serialize};
+
+pub use crate::de::Error;
+use crate::{
+    algorithm::{ErrorContext, FillError},
+    Array, Boxed, Value,
+};
+
+/// Convert a Rust value into a Uiua value
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    value.serialize(Serializer)
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl FillError for Error {
+    fn is_fill(&self) -> bool {
+        false
+    }
+}
+
+impl ErrorContext for Serializer {
+    type Error = Error;
+    fn error(&self, msg: impl ToString) -> Self::Error {
+        Error(msg.to_string())
+    }
+}
+
+/// Make a list from row values
+///
+/// Scalars of the same type are joined into a flat list, and anything else is boxed.
+fn rows_value(rows: Vec<Value>) -> Value {
+    if rows
+        .iter()
+        .all(|row| row.shape().is_empty() && !row.is_map())
+        && (rows.windows(2)).all(|win| win[0].type_id() == win[1].type_id())
+    {
+        Value::from_row_values_infallible(rows)
+    } else {
+        Array::from(
+            rows.into_iter()
+                .map(Value::boxed_if_not)
+                .collect::<EcoVec<_>>(),
+        )
+        .into()
+    }
+}
+
+/// Make a map array from keys and values
+fn map_value(keys: Vec<Value>, values: Vec<Value>) -> Result<Value, Error> {
+    let keys = rows_value(keys);
+    let mut values = if (values.windows(2))
+        .all(|win| win[0].shape() == win[1].shape() && win[0].type_id() == win[1].type_id())
+        && !values.iter().any(Value::is_map)
+    {
+        Value::from_row_values_infallible(values)
+    } else {
+        Array::from(values.into_iter().map(Boxed).collect::<EcoVec<_>>()).into()
+    };
+    match &mut values {
+        Value::Num(arr) => arr.map(keys, &Serializer)?,
+        Value::Byte(arr) => arr.map(keys, &Serializer)?,
+        Value::Complex(arr) => arr.map(keys, &Serializer)?,
+        Value::Char(arr) => arr.map(keys, &Serializer)?,
+        Value::Box(arr) => arr.map(keys, &Serializer)?,
+    }
+    Ok(values)
+}
+
+/// A [`serde::Serializer`] that produces Uiua values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+/// Serializes sequences and tuples into lists
+#[doc(hidden)]
+pub struct SerializeRows {
+    variant: Option<&'static str>,
+    rows: Vec<Value>,
+}
+
+/// Serializes maps and structs into map arrays
+#[doc(hidden)]
+pub struct SerializeMap {
+    variant: Option<&'static str>,
+    keys: Vec<Value>,
+    values: Vec<Value>,
+}
+
+/// Wrap a value in a single-entry map from an enum variant name
+fn variant_value(variant: Option<&'static str>, value: Value) -> Result<Value, Error> {
+    match variant {
+        Some(variant) => map_value(vec![variant.into()], vec![value]),
+        None => Ok(value),
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SerializeRows;
+    type SerializeTuple = SerializeRows;
+    type SerializeTupleStruct = SerializeRows;
+    type SerializeTupleVariant = SerializeRows;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok((v as f64).into())
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(v.to_vec().into())
+    }
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(f64::NAN.into())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(f64::NAN.into())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(variant.into())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        variant_value(Some(variant), value.serialize(self)?)
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeRows, Error> {
+        Ok(SerializeRows {
+            variant: None,
+            rows: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SerializeRows, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeRows, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeRows, Error> {
+        Ok(SerializeRows {
+            variant: Some(variant),
+            rows: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            variant: None,
+            keys: Vec::with_capacity(len.unwrap_or(0)),
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            variant: Some(variant),
+            keys: Vec::with_capacity(len),
+            values: Vec::with_capacity(len),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeRows {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.rows.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, Error> {
+        variant_value(self.variant, rows_value(self.rows))
+    }
+}
+
+impl ser::SerializeTuple for SerializeRows {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeRows {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeRows {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.keys.push(to_value(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, Error> {
+        variant_value(self.variant, map_value(self.keys, self.values)?)
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.keys.push(key.into());
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn serde_bridge() {
+    use crate::{de, Uiua};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Dot,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Thing {
+        name: String,
+        visible: bool,
+        tags: Vec<String>,
+        pos: (i32, i32),
+        parent: Option<u8>,
+        shapes: Vec<Shape>,
+        counts: BTreeMap<char, usize>,
+    }
+    let thing = Thing {
+        name: "thing".into(),
+        visible: true,
+        tags: vec!["a".into(), "bc".into()],
+        pos: (-1, 2),
+        parent: None,
+        shapes: vec![Shape::Dot, Shape::Circle(0.5), Shape::Rect { w: 2, h: 3 }],
+        counts: [('x', 1), ('y', 2)].into(),
+    };
+    let value = to_value(&thing).unwrap();
+    assert!(value.is_map());
+    assert_eq!(de::from_value::<Thing>(&value).unwrap(), thing);
+
+    let mut env = Uiua::with_safe_sys();
+    env.push(value);
+    env.run_str("⊃(°□get \"name\"|°□get \"pos\")").unwrap();
+    assert_eq!(env.pop_string().unwrap(), "thing");
+    assert_eq!(env.pop(1).unwrap().show(), "[¯1 2]");
+    env.run_str("map {\"name\" \"visible\" \"tags\"} {\"x\" 0 {}}")
+        .unwrap();
+    let err = de::from_value::<Thing>(&env.pop(1).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("missing field"));
+}
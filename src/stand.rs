@@ -7,37 +7,53 @@ use crate::{Assembly, Compiler, NativeSys, UiuaResult};
 const STAND_DATA_SIGNATURE: &[u8] = b"Uiua standalone";
 
 pub fn build_exe(root: &Path) -> UiuaResult<Vec<u8>> {
-    let asm = Compiler::with_backend(NativeSys).load_file(root)?.finish();
-    // Serialize the files
-    let asm_bytes = asm.to_uasm().into_bytes();
-    // Append the files to the current exe
-    let mut bytes = env::current_exe()
+    let mut asm = Compiler::with_backend(NativeSys).load_file(root)?.finish();
+    asm.remove_dead_code();
+    let runtime = env::current_exe()
         .and_then(fs::read)
         .unwrap_or_else(|e| panic!("Unable to read current exe: {e}"));
-    bytes.extend_from_slice(&asm_bytes);
-    // Append the length of the serialized files and a signature
-    bytes.extend((asm_bytes.len() as u64).to_le_bytes());
-    bytes.extend(STAND_DATA_SIGNATURE);
-    Ok(bytes)
+    Ok(embed_asm(runtime, &asm))
+}
+
+/// Embed an assembly into the bytes of a Uiua interpreter executable
+///
+/// The interpreter must have been built with the `stand` feature.
+/// When the resulting executable is run, it runs the assembly.
+pub fn embed_asm(mut runtime: Vec<u8>, asm: &Assembly) -> Vec<u8> {
+    // Remove an assembly that is already embedded
+    if let Some((start, _)) = embedded_asm_range(&runtime) {
+        runtime.truncate(start);
+    }
+    // Append the serialized assembly
+    let asm_bytes = asm.to_uasm_bytes();
+    runtime.extend_from_slice(&asm_bytes);
+    // Append the length of the serialized assembly and a signature
+    runtime.extend((asm_bytes.len() as u64).to_le_bytes());
+    runtime.extend(STAND_DATA_SIGNATURE);
+    runtime
+}
+
+/// Get the range of the bytes of an embedded assembly
+fn embedded_asm_range(bytes: &[u8]) -> Option<(usize, usize)> {
+    let bytes = bytes.strip_suffix(STAND_DATA_SIGNATURE)?;
+    let len_start = bytes.len().checked_sub(8)?;
+    let asm_len = u64::from_le_bytes(bytes[len_start..].try_into().unwrap());
+    let start = len_start.checked_sub(asm_len as usize)?;
+    Some((start, len_start))
 }
 
 fn load_asm() -> io::Result<Option<Assembly>> {
     // Read the current exe
     let bytes = fs::read(env::current_exe()?)?;
     // Check if it is a standalone exe
-    let Some(bytes) = bytes.strip_suffix(STAND_DATA_SIGNATURE) else {
+    let Some((start, end)) = embedded_asm_range(&bytes) else {
         return Ok(None);
     };
-    // Get the length of the serialized files
-    let (bytes, len_bytes) = bytes.split_at(bytes.len() - 8);
-    let asm_len = u64::from_le_bytes(len_bytes.try_into().unwrap());
-    let start = bytes.len() - asm_len as usize;
-    // Deserialize the files
-    let uasm: String = std::str::from_utf8(&bytes[start..]).unwrap().to_string();
-    let asm = Assembly::from_uasm(&uasm).map_err(|e| {
+    // Deserialize the assembly
+    let asm = Assembly::from_uasm_bytes(&bytes[start..end]).map_err(|e| {
         io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("Failed to deserialize assembly: {e}\n\nuasm text:\n{uasm}"),
+            format!("Failed to deserialize assembly: {e}"),
         )
     })?;
     Ok(Some(asm))
@@ -0,0 +1,386 @@
+//! Parsing command line arguments according to a declarative spec
+
+use std::fmt::Write;
+
+use crate::{Array, Boxed, Uiua, UiuaResult, Value};
+
+/// A parsed argument spec
+struct Spec {
+    params: Vec<Param>,
+}
+
+struct Param {
+    kind: ParamKind,
+    desc: String,
+}
+
+enum ParamKind {
+    /// A flag or option that starts with dashes
+    Named {
+        short: Option<char>,
+        long: Option<String>,
+        value: Option<String>,
+    },
+    /// A positional argument
+    Positional {
+        name: String,
+        optional: bool,
+        rest: bool,
+    },
+}
+
+/// The result of parsing arguments
+pub(crate) enum ParsedArgs {
+    /// The arguments as a map array
+    Map(Value),
+    /// Help was requested
+    Help(String),
+}
+
+enum ArgValue {
+    Count(usize),
+    Str(String),
+    List(Vec<String>),
+}
+
+impl From<ArgValue> for Value {
+    fn from(value: ArgValue) -> Self {
+        match value {
+            ArgValue::Count(n) => n.into(),
+            ArgValue::Str(s) => s.into(),
+            ArgValue::List(list) => Array::<Boxed>::from_iter(list).into(),
+        }
+    }
+}
+
+impl Param {
+    fn key(&self) -> String {
+        match &self.kind {
+            ParamKind::Named { short, long, .. } => {
+                long.clone().unwrap_or_else(|| short.unwrap().into())
+            }
+            ParamKind::Positional { name, .. } => name.to_lowercase(),
+        }
+    }
+    fn usage(&self) -> String {
+        match &self.kind {
+            ParamKind::Named { short, long, value } => {
+                let mut s = String::new();
+                if let Some(short) = short {
+                    _ = write!(s, "-{short}");
+                }
+                if let Some(long) = long {
+                    if short.is_some() {
+                        s.push_str(", ");
+                    }
+                    _ = write!(s, "--{long}");
+                }
+                if let Some(value) = value {
+                    _ = write!(s, " {value}");
+                }
+                s
+            }
+            ParamKind::Positional {
+                name,
+                optional,
+                rest,
+            } => {
+                let dots = if *rest { "..." } else { "" };
+                if *optional {
+                    format!("[{name}{dots}]")
+                } else {
+                    format!("{name}{dots}")
+                }
+            }
+        }
+    }
+}
+
+impl Spec {
+    fn parse(lines: &[String]) -> Result<Self, String> {
+        let mut params: Vec<Param> = Vec::new();
+        for line in lines {
+            let (def, desc) = line.split_once(" # ").unwrap_or((line, ""));
+            let desc = desc.trim().to_string();
+            let mut words = def.split_whitespace();
+            let Some(first) = words.next() else {
+                return Err("Argument spec entries cannot be empty".into());
+            };
+            let kind = if first.starts_with('-') {
+                let (mut short, mut long, mut value) = (None, None, None);
+                for word in [first].into_iter().chain(words) {
+                    if let Some(name) = word.strip_prefix("--") {
+                        if name.is_empty() || long.is_some() {
+                            return Err(format!("Invalid argument spec `{line}`"));
+                        }
+                        long = Some(name.to_string());
+                    } else if let Some(name) = word.strip_prefix('-') {
+                        let mut chars = name.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) if short.is_none() => short = Some(c),
+                            _ => return Err(format!("Invalid argument spec `{line}`")),
+                        }
+                    } else if value.is_none() {
+                        value = Some(word.to_string());
+                    } else {
+                        return Err(format!("Invalid argument spec `{line}`"));
+                    }
+                }
+                ParamKind::Named { short, long, value }
+            } else {
+                if words.next().is_some() {
+                    return Err(format!("Invalid argument spec `{line}`"));
+                }
+                let (name, optional) = match first.strip_prefix('[') {
+                    Some(name) => (name.strip_suffix(']').unwrap_or(name), true),
+                    None => (first, false),
+                };
+                let (name, rest) = match name.strip_suffix("...") {
+                    Some(name) => (name, true),
+                    None => (name, false),
+                };
+                if let Some(prev) = params.iter().rev().find_map(|p| match &p.kind {
+                    ParamKind::Positional { name, rest, .. } if *rest => Some(name),
+                    _ => None,
+                }) {
+                    return Err(format!(
+                        "Positional argument `{name}` cannot come after `{prev}...`"
+                    ));
+                }
+                ParamKind::Positional {
+                    name: name.to_string(),
+                    optional,
+                    rest,
+                }
+            };
+            let param = Param { kind, desc };
+            if params.iter().any(|p| p.key() == param.key()) {
+                return Err(format!("Argument `{}` is specified twice", param.key()));
+            }
+            params.push(param);
+        }
+        Ok(Spec { params })
+    }
+    fn named(&self) -> impl Iterator<Item = &Param> {
+        (self.params.iter()).filter(|p| matches!(p.kind, ParamKind::Named { .. }))
+    }
+    fn positional(&self) -> impl Iterator<Item = &Param> {
+        (self.params.iter()).filter(|p| matches!(p.kind, ParamKind::Positional { .. }))
+    }
+    fn find_short(&self, c: char) -> Option<&Param> {
+        self.named()
+            .find(|p| matches!(&p.kind, ParamKind::Named { short, .. } if *short == Some(c)))
+    }
+    fn find_long(&self, name: &str) -> Option<&Param> {
+        self.named().find(
+            |p| matches!(&p.kind, ParamKind::Named { long, .. } if long.as_deref() == Some(name)),
+        )
+    }
+    fn usage(&self, program: &str) -> String {
+        let mut s = format!("Usage: {program}");
+        if self.named().next().is_some() {
+            s.push_str(" [OPTIONS]");
+        }
+        for param in self.positional() {
+            _ = write!(s, " {}", param.usage());
+        }
+        s
+    }
+    fn help(&self, program: &str) -> String {
+        let mut s = self.usage(program);
+        let help = Param {
+            kind: ParamKind::Named {
+                short: self.find_short('h').is_none().then_some('h'),
+                long: Some("help".into()),
+                value: None,
+            },
+            desc: "Print help".into(),
+        };
+        let sections = [
+            ("Arguments", self.positional().collect::<Vec<_>>()),
+            ("Options", self.named().chain([&help]).collect()),
+        ];
+        let width = (sections.iter())
+            .flat_map(|(_, params)| params.iter().map(|p| p.usage().chars().count()))
+            .max()
+            .unwrap_or(0);
+        for (title, params) in sections {
+            if params.is_empty() {
+                continue;
+            }
+            _ = write!(s, "\n\n{title}:");
+            for param in params {
+                let usage = param.usage();
+                if param.desc.is_empty() {
+                    _ = write!(s, "\n  {usage}");
+                } else {
+                    _ = write!(s, "\n  {usage:width$}  {}", param.desc);
+                }
+            }
+        }
+        s.push('\n');
+        s
+    }
+}
+
+/// Parse arguments according to a spec
+///
+/// Each spec entry is a flag like `-v --verbose`, an option with a value like
+/// `-o --output FILE`, or a positional argument like `FILE`, `[FILE]`, or `FILES...`.
+/// Anything after ` # ` is a description shown in the help text.
+pub(crate) fn parse_args(
+    spec: &[String],
+    args: &[String],
+    program: &str,
+    env: &Uiua,
+) -> UiuaResult<ParsedArgs> {
+    let spec = Spec::parse(spec).map_err(|e| env.error(e))?;
+    let err = |message: String| {
+        Err(env.error(format!(
+            "{message}\n\n{}\n\nFor more information, try '--help'",
+            spec.usage(program)
+        )))
+    };
+    let mut values: Vec<Option<ArgValue>> = (spec.params.iter())
+        .map(|p| match &p.kind {
+            ParamKind::Named { value: None, .. } => Some(ArgValue::Count(0)),
+            ParamKind::Positional { rest: true, .. } => Some(ArgValue::List(Vec::new())),
+            _ => None,
+        })
+        .collect();
+    let index_of = |param: &Param| spec.params.iter().position(|p| std::ptr::eq(p, param));
+    let mut positionals: Vec<String> = Vec::new();
+    let mut args = args.iter();
+    let mut only_positional = false;
+    while let Some(arg) = args.next() {
+        if only_positional || arg == "-" || !arg.starts_with('-') {
+            positionals.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            only_positional = true;
+            continue;
+        }
+        // Resolve the arguments to a list of parameters, each maybe with an inline value
+        let mut found: Vec<(&Param, Option<String>, String)> = Vec::new();
+        if let Some(name) = arg.strip_prefix("--") {
+            let (name, inline) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            match spec.find_long(name) {
+                Some(param) => found.push((param, inline, format!("--{name}"))),
+                None if name == "help" => return Ok(ParsedArgs::Help(spec.help(program))),
+                None => return err(format!("Unknown option `--{name}`")),
+            }
+        } else {
+            let shorts = &arg[1..];
+            for (i, c) in shorts.char_indices() {
+                let Some(param) = spec.find_short(c) else {
+                    if c == 'h' {
+                        return Ok(ParsedArgs::Help(spec.help(program)));
+                    }
+                    return err(format!("Unknown option `-{c}`"));
+                };
+                if let ParamKind::Named { value: Some(_), .. } = param.kind {
+                    // The rest of a short group is the value
+                    let rest = &shorts[i + c.len_utf8()..];
+                    let inline = (!rest.is_empty()).then(|| rest.to_string());
+                    found.push((param, inline, format!("-{c}")));
+                    break;
+                }
+                found.push((param, None, format!("-{c}")));
+            }
+        }
+        for (param, inline, written) in found {
+            let i = index_of(param).unwrap();
+            match (&param.kind, inline) {
+                (ParamKind::Named { value: None, .. }, Some(_)) => {
+                    return err(format!("Flag `{written}` does not take a value"))
+                }
+                (ParamKind::Named { value: None, .. }, None) => {
+                    if let Some(ArgValue::Count(n)) = &mut values[i] {
+                        *n += 1;
+                    }
+                }
+                (
+                    ParamKind::Named {
+                        value: Some(name), ..
+                    },
+                    inline,
+                ) => {
+                    let Some(value) = inline.or_else(|| args.next().cloned()) else {
+                        return err(format!("Option `{written}` expects a value {name}"));
+                    };
+                    values[i] = Some(ArgValue::Str(value));
+                }
+                (ParamKind::Positional { .. }, _) => unreachable!(),
+            }
+        }
+    }
+    // Assign positional arguments
+    let mut positionals = positionals.into_iter();
+    for (i, param) in spec.params.iter().enumerate() {
+        let ParamKind::Positional {
+            name,
+            optional,
+            rest,
+        } = &param.kind
+        else {
+            continue;
+        };
+        if *rest {
+            values[i] = Some(ArgValue::List(positionals.by_ref().collect()));
+        } else if let Some(arg) = positionals.next() {
+            values[i] = Some(ArgValue::Str(arg));
+        } else if !optional {
+            return err(format!("Missing argument {name}"));
+        }
+    }
+    if let Some(arg) = positionals.next() {
+        return err(format!("Unexpected argument `{arg}`"));
+    }
+    let (keys, values): (Vec<String>, Vec<Boxed>) = (spec.params.iter().zip(values))
+        .filter_map(|(param, value)| Some((param.key(), Boxed(value?.into()))))
+        .unzip();
+    let mut map: Value = Array::from_iter(values).into();
+    map.map(Array::<Boxed>::from_iter(keys).into(), env)?;
+    Ok(ParsedArgs::Map(map))
+}
+
+#[cfg(test)]
+#[test]
+fn parse_arg_specs() {
+    use crate::{Uiua, UiuaError, Value};
+    let parse = |args: &[&str]| {
+        let mut env = Uiua::with_safe_sys().with_args(args.iter().map(|s| s.to_string()).collect());
+        env.run_str(
+            r#"&pargs {"-v --verbose" "-o --output FILE # Where to write" "IN" "[REST...]"}
+            ⊃(⧻°□get "rest"|⍣(°□get "output")"none"|°□get "in"|°□get "verbose")"#,
+        )?;
+        Ok::<_, UiuaError>(
+            env.take_stack()
+                .iter()
+                .map(Value::format)
+                .collect::<Vec<_>>(),
+        )
+    };
+    assert_eq!(
+        parse(&["-vv", "a", "b", "c", "--output=d"]).unwrap_or_else(|e| panic!("{e}")),
+        ["2", "a", "d", "2"]
+    );
+    assert_eq!(
+        parse(&["-voc", "a", "--", "-b"]).unwrap_or_else(|e| panic!("{e}")),
+        ["1", "a", "c", "1"]
+    );
+    assert_eq!(
+        parse(&["a"]).unwrap_or_else(|e| panic!("{e}")),
+        ["0", "a", "none", "0"]
+    );
+    let Err(err) = parse(&[]) else {
+        panic!("Missing argument should fail")
+    };
+    assert!(err.to_string().contains("Missing argument IN"), "{err}");
+    assert!(parse(&["x", "--nope"]).is_err());
+    assert!(parse(&["x", "-v=1"]).is_err());
+}
@@ -0,0 +1,579 @@
+//! Serving the files of a program from a bundle
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    io::BufRead,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[cfg(feature = "image")]
+use image::DynamicImage;
+use parking_lot::Mutex;
+
+use super::{
+    AudioBufferFn, AudioStreamFn, CommandEnv, Email, FileMetadata, GamepadState, GitTarget, Handle,
+    MouseState, ReadLinesFn, ReadLinesReturnFn, SerialSettings, SmtpServer, SysBackend,
+    TlsClientSettings, TlsServerSettings, WebcamImage,
+};
+use crate::{FfiType, Uiua, Value};
+
+/// A system backend that serves files from a bundle of a program and its assets
+///
+/// A bundle is an in-memory tree of files, which can be loaded from a zip archive.
+/// Bundled files can be read and listed with the usual file system functions,
+/// using paths relative to the root of the bundle.
+/// Paths that are not in the bundle, and all other system calls, are forwarded
+/// to the wrapped backend.
+///
+/// Bundled files are read-only. Trying to write to or delete them is an error.
+pub struct BundleSys {
+    inner: Arc<dyn SysBackend>,
+    files: BTreeMap<PathBuf, Arc<[u8]>>,
+    streams: Mutex<HashMap<Handle, BundleStream>>,
+    next_handle: AtomicU64,
+}
+
+/// A bundled file opened for reading
+struct BundleStream {
+    contents: Arc<[u8]>,
+    pos: usize,
+}
+
+impl BundleStream {
+    fn read(&mut self, count: usize) -> Vec<u8> {
+        let end = self.contents.len().min(self.pos.saturating_add(count));
+        let bytes = self.contents[self.pos..end].to_vec();
+        self.pos = end;
+        bytes
+    }
+}
+
+/// Handles of bundled files start here so that they do not collide with the
+/// handles of the wrapped backend, while still being exactly representable
+/// as numbers in Uiua
+const FIRST_BUNDLE_HANDLE: u64 = 1 << 52;
+
+impl BundleSys {
+    /// Create a bundle from an iterator of paths and file contents
+    ///
+    /// Paths are relative to the root of the bundle.
+    /// Absolute paths and paths that leave the root are ignored.
+    pub fn new<P, B>(inner: Arc<dyn SysBackend>, files: impl IntoIterator<Item = (P, B)>) -> Self
+    where
+        P: AsRef<Path>,
+        B: Into<Vec<u8>>,
+    {
+        let files = (files.into_iter())
+            .filter_map(|(path, contents)| {
+                let path = bundle_path(path.as_ref())?;
+                (!path.as_os_str().is_empty()).then(|| (path, contents.into().into()))
+            })
+            .collect();
+        BundleSys {
+            inner,
+            files,
+            streams: Mutex::new(HashMap::new()),
+            next_handle: AtomicU64::new(FIRST_BUNDLE_HANDLE),
+        }
+    }
+    /// Create a bundle from the bytes of a zip archive
+    #[cfg(feature = "bundle")]
+    pub fn from_zip(inner: Arc<dyn SysBackend>, zip_bytes: &[u8]) -> Result<Self, String> {
+        use std::io::{Cursor, Read};
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip_bytes))
+            .map_err(|e| format!("Invalid bundle: {e}"))?;
+        let mut files = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| format!("Invalid bundle: {e}"))?;
+            if file.is_dir() {
+                continue;
+            }
+            let path = (file.enclosed_name())
+                .ok_or_else(|| format!("Invalid path in bundle: {}", file.name()))?
+                .to_path_buf();
+            let mut contents = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut contents)
+                .map_err(|e| format!("Error reading {} from bundle: {e}", path.display()))?;
+            files.push((path, contents));
+        }
+        Ok(Self::new(inner, files))
+    }
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &Arc<dyn SysBackend> {
+        &self.inner
+    }
+    /// Check if a file is in the bundle
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.bundle_file(path.as_ref()).is_some()
+    }
+    /// Iterate over the paths of the files in the bundle
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+    fn bundle_file(&self, path: &Path) -> Option<&Arc<[u8]>> {
+        self.files.get(&bundle_path(path)?)
+    }
+    fn bundle_dir(&self, path: &Path) -> Option<PathBuf> {
+        let path = bundle_path(path)?;
+        (self.files.keys())
+            .any(|file| file != &path && file.starts_with(&path))
+            .then_some(path)
+    }
+    fn read_only(&self, path: &Path) -> Result<(), String> {
+        if self.bundle_file(path).is_some() || self.bundle_dir(path).is_some() {
+            Err(format!(
+                "{} is in the bundle, which is read-only",
+                path.display()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    fn with_stream<T>(&self, handle: Handle, f: impl FnOnce(&mut BundleStream) -> T) -> Option<T> {
+        self.streams.lock().get_mut(&handle).map(f)
+    }
+}
+
+/// Normalize a path into a key of the bundle
+fn bundle_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+impl SysBackend for BundleSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, message: String, colored: String) {
+        self.inner.save_error_color(message, colored)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.inner.print_str_trace(s)
+    }
+    fn show(&self, value: Value) -> Result<(), String> {
+        self.inner.show(value)
+    }
+    fn show_colored(&self, value: Value) -> Result<(), String> {
+        self.inner.show_colored(value)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.inner.scan_line_stdin()
+    }
+    fn scan_stdin(&self, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.inner.scan_stdin(count)
+    }
+    fn scan_until_stdin(&self, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.inner.scan_until_stdin(delim)
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        self.inner.set_raw_mode(raw_mode)
+    }
+    fn get_raw_mode(&self) -> Result<bool, String> {
+        self.inner.get_raw_mode()
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        self.inner.var(name)
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.inner.term_size()
+    }
+    fn exit(&self, status: i32) -> Result<(), String> {
+        self.inner.exit(status)
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        self.bundle_file(path).is_some()
+            || self.bundle_dir(path).is_some()
+            || self.inner.file_exists(&path.to_string_lossy())
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let Some(dir) = self.bundle_dir(Path::new(path)) else {
+            return self.inner.list_dir(path);
+        };
+        let mut paths: Vec<String> = (self.files.keys())
+            .filter_map(|file| file.strip_prefix(&dir).ok()?.components().next())
+            .map(|name| Path::new(path).join(name).to_string_lossy().into_owned())
+            .collect();
+        if let Ok(inner_paths) = self.inner.list_dir(path) {
+            paths.extend(inner_paths);
+        }
+        paths.sort_unstable();
+        paths.dedup();
+        Ok(paths)
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        if self.bundle_file(Path::new(path)).is_some() {
+            Ok(true)
+        } else if self.bundle_dir(Path::new(path)).is_some() {
+            Ok(false)
+        } else {
+            self.inner.is_file(path)
+        }
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        if let Some(contents) = self.bundle_file(Path::new(path)) {
+            Ok(FileMetadata {
+                size: contents.len() as u64,
+                modified: None,
+                is_dir: false,
+            })
+        } else if self.bundle_dir(Path::new(path)).is_some() {
+            Ok(FileMetadata {
+                size: 0,
+                modified: None,
+                is_dir: true,
+            })
+        } else {
+            self.inner.file_metadata(path)
+        }
+    }
+    fn canonicalize(&self, path: &str) -> Result<String, String> {
+        match bundle_path(Path::new(path)) {
+            Some(bundled) if self.file_exists(path) && !self.inner.file_exists(path) => {
+                Ok(bundled.to_string_lossy().into_owned())
+            }
+            _ => self.inner.canonicalize(path),
+        }
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.read_only(Path::new(path))?;
+        self.inner.delete(path)
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.read_only(Path::new(path))?;
+        self.inner.trash(path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        match self.with_stream(handle, |stream| stream.read(count)) {
+            Some(bytes) => Ok(bytes),
+            None => self.inner.read(handle, count),
+        }
+    }
+    fn read_all(&self, handle: Handle) -> Result<Vec<u8>, String> {
+        match self.with_stream(handle, |stream| stream.read(usize::MAX)) {
+            Some(bytes) => Ok(bytes),
+            None => self.inner.read_all(handle),
+        }
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        let read = self.with_stream(handle, |stream| {
+            let rest = &stream.contents[stream.pos..];
+            let len = (rest.windows(delim.len().max(1)))
+                .position(|window| window == delim)
+                .map_or(rest.len(), |i| i + delim.len());
+            stream.read(len)
+        });
+        match read {
+            Some(bytes) => Ok(bytes),
+            None => self.inner.read_until(handle, delim),
+        }
+    }
+    fn read_lines<'a>(&self, handle: Handle) -> Result<ReadLinesReturnFn<'a>, String> {
+        let Some(rest) = self.with_stream(handle, |stream| stream.read(usize::MAX)) else {
+            return self.inner.read_lines(handle);
+        };
+        Ok(Box::new(move |env: &mut Uiua, mut f: ReadLinesFn| {
+            for line in rest.lines() {
+                let line = line.map_err(|e| env.error(format!("Error reading line: {e}")))?;
+                f(line, env)?;
+            }
+            Ok(())
+        }))
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        if self.streams.lock().contains_key(&handle) {
+            return Err("Cannot write to a file in the bundle".into());
+        }
+        self.inner.write(handle, contents)
+    }
+    fn create_file(&self, path: &Path) -> Result<Handle, String> {
+        self.read_only(path)?;
+        self.inner.create_file(path)
+    }
+    fn open_file(&self, path: &Path, write: bool) -> Result<Handle, String> {
+        let Some(contents) = self.bundle_file(path) else {
+            return self.inner.open_file(path, write);
+        };
+        // Bundled files are read-only, so writing to the handle is an error
+        let handle = Handle(self.next_handle.fetch_add(1, atomic::Ordering::Relaxed));
+        let stream = BundleStream {
+            contents: contents.clone(),
+            pos: 0,
+        };
+        self.streams.lock().insert(handle, stream);
+        Ok(handle)
+    }
+    fn make_dir(&self, path: &Path) -> Result<(), String> {
+        self.inner.make_dir(path)
+    }
+    fn file_read_all(&self, path: &Path) -> Result<Vec<u8>, String> {
+        match self.bundle_file(path) {
+            Some(contents) => Ok(contents.to_vec()),
+            None => self.inner.file_read_all(path),
+        }
+    }
+    fn file_append(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.read_only(path)?;
+        self.inner.file_append(path, contents)
+    }
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.read_only(path)?;
+        self.inner.file_write_atomic(path, contents)
+    }
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        // Bundled files cannot change, so there is nothing to guard
+        if self.streams.lock().contains_key(&handle) {
+            return Ok(());
+        }
+        self.inner.lock_file(handle)
+    }
+    fn file_write_all(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.read_only(path)?;
+        self.inner.file_write_all(path, contents)
+    }
+    fn clipboard(&self) -> Result<String, String> {
+        self.inner.clipboard()
+    }
+    fn set_clipboard(&self, contents: &str) -> Result<(), String> {
+        self.inner.set_clipboard(contents)
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.inner.sleep(seconds)
+    }
+    fn allow_thread_spawning(&self) -> bool {
+        self.inner.allow_thread_spawning()
+    }
+    #[cfg(feature = "image")]
+    fn show_image(&self, image: DynamicImage, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_image(image, label)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_gif(gif_bytes, label)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.play_audio(wave_bytes, label)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.inner.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.inner.stream_audio(f)
+    }
+    fn stream_audio_buffers(&self, f: AudioBufferFn) -> Result<(), String> {
+        self.inner.stream_audio_buffers(f)
+    }
+    fn now(&self) -> f64 {
+        self.inner.now()
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.inner.tcp_listen(addr)
+    }
+    fn tls_listen(&self, addr: &str, settings: &TlsServerSettings) -> Result<Handle, String> {
+        self.inner.tls_listen(addr, settings)
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.inner.tcp_accept(handle)
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.inner.tcp_connect(addr)
+    }
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        self.inner.tls_connect(addr, settings)
+    }
+    fn tls_alpn(&self, handle: Handle) -> Result<Option<String>, String> {
+        self.inner.tls_alpn(handle)
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<SocketAddr, String> {
+        self.inner.tcp_addr(handle)
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.inner.tcp_set_non_blocking(handle, non_blocking)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_read_timeout(handle, timeout)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_write_timeout(handle, timeout)
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        if self.streams.lock().remove(&handle).is_some() {
+            return Ok(());
+        }
+        self.inner.close(handle)
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.inner.invoke(path)
+    }
+    fn run_command_inherit(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<i32, String> {
+        self.inner.run_command_inherit(command, args, cmd_env)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<(i32, String, String), String> {
+        self.inner.run_command_capture(command, args, cmd_env)
+    }
+    fn run_command_stream(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<[Handle; 3], String> {
+        self.inner.run_command_stream(command, args, cmd_env)
+    }
+    fn current_dir(&self) -> Result<String, String> {
+        self.inner.current_dir()
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.inner.https_get(request, handle)
+    }
+    fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+        self.inner.smtp_send(server, email)
+    }
+    fn serial_ports(&self) -> Result<Vec<String>, String> {
+        self.inner.serial_ports()
+    }
+    fn serial_open(&self, path: &str, settings: &SerialSettings) -> Result<Handle, String> {
+        self.inner.serial_open(path, settings)
+    }
+    fn serial_read(&self, handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.inner.serial_read(handle, count)
+    }
+    fn serial_write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.inner.serial_write(handle, contents)
+    }
+    fn webcam_capture(&self, index: usize) -> Result<WebcamImage, String> {
+        self.inner.webcam_capture(index)
+    }
+    fn mouse(&self) -> Result<MouseState, String> {
+        self.inner.mouse()
+    }
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        self.inner.gamepad(index)
+    }
+    fn ffi(
+        &self,
+        file: &str,
+        result_ty: FfiType,
+        name: &str,
+        arg_tys: &[FfiType],
+        args: &[Value],
+    ) -> Result<Value, String> {
+        self.inner.ffi(file, result_ty, name, arg_tys, args)
+    }
+    fn mem_copy(&self, ty: FfiType, ptr: *const (), len: usize) -> Result<Value, String> {
+        self.inner.mem_copy(ty, ptr, len)
+    }
+    fn mem_free(&self, ptr: *const ()) -> Result<(), String> {
+        self.inner.mem_free(ptr)
+    }
+    fn load_git_module(&self, url: &str, target: GitTarget) -> Result<PathBuf, String> {
+        self.inner.load_git_module(url, target)
+    }
+    fn load_package(&self, name: &str) -> Result<PathBuf, String> {
+        self.inner.load_package(name)
+    }
+    fn timezone(&self) -> Result<f64, String> {
+        self.inner.timezone()
+    }
+    fn breakpoint(&self, env: &Uiua) -> Result<bool, String> {
+        self.inner.breakpoint(env)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn bundle_backend() {
+    use crate::{Array, Boxed, BundleSys, IntoSysBackend, SafeSys, Uiua, Value};
+    let files = [
+        ("lib.ua", "Greet ← $\"Hello, _!\""),
+        ("assets/name.txt", "bundle"),
+        ("./assets/data/x.txt", "a\nb"),
+    ];
+    let bundle = BundleSys::new(SafeSys::new().into_sys_backend(), files);
+    assert!(bundle.contains("assets/../lib.ua"));
+    assert!(!bundle.contains("/lib.ua"));
+    let mut env = Uiua::with_backend(bundle);
+    let sep = std::path::MAIN_SEPARATOR;
+    let cases: [(&str, Value); 6] = [
+        (
+            "~ \"lib\" ~ Greet\nGreet &fras \"assets/name.txt\"",
+            "Hello, bundle!".into(),
+        ),
+        (
+            r#"&fld "assets""#,
+            Array::<Boxed>::from_iter([format!("assets{sep}data"), "assets/name.txt".into()])
+                .into(),
+        ),
+        (r#"&fif "assets/data""#, false.into()),
+        (r#"&fe "assets/missing.txt""#, false.into()),
+        (r#"⍜&fo(&ru "\n") "assets/data/x.txt""#, "a\n".into()),
+        (
+            r#"&fwalk(°□get "size") "assets""#,
+            Array::<f64>::from_iter([0.0, 3.0, 6.0]).into(),
+        ),
+    ];
+    for (code, expected) in cases {
+        env.run_str(code).unwrap_or_else(|e| panic!("{code}: {e}"));
+        assert_eq!(env.pop("result").unwrap(), expected, "{code}");
+    }
+    assert!(env.run_str(r#"&fwa "lib.ua" """#).is_err());
+    #[cfg(feature = "bundle")]
+    {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("dir/file.txt", Default::default()).unwrap();
+        zip.write_all(b"zipped").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+        let bundle = BundleSys::from_zip(SafeSys::new().into_sys_backend(), &bytes).unwrap();
+        let mut env = Uiua::with_backend(bundle);
+        env.run_str(r#"&fras "dir/file.txt""#).unwrap();
+        assert_eq!(env.pop_string().unwrap(), "zipped");
+        assert!(BundleSys::from_zip(SafeSys::new().into_sys_backend(), b"zip").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Composing system backends from layers
+
+use std::{
+    any::Any,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(feature = "image")]
+use image::DynamicImage;
+
+use super::{
+    AudioBufferFn, AudioStreamFn, CommandEnv, Email, FileMetadata, GamepadState, GitTarget, Handle,
+    MouseState, ReadLinesReturnFn, SerialSettings, SmtpServer, SysBackend, TlsClientSettings,
+    TlsServerSettings, WebcamImage,
+};
+use crate::{FfiType, Uiua, Value};
+use SysCallKind::*;
+
+/// A layer that observes or filters the calls made to a system backend
+///
+/// Wrap a backend in a layer with [`LayeredSys`] or [`IntoSysBackend::with_layer`](crate::IntoSysBackend::with_layer).
+/// Every call to the wrapped backend is passed to [`SysLayer::before`] and [`SysLayer::after`],
+/// so a layer only needs to implement what it cares about.
+/// Layers can be stacked by wrapping a layered backend in another layer.
+///
+/// Any `Fn(&SysCall) -> Result<(), String>` is a layer that runs before each call.
+///
+/// ```
+/// use uiua::{IntoSysBackend, NativeSys, SysCallKind, Uiua};
+///
+/// let backend = NativeSys
+///     .with_layer(|call: &uiua::SysCall| {
+///         if call.kind == SysCallKind::WriteFile {
+///             Err(format!("{call} is not allowed"))
+///         } else {
+///             Ok(())
+///         }
+///     })
+///     .with_layer(|call: &uiua::SysCall| {
+///         eprintln!("{call}");
+///         Ok(())
+///     });
+/// let mut env = Uiua::with_backend(backend);
+/// assert!(env.run_str("&fwa \"file.txt\" \"hi\"").is_err());
+/// ```
+#[allow(unused_variables)]
+pub trait SysLayer: Send + Sync + 'static {
+    /// Called before a call is passed to the wrapped backend
+    ///
+    /// Returning an error prevents the call, and the error is returned instead.
+    /// Calls that cannot fail, like [`SysBackend::file_exists`], return a default value instead.
+    fn before(&self, call: &SysCall) -> Result<(), String> {
+        Ok(())
+    }
+    /// Called after the wrapped backend returns, with its error if there was one
+    fn after(&self, call: &SysCall, result: Result<(), &str>) {}
+}
+
+impl<F> SysLayer for F
+where
+    F: Fn(&SysCall) -> Result<(), String> + Send + Sync + 'static,
+{
+    fn before(&self, call: &SysCall) -> Result<(), String> {
+        self(call)
+    }
+}
+
+/// A call to a system backend, as seen by a [`SysLayer`]
+#[derive(Debug, Clone, Copy)]
+pub struct SysCall<'a> {
+    /// The name of the [`SysBackend`] method
+    pub name: &'static str,
+    /// What the call does
+    pub kind: SysCallKind,
+    /// The path the call uses, if any
+    pub path: Option<&'a Path>,
+    /// The stream handle the call uses, if any
+    pub handle: Option<Handle>,
+}
+
+impl fmt::Display for SysCall<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        match (self.path, self.handle) {
+            (Some(path), Some(handle)) => write!(f, "{path:?}, {}", handle.0)?,
+            (Some(path), None) => write!(f, "{path:?}")?,
+            (None, Some(handle)) => write!(f, "{}", handle.0)?,
+            (None, None) => {}
+        }
+        write!(f, ")")
+    }
+}
+
+/// The kind of a [`SysCall`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SysCallKind {
+    /// Printing, showing, or playing something
+    Output,
+    /// Reading stdin, the clipboard, the environment, or an input device
+    Input,
+    /// Reading a file or directory by its path
+    ReadFile,
+    /// Creating, changing, or deleting a file or directory by its path
+    ///
+    /// This includes opening a file for writing
+    WriteFile,
+    /// Reading from a stream handle
+    ReadStream,
+    /// Writing to, configuring, or closing a stream handle
+    WriteStream,
+    /// Connecting to or listening on a network
+    Network,
+    /// Running commands or foreign code, or exiting
+    Process,
+    /// Anything else
+    Other,
+}
+
+/// A system backend wrapped in a [`SysLayer`]
+pub struct LayeredSys<L> {
+    inner: Arc<dyn SysBackend>,
+    layer: L,
+}
+
+impl<L: SysLayer> LayeredSys<L> {
+    /// Wrap a backend in a layer
+    pub fn new(inner: Arc<dyn SysBackend>, layer: L) -> Self {
+        LayeredSys { inner, layer }
+    }
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &Arc<dyn SysBackend> {
+        &self.inner
+    }
+    /// Get the layer
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+}
+
+/// The outcome of a call, for [`SysLayer::after`]
+trait CallResult {
+    fn status(&self) -> Result<(), &str>;
+}
+
+impl<T> CallResult for Result<T, String> {
+    fn status(&self) -> Result<(), &str> {
+        self.as_ref().map(drop).map_err(String::as_str)
+    }
+}
+
+impl CallResult for () {
+    fn status(&self) -> Result<(), &str> {
+        Ok(())
+    }
+}
+
+impl CallResult for bool {
+    fn status(&self) -> Result<(), &str> {
+        Ok(())
+    }
+}
+
+impl CallResult for Option<String> {
+    fn status(&self) -> Result<(), &str> {
+        Ok(())
+    }
+}
+
+macro_rules! layered {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident $(<$lt:lifetime>)? ($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;
+        $kind:expr $(, path = $path:expr)? $(, handle = $handle:expr)? $(; else $denied:expr)?;
+    )*) => {
+        impl<L: SysLayer> SysBackend for LayeredSys<L> {
+            fn any(&self) -> &dyn Any {
+                self
+            }
+            fn any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+            fn allow_thread_spawning(&self) -> bool {
+                self.inner.allow_thread_spawning()
+            }
+            fn audio_sample_rate(&self) -> u32 {
+                self.inner.audio_sample_rate()
+            }
+            fn now(&self) -> f64 {
+                self.inner.now()
+            }
+            $(
+                $(#[$attr])*
+                fn $name $(<$lt>)? (&self, $($arg: $ty),*) $(-> $ret)? {
+                    let call = SysCall {
+                        name: stringify!($name),
+                        kind: $kind,
+                        path: None::<&Path> $(.or(Some(Path::new($path))))?,
+                        handle: None $(.or(Some($handle)))?,
+                    };
+                    let res = match self.layer.before(&call) {
+                        Ok(()) => self.inner.$name($($arg),*),
+                        Err(e) => return layered!(@denied e $(, $denied)?),
+                    };
+                    self.layer.after(&call, res.status());
+                    res
+                }
+            )*
+        }
+    };
+    (@denied $e:ident) => { Err($e) };
+    (@denied $e:ident, $denied:expr) => {{
+        _ = $e;
+        $denied
+    }};
+}
+
+layered! {
+    fn save_error_color(message: String, colored: String); Output; else ();
+    fn print_str_stdout(s: &str) -> Result<(), String>; Output;
+    fn print_str_stderr(s: &str) -> Result<(), String>; Output;
+    fn print_str_trace(s: &str); Output; else ();
+    fn show(value: Value) -> Result<(), String>; Output;
+    fn show_colored(value: Value) -> Result<(), String>; Output;
+    fn scan_line_stdin() -> Result<Option<String>, String>; Input;
+    fn scan_stdin(count: Option<usize>) -> Result<Vec<u8>, String>; Input;
+    fn scan_until_stdin(delim: &[u8]) -> Result<Vec<u8>, String>; Input;
+    fn set_raw_mode(raw_mode: bool) -> Result<(), String>; Other;
+    fn get_raw_mode() -> Result<bool, String>; Input;
+    fn var(name: &str) -> Option<String>; Input; else None;
+    fn term_size() -> Result<(usize, usize), String>; Input;
+    fn exit(status: i32) -> Result<(), String>; Process;
+    fn file_exists(path: &str) -> bool; ReadFile, path = path; else false;
+    fn list_dir(path: &str) -> Result<Vec<String>, String>; ReadFile, path = path;
+    fn is_file(path: &str) -> Result<bool, String>; ReadFile, path = path;
+    fn file_metadata(path: &str) -> Result<FileMetadata, String>; ReadFile, path = path;
+    fn canonicalize(path: &str) -> Result<String, String>; ReadFile, path = path;
+    fn delete(path: &str) -> Result<(), String>; WriteFile, path = path;
+    fn trash(path: &str) -> Result<(), String>; WriteFile, path = path;
+    fn read(handle: Handle, count: usize) -> Result<Vec<u8>, String>; ReadStream, handle = handle;
+    fn read_all(handle: Handle) -> Result<Vec<u8>, String>; ReadStream, handle = handle;
+    fn read_until(handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String>;
+        ReadStream, handle = handle;
+    fn read_lines<'a>(handle: Handle) -> Result<ReadLinesReturnFn<'a>, String>;
+        ReadStream, handle = handle;
+    fn write(handle: Handle, contents: &[u8]) -> Result<(), String>; WriteStream, handle = handle;
+    fn create_file(path: &Path) -> Result<Handle, String>; WriteFile, path = path;
+    fn open_file(path: &Path, write: bool) -> Result<Handle, String>;
+        if write { WriteFile } else { ReadFile }, path = path;
+    fn make_dir(path: &Path) -> Result<(), String>; WriteFile, path = path;
+    fn file_read_all(path: &Path) -> Result<Vec<u8>, String>; ReadFile, path = path;
+    fn file_append(path: &Path, contents: &[u8]) -> Result<(), String>; WriteFile, path = path;
+    fn file_write_atomic(path: &Path, contents: &[u8]) -> Result<(), String>;
+        WriteFile, path = path;
+    fn lock_file(handle: Handle) -> Result<(), String>; WriteStream, handle = handle;
+    fn file_write_all(path: &Path, contents: &[u8]) -> Result<(), String>;
+        WriteFile, path = path;
+    fn clipboard() -> Result<String, String>; Input;
+    fn set_clipboard(contents: &str) -> Result<(), String>; Output;
+    fn sleep(seconds: f64) -> Result<(), String>; Other;
+    #[cfg(feature = "image")]
+    fn show_image(image: DynamicImage, label: Option<&str>) -> Result<(), String>; Output;
+    fn show_gif(gif_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String>; Output;
+    fn play_audio(wave_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String>; Output;
+    fn stream_audio(f: AudioStreamFn) -> Result<(), String>; Output;
+    fn stream_audio_buffers(f: AudioBufferFn) -> Result<(), String>; Output;
+    fn tcp_listen(addr: &str) -> Result<Handle, String>; Network;
+    fn tls_listen(addr: &str, settings: &TlsServerSettings) -> Result<Handle, String>; Network;
+    fn tcp_accept(handle: Handle) -> Result<Handle, String>; Network, handle = handle;
+    fn tcp_connect(addr: &str) -> Result<Handle, String>; Network;
+    fn tls_connect(addr: &str, settings: &TlsClientSettings) -> Result<Handle, String>; Network;
+    fn tls_alpn(handle: Handle) -> Result<Option<String>, String>; Network, handle = handle;
+    fn tcp_addr(handle: Handle) -> Result<SocketAddr, String>; Network, handle = handle;
+    fn tcp_set_non_blocking(handle: Handle, non_blocking: bool) -> Result<(), String>;
+        WriteStream, handle = handle;
+    fn tcp_set_read_timeout(handle: Handle, timeout: Option<Duration>) -> Result<(), String>;
+        WriteStream, handle = handle;
+    fn tcp_set_write_timeout(handle: Handle, timeout: Option<Duration>) -> Result<(), String>;
+        WriteStream, handle = handle;
+    fn close(handle: Handle) -> Result<(), String>; WriteStream, handle = handle;
+    fn invoke(path: &str) -> Result<(), String>; Process, path = path;
+    fn run_command_inherit(command: &str, args: &[&str], cmd_env: &CommandEnv)
+        -> Result<i32, String>; Process;
+    fn run_command_capture(command: &str, args: &[&str], cmd_env: &CommandEnv)
+        -> Result<(i32, String, String), String>; Process;
+    fn run_command_stream(command: &str, args: &[&str], cmd_env: &CommandEnv)
+        -> Result<[Handle; 3], String>; Process;
+    fn current_dir() -> Result<String, String>; Input;
+    fn https_get(request: &str, handle: Handle) -> Result<String, String>;
+        Network, handle = handle;
+    fn smtp_send(server: &SmtpServer, email: &Email) -> Result<(), String>; Network;
+    fn serial_ports() -> Result<Vec<String>, String>; Input;
+    fn serial_open(path: &str, settings: &SerialSettings) -> Result<Handle, String>;
+        Other, path = path;
+    fn serial_read(handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String>;
+        ReadStream, handle = handle;
+    fn serial_write(handle: Handle, contents: &[u8]) -> Result<(), String>;
+        WriteStream, handle = handle;
+    fn webcam_capture(index: usize) -> Result<WebcamImage, String>; Input;
+    fn mouse() -> Result<MouseState, String>; Input;
+    fn gamepad(index: usize) -> Result<Option<GamepadState>, String>; Input;
+    fn ffi(
+        file: &str,
+        result_ty: FfiType,
+        name: &str,
+        arg_tys: &[FfiType],
+        args: &[Value]
+    ) -> Result<Value, String>; Process, path = file;
+    fn mem_copy(ty: FfiType, ptr: *const (), len: usize) -> Result<Value, String>; Process;
+    fn mem_free(ptr: *const ()) -> Result<(), String>; Process;
+    fn load_git_module(url: &str, target: GitTarget) -> Result<PathBuf, String>; Network;
+    fn load_package(name: &str) -> Result<PathBuf, String>; Network;
+    fn timezone() -> Result<f64, String>; Input;
+    fn breakpoint(env: &Uiua) -> Result<bool, String>; Other;
+}
+
+#[cfg(test)]
+#[test]
+fn layered_backend() {
+    use crate::{LayeredSys, MemFsBackend, SysCall, SysCallKind, SysLayer, Uiua, Value};
+    use std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+    impl SysLayer for Log {
+        fn after(&self, call: &SysCall, result: Result<(), &str>) {
+            let entry = match result {
+                Ok(()) => call.to_string(),
+                Err(e) => format!("{call}: {e}"),
+            };
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    let read_only = |call: &SysCall| match call.kind {
+        SysCallKind::WriteFile => Err(format!("{} is read-only", call.path.unwrap().display())),
+        _ => Ok(()),
+    };
+    let latency = |call: &SysCall| {
+        if call.kind == SysCallKind::ReadFile {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Ok(())
+    };
+    let backend = MemFsBackend::new()
+        .with_file("a.txt", "ay")
+        .with_layer(latency)
+        .with_layer(read_only)
+        .with_layer(Log::default());
+    let mut env = Uiua::with_backend(backend);
+
+    let start = Instant::now();
+    env.run_str(r#"&fras "a.txt""#).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(env.pop("contents").unwrap(), Value::from("ay"));
+    let Err(err) = env.run_str(r#"&fwa "b.txt" "bee""#) else {
+        panic!("write should be denied");
+    };
+    assert!(err.to_string().contains("b.txt is read-only"), "{err}");
+    assert!(env.run_str(r#"&fe "a.txt""#).is_ok());
+
+    let log = env.downcast_backend::<LayeredSys<Log>>().unwrap();
+    let log = log.layer().0.lock().unwrap();
+    assert_eq!(
+        *log,
+        [
+            r#"file_read_all("a.txt")"#,
+            r#"file_write_all("b.txt"): b.txt is read-only"#,
+            r#"file_exists("a.txt")"#,
+        ]
+    );
+}
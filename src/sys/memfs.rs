@@ -0,0 +1,613 @@
+//! An in-memory file system
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::BufRead,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(feature = "image")]
+use image::DynamicImage;
+use parking_lot::Mutex;
+
+use super::{
+    AudioBufferFn, AudioStreamFn, CommandEnv, Email, FileMetadata, GamepadState, GitTarget, Handle,
+    MouseState, ReadLinesFn, ReadLinesReturnFn, SafeSys, SerialSettings, SmtpServer, SysBackend,
+    TlsClientSettings, TlsServerSettings, WebcamImage,
+};
+use crate::{FfiType, IntoSysBackend, Uiua, Value};
+
+/// A system backend with a file system that only exists in memory
+///
+/// This is useful for testing programs that use files without touching the real file system.
+/// It works the same on every platform, including the web.
+///
+/// Paths are relative to the root of the file system, which is also the current directory.
+/// Directories list their entries in order of name.
+/// Files have no modification time.
+///
+/// All system calls that do not involve the file system are forwarded to the wrapped backend,
+/// which is a [`SafeSys`] by default.
+pub struct MemFsBackend {
+    inner: Arc<dyn SysBackend>,
+    fs: Mutex<MemFs>,
+}
+
+#[derive(Default)]
+struct MemFs {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+    streams: HashMap<Handle, MemStream>,
+    next_handle: u64,
+}
+
+/// An open file
+struct MemStream {
+    path: PathBuf,
+    pos: usize,
+}
+
+/// Handles of open files start here so that they do not collide with the
+/// handles of the wrapped backend
+const FIRST_MEM_HANDLE: u64 = 1 << 51;
+
+impl Default for MemFsBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemFsBackend {
+    /// Create an empty file system that wraps a [`SafeSys`]
+    pub fn new() -> Self {
+        Self::wrap(SafeSys::new().into_sys_backend())
+    }
+    /// Create an empty file system that wraps another backend
+    pub fn wrap(inner: Arc<dyn SysBackend>) -> Self {
+        MemFsBackend {
+            inner,
+            fs: Mutex::new(MemFs {
+                next_handle: FIRST_MEM_HANDLE,
+                ..MemFs::default()
+            }),
+        }
+    }
+    /// Add a file, creating its parent directories
+    pub fn with_file(self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        let path = mem_path(path.as_ref());
+        let mut fs = self.fs.lock();
+        if let Some(parent) = path.parent() {
+            fs.make_dir(parent);
+        }
+        fs.files.insert(path, contents.into());
+        drop(fs);
+        self
+    }
+    /// Add a directory, creating its parent directories
+    pub fn with_dir(self, path: impl AsRef<Path>) -> Self {
+        self.fs.lock().make_dir(&mem_path(path.as_ref()));
+        self
+    }
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &Arc<dyn SysBackend> {
+        &self.inner
+    }
+    /// Get the contents of a file
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.fs.lock().files.get(&mem_path(path.as_ref())).cloned()
+    }
+    /// Get the paths of all files in order
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.fs.lock().files.keys().cloned().collect()
+    }
+}
+
+impl MemFs {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.as_os_str().is_empty() || self.dirs.contains(path)
+    }
+    fn make_dir(&mut self, path: &Path) {
+        for dir in path.ancestors() {
+            if !dir.as_os_str().is_empty() {
+                self.dirs.insert(dir.into());
+            }
+        }
+    }
+    /// Check that a file can be created at a path
+    fn check_create(&self, path: &Path, display: &Path) -> Result<(), String> {
+        if self.is_dir(path) {
+            return Err(format!("{} is a directory", display.display()));
+        }
+        match path.parent() {
+            Some(parent) if !self.is_dir(parent) => Err(format!(
+                "{} does not exist",
+                Path::new(display).parent().unwrap_or(parent).display()
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn open(&mut self, path: PathBuf) -> Handle {
+        let handle = Handle(self.next_handle);
+        self.next_handle += 1;
+        self.streams.insert(handle, MemStream { path, pos: 0 });
+        handle
+    }
+    /// Read from an open file, or return `None` if the handle is not one
+    fn read(&mut self, handle: Handle, count: usize) -> Option<Result<Vec<u8>, String>> {
+        let stream = self.streams.get_mut(&handle)?;
+        let Some(contents) = self.files.get(&stream.path) else {
+            return Some(Err(format!("{} was deleted", stream.path.display())));
+        };
+        let start = stream.pos.min(contents.len());
+        let end = contents.len().min(start.saturating_add(count));
+        stream.pos = end;
+        Some(Ok(contents[start..end].to_vec()))
+    }
+}
+
+/// Normalize a path into a key of the file system
+fn mem_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+fn not_found(path: &Path) -> String {
+    format!("{} does not exist", path.display())
+}
+
+impl SysBackend for MemFsBackend {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, message: String, colored: String) {
+        self.inner.save_error_color(message, colored)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.inner.print_str_trace(s)
+    }
+    fn show(&self, value: Value) -> Result<(), String> {
+        self.inner.show(value)
+    }
+    fn show_colored(&self, value: Value) -> Result<(), String> {
+        self.inner.show_colored(value)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.inner.scan_line_stdin()
+    }
+    fn scan_stdin(&self, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.inner.scan_stdin(count)
+    }
+    fn scan_until_stdin(&self, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.inner.scan_until_stdin(delim)
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        self.inner.set_raw_mode(raw_mode)
+    }
+    fn get_raw_mode(&self) -> Result<bool, String> {
+        self.inner.get_raw_mode()
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        self.inner.var(name)
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.inner.term_size()
+    }
+    fn exit(&self, status: i32) -> Result<(), String> {
+        self.inner.exit(status)
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        let key = mem_path(Path::new(path));
+        let fs = self.fs.lock();
+        fs.files.contains_key(&key) || fs.is_dir(&key)
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let key = mem_path(Path::new(path));
+        let fs = self.fs.lock();
+        if !fs.is_dir(&key) {
+            return Err(if fs.files.contains_key(&key) {
+                format!("{path} is not a directory")
+            } else {
+                not_found(Path::new(path))
+            });
+        }
+        let mut names: Vec<_> = (fs.files.keys())
+            .chain(&fs.dirs)
+            .filter(|entry| entry.parent() == Some(&key))
+            .filter_map(|entry| entry.file_name())
+            .collect();
+        names.sort_unstable();
+        let paths = names.into_iter();
+        Ok(paths
+            .map(|name| Path::new(path).join(name).to_string_lossy().into_owned())
+            .collect())
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        let key = mem_path(Path::new(path));
+        let fs = self.fs.lock();
+        if fs.files.contains_key(&key) {
+            Ok(true)
+        } else if fs.is_dir(&key) {
+            Ok(false)
+        } else {
+            Err(not_found(Path::new(path)))
+        }
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        let key = mem_path(Path::new(path));
+        let fs = self.fs.lock();
+        let (size, is_dir) = if let Some(contents) = fs.files.get(&key) {
+            (contents.len() as u64, false)
+        } else if fs.is_dir(&key) {
+            (0, true)
+        } else {
+            return Err(not_found(Path::new(path)));
+        };
+        Ok(FileMetadata {
+            size,
+            modified: None,
+            is_dir,
+        })
+    }
+    fn canonicalize(&self, path: &str) -> Result<String, String> {
+        if !self.file_exists(path) {
+            return Err(not_found(Path::new(path)));
+        }
+        let path = Path::new("/").join(mem_path(Path::new(path)));
+        Ok(path.to_string_lossy().into_owned())
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        let key = mem_path(Path::new(path));
+        let mut fs = self.fs.lock();
+        fs.files.remove(&key);
+        if !key.as_os_str().is_empty() && fs.dirs.remove(&key) {
+            fs.files.retain(|file, _| !file.starts_with(&key));
+            fs.dirs.retain(|dir| !dir.starts_with(&key));
+        }
+        Ok(())
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.delete(path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        match self.fs.lock().read(handle, count) {
+            Some(res) => res,
+            None => self.inner.read(handle, count),
+        }
+    }
+    fn read_all(&self, handle: Handle) -> Result<Vec<u8>, String> {
+        match self.fs.lock().read(handle, usize::MAX) {
+            Some(res) => res,
+            None => self.inner.read_all(handle),
+        }
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        let mut fs = self.fs.lock();
+        let Some(stream) = fs.streams.get(&handle) else {
+            drop(fs);
+            return self.inner.read_until(handle, delim);
+        };
+        let rest = match fs.files.get(&stream.path) {
+            Some(contents) => &contents[stream.pos.min(contents.len())..],
+            None => &[],
+        };
+        let len = (rest.windows(delim.len().max(1)))
+            .position(|window| window == delim)
+            .map_or(rest.len(), |i| i + delim.len());
+        fs.read(handle, len).unwrap()
+    }
+    fn read_lines<'a>(&self, handle: Handle) -> Result<ReadLinesReturnFn<'a>, String> {
+        let Some(rest) = self.fs.lock().read(handle, usize::MAX) else {
+            return self.inner.read_lines(handle);
+        };
+        let rest = rest?;
+        Ok(Box::new(move |env: &mut Uiua, mut f: ReadLinesFn| {
+            for line in rest.lines() {
+                let line = line.map_err(|e| env.error(format!("Error reading line: {e}")))?;
+                f(line, env)?;
+            }
+            Ok(())
+        }))
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let MemFs { files, streams, .. } = &mut *fs;
+        let Some(stream) = streams.get_mut(&handle) else {
+            drop(fs);
+            return self.inner.write(handle, contents);
+        };
+        let file = (files.get_mut(&stream.path))
+            .ok_or_else(|| format!("{} was deleted", stream.path.display()))?;
+        let start = stream.pos.min(file.len());
+        let end = start + contents.len();
+        if end > file.len() {
+            file.resize(end, 0);
+        }
+        file[start..end].copy_from_slice(contents);
+        stream.pos = end;
+        Ok(())
+    }
+    fn create_file(&self, path: &Path) -> Result<Handle, String> {
+        let key = mem_path(path);
+        let mut fs = self.fs.lock();
+        fs.check_create(&key, path)?;
+        fs.files.insert(key.clone(), Vec::new());
+        Ok(fs.open(key))
+    }
+    fn open_file(&self, path: &Path, _write: bool) -> Result<Handle, String> {
+        let key = mem_path(path);
+        let mut fs = self.fs.lock();
+        if !fs.files.contains_key(&key) {
+            return Err(not_found(path));
+        }
+        Ok(fs.open(key))
+    }
+    fn make_dir(&self, path: &Path) -> Result<(), String> {
+        let key = mem_path(path);
+        let mut fs = self.fs.lock();
+        if let Some(file) = (key.ancestors()).find(|dir| fs.files.contains_key(*dir)) {
+            return Err(format!("{} is a file", file.display()));
+        }
+        fs.make_dir(&key);
+        Ok(())
+    }
+    fn file_read_all(&self, path: &Path) -> Result<Vec<u8>, String> {
+        let fs = self.fs.lock();
+        (fs.files.get(&mem_path(path)).cloned()).ok_or_else(|| not_found(path))
+    }
+    fn file_append(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        let key = mem_path(path);
+        let mut fs = self.fs.lock();
+        if !fs.files.contains_key(&key) {
+            fs.check_create(&key, path)?;
+        }
+        fs.files.entry(key).or_default().extend_from_slice(contents);
+        Ok(())
+    }
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.file_write_all(path, contents)
+    }
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        // There is nothing else to share the file system with
+        if self.fs.lock().streams.contains_key(&handle) {
+            Ok(())
+        } else {
+            Err("Invalid file handle".into())
+        }
+    }
+    fn file_write_all(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        let key = mem_path(path);
+        let mut fs = self.fs.lock();
+        fs.check_create(&key, path)?;
+        fs.files.insert(key, contents.into());
+        Ok(())
+    }
+    fn clipboard(&self) -> Result<String, String> {
+        self.inner.clipboard()
+    }
+    fn set_clipboard(&self, contents: &str) -> Result<(), String> {
+        self.inner.set_clipboard(contents)
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.inner.sleep(seconds)
+    }
+    fn allow_thread_spawning(&self) -> bool {
+        self.inner.allow_thread_spawning()
+    }
+    #[cfg(feature = "image")]
+    fn show_image(&self, image: DynamicImage, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_image(image, label)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_gif(gif_bytes, label)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.play_audio(wave_bytes, label)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.inner.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.inner.stream_audio(f)
+    }
+    fn stream_audio_buffers(&self, f: AudioBufferFn) -> Result<(), String> {
+        self.inner.stream_audio_buffers(f)
+    }
+    fn now(&self) -> f64 {
+        self.inner.now()
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.inner.tcp_listen(addr)
+    }
+    fn tls_listen(&self, addr: &str, settings: &TlsServerSettings) -> Result<Handle, String> {
+        self.inner.tls_listen(addr, settings)
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.inner.tcp_accept(handle)
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.inner.tcp_connect(addr)
+    }
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        self.inner.tls_connect(addr, settings)
+    }
+    fn tls_alpn(&self, handle: Handle) -> Result<Option<String>, String> {
+        self.inner.tls_alpn(handle)
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<SocketAddr, String> {
+        self.inner.tcp_addr(handle)
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.inner.tcp_set_non_blocking(handle, non_blocking)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_read_timeout(handle, timeout)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_write_timeout(handle, timeout)
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        if self.fs.lock().streams.remove(&handle).is_some() {
+            return Ok(());
+        }
+        self.inner.close(handle)
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.inner.invoke(path)
+    }
+    fn run_command_inherit(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<i32, String> {
+        self.inner.run_command_inherit(command, args, cmd_env)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<(i32, String, String), String> {
+        self.inner.run_command_capture(command, args, cmd_env)
+    }
+    fn run_command_stream(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<[Handle; 3], String> {
+        self.inner.run_command_stream(command, args, cmd_env)
+    }
+    fn current_dir(&self) -> Result<String, String> {
+        Ok(Path::new("/").to_string_lossy().into_owned())
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.inner.https_get(request, handle)
+    }
+    fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+        self.inner.smtp_send(server, email)
+    }
+    fn serial_ports(&self) -> Result<Vec<String>, String> {
+        self.inner.serial_ports()
+    }
+    fn serial_open(&self, path: &str, settings: &SerialSettings) -> Result<Handle, String> {
+        self.inner.serial_open(path, settings)
+    }
+    fn serial_read(&self, handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.inner.serial_read(handle, count)
+    }
+    fn serial_write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.inner.serial_write(handle, contents)
+    }
+    fn webcam_capture(&self, index: usize) -> Result<WebcamImage, String> {
+        self.inner.webcam_capture(index)
+    }
+    fn mouse(&self) -> Result<MouseState, String> {
+        self.inner.mouse()
+    }
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        self.inner.gamepad(index)
+    }
+    fn ffi(
+        &self,
+        file: &str,
+        result_ty: FfiType,
+        name: &str,
+        arg_tys: &[FfiType],
+        args: &[Value],
+    ) -> Result<Value, String> {
+        self.inner.ffi(file, result_ty, name, arg_tys, args)
+    }
+    fn mem_copy(&self, ty: FfiType, ptr: *const (), len: usize) -> Result<Value, String> {
+        self.inner.mem_copy(ty, ptr, len)
+    }
+    fn mem_free(&self, ptr: *const ()) -> Result<(), String> {
+        self.inner.mem_free(ptr)
+    }
+    fn load_git_module(&self, url: &str, target: GitTarget) -> Result<PathBuf, String> {
+        self.inner.load_git_module(url, target)
+    }
+    fn load_package(&self, name: &str) -> Result<PathBuf, String> {
+        self.inner.load_package(name)
+    }
+    fn timezone(&self) -> Result<f64, String> {
+        self.inner.timezone()
+    }
+    fn breakpoint(&self, env: &Uiua) -> Result<bool, String> {
+        self.inner.breakpoint(env)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn mem_fs_backend() {
+    use std::path::Path;
+
+    use crate::{Array, Boxed, MemFsBackend, Uiua, Value};
+    let backend = MemFsBackend::new()
+        .with_file("b.txt", "bee")
+        .with_file("dir/a.txt", "ay")
+        .with_dir("empty");
+    let mut env = Uiua::with_backend(backend);
+    let sep = std::path::MAIN_SEPARATOR;
+    let boxed =
+        |items: &[&str]| -> Value { Array::<Boxed>::from_iter(items.iter().copied()).into() };
+    let code = r#"
+        &cl &w "hello!" . &fc "dir/c.txt"
+        &fde "b.txt"
+        &fmd "empty/sub"
+    "#;
+    env.run_str(code).unwrap();
+    let cases: [(&str, Value); 7] = [
+        (r#"&fld ".""#, boxed(&["./dir", "./empty"])),
+        (
+            r#"&fld "dir""#,
+            boxed(&[&format!("dir{sep}a.txt"), &format!("dir{sep}c.txt")]),
+        ),
+        (r#"&fras "dir/c.txt""#, "hello!".into()),
+        (r#"&fe "b.txt""#, false.into()),
+        (r#"&fif "empty/sub""#, false.into()),
+        (r#"⍜&fo(&ru "l") "dir/c.txt""#, "hel".into()),
+        ("&cd \"dir\"\n&fras \"a.txt\"", "ay".into()),
+    ];
+    for (code, expected) in cases {
+        env.run_str(code).unwrap_or_else(|e| panic!("{code}: {e}"));
+        assert_eq!(env.pop("result").unwrap(), expected, "{code}");
+    }
+    assert!(env.run_str(r#"&fwa "missing/x.txt" "x""#).is_err());
+    assert!(env.run_str(r#"&fras "b.txt""#).is_err());
+    let backend = env.downcast_backend::<MemFsBackend>().unwrap();
+    assert_eq!(backend.file("dir/c.txt").unwrap(), b"hello!");
+    let files = [Path::new("dir/a.txt"), Path::new("dir/c.txt")];
+    assert_eq!(backend.files(), files);
+}
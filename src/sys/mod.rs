@@ -1,12 +1,18 @@
+mod args;
+mod bundle;
+mod layer;
+mod memfs;
 #[cfg(feature = "native_sys")]
 pub(crate) mod native;
+mod replay;
+mod suspend;
 
 use std::{
     any::Any,
     fmt,
     mem::take,
     net::SocketAddr,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf, MAIN_SEPARATOR},
     sync::{Arc, OnceLock},
     time::Duration,
 };
@@ -19,14 +25,20 @@ use parking_lot::Mutex;
 use serde::*;
 use time::UtcOffset;
 
+pub use self::bundle::BundleSys;
+pub use self::layer::{LayeredSys, SysCall, SysCallKind, SysLayer};
+pub use self::memfs::MemFsBackend;
 #[cfg(feature = "native_sys")]
 pub use self::native::*;
+pub use self::replay::{Recording, ReplaySys};
+pub use self::suspend::Suspendable;
 use crate::{
-    algorithm::{multi_output, validate_size},
+    algorithm::{encode, multi_output, validate_size},
     cowslice::cowslice,
     get_ops,
     primitive::PrimDoc,
-    Array, Boxed, FfiType, Ops, Primitive, Purity, Uiua, UiuaErrorKind, UiuaResult, Value,
+    Array, Boxed, FfiType, Node, Ops, Primitive, Purity, SigNode, Uiua, UiuaErrorKind, UiuaResult,
+    Value,
 };
 
 /// The text of Uiua's example module
@@ -220,6 +232,38 @@ sys_op! {
     ///
     /// See also: [&p], [&epf]
     (1(0), PrintErr, StdIO, "&ep", "print error with newline", Mutating),
+    /// Log a debug message
+    ///
+    /// [&logd], [&logi], [&logw], and [&loge] log messages at increasing levels of severity.
+    /// Each one prints its argument to stderr along with a timestamp and the level.
+    /// ex: # Experimental!
+    ///   : &logd "Connecting"
+    ///   : &logi $"Listening on port _" 8080
+    ///   : &logw "Disk space is low"
+    ///   : &loge "Connection lost"
+    ///
+    /// The `UIUA_LOG` environment variable sets the lowest level that is logged.
+    /// It can be `debug`, `info`, `warn`, `error`, or `off`. The default is `info`, so [&logd] messages are not shown unless it is set to `debug`.
+    ///
+    /// If the `UIUA_LOG_FILE` environment variable is set to a path, each logged message is also appended to that file as a line of JSON.
+    /// Each line has the fields `time`, `level`, `message`, and `span`.
+    ///
+    /// See also: [&ep]
+    (1(0), LogDebug, StdIO, "&logd", "log debug", Mutating),
+    /// Log an info message
+    ///
+    /// See [&logd] for how logging is configured.
+    (1(0), LogInfo, StdIO, "&logi", "log info", Mutating),
+    /// Log a warning message
+    ///
+    /// See [&logd] for how logging is configured.
+    (1(0), LogWarn, StdIO, "&logw", "log warning", Mutating),
+    /// Log an error message
+    ///
+    /// This does not stop the program. To throw an error, use [assert].
+    ///
+    /// See [&logd] for how logging is configured.
+    (1(0), LogError, StdIO, "&loge", "log error", Mutating),
     /// Read a line from stdin
     ///
     /// The normal output is a string.
@@ -249,11 +293,46 @@ sys_op! {
     /// The first element will always be the name of your script
     // Doesn't actually mutate, but this is necessary for the LSP
     (0, Args, Env, "&args", "arguments", Mutating),
+    /// Parse the command line arguments according to a spec
+    ///
+    /// Expects a list of [box]ed strings, one for each argument the program accepts.
+    /// Returns a map array from argument names to [box]ed values.
+    /// - `-v --verbose` is a flag. Its value is the number of times it was given.
+    /// - `-o --output FILE` is an option that takes a value. It is only in the map if it was given.
+    /// - `FILE` is a required positional argument. `[FILE]` is an optional one.
+    /// - `FILES...` collects the remaining positional arguments into a list.
+    ///
+    /// Flags and options are keyed by their long name, or their short name if they don't have one. Positional arguments are keyed by their lowercase name.
+    /// Anything after ` # ` in a spec entry is a description for the help text.
+    ///
+    /// Short flags can be combined, as in `-abc`. Values can be written as `--output=file`, `--output file`, `-ofile`, or `-o file`. Arguments after `--` are always positional.
+    ///
+    /// If `--help` or `-h` is given and not in the spec, help text generated from the spec is printed, and the program exits.
+    /// Invalid arguments throw an error with the usage.
+    /// ex: &pargs {"-v --verbose # Print more" "-o --output FILE" "[FILES...]"}
+    (1, ParseArgs, Env, "&pargs", "parse arguments", Mutating),
     /// Get the value of an environment variable
     ///
     /// Expects a string and returns a string.
     /// If the environment variable does not exist, an error is thrown.
+    /// Variables set with [&setvar] or unset with [&unsetvar] are taken into account.
     (1, Var, Env, "&var", "environment variable"),
+    /// Set an environment variable
+    ///
+    /// Expects a name and a value.
+    /// The variable is set for this program and the commands it runs with [&runi], [&runc], or [&runs]. The environment of the interpreter's process is not changed.
+    /// ex: &setvar "GREETING" "Hello!"
+    ///   : &var "GREETING"
+    ///
+    /// See also: [&unsetvar]
+    (2(0), SetVar, Env, "&setvar", "set environment variable", Mutating),
+    /// Unset an environment variable
+    ///
+    /// Like [&setvar], this only affects this program and the commands it runs.
+    /// ex: &setvar "GREETING" "Hello!"
+    ///   : &unsetvar "GREETING"
+    ///   : ⍣(&var "GREETING"|$"Error: _")
+    (1(0), UnsetVar, Env, "&unsetvar", "unset environment variable", Mutating),
     /// Run a command and wait for it to finish
     ///
     /// Standard IO will be inherited. Returns the exit code of the command.
@@ -276,7 +355,20 @@ sys_op! {
     /// [under][&runs] calls [&cl] on all 3 streams automatically.
     (1(3), RunStream, Command, "&runs", "run command stream", Mutating),
     /// Change the current directory
+    ///
+    /// Relative paths used by other system functions are resolved against the new directory, and commands run in it.
+    /// Only the directory of this program is changed, not that of the interpreter's process.
+    ///
+    /// Paths given to system functions can start with `~` for the home directory, and can include environment variables like `$HOME` or `${HOME}`. Use `$$` for a literal `$`.
+    ///
+    /// See also: [&cwd]
     (1(0), ChangeDirectory, Filesystem, "&cd", "change directory", Mutating),
+    /// Get the current directory
+    ///
+    /// ex: &cwd
+    ///
+    /// See also: [&cd]
+    (0, WorkingDirectory, Filesystem, "&cwd", "current directory"),
     /// Get the contents of the clipboard
     ///
     /// Returns a string of the clipboard's contents.
@@ -290,6 +382,30 @@ sys_op! {
     /// On the web, this example will hang for 1 second.
     /// ex: ⚂ &sl 1
     (1(0), Sleep, Misc, "&sl", "sleep", Mutating),
+    /// Set how numbers are formatted
+    ///
+    /// Expects a precision, a scientific notation threshold, and a thousands separator.
+    /// The precision is the maximum number of digits shown after the decimal point.
+    /// Numbers with a magnitude of at least `10` to the power of the threshold, or less than its reciprocal, are shown in scientific notation.
+    /// Use [infinity] for either to disable it.
+    /// The separator is a string of at most one character that is placed between groups of thousands.
+    /// ex: &nf 3 ∞ "" [1/3 2/3 1.5]
+    /// ex: &nf ∞ 6 "," [12345 1e9 0.5 1e¯8]
+    /// This affects [&s] and how values are shown at the end of a program, but not [&p].
+    /// It applies for the rest of the program, or until [&nf] is called again.
+    /// ex: &nf 2 ∞ "" &s 1/7
+    ///   : &nf 4 ∞ "" &s 1/7
+    (3(0), NumberFormat, Misc, "&nf", "number format", Mutating),
+    /// Set how many rows of large arrays are shown
+    ///
+    /// Expects a number of rows to show at the start and end of each axis. It must be at least 1.
+    /// The rows in between are replaced with a marker that says how many were hidden.
+    /// Use [infinity] to show all rows.
+    /// ex: &elide 3 ⇡1000
+    /// ex: &elide 2 ↯1000_3⇡3000
+    /// Without [&elide], very large arrays are summarized instead.
+    /// Like [&nf], this affects [&s] and how values are shown at the end of a program.
+    (1(0), Elide, Misc, "&elide", "elide rows", Mutating),
     /// Read characters formed by at most n bytes from a stream
     ///
     /// Expects a count and a stream handle.
@@ -328,6 +444,28 @@ sys_op! {
     /// Additional arguments to the function will be bellow the line.
     /// Outputs in excess of the number of accumulators will be collected into arrays.
     (1[1], ReadLines, Stream, "&rl", "read lines", Mutating),
+    /// Call a function on each line of a stream
+    ///
+    /// [&lines] works like [&rl], but it reads the stream incrementally through the generic stream interface, so it also works on stdin.
+    /// Only one line is held in memory at a time.
+    /// Trailing `\n` or `\r\n` is removed from each line.
+    /// Outputs in excess of the number of accumulators will be collected into arrays, as with [rows].
+    /// ex: &lines(⧻) &fo "example.txt"
+    (1[1], Lines, Stream, "&lines", "stream lines", Mutating),
+    /// Call a function on each chunk of bytes in a stream
+    ///
+    /// Expects a chunk size and a stream handle.
+    /// Each chunk will be a byte array with the given length, except possibly the last one, which may be shorter.
+    /// Outputs in excess of the number of accumulators will be collected into arrays, as with [rows].
+    /// ex: &chunks(/+)4 &fo "example.txt"
+    (2[1], Chunks, Stream, "&chunks", "stream chunks", Mutating),
+    /// Call a function on each line of a stream while copying the stream to another
+    ///
+    /// Expects a destination handle and a source handle.
+    /// Each line is written to the destination, including its line ending, before the function is called on it.
+    /// This is useful for logging or saving a stream while it is being processed.
+    /// ex: &teestream(⧻) 1 &fo "example.txt"
+    (2[1], TeeStream, Stream, "&teestream", "tee stream", Mutating),
     /// Write an array to a stream
     ///
     /// If the stream is a file, the file may not be written to until it is closed with [&cl].
@@ -340,8 +478,49 @@ sys_op! {
     (1(1), Invoke, Command, "&invk", "invoke", Mutating),
     /// Close a stream by its handle
     ///
-    /// This will close files, tcp listeners, and tcp sockets.
+    /// This will close files, tcp listeners, tcp sockets, and serial ports.
     (1(0), Close, Stream, "&cl", "close handle", Mutating),
+    /// List the available serial ports
+    ///
+    /// Returns a list of boxed paths that can be passed to [&serialopen].
+    /// ex: # Experimental!
+    ///   : &serialports
+    (0, SerialPorts, Stream, "&serialports", "serial - list ports", Mutating),
+    /// Open a serial port and return a handle to it
+    ///
+    /// The first argument is the path of the port. The second is either a baud rate or a map array of settings.
+    /// - `baud` is the baud rate. The default is `9600`.
+    /// - `databits` is the number of data bits, from `5` to `8`. The default is `8`.
+    /// - `parity` is `"none"`, `"odd"`, or `"even"`. The default is `"none"`.
+    /// - `stopbits` is `1` or `2`. The default is `1`.
+    /// - `flowcontrol` is `"none"`, `"software"`, or `"hardware"`. The default is `"none"`.
+    /// - `timeout` is how many seconds [&serialread] waits for data. The default is `1`. [infinity] waits forever.
+    /// ex: # Experimental!
+    ///   : &serialopen "/dev/ttyUSB0" 115200
+    /// ex: # Experimental!
+    ///   : &serialopen "/dev/ttyACM0" map {"baud" "parity" "timeout"} {57600 "even" 0.5}
+    ///
+    /// The port can also be read from with [&rs], [&rb], [&ru], or [&rl], and written to with [&w].
+    /// [under][&serialopen] calls [&cl] automatically.
+    (2, SerialOpen, Stream, "&serialopen", "serial - open", Mutating),
+    /// Read at most n bytes from a serial port
+    ///
+    /// Expects a count and a serial port handle.
+    /// Unlike [&rb], this returns early if no data arrives for the port's timeout, so it may return fewer bytes, or none at all.
+    /// Using [infinity] as the count will read until no data arrives for the timeout.
+    /// ex: # Experimental!
+    ///   : ⍜(&serialopen "/dev/ttyUSB0" 9600|&serialread ∞)
+    /// This makes it easy to log data from a sensor.
+    /// ex: # Experimental!
+    ///   : ⍜(&serialopen "/dev/ttyACM0" 9600|⍥(&p °utf₈ &serialread 64.)10)
+    (2, SerialRead, Stream, "&serialread", "serial - read", Mutating),
+    /// Write bytes or a string to a serial port
+    ///
+    /// Expects the data and a serial port handle.
+    /// Unlike [&w], this waits until all of the data has been transmitted.
+    /// ex: # Experimental!
+    ///   : ⍜(&serialopen "/dev/ttyUSB0" 9600|&serialwrite "on\n")
+    (2(0), SerialWrite, Stream, "&serialwrite", "serial - write", Mutating),
     /// Open a file and return a handle to it
     ///
     /// ex: &fo "example.txt"
@@ -358,6 +537,14 @@ sys_op! {
     /// In some cases, the file may not be actually written to until it is closed with [&cl].
     /// [under][&fc] calls [&cl] automatically.
     (1, FCreate, Filesystem, "&fc", "file - create", Mutating),
+    /// Lock a file by its handle
+    ///
+    /// The lock is exclusive and advisory. It only prevents other programs, or other handles in this one, from locking the same file.
+    /// If the file is already locked, [&flock] waits until it is unlocked.
+    /// The lock is released when the handle is closed with [&cl].
+    /// This is useful for state files that may be updated by several runs of a program at once.
+    /// ex: ⍜&fo(&flock.) "example.txt"
+    (1(0), FLock, Filesystem, "&flock", "file - lock", Mutating),
     /// Create a directory
     ///
     /// ex: &fmd "path/to/dir"
@@ -422,6 +609,78 @@ sys_op! {
     ///   : &fwa Path +@A⇡26
     ///   : &fras Path
     (2(0), FWriteAll, Filesystem, "&fwa", "file - write all", Mutating),
+    /// Atomically replace the contents of a file
+    ///
+    /// Expects a path and a rank-`1` array of either numbers or characters, like [&fwa].
+    /// The contents are first written to a temporary file in the same directory, which is then renamed to the path.
+    /// Anything reading the file will see either the old contents or the new ones, never a partial write.
+    /// ex: &fwriteatomic "state.txt" "count: 5"
+    ///   : &fras "state.txt"
+    (2(0), FWriteAtomic, Filesystem, "&fwriteatomic", "file - write atomic", Mutating),
+    /// Join path components with the platform's separator
+    ///
+    /// Expects a list of [box]ed strings.
+    /// A component that is an absolute path replaces everything before it.
+    /// ex: &pathjoin {"dir" "sub" "file.txt"}
+    /// ex: &pathjoin {"dir" "/root" "file.txt"}
+    /// [un][&pathjoin] is [&pathsplit].
+    /// ex: °&pathjoin "dir/sub/file.txt"
+    (1, PathJoin, Filesystem, "&pathjoin", "path - join"),
+    /// Split a path into its components
+    ///
+    /// On Windows, both `/` and `\` are separators. On other systems, only `/` is.
+    /// The root of an absolute path, like `/` or `C:\`, is its own component.
+    /// Repeated separators and `.` components are removed.
+    /// ex: &pathsplit "dir/sub//./file.txt"
+    /// ex: &pathsplit "/usr/bin"
+    /// [un][&pathsplit] is [&pathjoin].
+    (1, PathSplit, Filesystem, "&pathsplit", "path - split"),
+    /// Get the extension of a path
+    ///
+    /// Returns an empty string if the path has no extension.
+    /// ex: &ext "dir/archive.tar.gz"
+    /// ex: &ext "dir.d/file"
+    /// ex: &ext ".bashrc"
+    (1, Ext, Filesystem, "&ext", "path - extension"),
+    /// Get the absolute path of a file or directory with all symbolic links resolved
+    ///
+    /// The path must exist.
+    /// ex: &canonicalize "."
+    (1, Canonicalize, Filesystem, "&canonicalize", "path - canonicalize"),
+    /// Check if a path matches a glob pattern
+    ///
+    /// Expects a pattern and a path.
+    /// - `*` matches any number of characters
+    /// - `?` matches any one character
+    /// - `[abc]` matches any of the characters in the brackets. `[a-z]` matches a range of characters, and `[!abc]` matches any character not in the brackets.
+    /// - `**` as a whole component matches any number of components
+    /// Wildcards never match path separators, and only match a leading `.` if the pattern has one.
+    /// On Windows, matching ignores case.
+    /// ex: &globmatch "*.ua" "main.ua"
+    /// ex: &globmatch "src/**/*.rs" "src/sys/mod.rs"
+    /// ex: ▽⊸≡◇(&globmatch "*.txt") {"a.txt" "b.ua" "c.txt"}
+    (2, GlobMatch, Filesystem, "&globmatch", "path - glob match"),
+    /// Find the paths that match a glob pattern
+    ///
+    /// The pattern syntax is the same as for [&globmatch].
+    /// Returns a sorted list of [box]ed paths.
+    /// ex: &glob "*.ua"
+    /// ex: &glob "src/**/*.rs"
+    (1, Glob, Filesystem, "&glob", "path - glob"),
+    /// Call a function on each entry in a directory tree
+    ///
+    /// [&fwalk] visits every file and directory under the given root, except the root itself.
+    /// Entries are visited depth-first in order of name, and directories are only listed once they are reached, so the whole tree is never held in memory.
+    /// Each entry is a map array with the keys `"path"`, `"size"`, `"mtime"`, and `"isdir"`.
+    /// `"size"` is the size in bytes, which is always `0` for directories.
+    /// `"mtime"` is the modification time in seconds since the Unix epoch, or NaN if it is not available.
+    /// For each entry, it will be pushed onto the stack and the function will be called.
+    /// Additional arguments to the function will be bellow the entry.
+    /// Outputs in excess of the number of accumulators will be collected into arrays, as with [rows].
+    /// A function with no outputs can be used to only act on some entries.
+    /// ex: &fwalk(□°□get "path") "."
+    /// ex: &fwalk(+°□get "size") "." 0
+    (1[1], FWalk, Filesystem, "&fwalk", "file - walk", Mutating),
     /// Show an image
     ///
     /// How the image is shown depends on the system backend.
@@ -489,6 +748,17 @@ sys_op! {
     /// On the web, this will simply use the function to generate a fixed amount of audio.
     /// How long the audio is can be configured in the editor settings.
     (0(0)[1], AudioStream, Media, "&ast", "audio - stream", Mutating),
+    /// Stream audio from a function called for each buffer
+    ///
+    /// Expects a function that takes the index of the first sample in a buffer and the number of samples in the buffer, and returns that many samples.
+    /// Like with [&ast], the samples must either be a rank 1 array or a rank 2 array with 2nd axis length 2.
+    /// The buffers are small, so the function is called often and changes to the audio are heard with low latency.
+    /// ex: &astream(×0.3∿×τ×220÷&asr+⊙⇡)
+    /// If the function returns fewer samples than requested, the stream ends after playing them.
+    /// ex: &astream(×0.3∿×τ×440÷&asr▽⊸<×2&asr+⊙⇡)
+    /// On the web, this will simply use the function to generate a fixed amount of audio.
+    /// How long the audio is can be configured in the editor settings.
+    (0(0)[1], AudioCallback, Media, "&astream", "audio - stream buffers", Mutating),
     /// Create a TCP listener and bind it to an address
     ///
     /// Use [&tcpa] on the returned handle to accept connections.
@@ -497,13 +767,22 @@ sys_op! {
     (1, TcpListen, Tcp, "&tcpl", "tcp - listen", Mutating),
     /// Create a TLS listener and bind it to an address
     ///
+    /// The first argument is the address. The second is a map array of settings.
+    /// - `cert` and `key` are the PEM-encoded certificate chain and private key of the server
+    /// - `sni` is a map array from host names to boxed `{cert key}` pairs. If a client asks for one of the host names, the matching certificate is used instead.
+    /// - `alpn` is a protocol name or a list of boxed protocol names the server supports, in order of preference
+    /// - `clientroots` is PEM-encoded root certificates. If it is given, clients must authenticate with a certificate signed by one of them.
+    /// Either `cert` and `key` or `sni` must be given.
+    /// ex: &tlsl "0.0.0.0:8443" map {"cert" "key"} {&frab "cert.pem" &frab "key.pem"}
+    ///
     /// Use [&tcpa] on the returned handle to accept connections.
     ///
-    /// See also: [&tcpl]
-    (1, TlsListen, Tcp, "&tlsl", "tls - listen", Mutating),
+    /// See also: [&tcpl], [&tlsc]
+    (2, TlsListen, Tcp, "&tlsl", "tls - listen", Mutating),
     /// Accept a connection with a TCP or TLS listener
     ///
     /// Returns a stream handle
+    /// For a TLS listener, the TLS handshake is completed before returning.
     /// [under][&tcpl] calls [&cl] automatically.
     (1, TcpAccept, Tcp, "&tcpa", "tcp - accept", Mutating),
     /// Create a TCP socket and connect it to an address
@@ -532,8 +811,26 @@ sys_op! {
     ///   : $
     ///   : ⍜(&tlsc "example.com:443"|&rs∞⊸&w:)
     ///
-    /// See also: [&tcpc]
+    /// Instead of an address, the argument can be a map array of settings.
+    /// - `address` is the address to connect to, and is required
+    /// - `servername` is the name sent with SNI and checked against the server's certificate. By default, it is the host part of the address.
+    /// - `alpn` is a protocol name or a list of boxed protocol names to offer, in order of preference
+    /// - `roots` is PEM-encoded root certificates to trust instead of the built-in web roots
+    /// - `cert` and `key` are a PEM-encoded certificate chain and private key to authenticate with
+    /// ex: &tlsc map {"address" "alpn"} {"example.com:443" {"h2" "http/1.1"}}
+    /// ex: &tlsc map {"address" "servername" "roots"} {"10.0.0.5:8443" "internal.example.com" &frab "ca.pem"}
+    ///
+    /// The TLS handshake is completed before returning, so certificate problems are reported immediately.
+    ///
+    /// See also: [&tcpc], [&tlsalpn]
     (1, TlsConnect, Tcp, "&tlsc", "tls - connect", Mutating),
+    /// Get the protocol negotiated with ALPN on a TLS socket
+    ///
+    /// Returns an empty string if no protocol was negotiated.
+    /// ex: &tlsalpn &tlsc map {"address" "alpn"} {"example.com:443" {"h2" "http/1.1"}}
+    ///
+    /// See also: [&tlsc], [&tlsl]
+    (1, TlsAlpn, Tcp, "&tlsalpn", "tls - negotiated protocol", Mutating),
     /// Set a TCP socket to non-blocking mode
     (1, TcpSetNonBlocking, Tcp, "&tcpsnb", "tcp - set non-blocking", Mutating),
     /// Set the read timeout of a TCP socket in seconds
@@ -563,12 +860,62 @@ sys_op! {
     /// - The HTTP version
     /// - The `Host` header (if not defined)
     (2, HttpsWrite, Tcp, "&httpsw", "https - Make an HTTP(S) request", Mutating),
+    /// Send an email over SMTP
+    ///
+    /// The first argument is a map array of server settings.
+    /// - `host` is the address of the server, and is required
+    /// - `port` is the port to connect to
+    /// - `username` and `password` are used to log in, if given
+    /// - `security` is `"tls"` to connect with TLS, `"starttls"` to upgrade the connection to TLS after connecting, or `"none"`
+    /// By default, port 465 uses `"tls"` and other ports use `"starttls"`. Without a port, `"tls"` uses 465, `"starttls"` uses 587, and `"none"` uses 25.
+    ///
+    /// The second argument is a map array describing the message.
+    /// - `from` is the sender's address, and is required
+    /// - `to`, `cc`, and `bcc` are a recipient address or a list of boxed addresses. There must be at least one recipient.
+    /// - `subject` and `body` are strings
+    /// - `attachments` is a map array from file names to byte arrays or strings
+    /// Addresses can include a name, as in `"Uiua <uiua@example.com>"`.
+    ///
+    /// ex: # Experimental!
+    ///   : Server ← map {"host" "username" "password"} {"smtp.example.com" "me@example.com" "hunter2"}
+    ///   : Message ← map {"from" "to" "subject" "body"} {"me@example.com" "you@example.com" "Disk" "The disk is full"}
+    ///   : &smtpsend Server Message
+    /// ex: # Experimental!
+    ///   : Log ← &frab "log.txt"
+    ///   : Message ← map {"from" "to" "subject" "attachments"} {"me@example.com" {"you@example.com" "them@example.com"} "Log" map {"log.txt"} {Log}}
+    ///   : &smtpsend map {"host" "port"} {"localhost" 25} Message
+    (2(0), SmtpSend, Tcp, "&smtpsend", "smtp - send email", Mutating),
     /// Capture an image from a webcam
     ///
     /// Takes the index of the webcam to capture from.
     ///
     /// Returnes a rank-3 numeric array representing the image.
     (1, WebcamCapture, Misc, "&camcap", "webcam - capture", Mutating),
+    /// Get the state of the mouse
+    ///
+    /// Returns a map array from boxed string keys to numbers.
+    /// - `x` and `y` are the position of the mouse in the window
+    /// - `left`, `right`, and `middle` are `1` if that button is down and `0` otherwise
+    /// - `scroll` is how far the mouse wheel has scrolled down since the last poll
+    ///
+    /// Natively, this requires the `--window` flag.
+    ///
+    /// See also: [&gamepad]
+    (0, Mouse, Misc, "&mouse", "mouse", Mutating),
+    /// Get the state of a gamepad
+    ///
+    /// Takes the index of the gamepad to poll.
+    ///
+    /// Returns a map array from boxed string keys to numbers, using the standard gamepad layout.
+    /// - `leftx`, `lefty`, `rightx`, and `righty` are stick axes between `¯1` and `1`, with positive y down
+    /// - `south`, `east`, `west`, `north`, `leftbumper`, `rightbumper`, `lefttrigger`, `righttrigger`, `select`, `start`, `leftstick`, `rightstick`, `up`, `down`, `left`, `right`, and `mode` are buttons between `0` and `1`
+    ///
+    /// If no gamepad is connected at the index, the map is empty.
+    ///
+    /// Natively, this requires the `gamepad` feature.
+    ///
+    /// See also: [&mouse]
+    (1, Gamepad, Misc, "&gamepad", "gamepad", Mutating),
     /// Call a foreign function interface
     ///
     /// *Warning ⚠️: Using FFI is deeply unsafe. Calling a function incorrectly is undefined behavior.*
@@ -706,6 +1053,13 @@ impl Handle {
 }
 
 impl Value {
+    /// Get the kind of handle this value is, if it is one
+    pub fn handle_kind(&self) -> Option<&HandleKind> {
+        match self {
+            Value::Box(b) => b.as_scalar()?.0.meta().handle_kind.as_ref(),
+            value => value.meta().handle_kind.as_ref(),
+        }
+    }
     /// Attempt to convert the array to systme handle
     pub fn as_handle(&self, env: &Uiua, mut expected: &'static str) -> UiuaResult<Handle> {
         if expected.is_empty() {
@@ -730,6 +1084,10 @@ pub type ReadLinesFn<'a> = Box<dyn FnMut(String, &mut Uiua) -> UiuaResult + Send
 pub type ReadLinesReturnFn<'a> = Box<dyn FnMut(&mut Uiua, ReadLinesFn) -> UiuaResult + Send + 'a>;
 /// The function type passed to `&ast`
 pub type AudioStreamFn = Box<dyn FnMut(&[f64]) -> UiuaResult<Vec<[f64; 2]>> + Send>;
+/// The function type passed to `&astream`
+///
+/// It is called with the index of the first sample in a buffer and the number of samples in the buffer
+pub type AudioBufferFn = Box<dyn FnMut(u64, usize) -> UiuaResult<Vec<[f64; 2]>> + Send>;
 
 /// The kind of a handle
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -740,9 +1098,12 @@ pub enum HandleKind {
     TlsListener(SocketAddr),
     TcpSocket(SocketAddr),
     TlsSocket(SocketAddr),
+    SerialPort(String),
     ChildStdin(String),
     ChildStdout(String),
     ChildStderr(String),
+    Channel,
+    Cell,
 }
 
 impl fmt::Display for HandleKind {
@@ -753,18 +1114,234 @@ impl fmt::Display for HandleKind {
             Self::TlsListener(addr) => write!(f, "tls listener {}", addr),
             Self::TcpSocket(addr) => write!(f, "tcp socket {}", addr),
             Self::TlsSocket(addr) => write!(f, "tls socket {}", addr),
+            Self::SerialPort(path) => write!(f, "serial port {path}"),
             Self::ChildStdin(com) => write!(f, "stdin {com}"),
             Self::ChildStdout(com) => write!(f, "stdout {com}"),
             Self::ChildStderr(com) => write!(f, "stderr {com}"),
+            Self::Channel => write!(f, "channel"),
+            Self::Cell => write!(f, "cell"),
         }
     }
 }
 
+/// The settings for a TLS client connection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsClientSettings {
+    /// The name used for SNI and to verify the server's certificate
+    ///
+    /// If this is `None`, the host part of the address is used
+    pub server_name: Option<String>,
+    /// The protocols to offer with ALPN, in order of preference
+    pub alpn: Vec<String>,
+    /// PEM-encoded root certificates to trust instead of the built-in ones
+    pub roots: Option<Vec<u8>>,
+    /// A PEM-encoded certificate chain and private key to authenticate with
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// The settings for a TLS listener
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsServerSettings {
+    /// The PEM-encoded certificate chain and private key to use by default
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Host names and the PEM-encoded certificate chains and private keys to use for them
+    pub sni: Vec<(String, Vec<u8>, Vec<u8>)>,
+    /// The protocols to accept with ALPN, in order of preference
+    pub alpn: Vec<String>,
+    /// PEM-encoded root certificates that client certificates must be signed by
+    ///
+    /// If this is `None`, clients do not authenticate
+    pub client_roots: Option<Vec<u8>>,
+}
+
+/// The settings for connecting to an SMTP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpServer {
+    /// The address of the server
+    pub host: String,
+    /// The port to connect to
+    pub port: u16,
+    /// The username to log in with
+    pub username: Option<String>,
+    /// The password to log in with
+    pub password: Option<String>,
+    /// How the connection is secured
+    pub security: SmtpSecurity,
+}
+
+/// How a connection to an SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Connect with TLS
+    Tls,
+    /// Connect without TLS, then upgrade with `STARTTLS`
+    StartTls,
+    /// Do not use TLS
+    None,
+}
+
+/// An email ready to be sent over SMTP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    /// The address of the sender
+    pub from: String,
+    /// The addresses of all recipients, including `Bcc` ones
+    pub recipients: Vec<String>,
+    /// The message, including headers, with CRLF line endings
+    pub message: String,
+}
+
+/// The settings for a serial port
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerialSettings {
+    /// The baud rate
+    pub baud: u32,
+    /// The number of data bits, from 5 to 8
+    pub data_bits: u8,
+    /// The parity checking mode
+    pub parity: SerialParity,
+    /// The number of stop bits, 1 or 2
+    pub stop_bits: u8,
+    /// The flow control mode
+    pub flow_control: SerialFlowControl,
+    /// How long [`SysBackend::serial_read`] waits for data
+    ///
+    /// If this is `None`, it waits forever
+    pub timeout: Option<Duration>,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self {
+            baud: 9600,
+            data_bits: 8,
+            parity: SerialParity::None,
+            stop_bits: 1,
+            flow_control: SerialFlowControl::None,
+            timeout: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// The parity checking mode of a serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum SerialParity {
+    None,
+    Odd,
+    Even,
+}
+
+/// The flow control mode of a serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialFlowControl {
+    /// No flow control
+    None,
+    /// XON/XOFF flow control
+    Software,
+    /// RTS/CTS flow control
+    Hardware,
+}
+
+/// Information about a file or directory
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// The size in bytes
+    pub size: u64,
+    /// The last modification time in seconds since the Unix epoch, if available
+    pub modified: Option<f64>,
+    /// Whether the path is a directory
+    pub is_dir: bool,
+}
+
+/// The state of the mouse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MouseState {
+    /// The position of the mouse
+    pub pos: [f64; 2],
+    /// Whether the left, right, and middle buttons are down
+    pub buttons: [bool; 3],
+    /// How far the mouse wheel has scrolled down since the last poll
+    pub scroll: f64,
+}
+
+impl MouseState {
+    /// Get the names and values of the inputs
+    pub fn inputs(&self) -> impl Iterator<Item = (&'static str, f64)> {
+        let [left, right, middle] = self.buttons.map(|down| down as u8 as f64);
+        [
+            ("x", self.pos[0]),
+            ("y", self.pos[1]),
+            ("left", left),
+            ("right", right),
+            ("middle", middle),
+            ("scroll", self.scroll),
+        ]
+        .into_iter()
+    }
+}
+
+/// The state of a gamepad, using the standard gamepad layout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GamepadState {
+    /// The stick axes between `-1` and `1`, in the order of [`GamepadState::AXES`]
+    ///
+    /// Positive y is down.
+    pub axes: [f64; 4],
+    /// The buttons between `0` and `1`, in the order of [`GamepadState::BUTTONS`]
+    pub buttons: [f64; 17],
+}
+
+impl GamepadState {
+    /// The names of the stick axes
+    pub const AXES: [&'static str; 4] = ["leftx", "lefty", "rightx", "righty"];
+    /// The names of the buttons
+    pub const BUTTONS: [&'static str; 17] = [
+        "south",
+        "east",
+        "west",
+        "north",
+        "leftbumper",
+        "rightbumper",
+        "lefttrigger",
+        "righttrigger",
+        "select",
+        "start",
+        "leftstick",
+        "rightstick",
+        "up",
+        "down",
+        "left",
+        "right",
+        "mode",
+    ];
+    /// Get the names and values of the inputs
+    pub fn inputs(&self) -> impl Iterator<Item = (&'static str, f64)> {
+        (Self::AXES.into_iter().zip(self.axes)).chain(Self::BUTTONS.into_iter().zip(self.buttons))
+    }
+}
+
+/// The environment to run a command in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandEnv {
+    /// The directory to run the command in
+    ///
+    /// If this is `None`, the backend's working directory is used
+    pub dir: Option<PathBuf>,
+    /// Environment variables to set, or to unset if `None`
+    pub vars: Vec<(String, Option<String>)>,
+}
+
 #[cfg(feature = "image")]
 pub(crate) type WebcamImage = image::RgbImage;
 #[cfg(not(feature = "image"))]
 pub(crate) type WebcamImage = ();
 
+/// The error a [`SysBackend`] method returns when its operation has started but not finished
+///
+/// This suspends a [`Suspendable`] program.
+pub const SYS_PENDING: &str = "System call is pending";
+
 /// Trait for defining a system backend
 #[allow(unused_variables)]
 pub trait SysBackend: Any + Send + Sync + 'static {
@@ -788,6 +1365,12 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn show(&self, value: Value) -> Result<(), String> {
         self.print_str_stdout(&format!("{}\n", value.show()))
     }
+    /// Print a value to stdout with ANSI colors
+    ///
+    /// This is used instead of [`SysBackend::show`] when color output is enabled.
+    fn show_colored(&self, value: Value) -> Result<(), String> {
+        self.show(value)
+    }
     /// Read a line from stdin
     ///
     /// Should return `Ok(None)` if EOF is reached.
@@ -847,6 +1430,14 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn is_file(&self, path: &str) -> Result<bool, String> {
         Err("Checking if a path is a file is not supported in this environment".into())
     }
+    /// Get the metadata of a file or directory
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        Err("Getting file metadata is not supported in this environment".into())
+    }
+    /// Get the absolute path of a file or directory with all symbolic links resolved
+    fn canonicalize(&self, path: &str) -> Result<String, String> {
+        Err("Canonicalizing paths is not supported in this environment".into())
+    }
     /// Delete a file or directory
     fn delete(&self, path: &str) -> Result<(), String> {
         Err("Deleting files is not supported in this environment".into())
@@ -905,6 +1496,20 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         self.close(handle)?;
         Ok(bytes)
     }
+    /// Append bytes to a file, creating it if it does not exist
+    fn file_append(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        Err("Appending to files is not supported in this environment".into())
+    }
+    /// Write all bytes to a file by writing them to a temporary file and renaming it
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        Err("Writing files atomically is not supported in this environment".into())
+    }
+    /// Take an exclusive advisory lock on a file, waiting if it is already locked
+    ///
+    /// The lock should be released when the handle is closed
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        Err("Locking files is not supported in this environment".into())
+    }
     /// Write all bytes to a file
     fn file_write_all(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
         let handle = self.create_file(path)?;
@@ -949,6 +1554,12 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
         Err("Streaming audio not supported in this environment".into())
     }
+    /// Stream audio in buffers
+    ///
+    /// The stream should end once the function returns fewer samples than requested
+    fn stream_audio_buffers(&self, f: AudioBufferFn) -> Result<(), String> {
+        Err("Streaming audio not supported in this environment".into())
+    }
     /// The result of the `now` function
     ///
     /// Should be in seconds
@@ -960,7 +1571,7 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         Err("TCP listeners are not supported in this environment".into())
     }
     /// Create a TLS listener and bind it to an address
-    fn tls_listen(&self, addr: &str, cert: &[u8], key: &[u8]) -> Result<Handle, String> {
+    fn tls_listen(&self, addr: &str, settings: &TlsServerSettings) -> Result<Handle, String> {
         Err("TLS listeners are not supported in this environment".into())
     }
     /// Accept a connection with a TCP listener
@@ -972,7 +1583,11 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         Err("TCP sockets are not supported in this environment".into())
     }
     /// Create a TCP socket with TLS support and connect it to an address
-    fn tls_connect(&self, addr: &str) -> Result<Handle, String> {
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        Err("TLS sockets are not supported in this environment".into())
+    }
+    /// Get the protocol negotiated with ALPN on a TLS socket
+    fn tls_alpn(&self, handle: Handle) -> Result<Option<String>, String> {
         Err("TLS sockets are not supported in this environment".into())
     }
     /// Get the connection address of a TCP socket or listener
@@ -1008,7 +1623,12 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         Err("Invoking paths is not supported in this environment".into())
     }
     /// Run a command, inheriting standard IO
-    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+    fn run_command_inherit(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<i32, String> {
         Err("Running inheritting commands is not supported in this environment".into())
     }
     /// Run a command, capturing standard IO
@@ -1016,25 +1636,75 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         &self,
         command: &str,
         args: &[&str],
+        cmd_env: &CommandEnv,
     ) -> Result<(i32, String, String), String> {
         Err("Running capturing commands is not supported in this environment".into())
     }
     /// Run a command and return an IO stream handle
-    fn run_command_stream(&self, command: &str, args: &[&str]) -> Result<[Handle; 3], String> {
+    fn run_command_stream(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<[Handle; 3], String> {
         Err("Running streamed commands is not supported in this environment".into())
     }
     /// Change the current directory
+    #[deprecated(
+        since = "0.14.0",
+        note = "`&cd` now changes the directory of the interpreter rather than the process, \
+        so this is no longer called. Implement `current_dir` instead."
+    )]
     fn change_directory(&self, path: &str) -> Result<(), String> {
         Err("Changing directories is not supported in this environment".into())
     }
+    /// Get the working directory
+    ///
+    /// This is used to make relative paths given to `&cd` absolute
+    fn current_dir(&self) -> Result<String, String> {
+        Err("Getting the current directory is not supported in this environment".into())
+    }
     /// Make an HTTPS request on a TCP socket
     fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
         Err("Making HTTPS requests is not supported in this environment".into())
     }
+    /// Send an email over SMTP
+    fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+        Err("Sending email is not supported in this environment".into())
+    }
+    /// List the available serial ports
+    fn serial_ports(&self) -> Result<Vec<String>, String> {
+        Err("Serial ports are not supported in this environment".into())
+    }
+    /// Open a serial port
+    fn serial_open(&self, path: &str, settings: &SerialSettings) -> Result<Handle, String> {
+        Err("Serial ports are not supported in this environment".into())
+    }
+    /// Read at most `count` bytes from a serial port
+    ///
+    /// Should return early if no data arrives for the port's timeout.
+    /// If `count` is `None`, reads until no data arrives for the timeout.
+    fn serial_read(&self, handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String> {
+        Err("Serial ports are not supported in this environment".into())
+    }
+    /// Write to a serial port and wait for the data to be transmitted
+    fn serial_write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        Err("Serial ports are not supported in this environment".into())
+    }
     /// Capture an image from the webcam
     fn webcam_capture(&self, index: usize) -> Result<WebcamImage, String> {
         Err("Capturing from webcam is not supported in this environment".into())
     }
+    /// Get the state of the mouse
+    fn mouse(&self) -> Result<MouseState, String> {
+        Err("Mouse input is not supported in this environment".into())
+    }
+    /// Get the state of a gamepad
+    ///
+    /// Returns `None` if no gamepad is connected at the index
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        Err("Gamepad input is not supported in this environment".into())
+    }
     /// Call a foreign function interface
     fn ffi(
         &self,
@@ -1060,6 +1730,12 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn load_git_module(&self, url: &str, target: GitTarget) -> Result<PathBuf, String> {
         Err("Loading git modules is not supported in this environment".into())
     }
+    /// Load a package from the lockfile as a module
+    ///
+    /// The returned path should be loadable via [`SysBackend::file_read_all`]
+    fn load_package(&self, name: &str) -> Result<PathBuf, String> {
+        Err("Loading packages is not supported in this environment".into())
+    }
     /// Get the local timezone offset in hours
     fn timezone(&self) -> Result<f64, String> {
         if cfg!(target_arch = "wasm32") {
@@ -1152,6 +1828,13 @@ impl SafeSys {
 pub trait IntoSysBackend {
     /// Convert to a reference counted system backend
     fn into_sys_backend(self) -> Arc<dyn SysBackend>;
+    /// Wrap the backend in a [`SysLayer`]
+    fn with_layer<L: SysLayer>(self, layer: L) -> LayeredSys<L>
+    where
+        Self: Sized,
+    {
+        LayeredSys::new(self.into_sys_backend(), layer)
+    }
 }
 
 impl<T> IntoSysBackend for T
@@ -1170,11 +1853,34 @@ impl IntoSysBackend for Arc<dyn SysBackend> {
 }
 
 impl SysOp {
+    /// Run the function, suspending the program if the call is pending
+    /// and the program can be resumed
+    pub(crate) fn run_or_suspend(&self, env: &mut Uiua) -> UiuaResult {
+        if env.rt.suspension.is_none() {
+            return self.run(env);
+        }
+        let height = env.stack_height();
+        let args = env.clone_stack_top(self.args().min(height))?;
+        let res = self.run(env);
+        if matches!(&res, Err(e) if e.is_pending())
+            && env.start_suspending(Node::Prim(Primitive::Sys(*self), env.span_index()))
+        {
+            // Restore the arguments so the call can be made again
+            env.rt.stack.truncate(height - args.len());
+            env.push_all(args);
+        }
+        res
+    }
     pub(crate) fn run(&self, env: &mut Uiua) -> UiuaResult {
         match self {
             SysOp::Show => {
                 let val = env.pop(1)?;
-                env.rt.backend.show(val).map_err(|e| env.error(e))?;
+                if env.rt.color_output {
+                    env.rt.backend.show_colored(val)
+                } else {
+                    env.rt.backend.show(val)
+                }
+                .map_err(|e| env.error(e))?;
             }
             SysOp::Prin => {
                 let s = env.pop(1)?.format();
@@ -1200,6 +1906,10 @@ impl SysOp {
                     .print_str_stderr(&format!("{s}\n"))
                     .map_err(|e| env.error(e))?;
             }
+            SysOp::LogDebug => log(env, LogLevel::Debug)?,
+            SysOp::LogInfo => log(env, LogLevel::Info)?,
+            SysOp::LogWarn => log(env, LogLevel::Warn)?,
+            SysOp::LogError => log(env, LogLevel::Error)?,
             SysOp::ScanLine => {
                 let start = env.rt.backend.now();
                 let res = env.rt.backend.scan_line_stdin().map_err(|e| env.error(e));
@@ -1230,18 +1940,59 @@ impl SysOp {
                 args.extend(env.args().to_owned());
                 env.push(Array::<Boxed>::from_iter(args));
             }
+            SysOp::ParseArgs => {
+                let spec = env.pop(1)?;
+                let spec = match &spec {
+                    Value::Char(arr) if arr.rank() <= 1 => vec![arr.data.iter().collect()],
+                    Value::Box(arr) if arr.rank() <= 1 => (arr.data.iter())
+                        .map(|Boxed(val)| {
+                            val.as_string(env, "Argument spec entries must be strings")
+                        })
+                        .collect::<UiuaResult<_>>()?,
+                    _ => return Err(env.error("Argument spec must be a list of boxed strings")),
+                };
+                let program = (env.file_path().file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "uiua".into());
+                let args = env.args().to_owned();
+                match args::parse_args(&spec, &args, &program, env)? {
+                    args::ParsedArgs::Map(map) => env.push(map),
+                    args::ParsedArgs::Help(help) => {
+                        (env.rt.backend)
+                            .print_str_stdout(&help)
+                            .map_err(|e| env.error(e))?;
+                        (env.rt.backend).exit(0).map_err(|e| env.error(e))?;
+                    }
+                }
+            }
             SysOp::Var => {
                 let key = env
                     .pop(1)?
                     .as_string(env, "Augument to var must be a string")?;
-                let var =
-                    env.rt.backend.var(&key).ok_or_else(|| {
-                        env.error(format!("Environment variable `{key}` is not set"))
-                    })?;
+                let var = env_var(&key, env)
+                    .ok_or_else(|| env.error(format!("Environment variable `{key}` is not set")))?;
                 env.push(var);
             }
+            SysOp::SetVar => {
+                let key = env
+                    .pop(1)?
+                    .as_string(env, "Variable name must be a string")?;
+                let val = env
+                    .pop(2)?
+                    .as_string(env, "Variable value must be a string")?;
+                validate_var_name(&key, env)?;
+                env.rt.env_vars.insert(key, Some(val));
+            }
+            SysOp::UnsetVar => {
+                let key = env
+                    .pop(1)?
+                    .as_string(env, "Variable name must be a string")?;
+                validate_var_name(&key, env)?;
+                env.rt.env_vars.insert(key, None);
+            }
             SysOp::FOpen => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 let handle = (env.rt.backend)
                     .open_file(path.as_ref(), true)
                     .map_err(|e| env.error(e))?
@@ -1249,25 +2000,33 @@ impl SysOp {
                 env.push(handle);
             }
             SysOp::FCreate => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 let handle: Value = (env.rt.backend)
                     .create_file(path.as_ref())
                     .map_err(|e| env.error(e))?
                     .value(HandleKind::File(path.into()));
                 env.push(handle);
             }
+            SysOp::FLock => {
+                let handle = env.pop(1)?.as_handle(env, "")?;
+                env.rt.backend.lock_file(handle).map_err(|e| env.error(e))?;
+            }
             SysOp::FMakeDir => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 (env.rt.backend)
                     .make_dir(path.as_ref())
                     .map_err(|e| env.error(e))?;
             }
             SysOp::FDelete => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 env.rt.backend.delete(&path).map_err(|e| env.error(e))?;
             }
             SysOp::FTrash => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 env.rt.backend.trash(&path).map_err(|e| env.error(e))?;
             }
             SysOp::ReadStr => {
@@ -1463,8 +2222,9 @@ impl SysOp {
             }
             SysOp::FReadAllStr => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let full_path = resolve_path(&path, env)?;
                 let bytes = (env.rt.backend)
-                    .file_read_all(path.as_ref())
+                    .file_read_all(full_path.as_ref())
                     .or_else(|e| match path.as_str() {
                         "example.ua" => Ok(EXAMPLE_UA.as_bytes().to_vec()),
                         "example.txt" => Ok(EXAMPLE_TXT.as_bytes().to_vec()),
@@ -1476,8 +2236,9 @@ impl SysOp {
             }
             SysOp::FReadAllBytes => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let full_path = resolve_path(&path, env)?;
                 let bytes = (env.rt.backend)
-                    .file_read_all(path.as_ref())
+                    .file_read_all(full_path.as_ref())
                     .or_else(|e| match path.as_str() {
                         "example.ua" => Ok(EXAMPLE_UA.as_bytes().to_vec()),
                         "example.txt" => Ok(EXAMPLE_TXT.as_bytes().to_vec()),
@@ -1489,19 +2250,9 @@ impl SysOp {
             }
             SysOp::FWriteAll => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
-                let data = env.pop(2)?;
-                let bytes: Vec<u8> = match data {
-                    Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
-                    Value::Byte(arr) => arr.data.into(),
-
-                    Value::Complex(_) => {
-                        return Err(env.error("Cannot write complex array to file"))
-                    }
-                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
-                    Value::Box(_) => return Err(env.error("Cannot write box array to file")),
-                };
+                let bytes = file_bytes(env.pop(2)?, env)?;
                 (env.rt.backend)
-                    .file_write_all(path.as_ref(), &bytes)
+                    .file_write_all(resolve_path(&path, env)?.as_ref(), &bytes)
                     .or_else(|e| {
                         if path == "example.ua" {
                             let new_ex = String::from_utf8(bytes).map_err(|e| e.to_string())?;
@@ -1513,18 +2264,70 @@ impl SysOp {
                     })
                     .map_err(|e| env.error(e))?;
             }
+            SysOp::FWriteAtomic => {
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
+                let bytes = file_bytes(env.pop(2)?, env)?;
+                (env.rt.backend)
+                    .file_write_atomic(path.as_ref(), &bytes)
+                    .map_err(|e| env.error(e))?;
+            }
             SysOp::FExists => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 let exists = env.rt.backend.file_exists(&path);
                 env.push(exists);
             }
             SysOp::FListDir => {
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
+                let mut paths = env.rt.backend.list_dir(&path).map_err(|e| env.error(e))?;
+                if env.is_deterministic() {
+                    paths.sort_unstable();
+                }
+                env.push(Array::<Boxed>::from_iter(paths));
+            }
+            SysOp::PathJoin => {
+                let parts = string_list(&env.pop(1)?, "Path components must be strings", env)?;
+                let path: PathBuf = parts.into_iter().collect();
+                env.push(path.to_string_lossy().into_owned());
+            }
+            SysOp::PathSplit => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                env.push(Array::<Boxed>::from_iter(split_path(&path)));
+            }
+            SysOp::Ext => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
-                let paths = env.rt.backend.list_dir(&path).map_err(|e| env.error(e))?;
+                let ext = Path::new(&path).extension().unwrap_or_default();
+                env.push(ext.to_string_lossy().into_owned());
+            }
+            SysOp::Canonicalize => {
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
+                let path = env
+                    .rt
+                    .backend
+                    .canonicalize(&path)
+                    .map_err(|e| env.error(e))?;
+                env.push(path);
+            }
+            SysOp::GlobMatch => {
+                let pattern = env.pop(1)?.as_string(env, "Pattern must be a string")?;
+                let path = env.pop(2)?.as_string(env, "Path must be a string")?;
+                env.push(glob_match(&split_path(&pattern), &split_path(&path)));
+            }
+            SysOp::Glob => {
+                let pattern = env.pop(1)?.as_string(env, "Pattern must be a string")?;
+                let parts = split_path(&expand_path(&pattern, env)?);
+                let mut paths = Vec::new();
+                glob_walk(PathBuf::new(), &parts, env, &mut paths);
+                paths.sort_unstable();
+                paths.dedup();
                 env.push(Array::<Boxed>::from_iter(paths));
             }
             SysOp::FIsFile => {
-                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
                 let is_file = env.rt.backend.is_file(&path).map_err(|e| env.error(e))?;
                 env.push(is_file);
             }
@@ -1596,6 +2399,44 @@ impl SysOp {
                     seconds = seconds.min(max);
                 }
                 env.rt.backend.sleep(seconds).map_err(|e| env.error(e))?;
+                if let Some(time) = &mut env.rt.virtual_time {
+                    *time += seconds;
+                }
+            }
+            SysOp::NumberFormat => {
+                let precision = (env.pop(1)?)
+                    .as_nat_or_inf(env, "Precision must be a natural number or infinity")?;
+                let scientific = (env.pop(2)?).as_nat_or_inf(
+                    env,
+                    "Scientific threshold must be a natural number or infinity",
+                )?;
+                let separator = env.pop(3)?.as_string(env, "Separator must be a string")?;
+                let mut chars = separator.chars();
+                let separator = chars.next();
+                if chars.next().is_some() {
+                    return Err(env.error(format!(
+                        "Separator must be at most one character, but it is {separator:?}"
+                    )));
+                }
+                env.rt.format_settings = crate::FormatSettings {
+                    precision,
+                    scientific: scientific.map(|n| n.min(u32::MAX as usize) as u32),
+                    separator,
+                    ..env.rt.format_settings
+                };
+                env.rt.format_settings.set();
+            }
+            SysOp::Elide => {
+                let elide = (env.pop(1)?)
+                    .as_nat_or_inf(env, "Row count must be a natural number or infinity")?;
+                if elide == Some(0) {
+                    return Err(env.error("Row count must be at least 1"));
+                }
+                env.rt.format_settings = crate::FormatSettings {
+                    elide,
+                    ..env.rt.format_settings
+                };
+                env.rt.format_settings.set();
             }
             SysOp::TcpListen => {
                 let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
@@ -1608,28 +2449,32 @@ impl SysOp {
             }
             SysOp::TlsListen => {
                 let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
-                let cert = env
-                    .pop(2)?
-                    .into_bytes(env, "Cert must be a byte or character array")?;
-                let key = env
-                    .pop(3)?
-                    .into_bytes(env, "Key must be a byte or character array")?;
+                let settings = tls_server_settings(&env.pop(2)?, env)?;
                 let handle = (env.rt.backend)
-                    .tls_listen(&addr, &cert, &key)
+                    .tls_listen(&addr, &settings)
                     .map_err(|e| env.error(e))?;
                 let sock_addr = env.rt.backend.tcp_addr(handle).map_err(|e| env.error(e))?;
                 let handle = handle.value(HandleKind::TlsListener(sock_addr));
                 env.push(handle);
             }
             SysOp::TcpAccept => {
-                let handle = env.pop(1)?.as_handle(env, "")?;
+                let listener = env.pop(1)?;
+                let tls = matches!(
+                    listener.meta().handle_kind,
+                    Some(HandleKind::TlsListener(_))
+                );
+                let handle = listener.as_handle(env, "")?;
                 let handle = (env.rt.backend)
                     .tcp_accept(handle)
                     .map_err(|e| env.error(e))?;
                 let addr = (env.rt.backend)
                     .tcp_addr(handle)
                     .map_err(|e| env.error(e))?;
-                let handle = handle.value(HandleKind::TcpSocket(addr));
+                let handle = handle.value(if tls {
+                    HandleKind::TlsSocket(addr)
+                } else {
+                    HandleKind::TcpSocket(addr)
+                });
                 env.push(handle);
             }
             SysOp::TcpConnect => {
@@ -1642,14 +2487,21 @@ impl SysOp {
                 env.push(handle);
             }
             SysOp::TlsConnect => {
-                let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
+                let (addr, settings) = tls_client_settings(env.pop(1)?, env)?;
                 let handle = (env.rt.backend)
-                    .tls_connect(&addr)
+                    .tls_connect(&addr, &settings)
                     .map_err(|e| env.error(e))?;
                 let sock_addr = env.rt.backend.tcp_addr(handle).map_err(|e| env.error(e))?;
                 let handle = handle.value(HandleKind::TlsSocket(sock_addr));
                 env.push(handle);
             }
+            SysOp::TlsAlpn => {
+                let handle = env.pop(1)?.as_handle(env, "")?;
+                let protocol = (env.rt.backend)
+                    .tls_alpn(handle)
+                    .map_err(|e| env.error(e))?;
+                env.push(protocol.unwrap_or_default());
+            }
             SysOp::TcpAddr => {
                 let handle = env.pop(1)?.as_handle(env, "")?;
                 let addr = env.rt.backend.tcp_addr(handle).map_err(|e| env.error(e))?;
@@ -1695,15 +2547,57 @@ impl SysOp {
                     .map_err(|e| env.error(e))?;
                 env.push(res);
             }
+            SysOp::SmtpSend => {
+                let server = smtp_server(&env.pop(1)?, env)?;
+                let email = email(&env.pop(2)?, env)?;
+                (env.rt.backend)
+                    .smtp_send(&server, &email)
+                    .map_err(|e| env.error(e))?;
+            }
             SysOp::Close => {
-                let handle = env.pop(1)?.as_handle(env, "")?;
-                env.rt.backend.close(handle).map_err(|e| env.error(e))?;
+                let handle = env.pop(1)?;
+                env.close_handle(handle)?;
+            }
+            SysOp::SerialPorts => {
+                let ports = env.rt.backend.serial_ports().map_err(|e| env.error(e))?;
+                env.push(Array::<Boxed>::from_iter(ports));
+            }
+            SysOp::SerialOpen => {
+                let path =
+                    resolve_path(&env.pop(1)?.as_string(env, "Path must be a string")?, env)?;
+                let settings = serial_settings(&env.pop(2)?, env)?;
+                let handle = (env.rt.backend)
+                    .serial_open(&path, &settings)
+                    .map_err(|e| env.error(e))?
+                    .value(HandleKind::SerialPort(path));
+                env.push(handle);
+            }
+            SysOp::SerialRead => {
+                let count = env
+                    .pop(1)?
+                    .as_nat_or_inf(env, "Count must be an integer or infinity")?;
+                if let Some(count) = count {
+                    validate_size::<u8>([count], env)?;
+                }
+                let handle = env.pop(2)?.as_handle(env, "")?;
+                let bytes = (env.rt.backend)
+                    .serial_read(handle, count)
+                    .map_err(|e| env.error(e))?;
+                env.push(Array::from(bytes.as_slice()));
+            }
+            SysOp::SerialWrite => {
+                let data = (env.pop(1)?)
+                    .into_bytes(env, "Serial data must be a byte or character array")?;
+                let handle = env.pop(2)?.as_handle(env, "")?;
+                (env.rt.backend)
+                    .serial_write(handle, &data)
+                    .map_err(|e| env.error(e))?;
             }
             SysOp::RunInherit => {
                 let (command, args) = value_to_command(&env.pop(1)?, env)?;
                 let args: Vec<_> = args.iter().map(|s| s.as_str()).collect();
                 let code = (env.rt.backend)
-                    .run_command_inherit(&command, &args)
+                    .run_command_inherit(&command, &args, &command_env(env))
                     .map_err(|e| env.error(e))?;
                 env.push(code);
             }
@@ -1711,7 +2605,7 @@ impl SysOp {
                 let (command, args) = value_to_command(&env.pop(1)?, env)?;
                 let args: Vec<_> = args.iter().map(|s| s.as_str()).collect();
                 let (code, stdout, stderr) = (env.rt.backend)
-                    .run_command_capture(&command, &args)
+                    .run_command_capture(&command, &args, &command_env(env))
                     .map_err(|e| env.error(e))?;
                 env.push(stderr);
                 env.push(stdout);
@@ -1721,7 +2615,7 @@ impl SysOp {
                 let (command, args) = value_to_command(&env.pop(1)?, env)?;
                 let args: Vec<_> = args.iter().map(|s| s.as_str()).collect();
                 let handles = (env.rt.backend)
-                    .run_command_stream(&command, &args)
+                    .run_command_stream(&command, &args, &command_env(env))
                     .map_err(|e| env.error(e))?;
                 for (handle, kind) in handles
                     .into_iter()
@@ -1737,9 +2631,27 @@ impl SysOp {
             }
             SysOp::ChangeDirectory => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
-                (env.rt.backend)
-                    .change_directory(&path)
-                    .map_err(|e| env.error(e))?;
+                let mut dir = PathBuf::from(resolve_path(&path, env)?);
+                if dir.is_relative() {
+                    if let Ok(current) = env.rt.backend.current_dir() {
+                        dir = Path::new(&current).join(dir);
+                    }
+                }
+                let dir = normalize_path(&dir);
+                let dir_str = dir.to_string_lossy();
+                match env.rt.backend.is_file(&dir_str) {
+                    Ok(false) => {}
+                    Ok(true) => return Err(env.error(format!("{path} is not a directory"))),
+                    Err(e) => return Err(env.error(e)),
+                }
+                env.rt.working_dir = Some(dir);
+            }
+            SysOp::WorkingDirectory => {
+                let dir = match &env.rt.working_dir {
+                    Some(dir) => dir.to_string_lossy().into_owned(),
+                    None => env.rt.backend.current_dir().map_err(|e| env.error(e))?,
+                };
+                env.push(dir);
             }
             SysOp::WebcamCapture => {
                 let index = env.pop(1)?.as_nat(env, "Webcam index must be an integer")?;
@@ -1751,6 +2663,18 @@ impl SysOp {
                 #[cfg(not(feature = "image"))]
                 return Err(env.error("Webcam capture is not supported in this environment"));
             }
+            SysOp::Mouse => {
+                let mouse = env.rt.backend.mouse().map_err(|e| env.error(e))?;
+                env.push(input_map(mouse.inputs(), env)?);
+            }
+            SysOp::Gamepad => {
+                let index = env
+                    .pop(1)?
+                    .as_nat(env, "Gamepad index must be an integer")?;
+                let gamepad = (env.rt.backend.gamepad(index)).map_err(|e| env.error(e))?;
+                let inputs = gamepad.iter().flat_map(GamepadState::inputs);
+                env.push(input_map(inputs, env)?);
+            }
             SysOp::Ffi => {
                 let sig_def = env.pop(1)?;
                 let sig_def = match sig_def {
@@ -1846,6 +2770,33 @@ impl SysOp {
     }
     pub(crate) fn run_mod(&self, ops: Ops, env: &mut Uiua) -> UiuaResult {
         match self {
+            SysOp::FWalk => {
+                let [f] = get_ops(ops, env)?;
+                let root = env.pop(1)?.as_string(env, "Root path must be a string")?;
+                let real_root = PathBuf::from(resolve_path(&root, env)?);
+                // Entries waiting to be visited, in reverse order
+                let mut pending = Vec::new();
+                let root_meta = (env.rt.backend)
+                    .file_metadata(&real_root.to_string_lossy())
+                    .map_err(|e| env.error(e))?;
+                if root_meta.is_dir {
+                    walk_dir_entries(root.into(), real_root, &mut pending, env)?;
+                }
+                stream_rows(f, env, |env| {
+                    while let Some((path, real)) = pending.pop() {
+                        // Entries may disappear during the walk
+                        let Ok(meta) = env.rt.backend.file_metadata(&real.to_string_lossy()) else {
+                            continue;
+                        };
+                        let entry = file_entry_map(path.to_string_lossy().into(), meta, env)?;
+                        if meta.is_dir {
+                            walk_dir_entries(path, real, &mut pending, env)?;
+                        }
+                        return Ok(Some(entry));
+                    }
+                    Ok(None)
+                })?;
+            }
             SysOp::ReadLines => {
                 let [f] = get_ops(ops, env)?;
                 let handle = env.pop(1)?.as_handle(env, "")?;
@@ -1880,6 +2831,74 @@ impl SysOp {
                     env.push(val);
                 }
             }
+            SysOp::Lines => {
+                let [f] = get_ops(ops, env)?;
+                let handle = env.pop(1)?.as_handle(env, "")?;
+                stream_rows(f, env, |env| {
+                    let mut line = read_line_bytes(handle, env)?;
+                    if line.is_empty() {
+                        return Ok(None);
+                    }
+                    trim_line_ending(&mut line);
+                    let line = String::from_utf8(line).map_err(|e| env.error(e))?;
+                    Ok(Some(line.into()))
+                })?;
+            }
+            SysOp::Chunks => {
+                let [f] = get_ops(ops, env)?;
+                let size = env
+                    .pop(1)?
+                    .as_nat(env, "Chunk size must be a natural number")?;
+                if size == 0 {
+                    return Err(env.error("Chunk size must be positive"));
+                }
+                validate_size::<u8>([size], env)?;
+                let handle = env.pop(2)?.as_handle(env, "")?;
+                stream_rows(f, env, |env| {
+                    let mut chunk = Vec::with_capacity(size);
+                    while chunk.len() < size {
+                        let bytes = match handle {
+                            Handle::STDOUT => return Err(env.error("Cannot read from stdout")),
+                            Handle::STDERR => return Err(env.error("Cannot read from stderr")),
+                            Handle::STDIN => env.rt.backend.scan_stdin(Some(size - chunk.len())),
+                            _ => env.rt.backend.read(handle, size - chunk.len()),
+                        }
+                        .map_err(|e| env.error(e))?;
+                        if bytes.is_empty() {
+                            break;
+                        }
+                        chunk.extend(bytes);
+                    }
+                    Ok((!chunk.is_empty()).then(|| Array::<u8>::from(chunk.as_slice()).into()))
+                })?;
+            }
+            SysOp::TeeStream => {
+                let [f] = get_ops(ops, env)?;
+                let dest = env.pop(1)?.as_handle(env, "")?;
+                let source = env.pop(2)?.as_handle(env, "")?;
+                stream_rows(f, env, |env| {
+                    let mut line = read_line_bytes(source, env)?;
+                    if line.is_empty() {
+                        return Ok(None);
+                    }
+                    match dest {
+                        Handle::STDIN => return Err(env.error("Cannot write to stdin")),
+                        Handle::STDOUT => env
+                            .rt
+                            .backend
+                            .print_str_stdout(&String::from_utf8_lossy(&line)),
+                        Handle::STDERR => env
+                            .rt
+                            .backend
+                            .print_str_stderr(&String::from_utf8_lossy(&line)),
+                        _ => env.rt.backend.write(dest, &line),
+                    }
+                    .map_err(|e| env.error(e))?;
+                    trim_line_ending(&mut line);
+                    let line = String::from_utf8(line).map_err(|e| env.error(e))?;
+                    Ok(Some(line.into()))
+                })?;
+            }
             SysOp::AudioStream => {
                 let [f] = get_ops(ops, env)?;
                 let push_time = f.sig.args > 0;
@@ -1900,36 +2919,33 @@ impl SysOp {
                         stream_env.push(time_array);
                     }
                     stream_env.exec(f.clone())?;
-                    let samples = &stream_env.pop(1)?;
-                    let samples = samples.as_num_array().ok_or_else(|| {
-                        stream_env.error("Audio stream function must return a numeric array")
-                    })?;
-                    match samples.shape().dims() {
-                        [_] => Ok(samples.data.iter().map(|&x| [x, x]).collect()),
-                        &[n, 2] => {
-                            let mut samps: Vec<[f64; 2]> = Vec::with_capacity(n);
-                            for samp in samples.data.chunks_exact(2) {
-                                samps.push([samp[0], samp[1]]);
-                            }
-                            Ok(samps)
-                        }
-                        &[2, n] => {
-                            let mut samps: Vec<[f64; 2]> = Vec::with_capacity(n);
-                            for i in 0..n {
-                                samps.push([samples.data[i], samples.data[i + n]]);
-                            }
-                            Ok(samps)
-                        }
-                        _ => Err(stream_env.error(format!(
-                            "Audio stream function must return either a \
-                            rank 1 array or a rank 2 array with 2 rows, \
-                            but its shape is {}",
-                            samples.shape()
-                        ))),
-                    }
+                    let samples = stream_env.pop(1)?;
+                    stream_samples(&samples, &stream_env)
                 }));
                 res.map_err(|e| env.error(e))?;
             }
+            SysOp::AudioCallback => {
+                let [f] = get_ops(ops, env)?;
+                if f.sig != (2, 1) {
+                    return Err(env.error(format!(
+                        "&astream's function must have signature |2.1, \
+                        but its signature is {}",
+                        f.sig
+                    )));
+                }
+                let mut stream_env = env.clone();
+                let res = env
+                    .rt
+                    .backend
+                    .stream_audio_buffers(Box::new(move |start, len| {
+                        stream_env.push(len);
+                        stream_env.push(start as f64);
+                        stream_env.exec(f.clone())?;
+                        let samples = stream_env.pop(1)?;
+                        stream_samples(&samples, &stream_env)
+                    }));
+                res.map_err(|e| env.error(e))?;
+            }
             prim => {
                 return Err(env.error(if prim.modifier_args().is_some() {
                     format!(
@@ -1950,6 +2966,590 @@ impl SysOp {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl LogLevel {
+    fn from_var(var: &str) -> Option<Self> {
+        Some(match var.trim().to_lowercase().as_str() {
+            "debug" => LogLevel::Debug,
+            "info" => LogLevel::Info,
+            "warn" | "warning" => LogLevel::Warn,
+            "error" => LogLevel::Error,
+            "off" | "none" => LogLevel::Off,
+            _ => return None,
+        })
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Off => "off",
+        }
+    }
+}
+
+/// Log a message for one of the `&log` functions
+fn log(env: &mut Uiua, level: LogLevel) -> UiuaResult {
+    let message = env.pop(1)?.format();
+    let min_level = (env.rt.backend.var("UIUA_LOG"))
+        .and_then(|var| LogLevel::from_var(&var))
+        .unwrap_or(LogLevel::Info);
+    if level < min_level {
+        return Ok(());
+    }
+    let time = format_timestamp(env.now());
+    let line = format!("{time} {:<5} {message}\n", level.name().to_uppercase());
+    (env.rt.backend)
+        .print_str_stderr(&line)
+        .map_err(|e| env.error(e))?;
+    if let Some(path) = env.rt.backend.var("UIUA_LOG_FILE") {
+        let entry = serde_json::json!({
+            "time": time,
+            "level": level.name(),
+            "message": message,
+            "span": env.span().to_string(),
+        });
+        (env.rt.backend)
+            .file_append(Path::new(&path), format!("{entry}\n").as_bytes())
+            .map_err(|e| env.error(e))?;
+    }
+    Ok(())
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp
+fn format_timestamp(secs: f64) -> String {
+    let nanos = (secs * 1e9) as i128;
+    let Ok(time) = time::OffsetDateTime::from_unix_timestamp_nanos(nanos) else {
+        return secs.to_string();
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.millisecond()
+    )
+}
+
+/// Call a function on each value pulled from a stream, collecting its outputs like [`Primitive::Rows`]
+fn stream_rows(
+    f: SigNode,
+    env: &mut Uiua,
+    mut next: impl FnMut(&mut Uiua) -> UiuaResult<Option<Value>>,
+) -> UiuaResult {
+    let sig = f.sig;
+    let acc_count = sig.args.saturating_sub(1);
+    let out_count = sig.outputs.saturating_sub(acc_count);
+    let mut outputs = multi_output(out_count, Vec::new());
+    env.without_fill(|env| -> UiuaResult {
+        while let Some(val) = next(env)? {
+            env.push(val);
+            env.exec(f.clone())?;
+            for i in 0..out_count {
+                outputs[i].push(env.pop("stream output")?);
+            }
+        }
+        Ok(())
+    })?;
+    for rows in outputs.into_iter().rev() {
+        let val = Value::from_row_values(rows, env)?;
+        env.push(val);
+    }
+    Ok(())
+}
+
+/// Add the entries of a directory to the pending stack of [`SysOp::FWalk`]
+fn walk_dir_entries(
+    path: PathBuf,
+    real: PathBuf,
+    pending: &mut Vec<(PathBuf, PathBuf)>,
+    env: &Uiua,
+) -> UiuaResult {
+    let entries = (env.rt.backend)
+        .list_dir(&real.to_string_lossy())
+        .map_err(|e| env.error(e))?;
+    let mut entries: Vec<_> = (entries.iter())
+        .filter_map(|entry| {
+            let name = Path::new(entry).file_name()?;
+            Some((path.join(name), real.join(name)))
+        })
+        .collect();
+    entries.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+    pending.extend(entries);
+    Ok(())
+}
+
+/// Get the bytes to write to a file
+fn file_bytes(data: Value, env: &Uiua) -> UiuaResult<Vec<u8>> {
+    Ok(match data {
+        Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
+        Value::Byte(arr) => arr.data.into(),
+        Value::Complex(_) => return Err(env.error("Cannot write complex array to file")),
+        Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+        Value::Box(_) => return Err(env.error("Cannot write box array to file")),
+    })
+}
+
+/// Make a map array describing a file or directory
+fn file_entry_map(path: String, meta: FileMetadata, env: &Uiua) -> UiuaResult<Value> {
+    let keys = ["path", "size", "mtime", "isdir"];
+    let values = [
+        Value::from(path),
+        (if meta.is_dir { 0.0 } else { meta.size as f64 }).into(),
+        meta.modified.unwrap_or(f64::NAN).into(),
+        meta.is_dir.into(),
+    ];
+    let mut map: Value = Array::<Boxed>::from_iter(values.map(Boxed)).into();
+    map.map(Array::<Boxed>::from_iter(keys).into(), env)?;
+    Ok(map)
+}
+
+/// Make a map array from input names to their values
+fn input_map(
+    inputs: impl IntoIterator<Item = (&'static str, f64)>,
+    env: &Uiua,
+) -> UiuaResult<Value> {
+    let (keys, values): (Vec<&str>, Vec<f64>) = inputs.into_iter().unzip();
+    let mut map: Value = Array::from_iter(values).into();
+    map.map(Array::<Boxed>::from_iter(keys).into(), env)?;
+    Ok(map)
+}
+
+/// Get the entries of a map array with string keys
+fn string_map(val: &Value, name: &str, env: &Uiua) -> UiuaResult<Vec<(String, Value)>> {
+    if !val.is_map() {
+        return Err(env.error(format!(
+            "{name} must be a map array, but it is {}",
+            val.type_name_plural()
+        )));
+    }
+    (val.map_kv().into_iter())
+        .map(|(key, val)| {
+            let key = key.unboxed().as_string(env, "Map keys must be strings")?;
+            Ok((key, val.unboxed()))
+        })
+        .collect()
+}
+
+/// Get a list of strings from a string or a list of boxed strings
+fn string_list(val: &Value, requirement: &'static str, env: &Uiua) -> UiuaResult<Vec<String>> {
+    match val {
+        Value::Box(arr) if arr.rank() == 1 => (arr.data.iter())
+            .map(|b| b.0.as_string(env, requirement))
+            .collect(),
+        val => Ok(vec![val.as_string(env, requirement)?]),
+    }
+}
+
+/// Pair up a certificate and key, which must be given together
+fn tls_identity(
+    cert: Option<Vec<u8>>,
+    key: Option<Vec<u8>>,
+    env: &Uiua,
+) -> UiuaResult<Option<(Vec<u8>, Vec<u8>)>> {
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some((cert, key))),
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(env.error("TLS settings have a cert but no key")),
+        (None, Some(_)) => Err(env.error("TLS settings have a key but no cert")),
+    }
+}
+
+fn tls_client_settings(val: Value, env: &Uiua) -> UiuaResult<(String, TlsClientSettings)> {
+    if !val.is_map() {
+        let addr = val.as_string(env, "Address must be a string or a map array")?;
+        return Ok((addr, TlsClientSettings::default()));
+    }
+    let mut addr = None;
+    let mut settings = TlsClientSettings::default();
+    let (mut cert, mut key) = (None, None);
+    for (name, val) in string_map(&val, "TLS settings", env)? {
+        match name.as_str() {
+            "address" => addr = Some(val.as_string(env, "Address must be a string")?),
+            "servername" => {
+                settings.server_name = Some(val.as_string(env, "Server name must be a string")?)
+            }
+            "alpn" => settings.alpn = string_list(&val, "ALPN protocols must be strings", env)?,
+            "roots" => {
+                let roots = val.into_bytes(env, "Roots must be a byte or character array")?;
+                settings.roots = Some(roots);
+            }
+            "cert" => cert = Some(val.into_bytes(env, "Cert must be a byte or character array")?),
+            "key" => key = Some(val.into_bytes(env, "Key must be a byte or character array")?),
+            _ => return Err(env.error(format!("Unknown TLS client setting {name:?}"))),
+        }
+    }
+    let addr = addr.ok_or_else(|| env.error("TLS settings must have an address"))?;
+    settings.identity = tls_identity(cert, key, env)?;
+    Ok((addr, settings))
+}
+
+fn tls_server_settings(val: &Value, env: &Uiua) -> UiuaResult<TlsServerSettings> {
+    let mut settings = TlsServerSettings::default();
+    let (mut cert, mut key) = (None, None);
+    for (name, val) in string_map(val, "TLS listener settings", env)? {
+        match name.as_str() {
+            "cert" => cert = Some(val.into_bytes(env, "Cert must be a byte or character array")?),
+            "key" => key = Some(val.into_bytes(env, "Key must be a byte or character array")?),
+            "alpn" => settings.alpn = string_list(&val, "ALPN protocols must be strings", env)?,
+            "clientroots" => {
+                let roots =
+                    val.into_bytes(env, "Client roots must be a byte or character array")?;
+                settings.client_roots = Some(roots);
+            }
+            "sni" => {
+                for (host, pair) in string_map(&val, "SNI certificates", env)? {
+                    let pair = match pair {
+                        Value::Box(arr) if arr.shape() == [2] => arr,
+                        _ => return Err(env.error("SNI certificates must be {cert key} pairs")),
+                    };
+                    let [cert, key] = [0, 1].map(|i| pair.data[i].0.clone());
+                    let cert = cert.into_bytes(env, "Cert must be a byte or character array")?;
+                    let key = key.into_bytes(env, "Key must be a byte or character array")?;
+                    settings.sni.push((host, cert, key));
+                }
+            }
+            _ => return Err(env.error(format!("Unknown TLS listener setting {name:?}"))),
+        }
+    }
+    settings.identity = tls_identity(cert, key, env)?;
+    if settings.identity.is_none() && settings.sni.is_empty() {
+        return Err(env.error("TLS listener settings must have a cert and key or sni certificates"));
+    }
+    Ok(settings)
+}
+
+fn serial_settings(val: &Value, env: &Uiua) -> UiuaResult<SerialSettings> {
+    let mut settings = SerialSettings::default();
+    if !val.is_map() {
+        settings.baud = (val.as_nat(env, "Baud rate must be a natural number or a map array")?)
+            .try_into()
+            .map_err(|_| env.error("Baud rate is too large"))?;
+        return Ok(settings);
+    }
+    for (name, val) in string_map(val, "Serial settings", env)? {
+        match name.as_str() {
+            "baud" => {
+                settings.baud = (val.as_nat(env, "Baud rate must be a natural number")?)
+                    .try_into()
+                    .map_err(|_| env.error("Baud rate is too large"))?
+            }
+            "databits" => {
+                settings.data_bits = match val.as_nat(env, "Data bits must be a natural number")? {
+                    n @ 5..=8 => n as u8,
+                    n => return Err(env.error(format!("Data bits must be 5 to 8, but it is {n}"))),
+                }
+            }
+            "parity" => {
+                let parity = val.as_string(env, "Parity must be a string")?;
+                settings.parity = match parity.to_lowercase().as_str() {
+                    "none" => SerialParity::None,
+                    "odd" => SerialParity::Odd,
+                    "even" => SerialParity::Even,
+                    _ => return Err(env.error(format!("Unknown parity {parity:?}"))),
+                }
+            }
+            "stopbits" => {
+                settings.stop_bits = match val.as_nat(env, "Stop bits must be a natural number")? {
+                    n @ 1..=2 => n as u8,
+                    n => return Err(env.error(format!("Stop bits must be 1 or 2, but it is {n}"))),
+                }
+            }
+            "flowcontrol" => {
+                let flow = val.as_string(env, "Flow control must be a string")?;
+                settings.flow_control = match flow.to_lowercase().as_str() {
+                    "none" => SerialFlowControl::None,
+                    "software" => SerialFlowControl::Software,
+                    "hardware" => SerialFlowControl::Hardware,
+                    _ => return Err(env.error(format!("Unknown flow control {flow:?}"))),
+                }
+            }
+            "timeout" => {
+                let timeout = val.as_num(env, "Timeout must be a number")?.abs();
+                settings.timeout = if timeout.is_infinite() {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(timeout))
+                };
+            }
+            _ => return Err(env.error(format!("Unknown serial setting {name:?}"))),
+        }
+    }
+    Ok(settings)
+}
+
+fn smtp_server(val: &Value, env: &Uiua) -> UiuaResult<SmtpServer> {
+    let (mut host, mut port, mut security) = (None, None, None);
+    let (mut username, mut password) = (None, None);
+    for (key, val) in string_map(val, "SMTP server settings", env)? {
+        match key.as_str() {
+            "host" => host = Some(val.as_string(env, "SMTP host must be a string")?),
+            "port" => {
+                let n = val.as_nat(env, "SMTP port must be a natural number")?;
+                port = Some(u16::try_from(n).map_err(|_| env.error("SMTP port is too large"))?);
+            }
+            "username" => username = Some(val.as_string(env, "SMTP username must be a string")?),
+            "password" => password = Some(val.as_string(env, "SMTP password must be a string")?),
+            "security" => {
+                let sec = val.as_string(env, "SMTP security must be a string")?;
+                security = Some(match sec.as_str() {
+                    "tls" => SmtpSecurity::Tls,
+                    "starttls" => SmtpSecurity::StartTls,
+                    "none" => SmtpSecurity::None,
+                    _ => {
+                        return Err(env.error(format!(
+                            "Unknown SMTP security {sec:?}, \
+                            expected \"tls\", \"starttls\", or \"none\""
+                        )))
+                    }
+                });
+            }
+            _ => return Err(env.error(format!("Unknown SMTP server setting {key:?}"))),
+        }
+    }
+    let host = host.ok_or_else(|| env.error("SMTP server settings must have a host"))?;
+    let security = security.unwrap_or(match port {
+        None | Some(465) => SmtpSecurity::Tls,
+        Some(_) => SmtpSecurity::StartTls,
+    });
+    let port = port.unwrap_or(match security {
+        SmtpSecurity::Tls => 465,
+        SmtpSecurity::StartTls => 587,
+        SmtpSecurity::None => 25,
+    });
+    Ok(SmtpServer {
+        host,
+        port,
+        username,
+        password,
+        security,
+    })
+}
+
+/// Build an email from a message map array
+fn email(val: &Value, env: &Uiua) -> UiuaResult<Email> {
+    let header = |val: &Value, requirement: &'static str| {
+        let s = val.as_string(env, requirement)?;
+        if s.contains(['\r', '\n']) {
+            return Err(env.error("Email headers cannot contain newlines"));
+        }
+        Ok(s)
+    };
+    let addresses = |val: &Value| match val {
+        Value::Box(arr) if arr.rank() == 1 => (arr.data.iter())
+            .map(|b| header(&b.0, "Email addresses must be strings"))
+            .collect::<UiuaResult<Vec<_>>>(),
+        val => Ok(vec![header(val, "Email addresses must be strings")?]),
+    };
+    let (mut from, mut subject, mut body) = (None, String::new(), String::new());
+    let (mut to, mut cc, mut bcc) = (Vec::new(), Vec::new(), Vec::new());
+    let mut attachments = Vec::new();
+    for (key, val) in string_map(val, "Email message", env)? {
+        match key.as_str() {
+            "from" => from = Some(header(&val, "Email sender must be a string")?),
+            "to" => to = addresses(&val)?,
+            "cc" => cc = addresses(&val)?,
+            "bcc" => bcc = addresses(&val)?,
+            "subject" => subject = header(&val, "Email subject must be a string")?,
+            "body" => body = val.as_string(env, "Email body must be a string")?,
+            "attachments" => {
+                for (name, data) in string_map(&val, "Email attachments", env)? {
+                    let data = match data {
+                        Value::Char(_) => data.as_string(env, "")?.into_bytes(),
+                        data => data.as_bytes(env, "Attachments must be bytes or strings")?,
+                    };
+                    attachments.push((name, data));
+                }
+            }
+            _ => return Err(env.error(format!("Unknown email field {key:?}"))),
+        }
+    }
+    let from = from.ok_or_else(|| env.error("Email must have a sender"))?;
+    if to.is_empty() && cc.is_empty() && bcc.is_empty() {
+        return Err(env.error("Email must have at least one recipient"));
+    }
+
+    let mut message = format!("Date: {}\r\nFrom: {from}\r\n", email_date(env.now()));
+    if !to.is_empty() {
+        message.push_str(&format!("To: {}\r\n", to.join(", ")));
+    }
+    if !cc.is_empty() {
+        message.push_str(&format!("Cc: {}\r\n", cc.join(", ")));
+    }
+    message.push_str(&format!("Subject: {}\r\n", encoded_word(&subject)));
+    message.push_str("MIME-Version: 1.0\r\n");
+    let text = text_part(&body);
+    if attachments.is_empty() {
+        message.push_str(&text);
+    } else {
+        // Every part is base64 or 7-bit text without this sequence, so it can't collide
+        let boundary = format!("=_uiua_{}", attachments.len());
+        message.push_str(&format!(
+            "Content-Type: multipart/mixed; boundary=\"{boundary}\"\r\n\r\n"
+        ));
+        message.push_str(&format!("--{boundary}\r\n{text}\r\n"));
+        for (name, data) in attachments {
+            let ext = name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+            let mime = match ext.as_deref().unwrap_or("") {
+                "txt" | "log" => "text/plain",
+                "csv" => "text/csv",
+                "html" => "text/html",
+                "json" => "application/json",
+                "pdf" => "application/pdf",
+                "zip" => "application/zip",
+                "png" => "image/png",
+                "jpg" | "jpeg" => "image/jpeg",
+                "gif" => "image/gif",
+                "wav" => "audio/wav",
+                _ => "application/octet-stream",
+            };
+            let name = encoded_word(&name.replace(['"', '\\', '\r', '\n'], "_"));
+            message.push_str(&format!(
+                "--{boundary}\r\n\
+                Content-Type: {mime}; name=\"{name}\"\r\n\
+                Content-Disposition: attachment; filename=\"{name}\"\r\n\
+                Content-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+                wrapped_base64(&data)
+            ));
+        }
+        message.push_str(&format!("--{boundary}--\r\n"));
+    }
+
+    let recipients = (to.iter().chain(&cc).chain(&bcc))
+        .map(|addr| email_address(addr).into())
+        .collect();
+    Ok(Email {
+        from: email_address(&from).into(),
+        recipients,
+        message,
+    })
+}
+
+/// Get the address part of an address that may include a name
+fn email_address(addr: &str) -> &str {
+    match addr.rsplit_once('<') {
+        Some((_, addr)) => addr.trim_end().trim_end_matches('>'),
+        None => addr.trim(),
+    }
+}
+
+/// Encode a header value as an RFC 2047 encoded word if it is not ASCII
+fn encoded_word(s: &str) -> String {
+    if s.is_ascii() {
+        s.into()
+    } else {
+        format!("=?UTF-8?B?{}?=", encode::base64(s.as_bytes()))
+    }
+}
+
+/// Make the headers and content of a plain text body
+fn text_part(body: &str) -> String {
+    let body = body.replace("\r\n", "\n");
+    if body.is_ascii() && body.lines().all(|line| line.len() <= 998) {
+        let body: Vec<&str> = body.lines().collect();
+        format!(
+            "Content-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: 7bit\r\n\r\n{}\r\n",
+            body.join("\r\n")
+        )
+    } else {
+        format!(
+            "Content-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+            wrapped_base64(body.as_bytes())
+        )
+    }
+}
+
+/// Encode bytes as base64 in lines of 76 characters
+fn wrapped_base64(bytes: &[u8]) -> String {
+    let encoded = encode::base64(bytes);
+    let lines: Vec<&str> = (encoded.as_bytes().chunks(76))
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect();
+    lines.join("\r\n")
+}
+
+/// Format a Unix timestamp as an RFC 5322 date
+fn email_date(time: f64) -> String {
+    let time = time::OffsetDateTime::from_unix_timestamp(time as i64)
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    let weekday = &time.weekday().to_string()[..3];
+    let month = &time.month().to_string()[..3];
+    format!(
+        "{weekday}, {:02} {month} {} {:02}:{:02}:{:02} +0000",
+        time.day(),
+        time.year(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+/// Convert the samples returned by an audio stream function to stereo samples
+fn stream_samples(samples: &Value, env: &Uiua) -> UiuaResult<Vec<[f64; 2]>> {
+    let samples = samples
+        .as_num_array()
+        .ok_or_else(|| env.error("Audio stream function must return a numeric array"))?;
+    match samples.shape().dims() {
+        [_] => Ok(samples.data.iter().map(|&x| [x, x]).collect()),
+        &[n, 2] => {
+            let mut samps: Vec<[f64; 2]> = Vec::with_capacity(n);
+            for samp in samples.data.chunks_exact(2) {
+                samps.push([samp[0], samp[1]]);
+            }
+            Ok(samps)
+        }
+        &[2, n] => {
+            let mut samps: Vec<[f64; 2]> = Vec::with_capacity(n);
+            for i in 0..n {
+                samps.push([samples.data[i], samples.data[i + n]]);
+            }
+            Ok(samps)
+        }
+        _ => Err(env.error(format!(
+            "Audio stream function must return either a \
+            rank 1 array or a rank 2 array with 2 rows, \
+            but its shape is {}",
+            samples.shape()
+        ))),
+    }
+}
+
+/// Read a line from a stream, including its line ending
+///
+/// Returns an empty buffer at the end of the stream
+fn read_line_bytes(handle: Handle, env: &Uiua) -> UiuaResult<Vec<u8>> {
+    match handle {
+        Handle::STDOUT => Err("Cannot read from stdout".into()),
+        Handle::STDERR => Err("Cannot read from stderr".into()),
+        Handle::STDIN => env.rt.backend.scan_until_stdin(b"\n"),
+        _ => env.rt.backend.read_until(handle, b"\n"),
+    }
+    .map_err(|e| env.error(e))
+}
+
+fn trim_line_ending(line: &mut Vec<u8>) {
+    if line.ends_with(b"\n") {
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+    }
+}
+
 fn value_to_command(value: &Value, env: &Uiua) -> UiuaResult<(String, Vec<String>)> {
     let mut strings = Vec::new();
     match value {
@@ -2018,6 +3618,267 @@ fn value_to_command(value: &Value, env: &Uiua) -> UiuaResult<(String, Vec<String
     Ok((command, strings))
 }
 
+/// Get an environment variable, taking [`SysOp::SetVar`] and [`SysOp::UnsetVar`] into account
+fn env_var(name: &str, env: &Uiua) -> Option<String> {
+    match env.rt.env_vars.get(name) {
+        Some(val) => val.clone(),
+        None => env.rt.backend.var(name),
+    }
+}
+
+fn validate_var_name(name: &str, env: &Uiua) -> UiuaResult {
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(env.error(format!("{name:?} is not a valid environment variable name")));
+    }
+    Ok(())
+}
+
+fn command_env(env: &Uiua) -> CommandEnv {
+    CommandEnv {
+        dir: env.rt.working_dir.clone(),
+        vars: (env.rt.env_vars.iter())
+            .map(|(name, val)| (name.clone(), val.clone()))
+            .collect(),
+    }
+}
+
+/// Expand `~` and environment variables in a path, then resolve it against the working directory
+fn resolve_path(path: &str, env: &Uiua) -> UiuaResult<String> {
+    let expanded = expand_path(path, env)?;
+    Ok(match &env.rt.working_dir {
+        Some(dir) if Path::new(&expanded).is_relative() => {
+            dir.join(expanded).to_string_lossy().into_owned()
+        }
+        _ => expanded,
+    })
+}
+
+/// Expand `~` and environment variables in a path
+fn expand_path(path: &str, env: &Uiua) -> UiuaResult<String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    if let Some(after) = path.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            let home = (env_var("HOME", env).or_else(|| env_var("USERPROFILE", env))).ok_or_else(
+                || {
+                    env.error(format!(
+                        "Cannot expand ~ in {path:?} because the home directory is unknown"
+                    ))
+                },
+            )?;
+            expanded.push_str(&home);
+            rest = after;
+        }
+    }
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, tail) = if let Some(braced) = after.strip_prefix('{') {
+            let end = (braced.find('}'))
+                .ok_or_else(|| env.error(format!("Path {path:?} has an unclosed ${{")))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = (after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')))
+                .unwrap_or(after.len());
+            after.split_at(end)
+        };
+        // `$$` is a literal `$`
+        if let Some(after) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        // Something like `$5` is not a variable
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let val = env_var(name, env).ok_or_else(|| {
+            env.error(format!(
+                "Environment variable `{name}` in path {path:?} is not set"
+            ))
+        })?;
+        expanded.push_str(&val);
+        rest = tail;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Split a path into its components, keeping a root like `/` or `C:\` together
+fn split_path(path: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut prefix = None;
+    for comp in Path::new(path).components() {
+        match comp {
+            Component::Prefix(pre) => prefix = Some(pre.as_os_str().to_string_lossy().into_owned()),
+            Component::RootDir => {
+                let pre = prefix.take().unwrap_or_default();
+                parts.push(format!("{pre}{MAIN_SEPARATOR}"));
+            }
+            comp => {
+                parts.extend(prefix.take());
+                parts.push(comp.as_os_str().to_string_lossy().into_owned());
+            }
+        }
+    }
+    parts.extend(prefix);
+    parts
+}
+
+/// Check if the components of a path match the components of a glob pattern
+fn glob_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            for i in 0..=path.len() {
+                if glob_match(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i).is_some_and(|part| part.starts_with('.')) {
+                    return false;
+                }
+            }
+            false
+        }
+        Some((first, rest)) => path.split_first().is_some_and(|(part, path)| {
+            glob_match_component(first, part) && glob_match(rest, path)
+        }),
+    }
+}
+
+/// Check if a single path component matches a glob pattern
+fn glob_match_component(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let (pattern, name) = if cfg!(windows) {
+        (pattern.to_lowercase(), name.to_lowercase())
+    } else {
+        (pattern.into(), name.into())
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_chars(&pattern, &name)
+}
+
+fn glob_match_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_match_chars(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match_chars(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            match glob_class(rest, c) {
+                Some((matched, rest)) => matched && glob_match_chars(rest, &name[1..]),
+                // An unclosed bracket is literal
+                None => c == '[' && glob_match_chars(rest, &name[1..]),
+            }
+        }
+        Some((p, rest)) => name.first() == Some(p) && glob_match_chars(rest, &name[1..]),
+    }
+}
+
+/// Check if a character is in a bracketed glob class
+///
+/// Returns whether it matched and the rest of the pattern, or `None` if the class is unclosed
+fn glob_class(class: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negate, mut class) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        class => (false, class),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match class {
+            [] => return None,
+            [']', rest @ ..] if !first => return Some((matched != negate, rest)),
+            [lo, '-', hi, rest @ ..] if *hi != ']' => {
+                matched |= (*lo..=*hi).contains(&c);
+                class = rest;
+            }
+            [x, rest @ ..] => {
+                matched |= *x == c;
+                class = rest;
+            }
+        }
+        first = false;
+    }
+}
+
+/// Find the paths under a directory that match the rest of a glob pattern
+fn glob_walk(dir: PathBuf, parts: &[String], env: &Uiua, paths: &mut Vec<String>) {
+    let backend = &env.rt.backend;
+    let real = match &env.rt.working_dir {
+        Some(working) if dir.is_relative() => working.join(&dir),
+        _ if dir.as_os_str().is_empty() => PathBuf::from("."),
+        _ => dir.clone(),
+    };
+    let real = real.to_string_lossy();
+    let Some((part, rest)) = parts.split_first() else {
+        if backend.file_exists(&real) {
+            paths.push(dir.to_string_lossy().into_owned());
+        }
+        return;
+    };
+    let has_wildcard = part.contains(['*', '?', '[']);
+    if !has_wildcard {
+        glob_walk(dir.join(part), rest, env, paths);
+        return;
+    }
+    if part == "**" {
+        glob_walk(dir.clone(), rest, env, paths);
+        // Guard against symbolic link cycles
+        if dir.components().count() > 100 {
+            return;
+        }
+    }
+    let Ok(entries) = backend.list_dir(&real) else {
+        return;
+    };
+    for entry in entries {
+        let Some(name) = Path::new(&entry).file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        let child = dir.join(&*name);
+        let real_child = Path::new(&*real).join(&*name);
+        let is_dir = || backend.is_file(&real_child.to_string_lossy()) == Ok(false);
+        if part == "**" {
+            if !name.starts_with('.') && is_dir() {
+                glob_walk(child, parts, env, paths);
+            }
+        } else if glob_match_component(part, &name) && (rest.is_empty() || is_dir()) {
+            glob_walk(child, rest, env, paths);
+        }
+    }
+}
+
+/// Remove `.` and `..` components from a path without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(comp),
+            },
+            comp => normalized.push(comp),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
 /// Get the current time in seconds
 ///
 /// This function works on both native and web targets.
@@ -2056,3 +3917,130 @@ pub(crate) fn terminal_size() -> Option<(usize, usize)> {
     #[cfg(not(all(not(target_arch = "wasm32"), feature = "terminal_size")))]
     None
 }
+
+#[cfg(test)]
+#[test]
+fn log_levels() {
+    use crate::{SafeSys, Uiua};
+    let mut env = Uiua::with_safe_sys();
+    env.run_str("# Experimental!\n&logd \"hidden\"\n&logw \"shown\"")
+        .unwrap();
+    let stderr = env.downcast_backend::<SafeSys>().unwrap().take_stderr();
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert!(!stderr.contains("hidden"));
+    assert!(stderr.trim_end().ends_with("WARN  shown"), "{stderr}");
+}
+
+#[cfg(all(test, feature = "native_sys"))]
+#[test]
+fn using_handles() {
+    use crate::Uiua;
+    let path = std::env::temp_dir().join("uiua_using_handles.txt");
+    std::fs::write(&path, "hello").unwrap();
+    let mut env = Uiua::with_native_sys();
+    env.run_str(&format!("# Experimental!\nusing(&rs 3) &fo {path:?}"))
+        .unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(env.pop_string().unwrap(), "hel");
+    std::fs::remove_file(path).unwrap();
+    let code = "\
+# Experimental!
+Ch ← channel ∞
+⍣using(⍤\"Oops\" 0 ◌)◌ Ch
+send Ch 5";
+    let Err(err) = env.run_str(code) else {
+        panic!("Sending to a closed channel should fail");
+    };
+    assert!(err.to_string().contains("Invalid channel handle"), "{err}");
+    assert!(env.run_str("# Experimental!\nusing(+1) 5").is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn audio_buffer_stream() {
+    use crate::{AudioBufferFn, SysBackend, Uiua};
+    use parking_lot::Mutex;
+    use std::{any::Any, sync::Arc};
+    #[derive(Default, Clone)]
+    struct Speaker {
+        requests: Arc<Mutex<Vec<(u64, usize)>>>,
+        samples: Arc<Mutex<Vec<[f64; 2]>>>,
+    }
+    impl SysBackend for Speaker {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn stream_audio_buffers(&self, mut f: AudioBufferFn) -> Result<(), String> {
+            const LEN: usize = 4;
+            loop {
+                let start = self.samples.lock().len() as u64;
+                self.requests.lock().push((start, LEN));
+                let samples = f(start, LEN).map_err(|e| e.to_string())?;
+                let done = samples.len() < LEN;
+                self.samples.lock().extend(samples);
+                if done {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    let speaker = Speaker::default();
+    let mut env = Uiua::with_backend(speaker.clone());
+    env.run_str("&astream(÷10▽⊸<10+⊙⇡)").unwrap();
+    assert_eq!(*speaker.requests.lock(), [(0, 4), (4, 4), (8, 4)]);
+    let left: Vec<f64> = speaker.samples.lock().iter().map(|[l, _]| *l).collect();
+    assert_eq!(left, (0..10).map(|i| i as f64 / 10.0).collect::<Vec<_>>());
+    speaker.samples.lock().clear();
+    env.run_str("&astream(⍉⊟⟜¯÷10▽⊸<3+⊙⇡)").unwrap();
+    assert_eq!(
+        *speaker.samples.lock(),
+        [[0.0, -0.0], [0.1, -0.1], [0.2, -0.2]]
+    );
+    assert!(env.run_str("&astream(+1)").is_err());
+    assert!(env.run_str("&astream(↯3_3⋅⋅0)").is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn input_polling() {
+    use crate::{GamepadState, MouseState, SysBackend, Uiua};
+    use std::any::Any;
+    struct Controls;
+    impl SysBackend for Controls {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn mouse(&self) -> Result<MouseState, String> {
+            Ok(MouseState {
+                pos: [12.0, 34.0],
+                buttons: [true, false, false],
+                scroll: 0.0,
+            })
+        }
+        fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+            let mut state = GamepadState::default();
+            state.axes[0] = -0.5;
+            state.buttons[0] = 1.0;
+            Ok((index == 0).then_some(state))
+        }
+    }
+    let mut env = Uiua::with_backend(Controls);
+    let nums = |env: &mut Uiua, code: &str| {
+        env.run_str(&format!("# Experimental!\n{code}")).unwrap();
+        env.pop_num().unwrap()
+    };
+    assert_eq!(nums(&mut env, r#"get "x" &mouse"#), 12.0);
+    assert_eq!(nums(&mut env, r#"get "left" &mouse"#), 1.0);
+    assert_eq!(nums(&mut env, r#"get "right" &mouse"#), 0.0);
+    assert_eq!(nums(&mut env, r#"get "leftx" &gamepad 0"#), -0.5);
+    assert_eq!(nums(&mut env, r#"get "south" &gamepad 0"#), 1.0);
+    assert_eq!(nums(&mut env, r#"⧻&gamepad 0"#), 21.0);
+    assert_eq!(nums(&mut env, r#"⧻&gamepad 1"#), 0.0);
+    let mut safe = Uiua::with_safe_sys();
+    assert!(safe.run_str("# Experimental!\n&mouse").is_err());
+}
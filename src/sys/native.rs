@@ -19,9 +19,18 @@ use colored::Colorize;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 
+#[cfg(feature = "gamepad")]
+use crate::GamepadState;
+#[cfg(feature = "window")]
+use crate::MouseState;
 use crate::{
-    terminal_size, GitTarget, Handle, ReadLinesFn, ReadLinesReturnFn, Span, SysBackend, Uiua, Value,
+    encode::base64, terminal_size, CommandEnv, Email, FileMetadata, GitTarget, Handle, ReadLinesFn,
+    ReadLinesReturnFn, SmtpSecurity, SmtpServer, Span, SysBackend, Uiua, Value,
 };
+#[cfg(all(feature = "serial", unix))]
+use crate::{SerialFlowControl, SerialParity, SerialSettings};
+#[cfg(feature = "tls")]
+use crate::{TlsClientSettings, TlsServerSettings};
 
 /// The default native system backend
 #[derive(Default)]
@@ -29,6 +38,7 @@ pub struct NativeSys;
 
 struct GlobalNativeSys {
     output_enabled: AtomicBool,
+    output_redirect: parking_lot::RwLock<Option<OutputRedirect>>,
     next_handle: AtomicU64,
     files: DashMap<Handle, BufReader<File>>,
     child_stdins: DashMap<Handle, ChildStream<ChildStdin>>,
@@ -37,9 +47,12 @@ struct GlobalNativeSys {
     tcp_listeners: DashMap<Handle, TcpListener>,
     tls_listeners: DashMap<Handle, TlsListener>,
     tcp_sockets: DashMap<Handle, TcpStream>,
-    tls_sockets: DashMap<Handle, TlsSocket>,
+    tls_sockets: DashMap<Handle, Arc<TlsSocket>>,
+    serial_ports: DashMap<Handle, Arc<SerialPort>>,
     #[cfg(feature = "webcam")]
     cam_channels: DashMap<usize, WebcamChannel>,
+    #[cfg(feature = "gamepad")]
+    gilrs: parking_lot::Mutex<Option<gilrs::Gilrs>>,
     hostnames: DashMap<Handle, String>,
     git_paths: DashMap<String, Result<PathBuf, String>>,
     #[cfg(feature = "audio")]
@@ -59,7 +72,8 @@ enum SysStream<'a> {
     ChildStdout(dashmap::mapref::one::RefMut<'a, Handle, ChildStream<BufReader<ChildStdout>>>),
     ChildStderr(dashmap::mapref::one::RefMut<'a, Handle, ChildStream<BufReader<ChildStderr>>>),
     TcpSocket(dashmap::mapref::one::Ref<'a, Handle, TcpStream>),
-    TlsSocket(dashmap::mapref::one::Ref<'a, Handle, TlsSocket>),
+    TlsSocket(Arc<TlsSocket>),
+    SerialPort(Arc<SerialPort>),
 }
 
 struct ChildStream<T> {
@@ -194,16 +208,119 @@ impl Write for &TlsSocket {
     }
 }
 
+impl TlsSocket {
+    /// Tell the peer that the connection is closing
+    fn close(&self) -> std::io::Result<()> {
+        #[cfg(feature = "tls")]
+        {
+            match &mut *self.conn.lock() {
+                TslConnection::Client(conn) => {
+                    conn.send_close_notify();
+                    while conn.wants_write() {
+                        conn.write_tls(&mut &self.stream)?;
+                    }
+                }
+                TslConnection::Server(conn) => {
+                    conn.send_close_notify();
+                    while conn.wants_write() {
+                        conn.write_tls(&mut &self.stream)?;
+                    }
+                }
+            }
+        }
+        (&mut &self.stream).flush()
+    }
+}
+
+struct SerialPort {
+    file: File,
+    /// How long `serial_read` waits for data
+    #[cfg_attr(not(all(feature = "serial", unix)), allow(dead_code))]
+    timeout: Option<Duration>,
+}
+
 struct TlsListener {
     listener: TcpListener,
     #[cfg(feature = "tls")]
     config: std::sync::Arc<rustls::ServerConfig>,
 }
 
+/// Picks a server certificate by the name the client asked for with SNI
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct SniResolver {
+    by_name: std::collections::HashMap<String, Arc<rustls::sign::CertifiedKey>>,
+    default: Option<Arc<rustls::sign::CertifiedKey>>,
+}
+
+#[cfg(feature = "tls")]
+impl rustls::server::ResolvesServerCert for SniResolver {
+    fn resolve(
+        &self,
+        hello: rustls::server::ClientHello,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        (hello.server_name())
+            .and_then(|name| self.by_name.get(&name.to_lowercase()))
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_certs(pem: &[u8]) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>, String> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(pem))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if certs.is_empty() {
+        return Err("No certificates found".into());
+    }
+    Ok(certs)
+}
+
+#[cfg(feature = "tls")]
+fn tls_key(pem: &[u8]) -> Result<rustls::pki_types::PrivateKeyDer<'static>, String> {
+    rustls_pemfile::private_key(&mut BufReader::new(pem))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "No private key found".into())
+}
+
+#[cfg(feature = "tls")]
+fn tls_roots(pem: &[u8]) -> Result<rustls::RootCertStore, String> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in tls_certs(pem)? {
+        roots.add(cert).map_err(|e| e.to_string())?;
+    }
+    Ok(roots)
+}
+
+#[cfg(feature = "tls")]
+fn tls_certified_key(cert: &[u8], key: &[u8]) -> Result<Arc<rustls::sign::CertifiedKey>, String> {
+    let key = rustls::crypto::ring::sign::any_supported_type(&tls_key(key)?)
+        .map_err(|e| e.to_string())?;
+    Ok(Arc::new(rustls::sign::CertifiedKey::new(
+        tls_certs(cert)?,
+        key,
+    )))
+}
+
+/// Complete a TLS handshake so that errors are reported right away
+#[cfg(feature = "tls")]
+fn tls_handshake<D>(
+    conn: &mut rustls::ConnectionCommon<D>,
+    stream: &mut TcpStream,
+) -> Result<(), String> {
+    while conn.is_handshaking() {
+        conn.complete_io(stream)
+            .map_err(|e| format!("TLS handshake failed: {e}"))?;
+    }
+    Ok(())
+}
+
 impl Default for GlobalNativeSys {
     fn default() -> Self {
         Self {
             output_enabled: AtomicBool::new(true),
+            output_redirect: parking_lot::RwLock::new(None),
             next_handle: Handle::FIRST_UNRESERVED.0.into(),
             files: DashMap::new(),
             child_stdins: DashMap::new(),
@@ -213,8 +330,11 @@ impl Default for GlobalNativeSys {
             tls_listeners: DashMap::new(),
             tcp_sockets: DashMap::new(),
             tls_sockets: DashMap::new(),
+            serial_ports: DashMap::new(),
             #[cfg(feature = "webcam")]
             cam_channels: DashMap::new(),
+            #[cfg(feature = "gamepad")]
+            gilrs: parking_lot::Mutex::new(None),
             hostnames: DashMap::new(),
             git_paths: DashMap::new(),
             #[cfg(feature = "audio")]
@@ -239,8 +359,10 @@ impl GlobalNativeSys {
                 && !self.child_stdouts.contains_key(&handle)
                 && !self.child_stderrs.contains_key(&handle)
                 && !self.tcp_listeners.contains_key(&handle)
+                && !self.tls_listeners.contains_key(&handle)
                 && !self.tcp_sockets.contains_key(&handle)
                 && !self.tls_sockets.contains_key(&handle)
+                && !self.serial_ports.contains_key(&handle)
             {
                 return handle;
             }
@@ -259,7 +381,10 @@ impl GlobalNativeSys {
         } else if let Some(socket) = self.tcp_sockets.get(&handle) {
             SysStream::TcpSocket(socket)
         } else if let Some(tls_socket) = self.tls_sockets.get(&handle) {
-            SysStream::TlsSocket(tls_socket)
+            // Blocking IO must not hold the map's lock
+            SysStream::TlsSocket(Arc::clone(&tls_socket))
+        } else if let Some(port) = self.serial_ports.get(&handle) {
+            SysStream::SerialPort(Arc::clone(&port))
         } else {
             return Err("Invalid stream handle".to_string());
         })
@@ -307,6 +432,33 @@ pub(crate) fn set_output_enabled(enabled: bool) -> bool {
         .swap(enabled, atomic::Ordering::Relaxed)
 }
 
+/// A function that receives output instead of stdout and stderr
+///
+/// The second argument is whether the output is for stderr
+pub(crate) type OutputRedirect = Box<dyn Fn(&str, bool) + Send + Sync>;
+
+/// Send all output to a function instead of stdout and stderr
+///
+/// While output is redirected, stdin cannot be read
+#[cfg(feature = "dap")]
+pub(crate) fn set_output_redirect(redirect: Option<OutputRedirect>) {
+    *NATIVE_SYS.output_redirect.write() = redirect;
+}
+
+/// Send output to the redirect if there is one, returning whether it was sent
+fn redirect_output(s: &str, stderr: bool) -> bool {
+    if let Some(redirect) = &*NATIVE_SYS.output_redirect.read() {
+        redirect(s, stderr);
+        true
+    } else {
+        false
+    }
+}
+
+fn output_redirected() -> bool {
+    NATIVE_SYS.output_redirect.read().is_some()
+}
+
 impl SysBackend for NativeSys {
     fn any(&self) -> &dyn Any {
         self
@@ -315,7 +467,7 @@ impl SysBackend for NativeSys {
         self
     }
     fn print_str_stdout(&self, s: &str) -> Result<(), String> {
-        if !output_enabled() {
+        if !output_enabled() || redirect_output(s, false) {
             return Ok(());
         }
         let mut stdout = stdout().lock();
@@ -323,7 +475,7 @@ impl SysBackend for NativeSys {
         stdout.flush().map_err(|e| e.to_string())
     }
     fn print_str_stderr(&self, s: &str) -> Result<(), String> {
-        if !output_enabled() {
+        if !output_enabled() || redirect_output(s, true) {
             return Ok(());
         }
         let mut stderr = stderr().lock();
@@ -331,7 +483,7 @@ impl SysBackend for NativeSys {
         stderr.flush().map_err(|e| e.to_string())
     }
     fn print_str_trace(&self, s: &str) {
-        if !output_enabled() {
+        if !output_enabled() || redirect_output(s, true) {
             return;
         }
         eprint!("{s}");
@@ -344,10 +496,20 @@ impl SysBackend for NativeSys {
         }
         self.print_str_stdout(&format!("{}\n", value.show()))
     }
+    fn show_colored(&self, value: Value) -> Result<(), String> {
+        #[cfg(feature = "window")]
+        if crate::window::use_window() {
+            return self.show(value);
+        }
+        self.print_str_stdout(&format!("{}\n", value.show_colored()))
+    }
     fn scan_line_stdin(&self) -> Result<Option<String>, String> {
         if !output_enabled() {
             return Ok(None);
         }
+        if output_redirected() {
+            return Err("Reading from stdin is not supported while debugging".into());
+        }
         let mut buffer = Vec::new();
         let mut b = 0u8;
         loop {
@@ -372,6 +534,9 @@ impl SysBackend for NativeSys {
         if !output_enabled() {
             return Ok(Vec::new());
         }
+        if output_redirected() {
+            return Err("Reading from stdin is not supported while debugging".into());
+        }
         Ok(if let Some(count) = count {
             let mut buffer = vec![0; count];
             stdin().read_exact(&mut buffer).map_err(|e| e.to_string())?;
@@ -421,6 +586,26 @@ impl SysBackend for NativeSys {
             .map(|m| m.is_file())
             .map_err(|e| e.to_string())
     }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        let meta = fs::metadata(path).map_err(|e| e.to_string())?;
+        let modified = (meta.modified().ok())
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|dur| dur.as_secs_f64());
+        Ok(FileMetadata {
+            size: meta.len(),
+            modified,
+            is_dir: meta.is_dir(),
+        })
+    }
+    fn canonicalize(&self, path: &str) -> Result<String, String> {
+        let path = fs::canonicalize(path).map_err(|e| e.to_string())?;
+        let path = path.to_string_lossy();
+        // Windows adds a verbatim prefix that most programs do not understand
+        Ok(match path.strip_prefix(r"\\?\") {
+            Some(stripped) if !stripped.starts_with("UNC") => stripped.into(),
+            _ => path.into_owned(),
+        })
+    }
     fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
         let mut paths = Vec::new();
         for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
@@ -451,6 +636,59 @@ impl SysBackend for NativeSys {
         NATIVE_SYS.files.insert(handle, BufReader::new(file));
         Ok(handle)
     }
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        static TEMP_COUNT: AtomicU64 = AtomicU64::new(0);
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+        let temp_name = format!(
+            ".{}.{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id(),
+            TEMP_COUNT.fetch_add(1, atomic::Ordering::Relaxed)
+        );
+        let temp_path = path.with_file_name(temp_name);
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(contents)?;
+            // Keep the permissions of the file being replaced
+            if let Ok(meta) = fs::metadata(path) {
+                file.set_permissions(meta.permissions())?;
+            }
+            file.sync_all()?;
+            fs::rename(&temp_path, path)
+        };
+        write().map_err(|e| {
+            _ = fs::remove_file(&temp_path);
+            format!("{e} {}", path.display())
+        })
+    }
+    #[cfg(unix)]
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        use std::os::fd::AsRawFd;
+        // The clone shares the lock with the handle's file,
+        // so waiting on it does not block other uses of the file map
+        let file = (NATIVE_SYS.files.get(&handle))
+            .ok_or_else(|| "Invalid file handle".to_string())?
+            .get_ref()
+            .try_clone()
+            .map_err(|e| e.to_string())?;
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                return Ok(());
+            }
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error.to_string());
+            }
+        }
+    }
+    fn file_append(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        let mut file = (OpenOptions::new().create(true).append(true))
+            .open(path)
+            .map_err(|e| format!("{e} {}", path.display()))?;
+        file.write_all(contents).map_err(|e| e.to_string())
+    }
     fn make_dir(&self, path: &Path) -> Result<(), String> {
         fs::create_dir_all(path).map_err(|e| e.to_string())
     }
@@ -502,6 +740,14 @@ impl SysBackend for NativeSys {
                 buf.truncate(n);
                 buf
             }
+            SysStream::SerialPort(port) => {
+                let mut buf = vec![0; len];
+                let n = (&mut &port.file)
+                    .read(&mut buf)
+                    .map_err(|e| e.to_string())?;
+                buf.truncate(n);
+                buf
+            }
         })
     }
     fn read_all(&self, handle: Handle) -> Result<Vec<u8>, String> {
@@ -538,6 +784,11 @@ impl SysBackend for NativeSys {
                 ((&mut &*socket).read_to_end(&mut buf)).map_err(|e| e.to_string())?;
                 buf
             }
+            SysStream::SerialPort(port) => {
+                let mut buf = Vec::new();
+                ((&mut &port.file).read_to_end(&mut buf)).map_err(|e| e.to_string())?;
+                buf
+            }
         })
     }
     fn read_lines<'a>(&self, handle: Handle) -> Result<ReadLinesReturnFn<'a>, String> {
@@ -573,7 +824,14 @@ impl SysBackend for NativeSys {
                     }
                 }
                 SysStream::TlsSocket(socket) => {
-                    for line in &mut BufReader::new(&socket.stream).lines() {
+                    for line in &mut BufReader::new(&*socket).lines() {
+                        let line =
+                            line.map_err(|e| env.error(format!("Error reading line: {e}")))?;
+                        f(line, env)?;
+                    }
+                }
+                SysStream::SerialPort(port) => {
+                    for line in BufReader::new(&port.file).lines() {
                         let line =
                             line.map_err(|e| env.error(format!("Error reading line: {e}")))?;
                         f(line, env)?;
@@ -608,6 +866,9 @@ impl SysBackend for NativeSys {
             SysStream::TlsSocket(socket) => {
                 (&mut &*socket).write_all(conts).map_err(|e| e.to_string())
             }
+            SysStream::SerialPort(port) => (&mut &port.file)
+                .write_all(conts)
+                .map_err(|e| e.to_string()),
         }
     }
     #[cfg(feature = "clipboard")]
@@ -635,6 +896,10 @@ impl SysBackend for NativeSys {
     }
     #[cfg(all(feature = "terminal_image", feature = "image"))]
     fn show_image(&self, image: image::DynamicImage, _label: Option<&str>) -> Result<(), String> {
+        if output_redirected() {
+            let (w, h) = (image.width(), image.height());
+            return self.print_str_stdout(&format!("<{w}×{h} image>\n"));
+        }
         let (_width, _height) = if let Some((w, h)) = terminal_size() {
             let (tw, th) = (w as u32, h.saturating_sub(1) as u32);
             let (iw, ih) = (image.width(), (image.height() / 2).max(1));
@@ -804,6 +1069,62 @@ impl SysBackend for NativeSys {
             Err(e) => Err(format!("Failed to initialize audio output stream: {e}").to_string()),
         }
     }
+    #[cfg(feature = "audio")]
+    fn stream_audio_buffers(&self, f: crate::AudioBufferFn) -> Result<(), String> {
+        use hodaun::*;
+        struct TheSource {
+            index: u64,
+            samples: std::vec::IntoIter<[f64; 2]>,
+            done: bool,
+            f: crate::AudioBufferFn,
+        }
+        impl Source for TheSource {
+            type Frame = Stereo;
+            fn next(&mut self, sample_rate: f64) -> Option<Self::Frame> {
+                if let Some([left, right]) = self.samples.next() {
+                    return Some(Stereo { left, right });
+                }
+                if self.done {
+                    return None;
+                }
+                // Small buffers keep the latency low
+                const LEN: usize = 512;
+                if let Some(socket) = NATIVE_SYS.audio_time_socket.lock().as_ref() {
+                    let time = self.index as f64 / sample_rate;
+                    if let Err(e) = socket.send(&time.to_be_bytes()) {
+                        eprintln!("Failed to send audio time: {e}");
+                    }
+                }
+                match (self.f)(self.index, LEN) {
+                    Ok(samples) => {
+                        self.done = samples.len() < LEN;
+                        self.index += samples.len() as u64;
+                        self.samples = samples.into_iter();
+                        self.next(sample_rate)
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        None
+                    }
+                }
+            }
+        }
+        let start_time = NATIVE_SYS.audio_stream_time.lock().unwrap_or(0.0);
+        let source = TheSource {
+            index: (start_time * self.audio_sample_rate() as f64) as u64,
+            samples: Vec::new().into_iter(),
+            done: false,
+            f,
+        };
+        match default_output::<Stereo>() {
+            Ok(mut mixer) => {
+                mixer.add(source);
+                mixer.block();
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to initialize audio output stream: {e}").to_string()),
+        }
+    }
     fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
         let handle = NATIVE_SYS.new_handle();
         let listener = TcpListener::bind(addr).map_err(|e| e.to_string())?;
@@ -811,21 +1132,33 @@ impl SysBackend for NativeSys {
         Ok(handle)
     }
     #[cfg(feature = "tls")]
-    fn tls_listen(&self, addr: &str, cert: &[u8], key: &[u8]) -> Result<Handle, String> {
-        let handle = NATIVE_SYS.new_handle();
+    fn tls_listen(&self, addr: &str, settings: &TlsServerSettings) -> Result<Handle, String> {
+        let builder = rustls::ServerConfig::builder();
+        let builder = if let Some(roots) = &settings.client_roots {
+            let verifier = rustls::server::WebPkiClientVerifier::builder(tls_roots(roots)?.into())
+                .build()
+                .map_err(|e| e.to_string())?;
+            builder.with_client_cert_verifier(verifier)
+        } else {
+            builder.with_no_client_auth()
+        };
+        let default = (settings.identity.as_ref())
+            .map(|(cert, key)| tls_certified_key(cert, key))
+            .transpose()?;
+        let mut by_name = std::collections::HashMap::new();
+        for (host, cert, key) in &settings.sni {
+            by_name.insert(host.to_lowercase(), tls_certified_key(cert, key)?);
+        }
+        let mut config = builder.with_cert_resolver(Arc::new(SniResolver { by_name, default }));
+        config.alpn_protocols = (settings.alpn.iter())
+            .map(|p| p.as_bytes().to_vec())
+            .collect();
         let listener = TcpListener::bind(addr).map_err(|e| e.to_string())?;
-        let certs = rustls_pemfile::certs(&mut BufReader::new(cert))
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
-        let private_key = rustls_pemfile::private_key(&mut BufReader::new(key))
-            .map_err(|e| e.to_string())?
-            .ok_or("No private key found")?;
-        let config = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, private_key)
-            .map_err(|e| e.to_string())?
-            .into();
-        let listener = TlsListener { listener, config };
+        let handle = NATIVE_SYS.new_handle();
+        let listener = TlsListener {
+            listener,
+            config: config.into(),
+        };
         NATIVE_SYS.tls_listeners.insert(handle, listener);
         Ok(handle)
     }
@@ -838,24 +1171,22 @@ impl SysBackend for NativeSys {
             Ok(handle)
         } else {
             #[cfg(feature = "tls")]
-            {
-                if let Some(listener) = NATIVE_SYS.tls_listeners.get_mut(&handle) {
-                    let (stream, _) = listener.listener.accept().map_err(|e| e.to_string())?;
-                    let conn = rustls::ServerConnection::new(listener.config.clone())
-                        .map_err(|e| e.to_string())?;
-                    let handle = NATIVE_SYS.new_handle();
-                    NATIVE_SYS.tls_sockets.insert(
-                        handle,
-                        TlsSocket {
-                            stream,
-                            #[cfg(feature = "tls")]
-                            conn: parking_lot::Mutex::new(TslConnection::Server(conn)),
-                        },
-                    );
-                }
+            if let Some(listener) = NATIVE_SYS.tls_listeners.get(&handle) {
+                let (mut stream, _) = listener.listener.accept().map_err(|e| e.to_string())?;
+                let config = listener.config.clone();
+                drop(listener);
+                let mut conn = rustls::ServerConnection::new(config).map_err(|e| e.to_string())?;
+                tls_handshake(&mut conn, &mut stream)?;
+                let handle = NATIVE_SYS.new_handle();
+                NATIVE_SYS.tls_sockets.insert(
+                    handle,
+                    Arc::new(TlsSocket {
+                        stream,
+                        conn: parking_lot::Mutex::new(TslConnection::Server(conn)),
+                    }),
+                );
                 return Ok(handle);
             }
-            #[allow(unreachable_code)]
             Err("Invalid tcp listener handle".to_string())
         }
     }
@@ -870,16 +1201,28 @@ impl SysBackend for NativeSys {
         Ok(handle)
     }
     #[cfg(feature = "tls")]
-    fn tls_connect(&self, addr: &str) -> Result<Handle, String> {
-        let handle = NATIVE_SYS.new_handle();
-        let root_store =
-            rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-        let config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-        let stream = TcpStream::connect(addr).map_err(|e| e.to_string())?;
-        let server_name = if let Some(name) =
-            (addr.split(':').next()).and_then(|addr| addr.to_string().try_into().ok())
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        let roots = match &settings.roots {
+            Some(roots) => tls_roots(roots)?,
+            None => {
+                rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned())
+            }
+        };
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+        let mut config = if let Some((cert, key)) = &settings.identity {
+            (builder.with_client_auth_cert(tls_certs(cert)?, tls_key(key)?))
+                .map_err(|e| e.to_string())?
+        } else {
+            builder.with_no_client_auth()
+        };
+        config.alpn_protocols = (settings.alpn.iter())
+            .map(|p| p.as_bytes().to_vec())
+            .collect();
+        let mut stream = TcpStream::connect(addr).map_err(|e| e.to_string())?;
+        let server_name = if let Some(name) = &settings.server_name {
+            rustls::pki_types::ServerName::try_from(name.clone()).map_err(|e| e.to_string())?
+        } else if let Some(name) = (addr.rsplit_once(':'))
+            .and_then(|(host, _)| host.trim_matches(['[', ']']).to_string().try_into().ok())
         {
             name
         } else {
@@ -887,18 +1230,30 @@ impl SysBackend for NativeSys {
                 stream.peer_addr().map_err(|e| e.to_string())?.ip().into(),
             )
         };
-        let client = rustls::ClientConnection::new(config.clone().into(), server_name.clone())
-            .map_err(|e| e.to_string())?;
+        let mut client =
+            rustls::ClientConnection::new(config.into(), server_name).map_err(|e| e.to_string())?;
+        tls_handshake(&mut client, &mut stream)?;
+        let handle = NATIVE_SYS.new_handle();
         NATIVE_SYS.tls_sockets.insert(
             handle,
-            TlsSocket {
+            Arc::new(TlsSocket {
                 stream,
-                #[cfg(feature = "tls")]
                 conn: parking_lot::Mutex::new(TslConnection::Client(client)),
-            },
+            }),
         );
         Ok(handle)
     }
+    #[cfg(feature = "tls")]
+    fn tls_alpn(&self, handle: Handle) -> Result<Option<String>, String> {
+        let socket = (NATIVE_SYS.tls_sockets.get(&handle))
+            .ok_or_else(|| "Invalid tls socket handle".to_string())?;
+        let conn = socket.conn.lock();
+        let protocol = match &*conn {
+            TslConnection::Client(conn) => conn.alpn_protocol(),
+            TslConnection::Server(conn) => conn.alpn_protocol(),
+        };
+        Ok(protocol.map(|p| String::from_utf8_lossy(p).into_owned()))
+    }
     fn tcp_addr(&self, handle: Handle) -> Result<SocketAddr, String> {
         (NATIVE_SYS.get_tcp_stream(handle, |s| s.peer_addr()))
             .or_else(|| NATIVE_SYS.get_tcp_listener(handle, |l| l.local_addr()))
@@ -944,7 +1299,9 @@ impl SysBackend for NativeSys {
             (&mut &socket).flush().map_err(|e| e.to_string())
         } else if let Some((_, socket)) = NATIVE_SYS.tls_sockets.remove(&handle) {
             NATIVE_SYS.hostnames.remove(&handle);
-            (&mut &socket).flush().map_err(|e| e.to_string())
+            socket.close().map_err(|e| e.to_string())
+        } else if NATIVE_SYS.serial_ports.remove(&handle).is_some() {
+            Ok(())
         } else if NATIVE_SYS.tcp_listeners.remove(&handle).is_some()
             || NATIVE_SYS.tls_listeners.remove(&handle).is_some()
         {
@@ -958,9 +1315,13 @@ impl SysBackend for NativeSys {
     fn invoke(&self, path: &str) -> Result<(), String> {
         open::that(path).map_err(|e| e.to_string())
     }
-    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
-        let status = Command::new(command)
-            .args(args)
+    fn run_command_inherit(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<i32, String> {
+        let status = new_command(command, args, cmd_env)
             .spawn()
             .map_err(|e| e.to_string())?
             .wait()
@@ -971,9 +1332,9 @@ impl SysBackend for NativeSys {
         &self,
         command: &str,
         args: &[&str],
+        cmd_env: &CommandEnv,
     ) -> Result<(i32, String, String), String> {
-        let output = Command::new(command)
-            .args(args)
+        let output = new_command(command, args, cmd_env)
             .output()
             .map_err(|e| e.to_string())?;
         Ok((
@@ -982,9 +1343,13 @@ impl SysBackend for NativeSys {
             String::from_utf8_lossy(&output.stderr).into(),
         ))
     }
-    fn run_command_stream(&self, command: &str, args: &[&str]) -> Result<[Handle; 3], String> {
-        let mut child = Command::new(command)
-            .args(args)
+    fn run_command_stream(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<[Handle; 3], String> {
+        let mut child = new_command(command, args, cmd_env)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -1020,8 +1385,9 @@ impl SysBackend for NativeSys {
         );
         Ok([stdin_handle, stdout_handle, stderr_handle])
     }
-    fn change_directory(&self, path: &str) -> Result<(), String> {
-        env::set_current_dir(path).map_err(|e| e.to_string())
+    fn current_dir(&self) -> Result<String, String> {
+        let dir = env::current_dir().map_err(|e| e.to_string())?;
+        Ok(dir.to_string_lossy().into_owned())
     }
     #[cfg(feature = "tls")]
     fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
@@ -1071,6 +1437,63 @@ impl SysBackend for NativeSys {
 
         Ok(s)
     }
+    fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+        smtp_send(server, email).map_err(|e| format!("Failed to send email: {e}"))
+    }
+    #[cfg(all(feature = "serial", unix))]
+    fn serial_ports(&self) -> Result<Vec<String>, String> {
+        serial_ports().map_err(|e| format!("Unable to list serial ports: {e}"))
+    }
+    #[cfg(all(feature = "serial", unix))]
+    fn serial_open(&self, path: &str, settings: &SerialSettings) -> Result<Handle, String> {
+        let file = serial_open(path, settings)
+            .map_err(|e| format!("Unable to open serial port {path}: {e}"))?;
+        let handle = NATIVE_SYS.new_handle();
+        let port = SerialPort {
+            file,
+            timeout: settings.timeout,
+        };
+        NATIVE_SYS.serial_ports.insert(handle, Arc::new(port));
+        Ok(handle)
+    }
+    #[cfg(all(feature = "serial", unix))]
+    fn serial_read(&self, handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String> {
+        // Blocking IO must not hold the map's lock
+        let port = (NATIVE_SYS.serial_ports.get(&handle))
+            .map(|port| Arc::clone(&port))
+            .ok_or_else(|| "Invalid serial port handle".to_string())?;
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+        while count.map_or(true, |count| buf.len() < count) {
+            if !serial_wait(&port).map_err(|e| e.to_string())? {
+                break;
+            }
+            let max = count.map_or(chunk.len(), |count| (count - buf.len()).min(chunk.len()));
+            let n = (&port.file)
+                .read(&mut chunk[..max])
+                .map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        Ok(buf)
+    }
+    #[cfg(all(feature = "serial", unix))]
+    fn serial_write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        use std::os::fd::AsRawFd;
+        let port = (NATIVE_SYS.serial_ports.get(&handle))
+            .map(|port| Arc::clone(&port))
+            .ok_or_else(|| "Invalid serial port handle".to_string())?;
+        (&port.file)
+            .write_all(contents)
+            .map_err(|e| e.to_string())?;
+        // Wait for the data to actually be transmitted
+        if unsafe { libc::tcdrain(port.file.as_raw_fd()) } != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
     #[cfg(feature = "webcam")]
     fn webcam_capture(&self, index: usize) -> Result<crate::WebcamImage, String> {
         let cam_channels = &NATIVE_SYS.cam_channels;
@@ -1100,6 +1523,59 @@ impl SysBackend for NativeSys {
             }
         }
     }
+    #[cfg(feature = "window")]
+    fn mouse(&self) -> Result<MouseState, String> {
+        if !crate::window::use_window() {
+            return Err("Mouse input requires the --window flag".into());
+        }
+        crate::window::poll_mouse()
+    }
+    #[cfg(feature = "gamepad")]
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        use gilrs::{Axis, Button, Gilrs};
+        const AXES: [Axis; 4] = [
+            Axis::LeftStickX,
+            Axis::LeftStickY,
+            Axis::RightStickX,
+            Axis::RightStickY,
+        ];
+        const BUTTONS: [Button; 17] = [
+            Button::South,
+            Button::East,
+            Button::West,
+            Button::North,
+            Button::LeftTrigger,
+            Button::RightTrigger,
+            Button::LeftTrigger2,
+            Button::RightTrigger2,
+            Button::Select,
+            Button::Start,
+            Button::LeftThumb,
+            Button::RightThumb,
+            Button::DPadUp,
+            Button::DPadDown,
+            Button::DPadLeft,
+            Button::DPadRight,
+            Button::Mode,
+        ];
+        let mut gilrs = NATIVE_SYS.gilrs.lock();
+        if gilrs.is_none() {
+            *gilrs = Some(Gilrs::new().map_err(|e| e.to_string())?);
+        }
+        let gilrs = gilrs.as_mut().unwrap();
+        // Gamepad state is only updated by handling events
+        while gilrs.next_event().is_some() {}
+        let Some((_, gamepad)) = gilrs.gamepads().nth(index) else {
+            return Ok(None);
+        };
+        let mut axes = AXES.map(|axis| gamepad.value(axis) as f64);
+        // Positive y is up in gilrs but down in the standard layout
+        axes[1] = -axes[1];
+        axes[3] = -axes[3];
+        let buttons = BUTTONS
+            .map(|button| (gamepad.button_data(button)).map_or(0.0, |data| data.value() as f64));
+        Ok(Some(GamepadState { axes, buttons }))
+    }
     #[cfg(feature = "ffi")]
     fn ffi(
         &self,
@@ -1221,8 +1697,26 @@ impl SysBackend for NativeSys {
         NATIVE_SYS.git_paths.insert(url.to_string(), res.clone());
         res
     }
+    fn load_package(&self, name: &str) -> Result<PathBuf, String> {
+        use crate::package::*;
+        if !Path::new(LOCKFILE).exists() {
+            return Err(format!(
+                "There is no {LOCKFILE} in this directory. \
+                Add packages with `uiua add` or run `uiua module lock`."
+            ));
+        }
+        let lockfile = Lockfile::load(LOCKFILE)?;
+        let package = lockfile.get(name).ok_or_else(|| {
+            format!("Package `{name}` is not in {LOCKFILE}. Add it with `uiua add`.")
+        })?;
+        let lib_path = install(package)?.join("lib.ua");
+        if !lib_path.exists() {
+            return Err(format!("Package `{name}` does not have a lib.ua file"));
+        }
+        Ok(lib_path)
+    }
     fn breakpoint(&self, env: &Uiua) -> Result<bool, String> {
-        if !output_enabled() {
+        if !output_enabled() || output_redirected() {
             return Ok(true);
         }
         match env.span() {
@@ -1263,12 +1757,16 @@ pub fn print_stack(stack: &[Value], color: bool) {
         );
         return;
     }
-    if stack.len() == 1 || !color {
+    if !color {
         for value in stack {
             println!("{}", value.show()); // Allow println
         }
         return;
     }
+    if let [value] = stack {
+        println!("{}", value.show_colored()); // Allow println
+        return;
+    }
     #[cfg(feature = "terminal-light")]
     let is_light = terminal_light::luma().is_ok_and(|luma| luma > 0.6);
     #[cfg(not(feature = "terminal-light"))]
@@ -1288,6 +1786,15 @@ pub fn print_stack(stack: &[Value], color: bool) {
     }
 }
 
+/// Check whether colored output should be used by default
+///
+/// This is true when stdout is a terminal and `NO_COLOR` is not set.
+#[doc(hidden)]
+pub fn default_color_output() -> bool {
+    use std::io::IsTerminal;
+    stdout().is_terminal() && env::var_os("NO_COLOR").map_or(true, |s| s.is_empty())
+}
+
 /// Takes an HTTP request, validates it, and fixes it (if possible) by adding
 /// the HTTP version and trailing newlines if they aren't present.
 ///
@@ -1300,6 +1807,21 @@ pub fn print_stack(stack: &[Value], color: bool) {
 ///     "GET / HTTP/1.0\r\nhost: example.com\r\n\r\n"
 /// )
 /// ```
+fn new_command(command: &str, args: &[&str], cmd_env: &CommandEnv) -> Command {
+    let mut command = Command::new(command);
+    command.args(args);
+    if let Some(dir) = &cmd_env.dir {
+        command.current_dir(dir);
+    }
+    for (name, val) in &cmd_env.vars {
+        match val {
+            Some(val) => command.env(name, val),
+            None => command.env_remove(name),
+        };
+    }
+    command
+}
+
 #[cfg(feature = "tls")]
 fn check_http(mut request: String, hostname: &str) -> Result<String, String> {
     let mut headers = [httparse::EMPTY_HEADER; 64];
@@ -1375,3 +1897,641 @@ fn check_http(mut request: String, hostname: &str) -> Result<String, String> {
 
     Ok(request)
 }
+
+/// A connection to an SMTP server
+enum SmtpStream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Read for SmtpStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SmtpStream::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            SmtpStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SmtpStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            SmtpStream::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            SmtpStream::Tls(stream) => stream.write(buf),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            SmtpStream::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            SmtpStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn smtp_tls(stream: TcpStream, host: &str) -> Result<SmtpStream, String> {
+    let root_store =
+        rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    let name =
+        rustls::pki_types::ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+    let conn = rustls::ClientConnection::new(config.into(), name).map_err(|e| e.to_string())?;
+    Ok(SmtpStream::Tls(Box::new(rustls::StreamOwned::new(
+        conn, stream,
+    ))))
+}
+
+#[cfg(not(feature = "tls"))]
+fn smtp_tls(_: TcpStream, _: &str) -> Result<SmtpStream, String> {
+    Err("TLS is not enabled in this environment".into())
+}
+
+/// Read a reply and check that it has the same class as the expected code
+///
+/// Returns the text of each line of the reply
+fn smtp_reply(conn: &mut BufReader<SmtpStream>, expected: u16) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if conn.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("Connection closed by server".into());
+        }
+        let line = line.trim_end();
+        let code: u16 = (line.get(..3).and_then(|code| code.parse().ok()))
+            .ok_or_else(|| format!("Invalid reply {line:?}"))?;
+        if code / 100 != expected / 100 {
+            return Err(format!("Server replied {line:?}"));
+        }
+        lines.push(line.get(4..).unwrap_or("").to_string());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(lines);
+        }
+    }
+}
+
+fn smtp_command(
+    conn: &mut BufReader<SmtpStream>,
+    command: &str,
+    expected: u16,
+) -> Result<Vec<String>, String> {
+    let stream = conn.get_mut();
+    (stream.write_all(format!("{command}\r\n").as_bytes()))
+        .and_then(|_| stream.flush())
+        .map_err(|e| e.to_string())?;
+    smtp_reply(conn, expected)
+}
+
+fn smtp_send(server: &SmtpServer, email: &Email) -> Result<(), String> {
+    let stream =
+        TcpStream::connect((server.host.as_str(), server.port)).map_err(|e| e.to_string())?;
+    let timeout = Some(Duration::from_secs(30));
+    (stream.set_read_timeout(timeout))
+        .and_then(|_| stream.set_write_timeout(timeout))
+        .map_err(|e| e.to_string())?;
+    let mut conn = BufReader::new(match server.security {
+        SmtpSecurity::Tls => smtp_tls(stream, &server.host)?,
+        _ => SmtpStream::Plain(stream),
+    });
+    smtp_reply(&mut conn, 220)?;
+    let mut extensions = smtp_command(&mut conn, "EHLO localhost", 250)?;
+    if server.security == SmtpSecurity::StartTls {
+        if !(extensions.iter()).any(|ext| ext.eq_ignore_ascii_case("STARTTLS")) {
+            return Err("Server does not support STARTTLS".into());
+        }
+        smtp_command(&mut conn, "STARTTLS", 220)?;
+        #[allow(irrefutable_let_patterns)]
+        let SmtpStream::Plain(stream) = conn.into_inner() else {
+            unreachable!("STARTTLS on a TLS connection")
+        };
+        conn = BufReader::new(smtp_tls(stream, &server.host)?);
+        extensions = smtp_command(&mut conn, "EHLO localhost", 250)?;
+    }
+    if let Some(username) = &server.username {
+        let password = server.password.as_deref().unwrap_or("");
+        let mechanisms = (extensions.iter())
+            .find_map(|ext| {
+                ext.get(..5)?
+                    .eq_ignore_ascii_case("AUTH ")
+                    .then(|| &ext[5..])
+            })
+            .unwrap_or("")
+            .to_uppercase();
+        let mut mechanisms = mechanisms.split_whitespace();
+        if !mechanisms.clone().any(|m| m == "PLAIN") && mechanisms.any(|m| m == "LOGIN") {
+            smtp_command(&mut conn, "AUTH LOGIN", 334)?;
+            smtp_command(&mut conn, &base64(username.as_bytes()), 334)?;
+            smtp_command(&mut conn, &base64(password.as_bytes()), 235)?;
+        } else {
+            let credentials = format!("\0{username}\0{password}");
+            let command = format!("AUTH PLAIN {}", base64(credentials.as_bytes()));
+            smtp_command(&mut conn, &command, 235)?;
+        }
+    }
+    smtp_command(&mut conn, &format!("MAIL FROM:<{}>", email.from), 250)?;
+    for recipient in &email.recipients {
+        smtp_command(&mut conn, &format!("RCPT TO:<{recipient}>"), 250)?;
+    }
+    smtp_command(&mut conn, "DATA", 354)?;
+    // Lines starting with a period are escaped with another
+    let mut data = String::with_capacity(email.message.len() + 5);
+    for line in email.message.lines() {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push('.');
+    smtp_command(&mut conn, &data, 250)?;
+    _ = smtp_command(&mut conn, "QUIT", 221);
+    Ok(())
+}
+
+/// List serial ports by looking for the devices that have drivers
+#[cfg(all(feature = "serial", unix))]
+fn serial_ports() -> std::io::Result<Vec<String>> {
+    let mut ports = Vec::new();
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        for entry in fs::read_dir("/sys/class/tty")? {
+            let entry = entry?;
+            let Ok(device) = fs::canonicalize(entry.path().join("device")) else {
+                continue;
+            };
+            // The kernel creates placeholder ports whether or not there is hardware
+            if device.file_name().is_some_and(|name| name == "serial8250") {
+                continue;
+            }
+            ports.push(format!("/dev/{}", entry.file_name().to_string_lossy()));
+        }
+    } else {
+        for entry in fs::read_dir("/dev")? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if ["cu.", "cuaU", "ttyU"]
+                .iter()
+                .any(|pre| name.starts_with(pre))
+            {
+                ports.push(format!("/dev/{name}"));
+            }
+        }
+    }
+    ports.sort_unstable();
+    Ok(ports)
+}
+
+#[cfg(all(feature = "serial", unix))]
+fn serial_open(path: &str, settings: &SerialSettings) -> std::io::Result<File> {
+    use std::os::{fd::AsRawFd, unix::fs::OpenOptionsExt};
+    let check = |res: libc::c_int| {
+        if res < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(res)
+        }
+    };
+    // Opening without `O_NONBLOCK` can hang waiting for a carrier signal
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(path)?;
+    let fd = file.as_raw_fd();
+    let speed = serial_speed(settings.baud)?;
+    unsafe {
+        let mut tty: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut tty))?;
+        libc::cfmakeraw(&mut tty);
+        check(libc::cfsetispeed(&mut tty, speed))?;
+        check(libc::cfsetospeed(&mut tty, speed))?;
+        tty.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB | libc::CRTSCTS);
+        tty.c_cflag |= libc::CLOCAL
+            | libc::CREAD
+            | match settings.data_bits {
+                5 => libc::CS5,
+                6 => libc::CS6,
+                7 => libc::CS7,
+                _ => libc::CS8,
+            };
+        match settings.parity {
+            SerialParity::None => {}
+            SerialParity::Odd => tty.c_cflag |= libc::PARENB | libc::PARODD,
+            SerialParity::Even => tty.c_cflag |= libc::PARENB,
+        }
+        if settings.stop_bits == 2 {
+            tty.c_cflag |= libc::CSTOPB;
+        }
+        tty.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+        match settings.flow_control {
+            SerialFlowControl::None => {}
+            SerialFlowControl::Software => tty.c_iflag |= libc::IXON | libc::IXOFF,
+            SerialFlowControl::Hardware => tty.c_cflag |= libc::CRTSCTS,
+        }
+        // Reads block until at least one byte arrives
+        tty.c_cc[libc::VMIN] = 1;
+        tty.c_cc[libc::VTIME] = 0;
+        check(libc::tcsetattr(fd, libc::TCSANOW, &tty))?;
+        // Discard anything received before the port was configured
+        check(libc::tcflush(fd, libc::TCIFLUSH))?;
+        let flags = check(libc::fcntl(fd, libc::F_GETFL))?;
+        check(libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK))?;
+    }
+    Ok(file)
+}
+
+#[cfg(all(feature = "serial", unix))]
+fn serial_speed(baud: u32) -> std::io::Result<libc::speed_t> {
+    use libc::*;
+    Ok(match baud {
+        50 => B50,
+        75 => B75,
+        110 => B110,
+        134 => B134,
+        150 => B150,
+        200 => B200,
+        300 => B300,
+        600 => B600,
+        1200 => B1200,
+        1800 => B1800,
+        2400 => B2400,
+        4800 => B4800,
+        9600 => B9600,
+        19200 => B19200,
+        38400 => B38400,
+        57600 => B57600,
+        115200 => B115200,
+        230400 => B230400,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        460800 => B460800,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        500000 => B500000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        576000 => B576000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        921600 => B921600,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        1000000 => B1000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        1500000 => B1500000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        2000000 => B2000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        3000000 => B3000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        4000000 => B4000000,
+        // Other systems take the baud rate directly
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        baud => baud as speed_t,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        baud => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {baud}"),
+            ))
+        }
+    })
+}
+
+/// Wait for a serial port to have data to read
+///
+/// Returns `false` if the port's timeout passed first
+#[cfg(all(feature = "serial", unix))]
+fn serial_wait(port: &SerialPort) -> std::io::Result<bool> {
+    use std::os::fd::AsRawFd;
+    let timeout = (port.timeout).map_or(-1, |timeout| {
+        timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int
+    });
+    let mut fd = libc::pollfd {
+        fd: port.file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut fd, 1, timeout) } {
+            0 => return Ok(false),
+            n if n > 0 => return Ok(true),
+            _ => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn smtp_send_email() {
+    use crate::{Email, SmtpSecurity, SmtpServer, SysBackend, Uiua};
+    use std::{
+        any::Any,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+    #[derive(Default)]
+    struct Outbox(Arc<Mutex<Vec<(SmtpServer, Email)>>>);
+    impl SysBackend for Outbox {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+            self.0.lock().unwrap().push((server.clone(), email.clone()));
+            Ok(())
+        }
+    }
+    let outbox = Outbox::default();
+    let sent = outbox.0.clone();
+    let mut env = Uiua::with_backend(outbox);
+    let code = r#"# Experimental!
+&smtpsend map {"host" "username" "password"} {"mail.example.com" "me" "pw"} map {"from" "to" "bcc" "subject" "body"} {"Me <me@example.com>" {"a@example.com" "b@example.com"} "c@example.com" "Alert" "Disk full\n.\nDone"}
+&smtpsend map {"host" "port"} {"localhost" 2525} map {"from" "to" "subject" "attachments"} {"me@example.com" "a@example.com" "Olá" map {"log.txt" "data.bin"} {"hi" [1 2 3]}}"#;
+    env.run_str(code).unwrap_or_else(|e| panic!("{e}"));
+    let sent = sent.lock().unwrap().clone();
+    let (server, email) = &sent[0];
+    assert_eq!(server.port, 465);
+    assert_eq!(server.security, SmtpSecurity::Tls);
+    assert_eq!(server.username.as_deref(), Some("me"));
+    assert_eq!(email.from, "me@example.com");
+    assert_eq!(
+        email.recipients,
+        ["a@example.com", "b@example.com", "c@example.com"]
+    );
+    assert!(email.message.contains("From: Me <me@example.com>\r\n"));
+    assert!(email
+        .message
+        .contains("To: a@example.com, b@example.com\r\n"));
+    assert!(!email.message.contains("c@example.com"));
+    assert!(email
+        .message
+        .ends_with("\r\n\r\nDisk full\r\n.\r\nDone\r\n"));
+    let (server, email) = &sent[1];
+    assert_eq!(server.security, SmtpSecurity::StartTls);
+    assert!(email.message.contains("Subject: =?UTF-8?B?T2zDoQ==?=\r\n"));
+    assert!(email
+        .message
+        .contains("Content-Type: text/plain; name=\"log.txt\"\r\n"));
+    assert!(email.message.contains("\r\n\r\naGk=\r\n"));
+    assert!(email.message.contains("\r\n\r\nAQID\r\n"));
+    for code in [
+        r#"&smtpsend {"localhost"} map {"from" "to"} {"a" "b"}"#,
+        r#"&smtpsend map {"port"} {25} map {"from" "to"} {"a" "b"}"#,
+        r#"&smtpsend map {"host"} {"localhost"} map {"from"} {"a"}"#,
+        r#"&smtpsend map {"host"} {"localhost"} map {"to"} {"a"}"#,
+        r#"&smtpsend map {"host" "security"} {"localhost" "ssl"} map {"from" "to"} {"a" "b"}"#,
+        r#"&smtpsend map {"host"} {"localhost"} map {"from" "to" "subject"} {"a" "b" "x\ny"}"#,
+        r#"&smtpsend map {"host"} {"localhost"} map {"from" "to" "reply"} {"a" "b" "c"}"#,
+    ] {
+        let res = env.run_str(&format!("# Experimental!\n{code}"));
+        assert!(res.is_err(), "{code}");
+    }
+
+    // Talk to a fake server over an unsecured connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+        let mut received = Vec::new();
+        let mut in_data = false;
+        stream.write_all(b"220 fake\r\n").unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            let reply: &[u8] = if in_data {
+                if line == "." {
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else {
+                    received.push(line);
+                    continue;
+                }
+            } else if line.starts_with("EHLO") {
+                b"250-fake\r\n250 AUTH LOGIN\r\n"
+            } else if line == "AUTH LOGIN" || line == "bWU=" {
+                b"334 ok\r\n"
+            } else if line == "cHc=" {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go\r\n"
+            } else if line == "QUIT" {
+                stream.write_all(b"221 bye\r\n").unwrap();
+                break;
+            } else {
+                received.push(line);
+                b"250 ok\r\n"
+            };
+            stream.write_all(reply).unwrap();
+        }
+        received
+    });
+    let mut env = Uiua::with_native_sys();
+    let code = format!(
+        r#"# Experimental!
+&smtpsend map {{"host" "port" "security" "username" "password"}} {{"127.0.0.1" {port} "none" "me" "pw"}} map {{"from" "to" "body"}} {{"me@example.com" "you@example.com" ".dot"}}"#
+    );
+    env.run_str(&code).unwrap_or_else(|e| panic!("{e}"));
+    let received = server.join().unwrap();
+    assert_eq!(received[0], "MAIL FROM:<me@example.com>");
+    assert_eq!(received[1], "RCPT TO:<you@example.com>");
+    assert_eq!(received.last().unwrap(), "..dot");
+}
+
+#[cfg(test)]
+#[test]
+fn tls_streams() {
+    use crate::{Uiua, Value};
+    use std::sync::mpsc::channel;
+    let read = "# Experimental!\nRead ← &frab ⊂\"tests_special/tls/\"\n";
+    let (addr_send, addr_recv) = channel();
+    let server = std::thread::spawn(move || {
+        let mut env = Uiua::with_native_sys();
+        let code = format!(
+            "{read}&tlsl \"127.0.0.1:0\" map {{\"cert\" \"key\" \"sni\" \"alpn\" \"clientroots\"}} \
+            {{Read \"server.pem\" Read \"server.key\" map {{\"other.test\"}} {{{{Read \"other.pem\" Read \"other.key\"}}}} {{\"h2\" \"echo\"}} Read \"ca.pem\"}}"
+        );
+        env.run_str(&code).unwrap_or_else(|e| panic!("{e}"));
+        let listener = env.pop("listener").unwrap();
+        env.push(listener.clone());
+        env.run_str("# Experimental!\n&tcpaddr").unwrap();
+        addr_send.send(env.pop_string().unwrap()).unwrap();
+        (0..4)
+            .map(|_| {
+                env.push(listener.clone());
+                let res =
+                    env.run_str("# Experimental!\n&tcpa\n&tlsalpn .\n:\n&rs 4 .\n:\n&w \"pong\"");
+                res.map(|_| env.take_stack()).map_err(|e| e.to_string())
+            })
+            .collect::<Vec<_>>()
+    });
+    let addr = addr_recv.recv().unwrap();
+    let mut env = Uiua::with_native_sys();
+    let mut connect = |keys: &str, values: &str| {
+        let code = format!(
+            "{read}&tlsc map {{\"address\" {keys}}} {{{addr:?} {values}}}\n\
+            &tlsalpn .\n:\n&w \"ping\" .\n&rs 4"
+        );
+        env.run_str(&code).map(|_| env.take_stack())
+    };
+    let keys = r#""servername" "alpn" "roots" "cert" "key""#;
+    let client = r#"Read "ca.pem" Read "client.pem" Read "client.key""#;
+    let ok = connect(keys, &format!(r#""localhost" "echo" {client}"#));
+    let ok = ok.unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(ok, [Value::from("echo"), Value::from("pong")]);
+    let keys = r#""servername" "roots" "cert" "key""#;
+    let sni = connect(keys, &format!(r#""other.test" {client}"#));
+    let sni = sni.unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(sni, [Value::from(""), Value::from("pong")]);
+    assert!(connect(keys, &format!(r#""wrong.test" {client}"#)).is_err());
+    let no_client = connect(r#""servername" "roots""#, r#""localhost" Read "ca.pem""#);
+    assert!(no_client.is_err());
+
+    let results = server.join().unwrap();
+    let stack = results[0].as_ref().unwrap();
+    assert_eq!(stack, &[Value::from("echo"), Value::from("ping")]);
+    let stack = results[1].as_ref().unwrap();
+    assert_eq!(stack, &[Value::from(""), Value::from("ping")]);
+    assert!(results[2].is_err());
+    assert!(results[3].is_err());
+
+    for code in [
+        r#"&tlsl "127.0.0.1:0" map {"cert"} {"abc"}"#,
+        r#"&tlsl "127.0.0.1:0" map {"alpn"} {"h2"}"#,
+        r#"&tlsl "127.0.0.1:0" map {"sni"} {map {"a"} {□"abc"}}"#,
+        r#"&tlsl "127.0.0.1:0" map {"port"} {5}"#,
+        r#"&tlsc map {"servername"} {"localhost"}"#,
+        r#"&tlsc map {"address" "key"} {"localhost:1" "abc"}"#,
+        r#"&tlsc map {"address" "color"} {"localhost:1" "red"}"#,
+    ] {
+        let res = env.run_str(&format!("# Experimental!\n{code}"));
+        assert!(res.is_err(), "{code}");
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn file_walk() {
+    use crate::{Array, Boxed, Uiua, Value};
+    let dir = std::env::temp_dir().join(format!("uiua_file_walk_{}", std::process::id()));
+    for (file, contents) in [("b.txt", "hello"), ("a/c.txt", "hi"), ("a/d/e.txt", "")] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    let mut env = Uiua::with_native_sys().with_working_dir(&dir);
+    let sep = std::path::MAIN_SEPARATOR;
+    assert!(env.run_str(r#"&fwalk(□°□get "path") "root""#).is_err());
+    env.run_str(r#"&fwalk(□°□get "path") ".""#).unwrap();
+    let paths: Vec<String> = ["a", "a/c.txt", "a/d", "a/d/e.txt", "b.txt"]
+        .iter()
+        .map(|path| format!(".{sep}{}", path.replace('/', &sep.to_string())))
+        .collect();
+    assert_eq!(
+        env.pop("paths").unwrap(),
+        Value::from(Array::<Boxed>::from_iter(paths))
+    );
+    env.run_str(r#"&fwalk(+°□get "size") "." 0"#).unwrap();
+    assert_eq!(env.pop("size").unwrap(), Value::from(7));
+    env.run_str(r#"&fwalk(°□get "isdir") "a""#).unwrap();
+    assert_eq!(
+        env.pop("dirs").unwrap(),
+        Value::from(Array::<u8>::from_iter([0, 1, 0]))
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(test)]
+#[test]
+fn file_lock_and_atomic_write() {
+    use crate::Uiua;
+    let dir = std::env::temp_dir().join(format!("uiua_file_lock_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut env = Uiua::with_native_sys().with_working_dir(&dir);
+    env.run_str(r#"&fwriteatomic "state.txt" "one""#).unwrap();
+    env.run_str(r#"&fwriteatomic "state.txt" "two""#).unwrap();
+    let contents = std::fs::read_to_string(dir.join("state.txt")).unwrap();
+    assert_eq!(contents, "two");
+    let files: Vec<_> = (std::fs::read_dir(&dir).unwrap())
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(files, ["state.txt"]);
+    #[cfg(all(feature = "native_sys", unix))]
+    {
+        use std::os::fd::AsRawFd;
+        let file = std::fs::File::open(dir.join("state.txt")).unwrap();
+        let try_lock =
+            || unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0;
+        env.run_str(r#"&flock. &fo "state.txt""#).unwrap();
+        assert!(!try_lock());
+        env.run_str("&cl").unwrap();
+        assert!(try_lock());
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(test, feature = "serial", unix))]
+#[test]
+fn serial_port() {
+    use crate::Uiua;
+    use std::{
+        ffi::CStr,
+        fs::File,
+        io::{Read, Write},
+        os::fd::FromRawFd,
+    };
+    // A pseudoterminal stands in for a device
+    let (mut device, path) = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(fd >= 0 && libc::grantpt(fd) == 0 && libc::unlockpt(fd) == 0);
+        let path = CStr::from_ptr(libc::ptsname(fd))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        (File::from_raw_fd(fd), path)
+    };
+    let device = std::thread::spawn(move || {
+        let mut buf = [0; 4];
+        device.read_exact(&mut buf).unwrap();
+        device.write_all(b"pong\nline\n").unwrap();
+        // Closing the device would hang up the port
+        (buf, device)
+    });
+    let mut env = Uiua::with_native_sys();
+    let code = format!(
+        "# Experimental!\n\
+        &serialopen {path:?} map {{\"baud\" \"parity\" \"timeout\"}} {{115200 \"even\" 0.2}}\n\
+        &serialwrite \"ping\" .\n&serialread 5 .\n:\n&ru \"\\n\" .\n:\n&serialread ∞ .\n:\n&cl"
+    );
+    env.run_str(&code).unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(&device.join().unwrap().0, b"ping");
+    let stack: Vec<_> = (env.take_stack().into_iter())
+        .map(|val| val.into_bytes(&env, "").unwrap())
+        .collect();
+    assert_eq!(stack, [b"pong\n".to_vec(), b"line\n".to_vec(), Vec::new()]);
+
+    for code in [
+        format!(r#"&serialopen {path:?} map {{"databits"}} {{9}}"#),
+        format!(r#"&serialopen {path:?} map {{"stopbits"}} {{3}}"#),
+        format!(r#"&serialopen {path:?} map {{"parity"}} {{"mark"}}"#),
+        format!(r#"&serialopen {path:?} map {{"flowcontrol"}} {{"magic"}}"#),
+        format!(r#"&serialopen {path:?} map {{"color"}} {{"red"}}"#),
+        r#"&serialopen "/dev/not-a-serial-port" 9600"#.into(),
+        "&serialread 4 5".into(),
+    ] {
+        let res = env.run_str(&format!("# Experimental!\n{code}"));
+        assert!(res.is_err(), "{code}");
+    }
+}
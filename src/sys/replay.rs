@@ -0,0 +1,670 @@
+//! Recording and replaying the nondeterministic inputs of a program
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(feature = "image")]
+use image::DynamicImage;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    AudioBufferFn, AudioStreamFn, CommandEnv, Email, FileMetadata, GamepadState, GitTarget, Handle,
+    MouseState, ReadLinesFn, ReadLinesReturnFn, SerialSettings, SmtpServer, SysBackend,
+    TlsClientSettings, TlsServerSettings, WebcamImage,
+};
+use crate::{seed_random, FfiType, Uiua, Value};
+
+/// A system backend that records or replays the nondeterministic inputs of a program
+///
+/// In record mode, every call that observes the outside world (the clock,
+/// stdin, files, environment variables, sockets, commands, etc.) is forwarded
+/// to the wrapped backend and its result is saved in a [`Recording`].
+///
+/// In replay mode, those calls are not forwarded. Their results are taken
+/// from the recording in order instead, so the program sees exactly the same
+/// inputs it saw when it was recorded. Output, like printing and showing
+/// images, is always forwarded.
+///
+/// The random number generator of the current thread is seeded from the recording.
+/// Threads spawned by the program are not seeded, and calls made from multiple
+/// threads are only replayed correctly if they happen in the same order.
+pub struct ReplaySys {
+    inner: Arc<dyn SysBackend>,
+    state: Arc<Mutex<ReplayState>>,
+    save_on_exit: Option<PathBuf>,
+}
+
+/// The recorded inputs of a program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    version: String,
+    seed: u64,
+    calls: Vec<RecordedCall>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedCall {
+    call: String,
+    result: serde_json::Value,
+}
+
+struct ReplayState {
+    seed: u64,
+    mode: Mode,
+}
+
+enum Mode {
+    Record(Vec<RecordedCall>),
+    Replay(VecDeque<RecordedCall>),
+}
+
+impl Recording {
+    /// The version of Uiua that made the recording
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+    /// The seed of the random number generator
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// The number of recorded calls
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+    /// Check if no calls were recorded
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+    /// Load a recording from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid recording: {e}"))
+    }
+    /// Save the recording to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+}
+
+impl ReplaySys {
+    /// Record the inputs of a program run with the given backend
+    ///
+    /// This seeds the random number generator of the current thread
+    pub fn record(inner: Arc<dyn SysBackend>) -> Self {
+        let seed = rand::random();
+        seed_random(seed);
+        ReplaySys {
+            inner,
+            state: Arc::new(Mutex::new(ReplayState {
+                seed,
+                mode: Mode::Record(Vec::new()),
+            })),
+            save_on_exit: None,
+        }
+    }
+    /// Replay a recording, forwarding output to the given backend
+    ///
+    /// This seeds the random number generator of the current thread
+    pub fn replay(inner: Arc<dyn SysBackend>, recording: Recording) -> Self {
+        seed_random(recording.seed);
+        ReplaySys {
+            inner,
+            state: Arc::new(Mutex::new(ReplayState {
+                seed: recording.seed,
+                mode: Mode::Replay(recording.calls.into()),
+            })),
+            save_on_exit: None,
+        }
+    }
+    /// Save the recording to a file if the program exits early
+    pub fn save_on_exit(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_on_exit = Some(path.into());
+        self
+    }
+    /// Check if this backend is replaying a recording
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.state.lock().mode, Mode::Replay(_))
+    }
+    /// Get the calls recorded so far
+    ///
+    /// When replaying, this is the part of the recording that has not been used yet
+    pub fn recording(&self) -> Recording {
+        let state = self.state.lock();
+        let calls = match &state.mode {
+            Mode::Record(calls) => calls.clone(),
+            Mode::Replay(calls) => calls.iter().cloned().collect(),
+        };
+        Recording {
+            version: crate::VERSION.into(),
+            seed: state.seed,
+            calls,
+        }
+    }
+    /// Record a call, or take its result from the recording
+    ///
+    /// Only fails if the program does something different than it did when recorded
+    fn traced<T>(&self, call: impl fmt::Display, f: impl FnOnce() -> T) -> Result<T, String>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        traced(&self.state, call, f)
+    }
+    fn traced_res<T>(
+        &self,
+        call: impl fmt::Display,
+        f: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.traced(call, f)?
+    }
+    fn traced_handle(
+        &self,
+        call: impl fmt::Display,
+        f: impl FnOnce() -> Result<Handle, String>,
+    ) -> Result<Handle, String> {
+        self.traced_res(call, || f().map(|handle| handle.0))
+            .map(Handle)
+    }
+}
+
+fn traced<T>(
+    state: &Mutex<ReplayState>,
+    call: impl fmt::Display,
+    f: impl FnOnce() -> T,
+) -> Result<T, String>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let call = call.to_string();
+    if let Mode::Replay(calls) = &mut state.lock().mode {
+        let Some(recorded) = calls.pop_front() else {
+            return Err(format!(
+                "Replay ran past the end of the recording at {call}"
+            ));
+        };
+        if recorded.call != call {
+            let message = format!(
+                "Replay diverged from the recording: expected {}, but the program called {call}",
+                recorded.call
+            );
+            calls.push_front(recorded);
+            return Err(message);
+        }
+        return serde_json::from_value(recorded.result)
+            .map_err(|e| format!("Invalid recorded result for {call}: {e}"));
+    }
+    // The lock is not held while calling the inner backend
+    // because it may block for a long time
+    let result = f();
+    record(state, call, &result);
+    Ok(result)
+}
+
+fn record(state: &Mutex<ReplayState>, call: impl fmt::Display, result: impl Serialize) {
+    let result = serde_json::to_value(result).expect("recorded results are serializable");
+    if let Mode::Record(calls) = &mut state.lock().mode {
+        calls.push(RecordedCall {
+            call: call.to_string(),
+            result,
+        });
+    }
+}
+
+/// Standard handles are passed through so that output is not lost when replaying
+fn is_std_handle(handle: Handle) -> bool {
+    handle.0 < 3
+}
+
+impl SysBackend for ReplaySys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, message: String, colored: String) {
+        self.inner.save_error_color(message, colored)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.inner.print_str_trace(s)
+    }
+    fn show(&self, value: Value) -> Result<(), String> {
+        self.inner.show(value)
+    }
+    fn show_colored(&self, value: Value) -> Result<(), String> {
+        self.inner.show_colored(value)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.traced_res("scan_line_stdin()", || self.inner.scan_line_stdin())
+    }
+    fn scan_stdin(&self, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("scan_stdin({count:?})"), || {
+            self.inner.scan_stdin(count)
+        })
+    }
+    fn scan_until_stdin(&self, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("scan_until_stdin({delim:?})"), || {
+            self.inner.scan_until_stdin(delim)
+        })
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        self.inner.set_raw_mode(raw_mode)
+    }
+    fn get_raw_mode(&self) -> Result<bool, String> {
+        self.traced_res("get_raw_mode()", || self.inner.get_raw_mode())
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        (self.traced(format_args!("var({name:?})"), || self.inner.var(name)))
+            .unwrap_or_else(|_| self.inner.var(name))
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.traced_res("term_size()", || self.inner.term_size())
+    }
+    fn exit(&self, status: i32) -> Result<(), String> {
+        if let Some(path) = &self.save_on_exit {
+            self.recording().save(path)?;
+        }
+        self.inner.exit(status)
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        (self.traced(format_args!("file_exists({path:?})"), || {
+            self.inner.file_exists(path)
+        }))
+        .unwrap_or_else(|_| self.inner.file_exists(path))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        self.traced_res(format_args!("list_dir({path:?})"), || {
+            self.inner.list_dir(path)
+        })
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        self.traced_res(format_args!("is_file({path:?})"), || {
+            self.inner.is_file(path)
+        })
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        self.traced_res(format_args!("file_metadata({path:?})"), || {
+            self.inner.file_metadata(path)
+        })
+    }
+    fn canonicalize(&self, path: &str) -> Result<String, String> {
+        self.traced_res(format_args!("canonicalize({path:?})"), || {
+            self.inner.canonicalize(path)
+        })
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.traced_res(format_args!("delete({path:?})"), || self.inner.delete(path))
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.traced_res(format_args!("trash({path:?})"), || self.inner.trash(path))
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("read({}, {count})", handle.0), || {
+            self.inner.read(handle, count)
+        })
+    }
+    fn read_all(&self, handle: Handle) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("read_all({})", handle.0), || {
+            self.inner.read_all(handle)
+        })
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("read_until({}, {delim:?})", handle.0), || {
+            self.inner.read_until(handle, delim)
+        })
+    }
+    fn read_lines<'a>(&self, handle: Handle) -> Result<ReadLinesReturnFn<'a>, String> {
+        let call = format!("read_lines({})", handle.0);
+        let line_call = format!("read_line({})", handle.0);
+        let state = self.state.clone();
+        if self.is_replaying() {
+            self.traced_res(&call, || Ok(()))?;
+            return Ok(Box::new(move |env: &mut Uiua, mut f: ReadLinesFn| loop {
+                let line: Result<Option<String>, String> =
+                    traced(&state, &line_call, || Ok(None)).map_err(|e| env.error(e))?;
+                match line.map_err(|e| env.error(e))? {
+                    Some(line) => f(line, env)?,
+                    None => break Ok(()),
+                }
+            }));
+        }
+        let read_lines = self.inner.read_lines(handle);
+        self.traced(&call, || {
+            read_lines.as_ref().map(|_| ()).map_err(Clone::clone)
+        })??;
+        let mut read_lines = read_lines?;
+        Ok(Box::new(move |env: &mut Uiua, mut f: ReadLinesFn| {
+            let callback_failed = Arc::new(Mutex::new(false));
+            let res = read_lines(env, {
+                let state = state.clone();
+                let line_call = line_call.clone();
+                let callback_failed = callback_failed.clone();
+                Box::new(move |line, env| {
+                    let recorded: Result<_, String> = Ok(Some(line.clone()));
+                    record(&state, &line_call, recorded);
+                    let res = f(line, env);
+                    *callback_failed.lock() = res.is_err();
+                    res
+                })
+            });
+            // An error from the callback will happen again when replaying,
+            // so only errors from reading are recorded
+            if !*callback_failed.lock() {
+                let end: Result<Option<String>, String> = match &res {
+                    Ok(()) => Ok(None),
+                    Err(e) => Err(e.to_string()),
+                };
+                record(&state, &line_call, end);
+            }
+            res
+        }))
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        if is_std_handle(handle) {
+            return self.inner.write(handle, contents);
+        }
+        self.traced_res(
+            format_args!("write({}, {} bytes)", handle.0, contents.len()),
+            || self.inner.write(handle, contents),
+        )
+    }
+    fn create_file(&self, path: &Path) -> Result<Handle, String> {
+        self.traced_handle(format_args!("create_file({path:?})"), || {
+            self.inner.create_file(path)
+        })
+    }
+    fn open_file(&self, path: &Path, write: bool) -> Result<Handle, String> {
+        self.traced_handle(format_args!("open_file({path:?}, {write})"), || {
+            self.inner.open_file(path, write)
+        })
+    }
+    fn make_dir(&self, path: &Path) -> Result<(), String> {
+        self.traced_res(format_args!("make_dir({path:?})"), || {
+            self.inner.make_dir(path)
+        })
+    }
+    fn file_read_all(&self, path: &Path) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("file_read_all({path:?})"), || {
+            self.inner.file_read_all(path)
+        })
+    }
+    fn file_write_all(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.traced_res(
+            format_args!("file_write_all({path:?}, {} bytes)", contents.len()),
+            || self.inner.file_write_all(path, contents),
+        )
+    }
+    fn file_write_atomic(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.traced_res(
+            format_args!("file_write_atomic({path:?}, {} bytes)", contents.len()),
+            || self.inner.file_write_atomic(path, contents),
+        )
+    }
+    fn lock_file(&self, handle: Handle) -> Result<(), String> {
+        self.traced_res(format_args!("lock_file({})", handle.0), || {
+            self.inner.lock_file(handle)
+        })
+    }
+    fn file_append(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        self.traced_res(
+            format_args!("file_append({path:?}, {} bytes)", contents.len()),
+            || self.inner.file_append(path, contents),
+        )
+    }
+    fn clipboard(&self) -> Result<String, String> {
+        self.traced_res("clipboard()", || self.inner.clipboard())
+    }
+    fn set_clipboard(&self, contents: &str) -> Result<(), String> {
+        self.traced_res("set_clipboard(..)", || self.inner.set_clipboard(contents))
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.traced_res(format_args!("sleep({seconds})"), || {
+            self.inner.sleep(seconds)
+        })
+    }
+    fn allow_thread_spawning(&self) -> bool {
+        self.inner.allow_thread_spawning()
+    }
+    #[cfg(feature = "image")]
+    fn show_image(&self, image: DynamicImage, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_image(image, label)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.show_gif(gif_bytes, label)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>, label: Option<&str>) -> Result<(), String> {
+        self.inner.play_audio(wave_bytes, label)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.inner.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.inner.stream_audio(f)
+    }
+    fn stream_audio_buffers(&self, f: AudioBufferFn) -> Result<(), String> {
+        self.inner.stream_audio_buffers(f)
+    }
+    fn now(&self) -> f64 {
+        (self.traced("now()", || self.inner.now())).unwrap_or_else(|_| self.inner.now())
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.traced_handle(format_args!("tcp_listen({addr:?})"), || {
+            self.inner.tcp_listen(addr)
+        })
+    }
+    fn tls_listen(&self, addr: &str, settings: &TlsServerSettings) -> Result<Handle, String> {
+        self.traced_handle(format_args!("tls_listen({addr:?})"), || {
+            self.inner.tls_listen(addr, settings)
+        })
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.traced_handle(format_args!("tcp_accept({})", handle.0), || {
+            self.inner.tcp_accept(handle)
+        })
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.traced_handle(format_args!("tcp_connect({addr:?})"), || {
+            self.inner.tcp_connect(addr)
+        })
+    }
+    fn tls_connect(&self, addr: &str, settings: &TlsClientSettings) -> Result<Handle, String> {
+        self.traced_handle(format_args!("tls_connect({addr:?})"), || {
+            self.inner.tls_connect(addr, settings)
+        })
+    }
+    fn tls_alpn(&self, handle: Handle) -> Result<Option<String>, String> {
+        self.traced_res(format_args!("tls_alpn({})", handle.0), || {
+            self.inner.tls_alpn(handle)
+        })
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<SocketAddr, String> {
+        self.traced_res(format_args!("tcp_addr({})", handle.0), || {
+            self.inner.tcp_addr(handle)
+        })
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.traced_res(
+            format_args!("tcp_set_non_blocking({}, {non_blocking})", handle.0),
+            || self.inner.tcp_set_non_blocking(handle, non_blocking),
+        )
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.traced_res(
+            format_args!("tcp_set_read_timeout({}, {timeout:?})", handle.0),
+            || self.inner.tcp_set_read_timeout(handle, timeout),
+        )
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.traced_res(
+            format_args!("tcp_set_write_timeout({}, {timeout:?})", handle.0),
+            || self.inner.tcp_set_write_timeout(handle, timeout),
+        )
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.traced_res(format_args!("close({})", handle.0), || {
+            self.inner.close(handle)
+        })
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.traced_res(format_args!("invoke({path:?})"), || self.inner.invoke(path))
+    }
+    fn run_command_inherit(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<i32, String> {
+        self.traced_res(
+            format_args!("run_command_inherit({command:?}, {args:?})"),
+            || self.inner.run_command_inherit(command, args, cmd_env),
+        )
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<(i32, String, String), String> {
+        self.traced_res(
+            format_args!("run_command_capture({command:?}, {args:?})"),
+            || self.inner.run_command_capture(command, args, cmd_env),
+        )
+    }
+    fn run_command_stream(
+        &self,
+        command: &str,
+        args: &[&str],
+        cmd_env: &CommandEnv,
+    ) -> Result<[Handle; 3], String> {
+        (self.traced_res(
+            format_args!("run_command_stream({command:?}, {args:?})"),
+            || {
+                let handles = self.inner.run_command_stream(command, args, cmd_env)?;
+                Ok(handles.map(|handle| handle.0))
+            },
+        ))
+        .map(|handles| handles.map(Handle))
+    }
+    fn current_dir(&self) -> Result<String, String> {
+        self.traced_res("current_dir()", || self.inner.current_dir())
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.traced_res(format_args!("https_get(.., {})", handle.0), || {
+            self.inner.https_get(request, handle)
+        })
+    }
+    fn smtp_send(&self, server: &SmtpServer, email: &Email) -> Result<(), String> {
+        self.traced_res(format_args!("smtp_send({}, ..)", server.host), || {
+            self.inner.smtp_send(server, email)
+        })
+    }
+    fn serial_ports(&self) -> Result<Vec<String>, String> {
+        self.traced_res("serial_ports()", || self.inner.serial_ports())
+    }
+    fn serial_open(&self, path: &str, settings: &SerialSettings) -> Result<Handle, String> {
+        self.traced_handle(
+            format_args!("serial_open({path:?}, {})", settings.baud),
+            || self.inner.serial_open(path, settings),
+        )
+    }
+    fn serial_read(&self, handle: Handle, count: Option<usize>) -> Result<Vec<u8>, String> {
+        self.traced_res(format_args!("serial_read({}, {count:?})", handle.0), || {
+            self.inner.serial_read(handle, count)
+        })
+    }
+    fn serial_write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.traced_res(
+            format_args!("serial_write({}, {})", handle.0, contents.len()),
+            || self.inner.serial_write(handle, contents),
+        )
+    }
+    fn webcam_capture(&self, index: usize) -> Result<WebcamImage, String> {
+        self.inner.webcam_capture(index)
+    }
+    fn mouse(&self) -> Result<MouseState, String> {
+        self.traced_res("mouse()", || self.inner.mouse())
+    }
+    fn gamepad(&self, index: usize) -> Result<Option<GamepadState>, String> {
+        self.traced_res(format_args!("gamepad({index})"), || {
+            self.inner.gamepad(index)
+        })
+    }
+    fn ffi(
+        &self,
+        file: &str,
+        result_ty: FfiType,
+        name: &str,
+        arg_tys: &[FfiType],
+        args: &[Value],
+    ) -> Result<Value, String> {
+        self.inner.ffi(file, result_ty, name, arg_tys, args)
+    }
+    fn mem_copy(&self, ty: FfiType, ptr: *const (), len: usize) -> Result<Value, String> {
+        self.inner.mem_copy(ty, ptr, len)
+    }
+    fn mem_free(&self, ptr: *const ()) -> Result<(), String> {
+        self.inner.mem_free(ptr)
+    }
+    fn load_git_module(&self, url: &str, target: GitTarget) -> Result<PathBuf, String> {
+        self.inner.load_git_module(url, target)
+    }
+    fn load_package(&self, name: &str) -> Result<PathBuf, String> {
+        self.inner.load_package(name)
+    }
+    fn timezone(&self) -> Result<f64, String> {
+        self.traced_res("timezone()", || self.inner.timezone())
+    }
+    fn breakpoint(&self, env: &Uiua) -> Result<bool, String> {
+        self.inner.breakpoint(env)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn record_replay() {
+    use crate::{IntoSysBackend, Recording, ReplaySys, SafeSys, Uiua};
+    let code = "[⚂ now ⚂]";
+    let mut env = Uiua::with_backend(ReplaySys::record(SafeSys::default().into_sys_backend()));
+    env.run_str(code).unwrap();
+    let recorded = env.pop(1).unwrap();
+    let recording = env.downcast_backend::<ReplaySys>().unwrap().recording();
+    assert!(!recording.is_empty());
+    let replay = |code: &str, recording: Recording| {
+        let backend = ReplaySys::replay(SafeSys::default().into_sys_backend(), recording);
+        let mut env = Uiua::with_backend(backend);
+        env.run_str(code).map(|_| env.pop(1).unwrap())
+    };
+    assert_eq!(replay(code, recording.clone()).unwrap(), recorded);
+    let Err(err) = replay("&sc", recording) else {
+        panic!("replaying a different program should fail");
+    };
+    assert!(err.to_string().contains("recording"), "{err}");
+}
@@ -0,0 +1,120 @@
+//! Running programs that suspend at pending system calls
+
+use std::task::Poll;
+
+use crate::{run::Suspension, Assembly, IntoSysBackend, Uiua, UiuaResult, Value};
+
+/// A program that suspends when a system call is pending
+///
+/// This lets one thread drive many programs whose system calls are completed asynchronously.
+///
+/// When a method of the backend returns [`SYS_PENDING`](super::SYS_PENDING), the program stops
+/// and [`Suspendable::poll`] returns [`Poll::Pending`]. The backend should start the operation
+/// and return its result when the same call is made again.
+/// Polling again continues the program from where it stopped by making the pending call again.
+/// Nothing that happened before the call is run again.
+///
+/// The program runs on the thread that polls it, and it does not hold on to that thread
+/// while it is suspended.
+/// Only calls made by system functions outside of modifiers can be pending.
+/// If a call made inside a modifier, such as [`try`](crate::Primitive::Try),
+/// or on a thread the program spawns is pending, it fails with an error.
+pub struct Suspendable {
+    env: Uiua,
+    asm: Option<Assembly>,
+    finished: bool,
+}
+
+impl Suspendable {
+    /// Create a new suspendable program
+    ///
+    /// The program does not start until it is first polled
+    pub fn new(asm: Assembly, backend: impl IntoSysBackend) -> Self {
+        let mut env = Uiua::with_backend(backend);
+        env.rt.suspension = Some(Box::new(Suspension::default()));
+        Suspendable {
+            env,
+            asm: Some(asm),
+            finished: false,
+        }
+    }
+    /// Run the program until it finishes or a system call is pending
+    ///
+    /// Finished programs return their stack.
+    /// Polling a program after it has finished returns an error.
+    pub fn poll(&mut self) -> Poll<UiuaResult<Vec<Value>>> {
+        if self.finished {
+            return Poll::Ready(Err(self
+                .env
+                .error("Suspendable program polled after it finished")));
+        }
+        let res = match self.asm.take() {
+            Some(asm) => self.env.run_asm(asm),
+            None => self.env.resume(),
+        };
+        if self.env.is_suspended() {
+            return Poll::Pending;
+        }
+        self.finished = true;
+        Poll::Ready(res.map(|()| self.env.take_stack()))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn suspend_resume() {
+    use std::{any::Any, sync::Arc};
+
+    use parking_lot::Mutex;
+
+    use crate::{Compiler, SysBackend, SYS_PENDING};
+
+    #[derive(Default, Clone)]
+    struct Host {
+        started: Arc<Mutex<Vec<f64>>>,
+        finished: Arc<Mutex<Vec<f64>>>,
+        output: Arc<Mutex<String>>,
+    }
+    impl SysBackend for Host {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+            self.output.lock().push_str(s);
+            Ok(())
+        }
+        fn sleep(&self, seconds: f64) -> Result<(), String> {
+            if self.finished.lock().contains(&seconds) {
+                return Ok(());
+            }
+            self.started.lock().push(seconds);
+            Err(SYS_PENDING.into())
+        }
+    }
+    let host = Host::default();
+    let code = "\
+&p \"a\"
+F ← |1 (&sl 1 ×2)
+&p \"b\"
+⍜↻(⊂F 5) 1 [1 2 3]
+⍣(&sl 2 0)1";
+    let asm = Compiler::new().load_str(code).unwrap().finish();
+    let mut program = Suspendable::new(asm, host.clone());
+    assert!(program.poll().is_pending());
+    assert_eq!(*host.output.lock(), "a\nb\n");
+    assert_eq!(*host.started.lock(), [1.0]);
+    assert!(program.poll().is_pending());
+    assert_eq!(*host.started.lock(), [1.0, 1.0]);
+    host.finished.lock().push(1.0);
+    // Pending calls inside modifiers fail
+    let Poll::Ready(res) = program.poll() else {
+        panic!("program should finish");
+    };
+    assert_eq!(*host.started.lock(), [1.0, 1.0, 2.0]);
+    assert_eq!(*host.output.lock(), "a\nb\n");
+    assert_eq!(res.unwrap(), [Value::from([1, 10, 2, 3]), 1.into()]);
+    assert!(matches!(program.poll(), Poll::Ready(Err(_))));
+}
@@ -0,0 +1,588 @@
+//! Running the test blocks in a file one at a time
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc,
+    },
+    thread,
+};
+
+use ecow::EcoString;
+use parking_lot::Mutex;
+
+use crate::{
+    CodeSpan, Compiler, FormatSettings, RunMode, SafeSys, Uiua, UiuaError, UiuaResult, Value,
+};
+
+/// Runs each test block in a file separately and reports the results
+///
+/// Each test block is compiled on its own, with every other test block skipped,
+/// and run in a fresh runtime. By default, that runtime has its own [`SafeSys`]
+/// backend, so tests cannot affect each other and can be run in parallel.
+///
+/// Test blocks marked with `# Snapshot!` also compare the values they leave on the stack
+/// to a [`Snapshot`] named after the block.
+#[derive(Clone)]
+pub struct TestRunner {
+    compiler: Compiler,
+    new_runtime: Arc<dyn Fn() -> Uiua + Send + Sync>,
+    filter: Option<String>,
+    jobs: usize,
+    snapshot_dir: Option<PathBuf>,
+    update_snapshots: bool,
+}
+
+/// The results of running tests
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    /// The results of the tests that were run, in the order they appear
+    pub tests: Vec<TestResult>,
+    /// The number of tests that did not match the filter
+    pub filtered_out: usize,
+}
+
+/// The result of running a single test block
+///
+/// Code outside of test blocks is run as part of every test.
+/// If there are no test blocks, it is run as a test named `top level`.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    /// The test's name, or its location if it is unnamed
+    pub name: EcoString,
+    /// The span of the test block, or `None` for code outside of test blocks
+    pub span: Option<CodeSpan>,
+    /// What happened when the test was run
+    pub outcome: TestOutcome,
+    /// The time taken to run the test in seconds
+    pub time: f64,
+    /// The error the test failed with, if any
+    pub error: Option<UiuaError>,
+    /// Captured stdout, if the runtime's backend is a [`SafeSys`]
+    pub stdout: String,
+    /// Captured stderr, if the runtime's backend is a [`SafeSys`]
+    pub stderr: String,
+    /// The result of comparing the test's output to its snapshot, if it has one
+    pub snapshot: Option<SnapshotStatus>,
+}
+
+/// What happened when a test was run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestOutcome {
+    /// The test passed
+    Passed,
+    /// The test failed
+    Failed,
+    /// The test was marked with `# Should fail!` and failed
+    FailedAsExpected,
+    /// The test was marked with `# Should fail!` but passed
+    PassedUnexpectedly,
+}
+
+impl TestOutcome {
+    /// Whether this outcome counts as a success
+    pub fn is_success(&self) -> bool {
+        matches!(self, TestOutcome::Passed | TestOutcome::FailedAsExpected)
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            TestOutcome::Passed => "passed",
+            TestOutcome::Failed => "failed",
+            TestOutcome::FailedAsExpected => "failed as expected",
+            TestOutcome::PassedUnexpectedly => "passed unexpectedly",
+        }
+    }
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A test to be run
+struct TestCase {
+    /// The index of the test block, or `usize::MAX` for code outside of test blocks
+    index: usize,
+    name: EcoString,
+    span: Option<CodeSpan>,
+    should_fail: bool,
+    snapshot: bool,
+}
+
+#[derive(Clone)]
+enum TestSource {
+    File(PathBuf),
+    Str(String),
+}
+
+impl TestSource {
+    fn load<'a>(&self, comp: &'a mut Compiler) -> UiuaResult<&'a mut Compiler> {
+        match self {
+            TestSource::File(path) => comp.load_file(path),
+            TestSource::Str(input) => comp.load_str(input),
+        }
+    }
+}
+
+impl TestRunner {
+    /// Create a new test runner that compiles with the given compiler
+    ///
+    /// The compiler is put in test mode. Its diagnostics are only printed once,
+    /// not once per test.
+    pub fn new(compiler: Compiler) -> Self {
+        TestRunner {
+            compiler,
+            new_runtime: Arc::new(Uiua::with_safe_sys),
+            filter: None,
+            jobs: 1,
+            snapshot_dir: None,
+            update_snapshots: false,
+        }
+    }
+    /// Only run tests whose names contain the given string
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+    /// Set the number of tests to run at once
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+    /// Set the directory that snapshots are stored in
+    ///
+    /// When running a file, this defaults to `snapshots/<file name>` next to the file.
+    pub fn snapshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.snapshot_dir = Some(dir.into());
+        self
+    }
+    /// Overwrite snapshots that do not match instead of failing
+    pub fn update_snapshots(mut self, update: bool) -> Self {
+        self.update_snapshots = update;
+        self
+    }
+    /// Set the function used to create the runtime for each test
+    pub fn with_runtime(mut self, new_runtime: impl Fn() -> Uiua + Send + Sync + 'static) -> Self {
+        self.new_runtime = Arc::new(new_runtime);
+        self
+    }
+    /// Run the tests in a file
+    ///
+    /// Only fails if the file cannot be compiled
+    pub fn run_file(&self, path: impl AsRef<Path>) -> UiuaResult<TestReport> {
+        let path = path.as_ref();
+        if self.snapshot_dir.is_none() {
+            if let Some(stem) = path.file_stem() {
+                let dir = path.with_file_name("snapshots").join(stem);
+                return (self.clone().snapshot_dir(dir)).run(TestSource::File(path.into()));
+            }
+        }
+        self.run(TestSource::File(path.into()))
+    }
+    /// Run the tests in a string
+    ///
+    /// Only fails if the string cannot be compiled
+    pub fn run_str(&self, input: &str) -> UiuaResult<TestReport> {
+        self.run(TestSource::Str(input.into()))
+    }
+    fn run(&self, source: TestSource) -> UiuaResult<TestReport> {
+        // Find the test blocks without running any of them
+        let mut comp = self.compiler.clone();
+        comp.mode(RunMode::Test).only_test_block(Some(usize::MAX));
+        let asm = &source.load(&mut comp)?.asm;
+        let mut cases = Vec::new();
+        if asm.test_blocks.is_empty() {
+            cases.push(TestCase {
+                index: usize::MAX,
+                name: "top level".into(),
+                span: None,
+                should_fail: false,
+                snapshot: false,
+            });
+        }
+        cases.extend(
+            asm.test_blocks
+                .iter()
+                .enumerate()
+                .map(|(index, block)| TestCase {
+                    index,
+                    name: block.display_name(),
+                    span: Some(block.span.clone()),
+                    should_fail: block.should_fail,
+                    snapshot: block.snapshot,
+                }),
+        );
+        let total = cases.len();
+        let selected: Vec<TestCase> = (cases.into_iter())
+            .filter(|case| {
+                !matches!(&self.filter, Some(filter) if !case.name.contains(filter.as_str()))
+            })
+            .collect();
+        let filtered_out = total - selected.len();
+        let mut results: Vec<Option<TestResult>> = vec![None; selected.len()];
+        if self.jobs == 1 || selected.len() <= 1 {
+            for (result, case) in results.iter_mut().zip(&selected) {
+                *result = Some(self.run_one(&source, case));
+            }
+        } else {
+            let next = AtomicUsize::new(0);
+            let results_mutex = Mutex::new(&mut results);
+            thread::scope(|scope| {
+                for _ in 0..self.jobs.min(selected.len()) {
+                    let runner = self.clone();
+                    let source = source.clone();
+                    let (next, selected, results) = (&next, &selected, &results_mutex);
+                    scope.spawn(move || loop {
+                        let i = next.fetch_add(1, atomic::Ordering::Relaxed);
+                        let Some(case) = selected.get(i) else {
+                            break;
+                        };
+                        let result = runner.run_one(&source, case);
+                        results.lock()[i] = Some(result);
+                    });
+                }
+            });
+        }
+        Ok(TestReport {
+            tests: results.into_iter().flatten().collect(),
+            filtered_out,
+        })
+    }
+    fn run_one(&self, source: &TestSource, case: &TestCase) -> TestResult {
+        let mut comp = self.compiler.clone();
+        comp.mode(RunMode::Test)
+            .only_test_block(Some(case.index))
+            .print_diagnostics(false);
+        let mut env = (self.new_runtime)();
+        let mut time = 0.0;
+        let mut snapshot = None;
+        // Each test starts with the default format settings
+        let res = FormatSettings::with_default(|| {
+            source.load(&mut comp).and_then(|comp| {
+                let asm = comp.finish();
+                let start = env.backend().now();
+                let res = env.run_asm(asm);
+                time = env.backend().now() - start;
+                res?;
+                if case.snapshot {
+                    snapshot = Some(self.check_snapshot(&mut env, case)?);
+                }
+                Ok(())
+            })
+        });
+        // The runtime reports how many assertions passed, which is not needed here
+        env.take_reports();
+        let (stdout, stderr) = env
+            .downcast_backend::<SafeSys>()
+            .map(|sys| {
+                let stdout = String::from_utf8_lossy(&sys.take_stdout()).into_owned();
+                let stderr = String::from_utf8_lossy(&sys.take_stderr()).into_owned();
+                (stdout, stderr)
+            })
+            .unwrap_or_default();
+        let outcome = match (&res, case.should_fail) {
+            (Ok(()), false) => TestOutcome::Passed,
+            (Ok(()), true) => TestOutcome::PassedUnexpectedly,
+            (Err(_), false) => TestOutcome::Failed,
+            (Err(_), true) => TestOutcome::FailedAsExpected,
+        };
+        TestResult {
+            name: case.name.clone(),
+            span: case.span.clone(),
+            outcome,
+            time,
+            error: res.err(),
+            stdout,
+            stderr,
+            snapshot,
+        }
+    }
+    fn check_snapshot(&self, env: &mut Uiua, case: &TestCase) -> UiuaResult<SnapshotStatus> {
+        let error = |env: &Uiua, message: String| match &case.span {
+            Some(span) => env.error_with_span(span.clone().into(), message),
+            None => env.error(message),
+        };
+        let Some(dir) = &self.snapshot_dir else {
+            return Err(error(env, "No snapshot directory was set".into()));
+        };
+        let snapshot = Snapshot::new(dir.join(format!("{}.txt", case.name)));
+        let actual = Snapshot::format(&env.take_stack());
+        let status = (snapshot.check(&actual, self.update_snapshots))
+            .map_err(|e| error(env, format!("Failed to check snapshot: {e}")))?;
+        if let SnapshotStatus::Mismatched { expected } = &status {
+            let message = format!(
+                "Output does not match the snapshot at {}",
+                snapshot.path().display()
+            );
+            return Err(error(env, message).with_info([
+                (format!("Expected:\n{}", expected.trim_end()), None),
+                (format!("Actual:\n{}", actual.trim_end()), None),
+                (
+                    "Run `uiua test --update-snapshots` to update it".into(),
+                    None,
+                ),
+            ]));
+        }
+        Ok(status)
+    }
+}
+
+/// A file that stores the expected formatting of some values
+///
+/// If the file does not exist, it is created the first time it is checked.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    path: PathBuf,
+}
+
+/// The result of checking a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// The snapshot matched
+    Matched,
+    /// The snapshot did not exist and was created
+    Created,
+    /// The snapshot did not match and was updated
+    Updated,
+    /// The snapshot did not match
+    Mismatched {
+        /// The snapshot's contents
+        expected: String,
+    },
+}
+
+impl Snapshot {
+    /// Create a snapshot stored at the given path
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Snapshot { path: path.into() }
+    }
+    /// Get the path of the snapshot file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Format values the way they are stored in a snapshot
+    ///
+    /// Values are shown as they would be printed on the stack, from bottom to top.
+    pub fn format(values: &[Value]) -> String {
+        let mut s = String::new();
+        for value in values {
+            s.push_str(&value.show());
+            s.push('\n');
+        }
+        s
+    }
+    /// Compare a string to the snapshot
+    ///
+    /// If `update` is `true`, a mismatched snapshot is overwritten.
+    pub fn check(&self, actual: &str, update: bool) -> io::Result<SnapshotStatus> {
+        let expected = match fs::read_to_string(&self.path) {
+            Ok(expected) => expected.replace("\r\n", "\n"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.write(actual)?;
+                return Ok(SnapshotStatus::Created);
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(if expected == actual {
+            SnapshotStatus::Matched
+        } else if update {
+            self.write(actual)?;
+            SnapshotStatus::Updated
+        } else {
+            SnapshotStatus::Mismatched { expected }
+        })
+    }
+    fn write(&self, contents: &str) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, contents)
+    }
+}
+
+impl TestReport {
+    /// The number of tests that succeeded
+    pub fn passed(&self) -> usize {
+        self.tests.iter().filter(|t| t.outcome.is_success()).count()
+    }
+    /// The number of tests that did not succeed
+    pub fn failed(&self) -> usize {
+        self.tests.len() - self.passed()
+    }
+    /// Whether every test that was run succeeded
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+    /// The total time taken to run the tests in seconds
+    pub fn time(&self) -> f64 {
+        self.tests.iter().map(|t| t.time).sum()
+    }
+    /// Format the report as JSON
+    pub fn to_json(&self) -> String {
+        let tests: Vec<_> = (self.tests.iter())
+            .map(|test| {
+                serde_json::json!({
+                    "name": test.name,
+                    "location": test.span.as_ref().map(|span| span.to_string()),
+                    "outcome": test.outcome.name(),
+                    "success": test.outcome.is_success(),
+                    "time": test.time,
+                    "error": test.error.as_ref().map(|e| e.report().color(false).to_string()),
+                    "stdout": test.stdout,
+                    "stderr": test.stderr,
+                })
+            })
+            .collect();
+        let report = serde_json::json!({
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "filtered_out": self.filtered_out,
+            "time": self.time(),
+            "tests": tests,
+        });
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+    /// Format the report as JUnit XML
+    ///
+    /// `suite` is used as the name of the test suite, usually the path of the file
+    pub fn to_junit(&self, suite: &str) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let (tests, failures, time) = (self.tests.len(), self.failed(), self.time());
+        xml.push_str(&format!(
+            "<testsuites tests=\"{tests}\" failures=\"{failures}\" time=\"{time:.6}\">\n"
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" \
+            skipped=\"{}\" time=\"{time:.6}\">\n",
+            xml_escape(suite),
+            self.filtered_out,
+        ));
+        for test in &self.tests {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\">\n",
+                xml_escape(&test.name),
+                xml_escape(suite),
+                test.time
+            ));
+            if !test.outcome.is_success() {
+                let message = match &test.error {
+                    Some(error) => error.to_string(),
+                    None => format!("Test {}", test.outcome),
+                };
+                let details = (test.error.as_ref())
+                    .map(|e| e.report().color(false).to_string())
+                    .unwrap_or_default();
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\">{}</failure>\n",
+                    xml_escape(&message),
+                    xml_escape(&details)
+                ));
+            }
+            for (tag, output) in [("system-out", &test.stdout), ("system-err", &test.stderr)] {
+                if !output.is_empty() {
+                    xml.push_str(&format!("      <{tag}>{}</{tag}>\n", xml_escape(output)));
+                }
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())?;
+        if self.filtered_out > 0 {
+            write!(f, ", {} filtered out", self.filtered_out)?;
+        }
+        write!(f, " in {:.2}ms", self.time() * 1000.0)
+    }
+}
+
+/// Set `UIUA_UPDATE_SNAPSHOTS` to update snapshots that do not match
+#[cfg(test)]
+pub(crate) fn update_snapshots() -> bool {
+    std::env::var_os("UIUA_UPDATE_SNAPSHOTS").is_some()
+}
+
+#[cfg(test)]
+#[test]
+fn test_runner() {
+    use crate::{Compiler, TestOutcome, TestRunner};
+    let code = "\
+┌─╴test Pass
+  ⍤⤙≍ 3 +1 2
+└─╴
+┌─╴test Fail
+  &p \"output\"
+  ⍤⤙≍ 4 +1 2
+└─╴
+┌─╴test ShouldFail
+  # Should fail!
+  ⍤⤙≍ 4 +1 2
+└─╴
+┌─╴test Other
+  ⍤⤙≍ 1 1
+└─╴";
+    let report = TestRunner::new(Compiler::new())
+        .jobs(2)
+        .run_str(code)
+        .unwrap();
+    let outcomes: Vec<_> = (report.tests.iter())
+        .map(|t| (t.name.as_str(), t.outcome))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("Pass", TestOutcome::Passed),
+            ("Fail", TestOutcome::Failed),
+            ("ShouldFail", TestOutcome::FailedAsExpected),
+            ("Other", TestOutcome::Passed),
+        ]
+    );
+    assert_eq!(report.tests[1].stdout, "output\n");
+    assert!(report.to_json().contains("\"ShouldFail\""));
+    assert!(report.to_junit("suite").contains("<testcase name=\"Fail\""));
+
+    let report = TestRunner::new(Compiler::new())
+        .filter("Fail")
+        .run_str(code)
+        .unwrap();
+    assert_eq!(report.tests.len(), 2);
+    assert_eq!(report.filtered_out, 2);
+    assert!(!report.is_success());
+
+    // Code outside of test blocks is run with each test
+    let code = "\
+&p \"setup\"
+┌─╴test A
+  ⍤⤙≍ 1 1
+└─╴
+┌─╴test B
+  ⍤⤙≍ 2 2
+└─╴
+⍤⤙≍ 3 3";
+    let report = TestRunner::new(Compiler::new()).run_str(code).unwrap();
+    assert_eq!(report.tests.len(), 2);
+    assert!(report.is_success());
+    assert!(report.tests.iter().all(|t| t.stdout == "setup\n"));
+}
@@ -756,7 +756,7 @@ impl Node {
             let len = visited.len();
             let is = match node {
                 Node::Run(nodes) => nodes.iter().all(|node| recurse(node, asm, visited)),
-                Node::Prim(Primitive::Send | Primitive::Recv, _) => false,
+                Node::Prim(Primitive::Send | Primitive::Recv | Primitive::RecvAny, _) => false,
                 Node::Prim(Primitive::Sys(op), _) if op.purity() <= Purity::Mutating => false,
                 Node::Mod(_, args, _) | Node::ImplMod(_, args, _) => {
                     args.iter().all(|arg| recurse(&arg.node, asm, visited))
@@ -789,6 +789,50 @@ impl Node {
         }
         recurse(self, asm, &mut IndexSet::new())
     }
+    /// Hash the node along with the functions and constants it uses
+    ///
+    /// Unlike the node's [`Hash`] implementation, this changes when a called function's code or
+    /// a used constant's value changes, so it can identify the node across runs.
+    /// Returns `false` if the node uses anything that cannot be hashed this way.
+    pub(crate) fn deep_hash(&self, asm: &Assembly, hasher: &mut impl Hasher) -> bool {
+        fn recurse<'a>(
+            node: &'a Node,
+            asm: &'a Assembly,
+            visited: &mut IndexSet<&'a Function>,
+            hasher: &mut impl Hasher,
+        ) -> bool {
+            node.hash(hasher);
+            match node {
+                Node::Run(nodes) => nodes.iter().all(|node| recurse(node, asm, visited, hasher)),
+                Node::Mod(_, args, _) | Node::ImplMod(_, args, _) => args
+                    .iter()
+                    .all(|sn| recurse(&sn.node, asm, visited, hasher)),
+                Node::Switch { branches, .. } => {
+                    (branches.iter()).all(|br| recurse(&br.node, asm, visited, hasher))
+                }
+                Node::CustomInverse(cust, _) => {
+                    (cust.nodes()).all(|sn| recurse(&sn.node, asm, visited, hasher))
+                }
+                Node::Array { inner, .. } | Node::NoInline(inner) | Node::TrackCaller(inner) => {
+                    recurse(inner, asm, visited, hasher)
+                }
+                Node::Call(f, _) => !visited.insert(f) || recurse(&asm[f], asm, visited, hasher),
+                Node::CallGlobal(index, _) => match asm.bindings.get(*index).map(|b| &b.kind) {
+                    Some(BindingKind::Const(Some(val))) => {
+                        val.hash(hasher);
+                        true
+                    }
+                    Some(BindingKind::Func(f)) => {
+                        !visited.insert(f) || recurse(&asm[f], asm, visited, hasher)
+                    }
+                    _ => false,
+                },
+                Node::CallMacro { .. } | Node::BindGlobal { .. } | Node::Dynamic(_) => false,
+                _ => true,
+            }
+        }
+        recurse(self, asm, &mut IndexSet::new(), hasher)
+    }
     /// Check if the node is recursive
     pub fn is_recursive(&self, asm: &Assembly) -> bool {
         fn recurse<'a>(
@@ -0,0 +1,373 @@
+//! The binary `.uasm` format
+//!
+//! A binary assembly starts with a short header containing a magic number,
+//! the format version, and the version of Uiua that compiled it.
+//! The rest is a table of all strings in the assembly followed by a compact
+//! encoding of the assembly's serde data model that refers to that table.
+
+use std::{fmt, path::PathBuf};
+
+use dashmap::DashMap;
+use ecow::{EcoString, EcoVec};
+use indexmap::IndexSet;
+use serde::*;
+use serde_json::{Map, Number, Value as Json};
+
+use crate::{
+    Assembly, BindingInfo, BindingKind, BindingMeta, CodeSpan, Inputs, Node, Span, VERSION,
+};
+
+/// The bytes that every binary assembly starts with
+const MAGIC: &[u8] = b"\0uasm";
+
+/// The version of the binary assembly format
+///
+/// This must be incremented whenever a change to the interpreter would cause
+/// existing binary assemblies to load incorrectly.
+/// It is never incremented in patch releases.
+pub const ASSEMBLY_FORMAT_VERSION: u16 = 1;
+
+/// An error that occurs when loading an assembly
+#[derive(Debug, Clone)]
+pub enum AssemblyLoadError {
+    /// The data is not a valid assembly
+    Invalid(String),
+    /// The assembly was compiled with an incompatible version of Uiua
+    RecompileNeeded {
+        /// The version of Uiua that compiled the assembly
+        version: EcoString,
+        /// The format version of the assembly
+        format_version: u16,
+    },
+}
+
+impl fmt::Display for AssemblyLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblyLoadError::Invalid(e) => write!(f, "Invalid assembly: {e}"),
+            AssemblyLoadError::RecompileNeeded {
+                version,
+                format_version,
+            } => write!(
+                f,
+                "This assembly was compiled with Uiua {version} \
+                (assembly format {format_version}), but this is Uiua {VERSION} \
+                (assembly format {ASSEMBLY_FORMAT_VERSION}). \
+                Recompile it from source with `uiua build`."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AssemblyLoadError {}
+
+#[derive(Serialize, Deserialize)]
+struct AssemblyRep {
+    root: Node,
+    bindings: Vec<(bool, BindingKind)>,
+    functions: EcoVec<Node>,
+    spans: Vec<Option<CodeSpan>>,
+    files: Vec<(PathBuf, EcoString)>,
+    strings: EcoVec<EcoString>,
+}
+
+impl Assembly {
+    /// Serialize the assembly into the binary `.uasm` format
+    ///
+    /// The result can be loaded with [`Assembly::from_uasm_bytes`]
+    /// by any release of Uiua with the same [`ASSEMBLY_FORMAT_VERSION`].
+    pub fn to_uasm_bytes(&self) -> Vec<u8> {
+        let rep = AssemblyRep {
+            root: self.root.clone(),
+            bindings: (self.bindings.iter())
+                .map(|binding| (binding.public, binding.kind.clone()))
+                .collect(),
+            functions: self.functions.clone(),
+            spans: (self.spans.iter().skip(1))
+                .map(|span| match span {
+                    Span::Code(span) => Some(span.clone()),
+                    Span::Builtin => None,
+                })
+                .collect(),
+            files: (self.inputs.files.iter())
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect(),
+            strings: self.inputs.strings.clone(),
+        };
+        let json = serde_json::to_value(rep).unwrap();
+        let mut encoder = Encoder::default();
+        encoder.encode(&json);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(ASSEMBLY_FORMAT_VERSION.to_le_bytes());
+        write_str(&mut bytes, VERSION);
+        write_uint(&mut bytes, encoder.strings.len() as u64);
+        for s in &encoder.strings {
+            write_str(&mut bytes, s);
+        }
+        bytes.extend(encoder.bytes);
+        bytes
+    }
+    /// Load an assembly from the bytes of a `.uasm` file
+    ///
+    /// This accepts both the binary format and the text format
+    pub fn from_uasm_bytes(bytes: &[u8]) -> Result<Self, AssemblyLoadError> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+            let src = std::str::from_utf8(bytes)
+                .map_err(|_| AssemblyLoadError::Invalid("Not a Uiua assembly".into()))?;
+            return Assembly::from_uasm(src).map_err(AssemblyLoadError::Invalid);
+        };
+        let mut decoder = Decoder {
+            bytes,
+            strings: Vec::new(),
+        };
+        let invalid = |e: String| AssemblyLoadError::Invalid(e);
+        let format_version = decoder.u16().map_err(invalid)?;
+        let version: EcoString = decoder.str().map_err(invalid)?.into();
+        if format_version != ASSEMBLY_FORMAT_VERSION {
+            return Err(AssemblyLoadError::RecompileNeeded {
+                version,
+                format_version,
+            });
+        }
+        // An assembly with the right format version may still fail to load
+        // if it uses something that was removed in a later minor release
+        let recompile = |e: String| {
+            if version == VERSION {
+                AssemblyLoadError::Invalid(e)
+            } else {
+                AssemblyLoadError::RecompileNeeded {
+                    version: version.clone(),
+                    format_version,
+                }
+            }
+        };
+        let string_count = decoder.uint().map_err(invalid)?;
+        for _ in 0..string_count {
+            let s = decoder.str().map_err(invalid)?;
+            decoder.strings.push(s);
+        }
+        let json = decoder.decode().map_err(invalid)?;
+        if !decoder.bytes.is_empty() {
+            return Err(invalid("Trailing data".into()));
+        }
+        let rep: AssemblyRep =
+            serde_json::from_value(json).map_err(|e| recompile(e.to_string()))?;
+
+        let files = DashMap::new();
+        for (path, src) in rep.files {
+            files.insert(path, src);
+        }
+        Ok(Assembly {
+            root: rep.root,
+            functions: rep.functions,
+            bindings: (rep.bindings.into_iter())
+                .map(|(public, kind)| BindingInfo {
+                    kind,
+                    public,
+                    span: CodeSpan::dummy(),
+                    meta: BindingMeta::default(),
+                })
+                .collect(),
+            spans: (Some(Span::Builtin).into_iter())
+                .chain(rep.spans.into_iter().map(|span| match span {
+                    Some(span) => Span::Code(span),
+                    None => Span::Builtin,
+                }))
+                .collect(),
+            inputs: Inputs {
+                files: files.into(),
+                strings: rep.strings,
+                ..Inputs::default()
+            },
+            dynamic_functions: EcoVec::new(),
+            test_assert_count: 0,
+            test_blocks: EcoVec::new(),
+            bench_blocks: EcoVec::new(),
+        })
+    }
+}
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const UINT: u8 = 3;
+const NEG_INT: u8 = 4;
+const FLOAT: u8 = 5;
+const STRING: u8 = 6;
+const ARRAY: u8 = 7;
+const OBJECT: u8 = 8;
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    strings: IndexSet<String>,
+}
+
+impl Encoder {
+    fn string(&mut self, s: &str) {
+        let index = match self.strings.get_index_of(s) {
+            Some(index) => index,
+            None => self.strings.insert_full(s.into()).0,
+        };
+        write_uint(&mut self.bytes, index as u64);
+    }
+    fn encode(&mut self, json: &Json) {
+        match json {
+            Json::Null => self.bytes.push(NULL),
+            Json::Bool(false) => self.bytes.push(FALSE),
+            Json::Bool(true) => self.bytes.push(TRUE),
+            Json::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    self.bytes.push(UINT);
+                    write_uint(&mut self.bytes, n);
+                } else if let Some(n) = n.as_i64() {
+                    self.bytes.push(NEG_INT);
+                    write_uint(&mut self.bytes, !n as u64);
+                } else {
+                    self.bytes.push(FLOAT);
+                    let n = n.as_f64().unwrap();
+                    self.bytes.extend(n.to_le_bytes());
+                }
+            }
+            Json::String(s) => {
+                self.bytes.push(STRING);
+                self.string(s);
+            }
+            Json::Array(items) => {
+                self.bytes.push(ARRAY);
+                write_uint(&mut self.bytes, items.len() as u64);
+                for item in items {
+                    self.encode(item);
+                }
+            }
+            Json::Object(map) => {
+                self.bytes.push(OBJECT);
+                write_uint(&mut self.bytes, map.len() as u64);
+                for (key, value) in map {
+                    self.string(key);
+                    self.encode(value);
+                }
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    strings: Vec<String>,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.bytes.len() < n {
+            return Err("Unexpected end of data".into());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn uint(&mut self) -> Result<u64, String> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err("Invalid integer".into())
+    }
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.uint()? as usize;
+        if len > self.bytes.len() {
+            return Err("Invalid length".into());
+        }
+        Ok(len)
+    }
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }
+    fn string(&mut self) -> Result<String, String> {
+        let index = self.uint()? as usize;
+        (self.strings.get(index).cloned()).ok_or_else(|| "Invalid string index".into())
+    }
+    fn decode(&mut self) -> Result<Json, String> {
+        Ok(match self.take(1)?[0] {
+            NULL => Json::Null,
+            FALSE => Json::Bool(false),
+            TRUE => Json::Bool(true),
+            UINT => Json::Number(self.uint()?.into()),
+            NEG_INT => Json::Number((!self.uint()? as i64).into()),
+            FLOAT => {
+                let n = f64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                Json::Number(Number::from_f64(n).ok_or("Invalid number")?)
+            }
+            STRING => Json::String(self.string()?),
+            ARRAY => {
+                let len = self.len()?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.decode()?);
+                }
+                Json::Array(items)
+            }
+            OBJECT => {
+                let len = self.len()?;
+                let mut map = Map::new();
+                for _ in 0..len {
+                    let key = self.string()?;
+                    map.insert(key, self.decode()?);
+                }
+                Json::Object(map)
+            }
+            tag => return Err(format!("Invalid tag {tag}")),
+        })
+    }
+}
+
+fn write_uint(bytes: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    write_uint(bytes, s.len() as u64);
+    bytes.extend(s.as_bytes());
+}
+
+#[cfg(all(test, feature = "native_sys"))]
+#[test]
+fn binary_assembly_round_trip() {
+    use std::path::Path;
+
+    use crate::{Assembly, AssemblyLoadError, Compiler, Uiua};
+    let path = Path::new("tests_special/uasm.ua");
+    let mut comp = Compiler::new();
+    comp.load_file(path).unwrap();
+    let asm = comp.finish();
+    let root = asm.root.clone();
+    let mut bytes = asm.to_uasm_bytes();
+    let asm = Assembly::from_uasm_bytes(&bytes).unwrap();
+    assert_eq!(asm.root, root);
+    let mut env = Uiua::with_native_sys();
+    env.run_asm(asm).unwrap();
+    // Change the format version
+    bytes[5] = bytes[5].wrapping_add(1);
+    assert!(matches!(
+        Assembly::from_uasm_bytes(&bytes),
+        Err(AssemblyLoadError::RecompileNeeded { .. })
+    ));
+}
@@ -568,6 +568,10 @@ impl Value {
     pub fn show(&self) -> String {
         self.grid_string(true)
     }
+    /// Get the pretty-printed string representation of the value with ANSI colors
+    pub fn show_colored(&self) -> String {
+        crate::grid_fmt::color_grid_string(&self.show())
+    }
     /// Get the pretty-printed string representation of the value that appears when formatted
     pub fn format(&self) -> String {
         fn recur(val: &Value, qoute: bool) -> String {
@@ -1642,6 +1646,31 @@ impl<const N: usize> From<[i32; N]> for Value {
     }
 }
 
+macro_rules! value_try_into {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<Value> for $ty {
+                type Error = crate::de::Error;
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    crate::de::from_value(&value)
+                }
+            }
+        )*
+    };
+}
+
+value_try_into!(
+    bool,
+    u8,
+    usize,
+    i32,
+    i64,
+    f64,
+    String,
+    Vec<f64>,
+    Vec<String>
+);
+
 macro_rules! value_un_impl {
     ($name:ident, $(
         $([$(|$meta:ident| $pred:expr,)* $in_place:ident, $f:ident])?
@@ -2152,3 +2181,26 @@ impl ValueBuilder {
         self.value.unwrap_or_default()
     }
 }
+
+#[cfg(test)]
+#[test]
+fn colored_show() {
+    use crate::Uiua;
+    let mut env = Uiua::with_safe_sys();
+    env.run_str("map {\"a\" \"bb\"} [1 2]\n[1.5 ¯2 π NaN]\n{\"a\\\"b\" @c 3_4}\n°△2_3")
+        .unwrap();
+    for value in env.take_stack() {
+        let colored = value.show_colored();
+        let mut stripped = String::new();
+        let mut chars = colored.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+        assert_eq!(stripped, value.show());
+        assert!(colored.contains("\x1b[38;2;235;136;68m"));
+    }
+}
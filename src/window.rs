@@ -2,12 +2,12 @@ use std::{
     env::current_exe,
     fs,
     io::{ErrorKind, Read, Write},
-    net::{SocketAddr, TcpListener, TcpStream},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
     path::Path,
     process::{exit, Command, Stdio},
     sync::{
         atomic::{self, AtomicBool},
-        Arc,
+        Arc, Mutex,
     },
     thread::{self, sleep},
     time::{Duration, Instant},
@@ -19,7 +19,7 @@ use image::{GenericImageView, ImageFormat};
 use load::SizedTexture;
 use serde::*;
 
-use crate::encode::SmartOutput;
+use crate::{encode::SmartOutput, MouseState};
 
 static USE_WINDOW: AtomicBool = AtomicBool::new(false);
 pub fn use_window() -> bool {
@@ -46,6 +46,19 @@ pub enum Request {
     Separator,
     ClearBeforeNext,
     Shutdown,
+    Mouse,
+}
+
+/// Get the state of the mouse in the window
+pub fn poll_mouse() -> Result<MouseState, String> {
+    let mut stream = (Request::Mouse.send_impl(RETRIES)?)
+        .ok_or_else(|| "Failed to connect to window".to_string())?;
+    stream
+        .shutdown(Shutdown::Write)
+        .map_err(|e| e.to_string())?;
+    let mut buffer = Vec::new();
+    (stream.read_to_end(&mut buffer)).map_err(|e| e.to_string())?;
+    rmp_serde::from_slice(&buffer).map_err(|e| format!("Failed to decode mouse state: {e}"))
 }
 
 const RETRIES: usize = 20;
@@ -53,9 +66,9 @@ const RETRIES: usize = 20;
 impl Request {
     /// Send the request
     pub fn send(self) -> Result<(), String> {
-        self.send_impl(RETRIES)
+        self.send_impl(RETRIES).map(drop)
     }
-    fn send_impl(self, retries: usize) -> Result<(), String> {
+    fn send_impl(self, retries: usize) -> Result<Option<TcpStream>, String> {
         let socket_addr = ([127, 0, 0, 1], PORT).into();
         let timeout = Duration::from_secs_f32(0.1);
         let mut stream = match TcpStream::connect_timeout(&socket_addr, timeout) {
@@ -65,7 +78,7 @@ impl Request {
                     && [ErrorKind::TimedOut, ErrorKind::ConnectionRefused].contains(&e.kind()) =>
             {
                 if let Request::Shutdown = self {
-                    return Ok(());
+                    return Ok(None);
                 }
                 if retries + 1 == RETRIES {
                     if cfg!(debug_assertions) {
@@ -101,12 +114,14 @@ impl Request {
         let bin = serializer.into_inner();
         stream.write_all(&bin).map_err(|e| e.to_string())?;
         stream.flush().map_err(|e| e.to_string())?;
-        Ok(())
+        Ok(Some(stream))
     }
 }
 
 pub fn run_window() {
     let (send, recv) = crossbeam_channel::unbounded();
+    let mouse = Arc::new(Mutex::new(MouseState::default()));
+    let listener_mouse = mouse.clone();
     thread::spawn(move || {
         let addr = SocketAddr::from(([0u8; 4], PORT));
         let listener = match TcpListener::bind(addr) {
@@ -127,6 +142,19 @@ pub fn run_window() {
                     let mut deserializer =
                         rmp_serde::Deserializer::new(buffer.as_slice()).with_human_readable();
                     match Request::deserialize(&mut deserializer) {
+                        // Input requests are answered here so they don't wait for a frame
+                        Ok(Request::Mouse) => {
+                            let state = {
+                                let mut mouse = listener_mouse.lock().unwrap();
+                                let state = *mouse;
+                                mouse.scroll = 0.0;
+                                state
+                            };
+                            let bin = rmp_serde::to_vec(&state).unwrap();
+                            if let Err(e) = stream.write_all(&bin) {
+                                eprintln!("Failed to send mouse state: {e}")
+                            }
+                        }
                         Ok(req) => send.send(req).unwrap(),
                         Err(e) => {
                             eprintln!("Failed to decode request: {e}")
@@ -174,7 +202,7 @@ pub fn run_window() {
                 style.interaction.show_tooltips_only_when_still = false;
                 style.interaction.tooltip_delay = 0.2;
             });
-            Ok(Box::new(App::new(recv, &cc.egui_ctx)))
+            Ok(Box::new(App::new(recv, mouse, &cc.egui_ctx)))
         }),
     )
     .unwrap();
@@ -183,6 +211,7 @@ pub fn run_window() {
 struct App {
     items: Vec<OutputItem>,
     recv: Receiver<Request>,
+    mouse: Arc<Mutex<MouseState>>,
     cache: Cache,
     scroll_to_top: bool,
     clear: bool,
@@ -250,7 +279,7 @@ impl ImageState {
 }
 
 impl App {
-    fn new(recv: Receiver<Request>, ctx: &Context) -> Self {
+    fn new(recv: Receiver<Request>, mouse: Arc<Mutex<MouseState>>, ctx: &Context) -> Self {
         let (ppp, clear) = ctx.memory_mut(|mem| {
             (
                 mem.data.get_persisted(Id::new("ppp")).unwrap_or(1.5),
@@ -261,6 +290,7 @@ impl App {
         App {
             items: Vec::new(),
             recv,
+            mouse,
             cache: Cache {
                 ppp,
                 image_scale: 1.0,
@@ -326,9 +356,26 @@ impl eframe::App for App {
                 }
                 Request::ClearBeforeNext => self.clear_before_next = self.clear,
                 Request::Shutdown => ctx.send_viewport_cmd(ViewportCommand::Close),
+                Request::Mouse => {}
             }
         }
 
+        // Record the mouse state for polling
+        ctx.input(|input| {
+            let mut mouse = self.mouse.lock().unwrap();
+            if let Some(pos) = input.pointer.latest_pos() {
+                mouse.pos = [pos.x as f64, pos.y as f64];
+            }
+            mouse.buttons = [
+                PointerButton::Primary,
+                PointerButton::Secondary,
+                PointerButton::Middle,
+            ]
+            .map(|button| input.pointer.button_down(button));
+            // egui scroll deltas are positive up
+            mouse.scroll -= input.raw_scroll_delta.y as f64;
+        });
+
         // Top bar
         TopBottomPanel::top("top bar").show(ctx, |ui| {
             ui.horizontal_wrapped(|ui| {